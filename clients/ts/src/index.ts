@@ -0,0 +1,16 @@
+// Entry point of the client. The request and response types come from schema.ts, which
+// `cargo xtask generate-client` regenerates from the API's OpenAPI document, so don't
+// edit it by hand.
+import createClient from "openapi-fetch";
+import type { components, paths } from "./schema";
+
+export type { components, paths };
+export type Schemas = components["schemas"];
+
+// Paths are the full documented ones, e.g. client.post("/api/v1/game/candidates", { body })
+export function createApiClient(baseUrl: string, accessToken?: string) {
+  return createClient<paths>({
+    baseUrl,
+    headers: accessToken ? { Authorization: `Bearer ${accessToken}` } : {},
+  });
+}
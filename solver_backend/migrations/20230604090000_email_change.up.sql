@@ -0,0 +1,5 @@
+ALTER TABLE users
+    ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN pending_email VARCHAR(100),
+    ADD COLUMN email_change_token TEXT UNIQUE,
+    ADD COLUMN email_change_expires_at TIMESTAMP;
@@ -0,0 +1,6 @@
+ALTER TABLE users
+    DROP COLUMN suspension_reason,
+    DROP COLUMN suspended_until,
+    DROP COLUMN suspended_at,
+    DROP COLUMN token_version,
+    DROP COLUMN role;
@@ -0,0 +1,6 @@
+ALTER TABLE users
+    ADD COLUMN role VARCHAR(20) NOT NULL DEFAULT 'user',
+    ADD COLUMN token_version INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN suspended_at TIMESTAMP,
+    ADD COLUMN suspended_until TIMESTAMP,
+    ADD COLUMN suspension_reason TEXT;
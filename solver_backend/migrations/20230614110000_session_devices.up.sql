@@ -0,0 +1,4 @@
+ALTER TABLE refresh_tokens
+    ADD COLUMN user_agent VARCHAR(512),
+    ADD COLUMN ip_address VARCHAR(45),
+    ADD COLUMN device_label VARCHAR(100) NOT NULL DEFAULT 'Unknown device';
@@ -0,0 +1,3 @@
+DROP INDEX refresh_tokens_user_fingerprint_idx;
+ALTER TABLE users DROP COLUMN notify_new_device;
+ALTER TABLE refresh_tokens DROP COLUMN fingerprint;
@@ -0,0 +1,4 @@
+ALTER TABLE refresh_tokens ADD COLUMN fingerprint VARCHAR(64);
+ALTER TABLE users ADD COLUMN notify_new_device BOOLEAN NOT NULL DEFAULT TRUE;
+
+CREATE INDEX refresh_tokens_user_fingerprint_idx ON refresh_tokens (user_id, fingerprint);
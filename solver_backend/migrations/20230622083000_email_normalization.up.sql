@@ -0,0 +1,26 @@
+-- Addresses that only differ by case (or surrounding whitespace) can't be merged
+-- automatically, so list them and stop rather than pick a winner
+DO $$
+DECLARE
+    duplicates TEXT;
+BEGIN
+    SELECT string_agg(format('%s (user ids %s)', address, ids), '; ')
+    INTO duplicates
+    FROM (
+        SELECT lower(trim(email)) AS address, string_agg(id::TEXT, ', ' ORDER BY id) AS ids
+        FROM users
+        GROUP BY lower(trim(email))
+        HAVING COUNT(*) > 1
+    ) dupes;
+
+    IF duplicates IS NOT NULL THEN
+        RAISE EXCEPTION 'Resolve emails that differ only by case before migrating: %', duplicates;
+    END IF;
+END $$;
+
+UPDATE users SET email = trim(email);
+UPDATE users
+SET email = split_part(email, '@', 1) || '@' || lower(split_part(email, '@', 2))
+WHERE email LIKE '%@%' AND email NOT LIKE '%@%@%';
+
+CREATE UNIQUE INDEX users_email_lower_idx ON users (lower(email));
@@ -0,0 +1,3 @@
+DROP TRIGGER word_list_changed ON word_list;
+DROP FUNCTION bump_word_list_version();
+DROP TABLE word_list_version;
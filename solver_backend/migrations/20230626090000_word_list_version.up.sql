@@ -0,0 +1,19 @@
+-- Single row counter, bumped by every statement that changes word_list. Responses built
+-- from the word list use it as their ETag.
+CREATE TABLE word_list_version (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    version BIGINT NOT NULL DEFAULT 1
+);
+
+INSERT INTO word_list_version DEFAULT VALUES;
+
+CREATE FUNCTION bump_word_list_version() RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE word_list_version SET version = version + 1;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER word_list_changed
+    AFTER INSERT OR UPDATE OR DELETE OR TRUNCATE ON word_list
+    FOR EACH STATEMENT EXECUTE FUNCTION bump_word_list_version();
@@ -0,0 +1,22 @@
+-- Back to naive values, as UTC wall-clock time
+SELECT set_config('timezone', 'UTC', true);
+
+ALTER TABLE idempotency_keys ALTER COLUMN created_at TYPE TIMESTAMP;
+ALTER TABLE reserved_usernames ALTER COLUMN created_at TYPE TIMESTAMP;
+ALTER TABLE password_history ALTER COLUMN created_at TYPE TIMESTAMP;
+ALTER TABLE auth_events ALTER COLUMN created_at TYPE TIMESTAMP;
+
+ALTER TABLE refresh_tokens
+    ALTER COLUMN created_at TYPE TIMESTAMP,
+    ALTER COLUMN rotated_at TYPE TIMESTAMP;
+
+ALTER TABLE revoked_tokens ALTER COLUMN created_at TYPE TIMESTAMP;
+
+ALTER TABLE users
+    ALTER COLUMN last_login_at TYPE TIMESTAMP,
+    ALTER COLUMN last_exported_at TYPE TIMESTAMP,
+    ALTER COLUMN suspended_until TYPE TIMESTAMP,
+    ALTER COLUMN suspended_at TYPE TIMESTAMP,
+    ALTER COLUMN email_change_expires_at TYPE TIMESTAMP,
+    ALTER COLUMN updated_at TYPE TIMESTAMP,
+    ALTER COLUMN created_at TYPE TIMESTAMP;
@@ -0,0 +1,24 @@
+-- Timestamps were stored as naive wall-clock values. Casting to timestamptz reads them in
+-- the session zone, which is set here to the wordle.legacy_timezone setting (LEGACY_TIMEZONE,
+-- passed in by run_migrations) for the rest of this migration's transaction.
+SELECT set_config('timezone', COALESCE(NULLIF(current_setting('wordle.legacy_timezone', true), ''), 'UTC'), true);
+
+ALTER TABLE users
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN updated_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN email_change_expires_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN suspended_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN suspended_until TYPE TIMESTAMPTZ,
+    ALTER COLUMN last_exported_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN last_login_at TYPE TIMESTAMPTZ;
+
+ALTER TABLE revoked_tokens ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+
+ALTER TABLE refresh_tokens
+    ALTER COLUMN rotated_at TYPE TIMESTAMPTZ,
+    ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+
+ALTER TABLE auth_events ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+ALTER TABLE password_history ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+ALTER TABLE reserved_usernames ALTER COLUMN created_at TYPE TIMESTAMPTZ;
+ALTER TABLE idempotency_keys ALTER COLUMN created_at TYPE TIMESTAMPTZ;
@@ -0,0 +1,19 @@
+-- Work handed off by handlers and picked up by the job worker. Payloads and results are
+-- JSON kept as text, since the Any driver can't decode JSONB.
+CREATE TABLE jobs (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    job_type VARCHAR(50) NOT NULL,
+    payload TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'queued',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    scheduled_at TIMESTAMPTZ NOT NULL,
+    locked_at TIMESTAMPTZ,
+    result TEXT,
+    last_error TEXT,
+    created_at TIMESTAMPTZ NOT NULL,
+    finished_at TIMESTAMPTZ
+);
+
+CREATE INDEX jobs_claimable_idx ON jobs (status, scheduled_at);
@@ -0,0 +1,14 @@
+-- One row per fetch of the remote word list that got a list back, with how it changed the
+-- stored one. The newest row's validators make the next fetch conditional.
+CREATE TABLE word_list_revisions (
+    id SERIAL PRIMARY KEY,
+    source_url TEXT NOT NULL,
+    added INTEGER NOT NULL,
+    removed INTEGER NOT NULL,
+    word_count INTEGER NOT NULL,
+    etag TEXT,
+    last_modified TEXT,
+    -- NULL for scheduled syncs
+    synced_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
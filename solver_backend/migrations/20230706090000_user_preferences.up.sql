@@ -0,0 +1,10 @@
+-- Preferences a user saved. Users without a row get the defaults from the code, and the
+-- notification opt-outs stay on the users table.
+CREATE TABLE user_preferences (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    hard_mode BOOLEAN NOT NULL,
+    colorblind BOOLEAN NOT NULL,
+    word_length INTEGER NOT NULL,
+    sort_order VARCHAR(32) NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,5 @@
+DROP INDEX users_pending_anonymization_idx;
+
+ALTER TABLE users
+    DROP COLUMN anonymized_at,
+    DROP COLUMN deleted_at;
@@ -0,0 +1,7 @@
+-- Deleted accounts keep their row through the grace period, so a deletion can still be
+-- undone, and afterwards as an anonymized tombstone that statistics can keep pointing at
+ALTER TABLE users
+    ADD COLUMN deleted_at TIMESTAMPTZ,
+    ADD COLUMN anonymized_at TIMESTAMPTZ;
+
+CREATE INDEX users_pending_anonymization_idx ON users (deleted_at) WHERE anonymized_at IS NULL;
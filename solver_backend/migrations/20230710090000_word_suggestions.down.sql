@@ -0,0 +1,3 @@
+DROP TABLE word_suggestion_supporters;
+DROP TABLE word_suggestions;
+ALTER TABLE word_list DROP COLUMN is_answer;
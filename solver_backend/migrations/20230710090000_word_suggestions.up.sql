@@ -0,0 +1,33 @@
+-- Whether a word can be the answer of a game, not just a guess
+ALTER TABLE word_list ADD COLUMN is_answer BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Changes to the word list proposed by players, waiting for an admin to approve or
+-- reject them. Another proposal of the same pending change is merged into it.
+CREATE TABLE word_suggestions (
+    id SERIAL PRIMARY KEY,
+    word VARCHAR(255) NOT NULL,
+    -- add, remove, mark_answer or unmark_answer
+    action VARCHAR(20) NOT NULL,
+    -- pending, approved or rejected
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    suggested_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    -- Every supporter's reason, one per line in the order they were given
+    reason TEXT NOT NULL,
+    -- Everyone who proposed it, the first suggester included
+    supporters INTEGER NOT NULL DEFAULT 1,
+    reviewed_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMPTZ,
+    review_note TEXT,
+    revision_id INTEGER REFERENCES word_list_revisions(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE UNIQUE INDEX word_suggestions_pending_idx ON word_suggestions (word, action) WHERE status = 'pending';
+
+-- Who proposed each suggestion, so proposing it again doesn't count twice
+CREATE TABLE word_suggestion_supporters (
+    suggestion_id INTEGER NOT NULL REFERENCES word_suggestions(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (suggestion_id, user_id)
+);
@@ -0,0 +1,38 @@
+-- Endpoints game events are POSTed to. A user's endpoint gets their own events, one an
+-- admin registered gets everyone's.
+CREATE TABLE webhooks (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    -- Signs each payload, never sent back to clients
+    secret TEXT NOT NULL,
+    -- Comma separated event types
+    events TEXT NOT NULL,
+    all_users BOOLEAN NOT NULL DEFAULT FALSE,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    -- Failed attempts since the last success. Reaching WEBHOOK_FAILURE_LIMIT disables it.
+    consecutive_failures INTEGER NOT NULL DEFAULT 0,
+    disabled_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX webhooks_user_id_idx ON webhooks (user_id);
+
+-- One row per event sent to an endpoint, made by a job that's retried with backoff
+CREATE TABLE webhook_deliveries (
+    id SERIAL PRIMARY KEY,
+    webhook_id INTEGER NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    event VARCHAR(50) NOT NULL,
+    payload TEXT NOT NULL,
+    -- pending, retrying, succeeded or failed
+    status VARCHAR(20) NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- Of the last attempt. No response status means no response was received.
+    response_status INTEGER,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL,
+    last_attempt_at TIMESTAMPTZ
+);
+
+CREATE INDEX webhook_deliveries_webhook_id_idx ON webhook_deliveries (webhook_id, id);
@@ -0,0 +1,2 @@
+DROP TABLE session_guesses;
+DROP TABLE game_sessions;
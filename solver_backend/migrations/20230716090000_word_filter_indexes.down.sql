@@ -0,0 +1,3 @@
+DROP INDEX word_list_word_trigrams;
+DROP INDEX word_list_word_length;
+DROP INDEX word_list_word_pattern;
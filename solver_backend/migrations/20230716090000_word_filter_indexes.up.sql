@@ -0,0 +1,11 @@
+-- Lets word filtering use indexes instead of scanning word_list. Words are kept lowercase
+-- so queries can compare the column as it is, lower(word) would bypass every index.
+UPDATE word_list SET word = lower(word) WHERE word <> lower(word);
+
+-- Patterns anchored at the start like 'cra__', and exact lookups, whatever the collation
+CREATE INDEX word_list_word_pattern ON word_list (word varchar_pattern_ops);
+-- A pattern without % only matches words of its own length
+CREATE INDEX word_list_word_length ON word_list (length(word));
+-- Patterns that start with a wildcard like '_r_ne' or '%ran%'
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX word_list_word_trigrams ON word_list USING gin (word gin_trgm_ops);
@@ -0,0 +1,15 @@
+-- The word list versions a revision went from and to, so clients can ask what changed
+-- since the version they hold. Revisions from before this, and ones whose changes were
+-- pruned, have neither.
+ALTER TABLE word_list_revisions ADD COLUMN from_version BIGINT;
+ALTER TABLE word_list_revisions ADD COLUMN to_version BIGINT;
+
+CREATE INDEX word_list_revisions_to_version_idx ON word_list_revisions (to_version);
+
+-- Every word a revision added or removed
+CREATE TABLE word_list_changes (
+    revision_id INTEGER NOT NULL REFERENCES word_list_revisions(id) ON DELETE CASCADE,
+    word VARCHAR(255) NOT NULL,
+    added BOOLEAN NOT NULL,
+    PRIMARY KEY (revision_id, word)
+);
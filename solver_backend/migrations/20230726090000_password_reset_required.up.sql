@@ -0,0 +1,3 @@
+-- Accounts that can't log in until a new password is set, like imported ones that came
+-- without a password hash. Their password column holds no valid hash meanwhile.
+ALTER TABLE users ADD COLUMN password_reset_required BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,3 @@
+DROP TABLE game_session_tags;
+DROP INDEX game_sessions_note_search_idx;
+ALTER TABLE game_sessions DROP COLUMN note;
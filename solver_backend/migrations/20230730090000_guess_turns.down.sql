@@ -0,0 +1,2 @@
+DROP INDEX session_guesses_turn_idx;
+ALTER TABLE session_guesses DROP COLUMN turn;
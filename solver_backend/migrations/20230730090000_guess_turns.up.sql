@@ -0,0 +1,11 @@
+-- Which guess of the session each one was, counting from 1. Two requests racing to make
+-- the same turn can't both be recorded.
+ALTER TABLE session_guesses ADD COLUMN turn INTEGER;
+
+UPDATE session_guesses SET turn = numbered.turn
+FROM (SELECT id, ROW_NUMBER() OVER (PARTITION BY session_id ORDER BY id) AS turn FROM session_guesses) numbered
+WHERE numbered.id = session_guesses.id;
+
+ALTER TABLE session_guesses ALTER COLUMN turn SET NOT NULL;
+
+CREATE UNIQUE INDEX session_guesses_turn_idx ON session_guesses (session_id, turn);
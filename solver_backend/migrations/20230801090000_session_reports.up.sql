@@ -0,0 +1,18 @@
+-- A player's report that a session went wrong, with what the solver told them at the
+-- time. The guesses are copied so the report outlives the session, and the word list
+-- version lets it be replayed against the list as it was.
+CREATE TABLE session_reports (
+    id SERIAL PRIMARY KEY,
+    session_id INTEGER REFERENCES game_sessions(id) ON DELETE SET NULL,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    description TEXT NOT NULL,
+    -- JSON: the guesses and their feedback, in order
+    guesses TEXT NOT NULL,
+    word_list_version BIGINT NOT NULL,
+    -- JSON: the candidates and suggestions the reporter saw
+    original TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+-- Pruning keeps the word list history the oldest report needs
+CREATE INDEX session_reports_version_idx ON session_reports (word_list_version);
@@ -0,0 +1,2 @@
+DROP TABLE answer_schedule;
+DROP TABLE used_answers;
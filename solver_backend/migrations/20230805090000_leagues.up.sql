@@ -0,0 +1,33 @@
+-- Private competitions: members join with the invite code and all play the league's
+-- daily word
+CREATE TABLE leagues (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(64) NOT NULL,
+    owner_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    invite_code VARCHAR(16) NOT NULL UNIQUE,
+    -- uniform, no_repeat or scheduled, null for the server's ANSWER_STRATEGY
+    answer_strategy VARCHAR(16),
+    created_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE league_members (
+    league_id INTEGER NOT NULL REFERENCES leagues(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    joined_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (league_id, user_id)
+);
+
+CREATE INDEX league_members_user_idx ON league_members (user_id);
+
+-- A member's guesses at the league's word of the day, kept after they leave so the
+-- scores are back if they rejoin
+CREATE TABLE league_guesses (
+    league_id INTEGER NOT NULL REFERENCES leagues(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    day DATE NOT NULL,
+    turn INTEGER NOT NULL,
+    guess VARCHAR(255) NOT NULL,
+    feedback VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (league_id, user_id, day, turn)
+);
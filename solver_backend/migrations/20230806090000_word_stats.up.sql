@@ -0,0 +1,10 @@
+-- How each answer fared across the games the server picked the answer for. A finished
+-- game adds to its word's row in the same transaction as its last guess.
+CREATE TABLE word_stats (
+    word VARCHAR(255) PRIMARY KEY,
+    plays BIGINT NOT NULL DEFAULT 0,
+    failures BIGINT NOT NULL DEFAULT 0,
+    -- Summed over the solved plays only
+    solved_guesses BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL
+);
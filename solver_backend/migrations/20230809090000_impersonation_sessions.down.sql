@@ -0,0 +1,2 @@
+ALTER TABLE refresh_tokens DROP COLUMN expires_at;
+ALTER TABLE refresh_tokens DROP COLUMN impersonator_id;
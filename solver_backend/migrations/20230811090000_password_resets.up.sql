@@ -0,0 +1,5 @@
+-- A single use token mailed to the account's address. Setting a password with it is the
+-- only way to clear password_reset_required.
+ALTER TABLE users
+    ADD COLUMN password_reset_token TEXT UNIQUE,
+    ADD COLUMN password_reset_expires_at TIMESTAMP;
@@ -0,0 +1,4 @@
+CREATE TABLE revoked_tokens (
+    token TEXT PRIMARY KEY,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
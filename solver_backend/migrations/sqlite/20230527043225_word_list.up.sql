@@ -0,0 +1,4 @@
+CREATE TABLE word_list (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    word VARCHAR(255)
+);
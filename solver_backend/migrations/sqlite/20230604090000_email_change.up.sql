@@ -0,0 +1,7 @@
+-- SQLite can't add a UNIQUE column, the index stands in for the constraint
+ALTER TABLE users ADD COLUMN email_verified BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE users ADD COLUMN pending_email VARCHAR(100);
+ALTER TABLE users ADD COLUMN email_change_token TEXT;
+ALTER TABLE users ADD COLUMN email_change_expires_at TIMESTAMP;
+
+CREATE UNIQUE INDEX users_email_change_token_idx ON users (email_change_token);
@@ -0,0 +1,3 @@
+ALTER TABLE refresh_tokens ADD COLUMN user_agent VARCHAR(512);
+ALTER TABLE refresh_tokens ADD COLUMN ip_address VARCHAR(45);
+ALTER TABLE refresh_tokens ADD COLUMN device_label VARCHAR(100) NOT NULL DEFAULT 'Unknown device';
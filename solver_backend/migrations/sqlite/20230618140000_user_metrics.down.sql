@@ -0,0 +1,3 @@
+DROP INDEX users_last_login_at_idx;
+DROP INDEX users_created_at_idx;
+ALTER TABLE users DROP COLUMN last_login_at;
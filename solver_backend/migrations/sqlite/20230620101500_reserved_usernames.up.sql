@@ -0,0 +1,6 @@
+-- Additions to the built in reserved list, stored already normalized
+-- (lowercase, leetspeak folded, separators removed)
+CREATE TABLE reserved_usernames (
+    word VARCHAR(50) PRIMARY KEY,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+);
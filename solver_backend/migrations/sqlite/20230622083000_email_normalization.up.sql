@@ -0,0 +1,8 @@
+-- Unlike the Postgres version this doesn't list addresses differing only by case first,
+-- creating the index simply fails if there are any
+UPDATE users SET email = trim(email);
+UPDATE users
+SET email = substr(email, 1, instr(email, '@')) || lower(substr(email, instr(email, '@') + 1))
+WHERE email LIKE '%@%' AND email NOT LIKE '%@%@%';
+
+CREATE UNIQUE INDEX users_email_lower_idx ON users (lower(email));
@@ -0,0 +1,4 @@
+DROP TRIGGER word_list_deleted;
+DROP TRIGGER word_list_updated;
+DROP TRIGGER word_list_inserted;
+DROP TABLE word_list_version;
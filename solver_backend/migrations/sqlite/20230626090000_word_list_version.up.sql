@@ -0,0 +1,23 @@
+-- Single row counter, bumped on every change to word_list. SQLite triggers only run per
+-- row, so a bulk change bumps it more than once, which is fine for an ETag.
+CREATE TABLE word_list_version (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    version BIGINT NOT NULL DEFAULT 1
+);
+
+INSERT INTO word_list_version DEFAULT VALUES;
+
+CREATE TRIGGER word_list_inserted AFTER INSERT ON word_list
+BEGIN
+    UPDATE word_list_version SET version = version + 1;
+END;
+
+CREATE TRIGGER word_list_updated AFTER UPDATE ON word_list
+BEGIN
+    UPDATE word_list_version SET version = version + 1;
+END;
+
+CREATE TRIGGER word_list_deleted AFTER DELETE ON word_list
+BEGIN
+    UPDATE word_list_version SET version = version + 1;
+END;
@@ -0,0 +1,37 @@
+DROP TRIGGER idempotency_keys_created_at_utc;
+DROP TRIGGER reserved_usernames_created_at_utc;
+DROP TRIGGER password_history_created_at_utc;
+DROP TRIGGER auth_events_created_at_utc;
+DROP TRIGGER refresh_tokens_created_at_utc;
+DROP TRIGGER revoked_tokens_created_at_utc;
+DROP TRIGGER users_updated_at_utc;
+DROP TRIGGER users_created_at_utc;
+
+-- Back to the form CURRENT_TIMESTAMP and naive values are written in
+UPDATE idempotency_keys SET
+    created_at = strftime('%Y-%m-%d %H:%M:%f', created_at);
+
+UPDATE reserved_usernames SET
+    created_at = strftime('%Y-%m-%d %H:%M:%f', created_at);
+
+UPDATE password_history SET
+    created_at = strftime('%Y-%m-%d %H:%M:%f', created_at);
+
+UPDATE auth_events SET
+    created_at = strftime('%Y-%m-%d %H:%M:%f', created_at);
+
+UPDATE refresh_tokens SET
+    rotated_at = strftime('%Y-%m-%d %H:%M:%f', rotated_at),
+    created_at = strftime('%Y-%m-%d %H:%M:%f', created_at);
+
+UPDATE revoked_tokens SET
+    created_at = strftime('%Y-%m-%d %H:%M:%f', created_at);
+
+UPDATE users SET
+    created_at = strftime('%Y-%m-%d %H:%M:%f', created_at),
+    updated_at = strftime('%Y-%m-%d %H:%M:%f', updated_at),
+    email_change_expires_at = strftime('%Y-%m-%d %H:%M:%f', email_change_expires_at),
+    suspended_at = strftime('%Y-%m-%d %H:%M:%f', suspended_at),
+    suspended_until = strftime('%Y-%m-%d %H:%M:%f', suspended_until),
+    last_exported_at = strftime('%Y-%m-%d %H:%M:%f', last_exported_at),
+    last_login_at = strftime('%Y-%m-%d %H:%M:%f', last_login_at);
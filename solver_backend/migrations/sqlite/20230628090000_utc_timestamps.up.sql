@@ -0,0 +1,80 @@
+-- SQLite keeps timestamps as text and compares them as text, so every value has to be in
+-- the RFC 3339 form the server now binds (e.g. 2023-06-28T09:00:00.000+00:00). Existing
+-- values, already UTC here, are rewritten, and triggers do the same for the CURRENT_TIMESTAMP
+-- defaults, whose format can't be changed without rebuilding the tables.
+
+UPDATE users SET
+    created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', created_at),
+    updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', updated_at),
+    email_change_expires_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', email_change_expires_at),
+    suspended_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', suspended_at),
+    suspended_until = strftime('%Y-%m-%dT%H:%M:%f+00:00', suspended_until),
+    last_exported_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', last_exported_at),
+    last_login_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', last_login_at);
+
+UPDATE revoked_tokens SET
+    created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', created_at);
+
+UPDATE refresh_tokens SET
+    rotated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', rotated_at),
+    created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', created_at);
+
+UPDATE auth_events SET
+    created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', created_at);
+
+UPDATE password_history SET
+    created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', created_at);
+
+UPDATE reserved_usernames SET
+    created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', created_at);
+
+UPDATE idempotency_keys SET
+    created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', created_at);
+
+CREATE TRIGGER users_created_at_utc AFTER INSERT ON users
+WHEN NEW.created_at NOT LIKE '%+00:00'
+BEGIN
+    UPDATE users SET created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', NEW.created_at) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER users_updated_at_utc AFTER INSERT ON users
+WHEN NEW.updated_at NOT LIKE '%+00:00'
+BEGIN
+    UPDATE users SET updated_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', NEW.updated_at) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER revoked_tokens_created_at_utc AFTER INSERT ON revoked_tokens
+WHEN NEW.created_at NOT LIKE '%+00:00'
+BEGIN
+    UPDATE revoked_tokens SET created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', NEW.created_at) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER refresh_tokens_created_at_utc AFTER INSERT ON refresh_tokens
+WHEN NEW.created_at NOT LIKE '%+00:00'
+BEGIN
+    UPDATE refresh_tokens SET created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', NEW.created_at) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER auth_events_created_at_utc AFTER INSERT ON auth_events
+WHEN NEW.created_at NOT LIKE '%+00:00'
+BEGIN
+    UPDATE auth_events SET created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', NEW.created_at) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER password_history_created_at_utc AFTER INSERT ON password_history
+WHEN NEW.created_at NOT LIKE '%+00:00'
+BEGIN
+    UPDATE password_history SET created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', NEW.created_at) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER reserved_usernames_created_at_utc AFTER INSERT ON reserved_usernames
+WHEN NEW.created_at NOT LIKE '%+00:00'
+BEGIN
+    UPDATE reserved_usernames SET created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', NEW.created_at) WHERE rowid = NEW.rowid;
+END;
+
+CREATE TRIGGER idempotency_keys_created_at_utc AFTER INSERT ON idempotency_keys
+WHEN NEW.created_at NOT LIKE '%+00:00'
+BEGIN
+    UPDATE idempotency_keys SET created_at = strftime('%Y-%m-%dT%H:%M:%f+00:00', NEW.created_at) WHERE rowid = NEW.rowid;
+END;
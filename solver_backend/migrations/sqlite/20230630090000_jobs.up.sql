@@ -0,0 +1,19 @@
+-- Timestamps have no defaults so they are always written by the application, in the
+-- RFC 3339 form the other tables are normalized to
+CREATE TABLE jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    job_type VARCHAR(50) NOT NULL,
+    payload TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'queued',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    scheduled_at TIMESTAMP NOT NULL,
+    locked_at TIMESTAMP,
+    result TEXT,
+    last_error TEXT,
+    created_at TIMESTAMP NOT NULL,
+    finished_at TIMESTAMP
+);
+
+CREATE INDEX jobs_claimable_idx ON jobs (status, scheduled_at);
@@ -0,0 +1,8 @@
+-- Feature flags an admin switched at runtime. Flags without a row use their default from
+-- the environment.
+CREATE TABLE feature_flags (
+    name VARCHAR(50) PRIMARY KEY,
+    enabled BOOLEAN NOT NULL,
+    updated_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,4 @@
+DROP INDEX users_pending_anonymization_idx;
+
+ALTER TABLE users DROP COLUMN anonymized_at;
+ALTER TABLE users DROP COLUMN deleted_at;
@@ -0,0 +1,30 @@
+-- A game being solved, the guesses made so far and the feedback each got. Sessions started
+-- without logging in have no user and are reached with a token until someone claims them.
+CREATE TABLE game_sessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    -- SHA-256 of the anonymous session's token, cleared once it's claimed
+    token_hash VARCHAR(64),
+    -- active or solved
+    status VARCHAR(20) NOT NULL DEFAULT 'active',
+    -- Anonymous sessions can't be used or claimed after this
+    expires_at TIMESTAMP,
+    claimed_at TIMESTAMP,
+    completed_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX game_sessions_user_id_idx ON game_sessions (user_id);
+CREATE INDEX game_sessions_expires_at_idx ON game_sessions (expires_at);
+
+CREATE TABLE session_guesses (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    session_id INTEGER NOT NULL REFERENCES game_sessions(id) ON DELETE CASCADE,
+    guess VARCHAR(20) NOT NULL,
+    -- g, y or x per letter
+    feedback VARCHAR(20) NOT NULL,
+    created_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX session_guesses_session_id_idx ON session_guesses (session_id, id);
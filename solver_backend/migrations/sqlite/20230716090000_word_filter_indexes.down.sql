@@ -0,0 +1,2 @@
+DROP INDEX word_list_word_length;
+DROP INDEX word_list_word;
@@ -0,0 +1,7 @@
+-- SQLite only uses an index for LIKE on a NOCASE column, so the pattern itself is left to a
+-- scan. The length index still narrows patterns without %, and the word index serves
+-- exact lookups.
+UPDATE word_list SET word = lower(word) WHERE word <> lower(word);
+
+CREATE INDEX word_list_word ON word_list (word);
+CREATE INDEX word_list_word_length ON word_list (length(word));
@@ -0,0 +1,12 @@
+-- Authenticated calls per user, route and day, written in batches from each replica's
+-- in-memory counts. No foreign key on user_id, so a batch never fails over one account.
+CREATE TABLE usage_stats (
+    user_id INTEGER NOT NULL,
+    -- Method and matched route pattern, such as GET /api/v1/users/me/usage
+    route VARCHAR(200) NOT NULL,
+    day DATE NOT NULL,
+    calls BIGINT NOT NULL,
+    PRIMARY KEY (user_id, day, route)
+);
+
+CREATE INDEX usage_stats_day_idx ON usage_stats (day);
@@ -0,0 +1,3 @@
+-- Guesses a session allows, NULL for free play with no limit. Sessions from before the
+-- limit existed had none, so they count as free play.
+ALTER TABLE game_sessions ADD COLUMN max_guesses INTEGER;
@@ -0,0 +1,3 @@
+-- Who can see a user's stats: public, friends or private. Existing rows stay public, as
+-- their stats were before.
+ALTER TABLE user_preferences ADD COLUMN profile_visibility VARCHAR(16) NOT NULL DEFAULT 'public';
@@ -0,0 +1,4 @@
+DROP TABLE word_list_changes;
+DROP INDEX word_list_revisions_to_version_idx;
+ALTER TABLE word_list_revisions DROP COLUMN to_version;
+ALTER TABLE word_list_revisions DROP COLUMN from_version;
@@ -0,0 +1,2 @@
+DROP TABLE game_session_tags;
+ALTER TABLE game_sessions DROP COLUMN note;
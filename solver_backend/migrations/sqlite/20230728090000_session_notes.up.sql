@@ -0,0 +1,12 @@
+-- What the player wrote about a game, searched as text. Without a full text index here,
+-- searches scan the user's sessions.
+ALTER TABLE game_sessions ADD COLUMN note TEXT;
+
+-- Lowercased labels a player sorts their games with, filtered on by exact value
+CREATE TABLE game_session_tags (
+    session_id INTEGER NOT NULL REFERENCES game_sessions(id) ON DELETE CASCADE,
+    tag VARCHAR(32) NOT NULL,
+    PRIMARY KEY (session_id, tag)
+);
+
+CREATE INDEX game_session_tags_tag_idx ON game_session_tags (tag, session_id);
@@ -0,0 +1,10 @@
+-- Which guess of the session each one was, counting from 1. Two requests racing to make
+-- the same turn can't both be recorded.
+ALTER TABLE session_guesses ADD COLUMN turn INTEGER NOT NULL DEFAULT 0;
+
+UPDATE session_guesses SET turn = (
+    SELECT COUNT(*) FROM session_guesses earlier
+    WHERE earlier.session_id = session_guesses.session_id AND earlier.id <= session_guesses.id
+);
+
+CREATE UNIQUE INDEX session_guesses_turn_idx ON session_guesses (session_id, turn);
@@ -0,0 +1,20 @@
+-- The answer picked for each scope (the daily puzzle, a league) and day. A row is
+-- written the first time a day's answer is asked for, so the answer stays put and
+-- no-repeat selection can see what was used before.
+CREATE TABLE used_answers (
+    scope VARCHAR(64) NOT NULL,
+    day DATE NOT NULL,
+    word VARCHAR(255) NOT NULL,
+    created_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (scope, day)
+);
+
+CREATE INDEX used_answers_scope_word_idx ON used_answers (scope, word);
+
+-- A curated answer for a scope and day, read by the scheduled selector
+CREATE TABLE answer_schedule (
+    scope VARCHAR(64) NOT NULL,
+    day DATE NOT NULL,
+    word VARCHAR(255) NOT NULL,
+    PRIMARY KEY (scope, day)
+);
@@ -0,0 +1,3 @@
+DROP TABLE league_guesses;
+DROP TABLE league_members;
+DROP TABLE leagues;
@@ -0,0 +1,22 @@
+-- One row per email, rendered when it's queued and sent by a job that's retried with
+-- backoff. Kept after sending so admins can see what went out and what didn't.
+CREATE TABLE email_deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- The template it was rendered from
+    template VARCHAR(50) NOT NULL,
+    recipient VARCHAR(255) NOT NULL,
+    subject TEXT NOT NULL,
+    text_body TEXT NOT NULL,
+    html_body TEXT,
+    -- queued, retrying, sent or dead_letter once the attempts ran out or the server
+    -- refused it for good
+    status VARCHAR(20) NOT NULL DEFAULT 'queued',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    -- Of the last failed attempt
+    error TEXT,
+    created_at TIMESTAMP NOT NULL,
+    last_attempt_at TIMESTAMP,
+    sent_at TIMESTAMP
+);
+
+CREATE INDEX email_deliveries_status_idx ON email_deliveries (status, id);
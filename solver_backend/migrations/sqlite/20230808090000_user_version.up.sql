@@ -0,0 +1,2 @@
+-- Bumped by every profile edit, so a client saving over an edit it hasn't seen is refused
+ALTER TABLE users ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,4 @@
+-- Set on the session an admin's impersonation token is stored as. It can't be refreshed,
+-- so it ends when the token expires.
+ALTER TABLE refresh_tokens ADD COLUMN impersonator_id INTEGER REFERENCES users(id) ON DELETE CASCADE;
+ALTER TABLE refresh_tokens ADD COLUMN expires_at TIMESTAMP;
@@ -0,0 +1,2 @@
+DROP INDEX game_sessions_guest_idx;
+ALTER TABLE game_sessions DROP COLUMN guest_id;
@@ -0,0 +1,4 @@
+-- The guest token an anonymous session was started with, so registering with that token
+-- claims it. Guests have no users row, this is the id in their token.
+ALTER TABLE game_sessions ADD COLUMN guest_id TEXT;
+CREATE INDEX game_sessions_guest_idx ON game_sessions (guest_id);
@@ -0,0 +1,3 @@
+DROP INDEX users_password_reset_token_idx;
+ALTER TABLE users DROP COLUMN password_reset_expires_at;
+ALTER TABLE users DROP COLUMN password_reset_token;
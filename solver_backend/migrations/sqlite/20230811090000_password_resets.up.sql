@@ -0,0 +1,7 @@
+-- A single use token mailed to the account's address. Setting a password with it is the
+-- only way to clear password_reset_required. SQLite can't add a UNIQUE column, the index
+-- stands in for the constraint.
+ALTER TABLE users ADD COLUMN password_reset_token TEXT;
+ALTER TABLE users ADD COLUMN password_reset_expires_at TIMESTAMP;
+
+CREATE UNIQUE INDEX users_password_reset_token_idx ON users (password_reset_token);
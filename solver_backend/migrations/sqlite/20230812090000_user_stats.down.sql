@@ -0,0 +1,2 @@
+DROP TABLE user_guess_counts;
+DROP TABLE user_stats;
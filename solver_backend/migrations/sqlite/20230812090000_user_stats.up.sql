@@ -0,0 +1,42 @@
+-- Each user's totals over their game sessions, as GET /game/sessions/stats reports them.
+-- Starting, finishing or claiming a session adds to them in the same transaction, and the
+-- stats recompute job rebuilds them from the sessions themselves.
+CREATE TABLE user_stats (
+    user_id INTEGER PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+    -- Sessions with a guess limit
+    played BIGINT NOT NULL DEFAULT 0,
+    solved BIGINT NOT NULL DEFAULT 0,
+    lost BIGINT NOT NULL DEFAULT 0,
+    free_play_played BIGINT NOT NULL DEFAULT 0,
+    free_play_solved BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMP NOT NULL
+);
+
+-- Solved sessions with a guess limit by the guesses they took
+CREATE TABLE user_guess_counts (
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    guesses BIGINT NOT NULL,
+    count BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (user_id, guesses)
+);
+
+INSERT INTO user_stats (user_id, played, solved, lost, free_play_played, free_play_solved, updated_at)
+SELECT user_id,
+    SUM(CASE WHEN max_guesses IS NOT NULL THEN 1 ELSE 0 END),
+    SUM(CASE WHEN max_guesses IS NOT NULL AND status = 'solved' THEN 1 ELSE 0 END),
+    SUM(CASE WHEN max_guesses IS NOT NULL AND status = 'lost' THEN 1 ELSE 0 END),
+    SUM(CASE WHEN max_guesses IS NULL THEN 1 ELSE 0 END),
+    SUM(CASE WHEN max_guesses IS NULL AND status = 'solved' THEN 1 ELSE 0 END),
+    CURRENT_TIMESTAMP
+FROM game_sessions
+WHERE user_id IS NOT NULL
+GROUP BY user_id;
+
+INSERT INTO user_guess_counts (user_id, guesses, count)
+SELECT user_id, guesses, COUNT(*)
+FROM (
+    SELECT s.user_id, (SELECT COUNT(*) FROM session_guesses g WHERE g.session_id = s.id) AS guesses
+    FROM game_sessions s
+    WHERE s.user_id IS NOT NULL AND s.max_guesses IS NOT NULL AND s.status = 'solved'
+) solved
+GROUP BY user_id, guesses;
@@ -0,0 +1,190 @@
+// Offline solver: type each guess with the feedback it got, e.g. "crane gyxxx", and it
+// prints the words still possible and what to try next. Filtering and scoring are the
+// server's own, from `wordle_solver::solver`.
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::process::ExitCode;
+use wordle_solver::solver::{explain, outlook, parse_feedback, suggest, Constraints, Mark};
+
+const BUNDLED_WORDS: &str = include_str!("../../data/words.txt");
+const USAGE: &str = "Usage: wordle-cli [WORDS_FILE] [--hard] [--explain] [--length N] [--top N]";
+// Longer candidate lists are cut off after this many words
+const CANDIDATES_SHOWN: usize = 10;
+
+struct Options {
+    words_file: Option<String>,
+    hard: bool,
+    // Say why each suggestion is worth trying
+    explain: bool,
+    length: usize,
+    top: usize,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options { words_file: None, hard: false, explain: false, length: 5, top: 5 };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--hard" => options.hard = true,
+            "--explain" => options.explain = true,
+            "--length" | "--top" => {
+                let value = args
+                    .next()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .filter(|value| *value > 0)
+                    .ok_or_else(|| format!("{} needs a positive number", arg))?;
+                if arg == "--length" {
+                    options.length = value;
+                } else {
+                    options.top = value;
+                }
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown flag {}", flag)),
+            path if options.words_file.is_none() => options.words_file = Some(path.to_string()),
+            extra => return Err(format!("unexpected argument {}", extra)),
+        }
+    }
+
+    Ok(options)
+}
+
+// One word per line, anything that isn't a word of the requested length is skipped
+fn load_words(contents: &str, length: usize) -> Vec<String> {
+    let mut words: Vec<String> = contents
+        .lines()
+        .map(|line| line.trim().to_lowercase())
+        .filter(|word| word.chars().count() == length && word.chars().all(|letter| letter.is_alphabetic()))
+        .collect();
+    words.sort();
+    words.dedup();
+    words
+}
+
+fn print_state(out: &mut impl Write, words: &[String], candidates: &[&str], constraints: &Constraints, options: &Options) -> io::Result<()> {
+    let shown = candidates.iter().take(CANDIDATES_SHOWN).copied().collect::<Vec<_>>().join(", ");
+    match candidates.len() {
+        1 => writeln!(out, "1 candidate left: {}", shown)?,
+        n if n > CANDIDATES_SHOWN => writeln!(out, "{} candidates left: {}, ...", n, shown)?,
+        n => writeln!(out, "{} candidates left: {}", n, shown)?,
+    }
+
+    let guesses: Vec<&str> = words
+        .iter()
+        .map(String::as_str)
+        .filter(|word| !options.hard || constraints.hard_mode_allows(word))
+        .collect();
+    let suggestions = suggest(candidates, &guesses, options.top);
+    let listed = suggestions
+        .iter()
+        .map(|suggestion| format!("{} ({})", suggestion.word, suggestion.score))
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(out, "Try: {}", listed)?;
+
+    if options.explain {
+        for suggestion in &suggestions {
+            writeln!(out, "  {}", explain(suggestion.word, &outlook(suggestion.word, candidates)))?;
+        }
+    }
+    Ok(())
+}
+
+fn run(options: Options) -> Result<(), String> {
+    let contents = match &options.words_file {
+        Some(path) => std::fs::read_to_string(path).map_err(|error| format!("Failed to read {}: {}", path, error))?,
+        None => BUNDLED_WORDS.to_string(),
+    };
+    let words = load_words(&contents, options.length);
+    if words.is_empty() {
+        return Err(format!("No {}-letter words to solve with", options.length));
+    }
+
+    let stdin = io::stdin();
+    let interactive = stdin.is_terminal();
+    let mut out = io::stdout().lock();
+    let io_error = |error: io::Error| error.to_string();
+
+    let mut constraints = Constraints::with_length(options.length);
+    let mut guesses = 0;
+    writeln!(out, "{} words loaded{}. Enter a guess and its feedback, e.g. \"crane gyxxx\" (g green, y yellow, x gray).",
+        words.len(), if options.hard { " in hard mode" } else { "" }).map_err(io_error)?;
+    let candidates: Vec<&str> = words.iter().map(String::as_str).collect();
+    print_state(&mut out, &words, &candidates, &constraints, &options).map_err(io_error)?;
+
+    let mut lines = stdin.lock().lines();
+    loop {
+        if interactive {
+            write!(out, "> ").and_then(|_| out.flush()).map_err(io_error)?;
+        }
+        let line = match lines.next() {
+            Some(line) => line.map_err(io_error)?,
+            None => return Ok(()),
+        };
+
+        let (guess, pattern) = match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [] => continue,
+            ["quit"] | ["exit"] => return Ok(()),
+            [guess, pattern] => (guess.to_lowercase(), pattern),
+            _ => {
+                eprintln!("Expected a guess and its feedback, e.g. \"crane gyxxx\"");
+                continue;
+            }
+        };
+
+        if guess.chars().count() != options.length {
+            eprintln!("{} is not a {}-letter word", guess, options.length);
+            continue;
+        }
+        if options.hard && !constraints.hard_mode_allows(&guess) {
+            eprintln!("Hard mode: {} has to reuse every revealed hint", guess);
+            continue;
+        }
+        let marks = match parse_feedback(pattern) {
+            Ok(marks) => marks,
+            Err(error) => {
+                eprintln!("Invalid feedback: {}", error);
+                continue;
+            }
+        };
+
+        // Feedback that rules out every word is most likely a typo, so it isn't kept
+        let mut narrowed = constraints.clone();
+        if let Err(error) = narrowed.apply(&guess, &marks) {
+            eprintln!("Invalid feedback: {}", error);
+            continue;
+        }
+        guesses += 1;
+
+        if marks.iter().all(|mark| *mark == Mark::Green) {
+            writeln!(out, "Solved in {} {}.", guesses, if guesses == 1 { "guess" } else { "guesses" }).map_err(io_error)?;
+            return Ok(());
+        }
+
+        let candidates: Vec<&str> = words.iter().map(String::as_str).filter(|word| narrowed.allows(word)).collect();
+        if candidates.is_empty() {
+            guesses -= 1;
+            eprintln!("No word matches that feedback, check it and try again");
+            continue;
+        }
+
+        constraints = narrowed;
+        print_state(&mut out, &words, &candidates, &constraints, &options).map_err(io_error)?;
+    }
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{}\n{}", error, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match run(options) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,1028 @@
+use actix_web::http::header::{ContentEncoding, HeaderName, HeaderValue};
+use actix_web::http::{Method, Uri};
+use chrono::Duration;
+use crate::middleware::rate_limit::RATE_LIMITED_GROUPS;
+use crate::models::game_models::STANDARD_MAX_GUESSES;
+use crate::utils::answer_utils::AnswerStrategy;
+use crate::utils::concurrency_utils::ROUTE_GROUPS;
+use crate::utils::feature_utils::{FEATURES, OFF_BY_DEFAULT};
+use ipnet::IpNet;
+use jsonwebtoken::Algorithm;
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+
+// Everything the server can be configured with, read from the environment once at
+// startup. Handlers reach it through AppState instead of reading variables themselves.
+pub struct Settings {
+    pub storage: Storage,
+    // Always MEMORY_DATABASE_URL with STORAGE=memory
+    pub database_url: String,
+    pub db_max_connections: u32,
+    pub db_min_connections: u32,
+    // How long startup keeps retrying an unreachable database before giving up
+    pub db_connect_max_wait: std::time::Duration,
+    // Postgres cancels any statement running longer than this, zero turns it off
+    pub db_statement_timeout: std::time::Duration,
+    // How long a request waits for a free pool connection
+    pub db_acquire_timeout: std::time::Duration,
+    // Off where the schema is managed by a separate release step
+    pub run_migrations: bool,
+    // Zone the naive timestamps written before the switch to UTC were in, only read by the
+    // migration that converts them
+    pub legacy_timezone: String,
+    pub host: String,
+    pub port: u16,
+    // Plain HTTP on `port`, off once clients have moved to the TLS listener
+    pub http_enabled: bool,
+    // Terminates TLS itself when set, for deployments without a reverse proxy
+    pub tls: Option<TlsSettings>,
+    // Which peers' forwarding headers are believed when working out the client's address
+    pub proxies: ProxySettings,
+    // Defaults to one worker per CPU core when unset
+    pub workers: Option<usize>,
+    pub jwt: JwtSettings,
+    pub cors: CorsSettings,
+    pub security_headers: SecurityHeaderSettings,
+    pub bcrypt_cost: u32,
+    // How many recent passwords can't be reused, zero turns the check off
+    pub password_history_size: i64,
+    pub auth_cache_ttl: std::time::Duration,
+    // Shared store for revocations, rate limits and cached results, in process when unset
+    pub redis_url: Option<String>,
+    // Where spans are shipped, None keeps them in the log
+    pub otlp: Option<OtlpSettings>,
+    pub result_cache_ttl: std::time::Duration,
+    // Remote copy of the word list admins can sync from, None turns syncing off
+    pub word_sync: Option<WordSyncSettings>,
+    // Whether a token is accepted, or a request allowed, when its check can't be completed
+    pub revocation_fail_open: bool,
+    pub rate_limit_fail_open: bool,
+    pub idempotency_ttl: Duration,
+    // How long a deleted account is kept as it was before its personal data is anonymized
+    pub account_deletion_grace: Duration,
+    // How often an idle job worker looks for due jobs
+    pub job_poll_interval: std::time::Duration,
+    // Runs a failing job gets, the first one included
+    pub job_max_attempts: i32,
+    pub webhooks: WebhookSettings,
+    pub mail: MailSettings,
+    // How long a game session started without logging in can be played and claimed
+    pub anonymous_session_ttl: Duration,
+    // The most guesses a player may give a session when starting it
+    pub max_session_guesses: u32,
+    // The most guess and answer pairs one /game/feedback/batch request may send
+    pub max_feedback_batch: usize,
+    // Whether a word list failing its integrity check takes the game routes and readiness
+    // down with it, rather than only being reported
+    pub word_check_enforced: bool,
+    // Whether an admin impersonating a user may change anything, off leaves them read-only
+    pub impersonation_allows_writes: bool,
+    // Page size of listings when the client doesn't ask for one, and the most it may ask for
+    pub default_page_size: u32,
+    pub max_page_size: u32,
+    // Encodings responses may be compressed with, the client's Accept-Encoding picks one.
+    // Empty turns compression off.
+    pub compression_encodings: Vec<ContentEncoding>,
+    // Bodies smaller than this are sent uncompressed
+    pub compression_min_size: usize,
+    // Whether each feature flag starts out on, until an admin stores a value of their own
+    pub feature_defaults: Vec<(&'static str, bool)>,
+    // Most requests of each route group handled at once, None for no limit
+    pub concurrency_limits: Vec<(&'static str, Option<usize>)>,
+    // How long a request waits for a slot in its group before it's turned away
+    pub concurrency_queue_timeout: std::time::Duration,
+    // Longest a best-guess computation may run, for every request waiting on it
+    pub solver_timeout: std::time::Duration,
+    // File the solver's pattern matrix is kept in between restarts, None to rebuild it
+    // every time
+    pub solver_cache_path: Option<String>,
+    // Best-guess in auto mode ranks exactly below this many candidates, fast from there on
+    pub exact_threshold: usize,
+    // Most candidates best-guess ranks exactly for, even when exact is asked for
+    pub exact_ceiling: usize,
+    // Most requests per client IP in each rate limited group per window, None for no limit
+    pub rate_limits: Vec<(&'static str, Option<u64>)>,
+    pub rate_limit_window: std::time::Duration,
+    // How often flags changed through another replica are picked up, None only reads them at startup
+    pub feature_refresh_interval: Option<std::time::Duration>,
+    // The Retry-After sent with requests refused for maintenance
+    pub maintenance_retry_after: u64,
+    // How server-chosen answers are picked where a league doesn't choose for itself
+    pub answer_strategy: AnswerStrategy,
+    // How long a no-repeat answer stays out of the running after it's used
+    pub answer_no_repeat_days: u32,
+    pub email_lowercase_local_part: bool,
+    // Static token required to scrape /metrics, None leaves it open
+    pub metrics_token: Option<String>,
+    pub introspection: IntrospectionSettings,
+    pub api_docs_enabled: bool,
+    // Serves the unversioned /api paths alongside /api/v1, marked as deprecated
+    pub legacy_api_routes: bool,
+    // Requests taking longer are logged with their timing breakdown, zero turns it off
+    pub slow_request_threshold: std::time::Duration,
+    // Whether the breakdown is also sent to clients in a Server-Timing header
+    pub server_timing_header: bool,
+    // How long in-flight requests get to finish after a shutdown signal
+    pub shutdown_timeout_seconds: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Storage {
+    // The database DATABASE_URL points at
+    Database,
+    // An SQLite database in this process, for running without any other services.
+    // Everything in it is gone once the server stops.
+    Memory,
+}
+
+pub const MEMORY_DATABASE_URL: &str = "sqlite::memory:";
+const MIN_INTROSPECTION_SECRET_LENGTH: usize = 32;
+
+pub struct JwtSettings {
+    // `kid` -> secret pairs, oldest first
+    pub hmac_keys: Vec<(String, String)>,
+    // `kid` -> private key PEM path pairs, oldest first
+    pub rsa_keys: Vec<(String, String)>,
+    // Which family of keys signs new tokens
+    pub algorithm: Algorithm,
+    pub access_token_ttl: Duration,
+    pub refresh_token_ttl: Duration,
+    // Lifetime of the token an admin gets to act as another user, never refreshed
+    pub impersonation_ttl: Duration,
+    // Lifetime of a guest token, which has no user behind it and can't be refreshed
+    pub guest_ttl: Duration,
+    // How long a rotated refresh token may be presented again before it counts as reuse
+    pub refresh_reuse_grace: Duration,
+}
+
+pub struct TlsSettings {
+    pub cert_path: String,
+    pub key_path: String,
+    pub port: u16,
+    // How often the files are checked for a renewed certificate, None leaves reloading
+    // to SIGHUP
+    pub watch_interval: Option<std::time::Duration>,
+    // Whether plain HTTP requests are answered with a redirect to the TLS listener
+    pub redirect_http: bool,
+}
+
+#[derive(Clone)]
+pub struct OtlpSettings {
+    // A collector such as Jaeger or Tempo, over OTLP/gRPC
+    pub endpoint: String,
+    pub service_name: String,
+}
+
+#[derive(Clone)]
+pub struct WordSyncSettings {
+    pub url: String,
+    // For the whole fetch, body included
+    pub timeout: std::time::Duration,
+    // Larger lists are rejected without being read to the end
+    pub max_bytes: usize,
+    // How often the list is synced in the background, None only syncs when an admin asks
+    pub interval: Option<std::time::Duration>,
+}
+
+// Who may ask /auth/introspect about tokens. With neither set no one can.
+#[derive(Clone)]
+pub struct IntrospectionSettings {
+    // Sent by the calling service as a bearer token
+    pub secret: Option<String>,
+    // Set by a trusted proxy once it has verified the caller's client certificate, with
+    // the value it sets when verification succeeded
+    pub mtls_header: Option<(HeaderName, String)>,
+}
+
+#[derive(Clone)]
+pub struct WebhookSettings {
+    // Per delivery attempt, connecting through reading the response
+    pub timeout: std::time::Duration,
+    // Failed attempts in a row after which an endpoint is disabled
+    pub failure_limit: i32,
+    // Lets URLs name loopback and private network addresses, for local development
+    pub allow_private_hosts: bool,
+}
+
+#[derive(Clone)]
+pub struct MailSettings {
+    // The From of every message, a bare address or "Name <address>"
+    pub from: String,
+    // None writes mail to the log instead of sending it
+    pub smtp: Option<SmtpSettings>,
+    // Sending attempts before a message is dead-lettered
+    pub max_attempts: i32,
+}
+
+#[derive(Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    // Username and password, None sends without logging in
+    pub credentials: Option<(String, String)>,
+    // Per message, connecting through the server's last reply
+    pub timeout: std::time::Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SmtpTls {
+    // Plain text throughout, only for a relay on the same host
+    None,
+    // Upgraded after connecting, servers that can't are refused
+    StartTls,
+    // TLS from the first byte, usually on port 465
+    Implicit,
+}
+
+pub struct ProxySettings {
+    // Empty trusts no one, the socket address is always the client
+    pub trusted: Vec<IpNet>,
+    pub header: ForwardedHeader,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ForwardedHeader {
+    XForwardedFor,
+    // RFC 7239
+    Forwarded,
+}
+
+pub struct SecurityHeaderSettings {
+    pub enabled: bool,
+    // None leaves the header off
+    pub frame_options: Option<HeaderValue>,
+    pub referrer_policy: Option<HeaderValue>,
+    // Strict-Transport-Security, only sent on connections that are HTTPS for the client
+    pub hsts: Option<HeaderValue>,
+    // A proxy in front terminates TLS, so plain connections reaching us were HTTPS for the
+    // client and HSTS is sent on them too
+    pub trusted_proxy: bool,
+    // Content-Security-Policy for routes without an override
+    pub content_security_policy: Option<HeaderValue>,
+    // Path prefix -> policy pairs replacing the default, longest prefix first
+    pub route_policies: Vec<(String, HeaderValue)>,
+}
+
+pub struct CorsSettings {
+    // None allows any origin, only meant for local development
+    pub allowed_origins: Option<Vec<String>>,
+    pub allowed_methods: Vec<Method>,
+    pub allowed_headers: Vec<HeaderName>,
+    pub exposed_headers: Vec<HeaderName>,
+    pub allow_credentials: bool,
+    // How long browsers may cache a preflight response
+    pub max_age_seconds: usize,
+}
+
+// Every problem found while loading, so they can all be fixed in one go
+#[derive(Debug)]
+pub struct ConfigError(pub Vec<String>);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Invalid configuration:")?;
+        for problem in &self.0 {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Settings {
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Settings::from_vars(&env::vars().collect())
+    }
+
+    pub fn from_vars(vars: &HashMap<String, String>) -> Result<Self, ConfigError> {
+        let mut env = Vars { vars, problems: Vec::new() };
+
+        let storage = match env.get("STORAGE").unwrap_or("database").to_lowercase().as_str() {
+            "database" => Storage::Database,
+            "memory" => Storage::Memory,
+            other => {
+                env.problem(&format!("STORAGE must be database or memory, got {:?}", other));
+                Storage::Database
+            }
+        };
+        let database_url = match storage {
+            Storage::Database => env.required("DATABASE_URL"),
+            Storage::Memory => MEMORY_DATABASE_URL.to_string(),
+        };
+        let db_max_connections = env.parse_or("DB_MAX_CONNECTIONS", 10u32);
+        let db_min_connections = env.parse_or("DB_MIN_CONNECTIONS", 0u32);
+        if db_max_connections == 0 {
+            env.problem("DB_MAX_CONNECTIONS must be at least 1");
+        }
+        if db_min_connections > db_max_connections {
+            env.problem("DB_MIN_CONNECTIONS must not exceed DB_MAX_CONNECTIONS");
+        }
+        let db_connect_max_wait = std::time::Duration::from_secs(env.parse_or("DB_CONNECT_MAX_WAIT_SECONDS", 60u64));
+        let db_statement_timeout = std::time::Duration::from_millis(env.parse_or("DB_STATEMENT_TIMEOUT_MS", 5000u64));
+        let db_acquire_timeout = std::time::Duration::from_secs(env.parse_or("DB_ACQUIRE_TIMEOUT_SECONDS", 10u64));
+        let run_migrations = env.flag("RUN_MIGRATIONS", true);
+        let legacy_timezone = env.get("LEGACY_TIMEZONE").unwrap_or("UTC").to_string();
+
+        let host = env.get("HOST").unwrap_or("127.0.0.1").to_string();
+        let port = env.parse_or("PORT", 8080u16);
+        let proxies = proxy_settings(&mut env);
+        let workers = env.parse::<usize>("WORKERS");
+        if workers == Some(0) {
+            env.problem("WORKERS must be at least 1");
+        }
+
+        let http_enabled = env.flag("HTTP_ENABLED", true);
+        let tls = tls_settings(&mut env);
+        if !http_enabled && tls.is_none() {
+            env.problem("HTTP_ENABLED can only be turned off when TLS is configured");
+        }
+        if let Some(tls) = &tls {
+            if http_enabled && tls.port == port {
+                env.problem("TLS_PORT must differ from PORT while HTTP_ENABLED is on");
+            }
+            if !http_enabled && tls.redirect_http {
+                env.problem("HTTP_REDIRECT_TO_HTTPS needs HTTP_ENABLED to have anything to redirect");
+            }
+        }
+
+        let jwt = jwt_settings(&mut env);
+        let cors = cors_settings(&mut env);
+        let security_headers = security_header_settings(&mut env);
+
+        let bcrypt_cost = env.parse_or("BCRYPT_COST", bcrypt::DEFAULT_COST);
+        if !(4..=31).contains(&bcrypt_cost) {
+            env.problem("BCRYPT_COST must be between 4 and 31");
+        }
+
+        let password_history_size = env.parse_or("PASSWORD_HISTORY_SIZE", 5i64);
+        if password_history_size < 0 {
+            env.problem("PASSWORD_HISTORY_SIZE must not be negative");
+        }
+
+        let auth_cache_ttl = std::time::Duration::from_secs(env.parse_or("AUTH_CACHE_TTL_SECONDS", 30u64));
+        let redis_url = redis_url(&mut env);
+        let otlp = otlp_settings(&mut env);
+        let result_cache_ttl = std::time::Duration::from_secs(env.parse_or("RESULT_CACHE_TTL_SECONDS", 300u64));
+        let word_sync = word_sync_settings(&mut env);
+        let revocation_fail_open = env.flag("REVOCATION_FAIL_OPEN", true);
+        let rate_limit_fail_open = env.flag("RATE_LIMIT_FAIL_OPEN", true);
+        let idempotency_ttl = Duration::hours(env.parse_or("IDEMPOTENCY_TTL_HOURS", 24u32).into());
+        let account_deletion_grace = Duration::days(env.parse_or("ACCOUNT_DELETION_GRACE_DAYS", 30u32).into());
+        let job_poll_interval = std::time::Duration::from_millis(env.parse_or("JOB_POLL_INTERVAL_MS", 1000u64));
+        let job_max_attempts = env.parse_or("JOB_MAX_ATTEMPTS", 3i32);
+        if job_max_attempts < 1 {
+            env.problem("JOB_MAX_ATTEMPTS must be at least 1");
+        }
+        let webhooks = webhook_settings(&mut env);
+        let mail = mail_settings(&mut env);
+        let anonymous_session_ttl = Duration::hours(env.parse_or("ANONYMOUS_SESSION_HOURS", 24u32).into());
+        let max_session_guesses = env.parse_or("MAX_SESSION_GUESSES", 20u32);
+        if max_session_guesses < STANDARD_MAX_GUESSES {
+            env.problem(&format!("MAX_SESSION_GUESSES must be at least {}", STANDARD_MAX_GUESSES));
+        }
+        let max_feedback_batch = env.parse_or("FEEDBACK_BATCH_MAX", 500usize);
+        let word_check_enforced = env.flag("WORD_CHECK_ENFORCED", false);
+        let impersonation_allows_writes = env.flag("IMPERSONATION_ALLOW_WRITES", false);
+        let default_page_size = env.parse_or("DEFAULT_PAGE_SIZE", 20u32);
+        let max_page_size = env.parse_or("MAX_PAGE_SIZE", 100u32);
+        if !(1..=max_page_size).contains(&default_page_size) {
+            env.problem("DEFAULT_PAGE_SIZE must be between 1 and MAX_PAGE_SIZE");
+        }
+        let compression_encodings = compression_encodings(&mut env);
+        let compression_min_size = env.parse_or("COMPRESSION_MIN_BYTES", 1024usize);
+        // FEATURE_REGISTRATION and so on, every feature but the maintenance mode defaults to on
+        let feature_defaults = FEATURES
+            .iter()
+            .map(|feature| (*feature, env.flag(&format!("FEATURE_{}", feature.to_uppercase()), !OFF_BY_DEFAULT.contains(feature))))
+            .collect();
+        let maintenance_retry_after = env.parse_or("MAINTENANCE_RETRY_AFTER_SECONDS", 300u64);
+        // uniform, no_repeat or scheduled
+        let answer_strategy = env.parse_or("ANSWER_STRATEGY", AnswerStrategy::NoRepeat);
+        let answer_no_repeat_days = env.parse_or("ANSWER_NO_REPEAT_DAYS", 365u32);
+        let feature_refresh_seconds = env.parse_or("FEATURE_FLAG_REFRESH_SECONDS", 30u64);
+        let feature_refresh_interval = (feature_refresh_seconds > 0).then(|| std::time::Duration::from_secs(feature_refresh_seconds));
+        // CONCURRENCY_LIMIT_SOLVER and so on, zero for no limit. Heavy groups are CPU bound,
+        // so they default to one request per core.
+        let cores = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+        let concurrency_limits = ROUTE_GROUPS
+            .iter()
+            .map(|group| {
+                let limit = env.parse_or(&format!("CONCURRENCY_LIMIT_{}", group.to_uppercase()), cores);
+                (*group, (limit > 0).then_some(limit))
+            })
+            .collect();
+        let concurrency_queue_timeout = std::time::Duration::from_millis(env.parse_or("CONCURRENCY_QUEUE_TIMEOUT_MS", 250u64));
+        let solver_timeout = std::time::Duration::from_millis(env.parse_or("SOLVER_TIMEOUT_MS", 10_000u64));
+        if solver_timeout.is_zero() {
+            env.problem("SOLVER_TIMEOUT_MS must be above zero");
+        }
+        let solver_cache_path = env.get("SOLVER_CACHE_PATH").map(str::to_string);
+        let exact_threshold = env.parse_or("BEST_GUESS_EXACT_THRESHOLD", 500usize);
+        let exact_ceiling = env.parse_or("BEST_GUESS_EXACT_CEILING", 2000usize);
+        if exact_threshold > exact_ceiling {
+            env.problem("BEST_GUESS_EXACT_THRESHOLD must not exceed BEST_GUESS_EXACT_CEILING");
+        }
+        // RATE_LIMIT_AUTH and so on, zero for no limit
+        let rate_limits = RATE_LIMITED_GROUPS
+            .iter()
+            .map(|(group, default)| {
+                let limit = env.parse_or(&format!("RATE_LIMIT_{}", group.to_uppercase()), *default);
+                (*group, (limit > 0).then_some(limit))
+            })
+            .collect();
+        let rate_limit_window = std::time::Duration::from_secs(env.parse_or("RATE_LIMIT_WINDOW_SECONDS", 60u64));
+        if rate_limit_window.is_zero() {
+            env.problem("RATE_LIMIT_WINDOW_SECONDS must be at least 1");
+        }
+        let email_lowercase_local_part = env.flag("EMAIL_LOWERCASE_LOCAL_PART", false);
+        let metrics_token = env.get("METRICS_TOKEN").map(str::to_string);
+        let introspection = introspection_settings(&mut env, &proxies);
+        let api_docs_enabled = env.flag("API_DOCS_ENABLED", true);
+        let legacy_api_routes = env.flag("LEGACY_API_ROUTES", true);
+        let slow_request_threshold = std::time::Duration::from_millis(env.parse_or("SLOW_REQUEST_THRESHOLD_MS", 1000u64));
+        let server_timing_header = env.flag("SERVER_TIMING_HEADER", true);
+        let shutdown_timeout_seconds = env.parse_or("SHUTDOWN_TIMEOUT_SECONDS", 30u64);
+
+        if !env.problems.is_empty() {
+            return Err(ConfigError(env.problems));
+        }
+
+        Ok(Settings {
+            storage,
+            database_url,
+            db_max_connections,
+            db_min_connections,
+            db_connect_max_wait,
+            db_statement_timeout,
+            db_acquire_timeout,
+            run_migrations,
+            legacy_timezone,
+            host,
+            port,
+            http_enabled,
+            tls,
+            proxies,
+            workers,
+            jwt,
+            cors,
+            security_headers,
+            bcrypt_cost,
+            password_history_size,
+            auth_cache_ttl,
+            redis_url,
+            otlp,
+            result_cache_ttl,
+            word_sync,
+            revocation_fail_open,
+            rate_limit_fail_open,
+            idempotency_ttl,
+            account_deletion_grace,
+            job_poll_interval,
+            job_max_attempts,
+            webhooks,
+            mail,
+            anonymous_session_ttl,
+            max_session_guesses,
+            max_feedback_batch,
+            word_check_enforced,
+            impersonation_allows_writes,
+            default_page_size,
+            max_page_size,
+            compression_encodings,
+            compression_min_size,
+            feature_defaults,
+            feature_refresh_interval,
+            maintenance_retry_after,
+            answer_strategy,
+            answer_no_repeat_days,
+            concurrency_limits,
+            concurrency_queue_timeout,
+            solver_timeout,
+            solver_cache_path,
+            exact_threshold,
+            exact_ceiling,
+            rate_limits,
+            rate_limit_window,
+            email_lowercase_local_part,
+            metrics_token,
+            introspection,
+            api_docs_enabled,
+            legacy_api_routes,
+            slow_request_threshold,
+            server_timing_header,
+            shutdown_timeout_seconds,
+        })
+    }
+}
+
+// JWT_KEYS holds comma separated `kid:secret` HS256 pairs and JWT_RSA_KEYS comma
+// separated `kid:/path/to/private.pem` RS256 pairs, both ordered oldest to newest.
+// Without JWT_KEYS the single SECRET_KEY is used under the "default" kid. JWT_ALGORITHM
+// (HS256 or RS256, default HS256) picks which family signs.
+fn jwt_settings(env: &mut Vars) -> JwtSettings {
+    let hmac_keys = match env.get("JWT_KEYS") {
+        Some(_) => key_pairs(env, "JWT_KEYS", &[]),
+        None => env
+            .get("SECRET_KEY")
+            .map(|secret| vec![("default".to_string(), secret.to_string())])
+            .unwrap_or_default(),
+    };
+    let rsa_keys = key_pairs(env, "JWT_RSA_KEYS", &hmac_keys);
+
+    let algorithm = match env.get("JWT_ALGORITHM") {
+        None | Some("HS256") => Algorithm::HS256,
+        Some("RS256") => Algorithm::RS256,
+        Some(other) => {
+            env.problem(&format!("JWT_ALGORITHM must be HS256 or RS256, got {:?}", other));
+            Algorithm::HS256
+        }
+    };
+
+    match algorithm {
+        Algorithm::RS256 if rsa_keys.is_empty() => env.problem("JWT_RSA_KEYS is required when JWT_ALGORITHM is RS256"),
+        Algorithm::HS256 if hmac_keys.is_empty() => env.problem("SECRET_KEY or JWT_KEYS is required"),
+        _ => {}
+    }
+
+    let access_token_ttl = Duration::minutes(env.parse_or("ACCESS_TOKEN_TTL_MINUTES", 60u32).into());
+    let refresh_token_ttl = Duration::days(env.parse_or("REFRESH_TOKEN_TTL_DAYS", 7u32).into());
+    let impersonation_ttl = Duration::minutes(env.parse_or("IMPERSONATION_TTL_MINUTES", 15u32).into());
+    let guest_ttl = Duration::minutes(env.parse_or("GUEST_TOKEN_TTL_MINUTES", 30u32).into());
+    let refresh_reuse_grace = Duration::seconds(env.parse_or("REFRESH_REUSE_GRACE_SECONDS", 10u32).into());
+
+    JwtSettings {
+        hmac_keys,
+        rsa_keys,
+        algorithm,
+        access_token_ttl,
+        refresh_token_ttl,
+        impersonation_ttl,
+        guest_ttl,
+        refresh_reuse_grace,
+    }
+}
+
+// Either TLS_CERT_PATH and TLS_KEY_PATH, or TLS_CERT_DIR holding the fullchain.pem and
+// privkey.pem certbot keeps under /etc/letsencrypt/live/<domain>. A directory is checked
+// for renewals every minute unless TLS_WATCH_INTERVAL_SECONDS says otherwise, zero only
+// reloads on SIGHUP.
+fn tls_settings(env: &mut Vars) -> Option<TlsSettings> {
+    let (cert_path, key_path, default_watch) = match (env.get("TLS_CERT_DIR"), env.get("TLS_CERT_PATH"), env.get("TLS_KEY_PATH")) {
+        (None, None, None) => {
+            if env.flag("HTTP_REDIRECT_TO_HTTPS", false) {
+                env.problem("HTTP_REDIRECT_TO_HTTPS needs TLS to be configured");
+            }
+            return None;
+        }
+        (Some(dir), None, None) => {
+            let dir = dir.trim_end_matches('/');
+            (format!("{}/fullchain.pem", dir), format!("{}/privkey.pem", dir), 60)
+        }
+        (None, Some(cert), Some(key)) => (cert.to_string(), key.to_string(), 0),
+        (Some(_), _, _) => {
+            env.problem("TLS_CERT_DIR can't be combined with TLS_CERT_PATH or TLS_KEY_PATH");
+            return None;
+        }
+        _ => {
+            env.problem("TLS_CERT_PATH and TLS_KEY_PATH must be set together");
+            return None;
+        }
+    };
+
+    let port = env.parse_or("TLS_PORT", 8443u16);
+    let watch_seconds = env.parse_or("TLS_WATCH_INTERVAL_SECONDS", default_watch);
+    let redirect_http = env.flag("HTTP_REDIRECT_TO_HTTPS", false);
+
+    Some(TlsSettings {
+        cert_path,
+        key_path,
+        port,
+        watch_interval: (watch_seconds > 0).then(|| std::time::Duration::from_secs(watch_seconds)),
+        redirect_http,
+    })
+}
+
+// TRUSTED_PROXIES is a comma separated list of CIDRs or single addresses of the proxies in
+// front, FORWARDED_HEADER names what they add: x-forwarded-for (default) or forwarded
+fn proxy_settings(env: &mut Vars) -> ProxySettings {
+    let mut trusted = Vec::new();
+    for proxy in list(env.get("TRUSTED_PROXIES").unwrap_or("")) {
+        match proxy.parse::<IpNet>() {
+            Ok(network) => trusted.push(network.trunc()),
+            Err(_) => match proxy.parse::<std::net::IpAddr>() {
+                Ok(address) => trusted.push(IpNet::from(address)),
+                Err(_) => env.problem(&format!("TRUSTED_PROXIES entry {:?} must be an address or a CIDR like 10.0.0.0/8", proxy)),
+            },
+        }
+    }
+
+    let header = match env.get("FORWARDED_HEADER").unwrap_or("x-forwarded-for").to_lowercase().as_str() {
+        "x-forwarded-for" => ForwardedHeader::XForwardedFor,
+        "forwarded" => ForwardedHeader::Forwarded,
+        other => {
+            env.problem(&format!("FORWARDED_HEADER must be x-forwarded-for or forwarded, got {:?}", other));
+            ForwardedHeader::XForwardedFor
+        }
+    };
+
+    ProxySettings { trusted, header }
+}
+
+// INTROSPECTION_SECRET is shared with the services allowed to introspect tokens. Behind a
+// proxy that checks client certificates, INTROSPECTION_MTLS_HEADER names the header it sets
+// and INTROSPECTION_MTLS_VALUE (default SUCCESS) what it holds for a verified certificate.
+// Only TRUSTED_PROXIES are believed, since anyone else could send the header too.
+fn introspection_settings(env: &mut Vars, proxies: &ProxySettings) -> IntrospectionSettings {
+    let secret = env.get("INTROSPECTION_SECRET").map(str::to_string);
+    if secret.as_ref().is_some_and(|secret| secret.len() < MIN_INTROSPECTION_SECRET_LENGTH) {
+        env.problem(&format!("INTROSPECTION_SECRET must be at least {} characters", MIN_INTROSPECTION_SECRET_LENGTH));
+    }
+
+    let mtls_header = match env.get("INTROSPECTION_MTLS_HEADER") {
+        None => None,
+        Some(name) => match HeaderName::from_bytes(name.to_lowercase().as_bytes()) {
+            Ok(header) => Some((header, env.get("INTROSPECTION_MTLS_VALUE").unwrap_or("SUCCESS").to_string())),
+            Err(_) => {
+                env.problem(&format!("INTROSPECTION_MTLS_HEADER has an invalid header name {:?}", name));
+                None
+            }
+        },
+    };
+    if mtls_header.is_some() && proxies.trusted.is_empty() {
+        env.problem("INTROSPECTION_MTLS_HEADER needs TRUSTED_PROXIES to say which proxy sets it");
+    }
+
+    IntrospectionSettings { secret, mtls_header }
+}
+
+// WORD_LIST_SYNC_URL points at a plain text list, one word per line. Syncing also runs
+// every WORD_LIST_SYNC_INTERVAL_SECONDS when that's above zero.
+fn word_sync_settings(env: &mut Vars) -> Option<WordSyncSettings> {
+    let url = env.get("WORD_LIST_SYNC_URL")?.to_string();
+    match url.parse::<Uri>() {
+        Ok(uri) if matches!(uri.scheme_str(), Some("http" | "https")) && uri.host().is_some() => {}
+        _ => env.problem(&format!("WORD_LIST_SYNC_URL must be an http or https URL, got {:?}", url)),
+    }
+
+    let timeout = std::time::Duration::from_secs(env.parse_or("WORD_LIST_SYNC_TIMEOUT_SECONDS", 10u64));
+    if timeout.is_zero() {
+        env.problem("WORD_LIST_SYNC_TIMEOUT_SECONDS must be at least 1");
+    }
+    let max_bytes = env.parse_or("WORD_LIST_SYNC_MAX_BYTES", 1024 * 1024usize);
+    let interval_seconds = env.parse_or("WORD_LIST_SYNC_INTERVAL_SECONDS", 0u64);
+
+    Some(WordSyncSettings {
+        url,
+        timeout,
+        max_bytes,
+        interval: (interval_seconds > 0).then(|| std::time::Duration::from_secs(interval_seconds)),
+    })
+}
+
+fn webhook_settings(env: &mut Vars) -> WebhookSettings {
+    let timeout = std::time::Duration::from_secs(env.parse_or("WEBHOOK_TIMEOUT_SECONDS", 10u64));
+    if timeout.is_zero() {
+        env.problem("WEBHOOK_TIMEOUT_SECONDS must be at least 1");
+    }
+    let failure_limit = env.parse_or("WEBHOOK_FAILURE_LIMIT", 10i32);
+    if failure_limit < 1 {
+        env.problem("WEBHOOK_FAILURE_LIMIT must be at least 1");
+    }
+
+    WebhookSettings {
+        timeout,
+        failure_limit,
+        allow_private_hosts: env.flag("WEBHOOK_ALLOW_PRIVATE_HOSTS", false),
+    }
+}
+
+fn mail_settings(env: &mut Vars) -> MailSettings {
+    let from = env.get("MAIL_FROM").unwrap_or("Wordle Helper <no-reply@localhost>").to_string();
+    if from.parse::<lettre::message::Mailbox>().is_err() {
+        env.problem(&format!("MAIL_FROM is not a valid address: {:?}", from));
+    }
+    let max_attempts = env.parse_or("MAIL_MAX_ATTEMPTS", 5i32);
+    if max_attempts < 1 {
+        env.problem("MAIL_MAX_ATTEMPTS must be at least 1");
+    }
+
+    MailSettings { from, smtp: smtp_settings(env), max_attempts }
+}
+
+fn smtp_settings(env: &mut Vars) -> Option<SmtpSettings> {
+    let host = env.get("SMTP_HOST")?.to_string();
+
+    let tls = match env.get("SMTP_TLS").unwrap_or("starttls").to_lowercase().as_str() {
+        "none" => SmtpTls::None,
+        "starttls" => SmtpTls::StartTls,
+        "tls" => SmtpTls::Implicit,
+        other => {
+            env.problem(&format!("SMTP_TLS must be none, starttls or tls, got {:?}", other));
+            SmtpTls::StartTls
+        }
+    };
+    let default_port = match tls {
+        SmtpTls::None => 25,
+        SmtpTls::StartTls => 587,
+        SmtpTls::Implicit => 465,
+    };
+    let port = env.parse_or("SMTP_PORT", default_port);
+    let credentials = match (env.get("SMTP_USERNAME"), env.get("SMTP_PASSWORD")) {
+        (Some(username), Some(password)) => Some((username.to_string(), password.to_string())),
+        (None, None) => None,
+        _ => {
+            env.problem("SMTP_USERNAME and SMTP_PASSWORD must be set together");
+            None
+        }
+    };
+    let timeout = std::time::Duration::from_secs(env.parse_or("SMTP_TIMEOUT_SECONDS", 10u64));
+    if timeout.is_zero() {
+        env.problem("SMTP_TIMEOUT_SECONDS must be at least 1");
+    }
+
+    Some(SmtpSettings { host, port, tls, credentials, timeout })
+}
+
+fn redis_url(env: &mut Vars) -> Option<String> {
+    let url = env.get("REDIS_URL")?;
+
+    #[cfg(feature = "redis")]
+    if let Err(error) = redis::Client::open(url) {
+        env.problem(&format!("REDIS_URL is invalid: {}", error));
+    }
+
+    #[cfg(not(feature = "redis"))]
+    env.problem("REDIS_URL is set but the server was built without the redis feature");
+
+    Some(url.to_string())
+}
+
+// The standard OpenTelemetry variables, so a collector's usual setup applies
+fn otlp_settings(env: &mut Vars) -> Option<OtlpSettings> {
+    let endpoint = env.get("OTEL_EXPORTER_OTLP_ENDPOINT")?.to_string();
+
+    #[cfg(feature = "otlp")]
+    match endpoint.parse::<Uri>() {
+        Ok(uri) if matches!(uri.scheme_str(), Some("http" | "https")) && uri.host().is_some() => {}
+        _ => env.problem(&format!("OTEL_EXPORTER_OTLP_ENDPOINT must be an http or https URL, got {:?}", endpoint)),
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    env.problem("OTEL_EXPORTER_OTLP_ENDPOINT is set but the server was built without the otlp feature");
+
+    Some(OtlpSettings {
+        endpoint,
+        service_name: env.get("OTEL_SERVICE_NAME").unwrap_or("wordle_solver").to_string(),
+    })
+}
+
+// COMPRESSION_ENCODINGS is a comma separated list of gzip, br and zstd, or `none`
+fn compression_encodings(env: &mut Vars) -> Vec<ContentEncoding> {
+    let mut encodings = Vec::new();
+    let names = match env.get("COMPRESSION_ENCODINGS").unwrap_or("gzip,br,zstd") {
+        "none" => Vec::new(),
+        names => list(names),
+    };
+    for name in names {
+        match name.to_lowercase().as_str() {
+            "gzip" => encodings.push(ContentEncoding::Gzip),
+            "br" => encodings.push(ContentEncoding::Brotli),
+            "zstd" => encodings.push(ContentEncoding::Zstd),
+            _ => env.problem(&format!("COMPRESSION_ENCODINGS has an unsupported encoding {:?}, expected gzip, br or zstd", name)),
+        }
+    }
+    encodings
+}
+
+// Headers the API reads or sets itself, so they're allowed or exposed whatever is configured
+const REQUIRED_CORS_HEADERS: &[&str] = &["authorization", "x-api-key"];
+const EXPOSED_CORS_HEADERS: &[&str] = &["x-request-id", "cache-status", "server-timing"];
+
+// CORS_ALLOWED_ORIGINS is a comma separated list of exact origins, or `*` to allow any
+// origin during development. `*` can't be combined with credentials, browsers would
+// then send cookies and auth headers to whichever site asked.
+fn cors_settings(env: &mut Vars) -> CorsSettings {
+    let allowed_origins = match env.get("CORS_ALLOWED_ORIGINS").unwrap_or("http://localhost:3000") {
+        "*" => None,
+        origins => Some(list(origins)),
+    };
+    for origin in allowed_origins.iter().flatten() {
+        if !is_origin(origin) {
+            env.problem(&format!("CORS_ALLOWED_ORIGINS entry {:?} must look like https://example.com, without a path", origin));
+        }
+    }
+
+    let allow_credentials = env.flag("CORS_ALLOW_CREDENTIALS", true);
+    if allowed_origins.is_none() && allow_credentials {
+        env.problem("CORS_ALLOWED_ORIGINS=* cannot be used while CORS_ALLOW_CREDENTIALS is true, list the origins or turn credentials off");
+    }
+
+    let mut allowed_methods = Vec::new();
+    for method in list(env.get("CORS_ALLOWED_METHODS").unwrap_or("GET,POST,PATCH,PUT,DELETE")) {
+        match Method::from_bytes(method.to_uppercase().as_bytes()) {
+            Ok(method) => allowed_methods.push(method),
+            Err(_) => env.problem(&format!("CORS_ALLOWED_METHODS has an invalid method {:?}", method)),
+        }
+    }
+
+    let mut allowed_headers = header_names(env, "CORS_ALLOWED_HEADERS", "content-type,accept,idempotency-key,x-request-id");
+    let mut exposed_headers = header_names(env, "CORS_EXPOSED_HEADERS", "");
+    for (headers, required) in [(&mut allowed_headers, REQUIRED_CORS_HEADERS), (&mut exposed_headers, EXPOSED_CORS_HEADERS)] {
+        for name in required {
+            let name = HeaderName::from_static(name);
+            if !headers.contains(&name) {
+                headers.push(name);
+            }
+        }
+    }
+
+    let max_age_seconds = env.parse_or("CORS_MAX_AGE_SECONDS", 3600usize);
+
+    CorsSettings {
+        allowed_origins,
+        allowed_methods,
+        allowed_headers,
+        exposed_headers,
+        allow_credentials,
+        max_age_seconds,
+    }
+}
+
+// JSON responses never need to load anything, the Swagger UI loads its own scripts and
+// styles and sets some styles inline
+const DEFAULT_CONTENT_SECURITY_POLICY: &str = "default-src 'none'; frame-ancestors 'none'";
+const DEFAULT_DOCS_CONTENT_SECURITY_POLICY: &str =
+    "default-src 'self'; script-src 'self'; style-src 'self' 'unsafe-inline'; img-src 'self' data:; frame-ancestors 'none'";
+
+// Each header value can be set to `off` to leave the header out. HSTS_MAX_AGE_SECONDS of
+// zero turns HSTS off. CONTENT_SECURITY_POLICY_ROUTES adds per-route policies as `|`
+// separated `/prefix=policy` entries, policies have `;`s and `,`s of their own.
+fn security_header_settings(env: &mut Vars) -> SecurityHeaderSettings {
+    let enabled = env.flag("SECURITY_HEADERS_ENABLED", true);
+    let frame_options = header_value(env, "X_FRAME_OPTIONS", "DENY");
+    let referrer_policy = header_value(env, "REFERRER_POLICY", "no-referrer");
+
+    let hsts_max_age = env.parse_or("HSTS_MAX_AGE_SECONDS", 31_536_000u64);
+    let hsts_subdomains = env.flag("HSTS_INCLUDE_SUBDOMAINS", false);
+    let hsts = (hsts_max_age > 0).then(|| {
+        let value = format!("max-age={}{}", hsts_max_age, if hsts_subdomains { "; includeSubDomains" } else { "" });
+        HeaderValue::from_str(&value).unwrap()
+    });
+    let trusted_proxy = env.flag("TRUSTED_PROXY", false);
+
+    let content_security_policy = header_value(env, "CONTENT_SECURITY_POLICY", DEFAULT_CONTENT_SECURITY_POLICY);
+    let mut route_policies = Vec::new();
+    if let Some(policy) = header_value(env, "DOCS_CONTENT_SECURITY_POLICY", DEFAULT_DOCS_CONTENT_SECURITY_POLICY) {
+        route_policies.push(("/docs".to_string(), policy));
+    }
+    for entry in env.get("CONTENT_SECURITY_POLICY_ROUTES").unwrap_or("").split('|').map(str::trim).filter(|entry| !entry.is_empty()) {
+        match entry.split_once('=') {
+            Some((prefix, policy)) if prefix.starts_with('/') => match HeaderValue::from_str(policy.trim()) {
+                Ok(policy) => {
+                    // A later entry for the same prefix replaces the earlier one, the docs default included
+                    route_policies.retain(|(existing, _)| existing != prefix.trim());
+                    route_policies.push((prefix.trim().to_string(), policy));
+                }
+                Err(_) => env.problem(&format!("CONTENT_SECURITY_POLICY_ROUTES has an invalid policy for {}", prefix)),
+            },
+            _ => env.problem(&format!("CONTENT_SECURITY_POLICY_ROUTES entry {:?} must look like /prefix=policy", entry)),
+        }
+    }
+    route_policies.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+
+    SecurityHeaderSettings {
+        enabled,
+        frame_options,
+        referrer_policy,
+        hsts,
+        trusted_proxy,
+        content_security_policy,
+        route_policies,
+    }
+}
+
+fn header_value(env: &mut Vars, name: &str, default: &str) -> Option<HeaderValue> {
+    match env.get(name).unwrap_or(default) {
+        "off" => None,
+        value => match HeaderValue::from_str(value) {
+            Ok(value) => Some(value),
+            Err(_) => {
+                env.problem(&format!("{} has an invalid header value {:?}", name, value));
+                None
+            }
+        },
+    }
+}
+
+// An Origin header value is only a scheme and authority, so anything with a path,
+// query or trailing slash would never match one
+fn is_origin(value: &str) -> bool {
+    match value.parse::<Uri>() {
+        Ok(uri) => match (uri.scheme_str(), uri.authority()) {
+            (Some(scheme @ ("http" | "https")), Some(authority)) => format!("{}://{}", scheme, authority) == value,
+            _ => false,
+        },
+        Err(_) => false,
+    }
+}
+
+fn header_names(env: &mut Vars, name: &str, default: &str) -> Vec<HeaderName> {
+    let mut headers = Vec::new();
+    for header in list(env.get(name).unwrap_or(default)) {
+        match HeaderName::from_bytes(header.to_lowercase().as_bytes()) {
+            Ok(header) => headers.push(header),
+            Err(_) => env.problem(&format!("{} has an invalid header name {:?}", name, header)),
+        }
+    }
+    headers
+}
+
+fn list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// A kid names one key, so a repeated one would leave which key verifies a token up to
+// the order they were listed in
+fn key_pairs(env: &mut Vars, name: &str, taken: &[(String, String)]) -> Vec<(String, String)> {
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for pair in list(env.get(name).unwrap_or_default()) {
+        match pair.split_once(':').map(|(kid, value)| (kid.trim(), value.trim())) {
+            Some((kid, value)) if !kid.is_empty() && !value.is_empty() => {
+                if pairs.iter().chain(taken).any(|(other, _)| other == kid) {
+                    env.problem(&format!("{} repeats the key id {:?}", name, kid));
+                } else {
+                    pairs.push((kid.to_string(), value.to_string()));
+                }
+            }
+            _ => env.problem(&format!("{} entries must be kid:value, got {:?}", name, pair)),
+        }
+    }
+    pairs
+}
+
+// Reads variables while collecting problems instead of stopping at the first one.
+// Empty values count as unset.
+struct Vars<'a> {
+    vars: &'a HashMap<String, String>,
+    problems: Vec<String>,
+}
+
+impl<'a> Vars<'a> {
+    fn get(&self, name: &str) -> Option<&'a str> {
+        self.vars
+            .get(name)
+            .map(|value| value.trim())
+            .filter(|value| !value.is_empty())
+    }
+
+    fn problem(&mut self, problem: &str) {
+        self.problems.push(problem.to_string());
+    }
+
+    fn required(&mut self, name: &str) -> String {
+        match self.get(name) {
+            Some(value) => value.to_string(),
+            None => {
+                self.problem(&format!("{} is required", name));
+                String::new()
+            }
+        }
+    }
+
+    fn parse<T: FromStr>(&mut self, name: &str) -> Option<T> {
+        let value = self.get(name)?;
+
+        match value.parse() {
+            Ok(parsed) => Some(parsed),
+            Err(_) => {
+                self.problem(&format!("{} has an invalid value {:?}", name, value));
+                None
+            }
+        }
+    }
+
+    fn parse_or<T: FromStr>(&mut self, name: &str, default: T) -> T {
+        self.parse(name).unwrap_or(default)
+    }
+
+    fn flag(&mut self, name: &str, default: bool) -> bool {
+        match self.get(name) {
+            None => default,
+            Some("true") | Some("1") => true,
+            Some("false") | Some("0") => false,
+            Some(other) => {
+                self.problem(&format!("{} must be true or false, got {:?}", name, other));
+                default
+            }
+        }
+    }
+}
@@ -0,0 +1,465 @@
+use actix_web::error::{JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::header::{RETRY_AFTER, WWW_AUTHENTICATE};
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use tracing::error;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::error::Category;
+use serde_json::{json, Value};
+use std::fmt;
+use utoipa::ToSchema;
+
+// What a client gets back for a failed request, always sent as
+// { "error": { "code": ..., "message": ..., "details": ... } }
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorResponse {
+    pub error: ErrorInfo,
+}
+
+// Every code an error response can carry, with the name it's sent as. Clients branch on
+// and translate from these, so a name never changes once shipped. Add a variant for a new
+// kind of failure instead of reusing one that only roughly fits.
+// `$name` is a tt rather than a literal so ToSchema sees the rename, which it misses when
+// the string comes wrapped as a literal fragment.
+macro_rules! error_codes {
+    ($($variant:ident => $name:tt,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+        pub enum ErrorCode {
+            $(#[serde(rename = $name)] $variant,)*
+        }
+
+        impl ErrorCode {
+            pub const ALL: &'static [ErrorCode] = &[$(ErrorCode::$variant,)*];
+
+            pub fn as_str(self) -> &'static str {
+                match self {
+                    $(ErrorCode::$variant => $name,)*
+                }
+            }
+        }
+    };
+}
+
+error_codes! {
+    // Request shape
+    InvalidJson => "invalid_json",
+    InvalidField => "invalid_field",
+    InvalidBody => "invalid_body",
+    InvalidContentType => "invalid_content_type",
+    InvalidCsv => "invalid_csv",
+    InvalidQuery => "invalid_query",
+    PayloadTooLarge => "payload_too_large",
+    InvalidPage => "invalid_page",
+    InvalidPageSize => "invalid_page_size",
+    InvalidCursor => "invalid_cursor",
+    InvalidIdempotencyKey => "invalid_idempotency_key",
+    IdempotencyKeyReused => "idempotency_key_reused",
+    IdempotencyRequestInProgress => "idempotency_request_in_progress",
+
+    // Authentication. 401s say what was wrong with the credentials, 403s that they were
+    // fine but don't allow the request. Unauthorized predates the specific codes and is
+    // no longer sent.
+    Unauthorized => "unauthorized",
+    MissingToken => "missing_token",
+    InvalidCredentials => "invalid_credentials",
+    InvalidToken => "invalid_token",
+    UnknownSigningKey => "unknown_signing_key",
+    TokenExpired => "token_expired",
+    TokenRevoked => "token_revoked",
+    RefreshTokenReused => "refresh_token_reused",
+    AccountSuspended => "account_suspended",
+    PasswordResetRequired => "password_reset_required",
+    Forbidden => "forbidden",
+    AdminRequired => "admin_required",
+    NotOwner => "not_owner",
+    ImpersonationReadOnly => "impersonation_read_only",
+    GuestNotAllowed => "guest_not_allowed",
+
+    // Accounts
+    UserExists => "user_exists",
+    EmailExists => "email_exists",
+    EmailUnchanged => "email_unchanged",
+    UsernameNotAllowed => "username_not_allowed",
+    PasswordReused => "password_reused",
+    SelfModeration => "self_moderation",
+    ProfilePrivate => "profile_private",
+    StaleVersion => "stale_version",
+
+    // Solver
+    InvalidLetter => "invalid_letter",
+    ConstraintContradiction => "constraint_contradiction",
+    InvalidFeedback => "invalid_feedback",
+    InvalidSample => "invalid_sample",
+    WordSyncNotConfigured => "word_sync_not_configured",
+    WordSyncFetchFailed => "word_sync_fetch_failed",
+    WordSyncInvalidList => "word_sync_invalid_list",
+    WordHistoryGone => "word_history_gone",
+    BatchTooLarge => "batch_too_large",
+    WordListUnhealthy => "word_list_unhealthy",
+
+    // Word suggestions
+    InvalidWord => "invalid_word",
+    SuggestionNoChange => "suggestion_no_change",
+    SuggestionAlreadyReviewed => "suggestion_already_reviewed",
+
+    // Game sessions
+    SessionExpired => "session_expired",
+    SessionAlreadyClaimed => "session_already_claimed",
+    SessionFinished => "session_finished",
+    GameOver => "game_over",
+    TurnTaken => "turn_taken",
+    InvalidGuessLimit => "invalid_guess_limit",
+    InvalidSessionNote => "invalid_session_note",
+    InvalidSessionTags => "invalid_session_tags",
+    InvalidDateRange => "invalid_date_range",
+
+    // Leagues
+    InvalidLeagueName => "invalid_league_name",
+    LeagueOwnerCannotLeave => "league_owner_cannot_leave",
+    DailyFinished => "daily_finished",
+    NoAnswerAvailable => "no_answer_available",
+
+    // Webhooks
+    InvalidWebhookUrl => "invalid_webhook_url",
+    UnknownWebhookEvent => "unknown_webhook_event",
+
+    // Generic
+    NotFound => "not_found",
+    AlreadyExists => "already_exists",
+    TooManyRequests => "too_many_requests",
+    RateLimitUnavailable => "rate_limit_unavailable",
+    FeatureDisabled => "feature_disabled",
+    Maintenance => "maintenance",
+    Overloaded => "overloaded",
+    QueryTimeout => "query_timeout",
+    InternalError => "internal_error",
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ErrorInfo {
+    pub code: ErrorCode,
+    pub message: String,
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<Value>,
+    // Sent as a Retry-After header instead of in the body
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
+}
+
+impl ErrorInfo {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        ErrorInfo {
+            code,
+            message: message.into(),
+            details: None,
+            retry_after: None,
+        }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = Some(details);
+        self
+    }
+
+    pub fn with_retry_after(mut self, seconds: u64) -> Self {
+        self.retry_after = Some(seconds);
+        self
+    }
+}
+
+#[derive(Debug)]
+pub enum AppError {
+    // Unexpected database failures, logged and reported as a plain 500
+    Database(sqlx::Error),
+    Validation(ErrorInfo),
+    BadRequest(ErrorInfo),
+    Unauthorized(ErrorInfo),
+    Forbidden(ErrorInfo),
+    NotFound(ErrorInfo),
+    Conflict(ErrorInfo),
+    // Existed but can't be used any more, e.g. an expired anonymous session
+    Gone(ErrorInfo),
+    TooManyRequests(ErrorInfo),
+    PayloadTooLarge(ErrorInfo),
+    // A query ran past the statement timeout and was cancelled by Postgres
+    Timeout(ErrorInfo),
+    // Switched off for now, e.g. a feature flag an admin turned off
+    Unavailable(ErrorInfo),
+    // A service we fetch from failed or sent something unusable
+    BadGateway(ErrorInfo),
+    // Anything else that isn't the client's fault. The message is logged, never sent.
+    Internal(String),
+}
+
+impl AppError {
+    pub fn validation(code: ErrorCode, message: impl Into<String>) -> Self {
+        AppError::Validation(ErrorInfo::new(code, message))
+    }
+
+    pub fn bad_request(code: ErrorCode, message: impl Into<String>) -> Self {
+        AppError::BadRequest(ErrorInfo::new(code, message))
+    }
+
+    pub fn unauthorized(code: ErrorCode, message: impl Into<String>) -> Self {
+        AppError::Unauthorized(ErrorInfo::new(code, message))
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        AppError::Forbidden(ErrorInfo::new(ErrorCode::Forbidden, message))
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        AppError::NotFound(ErrorInfo::new(ErrorCode::NotFound, message))
+    }
+
+    pub fn conflict(code: ErrorCode, message: impl Into<String>) -> Self {
+        AppError::Conflict(ErrorInfo::new(code, message))
+    }
+
+    pub fn gone(code: ErrorCode, message: impl Into<String>) -> Self {
+        AppError::Gone(ErrorInfo::new(code, message))
+    }
+
+    pub fn payload_too_large(limit: usize) -> Self {
+        AppError::PayloadTooLarge(
+            ErrorInfo::new(ErrorCode::PayloadTooLarge, format!("Request body exceeds the {} byte limit", limit))
+                .with_details(json!({ "limit": limit })),
+        )
+    }
+
+    pub fn too_many_requests(message: impl Into<String>) -> Self {
+        AppError::TooManyRequests(ErrorInfo::new(ErrorCode::TooManyRequests, message))
+    }
+
+    pub fn unavailable(code: ErrorCode, message: impl Into<String>) -> Self {
+        AppError::Unavailable(ErrorInfo::new(code, message))
+    }
+
+    pub fn bad_gateway(code: ErrorCode, message: impl Into<String>) -> Self {
+        AppError::BadGateway(ErrorInfo::new(code, message))
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        AppError::Internal(message.into())
+    }
+
+    // What's sent to the client, internal details left out
+    pub fn info(&self) -> ErrorInfo {
+        match self {
+            AppError::Database(_) | AppError::Internal(_) => ErrorInfo::new(ErrorCode::InternalError, "Internal server error"),
+            AppError::Validation(info)
+            | AppError::BadRequest(info)
+            | AppError::Unauthorized(info)
+            | AppError::Forbidden(info)
+            | AppError::NotFound(info)
+            | AppError::Conflict(info)
+            | AppError::Gone(info)
+            | AppError::TooManyRequests(info)
+            | AppError::PayloadTooLarge(info)
+            | AppError::Timeout(info)
+            | AppError::Unavailable(info)
+            | AppError::BadGateway(info) => info.clone(),
+        }
+    }
+
+    // A copy for each request sharing one computation. Database errors become internal
+    // ones, which are logged and answered the same way.
+    pub fn replicate(&self) -> AppError {
+        match self {
+            AppError::Database(error) => AppError::Internal(format!("database error: {}", error)),
+            AppError::Internal(message) => AppError::Internal(message.clone()),
+            AppError::Validation(info) => AppError::Validation(info.clone()),
+            AppError::BadRequest(info) => AppError::BadRequest(info.clone()),
+            AppError::Unauthorized(info) => AppError::Unauthorized(info.clone()),
+            AppError::Forbidden(info) => AppError::Forbidden(info.clone()),
+            AppError::NotFound(info) => AppError::NotFound(info.clone()),
+            AppError::Conflict(info) => AppError::Conflict(info.clone()),
+            AppError::Gone(info) => AppError::Gone(info.clone()),
+            AppError::TooManyRequests(info) => AppError::TooManyRequests(info.clone()),
+            AppError::PayloadTooLarge(info) => AppError::PayloadTooLarge(info.clone()),
+            AppError::Timeout(info) => AppError::Timeout(info.clone()),
+            AppError::Unavailable(info) => AppError::Unavailable(info.clone()),
+            AppError::BadGateway(info) => AppError::BadGateway(info.clone()),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Database(error) => write!(f, "database error: {}", error),
+            AppError::Internal(message) => write!(f, "internal error: {}", message),
+            _ => {
+                let info = self.info();
+                write!(f, "{}: {}", info.code, info.message)
+            }
+        }
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::Database(_) | AppError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AppError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::Gone(_) => StatusCode::GONE,
+            AppError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            AppError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            AppError::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            AppError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        // Something switched off on purpose isn't a failure worth logging
+        if self.status_code().is_server_error() && !matches!(self, AppError::Unavailable(_)) {
+            error!("{}", self);
+        }
+
+        let info = self.info();
+        let mut response = HttpResponse::build(self.status_code());
+        if let Some(seconds) = info.retry_after {
+            response.insert_header((RETRY_AFTER, seconds));
+        }
+        if let AppError::Unauthorized(info) = self {
+            response.insert_header((WWW_AUTHENTICATE, www_authenticate(info)));
+        }
+        response.json(ErrorResponse { error: info })
+    }
+}
+
+const AUTH_REALM: &str = "wordle-solver";
+
+// RFC 6750's challenge: a request without credentials is only told the scheme, one whose
+// token was turned away is also told why
+fn www_authenticate(info: &ErrorInfo) -> String {
+    match info.code {
+        ErrorCode::InvalidToken | ErrorCode::UnknownSigningKey | ErrorCode::TokenExpired | ErrorCode::TokenRevoked | ErrorCode::RefreshTokenReused => {
+            format!("Bearer realm=\"{}\", error=\"invalid_token\", error_description=\"{}\"", AUTH_REALM, info.message.replace('"', "'"))
+        }
+        _ => format!("Bearer realm=\"{}\"", AUTH_REALM),
+    }
+}
+
+// Missing rows surface as 404, unique violations as 409 and cancelled statements as 504,
+// so handlers only need to map these themselves when they want a more specific message
+impl From<sqlx::Error> for AppError {
+    fn from(error: sqlx::Error) -> Self {
+        match &error {
+            sqlx::Error::RowNotFound => AppError::not_found("Resource not found"),
+            // 23505 from Postgres, SQLITE_CONSTRAINT_UNIQUE and _PRIMARYKEY from SQLite
+            sqlx::Error::Database(db_error) if matches!(db_error.code().as_deref(), Some("23505" | "2067" | "1555")) => {
+                AppError::conflict(ErrorCode::AlreadyExists, "Resource already exists")
+            }
+            sqlx::Error::Database(db_error) if db_error.code().as_deref() == Some("57014") => {
+                AppError::Timeout(ErrorInfo::new(ErrorCode::QueryTimeout, "The request took too long and was cancelled"))
+            }
+            _ => AppError::Database(error),
+        }
+    }
+}
+
+impl From<bcrypt::BcryptError> for AppError {
+    fn from(error: bcrypt::BcryptError) -> Self {
+        AppError::Internal(format!("bcrypt: {}", error))
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for AppError {
+    fn from(_: jsonwebtoken::errors::Error) -> Self {
+        AppError::Unauthorized(ErrorInfo::new(ErrorCode::InvalidToken, "Invalid or expired token"))
+    }
+}
+
+impl From<Box<dyn std::error::Error>> for AppError {
+    fn from(error: Box<dyn std::error::Error>) -> Self {
+        AppError::Internal(error.to_string())
+    }
+}
+
+// Bodies larger than this are refused before they're parsed. Scopes whose payloads are
+// known to be small install a tighter `json_config` of their own.
+pub const DEFAULT_JSON_LIMIT: usize = 64 * 1024;
+
+// Extractor failures (malformed JSON, bad path or query parameters) would otherwise be
+// answered with actix's plain text bodies
+pub fn json_config(limit: usize) -> web::JsonConfig {
+    web::JsonConfig::default()
+        .limit(limit)
+        .error_handler(|error: JsonPayloadError, _: &HttpRequest| json_error(error).into())
+}
+
+// For endpoints where the JSON body may be left out entirely, read from web::Bytes. An
+// empty body is T's default, anything else is checked like a web::Json body would be.
+pub fn optional_json<T: DeserializeOwned + Default>(body: &[u8], limit: usize) -> Result<T, AppError> {
+    if body.len() > limit {
+        return Err(json_error(JsonPayloadError::Overflow { limit }));
+    }
+    if body.iter().all(u8::is_ascii_whitespace) {
+        return Ok(T::default());
+    }
+    serde_json::from_slice(body).map_err(|error| json_error(JsonPayloadError::Deserialize(error)))
+}
+
+fn json_error(error: JsonPayloadError) -> AppError {
+    match error {
+        JsonPayloadError::OverflowKnownLength { limit, .. } | JsonPayloadError::Overflow { limit } => AppError::payload_too_large(limit),
+        JsonPayloadError::ContentType => AppError::bad_request(ErrorCode::InvalidContentType, "Expected a JSON body"),
+        // Well formed JSON that doesn't fit the expected shape, e.g. a missing or wrong-typed field
+        JsonPayloadError::Deserialize(error) if error.classify() == Category::Data => AppError::Validation(
+            ErrorInfo::new(ErrorCode::InvalidField, strip_position(&error)).with_details(json!({
+                "field": field_name(&error),
+                "line": error.line(),
+                "column": error.column(),
+            })),
+        ),
+        JsonPayloadError::Deserialize(error) => AppError::BadRequest(
+            ErrorInfo::new(ErrorCode::InvalidJson, strip_position(&error))
+                .with_details(json!({ "line": error.line(), "column": error.column() })),
+        ),
+        error => AppError::bad_request(ErrorCode::InvalidBody, error.to_string()),
+    }
+}
+
+// serde_json only names the field for missing, unknown and duplicate fields; wrong types
+// are reported by position alone
+fn field_name(error: &serde_json::Error) -> Option<String> {
+    let message = error.to_string();
+    let (_, rest) = message.split_once("field `")?;
+    let (field, _) = rest.split_once('`')?;
+    Some(field.to_string())
+}
+
+// The position is already in details, no need to repeat it in the message
+fn strip_position(error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    match message.rfind(" at line ") {
+        Some(index) => message[..index].to_string(),
+        None => message,
+    }
+}
+
+pub fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default().error_handler(|error: QueryPayloadError, _: &HttpRequest| {
+        AppError::bad_request(ErrorCode::InvalidQuery, error.to_string()).into()
+    })
+}
+
+pub fn path_config() -> web::PathConfig {
+    web::PathConfig::default().error_handler(|error: PathError, _: &HttpRequest| {
+        AppError::not_found(error.to_string()).into()
+    })
+}
@@ -0,0 +1,442 @@
+use crate::errors::AppError;
+use crate::handlers::{examples, API_PREFIX};
+use crate::models::admin_models::{AdminSummary, EmailQuery, ComponentError, FeatureFlag, Impersonation, PlanQuery, PoolSummary, QueryPlan, UpdateFeatureFlag, UserCounts, WordListSummary};
+use crate::models::game_models::{HardestWordsQuery, ReportReplay, ReviewSuggestion, SuggestionQuery, WordStats};
+use crate::models::jobs_models::JobAccepted;
+use crate::models::users_models::PageQuery;
+use crate::repositories::{emails, features, jobs, reports, suggestions, tokens, users, word_stats, words};
+use crate::utils::audit_utils::log_auth_event;
+use crate::utils::auth_utils::{require_admin, ADMIN_ROLE};
+use crate::utils::device_utils::ClientInfo;
+use crate::utils::jwt_utils::generate_impersonation_token;
+use crate::utils::feature_utils::FEATURES;
+use crate::utils::input_utils::sanitize_pattern;
+use crate::utils::concurrency_utils::SOLVER;
+use crate::utils::pagination_utils::Pagination;
+use crate::utils::session_utils::{constraints_of, report_of, solver_output};
+use crate::utils::stats_utils::STATS_RECOMPUTE;
+use crate::utils::suggestion_utils;
+use crate::utils::word_check_utils::run_check;
+use crate::AppState;
+use actix_web::{get, post, put, web, HttpRequest, HttpResponse};
+use chrono::Duration;
+use sqlx::any::AnyKind;
+use futures_util::join;
+use serde_json::json;
+use tracing::error;
+
+// Explained when no pattern is given, anchored so an index can serve it
+const SAMPLE_PATTERN: &str = "cra__";
+
+pub fn admin_routes(conf: &mut web::ServiceConfig) {
+    let scope = web::scope("/admin")
+        .service(admin_summary)
+        .service(list_feature_flags)
+        .service(set_feature_flag)
+        .service(list_word_suggestions)
+        .service(approve_word_suggestion)
+        .service(reject_word_suggestion)
+        .service(hardest_words)
+        .service(check_word_list)
+        .service(recompute_stats)
+        .service(list_emails)
+        .service(word_filter_plan)
+        .service(replay_report)
+        .service(impersonate_user);
+
+    conf.service(scope);
+}
+
+// The queries run concurrently, each on its own pool connection. One failing leaves its
+// component null instead of failing the response.
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Service health and load, with any component that couldn't be gathered listed in errors", body = AdminSummary, example = json!(examples::admin_summary())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+    )
+)]
+#[get("/summary")]
+pub async fn admin_summary(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+
+    let now = pool.clock.now();
+    let db = &pool.db;
+
+    let (user_counts, active_sessions, word_list, pending_jobs) = join!(
+        users::user_counts(db, now - Duration::days(7)),
+        tokens::count_sessions_active_since(db, now - Duration::hours(1)),
+        async { Ok(WordListSummary { size: words::count_words(db).await?, version: words::version(db).await? }) },
+        jobs::count_queued(db),
+    );
+
+    let mut errors = Vec::new();
+    let users = component("users", user_counts, &mut errors).map(|(total, active_last_7_days, verified)| UserCounts { total, active_last_7_days, verified });
+    let active_sessions_last_hour = component("active_sessions_last_hour", active_sessions, &mut errors);
+    let word_list = component("word_list", word_list, &mut errors);
+    let pending_jobs = component("pending_jobs", pending_jobs, &mut errors);
+
+    let idle = db.num_idle() as u32;
+    let size = db.size();
+    let max_connections = pool.settings.db_max_connections;
+
+    Ok(HttpResponse::Ok().json(AdminSummary {
+        generated_at: now,
+        users,
+        active_sessions_last_hour,
+        solver_queries_today: pool.metrics.solver_queries_on(now.date_naive()),
+        word_list,
+        pending_jobs,
+        cache_hit_rates: pool.metrics.cache_hit_rates(&pool.auth_cache),
+        db_pool: PoolSummary {
+            size,
+            idle,
+            max_connections,
+            utilization: size.saturating_sub(idle) as f64 / max_connections as f64,
+        },
+        errors,
+    }))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Every feature flag and whether it's on", body = [FeatureFlag], example = json!(examples::feature_flags())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+    )
+)]
+#[get("/flags")]
+pub async fn list_feature_flags(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(pool.features.snapshot()))
+}
+
+// Takes effect here straight away and on other replicas at their next refresh. Stored, so
+// it outlives restarts and overrides the FEATURE_* default from then on.
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    params(("name" = String, Path, description = "Flag name, such as registration, or maintenance and maintenance_lockdown for the maintenance mode")),
+    request_body(content = UpdateFeatureFlag, example = json!(examples::update_feature_flag())),
+    responses(
+        (status = 200, description = "The flag as it is now", body = FeatureFlag, example = json!(examples::feature_flag())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "No flag by that name", body = ErrorResponse),
+    )
+)]
+#[put("/flags/{name}")]
+pub async fn set_feature_flag(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(String,)>, update: web::Json<UpdateFeatureFlag>) -> Result<HttpResponse, AppError> {
+    let admin = require_admin(&req, &pool).await?;
+    let (name,) = path.into_inner();
+
+    if !FEATURES.contains(&name.as_str()) {
+        return Err(AppError::not_found("No feature flag by that name"));
+    }
+
+    features::store_flag(&pool.db, &name, update.enabled, admin.id, pool.clock.now()).await?;
+    pool.features.set(&name, update.enabled);
+    log_auth_event(&pool.db, Some(admin.id), "feature_flag_changed", &format!("{} turned {}", name, if update.enabled { "on" } else { "off" })).await;
+
+    Ok(HttpResponse::Ok().json(FeatureFlag { name, enabled: update.enabled }))
+}
+
+// The review queue, most supported first
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    params(SuggestionQuery, PageQuery),
+    responses(
+        (status = 200, description = "A page of the suggestions with that status", body = WordSuggestionPage, example = json!(examples::word_suggestion_page())),
+        (status = 400, description = "status, page or per_page isn't one of the allowed values", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 422, description = "page or per_page out of range", body = ErrorResponse),
+    )
+)]
+#[get("/word-suggestions")]
+pub async fn list_word_suggestions(pool: web::Data<AppState>, req: HttpRequest, query: web::Query<SuggestionQuery>, pagination: Pagination) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+    let status = query.status.unwrap_or_default();
+
+    let items = suggestions::list(&pool.db, status, pagination.limit(), pagination.offset()).await?;
+    let total = suggestions::count(&pool.db, status).await?;
+
+    Ok(HttpResponse::Ok().json(pagination.page_of(items, total)))
+}
+
+// Highest failure rate first, ties going to the word that took more guesses to solve
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    params(HardestWordsQuery, PageQuery),
+    responses(
+        (status = 200, description = "A page of the answers played at least min_plays times", body = WordStatsPage, example = json!(examples::word_stats_page())),
+        (status = 400, description = "min_plays, page or per_page isn't a number", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 422, description = "page or per_page out of range", body = ErrorResponse),
+    )
+)]
+#[get("/hardest-words")]
+pub async fn hardest_words(pool: web::Data<AppState>, req: HttpRequest, query: web::Query<HardestWordsQuery>, pagination: Pagination) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+    let min_plays = query.min_plays.unwrap_or(1).max(1);
+
+    let items: Vec<WordStats> = word_stats::hardest(&pool.db, min_plays, pagination.limit(), pagination.offset()).await?.into_iter().map(WordStats::from).collect();
+    let total = word_stats::count(&pool.db, min_plays).await?;
+
+    Ok(HttpResponse::Ok().json(pagination.page_of(items, total)))
+}
+
+// Changes the word list as suggested, recorded as a revision like a sync is
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Suggestion id")),
+    request_body(content = ReviewSuggestion, example = json!(examples::review_suggestion())),
+    responses(
+        (status = 200, description = "The approved suggestion, with the revision it made", body = WordSuggestion, example = json!(examples::approved_word_suggestion())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "No suggestion with that id", body = ErrorResponse),
+        (status = 409, description = "Already reviewed, or the list changed that way since it was suggested", body = ErrorResponse),
+    )
+)]
+#[post("/word-suggestions/{id}/approve")]
+pub async fn approve_word_suggestion(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, review: web::Json<ReviewSuggestion>) -> Result<HttpResponse, AppError> {
+    let admin = require_admin(&req, &pool).await?;
+    let (id,) = path.into_inner();
+
+    let suggestion = suggestion_utils::approve(&pool.db, id, admin.id, review.note.as_deref(), pool.clock.now()).await?;
+    log_auth_event(&pool.db, Some(admin.id), "word_suggestion_approved", &format!("{} {} (suggestion {})", suggestion.action, suggestion.word, id)).await;
+
+    Ok(HttpResponse::Ok().json(suggestion))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Suggestion id")),
+    request_body(content = ReviewSuggestion, example = json!(examples::review_suggestion())),
+    responses(
+        (status = 200, description = "The rejected suggestion", body = WordSuggestion, example = json!(examples::rejected_word_suggestion())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "No suggestion with that id", body = ErrorResponse),
+        (status = 409, description = "Already reviewed", body = ErrorResponse),
+    )
+)]
+#[post("/word-suggestions/{id}/reject")]
+pub async fn reject_word_suggestion(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, review: web::Json<ReviewSuggestion>) -> Result<HttpResponse, AppError> {
+    let admin = require_admin(&req, &pool).await?;
+    let (id,) = path.into_inner();
+
+    let suggestion = suggestion_utils::reject(&pool.db, id, admin.id, review.note.as_deref(), pool.clock.now()).await?;
+    log_auth_event(&pool.db, Some(admin.id), "word_suggestion_rejected", &format!("{} {} (suggestion {})", suggestion.action, suggestion.word, id)).await;
+
+    Ok(HttpResponse::Ok().json(suggestion))
+}
+
+// Runs the startup integrity check again, e.g. once the list was fixed. The report replaces
+// the one readiness and the game routes go by.
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The check's report, passed is false when a critical check failed", body = WordListCheck, example = json!(examples::word_list_check())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+    )
+)]
+#[post("/word-list/check")]
+pub async fn check_word_list(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+
+    let report = run_check(&pool.db, &pool.word_check, pool.clock.now()).await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+// Rebuilds every user's stats from their sessions in the background, e.g. after a change
+// to how they're counted. The job's result lists the users whose stored stats were off.
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 202, description = "Recompute queued", body = JobAccepted, example = json!(examples::job_accepted())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+    )
+)]
+#[post("/recompute-stats")]
+pub async fn recompute_stats(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let admin = require_admin(&req, &pool).await?;
+
+    let id = jobs::enqueue(&pool.db, Some(admin.id), STATS_RECOMPUTE, &json!({}), pool.settings.job_max_attempts, pool.clock.now()).await?;
+    let status_url = format!("{}/jobs/{}", API_PREFIX, id);
+
+    Ok(HttpResponse::Accepted()
+        .insert_header(("Location", status_url.clone()))
+        .json(JobAccepted { id, status_url }))
+}
+
+// Recent mail and how sending it went, newest first. Dead letters are the ones given up on.
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    params(EmailQuery, PageQuery),
+    responses(
+        (status = 200, description = "A page of the emails with that status, or all of them", body = EmailDeliveryPage, example = json!(examples::email_delivery_page())),
+        (status = 400, description = "status, page or per_page isn't one of the allowed values", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 422, description = "page or per_page out of range", body = ErrorResponse),
+    )
+)]
+#[get("/emails")]
+pub async fn list_emails(pool: web::Data<AppState>, req: HttpRequest, query: web::Query<EmailQuery>, pagination: Pagination) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+
+    let items = emails::list(&pool.db, query.status, pagination.limit(), pagination.offset()).await?;
+    let total = emails::count(&pool.db, query.status).await?;
+
+    Ok(HttpResponse::Ok().json(pagination.page_of(items, total)))
+}
+
+// For checking the word list indexes are used, with the query general-letters runs on a
+// cache miss. On Postgres the query really runs, so the plan has actual timings.
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    params(PlanQuery),
+    responses(
+        (status = 200, description = "The database's plan for the pattern", body = QueryPlan, example = json!(examples::query_plan())),
+        (status = 400, description = "Malformed query string", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 422, description = "exact has something other than a to z and wildcards", body = ErrorResponse),
+    )
+)]
+#[get("/word-filter-plan")]
+pub async fn word_filter_plan(pool: web::Data<AppState>, req: HttpRequest, query: web::Query<PlanQuery>) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+    let pattern = sanitize_pattern("exact", query.exact.as_deref().unwrap_or(SAMPLE_PATTERN))?;
+    let kind = pool.db.any_kind();
+
+    let plan = words::explain_filter(&pool.db, kind, &pattern).await?;
+    let database = match kind {
+        AnyKind::Postgres => "postgres",
+        AnyKind::Sqlite => "sqlite",
+    };
+
+    Ok(HttpResponse::Ok().json(QueryPlan { pattern, database, plan }))
+}
+
+// The error itself goes to the log, the response only says which part is missing
+fn component<T>(name: &str, result: Result<T, sqlx::Error>, errors: &mut Vec<ComponentError>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(error) => {
+            error!("Admin summary couldn't get {}: {}", name, error);
+            let message = match error {
+                sqlx::Error::PoolTimedOut => "Timed out waiting for a database connection",
+                _ => "Database query failed",
+            };
+            errors.push(ComponentError { component: name.to_string(), message: message.to_string() });
+            None
+        }
+    }
+}
+
+// Runs a reported session's guesses again, against the word list at the report's version,
+// rebuilt from the revisions since, and against today's. `replayed` matching `original`
+// means the solver still does what the reporter saw, a difference in `current` is down
+// to the list having changed since.
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Report id")),
+    responses(
+        (status = 200, description = "The report with its replays", body = ReportReplay, example = json!(examples::report_replay())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "No such report", body = ErrorResponse),
+        (status = 503, description = "Too many solver queries running, retry after the Retry-After seconds", body = ErrorResponse),
+    )
+)]
+#[get("/reports/{id}/replay")]
+pub async fn replay_report(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+    let (id,) = path.into_inner();
+    let record = reports::find(&pool.db, id).await?.ok_or_else(|| AppError::not_found("Report not found"))?;
+    let reported_version = record.word_list_version;
+    let report = report_of(record)?;
+    let constraints = constraints_of(&report.guesses.iter().map(|guess| (guess.guess.as_str(), guess.feedback.as_str())).collect::<Vec<_>>())?;
+
+    let permit = pool.limits.acquire(SOLVER, &pool.metrics).await?;
+    let replayed = words::words_at(&pool.db, reported_version).await?.map(|then| {
+        let candidates = then.iter().filter(|word| constraints.allows(word)).cloned().collect();
+        solver_output(reported_version, candidates, &then)
+    });
+    let (version, candidates, all) = join!(words::version(&pool.db), words::filter_words(&pool.db, "%", &constraints), words::all_words(&pool.db));
+    let current = solver_output(version?, candidates?, &all?);
+    drop(permit);
+
+    Ok(HttpResponse::Ok().json(ReportReplay { report, replayed, current }))
+}
+
+// Lets support see the service as the user does. The token is stored as one of the user's
+// sessions, so they can see and revoke it, and every request made with it is audited.
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/admin",
+    security(("bearer_auth" = [])),
+    params(("user_id" = i32, Path, description = "User to act as")),
+    responses(
+        (status = 200, description = "A short-lived access token for the user", body = Impersonation, example = json!(examples::impersonation())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin, or the user is an admin", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
+    )
+)]
+#[post("/impersonate/{user_id}")]
+pub async fn impersonate_user(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let admin = require_admin(&req, &pool).await?;
+    let (user_id,) = path.into_inner();
+    let now = pool.clock.now();
+
+    let role = users::role_of(&pool.db, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("User not found"))?;
+    if role == ADMIN_ROLE {
+        return Err(AppError::forbidden("Admins cannot be impersonated"));
+    }
+
+    let mut conn = pool.db.acquire().await?;
+    let token_version = users::token_version(&mut *conn, user_id).await?;
+    let access = generate_impersonation_token(&pool.signing_keys, user_id, token_version, admin.id, &pool.settings.jwt, now)?;
+    let expires_at = now + pool.settings.jwt.impersonation_ttl;
+    tokens::insert_impersonation(&mut conn, user_id, admin.id, &access, &ClientInfo::from_request(&req), expires_at).await?;
+
+    log_auth_event(&pool.db, Some(user_id), "impersonation_started", &format!("by admin {}", admin.id)).await;
+    log_auth_event(&pool.db, Some(admin.id), "impersonating", &format!("user {}", user_id)).await;
+
+    Ok(HttpResponse::Ok().json(Impersonation { access, user_id, impersonator: admin.id, expires_at }))
+}
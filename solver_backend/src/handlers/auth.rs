@@ -0,0 +1,73 @@
+use crate::errors::{self, AppError, ErrorCode};
+use crate::handlers::examples;
+use crate::models::users_models::{Token, TokenIntrospection};
+use crate::utils::auth_utils::{get_bearer_token, has_credentials, inspect_token};
+use crate::utils::client_ip_utils::trusted_proxy;
+use crate::AppState;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use sha2::{Digest, Sha256};
+
+// A token and nothing else
+const AUTH_BODY_LIMIT: usize = 4 * 1024;
+
+pub fn auth_routes(conf: &mut web::ServiceConfig) {
+    let scope = web::scope("/auth")
+        .app_data(errors::json_config(AUTH_BODY_LIMIT))
+        .service(introspect);
+
+    conf.service(scope);
+}
+
+// For other services to check a token this backend issued, with the same revocation and
+// token version checks every route makes. Callers send INTROSPECTION_SECRET as a bearer
+// token, or come through a trusted proxy that verified their client certificate. A token
+// that isn't accepted is still a 200, with active false and the reason.
+#[utoipa::path(
+    tag = "auth",
+    context_path = "/api/v1/auth",
+    security(("bearer_auth" = [])),
+    request_body(content = Token, example = json!(examples::access_token())),
+    responses(
+        (status = 200, description = "Whether the token is accepted, and whose it is", body = TokenIntrospection, example = json!(examples::token_introspection())),
+        (status = 401, description = "The caller isn't a service allowed to introspect tokens", body = ErrorResponse),
+    )
+)]
+#[post("/introspect")]
+pub async fn introspect(pool: web::Data<AppState>, req: HttpRequest, token: web::Json<Token>) -> Result<HttpResponse, AppError> {
+    require_service(&req, &pool)?;
+
+    let introspection = match inspect_token(&token.token, &pool).await {
+        Ok((user, claims)) => TokenIntrospection {
+            active: true,
+            sub: Some(user.guest.clone().unwrap_or_else(|| user.id.to_string())),
+            role: Some(user.role),
+            exp: Some(claims.exp as i64),
+            iat: Some(claims.issued.timestamp()),
+            reason: None,
+        },
+        Err(reason) => TokenIntrospection { active: false, sub: None, role: None, exp: None, iat: None, reason: Some(reason) },
+    };
+
+    Ok(HttpResponse::Ok().json(introspection))
+}
+
+// The secret is compared by digest so the time taken says nothing about how much of it a
+// guess got right
+fn require_service(req: &HttpRequest, pool: &AppState) -> Result<(), AppError> {
+    let settings = &pool.settings.introspection;
+    if let Some((header, verified)) = &settings.mtls_header {
+        let from_proxy = req.peer_addr().is_some_and(|peer| trusted_proxy(peer.ip(), &pool.settings.proxies));
+        if from_proxy && req.headers().get(header).is_some_and(|value| value.as_bytes() == verified.as_bytes()) {
+            return Ok(());
+        }
+    }
+
+    if !has_credentials(req) {
+        return Err(AppError::unauthorized(ErrorCode::MissingToken, "A service secret is required"));
+    }
+    let sent = get_bearer_token(req).unwrap_or_default();
+    match &settings.secret {
+        Some(secret) if Sha256::digest(sent.as_bytes()) == Sha256::digest(secret.as_bytes()) => Ok(()),
+        _ => Err(AppError::unauthorized(ErrorCode::InvalidToken, "Invalid service secret")),
+    }
+}
@@ -0,0 +1,167 @@
+use crate::errors::{ErrorCode, ErrorInfo, ErrorResponse};
+use crate::handlers::{admin, auth, game, game_sessions, health, jobs, leagues, users, webhooks};
+use crate::models::admin_models::{AdminSummary, ComponentError, FeatureFlag, PoolSummary, QueryPlan, UpdateFeatureFlag, UserCounts, WordListSummary, CheckSeverity, WordListViolation, WordListCheck, EmailStatus, EmailDelivery, Impersonation};
+use crate::models::game_models::{ClaimSession, CreatedGameSession, FreePlayStats, GameSession, GuessCount, NewGameSession, NewSessionGuess, SessionStats, SessionGuess, SessionSummary, UpdateGameSession, NewSessionReport, ReportedGuess, ReportReplay, SessionReport, SolverOutput, BestGuessMode, BestGuessRequest, BestGuesses, FeedbackBatch, FeedbackBatchRequest, FeedbackPair, FeedbackResult, GuessSuggestion, CandidateDiff, CandidateDiffRequest, DiffBranch, HypotheticalFeedback, LetterMatches, NewWordSuggestion, NormalizedLetters, RequestLetters, ReviewSuggestion, SuggestionAction, SuggestionStatus, WordListChanges, WordListSync, WordStats, WordSuggestion};
+use crate::models::jobs_models::{BenchmarkRequest, JobAccepted, JobStatus};
+use crate::models::leagues_models::{JoinLeague, Leaderboard, LeaderboardEntry, League, LeagueDaily, LeagueDetails, LeagueMember, NewLeague, NewLeagueGuess};
+use crate::models::page_models::{EmailDeliveryPage, GameSessionPage, SessionPage, UserPage, WebhookDeliveryPage, WordPage, WordStatsPage, WordSuggestionPage};
+use crate::utils::answer_utils::AnswerStrategy;
+use crate::utils::feedback_utils::{Palette, Square};
+use crate::solver::Mark;
+use crate::models::webhooks_models::{NewWebhook, UpdateWebhook, Webhook, WebhookDelivery};
+use crate::models::users_models::{
+    DailyCount, EmailChange, ImportOutcome, ImportProblem, LoginCredentials, NewUser, NotificationKind, PasswordReset, PasswordResetRequest, Preferences, ProfileVisibility, Session, SortOrder,
+    SuspendUser, Token, TokenIntrospection, InactiveReason, TokenRevocation, Tokens, GuestToken, UpdatePassword, UpdatePreferences, UpdateSettings, UpdateUser, UsageReport, RouteUsage, UserImportReport,
+    UserMetrics, UserResponse,
+};
+use actix_web::web;
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use utoipa_swagger_ui::SwaggerUi;
+
+pub const OPENAPI_PATH: &str = "/api-docs/openapi.json";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Wordle Solver API"),
+    paths(
+        users::create_user,
+        users::create_guest,
+        users::get_all_users,
+        users::confirm_email,
+        users::user_metrics,
+        users::import_users,
+        users::get_user_by_id,
+        users::update_user,
+        users::patch_user,
+        users::update_user_password,
+        users::request_password_reset,
+        users::reset_password,
+        users::request_email_change,
+        users::export_user_data,
+        users::list_sessions,
+        users::revoke_session,
+        users::my_usage,
+        users::user_usage,
+        users::update_settings,
+        users::get_preferences,
+        users::update_preferences,
+        users::suspend_user,
+        users::unsuspend_user,
+        users::force_logout,
+        users::delete_user,
+        users::login_user,
+        users::revoke_token,
+        users::refresh_tokens,
+        users::check_access,
+        auth::introspect,
+        game::find_letters,
+        game::diff_candidates,
+        game::best_guess,
+        game_sessions::create_session,
+        game_sessions::session_history,
+        game_sessions::export_history,
+        game_sessions::session_stats,
+        game_sessions::user_session_stats,
+        game_sessions::get_session,
+        game_sessions::update_session,
+        game_sessions::add_guess,
+        game_sessions::report_session,
+        game_sessions::claim_session,
+        leagues::create_league,
+        leagues::list_leagues,
+        leagues::join_league,
+        leagues::get_league,
+        leagues::leave_league,
+        leagues::kick_member,
+        leagues::daily_game,
+        leagues::add_daily_guess,
+        leagues::leaderboard,
+        game::feedback_batch,
+        game::word_list,
+        game::word_list_changes,
+        game::get_word_stats,
+        game::sync_word_list,
+        game::suggest_word,
+        game::benchmark_solver,
+        jobs::get_job,
+        webhooks::create_webhook,
+        webhooks::list_webhooks,
+        webhooks::update_webhook,
+        webhooks::delete_webhook,
+        webhooks::list_deliveries,
+        webhooks::ping_webhook,
+        admin::admin_summary,
+        admin::list_feature_flags,
+        admin::set_feature_flag,
+        admin::list_word_suggestions,
+        admin::approve_word_suggestion,
+        admin::reject_word_suggestion,
+        admin::hardest_words,
+        admin::check_word_list,
+        admin::recompute_stats,
+        admin::list_emails,
+        admin::word_filter_plan,
+        admin::replay_report,
+        admin::impersonate_user,
+        health::live,
+        health::ready,
+    ),
+    components(schemas(
+        NewUser, UpdateUser, UpdatePassword, PasswordResetRequest, PasswordReset, UserResponse, UpdateSettings, Preferences, UpdatePreferences, SortOrder, ProfileVisibility, NotificationKind, EmailChange,
+        SuspendUser, LoginCredentials, Token, TokenIntrospection, InactiveReason, TokenRevocation, Tokens, GuestToken, Session, DailyCount, UserMetrics, UserImportReport, ImportProblem, ImportOutcome, RouteUsage, UsageReport,
+        RequestLetters, NormalizedLetters, LetterMatches, GameSession, SessionGuess, CreatedGameSession, NewGameSession, NewSessionGuess, UpdateGameSession, SessionSummary, NewSessionReport, ReportedGuess, SolverOutput, SessionReport, ReportReplay, GuessCount, FreePlayStats, SessionStats, ClaimSession, BestGuessRequest, BestGuessMode, GuessSuggestion, BestGuesses, HypotheticalFeedback, CandidateDiffRequest, DiffBranch, CandidateDiff, FeedbackPair, FeedbackBatchRequest, FeedbackResult, FeedbackBatch, WordListChanges, WordListSync, NewWordSuggestion, SuggestionAction, SuggestionStatus, WordSuggestion, ReviewSuggestion, WordSuggestionPage, WordStats, WordStatsPage, BenchmarkRequest, JobAccepted, JobStatus, NewLeague, JoinLeague, League, LeagueMember, LeagueDetails, NewLeagueGuess, LeagueDaily, LeaderboardEntry, Leaderboard, AnswerStrategy, Mark, Palette, Square, NewWebhook, UpdateWebhook, Webhook, WebhookDelivery, WebhookDeliveryPage, UserPage, SessionPage, GameSessionPage, WordPage,
+        AdminSummary, UserCounts, WordListSummary, PoolSummary, ComponentError, FeatureFlag, UpdateFeatureFlag, QueryPlan, CheckSeverity, WordListViolation, WordListCheck, EmailStatus, EmailDelivery, EmailDeliveryPage, Impersonation,
+        ErrorResponse, ErrorInfo, ErrorCode,
+    )),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "auth", description = "Logging in and managing tokens"),
+        (name = "users", description = "Registration and account management"),
+        (name = "me", description = "Endpoints acting on the logged in user"),
+        (name = "admin", description = "Moderation and dashboards, admin role required"),
+        (name = "game", description = "Word suggestions"),
+        (name = "leagues", description = "Private competitions on a shared daily word"),
+        (name = "jobs", description = "Background work queued by other endpoints"),
+        (name = "webhooks", description = "Signed event notifications POSTed to your own endpoints"),
+        (name = "health", description = "Probes for load balancers and orchestrators"),
+    )
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .description(Some(
+                        "Access token from /api/v1/users/login. Without one a protected route answers 401 missing_token, \
+                         and a token that isn't accepted 401 invalid_token, unknown_signing_key, token_expired or \
+                         token_revoked, both with a WWW-Authenticate challenge. An accepted token that doesn't allow the request gets 403 \
+                         admin_required, not_owner or another specific code.",
+                    ))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "X-API-Key",
+                "Key for service to service calls",
+            ))),
+        );
+    }
+}
+
+// Only mounted when API_DOCS_ENABLED is on, production turns it off
+pub fn docs_routes(conf: &mut web::ServiceConfig) {
+    conf.service(web::redirect("/docs", "/docs/"))
+        .service(SwaggerUi::new("/docs/{_:.*}").url(OPENAPI_PATH, ApiDoc::openapi()));
+}
@@ -0,0 +1,729 @@
+// Request and response examples shown in the OpenAPI doc. tests/openapi_examples.rs checks
+// each one against its schema, and replays the solver ones against the test word list
+// (crane, crate, trace, react, slate, adieu, pious) expecting exactly the documented response.
+// The solver's letters are fixture states, which tests/solver_snapshots.rs pins the ranking for.
+use crate::solver::fixtures;
+use crate::utils::feedback_utils::{squares, Palette};
+use serde_json::{json, Value};
+
+const CREATED_AT: &str = "2023-07-14T09:00:00Z";
+const UPDATED_AT: &str = "2023-07-14T09:30:00Z";
+const ACCESS_TOKEN: &str = "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9.eyJ1c2VyX2lkIjo0Mn0.access";
+const REFRESH_TOKEN: &str = "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9.eyJ1c2VyX2lkIjo0Mn0.refresh";
+
+// Users and auth
+
+pub fn new_user() -> Value {
+    json!({ "username": "wordsmith", "email": "wordsmith@example.com", "password": "correct horse battery" })
+}
+
+pub fn update_user() -> Value {
+    json!({ "username": "wordsmith", "email": "new@example.com", "version": 3 })
+}
+
+pub fn update_password() -> Value {
+    json!({ "password": "correct horse battery staple" })
+}
+
+pub fn password_reset_request() -> Value {
+    json!({ "email": "wordsmith@example.com" })
+}
+
+pub fn password_reset() -> Value {
+    json!({ "token": "q3Vb8TfKx2LmN7pRs4WdYh6JcZ9aE1uGo5XiB0vDkQnHt8Mw", "password": "correct horse battery staple" })
+}
+
+pub fn email_change() -> Value {
+    json!({ "email": "new@example.com", "password": "correct horse battery" })
+}
+
+pub fn suspend_user() -> Value {
+    json!({ "reason": "Spamming word suggestions", "until": "2023-07-21T09:00:00Z" })
+}
+
+pub fn update_settings() -> Value {
+    json!({ "notify_new_device": false })
+}
+
+pub fn update_preferences() -> Value {
+    json!({ "hard_mode": true, "sort_order": "reverse_alphabetical" })
+}
+
+pub fn preferences() -> Value {
+    json!({
+        "hard_mode": true,
+        "colorblind": false,
+        "word_length": 5,
+        "sort_order": "reverse_alphabetical",
+        "notification_opt_outs": ["new_device"],
+        "profile_visibility": "public",
+    })
+}
+
+pub fn login_credentials() -> Value {
+    json!({ "username": "wordsmith", "password": "correct horse battery" })
+}
+
+pub fn refresh_token() -> Value {
+    json!({ "token": REFRESH_TOKEN })
+}
+
+pub fn access_token() -> Value {
+    json!({ "token": ACCESS_TOKEN })
+}
+
+pub fn token_introspection() -> Value {
+    json!({ "active": true, "sub": "42", "role": "user", "exp": 1689326100, "iat": 1689325200 })
+}
+
+pub fn tokens() -> Value {
+    json!({ "access": ACCESS_TOKEN, "refresh": REFRESH_TOKEN })
+}
+
+pub fn guest_token() -> Value {
+    json!({ "access": ACCESS_TOKEN, "expires_at": "2023-07-14T10:00:00Z" })
+}
+
+pub fn token_revocation() -> Value {
+    json!({ "already_revoked": false })
+}
+
+pub fn user() -> Value {
+    json!({
+        "id": 42,
+        "username": "wordsmith",
+        "email": "wordsmith@example.com",
+        "created_at": CREATED_AT,
+        "updated_at": UPDATED_AT,
+        "version": 3,
+    })
+}
+
+pub fn stale_version() -> Value {
+    let mut current = user();
+    current["email"] = json!("elsewhere@example.com");
+    current["version"] = json!(4);
+    json!({
+        "error": {
+            "code": "stale_version",
+            "message": "The user was edited since this version, merge with the current one and retry",
+            "details": { "version": 3, "current": current },
+        }
+    })
+}
+
+pub fn user_page() -> Value {
+    page(vec![user()])
+}
+
+pub fn session_page() -> Value {
+    page(vec![
+        json!({
+            "id": 9,
+            "device_label": "Chrome on Windows",
+            "user_agent": "Mozilla/5.0 (Windows NT 10.0; Win64; x64) Chrome/115.0.0.0 Safari/537.36",
+            "ip_address": "198.51.100.4",
+            "created_at": UPDATED_AT,
+            "impersonated_by": 1,
+            "expires_at": "2023-07-14T09:45:00Z",
+        }),
+        json!({
+            "id": 7,
+            "device_label": "Firefox on Linux",
+            "user_agent": "Mozilla/5.0 (X11; Linux x86_64; rv:115.0) Gecko/20100101 Firefox/115.0",
+            "ip_address": "203.0.113.7",
+            "created_at": CREATED_AT,
+            "impersonated_by": null,
+            "expires_at": null,
+        }),
+    ])
+}
+
+pub fn impersonation() -> Value {
+    json!({
+        "access": ACCESS_TOKEN,
+        "user_id": 42,
+        "impersonator": 1,
+        "expires_at": "2023-07-14T09:45:00Z",
+    })
+}
+
+pub fn user_metrics() -> Value {
+    json!({
+        "total_users": 1280,
+        "registrations": [
+            { "day": "2023-07-13", "count": 12 },
+            { "day": "2023-07-14", "count": 9 },
+        ],
+        "active_last_7_days": 342,
+        "verified_email_percentage": 71.5,
+    })
+}
+
+pub fn user_import_csv() -> Value {
+    json!("username,email,password_hash,created_at\n\
+        wordsmith,wordsmith@example.com,$2b$12$R9h/cIPz0gi.URNNX3kh2OPST9/PgBkqquzi.Ss7KIUgO2t0jWMUW,2022-03-01T09:30:00Z\n\
+        newcomer,newcomer@example.com,,2022-11-20T18:02:11Z\n")
+}
+
+pub fn user_import_report() -> Value {
+    json!({
+        "inserted": 1480,
+        "skipped": 2,
+        "failed": 1,
+        "problems": [
+            { "row": 17, "outcome": "skipped", "reason": "Email ada@example.com is also on row 4" },
+            { "row": 203, "outcome": "failed", "reason": "created_at \"yesterday\" isn't an RFC 3339 timestamp" },
+            { "row": 911, "outcome": "skipped", "reason": "An account with this username or email already exists" },
+        ],
+    })
+}
+
+pub fn usage_report() -> Value {
+    json!({
+        "user_id": 42,
+        "since": "2023-06-15",
+        "total_calls": 61,
+        "usage": [
+            { "day": "2023-07-13", "route": "POST /api/v1/game/best-guess", "calls": 14 },
+            { "day": "2023-07-13", "route": "POST /api/v1/game/candidates", "calls": 38 },
+            { "day": "2023-07-14", "route": "GET /api/v1/users/me/usage", "calls": 1 },
+            { "day": "2023-07-14", "route": "POST /api/v1/game/candidates", "calls": 8 },
+        ],
+    })
+}
+
+// Solver
+
+fn fixture_letters(name: &str) -> Value {
+    let state = fixtures::state(name);
+    json!({ "correct": state.correct, "incorrect": state.incorrect, "exact": state.exact })
+}
+
+pub fn request_letters() -> Value {
+    fixture_letters("ends_in_e_with_r_and_a")
+}
+
+pub fn letter_matches() -> Value {
+    json!({ "items": ["crane", "crate", "trace"], "page": 1, "per_page": 20, "total": 3, "total_pages": 1 })
+}
+
+pub fn best_guess_request() -> Value {
+    json!({ "letters": fixture_letters("contains_a"), "count": 2, "explain": true })
+}
+
+pub fn best_guesses() -> Value {
+    json!({
+        "candidates": 6,
+        "mode": "fast",
+        "suggestions": [
+            {
+                "word": "crate",
+                "score": 16,
+                "explanation": "Of the 6 candidates left, CRATE leaves at most 1 and about 1.0 on average. It checks for C, R and T. It could be the answer itself.",
+            },
+            {
+                "word": "trace",
+                "score": 15,
+                "explanation": "Of the 6 candidates left, TRACE leaves at most 1 and about 1.0 on average. It checks for C, R and T. It could be the answer itself.",
+            },
+        ],
+    })
+}
+
+pub fn feedback_batch_request() -> Value {
+    json!({
+        "pairs": [
+            { "guess": "crane", "answer": "crate" },
+            { "guess": "slate", "answer": "crate" },
+            { "guess": "crane", "answer": "cranes" },
+        ],
+    })
+}
+
+pub fn feedback_batch() -> Value {
+    let marked = |guess: &str, feedback: &str| json!({ "feedback": feedback, "squares": squares(guess, feedback, Palette::Standard), "error": null });
+    json!({
+        "results": [
+            marked("crane", "gggxg"),
+            marked("slate", "xxggg"),
+            {
+                "feedback": null,
+                "squares": null,
+                "error": { "code": "invalid_word", "message": "answer must have 5 letters", "details": { "field": "answer" } },
+            },
+        ],
+    })
+}
+
+pub fn candidate_diff_request() -> Value {
+    json!({
+        "base": fixture_letters("opening"),
+        "first": { "guess": "slate", "feedback": "xxggg" },
+        "second": { "guess": "adieu", "feedback": "yxxyx" },
+    })
+}
+
+pub fn candidate_diff() -> Value {
+    json!({
+        "candidates": 7,
+        "first": {
+            "guess": "slate",
+            "feedback": "xxggg",
+            "remaining": 1,
+            "eliminated": { "items": ["crane", "react", "slate", "trace"], "page": 1, "per_page": 20, "total": 4, "total_pages": 1 },
+        },
+        "second": {
+            "guess": "adieu",
+            "feedback": "yxxyx",
+            "remaining": 5,
+            "eliminated": { "items": [], "page": 1, "per_page": 20, "total": 0, "total_pages": 0 },
+        },
+    })
+}
+
+pub fn game_session() -> Value {
+    json!({
+        "id": 12,
+        "status": "active",
+        "max_guesses": 6,
+        "anonymous": true,
+        "guesses": [session_guess("crane", "xyxxg", UPDATED_AT)],
+        "remaining": 3,
+        "expires_at": "2023-07-15T09:00:00Z",
+        "claimed_at": null,
+        "completed_at": null,
+        "created_at": CREATED_AT,
+        "note": null,
+        "tags": [],
+    })
+}
+
+pub fn claimed_game_session() -> Value {
+    let mut session = game_session();
+    session["anonymous"] = json!(false);
+    session["expires_at"] = Value::Null;
+    session["claimed_at"] = json!(UPDATED_AT);
+    session
+}
+
+pub fn created_game_session() -> Value {
+    json!({
+        "session": {
+            "id": 12,
+            "status": "active",
+            "max_guesses": 6,
+            "anonymous": true,
+            "guesses": [],
+            "remaining": 2315,
+            "expires_at": "2023-07-15T09:00:00Z",
+            "claimed_at": null,
+            "completed_at": null,
+            "created_at": CREATED_AT,
+            "note": null,
+            "tags": [],
+        },
+        "token": "Jx3k9QmT1vYp0sLw8RzA4bNc6dEf2GhU",
+    })
+}
+
+pub fn new_game_session() -> Value {
+    json!({ "max_guesses": 6 })
+}
+
+pub fn new_session_guess() -> Value {
+    json!({ "guess": "crane", "feedback": "xyxxg" })
+}
+
+pub fn update_game_session() -> Value {
+    json!({ "note": "answer had a double letter", "tags": ["double-letter", "new-opener"] })
+}
+
+pub fn new_session_report() -> Value {
+    json!({ "description": "CRATE was suggested after the T came back gray" })
+}
+
+pub fn session_report() -> Value {
+    json!({
+        "id": 3,
+        "session_id": 12,
+        "user_id": 7,
+        "description": "CRATE was suggested after the T came back gray",
+        "guesses": [{ "guess": "pious", "feedback": "xxxxx" }],
+        "original": {
+            "word_list_version": 41,
+            "candidate_count": 4,
+            "candidates": ["crane", "crate", "react", "trace"],
+            "suggestions": [{ "word": "crate", "score": 4 }, { "word": "react", "score": 4 }],
+        },
+        "created_at": CREATED_AT,
+    })
+}
+
+pub fn report_replay() -> Value {
+    json!({
+        "report": session_report(),
+        "replayed": session_report()["original"],
+        "current": {
+            "word_list_version": 42,
+            "candidate_count": 3,
+            "candidates": ["crane", "react", "trace"],
+            "suggestions": [{ "word": "crane", "score": 4 }, { "word": "react", "score": 4 }],
+        },
+    })
+}
+
+pub fn history_csv() -> Value {
+    json!("session_id,date,turn,guess,pattern,solved,duration_seconds,note\r\n\
+        17,2023-07-20 09:00:05,1,crane,yggxg,true,95,\"started with crane, again\"\r\n\
+        17,2023-07-20 09:01:40,2,trace,ggggg,true,95,\"started with crane, again\"\r\n")
+}
+
+pub fn game_session_page() -> Value {
+    json!({
+        "items": [
+            {
+                "id": 12,
+                "status": "solved",
+                "max_guesses": 6,
+                "guesses": 4,
+                "note": "answer had a double letter",
+                "tags": ["double-letter", "new-opener"],
+                "completed_at": UPDATED_AT,
+                "created_at": CREATED_AT,
+            },
+        ],
+        "page": 1,
+        "per_page": 20,
+        "total": 1,
+        "total_pages": 1,
+    })
+}
+
+pub fn claim_session() -> Value {
+    json!({ "token": "Jx3k9QmT1vYp0sLw8RzA4bNc6dEf2GhU" })
+}
+
+pub fn session_stats() -> Value {
+    json!({
+        "played": 24,
+        "solved": 22,
+        "lost": 1,
+        "distribution": [
+            { "guesses": 1, "count": 0 },
+            { "guesses": 2, "count": 2 },
+            { "guesses": 3, "count": 8 },
+            { "guesses": 4, "count": 9 },
+            { "guesses": 5, "count": 2 },
+            { "guesses": 6, "count": 1 },
+        ],
+        "free_play": { "played": 3, "solved": 3 },
+    })
+}
+
+// Word list
+
+pub fn word_list() -> Value {
+    json!(["adieu", "crane", "crate", "pious", "react", "slate", "trace"])
+}
+
+pub fn word_list_changes() -> Value {
+    json!({ "since_version": 6, "version": 8, "added": ["plumb"], "removed": ["pious"] })
+}
+
+pub fn word_list_sync() -> Value {
+    json!({ "status": "updated", "added": 14, "removed": 2, "word_count": 12984, "version": 8, "revision": 5 })
+}
+
+pub fn word_stats() -> Value {
+    json!({ "word": "crane", "plays": 40, "failures": 2, "average_guesses": 3.5, "failure_rate": 0.05 })
+}
+
+pub fn word_stats_page() -> Value {
+    page(vec![json!({ "word": "jazzy", "plays": 12, "failures": 5, "average_guesses": 5.25, "failure_rate": 0.4167 }), word_stats()])
+}
+
+pub fn new_word_suggestion() -> Value {
+    json!({ "word": "quoll", "action": "add", "reason": "An Australian marsupial, fair game as a guess" })
+}
+
+pub fn word_suggestion() -> Value {
+    json!({
+        "id": 3,
+        "word": "quoll",
+        "action": "add",
+        "status": "pending",
+        "suggested_by": 42,
+        "reason": "An Australian marsupial, fair game as a guess",
+        "supporters": 2,
+        "reviewed_by": null,
+        "reviewed_at": null,
+        "review_note": null,
+        "revision_id": null,
+        "created_at": CREATED_AT,
+    })
+}
+
+pub fn approved_word_suggestion() -> Value {
+    json!({
+        "id": 3,
+        "word": "quoll",
+        "action": "add",
+        "status": "approved",
+        "suggested_by": 42,
+        "reason": "An Australian marsupial, fair game as a guess",
+        "supporters": 2,
+        "reviewed_by": 1,
+        "reviewed_at": UPDATED_AT,
+        "review_note": "Fine by the dictionary",
+        "revision_id": 6,
+        "created_at": CREATED_AT,
+    })
+}
+
+pub fn rejected_word_suggestion() -> Value {
+    let mut suggestion = approved_word_suggestion();
+    suggestion["status"] = json!("rejected");
+    suggestion["review_note"] = json!("Too obscure for the answer list");
+    suggestion["revision_id"] = Value::Null;
+    suggestion
+}
+
+pub fn word_suggestion_page() -> Value {
+    page(vec![word_suggestion()])
+}
+
+pub fn review_suggestion() -> Value {
+    json!({ "note": "Fine by the dictionary" })
+}
+
+// Jobs
+
+pub fn benchmark_request() -> Value {
+    json!({ "sample": 100 })
+}
+
+pub fn job_accepted() -> Value {
+    json!({ "id": 9, "status_url": "/api/v1/jobs/9" })
+}
+
+pub fn job_status() -> Value {
+    json!({
+        "id": 9,
+        "job_type": "solver_benchmark",
+        "status": "succeeded",
+        "attempts": 1,
+        "result": { "games": 100, "solved": 98, "average_guesses": 3.71, "max_guesses": 6, "unsolved": ["jazzy", "fuzzy"], "elapsed_ms": 8412 },
+        "error": null,
+        "created_at": CREATED_AT,
+        "finished_at": UPDATED_AT,
+    })
+}
+
+// Webhooks
+
+pub fn new_webhook() -> Value {
+    json!({ "url": "https://hooks.example.com/wordle", "secret": "a long shared secret", "events": ["daily_completed", "streak_milestone"] })
+}
+
+pub fn update_webhook() -> Value {
+    json!({ "events": ["achievement_earned"], "enabled": true })
+}
+
+pub fn webhook() -> Value {
+    json!({
+        "id": 4,
+        "url": "https://hooks.example.com/wordle",
+        "events": ["daily_completed", "streak_milestone"],
+        "all_users": false,
+        "enabled": true,
+        "consecutive_failures": 0,
+        "disabled_at": null,
+        "created_at": CREATED_AT,
+        "updated_at": CREATED_AT,
+    })
+}
+
+pub fn webhooks() -> Value {
+    json!([webhook()])
+}
+
+pub fn webhook_delivery() -> Value {
+    json!({
+        "id": 31,
+        "webhook_id": 4,
+        "event": "ping",
+        "status": "pending",
+        "attempts": 0,
+        "response_status": null,
+        "error": null,
+        "created_at": CREATED_AT,
+        "last_attempt_at": null,
+    })
+}
+
+pub fn webhook_delivery_page() -> Value {
+    let mut delivered = webhook_delivery();
+    delivered["status"] = json!("delivered");
+    delivered["attempts"] = json!(1);
+    delivered["response_status"] = json!(204);
+    delivered["last_attempt_at"] = json!(CREATED_AT);
+    page(vec![delivered])
+}
+
+pub fn new_league() -> Value {
+    json!({ "name": "Third floor", "answer_strategy": "no_repeat" })
+}
+
+pub fn join_league() -> Value {
+    json!({ "invite_code": "k3Zq8vRb2M" })
+}
+
+pub fn league() -> Value {
+    json!({
+        "id": 2,
+        "name": "Third floor",
+        "owner_id": 7,
+        "invite_code": "k3Zq8vRb2M",
+        "answer_strategy": "no_repeat",
+        "created_at": CREATED_AT,
+    })
+}
+
+pub fn leagues() -> Value {
+    json!([league()])
+}
+
+pub fn league_details() -> Value {
+    json!({
+        "league": league(),
+        "members": [
+            { "user_id": 7, "username": "tester", "joined_at": CREATED_AT },
+            { "user_id": 9, "username": "referee", "joined_at": "2023-07-15T08:30:00Z" },
+        ],
+    })
+}
+
+pub fn new_league_guess() -> Value {
+    json!({ "guess": "crane" })
+}
+
+pub fn league_daily() -> Value {
+    json!({
+        "league_id": 2,
+        "day": "2023-07-14",
+        "status": "solved",
+        "max_guesses": 6,
+        "guesses": [
+            session_guess("slate", "xxgxg", CREATED_AT),
+            session_guess("crane", "gggxg", "2023-07-14T09:01:00Z"),
+            session_guess("crate", "ggggg", "2023-07-14T09:02:00Z"),
+        ],
+        "answer": "crate",
+    })
+}
+
+pub fn leaderboard() -> Value {
+    json!({
+        "league_id": 2,
+        "day": "2023-07-14",
+        "entries": [
+            { "user_id": 9, "username": "referee", "guesses": 2, "solved": true, "day_points": 5, "total_points": 9, "days_played": 2 },
+            { "user_id": 7, "username": "tester", "guesses": 3, "solved": true, "day_points": 4, "total_points": 4, "days_played": 1 },
+        ],
+    })
+}
+
+// Admin
+
+pub fn admin_summary() -> Value {
+    json!({
+        "generated_at": CREATED_AT,
+        "users": { "total": 1280, "active_last_7_days": 342, "verified": 915 },
+        "active_sessions_last_hour": 57,
+        "solver_queries_today": 4810,
+        "word_list": { "size": 12984, "version": 8 },
+        "pending_jobs": 0,
+        "cache_hit_rates": { "token_versions": 0.97 },
+        "db_pool": { "size": 5, "idle": 4, "max_connections": 10, "utilization": 0.1 },
+        "errors": [],
+    })
+}
+
+pub fn feature_flags() -> Value {
+    json!([{ "name": "registration", "enabled": true }, { "name": "benchmarks", "enabled": false }, { "name": "maintenance", "enabled": false }])
+}
+
+pub fn update_feature_flag() -> Value {
+    json!({ "enabled": false })
+}
+
+pub fn feature_flag() -> Value {
+    json!({ "name": "benchmarks", "enabled": false })
+}
+
+pub fn query_plan() -> Value {
+    json!({
+        "pattern": "cra__",
+        "database": "postgres",
+        "plan": [
+            "Index Only Scan using word_list_word_pattern on word_list  (cost=0.29..8.31 rows=1 width=6) (actual time=0.009..0.010 rows=1 loops=1)",
+            "  Index Cond: ((word ~>=~ 'cra'::text) AND (word ~<~ 'crb'::text))",
+            "  Filter: (((word)::text ~~ 'cra__'::text) AND (length((word)::text) = 5))",
+            "  Heap Fetches: 1",
+            "Planning Time: 0.242 ms",
+            "Execution Time: 0.020 ms",
+        ],
+    })
+}
+
+pub fn word_list_check() -> Value {
+    json!({
+        "checked_at": "2023-08-07T06:00:02Z",
+        "version": 8,
+        "words": 12986,
+        "answers": 2309,
+        "passed": false,
+        "violations": [
+            { "check": "wrong_length", "severity": "critical", "message": "1 words don't have 5 letters", "count": 1, "examples": ["cranes"] },
+            { "check": "duplicate", "severity": "warning", "message": "2 rows repeat a word listed before", "count": 2, "examples": ["crane", "slate"] },
+        ],
+    })
+}
+
+pub fn email_delivery_page() -> Value {
+    page(vec![
+        json!({
+            "id": 12,
+            "template": "new_device_login",
+            "recipient": "tester@example.com",
+            "subject": "New login to your account",
+            "status": "retrying",
+            "attempts": 1,
+            "error": "Connection error: Connection refused (os error 111)",
+            "created_at": CREATED_AT,
+            "last_attempt_at": CREATED_AT,
+            "sent_at": null,
+        }),
+        json!({
+            "id": 11,
+            "template": "confirm_email",
+            "recipient": "new@example.com",
+            "subject": "Confirm your new email address",
+            "status": "sent",
+            "attempts": 1,
+            "error": null,
+            "created_at": CREATED_AT,
+            "last_attempt_at": CREATED_AT,
+            "sent_at": CREATED_AT,
+        }),
+    ])
+}
+
+fn session_guess(guess: &str, feedback: &str, created_at: &str) -> Value {
+    json!({ "guess": guess, "feedback": feedback, "squares": squares(guess, feedback, Palette::Standard), "created_at": created_at })
+}
+
+fn page(items: Vec<Value>) -> Value {
+    json!({ "items": items, "page": 1, "per_page": 20, "total": items.len(), "total_pages": 1 })
+}
@@ -1,57 +1,584 @@
-use crate::models::game_models::RequestLetters;
+use crate::models::game_models::{BestGuessMode, BestGuessRequest, BestGuesses, CandidateDiff, CandidateDiffRequest, DetailQuery, DiffBranch, FeedbackBatch, FeedbackBatchRequest, FeedbackResult, GuessSuggestion, HypotheticalFeedback, LetterMatches, NewWordSuggestion, NormalizedLetters, RequestLetters, SampleQuery, WordChangesQuery, WordStats};
+use crate::models::users_models::SortOrder;
+use crate::models::users_models::PageQuery;
+use crate::models::jobs_models::{BenchmarkRequest, JobAccepted};
+use crate::repositories::{jobs, suggestions, users, word_stats, words};
+use crate::utils::job_utils::{DEFAULT_BENCHMARK_SAMPLE, MAX_BENCHMARK_SAMPLE, SOLVER_BENCHMARK};
+use crate::errors::{self, AppError, ErrorCode, ErrorInfo};
+use crate::handlers::{examples, API_PREFIX};
 use crate::AppState;
-use actix_web::{post, web, HttpResponse, HttpRequest};
-use crate::utils::jwt_utils::verify_token;
+use actix_web::{get, post, web, HttpResponse, HttpRequest};
+use serde_json::json;
+use crate::middleware::rate_limit::RateLimit;
+use crate::middleware::server_timing::RequestTimings;
+use crate::utils::audit_utils::log_auth_event;
+use crate::utils::auth_utils::{require_admin, require_user};
+use crate::utils::concurrency_utils::SOLVER;
+use crate::utils::input_utils::{sanitize_letters, sanitize_request};
+use crate::utils::suggestion_utils::unchanged_by;
+use crate::utils::word_sync_utils::{sync_words, WORD_LENGTH};
+use crate::utils::feature_utils::BENCHMARKS;
+use crate::solver::{explain, feedback, feedback_pattern, parse_feedback, suggest, suggest_exact, Constraints, FeedbackError, Mark};
+use crate::utils::etag_utils::{conditional, private_cache, versioned_tag};
+use crate::utils::feedback_utils::{palette_for, squares, PaletteQuery};
+use crate::utils::pagination_utils::Pagination;
+use crate::repositories::words::pick_sample;
+use crate::utils::tracing_utils::SpanTimer;
+use std::collections::BTreeSet;
+use tracing::{instrument, Span};
+
+// Three short letter patterns
+const LETTERS_BODY_LIMIT: usize = 1024;
+// Longer reasons are refused rather than cut
+const MAX_SUGGESTION_REASON_LENGTH: usize = 500;
+// Suggestions given when the request doesn't say, and the most it may ask for
+const DEFAULT_SUGGESTIONS: usize = 5;
+const MAX_SUGGESTIONS: usize = 20;
+// Clients revalidate after this, the list only changes when an admin edits it
+const WORD_LIST_MAX_AGE_SECONDS: u32 = 300;
+
+// Under /game, so mounted before the game scope would claim the path. A full batch is
+// far over LETTERS_BODY_LIMIT.
+pub fn feedback_routes(conf: &mut web::ServiceConfig) {
+    let scope = web::scope("/game/feedback")
+        .app_data(errors::json_config(errors::DEFAULT_JSON_LIMIT))
+        .service(feedback_batch);
+
+    conf.service(scope);
+}
 
 pub fn game_routes(conf: &mut web::ServiceConfig) {
     let scope = web::scope("/game")
+        .app_data(errors::json_config(LETTERS_BODY_LIMIT))
 //      .wrap(Auth)
-        .service(find_letters);
+        .service(find_letters)
+        .service(diff_candidates)
+        .service(best_guess)
+        .service(word_list)
+        .service(word_list_changes)
+        .service(get_word_stats)
+        .service(sync_word_list)
+        .service(suggest_word)
+        .service(benchmark_solver);
 
     conf.service(scope);
 }
 
-pub fn get_bearer_token(req: &HttpRequest) -> Option<String> {
-    req.headers()
-        .get("Authorization")
-        .and_then(|header| {
-            let header_value = header.to_str().ok()?;
-            if header_value.starts_with("Bearer ") {
-                Some(header_value.trim_start_matches("Bearer ").to_string())
-            } else {
-                None
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game",
+    security(("bearer_auth" = [])),
+    request_body(content = RequestLetters, example = json!(examples::request_letters())),
+    params(PageQuery, DetailQuery, SampleQuery),
+    responses(
+        (status = 200, description = "A page of the words matching the letters, or a random sample of them when sample is set, with the letters as read when detailed is set", body = LetterMatches, example = json!(examples::letter_matches())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 413, description = "Body larger than 1 KiB", body = ErrorResponse),
+        (status = 422, description = "A field is missing or has the wrong type, has something other than a to z, a letter is both correct and incorrect, or the page is out of range", body = ErrorResponse),
+        (status = 503, description = "Too many solver queries running, retry after the Retry-After seconds", body = ErrorResponse),
+    )
+)]
+#[post("/candidates", wrap = "RateLimit::new(SOLVER)")]
+#[instrument(skip_all, fields(user_id, cache_hit, candidate_count, duration_ms))]
+pub async fn find_letters(pool: web::Data<AppState>, req: HttpRequest, letters: web::Json<RequestLetters>, query: web::Query<DetailQuery>, sampling: web::Query<SampleQuery>, pagination: Pagination) -> Result<HttpResponse, AppError> {
+    let timings = RequestTimings::of(&req);
+    let _timer = SpanTimer::start();
+    let user = timings.measure("auth", require_user(&req, &pool)).await?;
+    Span::current().record("user_id", user.id);
+    let letters = sanitize_request(letters.into_inner())?;
+    if let Some(letter) = letters.contradiction() {
+        return Err(AppError::Validation(contradiction(letter)));
+    }
+    pool.metrics.count_solver_query(pool.clock.now().date_naive());
+    let sort_order = timings.measure("db", sort_order_for(&pool, user.id, letters.sort_order)).await?;
+    let seed = sampling.sample.unwrap_or(false).then(|| sampling.seed.unwrap_or_else(rand::random));
+    let sample_size = pagination.per_page as usize;
+    // A sample is always the first page of itself, with `total` counting every match
+    let respond = |mut words: Vec<String>, total: usize| {
+        sort_order.sort(&mut words);
+        let normalized = query.detailed.unwrap_or(false).then(|| NormalizedLetters::from(&letters));
+        let words = match seed {
+            Some(_) => Pagination { page: 1, per_page: pagination.per_page }.page_of(words, total as i64),
+            None => pagination.slice(words),
+        };
+        HttpResponse::Ok().json(LetterMatches { words, normalized, seed })
+    };
+    let respond_all = |words: Vec<String>| {
+        let total = words.len();
+        match seed {
+            Some(seed) => respond(pick_sample(words, sample_size, seed), total),
+            None => respond(words, total),
+        }
+    };
+
+    // The whole candidate list is cached, each page is cut from it. Keyed by the list
+    // version too, so results from before a change to the list aren't served after it.
+    let version = timings.measure("db", pool.words.version()).await?;
+    let cache_key = format!("general_letters:{}:{}", version, json!([letters.exact, letters.correct, letters.incorrect]));
+
+    let cached = timings.measure("cache", pool.stores.cached_result(&cache_key)).await;
+    pool.metrics.observe_cache_lookup("results", cached.is_some());
+    Span::current().record("cache_hit", cached.is_some());
+    if let Some(cached) = cached {
+        if let Ok(words) = serde_json::from_str::<Vec<String>>(&cached) {
+            pool.metrics.observe_candidates("general_letters", words.len());
+            Span::current().record("candidate_count", words.len());
+            return Ok(respond_all(words));
+        }
+    }
+
+    // Only cache misses do the heavy part and take a slot
+    let permit = timings.measure("queue", pool.limits.acquire(SOLVER, &pool.metrics)).await?;
+    // Sampled by the repository, which can avoid loading every match. Nothing is cached
+    // since the full list never arrives.
+    if let Some(seed) = seed {
+        let (words, total) = timings.measure("db", pool.words.sample_words(&letters.exact, &letters.constraints(), sample_size, seed)).await?;
+        drop(permit);
+        pool.metrics.observe_candidates("general_letters", total);
+        Span::current().record("candidate_count", total);
+        return Ok(respond(words, total));
+    }
+    let words = timings.measure("db", pool.words.filter_words(&letters.exact, &letters.constraints())).await?;
+    drop(permit);
+    pool.metrics.observe_candidates("general_letters", words.len());
+    Span::current().record("candidate_count", words.len());
+
+    if let Ok(serialized) = serde_json::to_string(&words) {
+        timings.measure("cache", pool.stores.cache_result(&cache_key, &serialized)).await;
+    }
+    Ok(respond_all(words))
+}
+
+// Ranks every word in the list as the next guess for the words the letters still allow,
+// with `solver::suggest`, the same scoring as the CLI's, or by entropy with
+// `solver::suggest_exact` in exact mode, see BestGuessMode. The list, the feedback patterns
+// explanations and exact ranking are made of and the opening ranking come from the solver
+// cache.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game",
+    security(("bearer_auth" = [])),
+    request_body(content = BestGuessRequest, example = json!(examples::best_guess_request())),
+    responses(
+        (status = 200, description = "The best guesses, best first, none when no word is left", body = BestGuesses, example = json!(examples::best_guesses())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 413, description = "Body larger than 1 KiB", body = ErrorResponse),
+        (status = 422, description = "Invalid letters, a letter both correct and incorrect, or count outside 1 to 20", body = ErrorResponse),
+        (status = 503, description = "Too many solver queries running, retry after the Retry-After seconds", body = ErrorResponse),
+        (status = 504, description = "Working out the suggestions took longer than the server allows", body = ErrorResponse),
+    )
+)]
+#[post("/best-guess", wrap = "RateLimit::new(SOLVER)")]
+pub async fn best_guess(pool: web::Data<AppState>, req: HttpRequest, request: web::Json<BestGuessRequest>) -> Result<HttpResponse, AppError> {
+    require_user(&req, &pool).await?;
+    let request = request.into_inner();
+    let letters = sanitize_request(request.letters)?;
+    if let Some(letter) = letters.contradiction() {
+        return Err(AppError::Validation(contradiction(letter)));
+    }
+    let count = request.count.unwrap_or(DEFAULT_SUGGESTIONS);
+    if !(1..=MAX_SUGGESTIONS).contains(&count) {
+        return Err(AppError::validation(ErrorCode::InvalidField, format!("count must be between 1 and {}", MAX_SUGGESTIONS)));
+    }
+    pool.metrics.count_solver_query(pool.clock.now().date_naive());
+
+    // Requests for the same letters while one is being worked out, as when a new puzzle
+    // comes out, wait for its result instead of taking a slot of their own
+    let key = format!("best_guess:{}", json!([letters.exact, distinct(&letters.correct), distinct(&letters.incorrect), count, request.explain, request.mode]));
+    let (words, limits, metrics, cache) = (pool.words.clone(), pool.limits.clone(), pool.metrics.clone(), pool.solver_cache.clone());
+    let (threshold, ceiling) = (pool.settings.exact_threshold, pool.settings.exact_ceiling);
+    let best = pool
+        .best_guesses
+        .run(key, move || async move {
+            let _permit = limits.acquire(SOLVER, &metrics).await?;
+            let data = cache.current(words.as_ref()).await?;
+            let guesses: Vec<&str> = data.guesses.iter().map(String::as_str).collect();
+            let suggest_with = |word: &str, score: usize, candidates: &[&str]| GuessSuggestion {
+                word: word.to_string(),
+                score,
+                explanation: request.explain.then(|| explain(word, &data.outlook(word, candidates))),
+            };
+
+            // Nothing known yet: every guess is a candidate, ranked fast when the cache was built
+            let blank = letters.correct.is_empty() && letters.incorrect.is_empty() && letters.exact.len() == WORD_LENGTH && letters.exact.chars().all(|letter| letter == '_');
+            if blank && request.mode.resolve(guesses.len(), threshold, ceiling) == BestGuessMode::Fast {
+                let suggestions = data.starters.iter().take(count).map(|(word, score)| suggest_with(word, *score, &guesses)).collect();
+                return Ok(BestGuesses { candidates: guesses.len(), mode: BestGuessMode::Fast, suggestions });
+            }
+
+            let candidates = words.filter_words(&letters.exact, &letters.constraints()).await?;
+            let candidates: Vec<&str> = candidates.iter().map(String::as_str).collect();
+            let mode = request.mode.resolve(candidates.len(), threshold, ceiling);
+            let suggestions = match mode {
+                _ if candidates.is_empty() => Vec::new(),
+                BestGuessMode::Exact => suggest_exact(&candidates, &guesses, count, |guess, candidate| data.pattern(guess, candidate)),
+                _ => suggest(&candidates, &guesses, count),
+            };
+            let suggestions = suggestions.into_iter().map(|suggestion| suggest_with(suggestion.word, suggestion.score, &candidates)).collect();
+            Ok(BestGuesses { candidates: candidates.len(), mode, suggestions })
+        })
+        .await?;
+    pool.metrics.observe_candidates("best_guess", best.candidates);
+
+    Ok(HttpResponse::Ok().json(&*best))
+}
+
+// The letters in `letters` once each, in order, so the same set makes the same key
+fn distinct(letters: &str) -> String {
+    letters.chars().collect::<BTreeSet<char>>().into_iter().collect()
+}
+
+// Compares two feedbacks the next guess might get, given what's known so far: how many
+// candidates each leaves, and which words only one of them would rule out. Both pages
+// follow the same `page` and `per_page`.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game",
+    security(("bearer_auth" = [])),
+    request_body(content = CandidateDiffRequest, example = json!(examples::candidate_diff_request())),
+    params(PageQuery),
+    responses(
+        (status = 200, description = "What each feedback would leave and rule out", body = CandidateDiff, example = json!(examples::candidate_diff())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 413, description = "Body larger than 1 KiB", body = ErrorResponse),
+        (status = 422, description = "Invalid letters, a guess that isn't five letters, feedback that isn't one of g, y or x per letter, or feedback at odds with the base letters. details.branch says which part of the body is at fault.", body = ErrorResponse),
+        (status = 503, description = "Too many solver queries running, retry after the Retry-After seconds", body = ErrorResponse),
+    )
+)]
+#[post("/diff", wrap = "RateLimit::new(SOLVER)")]
+pub async fn diff_candidates(pool: web::Data<AppState>, req: HttpRequest, diff: web::Json<CandidateDiffRequest>, pagination: Pagination) -> Result<HttpResponse, AppError> {
+    let user = require_user(&req, &pool).await?;
+    let diff = diff.into_inner();
+    let base = sanitize_request(diff.base).map_err(|error| in_branch(error, "base"))?;
+    if let Some(letter) = base.contradiction() {
+        return Err(in_branch(AppError::Validation(contradiction(letter)), "base"));
+    }
+    let (first_guess, first_feedback, first) = hypothesis(&base, diff.first).map_err(|error| in_branch(error, "first"))?;
+    let (second_guess, second_feedback, second) = hypothesis(&base, diff.second).map_err(|error| in_branch(error, "second"))?;
+    pool.metrics.count_solver_query(pool.clock.now().date_naive());
+    let sort_order = sort_order_for(&pool, user.id, base.sort_order).await?;
+
+    let permit = pool.limits.acquire(SOLVER, &pool.metrics).await?;
+    let candidates = pool.words.filter_words(&base.exact, &base.constraints()).await?;
+    drop(permit);
+    pool.metrics.observe_candidates("diff", candidates.len());
+
+    let kept: Vec<(bool, bool)> = candidates.iter().map(|word| (first.allows(word), second.allows(word))).collect();
+    let eliminated_only = |by_first: bool| {
+        let mut words: Vec<String> = candidates
+            .iter()
+            .zip(&kept)
+            .filter(|(_, &(first, second))| if by_first { !first && second } else { first && !second })
+            .map(|(word, _)| word.clone())
+            .collect();
+        sort_order.sort(&mut words);
+        pagination.slice(words)
+    };
+
+    Ok(HttpResponse::Ok().json(CandidateDiff {
+        candidates: candidates.len(),
+        first: DiffBranch {
+            guess: first_guess,
+            feedback: first_feedback,
+            remaining: kept.iter().filter(|(first, _)| *first).count(),
+            eliminated: eliminated_only(true),
+        },
+        second: DiffBranch {
+            guess: second_guess,
+            feedback: second_feedback,
+            remaining: kept.iter().filter(|(_, second)| *second).count(),
+            eliminated: eliminated_only(false),
+        },
+    }))
+}
+
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game",
+    security(("bearer_auth" = [])),
+    params(
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previously fetched list"),
+    ),
+    responses(
+        (status = 200, description = "Every word the solver knows, alphabetically", body = [String], example = json!(examples::word_list())),
+        (status = 304, description = "The list hasn't changed since the given ETag"),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    )
+)]
+#[get("/words")]
+pub async fn word_list(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    require_user(&req, &pool).await?;
+
+    let version = pool.words.version().await?;
+
+    conditional(&req, versioned_tag("words", version), private_cache(WORD_LIST_MAX_AGE_SECONDS), || async {
+        let words = pool.words.all_words().await?;
+        Ok(HttpResponse::Ok().json(words))
+    })
+    .await
+}
+
+// For clients keeping their own copy of /game/words. The ETag names both versions, so a
+// client asking again before the list changes gets a 304.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game",
+    security(("bearer_auth" = [])),
+    params(
+        WordChangesQuery,
+        ("If-None-Match" = Option<String>, Header, description = "ETag of a previous answer for the same since_version"),
+    ),
+    responses(
+        (status = 200, description = "The words added and removed since the given version", body = WordListChanges, example = json!(examples::word_list_changes())),
+        (status = 304, description = "The list hasn't changed since the given ETag"),
+        (status = 400, description = "since_version is missing or not a number", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 410, description = "The changes since that version are no longer kept, fetch /game/words in full", body = ErrorResponse),
+    )
+)]
+#[get("/words/changes")]
+pub async fn word_list_changes(pool: web::Data<AppState>, req: HttpRequest, query: web::Query<WordChangesQuery>) -> Result<HttpResponse, AppError> {
+    require_user(&req, &pool).await?;
+    let since = query.since_version;
+
+    let version = words::version(&pool.db).await?;
+
+    conditional(&req, versioned_tag(&format!("words-since-{}", since), version), private_cache(WORD_LIST_MAX_AGE_SECONDS), || async {
+        let changes = words::changes_since(&pool.db, since)
+            .await?
+            .ok_or_else(|| AppError::gone(ErrorCode::WordHistoryGone, format!("The changes since version {} aren't kept", since)))?;
+        Ok(HttpResponse::Ok().json(changes))
+    })
+    .await
+}
+
+// Marks every pair on its own: one that isn't two five letter words gets its error in
+// its place and the rest are still marked. Answers don't have to be in the word list.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/feedback",
+    security(("bearer_auth" = [])),
+    params(PaletteQuery),
+    request_body(content = FeedbackBatchRequest, example = json!(examples::feedback_batch_request())),
+    responses(
+        (status = 200, description = "One result per pair, in order", body = FeedbackBatch, example = json!(examples::feedback_batch())),
+        (status = 400, description = "Malformed body, or an unknown palette", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 413, description = "More pairs than FEEDBACK_BATCH_MAX, or a body over the size limit", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+#[post("/batch", wrap = "RateLimit::new(SOLVER)")]
+pub async fn feedback_batch(pool: web::Data<AppState>, req: HttpRequest, batch: web::Json<FeedbackBatchRequest>, palette: web::Query<PaletteQuery>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let most = pool.settings.max_feedback_batch;
+    if batch.pairs.len() > most {
+        let info = ErrorInfo::new(ErrorCode::BatchTooLarge, format!("A batch can have at most {} pairs, this one has {}", most, batch.pairs.len()))
+            .with_details(json!({ "max_pairs": most, "pairs": batch.pairs.len() }));
+        return Err(AppError::PayloadTooLarge(info));
+    }
+    let palette = palette_for(&pool.db, Some(user_id), palette.palette).await?;
+
+    let results = batch
+        .pairs
+        .iter()
+        .map(|pair| match feedback_for(&pair.guess, &pair.answer) {
+            Ok((guess, marks)) => {
+                let pattern = feedback_pattern(&marks);
+                FeedbackResult { squares: Some(squares(&guess, &pattern, palette)), feedback: Some(pattern), error: None }
             }
+            Err(error) => FeedbackResult { feedback: None, squares: None, error: Some(error.info()) },
         })
+        .collect();
+    Ok(HttpResponse::Ok().json(FeedbackBatch { results }))
 }
 
-#[post("/general-letters")]
-pub async fn find_letters(pool: web::Data<AppState>, req: HttpRequest, letters: web::Json<RequestLetters>) -> HttpResponse {
-    let access_token = get_bearer_token(&req);
+// The checks and marking for one pair, giving the cleaned up guess with its marks
+fn feedback_for(guess: &str, answer: &str) -> Result<(String, Vec<Mark>), AppError> {
+    let guess = sanitize_letters("guess", guess)?;
+    let answer = sanitize_letters("answer", answer)?;
+    for (field, word) in [("guess", &guess), ("answer", &answer)] {
+        if word.len() != WORD_LENGTH {
+            let info = ErrorInfo::new(ErrorCode::InvalidWord, format!("{} must have {} letters", field, WORD_LENGTH)).with_details(json!({ "field": field }));
+            return Err(AppError::Validation(info));
+        }
+    }
+
+    let marks = feedback(&guess, &answer);
+    Ok((guess, marks))
+}
 
-    if access_token.is_none() {
-        return HttpResponse::Unauthorized().body("Unauthorized");
+// A listed word no finished game has had yet comes back with zeros
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game",
+    security(("bearer_auth" = [])),
+    params(("word" = String, Path, description = "A word from the list")),
+    responses(
+        (status = 200, description = "How the word fared as an answer", body = WordStats, example = json!(examples::word_stats())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "The word isn't in the list", body = ErrorResponse),
+    )
+)]
+#[get("/words/{word}/stats")]
+pub async fn get_word_stats(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(String,)>) -> Result<HttpResponse, AppError> {
+    require_user(&req, &pool).await?;
+    let word = path.into_inner().0.trim().to_lowercase();
+
+    let listed = word.len() == WORD_LENGTH
+        && word.chars().all(|letter| letter.is_ascii_lowercase())
+        && !pool.words.filter_words(&word, &Constraints::default()).await?.is_empty();
+    if !listed {
+        return Err(AppError::not_found(format!("{} isn't in the word list", word)));
+    }
+
+    let stats = word_stats::find(&pool.db, &word).await?.map(WordStats::from).unwrap_or_else(|| WordStats::empty(word));
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+// Makes the stored list match the one at WORD_LIST_SYNC_URL. The list is checked before
+// anything changes, and a failed sync leaves the stored list as it was.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "What the sync changed, if anything", body = WordListSync, example = json!(examples::word_list_sync())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 502, description = "The source couldn't be fetched or sent something that isn't a word list", body = ErrorResponse),
+        (status = 503, description = "No source is configured", body = ErrorResponse),
+    )
+)]
+#[post("/words/sync")]
+pub async fn sync_word_list(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let admin = require_admin(&req, &pool).await?;
+    let settings = pool.settings.word_sync.as_ref().ok_or_else(|| AppError::unavailable(ErrorCode::WordSyncNotConfigured, "No word list source is configured"))?;
+
+    let sync = sync_words(&pool.db, settings, Some(admin.id), pool.clock.as_ref()).await?;
+    if sync.revision.is_some() {
+        log_auth_event(&pool.db, Some(admin.id), "word_list_synced", &format!("{} added, {} removed", sync.added, sync.removed)).await;
     }
 
-    let token_valid = verify_token(&access_token.unwrap()).unwrap_or(false);
+    Ok(HttpResponse::Ok().json(sync))
+}
 
-    if !token_valid {
-        return HttpResponse::Unauthorized().body("Unauthorized");
+// Queued for an admin to approve or reject. Proposing a change that's already pending adds
+// the proposer and their reason to it instead of opening another.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game",
+    security(("bearer_auth" = [])),
+    request_body(content = NewWordSuggestion, example = json!(examples::new_word_suggestion())),
+    responses(
+        (status = 201, description = "Suggestion opened", body = WordSuggestion, example = json!(examples::word_suggestion())),
+        (status = 200, description = "Merged into the pending suggestion for the same change", body = WordSuggestion, example = json!(examples::word_suggestion())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 413, description = "Body larger than 1 KiB", body = ErrorResponse),
+        (status = 422, description = "Not a five letter word, no reason given, or the list already reflects the change", body = ErrorResponse),
+    )
+)]
+#[post("/words/suggestions")]
+pub async fn suggest_word(pool: web::Data<AppState>, req: HttpRequest, suggestion: web::Json<NewWordSuggestion>) -> Result<HttpResponse, AppError> {
+    let user = require_user(&req, &pool).await?;
+    let word = sanitize_letters("word", &suggestion.word)?;
+    if word.len() != WORD_LENGTH {
+        return Err(AppError::validation(ErrorCode::InvalidWord, format!("word must have {} letters", WORD_LENGTH)));
+    }
+    let reason = suggestion.reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_SUGGESTION_REASON_LENGTH {
+        return Err(AppError::validation(ErrorCode::InvalidField, format!("reason must have 1 to {} characters", MAX_SUGGESTION_REASON_LENGTH)));
     }
-    
-    let mut correct_pattern = String::new();
-    for letter in letters.correct.chars() {
-        correct_pattern.push_str(&format!("(?=.*{})", letter));
+    if let Some(reason) = unchanged_by(&pool.db, &word, suggestion.action).await? {
+        return Err(AppError::validation(ErrorCode::SuggestionNoChange, reason));
     }
 
-    let query = format!("SELECT word FROM word_list WHERE word ILIKE '{}' AND word ~* '{}' AND NOT (word ~* '.*[{}].*')", letters.exact, correct_pattern, letters.incorrect);
+    let mut tx = pool.db.begin().await?;
+    let (id, merged) = suggestions::propose(&mut tx, &word, suggestion.action, user.id, reason, pool.clock.now()).await?;
+    let suggestion = suggestions::find(&mut tx, id).await?.ok_or_else(|| AppError::internal(format!("suggestion {} vanished", id)))?;
+    tx.commit().await?;
+
+    let mut response = if merged { HttpResponse::Ok() } else { HttpResponse::Created() };
+    Ok(response.json(suggestion))
+}
 
-    let words = sqlx::query_scalar(&query).fetch_all(&pool.db).await;
-    let words: Vec<String> = match words {
-        Ok(result) => result,
-        Err(error) => {
-            println!("error: {}", error);
-            return HttpResponse::InternalServerError().finish();
+// Plays the solver against words from the list in the background. The response points at
+// the job to poll, whose result sums up how many games were solved and in how many guesses.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game",
+    security(("bearer_auth" = [])),
+    request_body(content = BenchmarkRequest, example = json!(examples::benchmark_request())),
+    responses(
+        (status = 202, description = "Benchmark queued", body = JobAccepted, example = json!(examples::job_accepted())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 422, description = "Sample outside 1 to 500", body = ErrorResponse),
+        (status = 503, description = "Benchmarks are switched off", body = ErrorResponse),
+    )
+)]
+#[post("/benchmark")]
+pub async fn benchmark_solver(pool: web::Data<AppState>, req: HttpRequest, request: web::Json<BenchmarkRequest>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    pool.features.require(BENCHMARKS)?;
+
+    let sample = request.sample.unwrap_or(DEFAULT_BENCHMARK_SAMPLE);
+    if !(1..=MAX_BENCHMARK_SAMPLE).contains(&sample) {
+        return Err(AppError::validation(ErrorCode::InvalidSample, format!("sample must be between 1 and {}", MAX_BENCHMARK_SAMPLE)));
+    }
+
+    let id = jobs::enqueue(&pool.db, Some(user_id), SOLVER_BENCHMARK, &json!({ "sample": sample }), pool.settings.job_max_attempts, pool.clock.now()).await?;
+    let status_url = format!("{}/jobs/{}", API_PREFIX, id);
+
+    Ok(HttpResponse::Accepted()
+        .insert_header(("Location", status_url.clone()))
+        .json(JobAccepted { id, status_url }))
+}
+
+fn contradiction(letter: char) -> ErrorInfo {
+    ErrorInfo::new(ErrorCode::ConstraintContradiction, format!("{} is listed as both in the word and not in it", letter))
+        .with_details(json!({ "letter": letter.to_string() }))
+}
+
+// The preference saved by the user unless the request picked one
+async fn sort_order_for(pool: &AppState, user_id: i32, requested: Option<SortOrder>) -> Result<SortOrder, AppError> {
+    match requested {
+        Some(sort_order) => Ok(sort_order),
+        None => Ok(users::find_preferences(&pool.db, user_id).await?.unwrap_or_default().sort_order),
+    }
+}
+
+// The cleaned up guess and feedback, and the base constraints narrowed by them
+fn hypothesis(base: &RequestLetters, hypothetical: HypotheticalFeedback) -> Result<(String, String, Constraints), AppError> {
+    let guess = sanitize_letters("guess", &hypothetical.guess)?;
+    if guess.len() != WORD_LENGTH {
+        return Err(AppError::validation(ErrorCode::InvalidWord, format!("guess must have {} letters", WORD_LENGTH)));
+    }
+    let feedback = hypothetical.feedback.trim().to_ascii_lowercase();
+    let invalid_feedback = |error: FeedbackError| AppError::validation(ErrorCode::InvalidFeedback, error.to_string());
+    let marks = parse_feedback(&feedback).map_err(invalid_feedback)?;
+
+    let mut constraints = base.constraints();
+    constraints.apply(&guess, &marks).map_err(invalid_feedback)?;
+    if let Some(letter) = base.with_feedback(&guess, &marks).contradiction() {
+        return Err(AppError::Validation(contradiction(letter)));
+    }
+    if let Some((position, letter)) = base.conflicting_position(&guess, &marks) {
+        let info = ErrorInfo::new(ErrorCode::ConstraintContradiction, format!("{} is green at position {}, where exact has another letter", letter, position + 1))
+            .with_details(json!({ "letter": letter.to_string(), "position": position + 1 }));
+        return Err(AppError::Validation(info));
+    }
+
+    Ok((guess, feedback, constraints))
+}
+
+// Adds which part of a diff request `error` is about to its details
+fn in_branch(error: AppError, branch: &str) -> AppError {
+    match error {
+        AppError::Validation(mut info) => {
+            let mut details = info.details.take().unwrap_or_else(|| json!({}));
+            details["branch"] = json!(branch);
+            AppError::Validation(info.with_details(details))
         }
-    };
-    HttpResponse::Ok().json(words)
+        other => other,
+    }
 }
@@ -0,0 +1,637 @@
+use crate::errors::{self, AppError, ErrorCode, ErrorInfo};
+use crate::handlers::examples;
+use crate::models::game_models::{
+    ClaimSession, CreatedGameSession, GameSession, GameSessionRecord, HistoryExportQuery, NewGameSession, NewSessionGuess, NewSessionReport,
+    ReportedGuess, SessionHistoryQuery, SessionStats, SessionSummary, UpdateGameSession, UserStats,
+};
+use crate::models::users_models::PageQuery;
+use crate::models::users_models::ProfileVisibility;
+use crate::repositories::game_sessions::{ExportedGuess, HistoryFilter};
+use crate::repositories::reports::NewReport;
+use crate::repositories::{game_sessions, reports, user_stats, users, words};
+use crate::solver::{parse_feedback, Mark};
+use crate::utils::audit_utils::log_auth_event;
+use crate::utils::auth_utils::{has_credentials, not_owner, random_token, require_user};
+use crate::utils::feedback_utils::{painted, palette_for, Palette, PaletteQuery};
+use crate::utils::input_utils::sanitize_letters;
+use crate::utils::word_sync_utils::WORD_LENGTH;
+use crate::middleware::rate_limit::RateLimit;
+use crate::utils::concurrency_utils::SOLVER;
+use crate::utils::pagination_utils::Pagination;
+use crate::utils::session_utils::{add_claimed, constraints_of, report_of, solver_output};
+use crate::utils::stream_utils::{paged_csv, CsvRow};
+use crate::AppState;
+use actix_web::{get, patch, post, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+// Plays an anonymous session, in place of a bearer token
+pub const SESSION_TOKEN_HEADER: &str = "X-Session-Token";
+// A guess and its feedback, a token, or a note with its tags
+const SESSION_BODY_LIMIT: usize = 4 * 1024;
+// Characters, not bytes
+const MAX_NOTE_LENGTH: usize = 500;
+const MAX_TAG_LENGTH: usize = 32;
+const MAX_TAGS: usize = 10;
+// How long after a guess the same guess counts as a retry of it
+const RETRY_WINDOW_SECONDS: i64 = 30;
+const MAX_REPORT_LENGTH: usize = 2000;
+const EXPORT_HEADER: &[&str] = &["session_id", "date", "turn", "guess", "pattern", "solved", "duration_seconds", "note"];
+
+// Under /game, so mounted before the game scope would claim the path
+pub fn game_session_routes(conf: &mut web::ServiceConfig) {
+    let scope = web::scope("/game/sessions")
+        .app_data(errors::json_config(SESSION_BODY_LIMIT))
+        .service(create_session)
+        .service(session_history)
+        .service(session_stats)
+        .service(user_session_stats)
+        .service(get_session)
+        .service(update_session)
+        .service(add_guess)
+        .service(report_session)
+        .service(claim_session);
+
+    conf.service(scope);
+}
+
+// Under /game as well
+pub fn history_routes(conf: &mut web::ServiceConfig) {
+    conf.service(web::scope("/game/history").service(export_history));
+}
+
+// Without a bearer token, or with a guest one, the session is anonymous: the response
+// carries the token that plays it, and it expires after ANONYMOUS_SESSION_HOURS unless
+// claimed by an account
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/sessions",
+    security((), ("bearer_auth" = [])),
+    params(PaletteQuery),
+    request_body(content = Option<NewGameSession>, description = "Can be left out for a standard six guess game", example = json!(examples::new_game_session())),
+    responses(
+        (status = 201, description = "Session started", body = CreatedGameSession, example = json!(examples::created_game_session())),
+        (status = 400, description = "Malformed body, or an unknown palette", body = ErrorResponse),
+        (status = 401, description = "A bearer token was sent but isn't accepted", body = ErrorResponse),
+        (status = 422, description = "max_guesses is below 1 or above the server's limit", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+    )
+)]
+#[post("", wrap = "RateLimit::new(SOLVER)")]
+pub async fn create_session(pool: web::Data<AppState>, req: HttpRequest, body: web::Bytes, palette: web::Query<PaletteQuery>) -> Result<HttpResponse, AppError> {
+    // A token that doesn't check out is refused rather than quietly ignored
+    let user = if has_credentials(&req) { Some(require_user(&req, &pool).await?) } else { None };
+    let request: NewGameSession = errors::optional_json(&body, SESSION_BODY_LIMIT)?;
+    let max_guesses = guess_limit(request.max_guesses, pool.settings.max_session_guesses)?;
+    let now = pool.clock.now();
+
+    // A guest's session is anonymous, kept under their guest id for when they register
+    let (record, token) = match user {
+        Some(user) if !user.is_guest() => {
+            let mut tx = pool.db.begin().await?;
+            let record = game_sessions::insert(&mut tx, Some(user.id), None, None, max_guesses, None, now).await?;
+            user_stats::add(&mut tx, user.id, &UserStats::started(max_guesses), now).await?;
+            tx.commit().await?;
+            (record, None)
+        }
+        user => {
+            let token = random_token();
+            let expires_at = now + pool.settings.anonymous_session_ttl;
+            let guest_id = user.and_then(|user| user.guest);
+            (game_sessions::insert(&pool.db, None, Some(&hash_token(&token)), guest_id.as_deref(), max_guesses, Some(expires_at), now).await?, Some(token))
+        }
+    };
+
+    let session = view(&pool, record, palette.palette).await?;
+    Ok(HttpResponse::Created().json(CreatedGameSession { session, token }))
+}
+
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/sessions",
+    security((), ("bearer_auth" = [])),
+    params(
+        ("id" = i32, Path, description = "Session id"),
+        ("X-Session-Token" = Option<String>, Header, description = "Token of an anonymous session"),
+        PaletteQuery,
+    ),
+    responses(
+        (status = 200, description = "The session and the guesses made so far", body = GameSession, example = json!(examples::game_session())),
+        (status = 400, description = "palette isn't standard or high_contrast", body = ErrorResponse),
+        (status = 401, description = "The session belongs to an account and no accepted token was sent", body = ErrorResponse),
+        (status = 403, description = "The session belongs to another account", body = ErrorResponse),
+        (status = 404, description = "No such session, or the anonymous session's token is missing or wrong", body = ErrorResponse),
+        (status = 410, description = "The anonymous session expired", body = ErrorResponse),
+    )
+)]
+#[get("/{id}")]
+pub async fn get_session(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, palette: web::Query<PaletteQuery>) -> Result<HttpResponse, AppError> {
+    let record = accessible(&pool, &req, path.into_inner().0).await?;
+
+    Ok(HttpResponse::Ok().json(view(&pool, record, palette.palette).await?))
+}
+
+// One line per guess. The session's columns repeat on each of its guesses, the duration is
+// left empty while it's unfinished.
+impl CsvRow for ExportedGuess {
+    fn id(&self) -> i32 {
+        self.id
+    }
+
+    fn record(&self) -> Vec<String> {
+        let duration = self.completed_at.map(|completed_at| (completed_at - self.started_at).num_seconds().to_string());
+        vec![
+            self.session_id.to_string(),
+            self.created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+            self.turn.to_string(),
+            self.guess.clone(),
+            self.feedback.clone(),
+            (self.status == "solved").to_string(),
+            duration.unwrap_or_default(),
+            self.note.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+fn start_of(day: NaiveDate) -> DateTime<Utc> {
+    Utc.from_utc_datetime(&day.and_hms_opt(0, 0, 0).unwrap())
+}
+
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/history",
+    security(("bearer_auth" = [])),
+    params(HistoryExportQuery),
+    responses(
+        (status = 200, description = "Every guess in the caller's sessions, oldest first, as a CSV attachment", content_type = "text/csv", body = String, example = json!(examples::history_csv())),
+        (status = 400, description = "A query parameter isn't valid", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 422, description = "from is after to", body = ErrorResponse),
+    )
+)]
+#[get("/export")]
+pub async fn export_history(pool: web::Data<AppState>, req: HttpRequest, query: web::Query<HistoryExportQuery>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from > to {
+            return Err(AppError::validation(ErrorCode::InvalidDateRange, "from must not be after to"));
+        }
+    }
+
+    let from = query.from.map(start_of);
+    let until = query.to.map(|to| start_of(to) + Duration::days(1));
+    let db = pool.db.clone();
+    let rows = paged_csv(EXPORT_HEADER, query.bom, move |after_id, limit| {
+        let db = db.clone();
+        async move { game_sessions::export_guesses(&db, user_id, from, until, after_id, limit).await }
+    });
+
+    let filename = format!("wordle-history-{}.csv", pool.clock.now().format("%Y-%m-%d"));
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", filename)))
+        .streaming(rows))
+}
+
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/sessions",
+    security(("bearer_auth" = [])),
+    params(SessionHistoryQuery, PageQuery),
+    responses(
+        (status = 200, description = "A page of the caller's sessions, newest first", body = GameSessionPage, example = json!(examples::game_session_page())),
+        (status = 400, description = "A query parameter isn't valid", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 422, description = "page or per_page out of range, or a tag that isn't valid", body = ErrorResponse),
+    )
+)]
+#[get("")]
+pub async fn session_history(pool: web::Data<AppState>, req: HttpRequest, query: web::Query<SessionHistoryQuery>, pagination: Pagination) -> Result<HttpResponse, AppError> {
+    let user = require_user(&req, &pool).await?;
+    let tags = match &query.tags {
+        Some(tags) => normalize_tags(tags.split(',').map(str::to_string).filter(|tag| !tag.trim().is_empty()).collect())?,
+        None => Vec::new(),
+    };
+    let filter = HistoryFilter { tags: &tags, search: query.q.as_deref().map(str::trim).filter(|search| !search.is_empty()) };
+    let kind = pool.db.any_kind();
+
+    let rows = game_sessions::history(&pool.db, kind, user.id, &filter, pagination.limit(), pagination.offset()).await?;
+    let total = game_sessions::count_history(&pool.db, kind, user.id, &filter).await?;
+    let mut tags = game_sessions::tags_of(&pool.db, &rows.iter().map(|row| row.id).collect::<Vec<_>>()).await?;
+
+    let items = rows
+        .into_iter()
+        .map(|row| SessionSummary {
+            id: row.id,
+            status: row.status,
+            max_guesses: row.max_guesses,
+            guesses: row.guesses,
+            note: row.note,
+            tags: tags.remove(&row.id).unwrap_or_default(),
+            completed_at: row.completed_at,
+            created_at: row.created_at,
+        })
+        .collect();
+    Ok(HttpResponse::Ok().json(pagination.page_of(items, total)))
+}
+
+// Only the owner can annotate a session, anonymous ones can't be until they're claimed
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/sessions",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Session id"), PaletteQuery),
+    request_body(content = UpdateGameSession, example = json!(examples::update_game_session())),
+    responses(
+        (status = 200, description = "The session with its note and tags", body = GameSession, example = json!(examples::game_session())),
+        (status = 400, description = "palette isn't standard or high_contrast", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "The session isn't the caller's", body = ErrorResponse),
+        (status = 404, description = "No such session", body = ErrorResponse),
+        (status = 422, description = "The note is too long, or the tags aren't valid", body = ErrorResponse),
+    )
+)]
+#[patch("/{id}")]
+pub async fn update_session(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, update: web::Json<UpdateGameSession>, palette: web::Query<PaletteQuery>) -> Result<HttpResponse, AppError> {
+    let user = require_user(&req, &pool).await?;
+    let (id,) = path.into_inner();
+    let record = game_sessions::find(&pool.db, id).await?.ok_or_else(|| AppError::not_found("Session not found"))?;
+    if record.user_id != Some(user.id) {
+        return Err(not_owner("Only the session's owner can annotate it"));
+    }
+
+    let update = update.into_inner();
+    let note = update.note.as_deref().map(normalize_note).transpose()?;
+    let tags = update.tags.map(normalize_tags).transpose()?;
+
+    let mut tx = pool.db.begin().await?;
+    if let Some(note) = note {
+        game_sessions::set_note(&mut tx, id, note.as_deref(), pool.clock.now()).await?;
+    }
+    if let Some(tags) = tags {
+        game_sessions::replace_tags(&mut tx, id, &tags).await?;
+    }
+    tx.commit().await?;
+
+    let record = game_sessions::find(&pool.db, id).await?.ok_or_else(|| AppError::not_found("Session not found"))?;
+    Ok(HttpResponse::Ok().json(view(&pool, record, palette.palette).await?))
+}
+
+// Standard sessions are the ones with a guess limit, free play is counted apart
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/sessions",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "How the caller's sessions went", body = SessionStats, example = json!(examples::session_stats())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    )
+)]
+#[get("/stats")]
+pub async fn session_stats(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let user = require_user(&req, &pool).await?;
+
+    Ok(HttpResponse::Ok().json(stats_of(&pool, user.id).await?))
+}
+
+// Visibility is read from the database on every request, so a change applies to the next
+// view. The owner and admins can always look.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/sessions",
+    security(("bearer_auth" = [])),
+    params(("user_id" = i32, Path, description = "Whose sessions")),
+    responses(
+        (status = 200, description = "How the user's sessions went", body = SessionStats, example = json!(examples::session_stats())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "The user's profile isn't visible to the caller", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
+    )
+)]
+#[get("/stats/{user_id}")]
+pub async fn user_session_stats(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let viewer = require_user(&req, &pool).await?;
+    let (user_id,) = path.into_inner();
+
+    let preferences = users::find_preferences(&pool.db, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("User not found"))?;
+    // Friends-only profiles have no friends to show them to yet
+    let visible = viewer.id == user_id || viewer.is_admin() || preferences.profile_visibility == ProfileVisibility::Public;
+    if !visible {
+        return Err(AppError::Forbidden(ErrorInfo::new(ErrorCode::ProfilePrivate, "This user doesn't share their stats")));
+    }
+
+    Ok(HttpResponse::Ok().json(stats_of(&pool, user_id).await?))
+}
+
+// From user_stats, which is added to as sessions start, end and are claimed
+async fn stats_of(pool: &AppState, user_id: i32) -> Result<SessionStats, AppError> {
+    let mut conn = pool.db.acquire().await?;
+    Ok(user_stats::find(&mut conn, user_id).await?.into())
+}
+
+// Feedback that would leave no word at all is refused and not recorded, it's most likely
+// a typo. All green solves the session. Sending the last guess again within
+// RETRY_WINDOW_SECONDS answers as the first time did and records nothing.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/sessions",
+    security((), ("bearer_auth" = [])),
+    params(
+        ("id" = i32, Path, description = "Session id"),
+        ("X-Session-Token" = Option<String>, Header, description = "Token of an anonymous session"),
+        PaletteQuery,
+    ),
+    request_body(content = NewSessionGuess, example = json!(examples::new_session_guess())),
+    responses(
+        (status = 200, description = "The session with the guess added", body = GameSession, example = json!(examples::game_session())),
+        (status = 400, description = "palette isn't standard or high_contrast", body = ErrorResponse),
+        (status = 401, description = "The session belongs to an account and no accepted token was sent", body = ErrorResponse),
+        (status = 403, description = "The session belongs to another account", body = ErrorResponse),
+        (status = 404, description = "No such session, or the anonymous session's token is missing or wrong", body = ErrorResponse),
+        (status = 409, description = "The session is already solved, has no guesses left, or another guess took the turn", body = ErrorResponse),
+        (status = 410, description = "The anonymous session expired", body = ErrorResponse),
+        (status = 422, description = "Not a five letter guess, feedback that isn't one of g, y or x per letter, or feedback no word matches", body = ErrorResponse),
+    )
+)]
+#[post("/{id}/guesses", wrap = "RateLimit::new(SOLVER)")]
+pub async fn add_guess(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, guess: web::Json<NewSessionGuess>, palette: web::Query<PaletteQuery>) -> Result<HttpResponse, AppError> {
+    let (id,) = path.into_inner();
+    accessible(&pool, &req, id).await?;
+
+    let word = sanitize_letters("guess", &guess.guess)?;
+    if word.len() != WORD_LENGTH {
+        return Err(AppError::validation(ErrorCode::InvalidWord, format!("guess must have {} letters", WORD_LENGTH)));
+    }
+    let feedback = guess.feedback.trim().to_ascii_lowercase();
+    let marks = parse_feedback(&feedback).map_err(|error| AppError::validation(ErrorCode::InvalidFeedback, error.to_string()))?;
+    let now = pool.clock.now();
+
+    // Held until the guess is recorded, so a second request for the same session reads the
+    // guesses only once this one's are in
+    let mut tx = pool.db.begin().await?;
+    let record = game_sessions::lock(&mut tx, id).await?.ok_or_else(|| AppError::not_found("Session not found"))?;
+    let made = game_sessions::guesses(&mut tx, id).await?;
+
+    // A retry of the guess just made, e.g. after the response to it was lost, gets the same
+    // answer instead of using up another turn
+    let retried = made.last().is_some_and(|last| last.guess == word && last.feedback == feedback && now - last.created_at <= Duration::seconds(RETRY_WINDOW_SECONDS));
+    if retried {
+        tx.rollback().await?;
+        return Ok(HttpResponse::Ok().json(view(&pool, record, palette.palette).await?));
+    }
+
+    if record.status == "solved" {
+        return Err(AppError::conflict(ErrorCode::SessionFinished, "The session is already solved"));
+    }
+    if let Some(max_guesses) = record.max_guesses.filter(|max_guesses| made.len() >= *max_guesses as usize) {
+        let info = ErrorInfo::new(ErrorCode::GameOver, format!("Game over, all {} guesses were used", max_guesses)).with_details(json!({ "max_guesses": max_guesses }));
+        return Err(AppError::Conflict(info));
+    }
+
+    let mut constraints = constraints_of(&made.iter().map(|guess| (guess.guess.as_str(), guess.feedback.as_str())).collect::<Vec<_>>())?;
+    constraints.apply(&word, &marks).map_err(|error| AppError::validation(ErrorCode::InvalidFeedback, error.to_string()))?;
+    if pool.words.filter_words("%", &constraints).await?.is_empty() {
+        return Err(AppError::validation(ErrorCode::ConstraintContradiction, "No word matches that feedback together with the earlier guesses"));
+    }
+
+    // Where the lock isn't available the turn's unique index still lets only one through
+    let turn = made.len() as i32 + 1;
+    game_sessions::insert_guess(&mut tx, id, turn, &word, &feedback, now).await.map_err(|error| match AppError::from(error) {
+        AppError::Conflict(_) => AppError::conflict(ErrorCode::TurnTaken, "Another guess was made for this turn at the same time"),
+        error => error,
+    })?;
+    let out_of_guesses = record.max_guesses.is_some_and(|max_guesses| turn >= max_guesses);
+    let solved = marks.iter().all(|mark| *mark == Mark::Green);
+    game_sessions::record_progress(&mut tx, id, solved, out_of_guesses, now).await?;
+    // Anonymous sessions are counted when they're claimed
+    if let Some(user_id) = record.user_id.filter(|_| solved || out_of_guesses) {
+        let status = if solved { "solved" } else { "lost" };
+        user_stats::add(&mut tx, user_id, &UserStats::finished(record.max_guesses, status, turn.into()), now).await?;
+    }
+    tx.commit().await?;
+
+    let record = game_sessions::find(&pool.db, id).await?.ok_or_else(|| AppError::not_found("Session not found"))?;
+    Ok(HttpResponse::Ok().json(view(&pool, record, palette.palette).await?))
+}
+
+// Keeps the guesses made so far and what the solver made of them, so an admin can replay
+// the session later against the word list as it was. The body stays under the session
+// limit, so a description can be at most MAX_REPORT_LENGTH characters.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/sessions",
+    security((), ("bearer_auth" = [])),
+    params(
+        ("id" = i32, Path, description = "Session id"),
+        ("X-Session-Token" = Option<String>, Header, description = "Token of an anonymous session"),
+        PaletteQuery,
+    ),
+    request_body(content = NewSessionReport, example = json!(examples::new_session_report())),
+    responses(
+        (status = 201, description = "Report filed", body = SessionReport, example = json!(examples::session_report())),
+        (status = 401, description = "The session belongs to an account and no accepted token was sent", body = ErrorResponse),
+        (status = 403, description = "The session belongs to another account", body = ErrorResponse),
+        (status = 404, description = "No such session, or the anonymous session's token is missing or wrong", body = ErrorResponse),
+        (status = 410, description = "The anonymous session expired", body = ErrorResponse),
+        (status = 422, description = "The description is empty or too long", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+        (status = 503, description = "Too many solver queries running, retry after the Retry-After seconds", body = ErrorResponse),
+    )
+)]
+#[post("/{id}/report", wrap = "RateLimit::new(SOLVER)")]
+pub async fn report_session(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, report: web::Json<NewSessionReport>) -> Result<HttpResponse, AppError> {
+    let (id,) = path.into_inner();
+    let record = accessible(&pool, &req, id).await?;
+    let description = report.description.trim();
+    if description.is_empty() || description.chars().count() > MAX_REPORT_LENGTH {
+        return Err(AppError::validation(ErrorCode::InvalidField, format!("description must have 1 to {} characters", MAX_REPORT_LENGTH)));
+    }
+
+    let guesses: Vec<ReportedGuess> = game_sessions::guesses(&pool.db, id)
+        .await?
+        .into_iter()
+        .map(|guess| ReportedGuess { guess: guess.guess, feedback: guess.feedback })
+        .collect();
+    let constraints = constraints_of(&guesses.iter().map(|guess| (guess.guess.as_str(), guess.feedback.as_str())).collect::<Vec<_>>())?;
+
+    // The database's list rather than the cached one, read again if it changed meanwhile,
+    // so the version is the one the words came from
+    let permit = pool.limits.acquire(SOLVER, &pool.metrics).await?;
+    let (version, candidates, all) = loop {
+        let version = words::version(&pool.db).await?;
+        let candidates = words::filter_words(&pool.db, "%", &constraints).await?;
+        let all = words::all_words(&pool.db).await?;
+        if words::version(&pool.db).await? == version {
+            break (version, candidates, all);
+        }
+    };
+    let original = solver_output(version, candidates, &all);
+    drop(permit);
+
+    let guesses_json = serde_json::to_string(&guesses).map_err(|error| AppError::internal(format!("report guesses: {}", error)))?;
+    let original_json = serde_json::to_string(&original).map_err(|error| AppError::internal(format!("report output: {}", error)))?;
+    let new_report = NewReport {
+        session_id: id,
+        user_id: record.user_id,
+        description,
+        guesses: &guesses_json,
+        word_list_version: version,
+        original: &original_json,
+    };
+    let stored = reports::insert(&pool.db, &new_report, pool.clock.now()).await?;
+
+    Ok(HttpResponse::Created().json(report_of(stored)?))
+}
+
+// Moves an anonymous session and its guesses to the caller's account, e.g. once they log
+// in partway through. A session can only be claimed once.
+#[utoipa::path(
+    tag = "game",
+    context_path = "/api/v1/game/sessions",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Session id"), PaletteQuery),
+    request_body(content = ClaimSession, example = json!(examples::claim_session())),
+    responses(
+        (status = 200, description = "The session, now the caller's", body = GameSession, example = json!(examples::claimed_game_session())),
+        (status = 400, description = "palette isn't standard or high_contrast", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such session, or the token isn't its own", body = ErrorResponse),
+        (status = 409, description = "The session already belongs to an account", body = ErrorResponse),
+        (status = 410, description = "The anonymous session expired", body = ErrorResponse),
+    )
+)]
+#[post("/{id}/claim")]
+pub async fn claim_session(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, claim: web::Json<ClaimSession>, palette: web::Query<PaletteQuery>) -> Result<HttpResponse, AppError> {
+    let user = require_user(&req, &pool).await?;
+    let (id,) = path.into_inner();
+    let record = game_sessions::find(&pool.db, id).await?.ok_or_else(|| AppError::not_found("Session not found"))?;
+    let now = pool.clock.now();
+
+    if record.user_id.is_some() {
+        return Err(AppError::conflict(ErrorCode::SessionAlreadyClaimed, "The session already belongs to an account"));
+    }
+    let token_hash = hash_token(&claim.token);
+    if record.token_hash.as_deref() != Some(token_hash.as_str()) {
+        return Err(AppError::not_found("Session not found"));
+    }
+    if expired(&record, now) {
+        return Err(AppError::gone(ErrorCode::SessionExpired, "The session expired before it was claimed"));
+    }
+
+    // Someone else may have claimed it since it was read
+    let mut tx = pool.db.begin().await?;
+    if !game_sessions::claim(&mut tx, id, user.id, &token_hash, now).await? {
+        return Err(AppError::conflict(ErrorCode::SessionAlreadyClaimed, "The session already belongs to an account"));
+    }
+    add_claimed(&mut tx, user.id, &[id], now).await?;
+    tx.commit().await?;
+    log_auth_event(&pool.db, Some(user.id), "game_session_claimed", &id.to_string()).await;
+
+    let record = game_sessions::find(&pool.db, id).await?.ok_or_else(|| AppError::not_found("Session not found"))?;
+    Ok(HttpResponse::Ok().json(view(&pool, record, palette.palette).await?))
+}
+
+// The session if the request may play it: its owner's bearer token, or the token of an
+// anonymous session that hasn't expired. Another account's session is refused with a 403.
+// Without the right session token an anonymous one is reported missing.
+async fn accessible(pool: &AppState, req: &HttpRequest, id: i32) -> Result<GameSessionRecord, AppError> {
+    let record = game_sessions::find(&pool.db, id).await?.ok_or_else(|| AppError::not_found("Session not found"))?;
+
+    match (record.user_id, &record.token_hash) {
+        (Some(owner), _) => {
+            if require_user(req, pool).await?.id != owner {
+                return Err(not_owner("The session belongs to another account"));
+            }
+        }
+        (None, Some(token_hash)) => {
+            let sent = req.headers().get(SESSION_TOKEN_HEADER).and_then(|value| value.to_str().ok()).map(hash_token);
+            if sent.as_ref() != Some(token_hash) {
+                return Err(AppError::not_found("Session not found"));
+            }
+            if expired(&record, pool.clock.now()) {
+                return Err(AppError::gone(ErrorCode::SessionExpired, "The session expired"));
+            }
+        }
+        (None, None) => return Err(AppError::not_found("Session not found")),
+    }
+
+    Ok(record)
+}
+
+async fn view(pool: &AppState, record: GameSessionRecord, palette: Option<Palette>) -> Result<GameSession, AppError> {
+    let guesses = game_sessions::guesses(&pool.db, record.id).await?;
+    let tags = game_sessions::tags(&pool.db, record.id).await?;
+    let constraints = constraints_of(&guesses.iter().map(|guess| (guess.guess.as_str(), guess.feedback.as_str())).collect::<Vec<_>>())?;
+    let remaining = pool.words.filter_words("%", &constraints).await?.len();
+    let palette = palette_for(&pool.db, record.user_id, palette).await?;
+
+    Ok(GameSession {
+        id: record.id,
+        status: record.status,
+        max_guesses: record.max_guesses,
+        anonymous: record.user_id.is_none(),
+        guesses: painted(guesses, palette),
+        remaining,
+        expires_at: record.expires_at,
+        claimed_at: record.claimed_at,
+        completed_at: record.completed_at,
+        created_at: record.created_at,
+        note: record.note,
+        tags,
+    })
+}
+
+// None is free play. STANDARD_MAX_GUESSES is always allowed, the server may allow more.
+fn guess_limit(requested: Option<u32>, most: u32) -> Result<Option<i32>, AppError> {
+    match requested {
+        Some(max_guesses) if !(1..=most).contains(&max_guesses) => {
+            Err(AppError::validation(ErrorCode::InvalidGuessLimit, format!("max_guesses must be between 1 and {}, or null for free play", most)))
+        }
+        requested => Ok(requested.map(|max_guesses| max_guesses as i32)),
+    }
+}
+
+// Trimmed, with an empty note being none
+fn normalize_note(note: &str) -> Result<Option<String>, AppError> {
+    let note = note.trim();
+    if note.chars().count() > MAX_NOTE_LENGTH {
+        return Err(AppError::validation(ErrorCode::InvalidSessionNote, format!("note can have at most {} characters", MAX_NOTE_LENGTH)));
+    }
+    Ok(Some(note.to_string()).filter(|note| !note.is_empty()))
+}
+
+// Trimmed and lowercased, duplicates dropped and sorted. Commas are refused as they
+// separate the tags of the history filter.
+fn normalize_tags(tags: Vec<String>) -> Result<Vec<String>, AppError> {
+    let mut normalized = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH || tag.contains(',') {
+            return Err(AppError::validation(ErrorCode::InvalidSessionTags, format!("tags must have 1 to {} characters and no commas", MAX_TAG_LENGTH)));
+        }
+        normalized.push(tag);
+    }
+    normalized.sort();
+    normalized.dedup();
+
+    if normalized.len() > MAX_TAGS {
+        return Err(AppError::validation(ErrorCode::InvalidSessionTags, format!("A session can have at most {} tags", MAX_TAGS)));
+    }
+    Ok(normalized)
+}
+
+// Anonymous sessions always have an expiry, one without counts as expired
+fn expired(record: &GameSessionRecord, now: DateTime<Utc>) -> bool {
+    match record.expires_at {
+        Some(expires_at) => expires_at <= now,
+        None => true,
+    }
+}
+
+// Only the hash is stored, so a leaked table doesn't hand out playable sessions
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
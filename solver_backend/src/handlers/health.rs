@@ -0,0 +1,112 @@
+use crate::utils::db_utils::ping;
+use crate::utils::shutdown_utils::is_shutting_down;
+use crate::utils::solver_cache_utils::PrimingState;
+use crate::AppState;
+use actix_web::rt::time::timeout;
+use actix_web::{get, web, HttpResponse};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+
+// Kept short so a hung database fails the probe instead of stalling it
+const DB_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Lives outside /api so probes never go through auth
+pub fn health_routes(conf: &mut web::ServiceConfig) {
+    let scope = web::scope("/health")
+        .service(live)
+        .service(ready);
+
+    conf.service(scope);
+}
+
+#[utoipa::path(
+    tag = "health",
+    context_path = "/health",
+    responses((status = 200, description = "The process is up"))
+)]
+#[get("/live")]
+pub async fn live() -> HttpResponse {
+    HttpResponse::Ok().json(json!({ "status": "ok" }))
+}
+
+#[utoipa::path(
+    tag = "health",
+    context_path = "/health",
+    responses(
+        (status = 200, description = "Every dependency is reachable, with the maintenance level: off, read_only or lockdown. The word list check is ok, pending, or degraded when it found critical violations. The solver cache is ok once primed, pending when it will be built on first use, or degraded when priming failed."),
+        (status = 503, description = "At least one check failed, listed under `failed`. A word list that isn't checked yet or failed its check only fails it with WORD_CHECK_ENFORCED on. The solver cache fails it while it's being primed, with the progress in percent."),
+    )
+)]
+#[get("/ready")]
+pub async fn ready(pool: web::Data<AppState>) -> HttpResponse {
+    let mut checks = Map::new();
+
+    // Fails first during a deploy so traffic moves elsewhere while requests drain
+    let accepting = if is_shutting_down(&pool.shutdown) { Err("shutting down".to_string()) } else { Ok(()) };
+    checks.insert("accepting_traffic".to_string(), check_result(accepting));
+
+    let database = if !pool.db_status.is_connected() {
+        Err("still connecting".to_string())
+    } else {
+        match timeout(DB_CHECK_TIMEOUT, ping(&pool.db)).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(error)) => Err(error.to_string()),
+            Err(_) => Err(format!("timed out after {}s", DB_CHECK_TIMEOUT.as_secs())),
+        }
+    };
+    checks.insert("database".to_string(), check_result(database));
+    checks.insert("word_list".to_string(), word_list_check(&pool));
+    checks.insert("solver_cache".to_string(), solver_cache_check(&pool));
+
+    let failed: Vec<&String> = checks
+        .iter()
+        .filter(|(_, check)| check["status"] == "failed")
+        .map(|(name, _)| name)
+        .collect();
+
+    // Maintenance doesn't fail the check, reads are still served
+    let maintenance = pool.features.maintenance_level();
+    if failed.is_empty() {
+        HttpResponse::Ok().json(json!({ "status": "ok", "maintenance": maintenance, "checks": checks }))
+    } else {
+        HttpResponse::ServiceUnavailable().json(json!({ "status": "unavailable", "maintenance": maintenance, "failed": failed, "checks": checks }))
+    }
+}
+
+// Pending until the first check has run, and failing the probe only when WORD_CHECK_ENFORCED
+// is on. Otherwise a list with critical violations is reported as degraded.
+fn word_list_check(pool: &AppState) -> Value {
+    let enforced = pool.settings.word_check_enforced;
+    let Some(report) = pool.word_check.latest() else {
+        return if enforced { json!({ "status": "failed", "error": "not checked yet" }) } else { json!({ "status": "pending" }) };
+    };
+
+    let violations: Vec<&str> = report.violations.iter().map(|violation| violation.check).collect();
+    let status = match (report.passed, enforced) {
+        (true, _) => "ok",
+        (false, true) => "failed",
+        (false, false) => "degraded",
+    };
+    json!({ "status": status, "checked_at": report.checked_at, "violations": violations })
+}
+
+// Failing while startup priming runs, so traffic only arrives once the first best guess
+// won't stall building it
+fn solver_cache_check(pool: &AppState) -> Value {
+    match pool.solver_cache.state() {
+        PrimingState::Idle => json!({ "status": "pending" }),
+        PrimingState::Priming => json!({ "status": "failed", "error": "priming", "progress": pool.solver_cache.progress_percent() }),
+        PrimingState::Ready => match pool.solver_cache.latest() {
+            Some(data) => json!({ "status": "ok", "version": data.version, "source": data.source }),
+            None => json!({ "status": "ok" }),
+        },
+        PrimingState::Failed(error) => json!({ "status": "degraded", "error": error }),
+    }
+}
+
+fn check_result(result: Result<(), String>) -> Value {
+    match result {
+        Ok(()) => json!({ "status": "ok" }),
+        Err(error) => json!({ "status": "failed", "error": error }),
+    }
+}
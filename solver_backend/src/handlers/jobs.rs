@@ -0,0 +1,38 @@
+use crate::errors::AppError;
+use crate::handlers::examples;
+use crate::models::jobs_models::JobStatus;
+use crate::repositories::jobs;
+use crate::utils::auth_utils::require_user;
+use crate::AppState;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+
+pub fn job_routes(conf: &mut web::ServiceConfig) {
+    let scope = web::scope("/jobs")
+        .service(get_job);
+
+    conf.service(scope);
+}
+
+// Other users' jobs are reported as missing rather than forbidden, so ids can't be probed
+#[utoipa::path(
+    tag = "jobs",
+    context_path = "/api/v1/jobs",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Job id")),
+    responses(
+        (status = 200, description = "Where the job is at, with its result once it succeeded", body = JobStatus, example = json!(examples::job_status())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such job among the caller's", body = ErrorResponse),
+    )
+)]
+#[get("/{id}")]
+pub async fn get_job(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let (id,) = path.into_inner();
+
+    let job = jobs::find_for_user(&pool.db, id, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Job not found"))?;
+
+    Ok(HttpResponse::Ok().json(JobStatus::from(job)))
+}
@@ -0,0 +1,391 @@
+use crate::errors::{self, AppError, ErrorCode, ErrorInfo};
+use crate::handlers::examples;
+use crate::models::game_models::{SessionGuessRecord, STANDARD_MAX_GUESSES};
+use crate::models::leagues_models::{
+    JoinLeague, Leaderboard, LeaderboardEntry, LeaderboardQuery, League, LeagueDaily, LeagueDetails, LeagueRecord, NewLeague, NewLeagueGuess,
+};
+use crate::repositories::leagues::{self, LeagueGuess};
+use crate::repositories::word_stats;
+use crate::solver::{feedback, feedback_pattern, Constraints};
+use crate::utils::answer_utils::{answer_for, selector, AnswerStrategy};
+use crate::utils::auth_utils::{not_owner, random_token, require_user};
+use crate::utils::feedback_utils::{painted, palette_for, Palette, PaletteQuery};
+use crate::utils::input_utils::sanitize_letters;
+use crate::utils::webhook_utils::{dispatch, DAILY_COMPLETED};
+use crate::utils::word_sync_utils::WORD_LENGTH;
+use crate::middleware::rate_limit::RateLimit;
+use crate::utils::concurrency_utils::SOLVER;
+use crate::AppState;
+use actix_web::{delete, get, post, web, HttpRequest, HttpResponse};
+use chrono::NaiveDate;
+use serde_json::json;
+use tracing::warn;
+
+// A name, an invite code or a guess
+const LEAGUE_BODY_LIMIT: usize = 1024;
+// Characters, not bytes
+const MAX_NAME_LENGTH: usize = 64;
+const INVITE_CODE_LENGTH: usize = 10;
+
+// Under /game, so mounted before the game scope would claim the path
+pub fn league_routes(conf: &mut web::ServiceConfig) {
+    let scope = web::scope("/game/leagues")
+        .app_data(errors::json_config(LEAGUE_BODY_LIMIT))
+        .service(create_league)
+        .service(list_leagues)
+        .service(join_league)
+        .service(get_league)
+        .service(leave_league)
+        .service(kick_member)
+        .service(daily_game)
+        .service(add_daily_guess)
+        .service(leaderboard);
+
+    conf.service(scope);
+}
+
+// The creator owns the league and is its first member
+#[utoipa::path(
+    tag = "leagues",
+    context_path = "/api/v1/game/leagues",
+    security(("bearer_auth" = [])),
+    request_body(content = NewLeague, example = json!(examples::new_league())),
+    responses(
+        (status = 201, description = "League created", body = League, example = json!(examples::league())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 422, description = "The name is empty or too long, or the answer strategy is unknown", body = ErrorResponse),
+    )
+)]
+#[post("")]
+pub async fn create_league(pool: web::Data<AppState>, req: HttpRequest, new_league: web::Json<NewLeague>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let name = new_league.name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::validation(ErrorCode::InvalidLeagueName, format!("name must have 1 to {} characters", MAX_NAME_LENGTH)));
+    }
+
+    let now = pool.clock.now();
+    let invite_code: String = random_token().chars().take(INVITE_CODE_LENGTH).collect();
+    let mut tx = pool.db.begin().await?;
+    let record = leagues::insert(&mut tx, name, user_id, &invite_code, new_league.answer_strategy.map(AnswerStrategy::as_str), now).await?;
+    leagues::add_member(&mut tx, record.id, user_id, now).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Created().json(League::from(record)))
+}
+
+#[utoipa::path(
+    tag = "leagues",
+    context_path = "/api/v1/game/leagues",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The leagues the caller is a member of, oldest first", body = [League], example = json!(examples::leagues())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    )
+)]
+#[get("")]
+pub async fn list_leagues(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+
+    let joined: Vec<League> = leagues::list_for_member(&pool.db, user_id).await?.into_iter().map(League::from).collect();
+    Ok(HttpResponse::Ok().json(joined))
+}
+
+// Joining a league the caller is already in changes nothing
+#[utoipa::path(
+    tag = "leagues",
+    context_path = "/api/v1/game/leagues",
+    security(("bearer_auth" = [])),
+    request_body(content = JoinLeague, example = json!(examples::join_league())),
+    responses(
+        (status = 200, description = "The league joined", body = League, example = json!(examples::league())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No league has that invite code", body = ErrorResponse),
+    )
+)]
+#[post("/join")]
+pub async fn join_league(pool: web::Data<AppState>, req: HttpRequest, join: web::Json<JoinLeague>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let league = leagues::find_by_invite_code(&pool.db, join.invite_code.trim())
+        .await?
+        .ok_or_else(|| AppError::not_found("No league has that invite code"))?;
+
+    leagues::add_member(&pool.db, league.id, user_id, pool.clock.now()).await?;
+    Ok(HttpResponse::Ok().json(League::from(league)))
+}
+
+#[utoipa::path(
+    tag = "leagues",
+    context_path = "/api/v1/game/leagues",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "League id")),
+    responses(
+        (status = 200, description = "The league and its members", body = LeagueDetails, example = json!(examples::league_details())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such league among the caller's", body = ErrorResponse),
+    )
+)]
+#[get("/{id}")]
+pub async fn get_league(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let league = member_of(&pool, path.into_inner().0, user_id).await?;
+
+    let members = leagues::members(&pool.db, league.id).await?;
+    Ok(HttpResponse::Ok().json(LeagueDetails { league: League::from(league), members }))
+}
+
+// The owner can only leave once everyone else has, which deletes the league
+#[utoipa::path(
+    tag = "leagues",
+    context_path = "/api/v1/game/leagues",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "League id")),
+    responses(
+        (status = 204, description = "Left the league"),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such league among the caller's", body = ErrorResponse),
+        (status = 409, description = "The caller owns the league and others are still in it", body = ErrorResponse),
+    )
+)]
+#[post("/{id}/leave")]
+pub async fn leave_league(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let league = member_of(&pool, path.into_inner().0, user_id).await?;
+
+    if league.owner_id != user_id {
+        leagues::remove_member(&pool.db, league.id, user_id).await?;
+    } else if leagues::members(&pool.db, league.id).await?.len() > 1 {
+        return Err(AppError::conflict(ErrorCode::LeagueOwnerCannotLeave, "The owner can't leave while the league has other members"));
+    } else {
+        leagues::delete(&pool.db, league.id).await?;
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// They can rejoin with the invite code, finding their scores as they left them
+#[utoipa::path(
+    tag = "leagues",
+    context_path = "/api/v1/game/leagues",
+    security(("bearer_auth" = [])),
+    params(
+        ("id" = i32, Path, description = "League id"),
+        ("user_id" = i32, Path, description = "The member to remove"),
+    ),
+    responses(
+        (status = 204, description = "Member removed"),
+        (status = 400, description = "The owner tried to remove themselves", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not the league's owner", body = ErrorResponse),
+        (status = 404, description = "No such league among the caller's, or no such member", body = ErrorResponse),
+    )
+)]
+#[delete("/{id}/members/{user_id}")]
+pub async fn kick_member(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32, i32)>) -> Result<HttpResponse, AppError> {
+    let caller = require_user(&req, &pool).await?.id;
+    let (id, member) = path.into_inner();
+    let league = member_of(&pool, id, caller).await?;
+
+    if league.owner_id != caller {
+        return Err(not_owner("Only the league's owner can remove members"));
+    }
+    if member == caller {
+        return Err(AppError::bad_request(ErrorCode::SelfModeration, "The owner can't remove themselves, leave the league instead"));
+    }
+    if !leagues::remove_member(&pool.db, league.id, member).await? {
+        return Err(AppError::not_found("No such member"));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// The league's word changes at midnight UTC. The answer is only shown once the caller's
+// game is over.
+#[utoipa::path(
+    tag = "leagues",
+    context_path = "/api/v1/game/leagues",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "League id"), PaletteQuery),
+    responses(
+        (status = 200, description = "The caller's game on today's word", body = LeagueDaily, example = json!(examples::league_daily())),
+        (status = 400, description = "palette isn't standard or high_contrast", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such league among the caller's", body = ErrorResponse),
+        (status = 503, description = "The word list has no answers to pick from", body = ErrorResponse),
+    )
+)]
+#[get("/{id}/daily")]
+pub async fn daily_game(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, palette: web::Query<PaletteQuery>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let league = member_of(&pool, path.into_inner().0, user_id).await?;
+    let day = pool.clock.now().date_naive();
+
+    let answer = league_answer(&pool, &league, day).await?;
+    let guesses = leagues::guesses(&pool.db, league.id, user_id, day).await?;
+    let palette = palette_for(&pool.db, Some(user_id), palette.palette).await?;
+    Ok(HttpResponse::Ok().json(daily_view(league.id, day, guesses, &answer, palette)))
+}
+
+// The server marks the guess against the league's word. Finishing the game sends the
+// daily_completed webhook event.
+#[utoipa::path(
+    tag = "leagues",
+    context_path = "/api/v1/game/leagues",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "League id"), PaletteQuery),
+    request_body(content = NewLeagueGuess, example = json!(examples::new_league_guess())),
+    responses(
+        (status = 200, description = "The caller's game with the guess added", body = LeagueDaily, example = json!(examples::league_daily())),
+        (status = 400, description = "palette isn't standard or high_contrast", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such league among the caller's", body = ErrorResponse),
+        (status = 409, description = "Today's game is already over, or another guess took the turn", body = ErrorResponse),
+        (status = 422, description = "Not a five letter word from the word list", body = ErrorResponse),
+        (status = 429, description = "Too many requests from this client", body = ErrorResponse),
+        (status = 503, description = "The word list has no answers to pick from", body = ErrorResponse),
+    )
+)]
+#[post("/{id}/daily/guesses", wrap = "RateLimit::new(SOLVER)")]
+pub async fn add_daily_guess(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, guess: web::Json<NewLeagueGuess>, palette: web::Query<PaletteQuery>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let league = member_of(&pool, path.into_inner().0, user_id).await?;
+
+    let word = sanitize_letters("guess", &guess.guess)?;
+    if word.len() != WORD_LENGTH {
+        return Err(AppError::validation(ErrorCode::InvalidWord, format!("guess must have {} letters", WORD_LENGTH)));
+    }
+    if pool.words.filter_words(&word, &Constraints::default()).await?.is_empty() {
+        return Err(AppError::validation(ErrorCode::InvalidWord, "guess isn't in the word list"));
+    }
+
+    let now = pool.clock.now();
+    let day = now.date_naive();
+    let answer = league_answer(&pool, &league, day).await?;
+    let made = leagues::guesses(&pool.db, league.id, user_id, day).await?;
+    if status_of(&made) != "playing" {
+        return Err(AppError::conflict(ErrorCode::DailyFinished, "Today's game is already over"));
+    }
+
+    let marks = feedback_pattern(&feedback(&word, &answer));
+    let turn = made.len() as i32 + 1;
+    let record = LeagueGuess { league_id: league.id, user_id, day, turn, guess: &word, feedback: &marks };
+    let mut tx = pool.db.begin().await?;
+    leagues::insert_guess(&mut tx, &record, now).await.map_err(|error| match AppError::from(error) {
+        AppError::Conflict(_) => AppError::conflict(ErrorCode::TurnTaken, "Another guess was made for this turn at the same time"),
+        error => error,
+    })?;
+    let palette = palette_for(&mut tx, Some(user_id), palette.palette).await?;
+    let daily = daily_view(league.id, day, leagues::guesses(&mut tx, league.id, user_id, day).await?, &answer, palette);
+    // Only the guess that finishes the game gets this far with an answer, so each game
+    // counts once
+    if daily.answer.is_some() {
+        word_stats::record(&mut tx, &answer, daily.guesses.len() as i64, daily.status == "solved", now).await?;
+    }
+    tx.commit().await?;
+
+    if daily.answer.is_some() {
+        let data = json!({ "league_id": league.id, "day": day, "status": daily.status, "guesses": daily.guesses.len() });
+        if let Err(error) = dispatch(&pool.db, user_id, DAILY_COMPLETED, &data, pool.settings.job_max_attempts, now).await {
+            warn!("Couldn't queue the daily_completed webhooks of user {}: {}", user_id, error);
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(daily))
+}
+
+#[utoipa::path(
+    tag = "leagues",
+    context_path = "/api/v1/game/leagues",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "League id"), LeaderboardQuery),
+    responses(
+        (status = 200, description = "Every current member's score on the day and in total", body = Leaderboard, example = json!(examples::leaderboard())),
+        (status = 400, description = "day isn't a date", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such league among the caller's", body = ErrorResponse),
+        (status = 422, description = "day is after today", body = ErrorResponse),
+    )
+)]
+#[get("/{id}/leaderboard")]
+pub async fn leaderboard(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, query: web::Query<LeaderboardQuery>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let league = member_of(&pool, path.into_inner().0, user_id).await?;
+    let today = pool.clock.now().date_naive();
+    let day = query.day.unwrap_or(today);
+    if day > today {
+        let info = ErrorInfo::new(ErrorCode::InvalidField, "day can't be after today").with_details(json!({ "field": "day" }));
+        return Err(AppError::Validation(info));
+    }
+
+    let members = leagues::members(&pool.db, league.id).await?;
+    let results = leagues::results(&pool.db, league.id, day, &all_green()).await?;
+    let most = i64::from(STANDARD_MAX_GUESSES);
+    let points = |guesses: i64, solved: bool| if solved { most + 1 - guesses } else { 0 };
+
+    let mut entries: Vec<LeaderboardEntry> = members
+        .into_iter()
+        .map(|member| {
+            let played: Vec<_> = results.iter().filter(|result| result.user_id == member.user_id).collect();
+            let on_day = played.iter().find(|result| result.day == day);
+            let (guesses, solved) = on_day.map_or((0, false), |result| (result.guesses, result.solved == 1));
+            LeaderboardEntry {
+                user_id: member.user_id,
+                username: member.username,
+                guesses,
+                solved,
+                day_points: points(guesses, solved),
+                total_points: played.iter().map(|result| points(result.guesses, result.solved == 1)).sum(),
+                days_played: played.len() as i64,
+            }
+        })
+        .collect();
+    entries.sort_by(|a, b| b.total_points.cmp(&a.total_points).then(b.day_points.cmp(&a.day_points)).then_with(|| a.username.cmp(&b.username)));
+
+    Ok(HttpResponse::Ok().json(Leaderboard { league_id: league.id, day, entries }))
+}
+
+// Non-members are told the league doesn't exist
+async fn member_of(pool: &AppState, id: i32, user_id: i32) -> Result<LeagueRecord, AppError> {
+    match leagues::find(&pool.db, id).await? {
+        Some(league) if leagues::is_member(&pool.db, id, user_id).await? => Ok(league),
+        _ => Err(AppError::not_found("League not found")),
+    }
+}
+
+// Leagues share used_answers with the daily puzzle, each under its own scope
+async fn league_answer(pool: &AppState, league: &LeagueRecord, day: NaiveDate) -> Result<String, AppError> {
+    let strategy = league.answer_strategy.as_deref().and_then(|strategy| strategy.parse().ok()).unwrap_or(pool.settings.answer_strategy);
+    let selector = selector(strategy, pool.settings.answer_no_repeat_days);
+    let scope = format!("league:{}", league.id);
+
+    answer_for(&pool.db, pool.words.as_ref(), selector.as_ref(), &scope, day, pool.clock.now())
+        .await?
+        .ok_or_else(|| AppError::unavailable(ErrorCode::NoAnswerAvailable, "The word list has no answers to pick from"))
+}
+
+fn all_green() -> String {
+    "g".repeat(WORD_LENGTH)
+}
+
+fn status_of(guesses: &[SessionGuessRecord]) -> &'static str {
+    if guesses.last().is_some_and(|guess| guess.feedback == all_green()) {
+        "solved"
+    } else if guesses.len() >= STANDARD_MAX_GUESSES as usize {
+        "lost"
+    } else {
+        "playing"
+    }
+}
+
+fn daily_view(league_id: i32, day: NaiveDate, guesses: Vec<SessionGuessRecord>, answer: &str, palette: Palette) -> LeagueDaily {
+    let status = status_of(&guesses);
+
+    LeagueDaily {
+        league_id,
+        day,
+        status: status.to_string(),
+        max_guesses: STANDARD_MAX_GUESSES as i32,
+        guesses: painted(guesses, palette),
+        answer: (status != "playing").then(|| answer.to_string()),
+    }
+}
@@ -0,0 +1,26 @@
+use crate::errors::{AppError, ErrorCode};
+use crate::utils::auth_utils::{get_bearer_token, has_credentials};
+use crate::AppState;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+
+pub fn metrics_routes(conf: &mut web::ServiceConfig) {
+    conf.service(metrics);
+}
+
+// Prometheus scrape endpoint. When a metrics token is configured the scraper must send it as a
+// bearer token; user tokens are not accepted here.
+#[get("/metrics")]
+pub async fn metrics(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    if let Some(expected) = &pool.metrics.scrape_token {
+        if !has_credentials(&req) {
+            return Err(AppError::unauthorized(ErrorCode::MissingToken, "A scrape token is required"));
+        }
+        if get_bearer_token(&req).as_deref() != Some(expected.as_str()) {
+            return Err(AppError::unauthorized(ErrorCode::InvalidToken, "Invalid scrape token"));
+        }
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(pool.metrics.render(&pool.db, &pool.auth_cache)))
+}
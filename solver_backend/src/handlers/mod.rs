@@ -1,2 +1,84 @@
+pub mod admin;
+pub mod auth;
+pub mod docs;
+pub mod examples;
 pub mod game;
+pub mod game_sessions;
+pub mod health;
+pub mod jobs;
+pub mod leagues;
+pub mod metrics;
 pub mod users;
+pub mod webhooks;
+pub mod well_known;
+
+use actix_web::http::Method;
+use actix_web::middleware::NormalizePath;
+use actix_web::web;
+use crate::middleware::deprecation::{Deprecated, DeprecatedAliases, RouteAlias};
+use crate::middleware::localization::Localize;
+use crate::middleware::maintenance::Maintenance;
+use crate::middleware::word_check::WordCheckGuard;
+use admin::admin_routes;
+use auth::auth_routes;
+use game::{feedback_routes, game_routes};
+use game_sessions::{game_session_routes, history_routes};
+use jobs::job_routes;
+use leagues::league_routes;
+use users::user_routes;
+use webhooks::webhook_routes;
+
+pub const API_PREFIX: &str = "/api/v1";
+// Where the routes lived before versioning, kept as an alias while clients move over
+pub const LEGACY_API_PREFIX: &str = "/api";
+
+// Paths from before the routes followed REST conventions, served for a release more
+pub static ROUTE_ALIASES: &[RouteAlias] = &[
+    RouteAlias { method: Method::PUT, legacy: "/users/update/{id}", current: "/users/{id}" },
+    RouteAlias { method: Method::DELETE, legacy: "/users/delete/{id}", current: "/users/{id}" },
+    RouteAlias { method: Method::POST, legacy: "/game/general-letters", current: "/game/candidates" },
+];
+
+// Both mounts register the same routes through `versioned_routes`, so a handler added there
+// is reachable at either prefix. The versioned scope goes first since `/api` would
+// otherwise claim `/api/v1/...` as well. Trailing slashes are trimmed before routing, so
+// `/users/` and `/users` are the same route.
+pub fn api_routes(legacy_routes: bool) -> impl FnOnce(&mut web::ServiceConfig) {
+    move |conf| {
+        conf.service(
+            web::scope(API_PREFIX)
+                .wrap(Maintenance::new(API_PREFIX))
+                .wrap(WordCheckGuard::new(API_PREFIX))
+                .wrap(DeprecatedAliases::new(API_PREFIX, ROUTE_ALIASES))
+                .wrap(NormalizePath::trim())
+                .wrap(Localize)
+                .configure(versioned_routes),
+        );
+
+        if legacy_routes {
+            conf.service(
+                web::scope(LEGACY_API_PREFIX)
+                    .wrap(Maintenance::new(LEGACY_API_PREFIX))
+                    .wrap(WordCheckGuard::new(LEGACY_API_PREFIX))
+                    .wrap(Deprecated::new(LEGACY_API_PREFIX, API_PREFIX))
+                    .wrap(DeprecatedAliases::new(LEGACY_API_PREFIX, ROUTE_ALIASES))
+                    .wrap(NormalizePath::trim())
+                    .wrap(Localize)
+                    .configure(versioned_routes),
+            );
+        }
+    }
+}
+
+fn versioned_routes(conf: &mut web::ServiceConfig) {
+    user_routes(conf);
+    auth_routes(conf);
+    game_session_routes(conf);
+    history_routes(conf);
+    league_routes(conf);
+    feedback_routes(conf);
+    game_routes(conf);
+    job_routes(conf);
+    webhook_routes(conf);
+    admin_routes(conf);
+}
@@ -1,20 +1,72 @@
 use crate::AppState;
-use actix_web::{put, delete, get, post, web, HttpResponse, Responder};
-use chrono::Local;
-use sqlx::{Row, PgPool};
-use log::error;
-use crate::models::users_models::{NewUser, UserResponse, LoginCredentials, Token, Tokens, UpdateUser, UpdatePassword};
+use actix_web::{put, patch, delete, get, post, web, HttpRequest, HttpResponse, ResponseError};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde_json::json;
+use sqlx::{Any, AnyConnection, AnyPool, Executor};
+use tracing::error;
+use crate::errors::{self, AppError, ErrorCode, ErrorInfo};
+use crate::handlers::examples;
+use crate::models::users_models::{
+    ConfirmEmailQuery, DailyCount, EmailChange, GuestToken, ImportOutcome, NewUser, LoginCredentials, NotificationKind, PageQuery, PasswordReset, PasswordResetRequest, SuspendUser, Token,
+    TokenRevocation, Tokens,
+    UpdatePreferences, UpdateUser, UpdatePassword, UpdateSettings, UserImportReport, UserMetrics, MAX_WORD_LENGTH, MIN_WORD_LENGTH,
+};
+use crate::repositories::{game_sessions, tokens, users};
+use crate::utils::audit_utils::log_auth_event;
+use crate::utils::auth_utils::{authenticate_refresh_token, authenticate_token, authenticated_user, not_owner, random_token, record_revocation, require_admin, require_user, token_revoked, AuthUser, ADMIN_ROLE};
 use crate::utils::bcrypt_utils::{hash_password, verify_password};
-use crate::utils::jwt_utils::{generate_access_token, generate_refresh_token, verify_token, decode_token_id};
+use crate::utils::device_utils::ClientInfo;
+use crate::utils::feature_utils::{DATA_EXPORT, GUEST_ACCESS, REGISTRATION};
+use crate::utils::mail_utils::{normalize_email, queue_email, CONFIRM_EMAIL, EMAIL_CHANGED, NEW_DEVICE_LOGIN, PASSWORD_RESET};
+use crate::utils::pagination_utils::Pagination;
+use crate::utils::session_utils::add_claimed;
+use crate::utils::username_utils::username_rejection;
+use crate::utils::stream_utils::paged_json_document;
+use crate::utils::import_utils::{import_rows, parse_user_csv};
+use crate::utils::idempotency_utils::{claim_key, request_hash, store_response, IdempotencyClaim, IDEMPOTENCY_HEADER};
+use crate::config::JwtSettings;
+use crate::middleware::rate_limit::{RateLimit, AUTH, GUEST};
+use crate::middleware::server_timing::RequestTimings;
+use crate::utils::jwt_utils::{decode_claims, generate_access_token, generate_guest_token, generate_refresh_token, SigningKeys, TokenRejection};
 use std::time::Instant;
 
+const EMAIL_CHANGE_TTL_HOURS: i64 = 24;
+const PASSWORD_RESET_TTL_MINUTES: i64 = 60;
+const EXPORT_COOLDOWN_MINUTES: i64 = 60;
+// Days of registrations on the admin dashboard
+const REGISTRATION_DAYS: i64 = 30;
+// Credentials, profile edits and the like, all a few short strings
+const USER_BODY_LIMIT: usize = 4 * 1024;
+// A CSV import, around 40k accounts
+const IMPORT_BODY_LIMIT: usize = 4 * 1024 * 1024;
+
 pub fn user_routes(conf: &mut web::ServiceConfig) {
     let scope = web::scope("/users")
+        .app_data(errors::json_config(USER_BODY_LIMIT))
         .service(create_user)
+        .service(create_guest)
         .service(get_all_users)
+        .service(confirm_email)
+        .service(user_metrics)
+        .service(import_users)
         .service(get_user_by_id)
         .service(update_user)
+        .service(patch_user)
         .service(update_user_password)
+        .service(request_password_reset)
+        .service(reset_password)
+        .service(request_email_change)
+        .service(export_user_data)
+        .service(list_sessions)
+        .service(revoke_session)
+        .service(my_usage)
+        .service(user_usage)
+        .service(update_settings)
+        .service(get_preferences)
+        .service(update_preferences)
+        .service(suspend_user)
+        .service(unsuspend_user)
+        .service(force_logout)
         .service(delete_user)
         .service(login_user)
         .service(revoke_token)
@@ -24,357 +76,1194 @@ pub fn user_routes(conf: &mut web::ServiceConfig) {
     conf.service(scope);
 }
 
-#[post("/register")]
-pub async fn create_user(pool: web::Data<AppState>, new_user: web::Json<NewUser>) -> impl Responder {
-    let now = Local::now().naive_local();
+// Retries carrying the same Idempotency-Key and payload get the first attempt's response
+// replayed instead of registering twice
+#[utoipa::path(
+    tag = "users",
+    context_path = "/api/v1/users",
+    request_body(content = NewUser, example = json!(examples::new_user())),
+    params(("Idempotency-Key" = Option<String>, Header, description = "Replays the first response for retries of the same registration")),
+    responses(
+        (status = 200, description = "User registered, tokens issued", body = Tokens, example = json!(examples::tokens())),
+        (status = 400, description = "Malformed body or Idempotency-Key, or a guest_token that isn't valid", body = ErrorResponse),
+        (status = 409, description = "Username or email taken, or the key is still being processed", body = ErrorResponse),
+        (status = 422, description = "Username not allowed, or the key was reused with a different body", body = ErrorResponse),
+        (status = 503, description = "Registration is switched off", body = ErrorResponse),
+    )
+)]
+#[post("/register", wrap = "RateLimit::new(AUTH)")]
+pub async fn create_user(pool: web::Data<AppState>, req: HttpRequest, new_user: web::Json<NewUser>) -> Result<HttpResponse, AppError> {
+    // Admins can still create accounts. Checked before the key is claimed, so a retry once
+    // registration is back on isn't answered with a stored 503.
+    if !pool.features.is_enabled(REGISTRATION) && !matches!(authenticated_user(&req, &pool).await, Some(user) if user.is_admin()) {
+        pool.features.require(REGISTRATION)?;
+    }
+
+    let key = match req.headers().get(IDEMPOTENCY_HEADER) {
+        Some(value) => match value.to_str() {
+            Ok(key) if !key.is_empty() && key.len() <= 255 => key.to_string(),
+            _ => return Err(AppError::bad_request(ErrorCode::InvalidIdempotencyKey, "Invalid Idempotency-Key header")),
+        },
+        None => return register_user(&pool, &req, &new_user).await,
+    };
+
+    match claim_key(&pool.db, &key, &request_hash(&key, &*new_user), pool.settings.idempotency_ttl, pool.clock.now()).await? {
+        IdempotencyClaim::Claimed => {}
+        IdempotencyClaim::Respond(response) => return Ok(response),
+    }
+
+    // Errors are rendered here so their response is stored and replayed like any other
+    let response = register_user(&pool, &req, &new_user)
+        .await
+        .unwrap_or_else(|error| error.error_response());
+
+    Ok(store_response(&pool.db, &key, response).await)
+}
+
+async fn register_user(pool: &web::Data<AppState>, req: &HttpRequest, new_user: &NewUser) -> Result<HttpResponse, AppError> {
+    let now = pool.clock.now();
+
+    // The live anonymous sessions started with the guest token become the new account's
+    let guest = match &new_user.guest_token {
+        Some(token) => Some(guest_of(pool, token).await?),
+        None => None,
+    };
+
+    // Admins creating service accounts may use reserved names
+    let created_by_admin = matches!(authenticated_user(req, pool).await, Some(user) if user.is_admin());
+    if !created_by_admin {
+        if let Some(reason) = username_rejection(&pool.db, &new_user.username).await {
+            return Err(username_rejected(reason));
+        }
+    }
+
     //This line hashes the user's password using the hash_password function
     //It uses the match control flow construct to handle the result of the hash_password
     //the match control flow construct allows you to match a value against a series of patterns and execute code based on the matched pattern
-    let hashed_password = match hash_password(&new_user.password) {
+    let start_time = Instant::now();
+    let hashed_password = match hash_password(&new_user.password, pool.settings.bcrypt_cost) {
         //is the hashing is successful is assigns the hashed password to hashed_password
         Ok(hashed) => hashed,
-        //if it is not successful an error message is logged
-        Err(error) => {
-            error!("Failed to hash password: {}", error);
-            return HttpResponse::InternalServerError().body("Failed to create user");
-        }
+        //if it is not successful the error is handed back and logged as a 500
+        Err(error) => return Err(error.into()),
     };
-    // sqlx::query(r#"..."#): This starts building an SQL query using a raw string literal 
-    match sqlx::query(
-            r#"
-            INSERT INTO users (username, email, password, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5)
-            RETURNING id
-            "#)
-        .bind(&new_user.username)
-        .bind(&new_user.email)
-        .bind(&hashed_password)
-        .bind(now)
-        .bind(now)
-        .fetch_one(&pool.db)
-        .await {
-            //if the query is successful, it returns an httpresponse
-            //the "_" wildcard pattern is a catch-all for unmatched cases. If no patterns match and there is no "_" arm
-            //the match expression will be considered incomplete and the compiler will raise an error.
-            //Ok(_) => HttpResponse::Ok().body("User created"),
-            Ok(row) => {
-                let user_id: i32 = row.get("id");
-
-                let access_token = match generate_access_token(user_id) {
-                    Ok(token) => token,
-                    Err(_) => return HttpResponse::InternalServerError().body("Failed to generate token"),
-                };
-
-                let new_refresh_token = match generate_refresh_token(user_id) {
-                    Ok(token) => token,
-                    Err(_) => return HttpResponse::InternalServerError().body("Failed to generate token"),
-                };
-
-                let new_tokens = Tokens {
-                    access: access_token,
-                    refresh: new_refresh_token
-                };
-
-                let json_body = match serde_json::to_string(&new_tokens) {
-                    Ok(body) => body,
-                    Err(_) => return HttpResponse::InternalServerError().body("Failed to serialize response"),
-                };
-
-                HttpResponse::Ok().body(json_body)
-            }
-            //if not successful, it logs an error
-            Err(error) => {
-                let error_message = error.to_string();
-                if error_message.contains("duplicate key value violates unique constraint") {
-                    println!("{}", error);
-                    return HttpResponse::BadRequest().body("Username or Email already exists");
-                }
-
-                println!("{error}");
-                error!("Failed to insert new user: {}", error);
-                HttpResponse::InternalServerError().body("Failed to create user")
-            }
-        }
+    pool.metrics.observe_bcrypt("hash", start_time.elapsed());
+    // The account, its password history and its first session are written together, so a
+    // failure part way through can't leave a user nobody has tokens for
+    let mut tx = pool.db.begin().await?;
+
+    let email = normalize_email(&new_user.email, pool.settings.email_lowercase_local_part);
+    let user_id = match users::insert_user(&mut tx, &new_user.username, &email, &hashed_password, now).await {
+            Ok(user_id) => user_id,
+            //if not successful, the error is converted into an AppError
+            Err(error) => return match AppError::from(error) {
+                AppError::Conflict(_) => Err(AppError::conflict(ErrorCode::UserExists, "Username or Email already exists")),
+                error => Err(error),
+            },
+        };
+
+    let history_size = pool.settings.password_history_size;
+    if history_size > 0 {
+        users::record_password(&mut tx, user_id, &hashed_password, history_size).await?;
+    }
+
+    let claimed = match &guest {
+        Some(guest) => game_sessions::claim_guest(&mut tx, guest, user_id, now).await?,
+        None => Vec::new(),
+    };
+    add_claimed(&mut tx, user_id, &claimed, now).await?;
+
+    let (_, new_tokens) = issue_tokens(&mut tx, user_id, None, &ClientInfo::from_request(req), &pool.signing_keys, &pool.settings.jwt, pool.clock.now()).await?;
+    tx.commit().await?;
+
+    if !claimed.is_empty() {
+        log_auth_event(&pool.db, Some(user_id), "guest_sessions_claimed", &claimed.len().to_string()).await;
+    }
+
+    Ok(HttpResponse::Ok().json(new_tokens))
 }
 
-#[get("/")]
+// The id in a guest token that's still good. Anything else is refused rather than
+// registering without the sessions the caller expected to keep.
+async fn guest_of(pool: &AppState, token: &str) -> Result<String, AppError> {
+    authenticate_token(token, pool)
+        .await
+        .ok()
+        .and_then(|user| user.guest)
+        .ok_or_else(|| AppError::bad_request(ErrorCode::InvalidToken, "guest_token isn't a guest token that's still valid"))
+}
+
+// Lets someone try the solver without an account. Nothing is stored: the token names a
+// made up guest id, which the anonymous sessions started with it are recorded under so
+// registering with the token can claim them.
+#[utoipa::path(
+    tag = "users",
+    context_path = "/api/v1/users",
+    responses(
+        (status = 200, description = "A short-lived guest token", body = GuestToken, example = json!(examples::guest_token())),
+        (status = 429, description = "Too many guest tokens asked for from this client", body = ErrorResponse),
+        (status = 503, description = "Guest access is switched off", body = ErrorResponse),
+    )
+)]
+#[post("/guest", wrap = "RateLimit::new(GUEST)")]
+pub async fn create_guest(pool: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    pool.features.require(GUEST_ACCESS)?;
+
+    let now = pool.clock.now();
+    let guest_id = uuid::Uuid::new_v4().simple().to_string();
+    let access = generate_guest_token(&pool.signing_keys, &guest_id, &pool.settings.jwt, now)?;
+
+    Ok(HttpResponse::Ok().json(GuestToken { access, expires_at: now + pool.settings.jwt.guest_ttl }))
+}
+
+fn username_rejected(reason: String) -> AppError {
+    AppError::validation(ErrorCode::UsernameNotAllowed, reason)
+}
+
+#[utoipa::path(
+    tag = "users",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(PageQuery),
+    responses(
+        (status = 200, description = "A page of users, oldest first", body = UserPage, example = json!(examples::user_page())),
+        (status = 400, description = "page or per_page isn't a number", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 422, description = "page or per_page out of range", body = ErrorResponse),
+    )
+)]
+#[get("")]
 // defineing function, it take the application state as param, which allows you to share app data
-// "impl Responder" means mean the function is returning a value that can be converted to an Http
-// response
-pub async fn get_all_users(pool: web::Data<AppState>) -> impl Responder {
-    //This creates a row vairale with a SQL query to the database to retrieve all of the records in the users table.
-    let rows = sqlx::query("SELECT id, username, email, password, created_at, updated_at FROM users")
-    //The fetch_all() method sends the query to the database and returns a vector of rows representing 
-    //the results of the query. We store this vector of rows in a variable called rows
-        .fetch_all(&pool.db)
+// "Result<HttpResponse, AppError>" means the function returns either a response or an error,
+// which actix turns into an error response
+pub async fn get_all_users(pool: web::Data<AppState>, req: HttpRequest, pagination: Pagination) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+
+    //This asks the users repository for one page of the users table, each one mapped onto a UserResponse
+    let users = users::list_users(&pool.db, pagination.limit(), pagination.offset())
         .await
-        // unwrap  returns the values from the query
-        .unwrap();
-    // users is a variable that stores the data from our query, 
-    // where each row returned by the query is represented as a struct UserResponse
-    let users: Vec<UserResponse> = rows
-        //.into_iter() creates an iterator over the rows vector so that we can process each row individually
-        .into_iter()
-        //map() method applies a transformation to each element of the iterator, in this case, 
-        //we are constructing a new UserResponse object for each row.
-        .map(|row| {
-            UserResponse {
-                //.get() is a method provided by the Row struct of the sqlx crate. It's used to retrieve the value of a column from a row.
-                id: row.get("id"),
-                username: row.get("username"),
-                email: row.get("email"),
-                password: row.get("password"),
-                created_at: row.get("created_at"),
-                updated_at: row.get("updated_at"),
+        // ? returns the users, or hands the error back to be turned into a 500
+        ?;
+    let total = users::count_users(&pool.db).await?;
+
+    //HTTP response with a status code of 200 Ok, indicating that the request has been successfully processed. 
+    //The json() method serializes the page of users into a JSON string
+    Ok(HttpResponse::Ok().json(pagination.page_of(users, total)))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Dashboard numbers", body = UserMetrics, example = json!(examples::user_metrics())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+    )
+)]
+#[get("/metrics")]
+pub async fn user_metrics(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+
+    let now = pool.clock.now();
+
+    let (total_users, active_last_7_days, verified_users) = users::user_counts(&pool.db, now - Duration::days(7)).await?;
+
+    let verified_email_percentage = if total_users > 0 {
+        100.0 * verified_users as f64 / total_users as f64
+    } else {
+        0.0
+    };
+
+    // Bucketed here rather than with generate_series, which SQLite doesn't have
+    let first_day = now.date_naive() - Duration::days(REGISTRATION_DAYS - 1);
+    let created = users::created_since(&pool.db, Utc.from_utc_datetime(&first_day.and_hms_opt(0, 0, 0).unwrap())).await?;
+
+    let registrations = (0..REGISTRATION_DAYS)
+        .map(|offset| {
+            let day = first_day + Duration::days(offset);
+            DailyCount {
+                day,
+                count: created.iter().filter(|created_at| created_at.date_naive() == day).count() as i64,
             }
         })
-    //collect() method is called on the iterator to collect all the transformed elements into a new vector of type Vec<UserResponse>.
-    .collect();
+        .collect();
 
-    //HTTP response with a status code of 200 Ok, indicating that the request has been successfully processed. 
-    //The json() method serializes the users variable into a JSON string
-    HttpResponse::Ok().json(users)
+    Ok(HttpResponse::Ok().json(UserMetrics {
+        total_users,
+        registrations,
+        active_last_7_days,
+        verified_email_percentage,
+    }))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    request_body(
+        content = String,
+        content_type = "text/csv",
+        description = "A header naming the username, email, password_hash and created_at columns in any order, then one account per line. \
+            password_hash is a bcrypt hash and may be empty or left out, such accounts have to set a password before they can log in. \
+            created_at is an RFC 3339 timestamp.",
+        example = json!(examples::user_import_csv()),
+    ),
+    responses(
+        (status = 200, description = "Rows imported, with every row that wasn't", body = UserImportReport, example = json!(examples::user_import_report())),
+        (status = 400, description = "The header is missing a column", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 413, description = "The file is too large", body = ErrorResponse),
+    )
+)]
+#[post("/import")]
+pub async fn import_users(pool: web::Data<AppState>, req: HttpRequest, payload: web::Payload) -> Result<HttpResponse, AppError> {
+    let admin = require_admin(&req, &pool).await?;
+
+    let body = payload
+        .to_bytes_limited(IMPORT_BODY_LIMIT)
+        .await
+        .map_err(|_| AppError::payload_too_large(IMPORT_BODY_LIMIT))?
+        .map_err(|error| AppError::bad_request(ErrorCode::InvalidBody, error.to_string()))?;
+
+    let (rows, mut problems) = parse_user_csv(&body, pool.settings.email_lowercase_local_part)
+        .map_err(|message| AppError::bad_request(ErrorCode::InvalidCsv, message))?;
+    let (inserted, import_problems) = import_rows(&pool.db, &rows, pool.settings.password_history_size, pool.clock.now()).await;
+    problems.extend(import_problems);
+    problems.sort_by_key(|problem| problem.row);
+
+    let skipped = problems.iter().filter(|problem| problem.outcome == ImportOutcome::Skipped).count();
+    let failed = problems.len() - skipped;
+    log_auth_event(&pool.db, None, "users_imported", &format!("by admin {}: {} inserted, {} skipped, {} failed", admin.id, inserted, skipped, failed)).await;
+
+    Ok(HttpResponse::Ok().json(UserImportReport { inserted, skipped, failed, problems }))
 }
 
+#[utoipa::path(
+    tag = "users",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "User id")),
+    responses(
+        (status = 200, description = "The user", body = UserResponse, example = json!(examples::user())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Another user's account", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
+    )
+)]
 #[get("/{id}")]
-pub async fn get_user_by_id(pool: web::Data<AppState>, path: web::Path<(i32,)>) -> impl Responder {
+pub async fn get_user_by_id(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
     let (id,) = path.into_inner();
+    require_owner_or_admin(&req, &pool, id).await?;
 
-    let query = sqlx::query_as::<_, UserResponse>(
-            "SELECT id, username, email, password, created_at, updated_at FROM users WHERE id = $1"
-        )
-        .bind(id)
-        .fetch_one(&pool.db)
-        .await;
+    let user = users::find_by_id(&pool.db, id)
+        .await?
+        .ok_or_else(|| AppError::not_found("User not found"))?;
 
-    match query {
-        Ok(user) => HttpResponse::Ok().json(user),
-        Err(_) => HttpResponse::NotFound().json("User not found"),
-    }
+    Ok(HttpResponse::Ok().json(user))
 }
 
-#[put("/update/{id}")]
-pub async fn update_user(pool: web::Data<AppState>, path: web::Path<(i32,)>, updated_user: web::Json<UpdateUser>) -> impl Responder{
+#[utoipa::path(
+    tag = "users",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "User id")),
+    request_body(content = UpdateUser, example = json!(examples::update_user())),
+    responses(
+        (status = 200, description = "User updated, at its new version", body = UserResponse, example = json!(examples::user())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Another user's account", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
+        (status = 409, description = "Username or email taken, or the user was edited since `version`. A stale edit gets the user as it is now in `details.current`.", body = ErrorResponse, example = json!(examples::stale_version())),
+        (status = 422, description = "Username not allowed", body = ErrorResponse),
+    )
+)]
+#[put("/{id}")]
+pub async fn update_user(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, updated_user: web::Json<UpdateUser>) -> Result<HttpResponse, AppError> {
     let (id,) = path.into_inner();
-    let user = updated_user.into_inner();
+    require_owner_or_admin(&req, &pool, id).await?;
+    save_profile(&pool, id, updated_user.into_inner()).await
+}
 
-    let query = sqlx::query(
-            "UPDATE users SET username = $1, email = $2 WHERE id = $3"
-        )
-        .bind(user.username)
-        .bind(user.email)
-        .bind(id)
-        .execute(&pool.db)
-        .await;
-
-    match query {
-        Ok(_) => HttpResponse::Ok().json("User updated successfully"),
-        Err(error) => {
-            eprintln!("Error: {}", error);
-            HttpResponse::InternalServerError().json("Failed to update user")
+// The same full update as PUT, for clients that send profile edits as PATCH
+#[utoipa::path(
+    tag = "users",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "User id")),
+    request_body(content = UpdateUser, example = json!(examples::update_user())),
+    responses(
+        (status = 200, description = "User updated, at its new version", body = UserResponse, example = json!(examples::user())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Another user's account", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
+        (status = 409, description = "Username or email taken, or the user was edited since `version`. A stale edit gets the user as it is now in `details.current`.", body = ErrorResponse, example = json!(examples::stale_version())),
+        (status = 422, description = "Username not allowed", body = ErrorResponse),
+    )
+)]
+#[patch("/{id}")]
+pub async fn patch_user(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, updated_user: web::Json<UpdateUser>) -> Result<HttpResponse, AppError> {
+    let (id,) = path.into_inner();
+    require_owner_or_admin(&req, &pool, id).await?;
+    save_profile(&pool, id, updated_user.into_inner()).await
+}
+
+// The routes addressing an account by id are for its owner, and admins managing it
+async fn require_owner_or_admin(req: &HttpRequest, pool: &AppState, id: i32) -> Result<AuthUser, AppError> {
+    let caller = require_user(req, pool).await?;
+
+    if caller.id != id && !caller.is_admin() {
+        return Err(not_owner("Only the account's owner or an admin can do this"));
+    }
+
+    Ok(caller)
+}
+
+async fn save_profile(pool: &web::Data<AppState>, id: i32, user: UpdateUser) -> Result<HttpResponse, AppError> {
+    let current_username = users::username_of(&pool.db, id).await?;
+
+    if current_username.as_deref() != Some(user.username.as_str()) {
+        if let Some(reason) = username_rejection(&pool.db, &user.username).await {
+            return Err(username_rejected(reason));
         }
     }
+
+    let email = normalize_email(&user.email, pool.settings.email_lowercase_local_part);
+    let saved = users::update_profile(&pool.db, id, &user.username, &email, user.version, pool.clock.now())
+        .await
+        .map_err(|error| match AppError::from(error) {
+            AppError::Conflict(_) => AppError::conflict(ErrorCode::UserExists, "Username or Email already exists"),
+            error => error,
+        })?;
+
+    match saved {
+        Some(saved) => Ok(HttpResponse::Ok().json(saved)),
+        // Someone saved first, send what they saved so the client can merge
+        None => match users::find_by_id(&pool.db, id).await? {
+            Some(current) => Err(AppError::Conflict(
+                ErrorInfo::new(ErrorCode::StaleVersion, "The user was edited since this version, merge with the current one and retry")
+                    .with_details(json!({ "version": user.version, "current": current })),
+            )),
+            None => Err(AppError::not_found("User not found")),
+        },
+    }
 }
 
+#[utoipa::path(
+    tag = "users",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "User id")),
+    request_body(content = UpdatePassword, example = json!(examples::update_password())),
+    responses(
+        (status = 200, description = "Password updated"),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Another user's account", body = ErrorResponse),
+        (status = 422, description = "Password was used recently", body = ErrorResponse),
+    )
+)]
 #[put("/update_password/{id}")]
-pub async fn update_user_password(pool: web::Data<AppState>, path: web::Path<(i32,)>, updated_user: web::Json<UpdatePassword>) -> impl Responder{
+pub async fn update_user_password(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, updated_user: web::Json<UpdatePassword>) -> Result<HttpResponse, AppError> {
     let (id,) = path.into_inner();
+    require_owner_or_admin(&req, &pool, id).await?;
     let user = updated_user.into_inner();
+    let history_size = pool.settings.password_history_size;
 
-    let hashed_password = match hash_password(&user.password) {
-        Ok(hashed) => hashed,
-        Err(error) => {
-            error!("Failed to hash password: {}", error);
-            return HttpResponse::InternalServerError().body("Failed to create user");
-        }
-    };
+    if history_size > 0 && password_recently_used(&pool.db, id, &user.password, history_size).await? {
+        return Err(password_reused(history_size));
+    }
 
-    let query = sqlx::query(
-            "UPDATE users SET password = $1 WHERE id = $2"
-        )
-        .bind(hashed_password)
-        .bind(id)
-        .execute(&pool.db)
-        .await;
+    let start_time = Instant::now();
+    let hashed_password = hash_password(&user.password, pool.settings.bcrypt_cost)?;
+    pool.metrics.observe_bcrypt("hash", start_time.elapsed());
 
-    match query {
-        Ok(_) => HttpResponse::Ok().json("User password updated"),
-        Err(error) => {
-            eprintln!("Error: {}", error);
-            HttpResponse::InternalServerError().json("Failed to update user")
-        }
+    // Recorded together with the change so the history can't miss a password that was set
+    let mut tx = pool.db.begin().await?;
+
+    users::update_password(&mut tx, id, &hashed_password).await?;
+
+    if history_size > 0 {
+        users::record_password(&mut tx, id, &hashed_password, history_size).await?;
+    }
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json("User password updated"))
+}
+
+// Checks a candidate against the current password and the stored history. The current
+// hash is checked directly so accounts created before the history existed are covered.
+async fn password_recently_used<'e>(db: impl Executor<'e, Database = Any>, user_id: i32, candidate: &str, history_size: i64) -> Result<bool, AppError> {
+    let hashes = users::recent_password_hashes(db, user_id, history_size).await?;
+
+    Ok(hashes.iter().any(|hashed| verify_password(candidate, hashed)))
+}
+
+fn password_reused(history_size: i64) -> AppError {
+    AppError::validation(ErrorCode::PasswordReused, format!("Password must differ from your last {} passwords", history_size))
+}
+
+#[utoipa::path(
+    tag = "auth",
+    context_path = "/api/v1/users",
+    request_body(content = PasswordResetRequest, example = json!(examples::password_reset_request())),
+    responses(
+        (status = 202, description = "A reset token is mailed to the address if an account has it"),
+    )
+)]
+#[post("/password-reset", wrap = "RateLimit::new(AUTH)")]
+pub async fn request_password_reset(pool: web::Data<AppState>, request: web::Json<PasswordResetRequest>) -> Result<HttpResponse, AppError> {
+    let email = normalize_email(&request.email, pool.settings.email_lowercase_local_part);
+    let reset_token = random_token();
+    let now = pool.clock.now();
+
+    // Answered the same either way, so the endpoint can't be used to find out who has an account
+    if let Some((user_id, address)) = users::set_password_reset(&pool.db, &email, &reset_token, now + Duration::minutes(PASSWORD_RESET_TTL_MINUTES)).await? {
+        let minutes = PASSWORD_RESET_TTL_MINUTES.to_string();
+        let values = [("token", reset_token.as_str()), ("minutes", minutes.as_str())];
+        queue_email(&pool.db, &PASSWORD_RESET, &address, &values, pool.settings.mail.max_attempts, now).await?;
+        log_auth_event(&pool.db, Some(user_id), "password_reset_requested", "").await;
+    }
+
+    Ok(HttpResponse::Accepted().json("If an account has this address, a reset email is on its way"))
+}
+
+#[utoipa::path(
+    tag = "auth",
+    context_path = "/api/v1/users",
+    request_body(content = PasswordReset, example = json!(examples::password_reset())),
+    responses(
+        (status = 200, description = "Password set, every session logged out"),
+        (status = 400, description = "Invalid, expired or already used token", body = ErrorResponse),
+        (status = 422, description = "Password was used recently", body = ErrorResponse),
+    )
+)]
+#[post("/password-reset/confirm", wrap = "RateLimit::new(AUTH)")]
+pub async fn reset_password(pool: web::Data<AppState>, reset: web::Json<PasswordReset>) -> Result<HttpResponse, AppError> {
+    let reset = reset.into_inner();
+    let history_size = pool.settings.password_history_size;
+
+    let mut tx = pool.db.begin().await?;
+
+    let user_id = users::lock_password_reset(&mut tx, pool.db.any_kind(), &reset.token, pool.clock.now())
+        .await?
+        .ok_or_else(|| AppError::bad_request(ErrorCode::InvalidToken, "Invalid or expired token"))?;
+
+    if history_size > 0 && password_recently_used(&mut tx, user_id, &reset.password, history_size).await? {
+        return Err(password_reused(history_size));
+    }
+
+    let start_time = Instant::now();
+    let hashed_password = hash_password(&reset.password, pool.settings.bcrypt_cost)?;
+    pool.metrics.observe_bcrypt("hash", start_time.elapsed());
+
+    users::reset_password(&mut tx, user_id, &hashed_password).await?;
+    if history_size > 0 {
+        users::record_password(&mut tx, user_id, &hashed_password, history_size).await?;
+    }
+    tx.commit().await?;
+
+    pool.auth_cache.token_versions.invalidate(&user_id);
+    log_auth_event(&pool.db, Some(user_id), "password_reset", "").await;
+
+    Ok(HttpResponse::Ok().json("Password reset"))
+}
+
+#[utoipa::path(
+    tag = "me",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    request_body(content = EmailChange, example = json!(examples::email_change())),
+    responses(
+        (status = 202, description = "Confirmation email sent to the new address"),
+        (status = 400, description = "New email matches the current one", body = ErrorResponse),
+        (status = 401, description = "Not logged in or wrong password", body = ErrorResponse),
+        (status = 409, description = "Email taken", body = ErrorResponse),
+    )
+)]
+#[post("/me/email")]
+pub async fn request_email_change(pool: web::Data<AppState>, req: HttpRequest, change: web::Json<EmailChange>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+
+    let (current_email, stored_password) = users::email_and_password(&pool.db, user_id)
+        .await?
+        .ok_or_else(token_revoked)?;
+
+    if !verify_password(&change.password, &stored_password) {
+        return Err(invalid_credentials());
+    }
+
+    let new_email = normalize_email(&change.email, pool.settings.email_lowercase_local_part);
+    if current_email.to_lowercase() == new_email.to_lowercase() {
+        return Err(AppError::bad_request(ErrorCode::EmailUnchanged, "New email matches the current email"));
+    }
+
+    if users::email_taken(&pool.db, &new_email, user_id).await? {
+        return Err(email_exists());
+    }
+
+    // Overwriting the token invalidates any earlier pending change
+    let confirmation_token = random_token();
+    let expires_at = pool.clock.now() + Duration::hours(EMAIL_CHANGE_TTL_HOURS);
+
+    users::set_pending_email(&pool.db, user_id, &new_email, &confirmation_token, expires_at).await?;
+
+    let hours = EMAIL_CHANGE_TTL_HOURS.to_string();
+    let values = [("token", confirmation_token.as_str()), ("hours", hours.as_str())];
+    queue_email(&pool.db, &CONFIRM_EMAIL, &new_email, &values, pool.settings.mail.max_attempts, pool.clock.now()).await?;
+
+    Ok(HttpResponse::Accepted().json("Confirmation email sent"))
+}
+
+#[utoipa::path(
+    tag = "users",
+    context_path = "/api/v1/users",
+    params(ConfirmEmailQuery),
+    responses(
+        (status = 200, description = "Email changed"),
+        (status = 400, description = "Invalid or expired token", body = ErrorResponse),
+        (status = 409, description = "Email taken in the meantime", body = ErrorResponse),
+    )
+)]
+#[get("/confirm-email")]
+pub async fn confirm_email(pool: web::Data<AppState>, query: web::Query<ConfirmEmailQuery>) -> Result<HttpResponse, AppError> {
+    let now = pool.clock.now();
+
+    let mut tx = pool.db.begin().await?;
+
+    let (user_id, old_email) = users::lock_pending_email_change(&mut tx, pool.db.any_kind(), &query.token, now)
+        .await?
+        .ok_or_else(|| AppError::bad_request(ErrorCode::InvalidToken, "Invalid or expired token"))?;
+
+    // The unique constraint on email settles any race with another account claiming the
+    // same address between the request and the confirmation
+    let new_email = users::confirm_pending_email(&mut tx, user_id, now)
+        .await
+        .map_err(|error| match AppError::from(error) {
+            AppError::Conflict(_) => email_exists(),
+            error => error,
+        })?;
+
+    tx.commit().await?;
+
+    if let Err(error) = queue_email(&pool.db, &EMAIL_CHANGED, &old_email, &[("email", &new_email)], pool.settings.mail.max_attempts, now).await {
+        error!("Failed to notify previous email: {}", error);
     }
+
+    log_auth_event(&pool.db, Some(user_id), "email_changed", &format!("{} -> {}", old_email, new_email)).await;
+
+    Ok(HttpResponse::Ok().json("Email confirmed"))
 }
 
-#[delete("/delete/{id}")]
-pub async fn delete_user(pool: web::Data<AppState>, path: web::Path<(i32,)>) -> impl Responder {
+fn email_exists() -> AppError {
+    AppError::conflict(ErrorCode::EmailExists, "Email already exists")
+}
+
+// Admins may suspend or log out regular users. Suspending another admin is refused so a
+// single compromised admin account can't lock the other admins out.
+async fn load_moderation_target(pool: &AnyPool, admin: &AuthUser, target_id: i32) -> Result<String, AppError> {
+    let role = users::role_of(pool, target_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("User not found"))?;
+
+    if target_id == admin.id {
+        return Err(AppError::bad_request(ErrorCode::SelfModeration, "Admins cannot moderate their own account"));
+    }
+
+    Ok(role)
+}
+
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "User id")),
+    request_body(content = SuspendUser, example = json!(examples::suspend_user())),
+    responses(
+        (status = 200, description = "User suspended and logged out"),
+        (status = 400, description = "Admins cannot moderate themselves", body = ErrorResponse),
+        (status = 403, description = "Not an admin, or the target is an admin", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
+    )
+)]
+#[post("/{id}/suspend")]
+pub async fn suspend_user(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, suspension: web::Json<SuspendUser>) -> Result<HttpResponse, AppError> {
+    let admin = require_admin(&req, &pool).await?;
     let (id,) = path.into_inner();
 
-    let query = sqlx::query("DELETE FROM users WHERE id = $1")
-        .bind(id)
-        .execute(&pool.db)
-        .await;
+    let role = load_moderation_target(&pool.db, &admin, id).await?;
 
-    match query {
-        Ok(_) => HttpResponse::Ok().json("User deleted successfully"),
-        Err(_) => HttpResponse::InternalServerError().json("Failed to delete user"),
+    if role == ADMIN_ROLE {
+        return Err(AppError::forbidden("Admins cannot suspend other admins"));
     }
+
+    users::suspend(&pool.db, id, pool.clock.now(), suspension.until, &suspension.reason).await?;
+    pool.auth_cache.token_versions.invalidate(&id);
+
+    log_auth_event(&pool.db, Some(id), "account_suspended", &format!("by admin {}: {}", admin.id, suspension.reason)).await;
+
+    Ok(HttpResponse::Ok().json("User suspended"))
 }
 
-async fn validate_credentials(pool: &web::Data<AppState>, username: &str, password: &str) -> Option<i32> {
-    // if I dont need to bind anything, use the query! macro instead of query
-    let query_result = sqlx::query!(
-        r#"
-        SELECT id, password FROM users WHERE username = $1
-        "#,
-        username
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "User id")),
+    responses(
+        (status = 200, description = "Suspension lifted"),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
     )
-    .fetch_optional(&pool.db)
-    .await
-    .expect("Failed to execute SQL query");
+)]
+#[post("/{id}/unsuspend")]
+pub async fn unsuspend_user(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let admin = require_admin(&req, &pool).await?;
+    let (id,) = path.into_inner();
 
-    if let Some(row) = query_result {
-        let stored_password = row.password;
+    load_moderation_target(&pool.db, &admin, id).await?;
 
-        let start_time = Instant::now();
-            if verify_password(password, &stored_password) {
-                // Return the user ID if the credentials are valid
-                let total_duration = start_time.elapsed();
-                println!("Total validation time: {:?}", total_duration);
-                return Some(row.id);
-            }
+    users::unsuspend(&pool.db, id).await?;
+
+    log_auth_event(&pool.db, Some(id), "account_unsuspended", &format!("by admin {}", admin.id)).await;
+
+    Ok(HttpResponse::Ok().json("User unsuspended"))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "User id")),
+    responses(
+        (status = 200, description = "Every session of the user revoked"),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
+    )
+)]
+#[post("/{id}/force-logout")]
+pub async fn force_logout(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let admin = require_admin(&req, &pool).await?;
+    let (id,) = path.into_inner();
+
+    load_moderation_target(&pool.db, &admin, id).await?;
+
+    users::bump_token_version(&pool.db, id).await?;
+    pool.auth_cache.token_versions.invalidate(&id);
+
+    log_auth_event(&pool.db, Some(id), "forced_logout", &format!("by admin {}", admin.id)).await;
+
+    Ok(HttpResponse::Ok().json("User logged out"))
+}
+
+#[utoipa::path(
+    tag = "me",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    request_body(content = UpdateSettings, example = json!(examples::update_settings())),
+    responses(
+        (status = 200, description = "Settings updated"),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    )
+)]
+#[put("/me/settings")]
+pub async fn update_settings(pool: web::Data<AppState>, req: HttpRequest, settings: web::Json<UpdateSettings>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+
+    users::update_notify_new_device(&pool.db, user_id, settings.notify_new_device).await?;
+
+    Ok(HttpResponse::Ok().json("Settings updated"))
+}
+
+#[utoipa::path(
+    tag = "me",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Saved preferences, with defaults for anything never saved", body = Preferences, example = json!(examples::preferences())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    )
+)]
+#[get("/me/preferences")]
+pub async fn get_preferences(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+
+    let preferences = users::find_preferences(&pool.db, user_id).await?.unwrap_or_default();
+
+    Ok(HttpResponse::Ok().json(preferences))
+}
+
+#[utoipa::path(
+    tag = "me",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    request_body(content = UpdatePreferences, example = json!(examples::update_preferences())),
+    responses(
+        (status = 200, description = "The preferences after the update", body = Preferences, example = json!(examples::preferences())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 422, description = "Unknown key, the message lists the valid ones, or a value of the wrong type or out of range", body = ErrorResponse),
+    )
+)]
+#[put("/me/preferences")]
+pub async fn update_preferences(pool: web::Data<AppState>, req: HttpRequest, update: web::Json<UpdatePreferences>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let update = update.into_inner();
+
+    if let Some(length) = update.word_length {
+        if !(MIN_WORD_LENGTH..=MAX_WORD_LENGTH).contains(&length) {
+            let info = ErrorInfo::new(ErrorCode::InvalidField, format!("word_length must be between {} and {}", MIN_WORD_LENGTH, MAX_WORD_LENGTH))
+                .with_details(json!({ "field": "word_length" }));
+            return Err(AppError::Validation(info));
+        }
     }
 
-    None
+    let mut tx = pool.db.begin().await?;
+    let mut preferences = users::find_preferences(&mut tx, user_id).await?.unwrap_or_default();
+    preferences.hard_mode = update.hard_mode.unwrap_or(preferences.hard_mode);
+    preferences.colorblind = update.colorblind.unwrap_or(preferences.colorblind);
+    preferences.word_length = update.word_length.unwrap_or(preferences.word_length);
+    preferences.sort_order = update.sort_order.unwrap_or(preferences.sort_order);
+    preferences.profile_visibility = update.profile_visibility.unwrap_or(preferences.profile_visibility);
+    if let Some(opt_outs) = update.notification_opt_outs {
+        users::update_notify_new_device(&mut tx, user_id, !opt_outs.contains(&NotificationKind::NewDevice)).await?;
+        preferences.notification_opt_outs = opt_outs;
+        preferences.notification_opt_outs.dedup();
+    }
+    users::store_preferences(&mut tx, user_id, &preferences, pool.clock.now()).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(preferences))
 }
 
-#[post("/login")]
-pub async fn login_user(pool: web::Data<AppState>, credentials: web::Json<LoginCredentials>) -> HttpResponse {
-    let user_id = match validate_credentials(&pool, &credentials.username, &credentials.password).await {
-        Some(user_id) => user_id,
-        None => return HttpResponse::Unauthorized().body("Invalid credentials"),
-    };
+// Sessions are the newest link of each unrevoked refresh token chain
+#[utoipa::path(
+    tag = "me",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(PageQuery),
+    responses(
+        (status = 200, description = "A page of active sessions, newest first", body = SessionPage, example = json!(examples::session_page())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 422, description = "page or per_page out of range", body = ErrorResponse),
+    )
+)]
+#[get("/me/sessions")]
+pub async fn list_sessions(pool: web::Data<AppState>, req: HttpRequest, pagination: Pagination) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
 
-    let access_token = match generate_access_token(user_id) {
-        Ok(token) => token,
-        Err(_) => return HttpResponse::InternalServerError().body("Failed to generate token"),
-    };
+    let now = pool.clock.now();
+    let sessions = tokens::active_sessions(&pool.db, user_id, now, pagination.limit(), pagination.offset()).await?;
+    let total = tokens::count_active_sessions(&pool.db, user_id, now).await?;
 
-    let new_refresh_token = match generate_refresh_token(user_id) {
-        Ok(token) => token,
-        Err(_) => return HttpResponse::InternalServerError().body("Failed to generate token"),
-    };
+    Ok(HttpResponse::Ok().json(pagination.page_of(sessions, total)))
+}
 
-    let new_tokens = Tokens {
-        access: access_token,
-        refresh: new_refresh_token
-    };
+// Logs the session out everywhere its tokens are used, including an admin impersonating
+// the user through it
+#[utoipa::path(
+    tag = "me",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Session id, as listed by /me/sessions")),
+    responses(
+        (status = 204, description = "Session revoked"),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such active session of yours", body = ErrorResponse),
+    )
+)]
+#[delete("/me/sessions/{id}")]
+pub async fn revoke_session(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let (id,) = path.into_inner();
+    let now = pool.clock.now();
+
+    let mut tx = pool.db.begin().await?;
+    let (family_id, impersonator) = tokens::find_active_session(&mut tx, user_id, id, now)
+        .await?
+        .ok_or_else(|| AppError::not_found("Session not found"))?;
+    let revoked = tokens::revoke_family(&mut tx, family_id, now).await?;
+    tx.commit().await?;
+
+    for token in &revoked {
+        record_revocation(token, true, &pool).await;
+    }
 
-    let json_body = match serde_json::to_string(&new_tokens) {
-        Ok(body) => body,
-        Err(_) => return HttpResponse::InternalServerError().body("Failed to serialize response"),
+    let detail = match impersonator {
+        Some(admin_id) => format!("session {}, impersonation by admin {}", id, admin_id),
+        None => format!("session {}", id),
     };
+    log_auth_event(&pool.db, Some(user_id), "session_revoked", &detail).await;
 
-    HttpResponse::Ok().body(json_body)
+    Ok(HttpResponse::NoContent().finish())
 }
 
-#[post("/revoke_token")]
-pub async fn revoke_token(pool: web::Data<AppState>, token: web::Json<Token>) -> HttpResponse {
-    let now = Local::now().naive_local();
-
-    match sqlx::query(
-            r#"
-            INSERT INTO revoked_tokens (token, created_at)
-            VALUES ($1, $2)
-            "#)
-        .bind(&token.token)
-        .bind(now)
-        .execute(&pool.db)
-        .await {
-            Ok(_) => HttpResponse::Ok().body("Token added"),
-            Err(error) => {
-                println!("erro: {}", error);
-                error!("Failed to insert new token: {}", error);
-                HttpResponse::InternalServerError().body("Failed to add token")
-            }
-        }
+// Streams everything stored about the caller as one JSON document:
+// {
+//   "schema_version": 1,
+//   "exported_at": "2023-06-10T10:00:00",
+//   "profile": { id, username, email, email_verified, pending_email, role, created_at,
+//                updated_at, suspended_at, suspended_until, suspension_reason },
+//   "sessions": [{ id, family_id, device_label, user_agent, ip_address, created_at,
+//                  rotated_at, revoked }],
+//   "auth_events": [{ id, event_type, detail, created_at }]
+// }
+// Password hashes and token values are never included. New sections may be appended,
+// existing keys keep their meaning within a schema_version.
+#[utoipa::path(
+    tag = "me",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Everything stored about the caller, as a JSON attachment"),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 429, description = "Exported too recently", body = ErrorResponse),
+        (status = 503, description = "Exports are switched off", body = ErrorResponse),
+    )
+)]
+#[get("/me/export")]
+pub async fn export_user_data(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    pool.features.require(DATA_EXPORT)?;
+
+    let now = pool.clock.now();
+
+    if !users::claim_export(&pool.db, user_id, now, now - Duration::minutes(EXPORT_COOLDOWN_MINUTES)).await? {
+        return Err(AppError::too_many_requests("An export was requested recently, try again later"));
+    }
+
+    let profile = users::exported_profile(&pool.db, user_id).await?;
+    let profile = serde_json::to_string(&profile).map_err(|error| AppError::internal(format!("Failed to serialize profile: {}", error)))?;
+
+    let exported_at = serde_json::to_string(&now).unwrap_or_else(|_| "null".to_string());
+    let prefix = format!("{{\"schema_version\":1,\"exported_at\":{},\"profile\":{}", exported_at, profile);
+
+    log_auth_event(&pool.db, Some(user_id), "data_exported", "").await;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header(("Content-Disposition", "attachment; filename=\"export.json\""))
+        .streaming(paged_json_document(pool.db.clone(), user_id, prefix, users::export_sections())))
+}
+
+// Counts come from the access token's user on every authenticated call, this one included
+#[utoipa::path(
+    tag = "me",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Calls per day and route over the last 30 days, oldest first", body = UsageReport, example = json!(examples::usage_report())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    )
+)]
+#[get("/me/usage")]
+pub async fn my_usage(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+
+    let report = pool.usage.report(&pool.db, user_id, pool.clock.now().date_naive()).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[utoipa::path(
+    tag = "admin",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "User id")),
+    responses(
+        (status = 200, description = "The user's calls per day and route over the last 30 days, oldest first", body = UsageReport, example = json!(examples::usage_report())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "No such user", body = ErrorResponse),
+    )
+)]
+#[get("/{id}/usage")]
+pub async fn user_usage(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    require_admin(&req, &pool).await?;
+    let (id,) = path.into_inner();
+
+    if users::find_by_id(&pool.db, id).await?.is_none() {
+        return Err(AppError::not_found("User not found"));
+    }
+    let report = pool.usage.report(&pool.db, id, pool.clock.now().date_naive()).await?;
+
+    Ok(HttpResponse::Ok().json(report))
 }
 
-#[post("/get_new_tokens")]
-pub async fn refresh_tokens(token: web::Json<Token>, pool: web::Data<AppState>) -> HttpResponse {
-    let revoked_token = check_revoked_token(&token.token, &pool.db).await;
+#[utoipa::path(
+    tag = "users",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "User id")),
+    responses(
+        (status = 200, description = "User deleted, their personal data is anonymized once the grace period is over"),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "Another user's account", body = ErrorResponse),
+    )
+)]
+#[delete("/{id}")]
+pub async fn delete_user(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let (id,) = path.into_inner();
+    require_owner_or_admin(&req, &pool, id).await?;
 
-    if revoked_token {
-        return HttpResponse::Unauthorized().body("Token has been revoked");
+    if users::soft_delete(&pool.db, id, pool.clock.now()).await? {
+        pool.auth_cache.token_versions.invalidate(&id);
+        log_auth_event(&pool.db, Some(id), "account_deleted", "").await;
     }
 
-    let token_valid = verify_token(&token.token).unwrap_or(false);
+    Ok(HttpResponse::Ok().json("User deleted successfully"))
+}
+
+// `username` may also be the account's email address
+async fn validate_credentials(pool: &web::Data<AppState>, timings: &RequestTimings, username: &str, password: &str) -> Result<Option<i32>, AppError> {
+    let email = normalize_email(username, pool.settings.email_lowercase_local_part);
+    let query_result = timings.measure("db", users::find_credentials(&pool.db, username, &email)).await?;
 
-    if !token_valid {
-        return HttpResponse::Unauthorized().body("Unauthorized");
+    if let Some((user_id, stored_password, reset_required)) = query_result {
+        // Imported without a password, there's nothing to check against
+        if reset_required {
+            return Err(AppError::Forbidden(ErrorInfo::new(ErrorCode::PasswordResetRequired, "A new password has to be set before logging in")));
+        }
+
+        let start_time = Instant::now();
+        let valid = verify_password(password, &stored_password);
+        pool.metrics.observe_bcrypt("verify", start_time.elapsed());
+        timings.record("bcrypt", start_time.elapsed());
+
+        if valid {
+            // Return the user ID if the credentials are valid
+            return Ok(Some(user_id));
+        }
     }
 
-    let user_id = decode_token_id(&token.token);
+    Ok(None)
+}
+
+fn invalid_credentials() -> AppError {
+    AppError::Unauthorized(ErrorInfo::new(ErrorCode::InvalidCredentials, "Invalid credentials"))
+}
 
-    let access_token = match generate_refresh_token(user_id) {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error generating refresh token: {}", err);
-            return HttpResponse::InternalServerError().finish();
+#[utoipa::path(
+    tag = "auth",
+    context_path = "/api/v1/users",
+    request_body(content = LoginCredentials, example = json!(examples::login_credentials())),
+    responses(
+        (status = 200, description = "Logged in", body = Tokens, example = json!(examples::tokens())),
+        (status = 401, description = "Invalid credentials", body = ErrorResponse),
+        (status = 403, description = "Account suspended, details carry the reason and end, or imported without a password", body = ErrorResponse),
+    )
+)]
+#[post("/login", wrap = "RateLimit::new(AUTH)")]
+pub async fn login_user(pool: web::Data<AppState>, req: HttpRequest, credentials: web::Json<LoginCredentials>) -> Result<HttpResponse, AppError> {
+    let timings = RequestTimings::of(&req);
+    let user_id = match validate_credentials(&pool, &timings, &credentials.username, &credentials.password).await? {
+        Some(user_id) => user_id,
+        None => {
+            pool.metrics.observe_login(false);
+            return Err(invalid_credentials());
         }
     };
 
-    let new_refresh_token = match generate_refresh_token(user_id) {
-        Ok(token) => token,
-        Err(err) => {
-            println!("Error generating refresh token: {}", err);
-            return HttpResponse::InternalServerError().finish();
+    let suspension = timings.measure("db", users::active_suspension(&pool.db, user_id, pool.clock.now())).await?;
+
+    if let Some((reason, until)) = suspension {
+        pool.metrics.observe_login(false);
+        return Err(AppError::Forbidden(
+            ErrorInfo::new(ErrorCode::AccountSuspended, "This account is suspended")
+                .with_details(json!({ "reason": reason, "until": until })),
+        ));
+    }
+
+    let mut conn = timings.measure("db", pool.db.acquire()).await?;
+
+    let last_login = timings.measure("db", users::record_login(&mut conn, user_id, pool.clock.now())).await;
+
+    if let Err(error) = last_login {
+        error!("Failed to record last login: {}", error);
+    }
+
+    let client = ClientInfo::from_request(&req);
+
+    // Must be checked before issuing, since issuing records this fingerprint
+    let seen_device = timings.measure("db", tokens::device_seen(&mut conn, user_id, &client.fingerprint))
+        .await
+        .unwrap_or(true);
+
+    let (_, new_tokens) = timings.measure("tokens", issue_tokens(&mut conn, user_id, None, &client, &pool.signing_keys, &pool.settings.jwt, pool.clock.now())).await?;
+
+    let detail = format!("{} from {}", client.device_label, client.ip_address.as_deref().unwrap_or("unknown IP"));
+    log_auth_event(&pool.db, Some(user_id), "login", &detail).await;
+
+    if !seen_device {
+        notify_new_device(&pool, user_id, &detail).await;
+    }
+    pool.metrics.observe_login(true);
+
+    Ok(HttpResponse::Ok().json(new_tokens))
+}
+
+async fn notify_new_device(pool: &AppState, user_id: i32, detail: &str) {
+    log_auth_event(&pool.db, Some(user_id), "new_device_login", detail).await;
+
+    let recipient = users::new_device_recipient(&pool.db, user_id).await;
+
+    let (email, opted_in) = match recipient {
+        Ok(Some(recipient)) => recipient,
+        Ok(None) => return,
+        Err(error) => {
+            error!("Failed to load user for new device notification: {}", error);
+            return;
         }
     };
 
+    if !opted_in {
+        return;
+    }
+
+    if let Err(error) = queue_email(&pool.db, &NEW_DEVICE_LOGIN, &email, &[("device", detail)], pool.settings.mail.max_attempts, pool.clock.now()).await {
+        error!("Failed to send new device notification: {}", error);
+    }
+}
+
+// Creates an access/refresh pair and records the refresh token as the newest link of its
+// rotation chain. A `family_id` of None starts a new chain rooted at the inserted row.
+async fn issue_tokens(conn: &mut AnyConnection, user_id: i32, family_id: Option<i32>, client: &ClientInfo, keys: &SigningKeys, jwt: &JwtSettings, now: DateTime<Utc>) -> Result<(i32, Tokens), AppError> {
+    let token_version = users::token_version(&mut *conn, user_id).await?;
+
+    let access_token = generate_access_token(keys, user_id, token_version, jwt, now)?;
+    let refresh_token = generate_refresh_token(keys, user_id, token_version, jwt, now)?;
+
+    let token_id = tokens::insert_refresh_token(conn, user_id, family_id, &refresh_token, &access_token, client).await?;
+
     let new_tokens = Tokens {
         access: access_token,
-        refresh: new_refresh_token
-    };
-
-    let json_body = match serde_json::to_string(&new_tokens) {
-        Ok(body) => body,
-        Err(_) => return HttpResponse::InternalServerError().body("Failed to serialize response"),
+        refresh: refresh_token
     };
 
-    HttpResponse::Ok().body(json_body)
+    Ok((token_id, new_tokens))
 }
 
-async fn check_revoked_token(token: &str, pool: &PgPool) -> bool {
-    let query = sqlx::query(
-        r#"
-        SELECT token FROM revoked_tokens WHERE token = $1
-        "#,
+// Users can revoke their own tokens, admins anyone's. Revoking a token twice is harmless.
+#[utoipa::path(
+    tag = "auth",
+    context_path = "/api/v1/users",
+    security(("bearer_auth" = [])),
+    request_body(content = Token, example = json!(examples::refresh_token())),
+    responses(
+        (status = 200, description = "Token revoked, or it already was", body = TokenRevocation, example = json!(examples::token_revocation())),
+        (status = 400, description = "Not a token this service issued, or it has expired", body = ErrorResponse),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 403, description = "The token belongs to someone else", body = ErrorResponse),
     )
-    .bind(token)
-    .fetch_one(pool)
-    .await;
+)]
+#[post("/revoke_token")]
+pub async fn revoke_token(pool: web::Data<AppState>, req: HttpRequest, token: web::Json<Token>) -> Result<HttpResponse, AppError> {
+    let caller = require_user(&req, &pool).await?;
+    let now = pool.clock.now();
 
-    match query {
-        Ok(_) => true,
-        Err(_) => false,
+    let claims = decode_claims(&pool.signing_keys, &token.token, now).map_err(|rejection| match rejection {
+        TokenRejection::Invalid => AppError::bad_request(ErrorCode::InvalidToken, "Not a valid token"),
+        TokenRejection::UnknownKey => AppError::bad_request(ErrorCode::UnknownSigningKey, "Token was signed with a key that is no longer trusted"),
+        TokenRejection::Expired => AppError::bad_request(ErrorCode::TokenExpired, "Token has already expired"),
+    })?;
+    if claims.user_id != caller.id && !caller.is_admin() {
+        return Err(not_owner("Only your own tokens can be revoked"));
     }
+
+    let revoked = tokens::revoke(&pool.db, &token.token, now).await?;
+    if revoked && caller.id != claims.user_id {
+        log_auth_event(&pool.db, Some(claims.user_id), "token_revoked", &format!("by admin {}", caller.id)).await;
+    }
+
+    record_revocation(&token.token, true, &pool).await;
+    Ok(HttpResponse::Ok().json(TokenRevocation { already_revoked: !revoked }))
 }
 
-#[post("/check_access")]
-pub async fn check_access(token: web::Json<Token>, pool: web::Data<AppState>) -> HttpResponse {
-    let revoked_token = check_revoked_token(&token.token, &pool.db).await;
+#[utoipa::path(
+    tag = "auth",
+    context_path = "/api/v1/users",
+    request_body(content = Token, example = json!(examples::refresh_token())),
+    responses(
+        (status = 200, description = "New token pair", body = Tokens, example = json!(examples::tokens())),
+        (status = 401, description = "Invalid, revoked or reused refresh token", body = ErrorResponse),
+    )
+)]
+#[post("/get_new_tokens", wrap = "RateLimit::new(AUTH)")]
+pub async fn refresh_tokens(token: web::Json<Token>, req: HttpRequest, pool: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    // Access tokens are refused here, impersonation and guest ones among them, so those
+    // end with their token and can't be extended
+    authenticate_refresh_token(&token.token, &pool).await?;
 
-    if revoked_token {
-        return HttpResponse::Unauthorized().body("Token has been revoked");
+    let mut tx = pool.db.begin().await?;
+
+    // Locking the row serializes concurrent refreshes of the same token, so the loser
+    // sees the rotation made by the winner instead of rotating a second time
+    let stored = tokens::lock_refresh_token(&mut tx, pool.db.any_kind(), &token.token)
+        .await?
+        // Validly signed but never stored, e.g. a refresh token issued by another deployment
+        .ok_or_else(|| AppError::Unauthorized(ErrorInfo::new(ErrorCode::InvalidToken, "Not a refresh token")))?;
+
+    if stored.revoked {
+        return Err(token_revoked());
     }
 
-    let token_valid = verify_token(&token.token).unwrap_or(false);
+    let family_id = stored.family_id.unwrap_or(stored.id);
+
+    if let Some(successor_id) = stored.replaced_by {
+        let now = pool.clock.now();
+        let within_grace = stored
+            .rotated_at
+            .map(|rotated_at| now - rotated_at <= pool.settings.jwt.refresh_reuse_grace)
+            .unwrap_or(false);
+
+        if within_grace {
+            let successor = tokens::live_successor(&mut tx, successor_id).await?;
+
+            if let Some(successor) = successor {
+                return Ok(HttpResponse::Ok().json(Tokens {
+                    access: successor.access_token,
+                    refresh: successor.token,
+                }));
+            }
+        }
+
+        // A rotated token showing up again outside the grace window means it was copied
+        let revoked = tokens::revoke_family(&mut tx, family_id, pool.clock.now()).await?;
+        tx.commit().await?;
+
+        // The family's access tokens may be cached as valid
+        for token in &revoked {
+            record_revocation(token, true, &pool).await;
+        }
+
+        log_auth_event(&pool.db, Some(stored.user_id), "refresh_token_reused", &format!("revoked token family {}", family_id)).await;
 
-    if !token_valid {
-        return HttpResponse::Unauthorized().body("Unauthorized");
+        return Err(AppError::Unauthorized(ErrorInfo::new(
+            ErrorCode::RefreshTokenReused,
+            "Refresh token was already used, please log in again",
+        )));
     }
 
+    let (successor_id, new_tokens) = issue_tokens(&mut tx, stored.user_id, Some(family_id), &ClientInfo::from_request(&req), &pool.signing_keys, &pool.settings.jwt, pool.clock.now()).await?;
+
+    tokens::mark_rotated(&mut tx, stored.id, successor_id, pool.clock.now()).await?;
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(new_tokens))
+}
+
+#[utoipa::path(
+    tag = "auth",
+    context_path = "/api/v1/users",
+    request_body(content = Token, example = json!(examples::access_token())),
+    responses(
+        (status = 200, description = "Token is valid"),
+        (status = 401, description = "Invalid or revoked token", body = ErrorResponse),
+    )
+)]
+#[post("/check_access")]
+pub async fn check_access(token: web::Json<Token>, pool: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    authenticate_token(&token.token, &pool).await?;
 
-    HttpResponse::Ok().body("access granted")
+    Ok(HttpResponse::Ok().body("access granted"))
 }
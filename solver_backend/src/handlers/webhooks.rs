@@ -0,0 +1,235 @@
+use crate::errors::{self, AppError, ErrorCode};
+use crate::handlers::examples;
+use crate::models::users_models::PageQuery;
+use crate::models::webhooks_models::{NewWebhook, UpdateWebhook, Webhook, WebhookDelivery, WebhookRecord};
+use crate::repositories::webhooks::{self, NewWebhookRecord};
+use crate::utils::audit_utils::log_auth_event;
+use crate::utils::auth_utils::require_user;
+use crate::utils::pagination_utils::Pagination;
+use crate::utils::webhook_utils::{queue_delivery, url_problem, EVENTS, MIN_SECRET_LENGTH, PING};
+use crate::AppState;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use serde_json::json;
+
+// A URL, a secret and a few event names
+const WEBHOOK_BODY_LIMIT: usize = 4 * 1024;
+
+pub fn webhook_routes(conf: &mut web::ServiceConfig) {
+    let scope = web::scope("/webhooks")
+        .app_data(errors::json_config(WEBHOOK_BODY_LIMIT))
+        .service(create_webhook)
+        .service(list_webhooks)
+        .service(update_webhook)
+        .service(delete_webhook)
+        .service(list_deliveries)
+        .service(ping_webhook);
+
+    conf.service(scope);
+}
+
+// Deliveries are POSTs of a JSON payload signed with the secret, see SIGNATURE_HEADER. An
+// admin's endpoint gets every user's events, anyone else's only their own.
+#[utoipa::path(
+    tag = "webhooks",
+    context_path = "/api/v1/webhooks",
+    security(("bearer_auth" = [])),
+    request_body(content = NewWebhook, example = json!(examples::new_webhook())),
+    responses(
+        (status = 201, description = "Endpoint registered", body = Webhook, example = json!(examples::webhook())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 422, description = "The URL isn't a public http or https URL, an event is unknown, or the secret is too short", body = ErrorResponse),
+    )
+)]
+#[post("")]
+pub async fn create_webhook(pool: web::Data<AppState>, req: HttpRequest, webhook: web::Json<NewWebhook>) -> Result<HttpResponse, AppError> {
+    let user = require_user(&req, &pool).await?;
+    check_url(&webhook.url, &pool)?;
+    check_secret(&webhook.secret)?;
+    let events = check_events(&webhook.events)?;
+
+    let record = NewWebhookRecord { user_id: user.id, url: &webhook.url, secret: &webhook.secret, events: &events, all_users: user.is_admin() };
+    let created = webhooks::insert(&pool.db, &record, pool.clock.now()).await?;
+    log_auth_event(&pool.db, Some(user.id), "webhook_created", &format!("{} for {}", created.url, created.events)).await;
+
+    Ok(HttpResponse::Created().json(Webhook::from(created)))
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    context_path = "/api/v1/webhooks",
+    security(("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The caller's endpoints, oldest first", body = [Webhook], example = json!(examples::webhooks())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+    )
+)]
+#[get("")]
+pub async fn list_webhooks(pool: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+
+    let registered: Vec<Webhook> = webhooks::list_for_user(&pool.db, user_id).await?.into_iter().map(Webhook::from).collect();
+    Ok(HttpResponse::Ok().json(registered))
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    context_path = "/api/v1/webhooks",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Webhook id")),
+    request_body(content = UpdateWebhook, example = json!(examples::update_webhook())),
+    responses(
+        (status = 200, description = "The endpoint as it is now", body = Webhook, example = json!(examples::webhook())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such endpoint among the caller's", body = ErrorResponse),
+        (status = 422, description = "The URL isn't a public http or https URL, an event is unknown, or the secret is too short", body = ErrorResponse),
+    )
+)]
+#[put("/{id}")]
+pub async fn update_webhook(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, update: web::Json<UpdateWebhook>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let mut webhook = owned(&pool, path.into_inner().0, user_id).await?;
+    let update = update.into_inner();
+
+    if let Some(url) = update.url {
+        check_url(&url, &pool)?;
+        webhook.url = url;
+    }
+    if let Some(secret) = update.secret {
+        check_secret(&secret)?;
+        webhook.secret = secret;
+    }
+    if let Some(events) = update.events {
+        webhook.events = check_events(&events)?;
+    }
+    match update.enabled {
+        Some(true) if !webhook.enabled => {
+            webhook.enabled = true;
+            webhook.consecutive_failures = 0;
+            webhook.disabled_at = None;
+        }
+        Some(false) if webhook.enabled => {
+            webhook.enabled = false;
+            webhook.disabled_at = Some(pool.clock.now());
+        }
+        _ => {}
+    }
+
+    let now = pool.clock.now();
+    webhooks::update(&pool.db, &webhook, now).await?;
+    webhook.updated_at = now;
+
+    Ok(HttpResponse::Ok().json(Webhook::from(webhook)))
+}
+
+// Deliveries still queued for it are dropped
+#[utoipa::path(
+    tag = "webhooks",
+    context_path = "/api/v1/webhooks",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Webhook id")),
+    responses(
+        (status = 204, description = "Endpoint deleted"),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such endpoint among the caller's", body = ErrorResponse),
+    )
+)]
+#[delete("/{id}")]
+pub async fn delete_webhook(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let (id,) = path.into_inner();
+
+    if !webhooks::delete_for_user(&pool.db, id, user_id).await? {
+        return Err(AppError::not_found("Webhook not found"));
+    }
+    log_auth_event(&pool.db, Some(user_id), "webhook_deleted", &id.to_string()).await;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    tag = "webhooks",
+    context_path = "/api/v1/webhooks",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Webhook id"), PageQuery),
+    responses(
+        (status = 200, description = "A page of the endpoint's deliveries, newest first", body = WebhookDeliveryPage, example = json!(examples::webhook_delivery_page())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such endpoint among the caller's", body = ErrorResponse),
+        (status = 422, description = "page or per_page out of range", body = ErrorResponse),
+    )
+)]
+#[get("/{id}/deliveries")]
+pub async fn list_deliveries(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>, pagination: Pagination) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let webhook = owned(&pool, path.into_inner().0, user_id).await?;
+
+    let deliveries: Vec<WebhookDelivery> = webhooks::list_deliveries(&pool.db, webhook.id, pagination.limit(), pagination.offset()).await?;
+    let total = webhooks::count_deliveries(&pool.db, webhook.id).await?;
+
+    Ok(HttpResponse::Ok().json(pagination.page_of(deliveries, total)))
+}
+
+// Queues a ping event, to check the endpoint is reachable and accepts the signature
+#[utoipa::path(
+    tag = "webhooks",
+    context_path = "/api/v1/webhooks",
+    security(("bearer_auth" = [])),
+    params(("id" = i32, Path, description = "Webhook id")),
+    responses(
+        (status = 202, description = "Ping queued, its delivery shows how it went", body = WebhookDelivery, example = json!(examples::webhook_delivery())),
+        (status = 401, description = "Not logged in", body = ErrorResponse),
+        (status = 404, description = "No such endpoint among the caller's", body = ErrorResponse),
+    )
+)]
+#[post("/{id}/ping")]
+pub async fn ping_webhook(pool: web::Data<AppState>, req: HttpRequest, path: web::Path<(i32,)>) -> Result<HttpResponse, AppError> {
+    let user_id = require_user(&req, &pool).await?.id;
+    let webhook = owned(&pool, path.into_inner().0, user_id).await?;
+    let now = pool.clock.now();
+
+    let payload = json!({ "event": PING, "user_id": user_id, "occurred_at": now, "data": { "webhook_id": webhook.id } }).to_string();
+    let mut tx = pool.db.begin().await?;
+    let delivery_id = queue_delivery(&mut tx, webhook.id, PING, &payload, pool.settings.job_max_attempts, now).await?;
+    let delivery = webhooks::find_delivery(&mut tx, delivery_id).await?.ok_or_else(|| AppError::internal(format!("delivery {} vanished", delivery_id)))?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Accepted().json(delivery))
+}
+
+// Other users' endpoints are reported as missing rather than forbidden, like jobs
+async fn owned(pool: &AppState, id: i32, user_id: i32) -> Result<WebhookRecord, AppError> {
+    webhooks::find_for_user(&pool.db, id, user_id)
+        .await?
+        .ok_or_else(|| AppError::not_found("Webhook not found"))
+}
+
+fn check_url(url: &str, pool: &AppState) -> Result<(), AppError> {
+    match url_problem(url, pool.settings.webhooks.allow_private_hosts) {
+        Some(problem) => Err(AppError::validation(ErrorCode::InvalidWebhookUrl, problem)),
+        None => Ok(()),
+    }
+}
+
+fn check_secret(secret: &str) -> Result<(), AppError> {
+    if secret.chars().count() < MIN_SECRET_LENGTH {
+        return Err(AppError::validation(ErrorCode::InvalidField, format!("secret must have at least {} characters", MIN_SECRET_LENGTH)));
+    }
+    Ok(())
+}
+
+// The events as stored, comma separated without duplicates
+fn check_events(events: &[String]) -> Result<String, AppError> {
+    if events.is_empty() {
+        return Err(AppError::validation(ErrorCode::UnknownWebhookEvent, format!("events must name at least one of {}", EVENTS.join(", "))));
+    }
+    let mut checked: Vec<&str> = Vec::new();
+    for event in events {
+        let known = EVENTS.iter().find(|known| **known == event).ok_or_else(|| {
+            AppError::validation(ErrorCode::UnknownWebhookEvent, format!("{:?} isn't one of {}", event, EVENTS.join(", ")))
+        })?;
+        if !checked.contains(known) {
+            checked.push(known);
+        }
+    }
+    Ok(checked.join(","))
+}
@@ -0,0 +1,14 @@
+use actix_web::{get, web, HttpResponse};
+use crate::AppState;
+
+pub fn well_known_routes(conf: &mut web::ServiceConfig) {
+    let scope = web::scope("/.well-known")
+        .service(jwks);
+
+    conf.service(scope);
+}
+
+#[get("/jwks.json")]
+pub async fn jwks(pool: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(pool.signing_keys.public_jwks())
+}
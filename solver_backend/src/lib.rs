@@ -0,0 +1,55 @@
+pub mod config;
+pub mod errors;
+pub mod handlers;
+pub mod middleware;
+pub mod models;
+pub mod repositories;
+pub mod solver;
+pub mod utils;
+
+use sqlx::{Any, Pool};
+use std::sync::Arc;
+use config::Settings;
+use models::game_models::BestGuesses;
+use repositories::words::WordRepository;
+use utils::cache_utils::AuthCache;
+use utils::clock_utils::Clock;
+use utils::coalesce_utils::SingleFlight;
+use utils::concurrency_utils::ConcurrencyLimits;
+use utils::db_utils::DbStatus;
+use utils::feature_utils::FeatureFlags;
+use utils::jwt_utils::SigningKeys;
+use utils::metrics_utils::Metrics;
+use utils::shutdown_utils::ShutdownSignal;
+use utils::solver_cache_utils::SolverCache;
+use utils::store_utils::Stores;
+use utils::usage_utils::UsageRecorder;
+use utils::word_check_utils::WordCheckStatus;
+
+// This struct represents state
+pub struct AppState {
+    // Postgres or SQLite, picked by the scheme of DATABASE_URL
+    pub db: Pool<Any>,
+    // The word list, read through a trait so game handler tests can use fixed words
+    pub words: Arc<dyn WordRepository>,
+    pub auth_cache: Arc<AuthCache>,
+    pub stores: Stores,
+    pub metrics: Arc<Metrics>,
+    pub settings: Arc<Settings>,
+    // Loaded from the settings at startup, the newest key of the algorithm signs
+    pub signing_keys: Arc<SigningKeys>,
+    pub features: Arc<FeatureFlags>,
+    pub limits: Arc<ConcurrencyLimits>,
+    pub db_status: Arc<DbStatus>,
+    // The system clock outside tests
+    pub clock: Arc<dyn Clock>,
+    pub shutdown: ShutdownSignal,
+    // Authenticated calls not yet written to usage_stats
+    pub usage: Arc<UsageRecorder>,
+    // Best-guess computations underway, shared by identical requests
+    pub best_guesses: Arc<SingleFlight<BestGuesses>>,
+    // The word lists, pattern matrix and opening guesses best-guess works from
+    pub solver_cache: Arc<SolverCache>,
+    // The latest word list integrity check
+    pub word_check: Arc<WordCheckStatus>,
+}
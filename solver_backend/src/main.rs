@@ -1,74 +1,321 @@
-pub mod handlers;
-pub mod models;
-pub mod utils;
-
-use handlers::users::user_routes;
-use handlers::game::game_routes;
-use actix_cors::Cors;
-use actix_web::middleware::Logger;
-use actix_web::{http::header, web::Data, App, HttpServer, web};
+use wordle_solver::handlers::api_routes;
+use wordle_solver::handlers::docs::docs_routes;
+use wordle_solver::handlers::health::health_routes;
+use wordle_solver::handlers::metrics::metrics_routes;
+use wordle_solver::middleware::compression::Compression;
+use wordle_solver::middleware::cors::cors;
+use wordle_solver::middleware::https_redirect::HttpsRedirect;
+use wordle_solver::middleware::request_id::RequestIdentifier;
+use wordle_solver::middleware::request_metrics::RequestMetrics;
+use wordle_solver::middleware::security_headers::SecurityHeaders;
+use wordle_solver::middleware::server_timing::ServerTiming;
+use wordle_solver::handlers::well_known::well_known_routes;
+use actix_web::middleware::{Condition, Logger};
+use actix_web::{web::Data, App, HttpServer};
 use dotenv::dotenv;
-use std::env;
-use sqlx::{postgres::PgPoolOptions, Pool, Postgres};
-
-// This struct represents state
-pub struct AppState {
-    db: Pool<Postgres>,
-}
+use wordle_solver::{errors, AppState};
+use std::sync::Arc;
+use wordle_solver::repositories::words::{DbWords, WordRepository};
+use wordle_solver::utils::cache_utils::AuthCache;
+use wordle_solver::utils::client_ip_utils::ClientIp;
+use wordle_solver::utils::clock_utils::{Clock, SystemClock};
+use wordle_solver::utils::concurrency_utils::ConcurrencyLimits;
+use wordle_solver::utils::db_utils::{open_memory_database, pool_options, run_migrations, wait_for_database, DbStatus};
+use wordle_solver::config::{Settings, Storage};
+use wordle_solver::utils::feature_utils::{spawn_flag_refresher, FeatureFlags};
+use wordle_solver::utils::job_utils::{spawn_job_worker, BuiltinJobs};
+use wordle_solver::utils::jwt_utils::SigningKeys;
+use wordle_solver::utils::mail_utils::mailer_from_settings;
+use wordle_solver::utils::coalesce_utils::SingleFlight;
+use wordle_solver::utils::maintenance_utils::spawn_maintenance;
+use wordle_solver::utils::metrics_utils::Metrics;
+use wordle_solver::utils::seed_utils;
+use wordle_solver::utils::store_utils::Stores;
+use wordle_solver::utils::shutdown_utils::{is_shutting_down, shutdown_channel, termination_signal};
+use wordle_solver::utils::solver_cache_utils::SolverCache;
+use wordle_solver::utils::usage_utils::{spawn_usage_flusher, UsageRecorder, USAGE_FLUSH_INTERVAL};
+use wordle_solver::utils::tracing_utils::{init_tracing, shutdown_tracing};
+use wordle_solver::utils::tls_utils::{server_config, spawn_certificate_reloader, ReloadingCertificate};
+use wordle_solver::utils::word_check_utils::{run_check, WordCheckStatus};
+use wordle_solver::utils::word_sync_utils::spawn_word_sync;
+use tracing::{error, info};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     if std::env::var_os("RUST_LOG").is_none() {
-        std::env::set_var("RUST_LOG", "actix_web=info");
+        std::env::set_var("RUST_LOG", "actix_web=info,wordle_solver=info");
     }
     dotenv().ok();
-    env_logger::init();
 
-    let database_url = match env::var("DATABASE_URL") {
-        Ok(url) => url,
-        Err(e) => {
-            panic!("Failed to read DATABASE_URL: {}", e);
+    let settings = match Settings::from_env() {
+        Ok(settings) => Arc::new(settings),
+        Err(error) => {
+            eprintln!("{}", error);
+            std::process::exit(1);
         }
     };
-
-    let pool = match PgPoolOptions::new()
-        .max_connections(10)
-        .connect(&database_url)
-        .await
-{
-    Ok(pool) => {
-        println!("Connection to the database is successful!");
-        pool
-    }
-    Err(err) => {
-        println!("Failed to connect to the database: {:?}", err);
+    if let Err(error) = init_tracing(settings.otlp.as_ref()) {
+        eprintln!("{}", error);
         std::process::exit(1);
     }
-};
-    println!("Server started successfully");
-
-    HttpServer::new(move || {
-        let cors = Cors::default()
-            .allowed_origin("http://localhost:3000")
-            .allowed_methods(vec!["GET", "POST", "PATCH", "DELETE", "PUT"])
-            .allowed_headers(vec![
-                header::CONTENT_TYPE,
-                header::AUTHORIZATION,
-                header::ACCEPT,
-            ])
-            .supports_credentials();
+
+    // `--migrate-only` applies pending migrations and exits, for a release pipeline step.
+    // `--seed` also fills in development data afterwards, `--force` lets it near real users.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let migrate_only = args.iter().any(|arg| arg == "--migrate-only");
+    let seed = args.iter().any(|arg| arg == "--seed");
+
+    let pool = match settings.storage {
+        Storage::Memory if migrate_only || seed => {
+            error!("--migrate-only and --seed need a database that outlasts the process, not STORAGE=memory");
+            std::process::exit(1);
+        }
+        // Ready before binding, there's nothing to wait for
+        Storage::Memory => match open_memory_database(&settings).await {
+            Ok(pool) => pool,
+            Err(error) => {
+                error!("{}", error);
+                std::process::exit(1);
+            }
+        },
+        // Connections are made on first use, so the server can start answering probes while
+        // the database is still coming up
+        Storage::Database => match pool_options(&settings).connect_lazy(&settings.database_url) {
+            Ok(pool) => pool,
+            Err(err) => {
+                error!("Invalid DATABASE_URL: {}", err);
+                std::process::exit(1);
+            }
+        },
+    };
+    let db_status = Arc::new(DbStatus::default());
+
+    let (shutdown_sender, shutdown) = shutdown_channel();
+
+    // Done before binding so no request ever sees a partly migrated schema
+    if settings.storage == Storage::Database && (migrate_only || seed || settings.run_migrations) {
+        if let Err(error) = run_migrations(&pool, &settings.database_url, &settings.legacy_timezone, settings.db_connect_max_wait, shutdown.clone()).await {
+            error!("{}", error);
+            std::process::exit(1);
+        }
+
+        if seed {
+            let force = args.iter().any(|arg| arg == "--force");
+            match seed_utils::seed(&pool, settings.bcrypt_cost, SystemClock.now(), force).await {
+                Ok(report) => info!("Seeded {} words and users [{}]", report.words_added, report.users_created.join(", ")),
+                Err(error) => {
+                    error!("{}", error);
+                    std::process::exit(1);
+                }
+            }
+        }
+
+        if migrate_only || seed {
+            pool.close().await;
+            shutdown_tracing();
+            return Ok(());
+        }
+    }
+
+    // Checked before binding, so a missing or mismatched certificate stops startup instead
+    // of failing every handshake
+    let certificate = match &settings.tls {
+        Some(tls) => match ReloadingCertificate::load(&tls.cert_path, &tls.key_path) {
+            Ok(certificate) => Some(certificate),
+            Err(error) => {
+                error!("{}", error);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+    let redirect_port = settings.tls.as_ref().filter(|tls| tls.redirect_http).map(|tls| tls.port);
+
+    let signing_keys = match SigningKeys::load(&settings.jwt) {
+        Ok(keys) => Arc::new(keys),
+        Err(error) => {
+            error!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let key_ids: Vec<String> = signing_keys.iter().map(|key| format!("{} ({:?})", key.kid, key.algorithm)).collect();
+    info!("Active JWT key ids: {}", key_ids.join(", "));
+
+    let mailer = match mailer_from_settings(&settings.mail) {
+        Ok(mailer) => mailer,
+        Err(error) => {
+            error!("Couldn't set up sending mail: {}", error);
+            std::process::exit(1);
+        }
+    };
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let app_clock = clock.clone();
+    // Shared across workers so an invalidation on one is seen by all of them
+    let auth_cache = Arc::new(AuthCache::new(settings.auth_cache_ttl, clock.clone()));
+    let metrics = Arc::new(Metrics::new(settings.metrics_token.clone()));
+    // Env defaults until the stored values are read below, once the database is up
+    let features = Arc::new(FeatureFlags::new(&settings.feature_defaults));
+    let app_features = features.clone();
+    let limits = Arc::new(ConcurrencyLimits::new(&settings.concurrency_limits, settings.concurrency_queue_timeout));
+    let best_guesses = Arc::new(SingleFlight::new(settings.solver_timeout));
+    let word_check = Arc::new(WordCheckStatus::default());
+    let startup_word_check = word_check.clone();
+    let stores = Stores::from_settings(&settings);
+    let usage = Arc::new(UsageRecorder::new());
+    let app_usage = usage.clone();
+    let flusher_usage = usage.clone();
+    let words: Arc<dyn WordRepository> = Arc::new(DbWords::new(pool.clone()));
+    let job_words = words.clone();
+    let priming_words = words.clone();
+    // Marked as priming before the first probe can arrive, readiness fails until it's done
+    let solver_cache = Arc::new(SolverCache::new(settings.solver_cache_path.as_deref()));
+    solver_cache.begin_priming();
+    let priming_cache = solver_cache.clone();
+    let bind_address = (settings.host.clone(), settings.port);
+    let workers = settings.workers;
+    let shutdown_timeout = settings.shutdown_timeout_seconds;
+    let app_pool = pool.clone();
+    let pool_for_startup = pool.clone();
+    let app_settings = settings.clone();
+    let app_db_status = db_status.clone();
+    let app_shutdown = shutdown.clone();
+
+    let server = HttpServer::new(move || {
         App::new()
-            .app_data(Data::new(AppState {db: pool.clone()}))
-            .service(
-                web::scope("/api")
-                    .configure(user_routes)
-                    .configure(game_routes)
-            )
+            .app_data(Data::new(AppState {db: app_pool.clone(), words: words.clone(), auth_cache: auth_cache.clone(), stores: stores.clone(), metrics: metrics.clone(), settings: app_settings.clone(), signing_keys: signing_keys.clone(), features: app_features.clone(), limits: limits.clone(), db_status: app_db_status.clone(), clock: app_clock.clone(), shutdown: app_shutdown.clone(), usage: app_usage.clone(), best_guesses: best_guesses.clone(), solver_cache: solver_cache.clone(), word_check: word_check.clone()}))
+            .app_data(errors::json_config(errors::DEFAULT_JSON_LIMIT))
+            .app_data(errors::query_config())
+            .app_data(errors::path_config())
+            .configure(api_routes(app_settings.legacy_api_routes))
+            .configure(well_known_routes)
+            .configure(health_routes)
+            .configure(metrics_routes)
+            .configure(|conf| if app_settings.api_docs_enabled { docs_routes(conf) })
             //.configure(user_routes)
-            .wrap(cors)
-            .wrap(Logger::default())
+            .wrap(cors(&app_settings.cors))
+            .wrap(Condition::new(app_settings.security_headers.enabled, SecurityHeaders::new(&app_settings.security_headers)))
+            .wrap(Compression::new(&app_settings.compression_encodings, app_settings.compression_min_size))
+            .wrap(ServerTiming::new(app_settings.slow_request_threshold, app_settings.server_timing_header))
+            .wrap(RequestMetrics::new(metrics.clone()))
+            .wrap(Condition::new(redirect_port.is_some(), HttpsRedirect::new(redirect_port.unwrap_or(443))))
+            .wrap(RequestIdentifier)
+            // Access lines are written once the body is sent, after the request span has
+            // closed, so the id is taken from the response header instead
+            .wrap(
+                Logger::new(r#"%{client_ip}xi "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T request_id=%{x-request-id}o"#)
+                    .custom_request_replace("client_ip", |req| ClientIp::of(req.request()).map_or_else(|| "-".to_string(), |ClientIp(ip)| ip.to_string())),
+            )
     })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
+    // Signals are handled below so the shutdown phases can be ordered and logged
+    .disable_signals()
+    .shutdown_timeout(shutdown_timeout);
+
+    let mut server = match workers {
+        Some(workers) => server.workers(workers),
+        None => server,
+    };
+
+    if settings.http_enabled {
+        server = server.bind(bind_address)?;
+    }
+    if let (Some(tls), Some(certificate)) = (&settings.tls, certificate) {
+        server = server.bind_rustls((settings.host.clone(), tls.port), server_config(certificate.clone()))?;
+        info!("Serving HTTPS on port {}", tls.port);
+        spawn_certificate_reloader(certificate, tls.watch_interval, shutdown.clone());
+    }
+
+    let server = server.run();
+    let server_handle = server.handle();
+    let startup_handle = server.handle();
+
+    // Background work only starts once the database is usable. Returns false when it
+    // never became usable, which stops the server and fails the process.
+    let background = actix_web::rt::spawn(async move {
+        if let Err(error) = wait_for_database(&pool_for_startup, &settings.database_url, settings.db_connect_max_wait, &db_status, shutdown.clone()).await {
+            if is_shutting_down(&shutdown) {
+                info!("{}", error);
+                return true;
+            }
+
+            error!("{}", error);
+            startup_handle.stop(false).await;
+            return false;
+        }
+
+        // Failing to run the check doesn't stop the server, the report just stays pending
+        if let Err(error) = run_check(&pool_for_startup, &startup_word_check, clock.now()).await {
+            error!("Couldn't check the word list: {}", error);
+        }
+
+        // Builds what the first best guess would otherwise stall on. Failing leaves that
+        // to the first request instead.
+        let priming = actix_web::rt::spawn(async move {
+            if let Err(error) = priming_cache.prime(priming_words.as_ref()).await {
+                error!("Couldn't prime the solver cache: {}", error);
+            }
+        });
+
+        let runner = Arc::new(BuiltinJobs { db: pool_for_startup.clone(), words: job_words, webhooks: settings.webhooks.clone(), mailer, clock: clock.clone() });
+        let job_worker = spawn_job_worker(pool_for_startup.clone(), runner, clock.clone(), settings.job_poll_interval, shutdown.clone());
+        let flag_refresher = spawn_flag_refresher(features, pool_for_startup.clone(), settings.feature_refresh_interval, shutdown.clone());
+        let usage_flusher = spawn_usage_flusher(flusher_usage, pool_for_startup.clone(), USAGE_FLUSH_INTERVAL, shutdown.clone());
+        let word_sync = settings
+            .word_sync
+            .as_ref()
+            .and_then(|sync| sync.interval.map(|interval| spawn_word_sync(pool_for_startup.clone(), sync.clone(), interval, clock.clone(), shutdown.clone())));
+
+        if let Err(error) = spawn_maintenance(pool_for_startup, clock, settings.idempotency_ttl, settings.account_deletion_grace, shutdown).await {
+            error!("Maintenance task ended abnormally: {}", error);
+        }
+        if let Err(error) = priming.await {
+            error!("Solver cache priming ended abnormally: {}", error);
+        }
+        if let Err(error) = job_worker.await {
+            error!("Job worker ended abnormally: {}", error);
+        }
+        if let Err(error) = flag_refresher.await {
+            error!("Feature flag refresher ended abnormally: {}", error);
+        }
+        if let Err(error) = usage_flusher.await {
+            error!("API usage flusher ended abnormally: {}", error);
+        }
+        if let Some(word_sync) = word_sync {
+            if let Err(error) = word_sync.await {
+                error!("Word list sync ended abnormally: {}", error);
+            }
+        }
+        true
+    });
+
+    actix_web::rt::spawn(async move {
+        let signal = termination_signal().await;
+        info!("Received {}, draining connections for up to {}s", signal, shutdown_timeout);
+
+        // Readiness starts failing and background tasks wind down while requests drain
+        let _ = shutdown_sender.send(true);
+        server_handle.stop(true).await;
+    });
+
+    server.await?;
+    info!("HTTP server stopped");
+
+    let started = background.await.unwrap_or(false);
+
+    // Requests have all finished by now, so this catches every call since the last flush
+    match usage.flush(&pool).await {
+        Ok(0) => {}
+        Ok(rows) => info!("Flushed API usage for {} user routes", rows),
+        Err(error) => error!("Failed to write API usage during shutdown, it is lost: {}", error),
+    }
+
+    // Closed last so requests and tasks finishing above still had their connections
+    pool.close().await;
+    info!("Database pool closed, shutdown complete");
+    shutdown_tracing();
+
+    if !started {
+        std::process::exit(1);
+    }
+
+    Ok(())
 }
@@ -0,0 +1,134 @@
+use actix_http::encoding::Encoder;
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{forward_ready, ResponseHead, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{AcceptEncoding, ContentEncoding, Encoding, HeaderValue, CONTENT_ENCODING, ETAG, VARY};
+use actix_web::http::StatusCode;
+use actix_web::{Error, HttpMessage};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use std::rc::Rc;
+
+// Compresses response bodies with whichever of `encodings` the client's Accept-Encoding
+// prefers. Bodies under `min_size` go out as they are, and so do responses a handler has
+// already encoded itself. Streamed bodies, whose size isn't known up front, are always
+// compressed when the client accepts it.
+pub struct Compression {
+    // The configured encodings followed by identity, which is always acceptable to offer
+    offered: Rc<Vec<Encoding>>,
+    min_size: usize,
+}
+
+impl Compression {
+    pub fn new(encodings: &[ContentEncoding], min_size: usize) -> Self {
+        let offered = encodings
+            .iter()
+            .map(|encoding| Encoding::Known(*encoding))
+            .chain(std::iter::once(Encoding::identity()))
+            .collect();
+
+        Compression { offered: Rc::new(offered), min_size }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Compression
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<Encoder<B>>;
+    type Error = Error;
+    type Transform = CompressionMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CompressionMiddleware {
+            service,
+            offered: self.offered.clone(),
+            min_size: self.min_size,
+        }))
+    }
+}
+
+pub struct CompressionMiddleware<S> {
+    service: S,
+    offered: Rc<Vec<Encoding>>,
+    min_size: usize,
+}
+
+impl<S, B> Service<ServiceRequest> for CompressionMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<Encoder<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // A client refusing every encoding, identity included, still gets identity rather
+        // than a 406
+        let encoding = match req.get_header::<AcceptEncoding>().and_then(|accept| accept.negotiate(self.offered.iter())) {
+            Some(Encoding::Known(encoding)) => encoding,
+            _ => ContentEncoding::Identity,
+        };
+        let varies = self.offered.len() > 1;
+        let min_size = self.min_size;
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let response = fut.await?;
+
+            Ok(response.map_body(move |head, body| {
+                let already_encoded = head.headers().contains_key(CONTENT_ENCODING);
+                if !already_encoded && encoding != ContentEncoding::Identity {
+                    weaken_etag(head);
+                }
+
+                let encoding = if !already_encoded && worth_compressing(head.status, body.size(), min_size) {
+                    encoding
+                } else {
+                    ContentEncoding::Identity
+                };
+                let encoder = Encoder::response(encoding, head, body);
+
+                // Caches must keep the encoded and plain copies apart, including for 304s and
+                // bodies too small to compress this time. The encoder adds it when it encodes.
+                if varies && !already_encoded && encoding == ContentEncoding::Identity {
+                    head.headers_mut().append(VARY, HeaderValue::from_static("accept-encoding"));
+                }
+
+                encoder
+            }))
+        })
+    }
+}
+
+fn worth_compressing(status: StatusCode, size: BodySize, min_size: usize) -> bool {
+    // A 206 carries a byte range of the plain body, compressing it would break the range
+    if matches!(status, StatusCode::NOT_MODIFIED | StatusCode::NO_CONTENT | StatusCode::PARTIAL_CONTENT) {
+        return false;
+    }
+
+    match size {
+        BodySize::None => false,
+        BodySize::Sized(size) => size >= min_size as u64,
+        BodySize::Stream => true,
+    }
+}
+
+// A strong ETag promises byte for byte identical bodies, which the encoded and plain
+// copies aren't. Weak tags still match If-None-Match, so revalidation keeps working.
+// Applied whenever an encoding was negotiated, so a 304 carries the same tag as the
+// 200 it stands in for.
+fn weaken_etag(head: &mut ResponseHead) {
+    let weak = match head.headers().get(ETAG).and_then(|tag| tag.to_str().ok()) {
+        Some(tag) if tag.starts_with('"') => HeaderValue::from_str(&format!("W/{}", tag)).ok(),
+        _ => None,
+    };
+
+    if let Some(weak) = weak {
+        head.headers_mut().insert(ETAG, weak);
+    }
+}
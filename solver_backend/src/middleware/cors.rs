@@ -0,0 +1,22 @@
+use actix_cors::Cors;
+use crate::config::CorsSettings;
+
+// Settings are validated at startup, so nothing here can fail on a bad value
+pub fn cors(settings: &CorsSettings) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(settings.allowed_methods.clone())
+        .allowed_headers(settings.allowed_headers.clone())
+        .expose_headers(settings.exposed_headers.clone())
+        .max_age(settings.max_age_seconds);
+
+    cors = match &settings.allowed_origins {
+        Some(origins) => origins.iter().fold(cors, |cors, origin| cors.allowed_origin(origin)),
+        None => cors.allow_any_origin(),
+    };
+
+    if settings.allow_credentials {
+        cors = cors.supports_credentials();
+    }
+
+    cors
+}
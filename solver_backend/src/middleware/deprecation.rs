@@ -0,0 +1,213 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, LINK};
+use actix_web::http::{Method, Uri};
+use actix_web::Error;
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use tracing::warn;
+
+// Marks every response from the wrapped scope as deprecated, pointing clients at the
+// same path under `successor_prefix` instead of `prefix`
+pub struct Deprecated {
+    prefix: &'static str,
+    successor_prefix: &'static str,
+}
+
+impl Deprecated {
+    pub fn new(prefix: &'static str, successor_prefix: &'static str) -> Self {
+        Deprecated { prefix, successor_prefix }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Deprecated
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = DeprecatedMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DeprecatedMiddleware {
+            service,
+            prefix: self.prefix,
+            successor_prefix: self.successor_prefix,
+        }))
+    }
+}
+
+pub struct DeprecatedMiddleware<S> {
+    service: S,
+    prefix: &'static str,
+    successor_prefix: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for DeprecatedMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let path = req.path().to_string();
+        let successor = match path.strip_prefix(self.prefix) {
+            Some(rest) => format!("{}{}", self.successor_prefix, rest),
+            None => self.successor_prefix.to_string(),
+        };
+        warn!("Deprecated route {} {} called, clients should move to {}", req.method(), path, successor);
+
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut response = fut.await?;
+            mark_deprecated(response.headers_mut(), &successor);
+            Ok(response)
+        })
+    }
+}
+
+// An inner middleware knows the more exact successor, so a Link already set is kept
+fn mark_deprecated(headers: &mut HeaderMap, successor: &str) {
+    headers.insert(HeaderName::from_static("deprecation"), HeaderValue::from_static("true"));
+    if headers.contains_key(LINK) {
+        return;
+    }
+    if let Ok(link) = HeaderValue::from_str(&format!("<{}>; rel=\"successor-version\"", successor)) {
+        headers.insert(LINK, link);
+    }
+}
+
+// A path a route used to have, still served by the route at `current`
+pub struct RouteAlias {
+    pub method: Method,
+    // Segments in braces match any one segment and are carried over to `current` by name
+    pub legacy: &'static str,
+    pub current: &'static str,
+}
+
+impl RouteAlias {
+    fn rewrite(&self, method: &Method, path: &str) -> Option<String> {
+        if *method != self.method {
+            return None;
+        }
+        let legacy: Vec<&str> = self.legacy.split('/').collect();
+        let given: Vec<&str> = path.split('/').collect();
+        if legacy.len() != given.len() {
+            return None;
+        }
+
+        let mut captures = Vec::new();
+        for (pattern, segment) in legacy.iter().zip(&given) {
+            if pattern.starts_with('{') && pattern.ends_with('}') && !segment.is_empty() {
+                captures.push((*pattern, *segment));
+            } else if pattern != segment {
+                return None;
+            }
+        }
+
+        let current: Vec<&str> = self
+            .current
+            .split('/')
+            .map(|pattern| captures.iter().find(|(name, _)| *name == pattern).map_or(pattern, |(_, segment)| *segment))
+            .collect();
+        Some(current.join("/"))
+    }
+}
+
+// Routes requests for an alias in `aliases` to the route it stands for, marking the
+// response as deprecated. Goes inside NormalizePath, so aliases are written without a
+// trailing slash.
+pub struct DeprecatedAliases {
+    prefix: &'static str,
+    aliases: &'static [RouteAlias],
+}
+
+impl DeprecatedAliases {
+    pub fn new(prefix: &'static str, aliases: &'static [RouteAlias]) -> Self {
+        DeprecatedAliases { prefix, aliases }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for DeprecatedAliases
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = DeprecatedAliasesMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(DeprecatedAliasesMiddleware {
+            service,
+            prefix: self.prefix,
+            aliases: self.aliases,
+        }))
+    }
+}
+
+pub struct DeprecatedAliasesMiddleware<S> {
+    service: S,
+    prefix: &'static str,
+    aliases: &'static [RouteAlias],
+}
+
+impl<S, B> Service<ServiceRequest> for DeprecatedAliasesMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, mut req: ServiceRequest) -> Self::Future {
+        let path = req.path().to_string();
+        let rest = path.strip_prefix(self.prefix).unwrap_or(&path);
+        let current = self
+            .aliases
+            .iter()
+            .find_map(|alias| alias.rewrite(req.method(), rest))
+            .map(|current| format!("{}{}", self.prefix, current));
+
+        if let Some(current) = &current {
+            warn!("Deprecated route {} {} called, clients should move to {}", req.method(), path, current);
+            // The scope's prefix is unchanged, so routing carries on from where it is
+            if let Some(uri) = with_path(req.uri(), current) {
+                req.match_info_mut().get_mut().update(&uri);
+                req.head_mut().uri = uri;
+            }
+        }
+
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut response = fut.await?;
+            if let Some(current) = current {
+                mark_deprecated(response.headers_mut(), &current);
+            }
+            Ok(response)
+        })
+    }
+}
+
+fn with_path(uri: &Uri, path: &str) -> Option<Uri> {
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path.to_string(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(path_and_query.parse().ok()?);
+    Uri::from_parts(parts).ok()
+}
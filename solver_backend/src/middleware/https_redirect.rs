@@ -0,0 +1,81 @@
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::LOCATION;
+use actix_web::{Error, HttpResponse};
+use futures_util::future::{ready, Either, LocalBoxFuture, Ready};
+
+// Probes are left on plain HTTP, load balancers checking it don't follow redirects
+const EXEMPT_PREFIX: &str = "/health/";
+
+// Answers requests that arrived over plain HTTP with a permanent redirect to the same URL
+// on the TLS listener at `tls_port`. 308 rather than 301 so clients repeat the method and
+// body instead of turning a POST into a GET.
+pub struct HttpsRedirect {
+    tls_port: u16,
+}
+
+impl HttpsRedirect {
+    pub fn new(tls_port: u16) -> Self {
+        HttpsRedirect { tls_port }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for HttpsRedirect
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = HttpsRedirectMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(HttpsRedirectMiddleware { service, tls_port: self.tls_port }))
+    }
+}
+
+pub struct HttpsRedirectMiddleware<S> {
+    service: S,
+    tls_port: u16,
+}
+
+impl<S, B> Service<ServiceRequest> for HttpsRedirectMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Either<LocalBoxFuture<'static, Result<Self::Response, Self::Error>>, Ready<Result<Self::Response, Self::Error>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        // Set on connections accepted by the TLS listener
+        if req.app_config().secure() || req.path().starts_with(EXEMPT_PREFIX) {
+            let fut = self.service.call(req);
+            return Either::Left(Box::pin(async move { Ok(fut.await?.map_into_left_body()) }));
+        }
+
+        let location = https_url(&req, self.tls_port);
+        let response = HttpResponse::PermanentRedirect().insert_header((LOCATION, location)).finish();
+        Either::Right(ready(Ok(req.into_response(response).map_into_right_body())))
+    }
+}
+
+// The request's host without its port, on `tls_port` unless that is the default 443
+fn https_url(req: &ServiceRequest, tls_port: u16) -> String {
+    let connection = req.connection_info();
+    let host = connection.host();
+    // Bracketed IPv6 literals have colons of their own
+    let hostname = match host.rfind(':') {
+        Some(index) if !host[index..].contains(']') => &host[..index],
+        _ => host,
+    };
+    let port = if tls_port == 443 { String::new() } else { format!(":{}", tls_port) };
+    let path = req.uri().path_and_query().map_or("/", |path| path.as_str());
+
+    format!("https://{}{}{}", hostname, port, path)
+}
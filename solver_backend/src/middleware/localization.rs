@@ -0,0 +1,71 @@
+use crate::errors::{AppError, ErrorResponse};
+use crate::utils::locale_utils::{negotiate, translate, DEFAULT_LANGUAGE};
+use actix_web::body::{BoxBody, EitherBody};
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderValue, CONTENT_LANGUAGE};
+use actix_web::Error;
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+
+// Sends error messages in the language the client asks for with Accept-Language. Only
+// `message` changes, `code` and `details` stay as they were so clients can keep branching
+// on them. Errors in the default language, or with no translation, pass through as raised.
+pub struct Localize;
+
+impl<S, B> Transform<S, ServiceRequest> for Localize
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = LocalizeMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LocalizeMiddleware { service }))
+    }
+}
+
+pub struct LocalizeMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service<ServiceRequest> for LocalizeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let language = negotiate(req.request());
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let response = fut.await?;
+            if language == DEFAULT_LANGUAGE {
+                return Ok(response.map_into_left_body());
+            }
+
+            // Only responses made from an AppError carry it, anything else is left alone
+            let localized = response.response().error().and_then(|error| error.as_error::<AppError>()).and_then(|error| {
+                let mut info = error.info();
+                info.message = translate(language, info.code)?.to_string();
+                serde_json::to_vec(&ErrorResponse { error: info }).ok()
+            });
+            let Some(body) = localized else {
+                return Ok(response.map_into_left_body());
+            };
+
+            Ok(response.map_body(|head, _| {
+                head.headers.insert(CONTENT_LANGUAGE, HeaderValue::from_static(language));
+                EitherBody::right(BoxBody::new(body))
+            }))
+        })
+    }
+}
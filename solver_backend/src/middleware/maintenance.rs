@@ -0,0 +1,94 @@
+use crate::errors::{AppError, ErrorCode, ErrorInfo};
+use crate::utils::feature_utils::MaintenanceLevel;
+use crate::AppState;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::Method;
+use actix_web::{web, Error};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use serde_json::json;
+
+// Served whatever the level, relative to the scope's prefix. Admins have to be able to
+// log in to switch maintenance off again, and checking a token writes nothing.
+const ALWAYS_SERVED: &[&str] = &["/users/login", "/users/get_new_tokens", "/users/check_access"];
+
+// Refuses requests with a 503 while the maintenance flags are on: writes in read only
+// maintenance, everything in lockdown. The admin routes under `prefix` are always served,
+// health checks live outside the API and aren't wrapped.
+pub struct Maintenance {
+    prefix: &'static str,
+}
+
+impl Maintenance {
+    pub fn new(prefix: &'static str) -> Self {
+        Maintenance { prefix }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for Maintenance
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = MaintenanceMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MaintenanceMiddleware { service, prefix: self.prefix }))
+    }
+}
+
+pub struct MaintenanceMiddleware<S> {
+    service: S,
+    prefix: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for MaintenanceMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let refusal = req.app_data::<web::Data<AppState>>().and_then(|state| {
+            let level = state.features.maintenance_level();
+            let path = req.path().strip_prefix(self.prefix).unwrap_or(req.path());
+            refused(level, req.method(), path).then_some((level, state.settings.maintenance_retry_after))
+        });
+
+        match refusal {
+            Some((level, retry_after)) => {
+                let message = match level {
+                    MaintenanceLevel::Lockdown => "The service is down for maintenance, try again later",
+                    _ => "The service is read only for maintenance, try again later",
+                };
+                let info = ErrorInfo::new(ErrorCode::Maintenance, message).with_details(json!({ "level": level })).with_retry_after(retry_after);
+                let response = req.error_response(AppError::Unavailable(info)).map_into_right_body();
+                Box::pin(async move { Ok(response) })
+            }
+            None => {
+                let fut = self.service.call(req);
+                Box::pin(async move { Ok(fut.await?.map_into_left_body()) })
+            }
+        }
+    }
+}
+
+fn refused(level: MaintenanceLevel, method: &Method, path: &str) -> bool {
+    let exempt = path == "/admin" || path.starts_with("/admin/") || ALWAYS_SERVED.contains(&path);
+    let read = matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS);
+
+    match level {
+        MaintenanceLevel::Off => false,
+        MaintenanceLevel::ReadOnly => !exempt && !read,
+        MaintenanceLevel::Lockdown => !exempt,
+    }
+}
@@ -0,0 +1,12 @@
+pub mod compression;
+pub mod cors;
+pub mod deprecation;
+pub mod https_redirect;
+pub mod localization;
+pub mod maintenance;
+pub mod rate_limit;
+pub mod request_id;
+pub mod request_metrics;
+pub mod security_headers;
+pub mod server_timing;
+pub mod word_check;
@@ -0,0 +1,123 @@
+use crate::errors::{AppError, ErrorCode, ErrorInfo};
+use crate::utils::client_ip_utils::ClientIp;
+use crate::utils::concurrency_utils::SOLVER;
+use crate::utils::store_utils::RateLimitHit;
+use crate::AppState;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::{web, Error};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use serde_json::json;
+use std::rc::Rc;
+
+// Logging in, registering and refreshing tokens, the targets of credential stuffing
+pub const AUTH: &str = "auth";
+
+// Issuing guest tokens, which anyone can ask for without an account
+pub const GUEST: &str = "guest";
+
+// Each group with its default requests per window
+pub const RATE_LIMITED_GROUPS: &[(&str, u64)] = &[(AUTH, 20), (SOLVER, 120), (GUEST, 5)];
+
+const LIMIT_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+const REMAINING_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+const RESET_HEADER: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+// Counts requests per client IP against the group's limit, rejecting them with a 429 once
+// it's used up. Every response, the 429 included, says where the caller's window stands so
+// clients can pace themselves. Requests without a peer address and groups without a limit
+// pass straight through.
+pub struct RateLimit {
+    group: &'static str,
+}
+
+impl RateLimit {
+    pub fn new(group: &'static str) -> Self {
+        RateLimit { group }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimit
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware { service: Rc::new(service), group: self.group }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    group: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let group = self.group;
+
+        Box::pin(async move {
+            let state = req.app_data::<web::Data<AppState>>().cloned();
+            let limited = state.and_then(|state| {
+                let limit = state.settings.rate_limits.iter().find(|(name, _)| *name == group).and_then(|(_, limit)| *limit)?;
+                let client = ClientIp::of(req.request())?;
+                Some((state, limit, client))
+            });
+            let Some((state, limit, client)) = limited else {
+                return Ok(service.call(req).await?.map_into_left_body());
+            };
+
+            let key = format!("{}:{}", group, client.0);
+            let hit = match state.stores.rate_limit_hit(&key, state.settings.rate_limit_window).await {
+                Some(hit) => hit,
+                None if state.stores.rate_limit_fail_open => return Ok(service.call(req).await?.map_into_left_body()),
+                None => {
+                    let error = AppError::unavailable(ErrorCode::RateLimitUnavailable, "Rate limits can't be checked right now, try again shortly");
+                    return Ok(req.error_response(error).map_into_right_body());
+                }
+            };
+            // Both rounded up to whole seconds so a client waiting until then finds a fresh
+            // window. The reset time is worked out from the window's end, so every response in
+            // the window names the same second.
+            let resets_in = hit.resets_in.as_millis() as i64;
+            let reset_seconds = (resets_in + 999) / 1000;
+            let reset_at = (state.clock.now().timestamp_millis() + resets_in + 999) / 1000;
+
+            let mut response = if hit.hits > limit {
+                let info = ErrorInfo::new(ErrorCode::TooManyRequests, "Too many requests, try again after the window resets")
+                    .with_details(json!({ "limit": limit, "reset": reset_at }))
+                    .with_retry_after(reset_seconds as u64);
+                req.error_response(AppError::TooManyRequests(info)).map_into_right_body()
+            } else {
+                service.call(req).await?.map_into_left_body()
+            };
+            insert_headers(response.headers_mut(), limit, hit, reset_at);
+
+            Ok(response)
+        })
+    }
+}
+
+fn insert_headers(headers: &mut HeaderMap, limit: u64, hit: RateLimitHit, reset_at: i64) {
+    headers.insert(LIMIT_HEADER, HeaderValue::from(limit));
+    headers.insert(REMAINING_HEADER, HeaderValue::from(limit.saturating_sub(hit.hits)));
+    headers.insert(RESET_HEADER, HeaderValue::from(reset_at));
+}
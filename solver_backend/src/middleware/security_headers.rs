@@ -0,0 +1,125 @@
+use crate::config::SecurityHeaderSettings;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{
+    HeaderMap, HeaderName, HeaderValue, CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS,
+    X_FRAME_OPTIONS,
+};
+use actix_web::Error;
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use std::rc::Rc;
+
+// Scraped and probed by machines, which ignore the headers
+const EXEMPT_PREFIXES: &[&str] = &["/health/", "/metrics"];
+
+// Sets the browser hardening headers on every response. Headers a handler set itself are
+// left as they are, so a route can override any of them.
+pub struct SecurityHeaders {
+    headers: Rc<Headers>,
+}
+
+struct Headers {
+    // Set on every response outside the exempt routes
+    common: Vec<(HeaderName, HeaderValue)>,
+    hsts: Option<HeaderValue>,
+    trusted_proxy: bool,
+    content_security_policy: Option<HeaderValue>,
+    route_policies: Vec<(String, HeaderValue)>,
+}
+
+impl SecurityHeaders {
+    pub fn new(settings: &SecurityHeaderSettings) -> Self {
+        let mut common = vec![(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"))];
+        if let Some(frame_options) = &settings.frame_options {
+            common.push((X_FRAME_OPTIONS, frame_options.clone()));
+        }
+        if let Some(referrer_policy) = &settings.referrer_policy {
+            common.push((REFERRER_POLICY, referrer_policy.clone()));
+        }
+
+        SecurityHeaders {
+            headers: Rc::new(Headers {
+                common,
+                hsts: settings.hsts.clone(),
+                trusted_proxy: settings.trusted_proxy,
+                content_security_policy: settings.content_security_policy.clone(),
+                route_policies: settings.route_policies.clone(),
+            }),
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for SecurityHeaders
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = SecurityHeadersMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(SecurityHeadersMiddleware { service, headers: self.headers.clone() }))
+    }
+}
+
+pub struct SecurityHeadersMiddleware<S> {
+    service: S,
+    headers: Rc<Headers>,
+}
+
+impl<S, B> Service<ServiceRequest> for SecurityHeadersMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let path = req.path();
+        if EXEMPT_PREFIXES.iter().any(|prefix| path.starts_with(prefix)) {
+            return Box::pin(self.service.call(req));
+        }
+
+        let headers = self.headers.clone();
+        let policy = headers
+            .route_policies
+            .iter()
+            .find(|(prefix, _)| path.starts_with(prefix.as_str()))
+            .map(|(_, policy)| policy)
+            .or(headers.content_security_policy.as_ref())
+            .cloned();
+        // Browsers ignore HSTS received over plain HTTP, behind a TLS terminating proxy
+        // the connection here is plain but the client's isn't
+        let hsts = headers.hsts.clone().filter(|_| req.app_config().secure() || headers.trusted_proxy);
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut response = fut.await?;
+
+            let response_headers = response.headers_mut();
+            for (name, value) in &headers.common {
+                insert_missing(response_headers, name, value);
+            }
+            if let Some(policy) = &policy {
+                insert_missing(response_headers, &CONTENT_SECURITY_POLICY, policy);
+            }
+            if let Some(hsts) = &hsts {
+                insert_missing(response_headers, &STRICT_TRANSPORT_SECURITY, hsts);
+            }
+
+            Ok(response)
+        })
+    }
+}
+
+fn insert_missing(headers: &mut HeaderMap, name: &HeaderName, value: &HeaderValue) {
+    if !headers.contains_key(name) {
+        headers.insert(name.clone(), value.clone());
+    }
+}
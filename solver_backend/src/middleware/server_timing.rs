@@ -0,0 +1,135 @@
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::{Error, HttpMessage, HttpRequest};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+pub const SERVER_TIMING_HEADER: &str = "server-timing";
+
+// Named phases of the current request, filled in by handlers. Reach it through
+// `RequestTimings::of(&req)`.
+#[derive(Clone, Default)]
+pub struct RequestTimings {
+    phases: Arc<Mutex<Vec<(&'static str, Duration)>>>,
+}
+
+impl RequestTimings {
+    // Outside the middleware, e.g. in tests mounting a bare handler, timings are recorded
+    // into a context nobody reads
+    pub fn of(req: &HttpRequest) -> RequestTimings {
+        req.extensions().get::<RequestTimings>().cloned().unwrap_or_default()
+    }
+
+    // Time spent in the same phase more than once is added up
+    pub fn record(&self, phase: &'static str, elapsed: Duration) {
+        let mut phases = self.phases.lock().unwrap();
+
+        match phases.iter_mut().find(|(name, _)| *name == phase) {
+            Some((_, total)) => *total += elapsed,
+            None => phases.push((phase, elapsed)),
+        }
+    }
+
+    pub async fn measure<F: Future>(&self, phase: &'static str, fut: F) -> F::Output {
+        let started = Instant::now();
+        let output = fut.await;
+        self.record(phase, started.elapsed());
+        output
+    }
+
+    // Server-Timing syntax, e.g. `db;dur=3.1, bcrypt;dur=212.4, total;dur=220.9`
+    fn describe(&self, total: Duration) -> String {
+        self.phases
+            .lock()
+            .unwrap()
+            .iter()
+            .chain(std::iter::once(&("total", total)))
+            .map(|(name, elapsed)| format!("{};dur={:.1}", name, elapsed.as_secs_f64() * 1000.0))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+// Times each request as a whole alongside the phases handlers record, reports the
+// breakdown in a Server-Timing header and logs it for requests slower than `slow_threshold`
+pub struct ServerTiming {
+    slow_threshold: Duration,
+    send_header: bool,
+}
+
+impl ServerTiming {
+    pub fn new(slow_threshold: Duration, send_header: bool) -> Self {
+        ServerTiming { slow_threshold, send_header }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for ServerTiming
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = ServerTimingMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(ServerTimingMiddleware {
+            service,
+            slow_threshold: self.slow_threshold,
+            send_header: self.send_header,
+        }))
+    }
+}
+
+pub struct ServerTimingMiddleware<S> {
+    service: S,
+    slow_threshold: Duration,
+    send_header: bool,
+}
+
+impl<S, B> Service<ServiceRequest> for ServerTimingMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let started = Instant::now();
+        let timings = RequestTimings::default();
+        req.extensions_mut().insert(timings.clone());
+
+        let method = req.method().to_string();
+        let path = req.path().to_string();
+        let slow_threshold = self.slow_threshold;
+        let send_header = self.send_header;
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let mut result = fut.await;
+            let elapsed = started.elapsed();
+            let breakdown = timings.describe(elapsed);
+
+            if !slow_threshold.is_zero() && elapsed > slow_threshold {
+                warn!("Slow request {} {} took {}ms: {}", method, path, elapsed.as_millis(), breakdown);
+            }
+
+            if let (true, Ok(response)) = (send_header, &mut result) {
+                if let Ok(value) = HeaderValue::from_str(&breakdown) {
+                    response.headers_mut().insert(HeaderName::from_static(SERVER_TIMING_HEADER), value);
+                }
+            }
+
+            result
+        })
+    }
+}
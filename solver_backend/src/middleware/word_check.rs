@@ -0,0 +1,79 @@
+use crate::errors::{AppError, ErrorCode, ErrorInfo};
+use crate::AppState;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{web, Error};
+use futures_util::future::{ready, LocalBoxFuture, Ready};
+use serde_json::json;
+
+// Still served, relative to the scope's prefix, so the list can be fixed from a source
+const ALWAYS_SERVED: &[&str] = &["/game/words/sync"];
+
+// Refuses the game routes under `prefix` with a 503 while the latest word list check
+// failed and WORD_CHECK_ENFORCED is on. Until the first check has run they are served.
+pub struct WordCheckGuard {
+    prefix: &'static str,
+}
+
+impl WordCheckGuard {
+    pub fn new(prefix: &'static str) -> Self {
+        WordCheckGuard { prefix }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for WordCheckGuard
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = WordCheckGuardMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(WordCheckGuardMiddleware { service, prefix: self.prefix }))
+    }
+}
+
+pub struct WordCheckGuardMiddleware<S> {
+    service: S,
+    prefix: &'static str,
+}
+
+impl<S, B> Service<ServiceRequest> for WordCheckGuardMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let path = req.path().strip_prefix(self.prefix).unwrap_or(req.path());
+        let game_route = (path == "/game" || path.starts_with("/game/")) && !ALWAYS_SERVED.contains(&path);
+        let report = req
+            .app_data::<web::Data<AppState>>()
+            .filter(|state| game_route && state.settings.word_check_enforced)
+            .and_then(|state| state.word_check.latest())
+            .filter(|report| !report.passed);
+
+        match report {
+            Some(report) => {
+                let failed: Vec<&str> = report.violations.iter().map(|violation| violation.check).collect();
+                let info = ErrorInfo::new(ErrorCode::WordListUnhealthy, "The word list failed its integrity check, games are unavailable")
+                    .with_details(json!({ "checked_at": report.checked_at, "failed": failed }));
+                let response = req.error_response(AppError::Unavailable(info)).map_into_right_body();
+                Box::pin(async move { Ok(response) })
+            }
+            None => {
+                let fut = self.service.call(req);
+                Box::pin(async move { Ok(fut.await?.map_into_left_body()) })
+            }
+        }
+    }
+}
@@ -0,0 +1,172 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+
+// An access token for acting as the user. It can't be refreshed, and without
+// IMPERSONATION_ALLOW_WRITES only reads are let through.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Impersonation {
+    pub access: String,
+    pub user_id: i32,
+    pub impersonator: i32,
+    pub expires_at: DateTime<Utc>,
+}
+
+// Whether the service is healthy and busy, in one call. A component whose numbers couldn't
+// be gathered is null, with the reason listed in `errors`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AdminSummary {
+    pub generated_at: DateTime<Utc>,
+    pub users: Option<UserCounts>,
+    // Sessions whose token was issued or refreshed in the last hour
+    pub active_sessions_last_hour: Option<i64>,
+    // Candidate queries this instance answered since midnight UTC. Games aren't recorded,
+    // each query is one guess being worked out.
+    pub solver_queries_today: u64,
+    pub word_list: Option<WordListSummary>,
+    pub pending_jobs: Option<i64>,
+    // Cache name -> share of lookups answered from the cache, null before the first lookup
+    pub cache_hit_rates: BTreeMap<String, Option<f64>>,
+    pub db_pool: PoolSummary,
+    pub errors: Vec<ComponentError>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserCounts {
+    pub total: i64,
+    pub active_last_7_days: i64,
+    pub verified: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WordListSummary {
+    pub size: i64,
+    pub version: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolSummary {
+    // Open connections, of which `idle` are free
+    pub size: u32,
+    pub idle: u32,
+    pub max_connections: u32,
+    // Connections in use as a share of `max_connections`
+    pub utilization: f64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ComponentError {
+    // The field left null
+    pub component: String,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeatureFlag {
+    pub name: String,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateFeatureFlag {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PlanQuery {
+    // A pattern like the solver's `exact`, cra__ when left out
+    pub exact: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QueryPlan {
+    #[schema(example = "cra__")]
+    pub pattern: String,
+    #[schema(example = "postgres")]
+    pub database: &'static str,
+    pub plan: Vec<String>,
+}
+
+// Critical findings fail the check, warnings are only reported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckSeverity {
+    Critical,
+    Warning,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WordListViolation {
+    // not_lowercase_alphabetic, wrong_length, duplicate, empty_list, no_answers or count_mismatch
+    #[schema(example = "duplicate")]
+    pub check: &'static str,
+    pub severity: CheckSeverity,
+    #[schema(example = "2 words are listed more than once")]
+    pub message: String,
+    // Rows found, 1 for checks on the list as a whole
+    pub count: i64,
+    // Up to 10 of the offending words
+    pub examples: Vec<String>,
+}
+
+// One pass over word_list, checking it's a list the solver can use
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct WordListCheck {
+    pub checked_at: DateTime<Utc>,
+    // The list's version when the check started
+    pub version: i64,
+    // Rows read, duplicates and invalid words included
+    pub words: i64,
+    pub answers: i64,
+    // No critical violations
+    pub passed: bool,
+    pub violations: Vec<WordListViolation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailStatus {
+    Queued,
+    Retrying,
+    Sent,
+    DeadLetter,
+}
+
+impl EmailStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EmailStatus::Queued => "queued",
+            EmailStatus::Retrying => "retrying",
+            EmailStatus::Sent => "sent",
+            EmailStatus::DeadLetter => "dead_letter",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EmailQuery {
+    // Every status when left out
+    pub status: Option<EmailStatus>,
+}
+
+// `status` is queued, retrying, sent or dead_letter, `error` is from the last failed
+// attempt. Bodies are left out, they can hold tokens meant only for the recipient.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct EmailDelivery {
+    pub id: i32,
+    #[schema(example = "confirm_email")]
+    pub template: String,
+    pub recipient: String,
+    pub subject: String,
+    #[schema(example = "sent")]
+    pub status: String,
+    pub attempts: i32,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    pub sent_at: Option<DateTime<Utc>>,
+}
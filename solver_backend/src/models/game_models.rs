@@ -1,7 +1,742 @@
-use serde::Deserialize;
-#[derive(Debug, Deserialize)]
+use chrono::{DateTime, NaiveDate, Utc};
+use crate::errors::ErrorInfo;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+use crate::models::page_models::Paginated;
+use crate::models::users_models::SortOrder;
+use crate::solver::{Constraints, Mark};
+use crate::utils::feedback_utils::Square;
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct RequestLetters {
     pub correct: String,
     pub incorrect: String,
     pub exact: String,
+    // The user's saved preference when left out
+    pub sort_order: Option<SortOrder>,
+}
+
+impl RequestLetters {
+    // Every correct letter is required and every incorrect one ruled out. `exact` is a LIKE
+    // pattern and is matched by the database instead.
+    pub fn constraints(&self) -> Constraints {
+        let mut constraints = Constraints::default();
+        self.correct.chars().for_each(|letter| constraints.require(letter));
+        self.incorrect.chars().for_each(|letter| constraints.exclude(letter));
+        constraints
+    }
+
+    // A letter the answer has to contain, as a correct letter or a fixed position in
+    // `exact`, that's also listed as incorrect. No word can match, which usually means
+    // the feedback was entered wrong.
+    pub fn contradiction(&self) -> Option<char> {
+        let incorrect = self.incorrect.to_lowercase();
+        self.correct
+            .chars()
+            .chain(self.exact.chars().filter(|letter| !matches!(letter, '_' | '%')))
+            .map(|letter| letter.to_ascii_lowercase())
+            .find(|letter| incorrect.contains(*letter))
+    }
+
+    // These letters with what `marks` reveal about `guess` added, so `contradiction` also
+    // catches feedback at odds with them. Gray letters only count as incorrect when the
+    // guess doesn't have them green or yellow elsewhere.
+    pub fn with_feedback(&self, guess: &str, marks: &[Mark]) -> RequestLetters {
+        let found: Vec<char> = guess.chars().zip(marks).filter(|(_, mark)| **mark != Mark::Gray).map(|(letter, _)| letter).collect();
+        let absent = guess.chars().zip(marks).filter(|(letter, mark)| **mark == Mark::Gray && !found.contains(letter)).map(|(letter, _)| letter);
+
+        RequestLetters {
+            correct: self.correct.chars().chain(found.iter().copied()).collect(),
+            incorrect: self.incorrect.chars().chain(absent).collect(),
+            exact: self.exact.clone(),
+            sort_order: self.sort_order,
+        }
+    }
+
+    // A green in `marks` where `exact` fixes a different letter, as (position, letter).
+    // Patterns with `%` don't pin positions, so they never conflict.
+    pub fn conflicting_position(&self, guess: &str, marks: &[Mark]) -> Option<(usize, char)> {
+        if self.exact.contains('%') {
+            return None;
+        }
+        let fixed: Vec<char> = self.exact.chars().collect();
+        guess
+            .chars()
+            .zip(marks)
+            .enumerate()
+            .find(|(position, (letter, mark))| {
+                **mark == Mark::Green && fixed.get(*position).is_some_and(|fixed| *fixed != '_' && !fixed.eq_ignore_ascii_case(letter))
+            })
+            .map(|(position, (letter, _))| (position, letter))
+    }
+}
+
+// Feedback a guess might get, one letter per square as in `parse_feedback`
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DetailQuery {
+    // Echoes the letters as the server read them in `normalized`
+    pub detailed: Option<bool>,
+}
+
+// Instead of the first page, per_page words picked at random from all the matches. total
+// still counts every match.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SampleQuery {
+    pub sample: Option<bool>,
+    // Picks the same sample again, as long as the word list hasn't changed. A random one is
+    // used when left out, and sent back in the response.
+    pub seed: Option<u32>,
+}
+
+// The letters after trimming, lowercasing and turning empty slots into `_`
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NormalizedLetters {
+    #[schema(example = "ra")]
+    pub correct: String,
+    #[schema(example = "s")]
+    pub incorrect: String,
+    #[schema(example = "_r_ne")]
+    pub exact: String,
+}
+
+impl From<&RequestLetters> for NormalizedLetters {
+    fn from(letters: &RequestLetters) -> Self {
+        NormalizedLetters { correct: letters.correct.clone(), incorrect: letters.incorrect.clone(), exact: letters.exact.clone() }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LetterMatches {
+    #[serde(flatten)]
+    #[schema(value_type = WordPage)]
+    pub words: Paginated<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalized: Option<NormalizedLetters>,
+    // The seed a sample was picked with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct HypotheticalFeedback {
+    #[schema(example = "slate")]
+    pub guess: String,
+    #[schema(example = "xxggg")]
+    pub feedback: String,
+}
+
+// Two outcomes to weigh against each other, on top of what's known already
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CandidateDiffRequest {
+    pub base: RequestLetters,
+    pub first: HypotheticalFeedback,
+    pub second: HypotheticalFeedback,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DiffBranch {
+    pub guess: String,
+    pub feedback: String,
+    // Candidates still in the running after this feedback
+    pub remaining: usize,
+    // Candidates this feedback rules out that the other branch keeps
+    #[schema(value_type = WordPage)]
+    pub eliminated: Paginated<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CandidateDiff {
+    // Candidates of the base letters alone
+    pub candidates: usize,
+    pub first: DiffBranch,
+    pub second: DiffBranch,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BestGuessRequest {
+    pub letters: RequestLetters,
+    // How many suggestions, 5 when left out
+    pub count: Option<usize>,
+    // Say in words why each suggestion is worth trying
+    #[serde(default)]
+    pub explain: bool,
+    // fast when left out
+    #[serde(default)]
+    pub mode: BestGuessMode,
+}
+
+// How best-guess ranks. fast scores how evenly each letter and each letter in its spot
+// splits the candidates, exact works out the entropy of the feedback every guess would get,
+// which is better but much slower on a long list. auto ranks exactly below
+// BEST_GUESS_EXACT_THRESHOLD candidates. Nothing is ranked exactly above
+// BEST_GUESS_EXACT_CEILING candidates, fast is used instead.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BestGuessMode {
+    Auto,
+    #[default]
+    Fast,
+    Exact,
+}
+
+impl BestGuessMode {
+    // The mode used for this many candidates, fast or exact
+    pub fn resolve(self, candidates: usize, threshold: usize, ceiling: usize) -> BestGuessMode {
+        match self {
+            BestGuessMode::Exact | BestGuessMode::Auto if candidates > ceiling => BestGuessMode::Fast,
+            BestGuessMode::Auto if candidates < threshold => BestGuessMode::Exact,
+            BestGuessMode::Auto => BestGuessMode::Fast,
+            mode => mode,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct GuessSuggestion {
+    #[schema(example = "slate")]
+    pub word: String,
+    // How well it splits the candidates, higher is better. In exact mode the entropy of
+    // its feedback in thousandths of a bit.
+    pub score: usize,
+    // Only when explain was asked for
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "Of the 12 candidates left, SLATE leaves at most 3 and about 1.8 on average. It checks for L, S and T. It could be the answer itself.")]
+    pub explanation: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BestGuesses {
+    // Words the letters still allow
+    pub candidates: usize,
+    // The mode the suggestions were ranked in, fast or exact
+    pub mode: BestGuessMode,
+    // Best first
+    pub suggestions: Vec<GuessSuggestion>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FeedbackPair {
+    #[schema(example = "crane")]
+    pub guess: String,
+    #[schema(example = "crate")]
+    pub answer: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FeedbackBatchRequest {
+    pub pairs: Vec<FeedbackPair>,
+}
+
+// Either the feedback or why the pair couldn't be marked, never both
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeedbackResult {
+    #[schema(example = "gggxg")]
+    pub feedback: Option<String>,
+    pub squares: Option<Vec<Square>>,
+    pub error: Option<ErrorInfo>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FeedbackBatch {
+    // In the order the pairs were sent
+    pub results: Vec<FeedbackResult>,
+}
+
+// The Wordle convention, and what a session allows when it's started without saying
+pub const STANDARD_MAX_GUESSES: u32 = 6;
+
+fn standard_max_guesses() -> Option<u32> {
+    Some(STANDARD_MAX_GUESSES)
+}
+
+// The body is optional, starting a session without one is the same as sending {}
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewGameSession {
+    // Guesses allowed, 6 when left out and null for free play with no limit
+    #[serde(default = "standard_max_guesses")]
+    #[schema(example = 6)]
+    pub max_guesses: Option<u32>,
+}
+
+impl Default for NewGameSession {
+    fn default() -> Self {
+        NewGameSession { max_guesses: standard_max_guesses() }
+    }
+}
+
+// A game session as stored
+#[derive(Debug, Clone, FromRow)]
+pub struct GameSessionRecord {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub token_hash: Option<String>,
+    pub status: String,
+    pub max_guesses: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct SessionGuessRecord {
+    pub guess: String,
+    pub feedback: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SessionGuess {
+    #[schema(example = "crane")]
+    pub guess: String,
+    #[schema(example = "xyxxg")]
+    pub feedback: String,
+    // The feedback letter by letter, in the palette asked for
+    pub squares: Vec<Square>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GameSession {
+    pub id: i32,
+    // active, solved once a guess got all green, or lost when the guesses ran out first
+    #[schema(example = "active")]
+    pub status: String,
+    // None in free play
+    #[schema(example = 6)]
+    pub max_guesses: Option<i32>,
+    // Not yet claimed by an account
+    pub anonymous: bool,
+    pub guesses: Vec<SessionGuess>,
+    // Words the feedback so far still allows
+    pub remaining: usize,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub claimed_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    #[schema(example = "answer had a double letter")]
+    pub note: Option<String>,
+    // Lowercase, in alphabetical order
+    pub tags: Vec<String>,
+}
+
+// Left out fields are left as they are. An empty note removes it and an empty list of
+// tags clears them. Tags are trimmed, lowercased and duplicates dropped.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateGameSession {
+    #[schema(example = "answer had a double letter")]
+    pub note: Option<String>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SessionHistoryQuery {
+    // Comma separated, sessions with every one of them
+    #[param(example = "double-letter,new-opener")]
+    pub tags: Option<String>,
+    // Words the note has to contain
+    #[param(example = "double letter")]
+    pub q: Option<String>,
+}
+
+// Days are UTC and both ends are included
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryExportQuery {
+    // Sessions started on or after this day
+    #[param(value_type = Option<String>, format = Date, example = "2023-07-01")]
+    pub from: Option<NaiveDate>,
+    // Sessions started on or before this day
+    #[param(value_type = Option<String>, format = Date, example = "2023-07-31")]
+    pub to: Option<NaiveDate>,
+    // Starts the file with a UTF-8 byte order mark, which Excel needs to read it as UTF-8
+    #[serde(default)]
+    pub bom: bool,
+}
+
+// A session in the caller's history, without the guesses themselves
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionSummary {
+    pub id: i32,
+    #[schema(example = "solved")]
+    pub status: String,
+    pub max_guesses: Option<i32>,
+    // Guesses made
+    pub guesses: i64,
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CreatedGameSession {
+    pub session: GameSession,
+    // Only for anonymous sessions and only sent this once. Send it as X-Session-Token to
+    // play the session, and in the body of a claim.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewSessionGuess {
+    #[schema(example = "crane")]
+    pub guess: String,
+    #[schema(example = "xyxxg")]
+    pub feedback: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ClaimSession {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GuessCount {
+    pub guesses: i64,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct FreePlayStats {
+    pub played: i64,
+    pub solved: i64,
+}
+
+// The caller's sessions. Those with a guess limit make up the standard numbers, free play
+// is counted on its own so an unlimited game can't stretch the distribution.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionStats {
+    pub played: i64,
+    pub solved: i64,
+    pub lost: i64,
+    // Solved sessions by the guesses they took, from 1 to at least 6 with zeros included
+    pub distribution: Vec<GuessCount>,
+    pub free_play: FreePlayStats,
+}
+
+// A user's row in user_stats
+#[derive(Debug, Default, Clone, PartialEq, Eq, FromRow)]
+pub struct UserStatsRecord {
+    pub played: i64,
+    pub solved: i64,
+    pub lost: i64,
+    pub free_play_played: i64,
+    pub free_play_solved: i64,
+}
+
+// A user's totals with their guess counts, as stored or as rebuilt from their sessions
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct UserStats {
+    pub totals: UserStatsRecord,
+    // Solved sessions with a guess limit by the guesses they took, without zeros
+    pub distribution: BTreeMap<i64, i64>,
+}
+
+impl UserStats {
+    // What a session adds when it's started
+    pub fn started(max_guesses: Option<i32>) -> Self {
+        let mut stats = UserStats::default();
+        if max_guesses.is_some() {
+            stats.totals.played = 1;
+        } else {
+            stats.totals.free_play_played = 1;
+        }
+        stats
+    }
+
+    // What it adds once it's `status` after `guesses`, nothing while it's still active
+    pub fn finished(max_guesses: Option<i32>, status: &str, guesses: i64) -> Self {
+        let mut stats = UserStats::default();
+        match (max_guesses.is_some(), status) {
+            (true, "solved") => {
+                stats.totals.solved = 1;
+                stats.distribution.insert(guesses, 1);
+            }
+            (true, "lost") => stats.totals.lost = 1,
+            (false, "solved") => stats.totals.free_play_solved = 1,
+            _ => {}
+        }
+        stats
+    }
+
+    // Everything a session counts for so far
+    pub fn of_session(max_guesses: Option<i32>, status: &str, guesses: i64) -> Self {
+        let mut stats = UserStats::started(max_guesses);
+        stats.add(&UserStats::finished(max_guesses, status, guesses));
+        stats
+    }
+
+    pub fn add(&mut self, other: &UserStats) {
+        let (totals, more) = (&mut self.totals, &other.totals);
+        totals.played += more.played;
+        totals.solved += more.solved;
+        totals.lost += more.lost;
+        totals.free_play_played += more.free_play_played;
+        totals.free_play_solved += more.free_play_solved;
+        for (guesses, count) in &other.distribution {
+            *self.distribution.entry(*guesses).or_default() += count;
+        }
+    }
+}
+
+impl From<UserStats> for SessionStats {
+    fn from(stats: UserStats) -> Self {
+        // Sessions started with a higher limit can take more than six
+        let buckets = stats.distribution.keys().copied().max().unwrap_or(0).max(STANDARD_MAX_GUESSES.into());
+        let distribution = (1..=buckets)
+            .map(|guesses| GuessCount { guesses, count: stats.distribution.get(&guesses).copied().unwrap_or(0) })
+            .collect();
+        let totals = stats.totals;
+
+        SessionStats {
+            played: totals.played,
+            solved: totals.solved,
+            lost: totals.lost,
+            distribution,
+            free_play: FreePlayStats { played: totals.free_play_played, solved: totals.free_play_solved },
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct WordStatsRecord {
+    pub word: String,
+    pub plays: i64,
+    pub failures: i64,
+    pub solved_guesses: i64,
+}
+
+// How an answer fared in the games the server picked it for, league dailies so far. Free
+// play has no answer to count against.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WordStats {
+    #[schema(example = "crane")]
+    pub word: String,
+    pub plays: i64,
+    pub failures: i64,
+    // Over the solved plays, None until one is solved
+    #[schema(example = 3.5)]
+    pub average_guesses: Option<f64>,
+    // From 0 to 1, 0 while there are no plays
+    #[schema(example = 0.2)]
+    pub failure_rate: f64,
+}
+
+impl WordStats {
+    // All zeros for a word no finished game has had yet
+    pub fn empty(word: String) -> Self {
+        WordStats { word, plays: 0, failures: 0, average_guesses: None, failure_rate: 0.0 }
+    }
+}
+
+impl From<WordStatsRecord> for WordStats {
+    fn from(record: WordStatsRecord) -> Self {
+        let solves = record.plays - record.failures;
+        WordStats {
+            average_guesses: (solves > 0).then(|| record.solved_guesses as f64 / solves as f64),
+            failure_rate: if record.plays > 0 { record.failures as f64 / record.plays as f64 } else { 0.0 },
+            word: record.word,
+            plays: record.plays,
+            failures: record.failures,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HardestWordsQuery {
+    // Words played fewer times are left out, 1 when left out
+    pub min_plays: Option<i64>,
+}
+
+// How a sync from the remote word list went. `revision` is None when the source said the
+// list hadn't changed since the last sync and nothing was fetched.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WordListSync {
+    #[schema(example = "updated")]
+    pub status: &'static str,
+    pub added: usize,
+    pub removed: usize,
+    pub word_count: i64,
+    // The word list version now, the ETag of /game/words follows it
+    pub version: i64,
+    pub revision: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WordChangesQuery {
+    // The version of the list the client holds, from the ETag of /game/words
+    pub since_version: i64,
+}
+
+// How to bring a list at `since_version` up to `version`, both sorted
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WordListChanges {
+    pub since_version: i64,
+    pub version: i64,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+// A change to the word list a player can propose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionAction {
+    Add,
+    Remove,
+    // Let the word be an answer, not only a guess
+    MarkAnswer,
+    UnmarkAnswer,
+}
+
+impl SuggestionAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SuggestionAction::Add => "add",
+            SuggestionAction::Remove => "remove",
+            SuggestionAction::MarkAnswer => "mark_answer",
+            SuggestionAction::UnmarkAnswer => "unmark_answer",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<SuggestionAction> {
+        match value {
+            "add" => Some(SuggestionAction::Add),
+            "remove" => Some(SuggestionAction::Remove),
+            "mark_answer" => Some(SuggestionAction::MarkAnswer),
+            "unmark_answer" => Some(SuggestionAction::UnmarkAnswer),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SuggestionStatus {
+    #[default]
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl SuggestionStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SuggestionStatus::Pending => "pending",
+            SuggestionStatus::Approved => "approved",
+            SuggestionStatus::Rejected => "rejected",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewWordSuggestion {
+    pub word: String,
+    pub action: SuggestionAction,
+    pub reason: String,
+}
+
+// `reason` has every supporter's reason, one per line
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct WordSuggestion {
+    pub id: i32,
+    pub word: String,
+    #[schema(example = "add")]
+    pub action: String,
+    #[schema(example = "pending")]
+    pub status: String,
+    pub suggested_by: Option<i32>,
+    pub reason: String,
+    pub supporters: i32,
+    pub reviewed_by: Option<i32>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub review_note: Option<String>,
+    // The word list revision an approval made
+    pub revision_id: Option<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SuggestionQuery {
+    // pending when left out
+    pub status: Option<SuggestionStatus>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReviewSuggestion {
+    // Kept with the review for other admins to see
+    pub note: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewSessionReport {
+    // What looked wrong, for the admin looking into it
+    #[schema(example = "CRATE was suggested after the T came back gray")]
+    pub description: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct ReportedGuess {
+    #[schema(example = "pious")]
+    pub guess: String,
+    #[schema(example = "xxxxx")]
+    pub feedback: String,
+}
+
+// What the solver makes of a session's guesses against one version of the word list
+#[derive(Debug, PartialEq, Deserialize, Serialize, ToSchema)]
+pub struct SolverOutput {
+    pub word_list_version: i64,
+    // Words the feedback allows
+    pub candidate_count: usize,
+    // The first of them in alphabetical order, at most 100
+    pub candidates: Vec<String>,
+    // The best next guesses, best first
+    pub suggestions: Vec<GuessSuggestion>,
+}
+
+// A session_reports row, its guesses and original output still JSON
+#[derive(Debug, FromRow)]
+pub struct SessionReportRecord {
+    pub id: i32,
+    pub session_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub description: String,
+    pub guesses: String,
+    pub word_list_version: i64,
+    pub original: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionReport {
+    pub id: i32,
+    // Null once the session or the account is gone
+    pub session_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub description: String,
+    pub guesses: Vec<ReportedGuess>,
+    // What the reporter was shown
+    pub original: SolverOutput,
+    pub created_at: DateTime<Utc>,
+}
+
+// The report's guesses run again, against the word list as it was reported and as it is
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReportReplay {
+    pub report: SessionReport,
+    // Null when the history of the list back to the report's version was lost, e.g. it
+    // changed outside a revision
+    pub replayed: Option<SolverOutput>,
+    pub current: SolverOutput,
 }
@@ -0,0 +1,71 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+// A job as the worker claims it
+#[derive(Debug, FromRow)]
+pub struct Job {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub job_type: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub max_attempts: i32,
+}
+
+#[derive(Debug, FromRow)]
+pub struct JobRecord {
+    pub id: i32,
+    pub job_type: String,
+    pub status: String,
+    pub attempts: i32,
+    pub result: Option<String>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+// `status` is one of queued, running, succeeded or failed. A job that failed but will be
+// retried is queued again, with the failure in `error`.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobStatus {
+    pub id: i32,
+    pub job_type: String,
+    pub status: String,
+    pub attempts: i32,
+    #[schema(value_type = Option<Object>)]
+    pub result: Option<Value>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+impl From<JobRecord> for JobStatus {
+    fn from(record: JobRecord) -> Self {
+        JobStatus {
+            id: record.id,
+            job_type: record.job_type,
+            status: record.status,
+            attempts: record.attempts,
+            result: record.result.and_then(|result| serde_json::from_str(&result).ok()),
+            error: record.last_error,
+            created_at: record.created_at,
+            finished_at: record.finished_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct JobAccepted {
+    pub id: i32,
+    // Where to poll for the outcome
+    pub status_url: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BenchmarkRequest {
+    // How many answers from the word list to play, 50 when left out
+    pub sample: Option<usize>,
+}
@@ -0,0 +1,131 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+use crate::models::game_models::SessionGuess;
+use crate::utils::answer_utils::AnswerStrategy;
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewLeague {
+    #[schema(example = "Third floor")]
+    pub name: String,
+    // Left out for the server's default
+    pub answer_strategy: Option<AnswerStrategy>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct JoinLeague {
+    #[schema(example = "k3Zq8vRb2M")]
+    pub invite_code: String,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct LeagueRecord {
+    pub id: i32,
+    pub name: String,
+    pub owner_id: i32,
+    pub invite_code: String,
+    pub answer_strategy: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Members all see the invite code, any of them can bring others in
+#[derive(Debug, Serialize, ToSchema)]
+pub struct League {
+    pub id: i32,
+    pub name: String,
+    pub owner_id: i32,
+    pub invite_code: String,
+    // None when the league follows the server's default
+    pub answer_strategy: Option<AnswerStrategy>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<LeagueRecord> for League {
+    fn from(record: LeagueRecord) -> Self {
+        League {
+            id: record.id,
+            name: record.name,
+            owner_id: record.owner_id,
+            invite_code: record.invite_code,
+            answer_strategy: record.answer_strategy.and_then(|strategy| strategy.parse().ok()),
+            created_at: record.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct LeagueMember {
+    pub user_id: i32,
+    #[schema(example = "tester")]
+    pub username: String,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LeagueDetails {
+    pub league: League,
+    // Oldest member first, the owner among them
+    pub members: Vec<LeagueMember>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewLeagueGuess {
+    #[schema(example = "crane")]
+    pub guess: String,
+}
+
+// The caller's game on the league's word of the day
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LeagueDaily {
+    pub league_id: i32,
+    pub day: NaiveDate,
+    // playing, solved, or lost once the guesses ran out
+    #[schema(example = "playing")]
+    pub status: String,
+    #[schema(example = 6)]
+    pub max_guesses: i32,
+    pub guesses: Vec<SessionGuess>,
+    // Only once the game is over
+    pub answer: Option<String>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LeaderboardQuery {
+    // The day to score, today when left out
+    pub day: Option<NaiveDate>,
+}
+
+// One member's guesses on one day, as counted from league_guesses
+#[derive(Debug, FromRow)]
+pub struct DayResult {
+    pub user_id: i32,
+    pub day: NaiveDate,
+    pub guesses: i64,
+    pub solved: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LeaderboardEntry {
+    pub user_id: i32,
+    #[schema(example = "tester")]
+    pub username: String,
+    // Guesses made on the day, 0 when the member didn't play
+    pub guesses: i64,
+    pub solved: bool,
+    // A solve in one guess earns max_guesses points, one fewer for every extra guess,
+    // anything else earns none
+    pub day_points: i64,
+    // Every day up to and including this one
+    pub total_points: i64,
+    pub days_played: i64,
+}
+
+// Current members only, most total points first
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Leaderboard {
+    pub league_id: i32,
+    pub day: NaiveDate,
+    pub entries: Vec<LeaderboardEntry>,
+}
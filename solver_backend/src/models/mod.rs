@@ -1,2 +1,7 @@
+pub mod admin_models;
 pub mod game_models;
+pub mod jobs_models;
+pub mod leagues_models;
+pub mod page_models;
 pub mod users_models;
+pub mod webhooks_models;
@@ -0,0 +1,26 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::models::admin_models::EmailDelivery;
+use crate::models::game_models::{SessionSummary, WordStats, WordSuggestion};
+use crate::models::users_models::{Session, UserResponse};
+use crate::models::webhooks_models::WebhookDelivery;
+
+// One page of a listing. `total` counts every item across all pages, `total_pages` is
+// zero when there are none.
+#[derive(Debug, Serialize, ToSchema)]
+#[aliases(UserPage = Paginated<UserResponse>, SessionPage = Paginated<Session>, GameSessionPage = Paginated<SessionSummary>, WordPage = Paginated<String>, WordSuggestionPage = Paginated<WordSuggestion>, WebhookDeliveryPage = Paginated<WebhookDelivery>, WordStatsPage = Paginated<WordStats>, EmailDeliveryPage = Paginated<EmailDelivery>)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub page: u32,
+    pub per_page: u32,
+    pub total: i64,
+    pub total_pages: i64,
+}
+
+// A page of a listing too long to count, walked with the cursor of the previous page.
+// `next_cursor` is None on the last page.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
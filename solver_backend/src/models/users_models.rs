@@ -1,6 +1,7 @@
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
 
 #[derive(Debug, Deserialize, Serialize, FromRow)]
 pub struct User {
@@ -14,54 +15,413 @@ pub struct User {
     pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct NewUser {
     pub username: String,
     pub email: String,
     pub password: String,
+    // A guest token from /users/guest. The live anonymous sessions started with it become
+    // the new account's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest_token: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct UpdateUser {
     pub username: String,
     pub email: String,
+    // The version the edit was made to, from the profile as last read
+    pub version: i32,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, ToSchema)]
 pub struct UpdatePassword {
     pub password: String,
 }
 
-#[derive(Debug, Deserialize, Serialize, FromRow)]
+#[derive(Debug, Deserialize, Serialize, FromRow, ToSchema)]
 pub struct UserResponse {
     pub id: i32,
     pub username: String,
     pub email: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Goes up with every edit to the username or email
+    pub version: i32,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateSettings {
+    pub notify_new_device: bool,
+}
+
+pub const MIN_WORD_LENGTH: u8 = 4;
+pub const MAX_WORD_LENGTH: u8 = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Alphabetical,
+    ReverseAlphabetical,
+}
+
+impl SortOrder {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SortOrder::Alphabetical => "alphabetical",
+            SortOrder::ReverseAlphabetical => "reverse_alphabetical",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<SortOrder> {
+        match value {
+            "alphabetical" => Some(SortOrder::Alphabetical),
+            "reverse_alphabetical" => Some(SortOrder::ReverseAlphabetical),
+            _ => None,
+        }
+    }
+
+    pub fn sort(self, words: &mut [String]) {
+        words.sort_unstable();
+        if self == SortOrder::ReverseAlphabetical {
+            words.reverse();
+        }
+    }
+}
+
+// Who besides the user and admins can see their stats. There are no friendships yet, so a
+// friends-only profile is shown to nobody else for now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProfileVisibility {
+    #[default]
+    Public,
+    Friends,
+    Private,
+}
+
+impl ProfileVisibility {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ProfileVisibility::Public => "public",
+            ProfileVisibility::Friends => "friends",
+            ProfileVisibility::Private => "private",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<ProfileVisibility> {
+        match value {
+            "public" => Some(ProfileVisibility::Public),
+            "friends" => Some(ProfileVisibility::Friends),
+            "private" => Some(ProfileVisibility::Private),
+            _ => None,
+        }
+    }
+}
+
+// Emails a user can turn off. Account emails like confirmations can't be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    NewDevice,
+}
+
+// What a user saved, or the defaults for anything they haven't
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Preferences {
+    pub hard_mode: bool,
+    pub colorblind: bool,
+    pub word_length: u8,
+    pub sort_order: SortOrder,
+    pub notification_opt_outs: Vec<NotificationKind>,
+    pub profile_visibility: ProfileVisibility,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            hard_mode: false,
+            colorblind: false,
+            word_length: 5,
+            sort_order: SortOrder::default(),
+            notification_opt_outs: Vec::new(),
+            profile_visibility: ProfileVisibility::default(),
+        }
+    }
+}
+
+// Keys left out keep their current value, unknown keys are rejected
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UpdatePreferences {
+    pub hard_mode: Option<bool>,
+    pub colorblind: Option<bool>,
+    pub word_length: Option<u8>,
+    pub sort_order: Option<SortOrder>,
+    pub notification_opt_outs: Option<Vec<NotificationKind>>,
+    pub profile_visibility: Option<ProfileVisibility>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct EmailChange {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+// The token from the reset email and the password to set
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct PasswordReset {
+    pub token: String,
     pub password: String,
-    pub created_at: NaiveDateTime,
-    pub updated_at: NaiveDateTime,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ConfirmEmailQuery {
+    pub token: String,
+}
+
+// Read by the Pagination extractor, listed here for the docs
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    // Starts at 1
+    pub page: Option<u32>,
+    // Up to MAX_PAGE_SIZE, DEFAULT_PAGE_SIZE when left out
+    pub per_page: Option<u32>,
+}
+
+// Read by the CursorPagination extractor, listed here for the docs
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CursorQuery {
+    // `next_cursor` of the previous page, left out for the first
+    pub cursor: Option<String>,
+    // Up to MAX_PAGE_SIZE, DEFAULT_PAGE_SIZE when left out
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SuspendUser {
+    pub reason: String,
+    pub until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct LoginCredentials {
     pub username: String,
     pub password: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToSchema)]
 pub struct Token {
     pub token: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenRevocation {
+    // True when an earlier request had already revoked it
+    pub already_revoked: bool,
+}
+
+// What /auth/introspect says about a token, after RFC 7662. Only `active` and, for a
+// token that isn't, `reason` are sent unless the token is accepted.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TokenIntrospection {
+    pub active: bool,
+    // The user id, or the guest id on a guest token
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    // Unix timestamps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<InactiveReason>,
+}
+
+// Why a token isn't accepted. version_mismatch is a token from before the user's last
+// logout everywhere or password change; revoked also covers users that no longer exist and
+// impersonation by someone who's no longer an admin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum InactiveReason {
+    Expired,
+    Revoked,
+    VersionMismatch,
+    // Malformed or badly signed
+    BadSignature,
+    // Signed with a key id that isn't configured, e.g. one that has been retired
+    UnknownKey,
+    // A refresh token where an access token is expected, or the other way round
+    WrongType,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
 pub struct Tokens {
     pub access: String,
     pub refresh: String,
 }
 
+// An access token for trying the solver without an account. It can't be refreshed, and
+// only filtering candidates, feedback and anonymous sessions accept it.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GuestToken {
+    pub access: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct RefreshToken {
+    pub id: i32,
+    pub user_id: i32,
+    pub family_id: Option<i32>,
+    pub token: String,
+    pub access_token: String,
+    pub replaced_by: Option<i32>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub revoked: bool,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct Session {
+    pub id: i32,
+    pub device_label: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    // The admin behind an impersonation, which ends at expires_at. Both are null for logins.
+    pub impersonated_by: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+// The sections of a data export, see `export_user_data` for the document they make up
+#[derive(Debug, Serialize, FromRow)]
+pub struct ExportedProfile {
+    pub id: i32,
+    pub username: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub pending_email: Option<String>,
+    pub role: String,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub suspended_at: Option<DateTime<Utc>>,
+    pub suspended_until: Option<DateTime<Utc>>,
+    pub suspension_reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ExportedSession {
+    pub id: i32,
+    pub family_id: Option<i32>,
+    pub device_label: String,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub revoked: bool,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ExportedAuthEvent {
+    pub id: i32,
+    pub event_type: String,
+    pub detail: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DailyCount {
+    pub day: NaiveDate,
+    pub count: i64,
+}
+
+// Admin dashboard numbers. `registrations` always has one entry per day for the last
+// 30 days, oldest first, with zero counts included so it can be charted directly.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserMetrics {
+    pub total_users: i64,
+    pub registrations: Vec<DailyCount>,
+    pub active_last_7_days: i64,
+    pub verified_email_percentage: f64,
+}
+
+// What came of a CSV import. `problems` lists every row that wasn't inserted, by its line
+// in the file, the header being line 1.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserImportReport {
+    pub inserted: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub problems: Vec<ImportProblem>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportProblem {
+    pub row: u64,
+    pub outcome: ImportOutcome,
+    pub reason: String,
+}
+
+// Skipped rows name an account that already exists, failed ones couldn't be read or saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportOutcome {
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, FromRow, ToSchema)]
+pub struct RouteUsage {
+    pub day: NaiveDate,
+    // Method and route pattern, such as GET /api/v1/users/{id}
+    pub route: String,
+    pub calls: i64,
+}
+
+// A user's authenticated calls from `since` to today, only days and routes with calls
+// listed. Includes calls this server hasn't written to the database yet.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UsageReport {
+    pub user_id: i32,
+    pub since: NaiveDate,
+    pub total_calls: i64,
+    pub usage: Vec<RouteUsage>,
+}
+
+// Which of the pair a token is, so neither can be used in place of the other. Tokens
+// issued before the claim existed count as access tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenType {
+    #[default]
+    Access,
+    Refresh,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AccessClaims {
     pub user_id: i32,
+    #[serde(default)]
+    pub token_version: i32,
     pub issued: NaiveDateTime,
     pub exp: usize,
+    #[serde(default)]
+    pub typ: TokenType,
+    // The admin acting as this user, only on impersonation tokens
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impersonator: Option<i32>,
+    // Only on guest tokens, which have no user behind them and a user_id of 0
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guest: Option<String>,
 }
@@ -0,0 +1,103 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, FromRow)]
+pub struct WebhookRecord {
+    pub id: i32,
+    pub user_id: i32,
+    pub url: String,
+    pub secret: String,
+    // Comma separated
+    pub events: String,
+    pub all_users: bool,
+    pub enabled: bool,
+    pub consecutive_failures: i32,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WebhookRecord {
+    pub fn subscribes_to(&self, event: &str) -> bool {
+        self.events.split(',').any(|subscribed| subscribed == event)
+    }
+}
+
+// A registration as its owner sees it, without the secret. `all_users` is set for
+// endpoints an admin registered, which get every user's events.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    pub events: Vec<String>,
+    pub all_users: bool,
+    pub enabled: bool,
+    pub consecutive_failures: i32,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<WebhookRecord> for Webhook {
+    fn from(record: WebhookRecord) -> Self {
+        Webhook {
+            id: record.id,
+            url: record.url,
+            events: record.events.split(',').map(str::to_string).collect(),
+            all_users: record.all_users,
+            enabled: record.enabled,
+            consecutive_failures: record.consecutive_failures,
+            disabled_at: record.disabled_at,
+            created_at: record.created_at,
+            updated_at: record.updated_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewWebhook {
+    pub url: String,
+    // At least 16 characters
+    pub secret: String,
+    #[schema(example = json!(["daily_completed"]))]
+    pub events: Vec<String>,
+}
+
+// Keys left out keep their current value. Enabling a disabled endpoint clears its failures.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateWebhook {
+    pub url: Option<String>,
+    pub secret: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+}
+
+// What the job sending a delivery needs
+#[derive(Debug, FromRow)]
+pub struct PendingDelivery {
+    pub id: i32,
+    pub event: String,
+    pub payload: String,
+    pub webhook_id: i32,
+    pub url: String,
+    pub secret: String,
+    pub enabled: bool,
+}
+
+// `status` is pending, retrying, succeeded or failed. `response_status` and `error` are
+// from the last attempt, a missing status meaning no response came back at all.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event: String,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,59 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Any, Executor};
+
+pub async fn used_on<'e>(db: impl Executor<'e, Database = Any>, scope: &str, day: NaiveDate) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT word FROM used_answers WHERE scope = $1 AND day = $2")
+        .bind(scope)
+        .bind(day)
+        .fetch_optional(db)
+        .await
+}
+
+// False when the day already had an answer, which is left as it was
+pub async fn record_used<'e>(db: impl Executor<'e, Database = Any>, scope: &str, day: NaiveDate, word: &str, now: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+            r#"
+            INSERT INTO used_answers (scope, day, word, created_at) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (scope, day) DO NOTHING
+            "#)
+        .bind(scope)
+        .bind(day)
+        .bind(word)
+        .bind(now)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// The answers of `scope` on `since` and every day after it up to, not including, `until`
+pub async fn used_between<'e>(db: impl Executor<'e, Database = Any>, scope: &str, since: NaiveDate, until: NaiveDate) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT word FROM used_answers WHERE scope = $1 AND day >= $2 AND day < $3")
+        .bind(scope)
+        .bind(since)
+        .bind(until)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn scheduled<'e>(db: impl Executor<'e, Database = Any>, scope: &str, day: NaiveDate) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT word FROM answer_schedule WHERE scope = $1 AND day = $2")
+        .bind(scope)
+        .bind(day)
+        .fetch_optional(db)
+        .await
+}
+
+// Replaces whatever was scheduled for the day
+pub async fn schedule<'e>(db: impl Executor<'e, Database = Any>, scope: &str, day: NaiveDate, word: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            INSERT INTO answer_schedule (scope, day, word) VALUES ($1, $2, $3)
+            ON CONFLICT (scope, day) DO UPDATE SET word = excluded.word
+            "#)
+        .bind(scope)
+        .bind(day)
+        .bind(word)
+        .execute(db)
+        .await?;
+    Ok(())
+}
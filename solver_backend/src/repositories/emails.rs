@@ -0,0 +1,70 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Any, Executor, FromRow};
+use crate::models::admin_models::{EmailDelivery, EmailStatus};
+use crate::utils::mail_utils::Email;
+
+const COLUMNS: &str = "id, template, recipient, subject, status, attempts, error, created_at, last_attempt_at, sent_at";
+
+pub async fn insert_delivery<'e>(db: impl Executor<'e, Database = Any>, template: &str, email: &Email, now: DateTime<Utc>) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar(
+            r#"
+            INSERT INTO email_deliveries (template, recipient, subject, text_body, html_body, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id
+            "#)
+        .bind(template)
+        .bind(&email.to)
+        .bind(&email.subject)
+        .bind(&email.text)
+        .bind(email.html.as_deref())
+        .bind(now)
+        .fetch_one(db)
+        .await
+}
+
+#[derive(Debug, FromRow)]
+struct UnsentRecord {
+    recipient: String,
+    subject: String,
+    text_body: String,
+    html_body: Option<String>,
+}
+
+// None once it was sent or dead-lettered
+pub async fn find_unsent<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<Email>, sqlx::Error> {
+    let record: Option<UnsentRecord> = sqlx::query_as("SELECT recipient, subject, text_body, html_body FROM email_deliveries WHERE id = $1 AND status IN ('queued', 'retrying')")
+        .bind(id)
+        .fetch_optional(db)
+        .await?;
+    Ok(record.map(|record| Email { to: record.recipient, subject: record.subject, text: record.text_body, html: record.html_body }))
+}
+
+pub async fn record_attempt<'e>(db: impl Executor<'e, Database = Any>, id: i32, status: EmailStatus, error: Option<&str>, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    let sent_at = (status == EmailStatus::Sent).then_some(now);
+    sqlx::query("UPDATE email_deliveries SET status = $1, attempts = attempts + 1, error = $2, last_attempt_at = $3, sent_at = $4 WHERE id = $5")
+        .bind(status.as_str())
+        .bind(error)
+        .bind(now)
+        .bind(sent_at)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Newest first
+pub async fn list<'e>(db: impl Executor<'e, Database = Any>, status: Option<EmailStatus>, limit: i64, offset: i64) -> Result<Vec<EmailDelivery>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM email_deliveries WHERE $1 IS NULL OR status = $1 ORDER BY id DESC LIMIT $2 OFFSET $3", COLUMNS))
+        .bind(status.map(EmailStatus::as_str))
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn count<'e>(db: impl Executor<'e, Database = Any>, status: Option<EmailStatus>) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM email_deliveries WHERE $1 IS NULL OR status = $1")
+        .bind(status.map(EmailStatus::as_str))
+        .fetch_one(db)
+        .await
+}
@@ -0,0 +1,24 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Any, Executor};
+
+// Flags an admin has set, as name -> enabled pairs
+pub async fn stored_flags<'e>(db: impl Executor<'e, Database = Any>) -> Result<Vec<(String, bool)>, sqlx::Error> {
+    sqlx::query_as("SELECT name, enabled FROM feature_flags")
+        .fetch_all(db)
+        .await
+}
+
+pub async fn store_flag<'e>(db: impl Executor<'e, Database = Any>, name: &str, enabled: bool, updated_by: i32, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            INSERT INTO feature_flags (name, enabled, updated_by, updated_at) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (name) DO UPDATE SET enabled = excluded.enabled, updated_by = excluded.updated_by, updated_at = excluded.updated_at
+            "#)
+        .bind(name)
+        .bind(enabled)
+        .bind(updated_by)
+        .bind(now)
+        .execute(db)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,331 @@
+use chrono::{DateTime, Utc};
+use sqlx::any::AnyKind;
+use sqlx::{Any, AnyConnection, Executor, FromRow};
+use std::collections::HashMap;
+use crate::models::game_models::{GameSessionRecord, SessionGuessRecord};
+
+const COLUMNS: &str = "id, user_id, token_hash, status, max_guesses, expires_at, claimed_at, completed_at, created_at, updated_at, note";
+
+// Owned by `user_id`, or anonymous with `token_hash` until `expires_at`, started by
+// `guest_id` when a guest token was sent. No `max_guesses` is free play.
+pub async fn insert<'e>(db: impl Executor<'e, Database = Any>, user_id: Option<i32>, token_hash: Option<&str>, guest_id: Option<&str>, max_guesses: Option<i32>, expires_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Result<GameSessionRecord, sqlx::Error> {
+    sqlx::query_as(&format!(
+            r#"
+            INSERT INTO game_sessions (user_id, token_hash, guest_id, max_guesses, expires_at, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            RETURNING {}
+            "#, COLUMNS))
+        .bind(user_id)
+        .bind(token_hash)
+        .bind(guest_id)
+        .bind(max_guesses)
+        .bind(expires_at)
+        .bind(now)
+        .fetch_one(db)
+        .await
+}
+
+pub async fn find<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<GameSessionRecord>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM game_sessions WHERE id = $1", COLUMNS))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Locked until the surrounding transaction ends, so guesses to the same session are made
+// one at a time. Written to rather than selected with `row_lock`, as SQLite only takes its
+// write lock on the first write and two transactions that both read first deadlock.
+pub async fn lock<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<GameSessionRecord>, sqlx::Error> {
+    sqlx::query_as(&format!("UPDATE game_sessions SET updated_at = updated_at WHERE id = $1 RETURNING {}", COLUMNS))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// In the order they were made
+pub async fn guesses<'e>(db: impl Executor<'e, Database = Any>, session_id: i32) -> Result<Vec<SessionGuessRecord>, sqlx::Error> {
+    sqlx::query_as("SELECT guess, feedback, created_at FROM session_guesses WHERE session_id = $1 ORDER BY turn")
+        .bind(session_id)
+        .fetch_all(db)
+        .await
+}
+
+// `turn` counts from 1. A turn that's already taken is a unique violation.
+pub async fn insert_guess<'e>(db: impl Executor<'e, Database = Any>, session_id: i32, turn: i32, guess: &str, feedback: &str, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO session_guesses (session_id, turn, guess, feedback, created_at) VALUES ($1, $2, $3, $4, $5)")
+        .bind(session_id)
+        .bind(turn)
+        .bind(guess)
+        .bind(feedback)
+        .bind(now)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Marks the session solved when `solved`, lost when `out_of_guesses` otherwise, and as
+// changed either way
+pub async fn record_progress<'e>(db: impl Executor<'e, Database = Any>, id: i32, solved: bool, out_of_guesses: bool, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            UPDATE game_sessions SET updated_at = $1,
+                status = CASE WHEN $2 THEN 'solved' WHEN $3 THEN 'lost' ELSE status END,
+                completed_at = CASE WHEN $2 OR $3 THEN $1 ELSE completed_at END
+            WHERE id = $4
+            "#)
+        .bind(now)
+        .bind(solved)
+        .bind(out_of_guesses)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Hands an anonymous session to `user_id`, its guesses with it. Checks the token, expiry
+// and that nobody claimed it first in the same statement, so of two racing claims one
+// wins. False when any of those didn't hold.
+pub async fn claim<'e>(db: impl Executor<'e, Database = Any>, id: i32, user_id: i32, token_hash: &str, now: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+            r#"
+            UPDATE game_sessions SET user_id = $1, token_hash = NULL, expires_at = NULL, claimed_at = $2, updated_at = $2
+            WHERE id = $3 AND user_id IS NULL AND token_hash = $4 AND expires_at > $2
+            "#)
+        .bind(user_id)
+        .bind(now)
+        .bind(id)
+        .bind(token_hash)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Hands every anonymous session `guest_id` started that hasn't expired to `user_id`, the
+// ids of those handed over
+pub async fn claim_guest<'e>(db: impl Executor<'e, Database = Any>, guest_id: &str, user_id: i32, now: DateTime<Utc>) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar(
+            r#"
+            UPDATE game_sessions SET user_id = $1, token_hash = NULL, guest_id = NULL, expires_at = NULL, claimed_at = $2, updated_at = $2
+            WHERE guest_id = $3 AND user_id IS NULL AND expires_at > $2
+            RETURNING id
+            "#)
+        .bind(user_id)
+        .bind(now)
+        .bind(guest_id)
+        .fetch_all(db)
+        .await
+}
+
+// Anonymous sessions nobody claimed in time, with their guesses
+pub async fn delete_expired<'e>(db: impl Executor<'e, Database = Any>, now: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM game_sessions WHERE user_id IS NULL AND expires_at < $1")
+        .bind(now)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+// The session as (max_guesses, status, guesses made)
+pub async fn outcome<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<(Option<i32>, String, i64)>, sqlx::Error> {
+    sqlx::query_as("SELECT max_guesses, status, (SELECT COUNT(*) FROM session_guesses g WHERE g.session_id = s.id) FROM game_sessions s WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Every session of each of `user_ids` as (user_id, max_guesses, status, guesses made)
+pub async fn outcomes_of<'e>(db: impl Executor<'e, Database = Any>, user_ids: &[i32]) -> Result<Vec<(i32, Option<i32>, String, i64)>, sqlx::Error> {
+    if user_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let placeholders: Vec<String> = (1..=user_ids.len()).map(|index| format!("${}", index)).collect();
+    let sql = format!(
+        r#"
+        SELECT s.user_id, s.max_guesses, s.status, COUNT(g.id)
+        FROM game_sessions s LEFT JOIN session_guesses g ON g.session_id = s.id
+        WHERE s.user_id IN ({})
+        GROUP BY s.id, s.user_id, s.max_guesses, s.status
+        "#,
+        placeholders.join(", "));
+    let mut query = sqlx::query_as(&sql);
+    for id in user_ids {
+        query = query.bind(*id);
+    }
+    query.fetch_all(db).await
+}
+
+// None removes the note
+pub async fn set_note<'e>(db: impl Executor<'e, Database = Any>, id: i32, note: Option<&str>, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE game_sessions SET note = $1, updated_at = $2 WHERE id = $3")
+        .bind(note)
+        .bind(now)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Replaces the session's tags with `tags`, which are expected to be normalized already
+pub async fn replace_tags(conn: &mut AnyConnection, id: i32, tags: &[String]) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM game_session_tags WHERE session_id = $1")
+        .bind(id)
+        .execute(&mut *conn)
+        .await?;
+
+    for tag in tags {
+        sqlx::query("INSERT INTO game_session_tags (session_id, tag) VALUES ($1, $2)")
+            .bind(id)
+            .bind(tag)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+// In alphabetical order
+pub async fn tags<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT tag FROM game_session_tags WHERE session_id = $1 ORDER BY tag")
+        .bind(id)
+        .fetch_all(db)
+        .await
+}
+
+// The tags of each of `ids`, sessions without any left out
+pub async fn tags_of<'e>(db: impl Executor<'e, Database = Any>, ids: &[i32]) -> Result<HashMap<i32, Vec<String>>, sqlx::Error> {
+    if ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let placeholders: Vec<String> = (1..=ids.len()).map(|index| format!("${}", index)).collect();
+    let sql = format!("SELECT session_id, tag FROM game_session_tags WHERE session_id IN ({}) ORDER BY tag", placeholders.join(", "));
+    let mut query = sqlx::query_as::<_, (i32, String)>(&sql);
+    for id in ids {
+        query = query.bind(*id);
+    }
+
+    let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+    for (id, tag) in query.fetch_all(db).await? {
+        tags.entry(id).or_default().push(tag);
+    }
+    Ok(tags)
+}
+
+// Narrows a user's history to sessions with every one of `tags` and a note containing
+// every word of `search`. Postgres matches the words as English, so "letters" finds
+// "letter", SQLite as case insensitive substrings.
+pub struct HistoryFilter<'a> {
+    pub tags: &'a [String],
+    pub search: Option<&'a str>,
+}
+
+// A guess with the session it was made in, as the history export lists it
+#[derive(Debug, FromRow)]
+pub struct ExportedGuess {
+    pub id: i32,
+    pub session_id: i32,
+    pub turn: i32,
+    pub guess: String,
+    pub feedback: String,
+    pub created_at: DateTime<Utc>,
+    pub status: String,
+    pub note: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+// The user's guesses after `after_id`, oldest first, in sessions started from `from` up to
+// but not including `until`
+pub async fn export_guesses<'e>(db: impl Executor<'e, Database = Any>, user_id: i32, from: Option<DateTime<Utc>>, until: Option<DateTime<Utc>>, after_id: i32, limit: i64) -> Result<Vec<ExportedGuess>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT g.id, g.session_id, g.turn, g.guess, g.feedback, g.created_at,
+                s.status, s.note, s.created_at AS started_at, s.completed_at
+            FROM session_guesses g
+            JOIN game_sessions s ON s.id = g.session_id
+            WHERE s.user_id = $1 AND g.id > $2
+                AND ($3 IS NULL OR s.created_at >= $3)
+                AND ($4 IS NULL OR s.created_at < $4)
+            ORDER BY g.id
+            LIMIT $5
+            "#)
+        .bind(user_id)
+        .bind(after_id)
+        .bind(from)
+        .bind(until)
+        .bind(limit)
+        .fetch_all(db)
+        .await
+}
+
+#[derive(Debug, FromRow)]
+pub struct HistoryRow {
+    pub id: i32,
+    pub status: String,
+    pub max_guesses: Option<i32>,
+    pub guesses: i64,
+    pub note: Option<String>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Newest first
+pub async fn history<'e>(db: impl Executor<'e, Database = Any>, kind: AnyKind, user_id: i32, filter: &HistoryFilter<'_>, limit: i64, offset: i64) -> Result<Vec<HistoryRow>, sqlx::Error> {
+    let (conditions, values) = history_conditions(kind, filter);
+    let sql = format!(
+        r#"
+        SELECT s.id, s.status, s.max_guesses, s.note, s.completed_at, s.created_at,
+            (SELECT COUNT(*) FROM session_guesses g WHERE g.session_id = s.id) AS guesses
+        FROM game_sessions s
+        WHERE s.user_id = $1{}
+        ORDER BY s.created_at DESC, s.id DESC
+        LIMIT ${} OFFSET ${}
+        "#,
+        conditions, values.len() + 2, values.len() + 3);
+
+    let mut query = sqlx::query_as(&sql).bind(user_id);
+    for value in &values {
+        query = query.bind(value);
+    }
+    query.bind(limit).bind(offset).fetch_all(db).await
+}
+
+pub async fn count_history<'e>(db: impl Executor<'e, Database = Any>, kind: AnyKind, user_id: i32, filter: &HistoryFilter<'_>) -> Result<i64, sqlx::Error> {
+    let (conditions, values) = history_conditions(kind, filter);
+    let sql = format!("SELECT COUNT(*) FROM game_sessions s WHERE s.user_id = $1{}", conditions);
+
+    let mut query = sqlx::query_scalar(&sql).bind(user_id);
+    for value in &values {
+        query = query.bind(value);
+    }
+    query.fetch_one(db).await
+}
+
+// The filter as SQL following `s.user_id = $1`, and the values for its placeholders from $2
+fn history_conditions(kind: AnyKind, filter: &HistoryFilter<'_>) -> (String, Vec<String>) {
+    let mut conditions = String::new();
+    let mut values = Vec::new();
+
+    for tag in filter.tags {
+        values.push(tag.clone());
+        conditions.push_str(&format!(" AND EXISTS (SELECT 1 FROM game_session_tags t WHERE t.session_id = s.id AND t.tag = ${})", values.len() + 1));
+    }
+
+    if let Some(search) = filter.search {
+        match kind {
+            // Uses the GIN index on the same expression
+            AnyKind::Postgres => {
+                values.push(search.to_string());
+                conditions.push_str(&format!(" AND to_tsvector('english', s.note) @@ plainto_tsquery('english', ${})", values.len() + 1));
+            }
+            AnyKind::Sqlite => {
+                for word in search.split_whitespace() {
+                    values.push(word.to_lowercase());
+                    conditions.push_str(&format!(" AND instr(lower(s.note), ${}) > 0", values.len() + 1));
+                }
+            }
+        }
+    }
+
+    (conditions, values)
+}
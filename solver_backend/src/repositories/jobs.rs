@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::any::AnyKind;
+use sqlx::{Any, Executor};
+use crate::models::jobs_models::{Job, JobRecord};
+use crate::utils::db_utils::skip_locked;
+
+pub async fn enqueue<'e>(db: impl Executor<'e, Database = Any>, user_id: Option<i32>, job_type: &str, payload: &Value, max_attempts: i32, now: DateTime<Utc>) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar(
+            r#"
+            INSERT INTO jobs (user_id, job_type, payload, max_attempts, scheduled_at, created_at)
+            VALUES ($1, $2, $3, $4, $5, $5)
+            RETURNING id
+            "#)
+        .bind(user_id)
+        .bind(job_type)
+        .bind(payload.to_string())
+        .bind(max_attempts)
+        .bind(now)
+        .fetch_one(db)
+        .await
+}
+
+// Marks the next due job as running and returns it. Jobs left running since before
+// `abandoned_before` belonged to a worker that died and are handed out again. Postgres
+// workers skip each other's rows instead of queueing behind them, and SQLite runs the
+// statement alone, so a job is never claimed twice.
+pub async fn claim<'e>(db: impl Executor<'e, Database = Any>, kind: AnyKind, now: DateTime<Utc>, abandoned_before: DateTime<Utc>) -> Result<Option<Job>, sqlx::Error> {
+    sqlx::query_as(&format!(
+            r#"
+            UPDATE jobs SET status = 'running', attempts = attempts + 1, locked_at = $1
+            WHERE id = (
+                SELECT id FROM jobs
+                WHERE (status = 'queued' AND scheduled_at <= $1)
+                    OR (status = 'running' AND locked_at < $2)
+                ORDER BY scheduled_at, id
+                LIMIT 1
+                {}
+            )
+            RETURNING id, user_id, job_type, payload, attempts, max_attempts
+            "#, skip_locked(kind)))
+        .bind(now)
+        .bind(abandoned_before)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn succeed<'e>(db: impl Executor<'e, Database = Any>, id: i32, result: &Value, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            UPDATE jobs SET status = 'succeeded', result = $1, locked_at = NULL, finished_at = $2
+            WHERE id = $3
+            "#)
+        .bind(result.to_string())
+        .bind(now)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Queues the job again for `retry_at`, or gives up on it when that is None
+pub async fn fail<'e>(db: impl Executor<'e, Database = Any>, id: i32, error: &str, retry_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    let query = match retry_at {
+        Some(retry_at) => sqlx::query("UPDATE jobs SET status = 'queued', last_error = $1, locked_at = NULL, scheduled_at = $2 WHERE id = $3")
+            .bind(error)
+            .bind(retry_at),
+        None => sqlx::query("UPDATE jobs SET status = 'failed', last_error = $1, locked_at = NULL, finished_at = $2 WHERE id = $3")
+            .bind(error)
+            .bind(now),
+    };
+
+    query.bind(id).execute(db).await?;
+    Ok(())
+}
+
+// Only the user who enqueued a job gets to see it
+pub async fn find_for_user<'e>(db: impl Executor<'e, Database = Any>, id: i32, user_id: i32) -> Result<Option<JobRecord>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT id, job_type, status, attempts, result, last_error, created_at, finished_at
+            FROM jobs WHERE id = $1 AND user_id = $2
+            "#)
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+}
+
+// Jobs waiting for a worker, retries not yet due included
+pub async fn count_queued<'e>(db: impl Executor<'e, Database = Any>) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM jobs WHERE status = 'queued'")
+        .fetch_one(db)
+        .await
+}
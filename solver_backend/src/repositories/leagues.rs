@@ -0,0 +1,162 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Any, Executor};
+use crate::models::game_models::SessionGuessRecord;
+use crate::models::leagues_models::{DayResult, LeagueMember, LeagueRecord};
+
+const COLUMNS: &str = "id, name, owner_id, invite_code, answer_strategy, created_at";
+
+// A taken invite code is a unique violation
+pub async fn insert<'e>(
+    db: impl Executor<'e, Database = Any>,
+    name: &str,
+    owner_id: i32,
+    invite_code: &str,
+    answer_strategy: Option<&str>,
+    now: DateTime<Utc>,
+) -> Result<LeagueRecord, sqlx::Error> {
+    sqlx::query_as(&format!(
+            r#"
+            INSERT INTO leagues (name, owner_id, invite_code, answer_strategy, created_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING {}
+            "#, COLUMNS))
+        .bind(name)
+        .bind(owner_id)
+        .bind(invite_code)
+        .bind(answer_strategy)
+        .bind(now)
+        .fetch_one(db)
+        .await
+}
+
+pub async fn find<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<LeagueRecord>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM leagues WHERE id = $1", COLUMNS))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn find_by_invite_code<'e>(db: impl Executor<'e, Database = Any>, invite_code: &str) -> Result<Option<LeagueRecord>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM leagues WHERE invite_code = $1", COLUMNS))
+        .bind(invite_code)
+        .fetch_optional(db)
+        .await
+}
+
+// Oldest league first
+pub async fn list_for_member<'e>(db: impl Executor<'e, Database = Any>, user_id: i32) -> Result<Vec<LeagueRecord>, sqlx::Error> {
+    sqlx::query_as(&format!(
+            "SELECT {} FROM leagues WHERE id IN (SELECT league_id FROM league_members WHERE user_id = $1) ORDER BY id",
+            COLUMNS))
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+// Its members and their guesses go with it
+pub async fn delete<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM leagues WHERE id = $1")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn is_member<'e>(db: impl Executor<'e, Database = Any>, league_id: i32, user_id: i32) -> Result<bool, sqlx::Error> {
+    let found: Option<i32> = sqlx::query_scalar("SELECT user_id FROM league_members WHERE league_id = $1 AND user_id = $2")
+        .bind(league_id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await?;
+    Ok(found.is_some())
+}
+
+// False when they were already a member
+pub async fn add_member<'e>(db: impl Executor<'e, Database = Any>, league_id: i32, user_id: i32, now: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+            r#"
+            INSERT INTO league_members (league_id, user_id, joined_at) VALUES ($1, $2, $3)
+            ON CONFLICT (league_id, user_id) DO NOTHING
+            "#)
+        .bind(league_id)
+        .bind(user_id)
+        .bind(now)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() == 1)
+}
+
+// False when they weren't a member
+pub async fn remove_member<'e>(db: impl Executor<'e, Database = Any>, league_id: i32, user_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM league_members WHERE league_id = $1 AND user_id = $2")
+        .bind(league_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Oldest member first
+pub async fn members<'e>(db: impl Executor<'e, Database = Any>, league_id: i32) -> Result<Vec<LeagueMember>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT m.user_id, u.username, m.joined_at FROM league_members m JOIN users u ON u.id = m.user_id
+            WHERE m.league_id = $1 ORDER BY m.joined_at, m.user_id
+            "#)
+        .bind(league_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn guesses<'e>(db: impl Executor<'e, Database = Any>, league_id: i32, user_id: i32, day: NaiveDate) -> Result<Vec<SessionGuessRecord>, sqlx::Error> {
+    sqlx::query_as("SELECT guess, feedback, created_at FROM league_guesses WHERE league_id = $1 AND user_id = $2 AND day = $3 ORDER BY turn")
+        .bind(league_id)
+        .bind(user_id)
+        .bind(day)
+        .fetch_all(db)
+        .await
+}
+
+pub struct LeagueGuess<'a> {
+    pub league_id: i32,
+    pub user_id: i32,
+    pub day: NaiveDate,
+    // Counts from 1
+    pub turn: i32,
+    pub guess: &'a str,
+    pub feedback: &'a str,
+}
+
+// A turn that's already taken is a unique violation
+pub async fn insert_guess<'e>(db: impl Executor<'e, Database = Any>, guess: &LeagueGuess<'_>, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO league_guesses (league_id, user_id, day, turn, guess, feedback, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7)")
+        .bind(guess.league_id)
+        .bind(guess.user_id)
+        .bind(guess.day)
+        .bind(guess.turn)
+        .bind(guess.guess)
+        .bind(guess.feedback)
+        .bind(now)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Every day each current member played up to and including `until`. `solved` is 1 for a
+// day with a guess given `all_green`.
+pub async fn results<'e>(db: impl Executor<'e, Database = Any>, league_id: i32, until: NaiveDate, all_green: &str) -> Result<Vec<DayResult>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT g.user_id, g.day, CAST(COUNT(*) AS BIGINT) AS guesses,
+                CAST(MAX(CASE WHEN g.feedback = $3 THEN 1 ELSE 0 END) AS BIGINT) AS solved
+            FROM league_guesses g
+            JOIN league_members m ON m.league_id = g.league_id AND m.user_id = g.user_id
+            WHERE g.league_id = $1 AND g.day <= $2
+            GROUP BY g.user_id, g.day
+            "#)
+        .bind(league_id)
+        .bind(until)
+        .bind(all_green)
+        .fetch_all(db)
+        .await
+}
@@ -0,0 +1,15 @@
+pub mod answers;
+pub mod emails;
+pub mod features;
+pub mod game_sessions;
+pub mod jobs;
+pub mod leagues;
+pub mod reports;
+pub mod suggestions;
+pub mod tokens;
+pub mod usage;
+pub mod user_stats;
+pub mod users;
+pub mod webhooks;
+pub mod word_stats;
+pub mod words;
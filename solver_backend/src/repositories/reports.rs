@@ -0,0 +1,40 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Any, Executor};
+use crate::models::game_models::SessionReportRecord;
+
+const COLUMNS: &str = "id, session_id, user_id, description, guesses, word_list_version, original, created_at";
+
+pub struct NewReport<'a> {
+    pub session_id: i32,
+    pub user_id: Option<i32>,
+    pub description: &'a str,
+    // JSON, stored as it's given
+    pub guesses: &'a str,
+    pub word_list_version: i64,
+    pub original: &'a str,
+}
+
+pub async fn insert<'e>(db: impl Executor<'e, Database = Any>, report: &NewReport<'_>, now: DateTime<Utc>) -> Result<SessionReportRecord, sqlx::Error> {
+    sqlx::query_as(&format!(
+            r#"
+            INSERT INTO session_reports (session_id, user_id, description, guesses, word_list_version, original, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING {}
+            "#, COLUMNS))
+        .bind(report.session_id)
+        .bind(report.user_id)
+        .bind(report.description)
+        .bind(report.guesses)
+        .bind(report.word_list_version)
+        .bind(report.original)
+        .bind(now)
+        .fetch_one(db)
+        .await
+}
+
+pub async fn find<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<SessionReportRecord>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM session_reports WHERE id = $1", COLUMNS))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
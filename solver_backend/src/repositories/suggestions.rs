@@ -0,0 +1,107 @@
+use chrono::{DateTime, Utc};
+use sqlx::any::AnyKind;
+use sqlx::{Any, AnyConnection, Executor};
+use crate::models::game_models::{SuggestionAction, SuggestionStatus, WordSuggestion};
+use crate::utils::db_utils::row_lock;
+
+const COLUMNS: &str = "id, word, action, status, suggested_by, reason, supporters, reviewed_by, reviewed_at, review_note, revision_id, created_at";
+
+// Opens a suggestion, or merges into the pending one for the same change. Someone who
+// already supports it doesn't count twice, and their reason isn't repeated. True when
+// it was merged.
+pub async fn propose(conn: &mut AnyConnection, word: &str, action: SuggestionAction, user_id: i32, reason: &str, now: DateTime<Utc>) -> Result<(i32, bool), sqlx::Error> {
+    let opened: Option<i32> = sqlx::query_scalar(
+            r#"
+            INSERT INTO word_suggestions (word, action, suggested_by, reason, created_at) VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (word, action) WHERE status = 'pending' DO NOTHING
+            RETURNING id
+            "#)
+        .bind(word)
+        .bind(action.as_str())
+        .bind(user_id)
+        .bind(reason)
+        .bind(now)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    let (id, merged) = match opened {
+        Some(id) => (id, false),
+        None => {
+            let id = sqlx::query_scalar("SELECT id FROM word_suggestions WHERE word = $1 AND action = $2 AND status = 'pending'")
+                .bind(word)
+                .bind(action.as_str())
+                .fetch_one(&mut *conn)
+                .await?;
+            (id, true)
+        }
+    };
+
+    let supporter = sqlx::query("INSERT INTO word_suggestion_supporters (suggestion_id, user_id, created_at) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING")
+        .bind(id)
+        .bind(user_id)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+
+    if merged && supporter.rows_affected() == 1 {
+        sqlx::query("UPDATE word_suggestions SET supporters = supporters + 1, reason = reason || $1 WHERE id = $2")
+            .bind(format!("\n{}", reason))
+            .bind(id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok((id, merged))
+}
+
+pub async fn find<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<WordSuggestion>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM word_suggestions WHERE id = $1", COLUMNS))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Held until the transaction ends, so two admins can't review it at once
+pub async fn find_for_review<'e>(db: impl Executor<'e, Database = Any>, id: i32, kind: AnyKind) -> Result<Option<WordSuggestion>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM word_suggestions WHERE id = $1 {}", COLUMNS, row_lock(kind)))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// The most supported first, then the oldest
+pub async fn list<'e>(db: impl Executor<'e, Database = Any>, status: SuggestionStatus, limit: i64, offset: i64) -> Result<Vec<WordSuggestion>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM word_suggestions WHERE status = $1 ORDER BY supporters DESC, id LIMIT $2 OFFSET $3", COLUMNS))
+        .bind(status.as_str())
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn count<'e>(db: impl Executor<'e, Database = Any>, status: SuggestionStatus) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM word_suggestions WHERE status = $1")
+        .bind(status.as_str())
+        .fetch_one(db)
+        .await
+}
+
+pub struct Review<'a> {
+    pub status: SuggestionStatus,
+    pub reviewed_by: i32,
+    pub note: Option<&'a str>,
+    pub revision_id: Option<i32>,
+}
+
+pub async fn record_review<'e>(db: impl Executor<'e, Database = Any>, id: i32, review: &Review<'_>, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE word_suggestions SET status = $1, reviewed_by = $2, reviewed_at = $3, review_note = $4, revision_id = $5 WHERE id = $6")
+        .bind(review.status.as_str())
+        .bind(review.reviewed_by)
+        .bind(now)
+        .bind(review.note)
+        .bind(review.revision_id)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
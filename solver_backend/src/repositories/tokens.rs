@@ -0,0 +1,192 @@
+use chrono::{DateTime, Utc};
+use sqlx::any::AnyKind;
+use sqlx::{Any, AnyConnection, Executor};
+use crate::models::users_models::{RefreshToken, Session};
+use crate::utils::db_utils::row_lock;
+use crate::utils::device_utils::ClientInfo;
+
+// Records a refresh token and the access token issued beside it as the newest link of a
+// rotation chain, returning its id. A `family_id` of None starts a new chain rooted at
+// the inserted row.
+pub async fn insert_refresh_token(conn: &mut AnyConnection, user_id: i32, family_id: Option<i32>, refresh_token: &str, access_token: &str, client: &ClientInfo) -> Result<i32, sqlx::Error> {
+    let token_id: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO refresh_tokens (user_id, family_id, token, access_token, user_agent, ip_address, device_label, fingerprint)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id
+            "#)
+        .bind(user_id)
+        .bind(family_id)
+        .bind(refresh_token)
+        .bind(access_token)
+        .bind(&client.user_agent)
+        .bind(&client.ip_address)
+        .bind(&client.device_label)
+        .bind(&client.fingerprint)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    if family_id.is_none() {
+        sqlx::query("UPDATE refresh_tokens SET family_id = id WHERE id = $1")
+            .bind(token_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(token_id)
+}
+
+// Stored like a login so it's listed among the user's sessions and can be revoked with
+// them, under the one token it has
+pub async fn insert_impersonation(conn: &mut AnyConnection, user_id: i32, impersonator_id: i32, token: &str, client: &ClientInfo, expires_at: DateTime<Utc>) -> Result<i32, sqlx::Error> {
+    let token_id: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO refresh_tokens (user_id, token, access_token, user_agent, ip_address, device_label, impersonator_id, expires_at)
+            VALUES ($1, $2, $2, $3, $4, $5, $6, $7)
+            RETURNING id
+            "#)
+        .bind(user_id)
+        .bind(token)
+        .bind(&client.user_agent)
+        .bind(&client.ip_address)
+        .bind(&client.device_label)
+        .bind(impersonator_id)
+        .bind(expires_at)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    sqlx::query("UPDATE refresh_tokens SET family_id = id WHERE id = $1")
+        .bind(token_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(token_id)
+}
+
+// Locked until the surrounding transaction ends
+pub async fn lock_refresh_token<'e>(db: impl Executor<'e, Database = Any>, kind: AnyKind, token: &str) -> Result<Option<RefreshToken>, sqlx::Error> {
+    sqlx::query_as(&format!(
+            r#"
+            SELECT id, user_id, family_id, token, access_token, replaced_by, rotated_at, revoked
+            FROM refresh_tokens WHERE token = $1
+            {}
+            "#, row_lock(kind)))
+        .bind(token)
+        .fetch_optional(db)
+        .await
+}
+
+// The token that replaced another, as long as it hasn't been rotated or revoked itself
+pub async fn live_successor<'e>(db: impl Executor<'e, Database = Any>, successor_id: i32) -> Result<Option<RefreshToken>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT id, user_id, family_id, token, access_token, replaced_by, rotated_at, revoked
+            FROM refresh_tokens WHERE id = $1 AND replaced_by IS NULL AND NOT revoked
+            "#)
+        .bind(successor_id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn mark_rotated<'e>(db: impl Executor<'e, Database = Any>, id: i32, successor_id: i32, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE refresh_tokens SET replaced_by = $1, rotated_at = $2 WHERE id = $3")
+        .bind(successor_id)
+        .bind(now)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Revokes every refresh token in a rotation chain along with the access tokens issued beside
+// them, returning the tokens that weren't already revoked
+pub async fn revoke_family(conn: &mut AnyConnection, family_id: i32, now: DateTime<Utc>) -> Result<Vec<String>, sqlx::Error> {
+    let revoked: Vec<String> = sqlx::query_scalar(
+            r#"
+            INSERT INTO revoked_tokens (token, created_at)
+            SELECT token, $2 FROM refresh_tokens WHERE family_id = $1
+            UNION
+            SELECT access_token, $2 FROM refresh_tokens WHERE family_id = $1
+            ON CONFLICT (token) DO NOTHING
+            RETURNING token
+            "#)
+        .bind(family_id)
+        .bind(now)
+        .fetch_all(&mut *conn)
+        .await?;
+
+    sqlx::query("UPDATE refresh_tokens SET revoked = TRUE WHERE family_id = $1")
+        .bind(family_id)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(revoked)
+}
+
+// False when the token was already revoked
+pub async fn revoke<'e>(db: impl Executor<'e, Database = Any>, token: &str, now: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("INSERT INTO revoked_tokens (token, created_at) VALUES ($1, $2) ON CONFLICT (token) DO NOTHING")
+        .bind(token)
+        .bind(now)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Sessions are the newest link of each unrevoked refresh token chain. Impersonations
+// drop out once their token has expired.
+pub async fn active_sessions<'e>(db: impl Executor<'e, Database = Any>, user_id: i32, now: DateTime<Utc>, limit: i64, offset: i64) -> Result<Vec<Session>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT id, device_label, user_agent, ip_address, created_at, impersonator_id AS impersonated_by, expires_at FROM refresh_tokens
+            WHERE user_id = $1 AND replaced_by IS NULL AND NOT revoked AND (expires_at IS NULL OR expires_at > $2)
+            ORDER BY created_at DESC, id DESC
+            LIMIT $3 OFFSET $4
+            "#)
+        .bind(user_id)
+        .bind(now)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn count_active_sessions<'e>(db: impl Executor<'e, Database = Any>, user_id: i32, now: DateTime<Utc>) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM refresh_tokens WHERE user_id = $1 AND replaced_by IS NULL AND NOT revoked AND (expires_at IS NULL OR expires_at > $2)")
+        .bind(user_id)
+        .bind(now)
+        .fetch_one(db)
+        .await
+}
+
+// The family of one of the user's active sessions, and the admin behind it if it's an
+// impersonation
+pub async fn find_active_session<'e>(db: impl Executor<'e, Database = Any>, user_id: i32, session_id: i32, now: DateTime<Utc>) -> Result<Option<(i32, Option<i32>)>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT COALESCE(family_id, id), impersonator_id FROM refresh_tokens
+            WHERE id = $1 AND user_id = $2 AND replaced_by IS NULL AND NOT revoked AND (expires_at IS NULL OR expires_at > $3)
+            "#)
+        .bind(session_id)
+        .bind(user_id)
+        .bind(now)
+        .fetch_optional(db)
+        .await
+}
+
+// Sessions across all users whose latest token was issued, at login or refresh, after `since`
+pub async fn count_sessions_active_since<'e>(db: impl Executor<'e, Database = Any>, since: DateTime<Utc>) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM refresh_tokens WHERE replaced_by IS NULL AND NOT revoked AND created_at > $1")
+        .bind(since)
+        .fetch_one(db)
+        .await
+}
+
+// Whether the user has been issued tokens on a device with this fingerprint before
+pub async fn device_seen<'e>(db: impl Executor<'e, Database = Any>, user_id: i32, fingerprint: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM refresh_tokens WHERE user_id = $1 AND fingerprint = $2)")
+        .bind(user_id)
+        .bind(fingerprint)
+        .fetch_one(db)
+        .await
+}
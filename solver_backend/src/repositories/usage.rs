@@ -0,0 +1,36 @@
+use chrono::NaiveDate;
+use sqlx::{Any, Executor};
+use crate::models::users_models::RouteUsage;
+
+// Adds to whatever a previous flush, here or on another replica, stored for the same key
+pub async fn add_calls<'e>(db: impl Executor<'e, Database = Any>, user_id: i32, route: &str, day: NaiveDate, calls: i64) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            INSERT INTO usage_stats (user_id, route, day, calls) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (user_id, day, route) DO UPDATE SET calls = usage_stats.calls + excluded.calls
+            "#)
+        .bind(user_id)
+        .bind(route)
+        .bind(day)
+        .bind(calls)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Oldest day first, routes alphabetical within a day
+pub async fn for_user<'e>(db: impl Executor<'e, Database = Any>, user_id: i32, since: NaiveDate) -> Result<Vec<RouteUsage>, sqlx::Error> {
+    sqlx::query_as("SELECT day, route, calls FROM usage_stats WHERE user_id = $1 AND day >= $2 ORDER BY day, route")
+        .bind(user_id)
+        .bind(since)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn delete_before<'e>(db: impl Executor<'e, Database = Any>, day: NaiveDate) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM usage_stats WHERE day < $1")
+        .bind(day)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
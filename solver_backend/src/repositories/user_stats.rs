@@ -0,0 +1,155 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Any, AnyConnection, Executor};
+use std::collections::HashMap;
+use crate::models::game_models::{UserStats, UserStatsRecord};
+
+// Adds `delta` to the user's totals and guess counts. The increments happen in the
+// database, so sessions finishing at the same time don't lose each other's counts. The
+// totals row is written first, so it's the row a recompute locks to hold these off.
+pub async fn add(conn: &mut AnyConnection, user_id: i32, delta: &UserStats, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    let totals = &delta.totals;
+    sqlx::query(
+            r#"
+            INSERT INTO user_stats (user_id, played, solved, lost, free_play_played, free_play_solved, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (user_id) DO UPDATE SET
+                played = user_stats.played + excluded.played,
+                solved = user_stats.solved + excluded.solved,
+                lost = user_stats.lost + excluded.lost,
+                free_play_played = user_stats.free_play_played + excluded.free_play_played,
+                free_play_solved = user_stats.free_play_solved + excluded.free_play_solved,
+                updated_at = excluded.updated_at
+            "#)
+        .bind(user_id)
+        .bind(totals.played)
+        .bind(totals.solved)
+        .bind(totals.lost)
+        .bind(totals.free_play_played)
+        .bind(totals.free_play_solved)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+
+    for (guesses, count) in &delta.distribution {
+        sqlx::query(
+                r#"
+                INSERT INTO user_guess_counts (user_id, guesses, count) VALUES ($1, $2, $3)
+                ON CONFLICT (user_id, guesses) DO UPDATE SET count = user_guess_counts.count + excluded.count
+                "#)
+            .bind(user_id)
+            .bind(*guesses)
+            .bind(*count)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+// Overwrites the user's totals and guess counts with `stats`
+pub async fn replace(conn: &mut AnyConnection, user_id: i32, stats: &UserStats, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    let totals = &stats.totals;
+    sqlx::query(
+            r#"
+            INSERT INTO user_stats (user_id, played, solved, lost, free_play_played, free_play_solved, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (user_id) DO UPDATE SET
+                played = excluded.played,
+                solved = excluded.solved,
+                lost = excluded.lost,
+                free_play_played = excluded.free_play_played,
+                free_play_solved = excluded.free_play_solved,
+                updated_at = excluded.updated_at
+            "#)
+        .bind(user_id)
+        .bind(totals.played)
+        .bind(totals.solved)
+        .bind(totals.lost)
+        .bind(totals.free_play_played)
+        .bind(totals.free_play_solved)
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query("DELETE FROM user_guess_counts WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut *conn)
+        .await?;
+    for (guesses, count) in &stats.distribution {
+        sqlx::query("INSERT INTO user_guess_counts (user_id, guesses, count) VALUES ($1, $2, $3)")
+            .bind(user_id)
+            .bind(*guesses)
+            .bind(*count)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+// All zeros for a user without a row, i.e. who never started a session
+pub async fn find(conn: &mut AnyConnection, user_id: i32) -> Result<UserStats, sqlx::Error> {
+    Ok(find_many(conn, &[user_id]).await?.remove(&user_id).unwrap_or_default())
+}
+
+// The stored stats of those of `user_ids` that have a row
+pub async fn find_many(conn: &mut AnyConnection, user_ids: &[i32]) -> Result<HashMap<i32, UserStats>, sqlx::Error> {
+    if user_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let sql = format!(
+        "SELECT user_id, played, solved, lost, free_play_played, free_play_solved FROM user_stats WHERE user_id IN ({})",
+        placeholders(user_ids.len()));
+    let mut query = sqlx::query_as::<_, (i32, i64, i64, i64, i64, i64)>(&sql);
+    for id in user_ids {
+        query = query.bind(*id);
+    }
+    let mut stats: HashMap<i32, UserStats> = query
+        .fetch_all(&mut *conn)
+        .await?
+        .into_iter()
+        .map(|(user_id, played, solved, lost, free_play_played, free_play_solved)| {
+            let totals = UserStatsRecord { played, solved, lost, free_play_played, free_play_solved };
+            (user_id, UserStats { totals, ..Default::default() })
+        })
+        .collect();
+
+    let sql = format!("SELECT user_id, guesses, count FROM user_guess_counts WHERE user_id IN ({})", placeholders(user_ids.len()));
+    let mut query = sqlx::query_as::<_, (i32, i64, i64)>(&sql);
+    for id in user_ids {
+        query = query.bind(*id);
+    }
+    // Counts without a totals row are kept, a recompute should see them to drop them
+    for (user_id, guesses, count) in query.fetch_all(&mut *conn).await? {
+        stats.entry(user_id).or_default().distribution.insert(guesses, count);
+    }
+    Ok(stats)
+}
+
+// Locks the rows of `user_ids` until the surrounding transaction ends, written to for the
+// same reason as `game_sessions::lock`
+pub async fn lock<'e>(db: impl Executor<'e, Database = Any>, user_ids: &[i32]) -> Result<(), sqlx::Error> {
+    if user_ids.is_empty() {
+        return Ok(());
+    }
+
+    let sql = format!("UPDATE user_stats SET updated_at = updated_at WHERE user_id IN ({})", placeholders(user_ids.len()));
+    let mut query = sqlx::query(&sql);
+    for id in user_ids {
+        query = query.bind(*id);
+    }
+    query.execute(db).await?;
+    Ok(())
+}
+
+// Up to `limit` user ids after `after_id`, in order, for going through every user in batches
+pub async fn user_ids_after<'e>(db: impl Executor<'e, Database = Any>, after_id: i32, limit: i64) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar("SELECT id FROM users WHERE id > $1 ORDER BY id LIMIT $2")
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(db)
+        .await
+}
+
+fn placeholders(count: usize) -> String {
+    (1..=count).map(|index| format!("${}", index)).collect::<Vec<_>>().join(", ")
+}
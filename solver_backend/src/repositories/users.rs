@@ -0,0 +1,576 @@
+use chrono::{DateTime, Utc};
+use sqlx::any::AnyKind;
+use sqlx::{Any, AnyConnection, Executor, FromRow};
+use crate::models::users_models::{ExportedAuthEvent, ExportedProfile, ExportedSession, NotificationKind, Preferences, ProfileVisibility, SortOrder, UserResponse};
+use crate::utils::db_utils::row_lock;
+use crate::utils::tracing_utils::SpanTimer;
+use tracing::instrument;
+use crate::utils::stream_utils::{json_row, JsonSection};
+
+pub async fn insert_user<'e>(db: impl Executor<'e, Database = Any>, username: &str, email: &str, hashed_password: &str, now: DateTime<Utc>) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar(
+            r#"
+            INSERT INTO users (username, email, password, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            "#)
+        .bind(username)
+        .bind(email)
+        .bind(hashed_password)
+        .bind(now)
+        .bind(now)
+        .fetch_one(db)
+        .await
+}
+
+// For accounts brought over from another system, which keep their creation date. Without a
+// hash the account can't log in until a new password is set. None when the username or
+// email is already taken, without failing the transaction the insert runs in.
+pub async fn import_user<'e>(db: impl Executor<'e, Database = Any>, username: &str, email: &str, hashed_password: Option<&str>, created_at: DateTime<Utc>, now: DateTime<Utc>) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar(
+            r#"
+            INSERT INTO users (username, email, password, password_reset_required, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT DO NOTHING
+            RETURNING id
+            "#)
+        .bind(username)
+        .bind(email)
+        .bind(hashed_password.unwrap_or(""))
+        .bind(hashed_password.is_none())
+        .bind(created_at)
+        .bind(now)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn list_users<'e>(db: impl Executor<'e, Database = Any>, limit: i64, offset: i64) -> Result<Vec<UserResponse>, sqlx::Error> {
+    sqlx::query_as("SELECT id, username, email, created_at, updated_at, version FROM users ORDER BY id LIMIT $1 OFFSET $2")
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn count_users<'e>(db: impl Executor<'e, Database = Any>) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM users")
+        .fetch_one(db)
+        .await
+}
+
+pub async fn find_by_id<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<UserResponse>, sqlx::Error> {
+    sqlx::query_as("SELECT id, username, email, created_at, updated_at, version FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn find_by_username<'e>(db: impl Executor<'e, Database = Any>, username: &str) -> Result<Option<UserResponse>, sqlx::Error> {
+    sqlx::query_as("SELECT id, username, email, created_at, updated_at, version FROM users WHERE username = $1")
+        .bind(username)
+        .fetch_optional(db)
+        .await
+}
+
+// The id and password hash of the account `login` names, by username or else by email.
+// `email` is `login` normalized the way stored addresses are. The flag is set when the
+// password has to be replaced before the account can log in.
+#[instrument(skip_all, fields(duration_ms))]
+pub async fn find_credentials<'e>(db: impl Executor<'e, Database = Any>, login: &str, email: &str) -> Result<Option<(i32, String, bool)>, sqlx::Error> {
+    let _timer = SpanTimer::start();
+    sqlx::query_as(
+            r#"
+            SELECT id, password, password_reset_required FROM users
+            WHERE (username = $1 OR lower(email) = lower($2)) AND deleted_at IS NULL
+            ORDER BY username = $1 DESC
+            LIMIT 1
+            "#)
+        .bind(login)
+        .bind(email)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn username_of<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT username FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn role_of<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT role FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn token_version<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar("SELECT token_version FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_one(db)
+        .await
+}
+
+// Saves the edit only while the user is still at `version`, and returns them as saved.
+// None when someone else saved first, or there's no such user.
+pub async fn update_profile<'e>(db: impl Executor<'e, Database = Any>, id: i32, username: &str, email: &str, version: i32, now: DateTime<Utc>) -> Result<Option<UserResponse>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            UPDATE users SET username = $1, email = $2, version = version + 1, updated_at = $3
+            WHERE id = $4 AND version = $5
+            RETURNING id, username, email, created_at, updated_at, version
+            "#)
+        .bind(username)
+        .bind(email)
+        .bind(now)
+        .bind(id)
+        .bind(version)
+        .fetch_optional(db)
+        .await
+}
+
+// Leaves password_reset_required alone, only a mailed reset token clears it
+pub async fn update_password<'e>(db: impl Executor<'e, Database = Any>, id: i32, hashed_password: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET password = $1 WHERE id = $2")
+        .bind(hashed_password)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Sets a reset token on the account `email` belongs to and returns its id and stored
+// address. Overwriting the token invalidates any earlier one.
+pub async fn set_password_reset<'e>(db: impl Executor<'e, Database = Any>, email: &str, token: &str, expires_at: DateTime<Utc>) -> Result<Option<(i32, String)>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            UPDATE users SET password_reset_token = $1, password_reset_expires_at = $2
+            WHERE lower(email) = lower($3) AND deleted_at IS NULL
+            RETURNING id, email
+            "#)
+        .bind(token)
+        .bind(expires_at)
+        .bind(email)
+        .fetch_optional(db)
+        .await
+}
+
+// The id of the account with an unexpired reset for `token`, locked until the surrounding
+// transaction ends
+pub async fn lock_password_reset<'e>(db: impl Executor<'e, Database = Any>, kind: AnyKind, token: &str, now: DateTime<Utc>) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar(&format!(
+            r#"
+            SELECT id FROM users
+            WHERE password_reset_token = $1 AND password_reset_expires_at > $2 AND deleted_at IS NULL
+            {}
+            "#, row_lock(kind)))
+        .bind(token)
+        .bind(now)
+        .fetch_optional(db)
+        .await
+}
+
+// Spends the reset token on the new password. Every session is logged out, whoever asked
+// for the reset may not be the one holding them.
+pub async fn reset_password<'e>(db: impl Executor<'e, Database = Any>, id: i32, hashed_password: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            UPDATE users SET
+                password = $1,
+                password_reset_required = FALSE,
+                password_reset_token = NULL,
+                password_reset_expires_at = NULL,
+                token_version = token_version + 1
+            WHERE id = $2
+            "#)
+        .bind(hashed_password)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn update_notify_new_device<'e>(db: impl Executor<'e, Database = Any>, id: i32, notify: bool) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET notify_new_device = $1 WHERE id = $2")
+        .bind(notify)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// The preference columns are NULL for users who never saved any
+#[derive(FromRow)]
+struct PreferencesRow {
+    notify_new_device: bool,
+    hard_mode: Option<bool>,
+    colorblind: Option<bool>,
+    word_length: Option<i32>,
+    sort_order: Option<String>,
+    profile_visibility: Option<String>,
+}
+
+// Saved preferences over the defaults, None when there's no such user. The opt-outs come
+// from the same switch /me/settings sets.
+#[instrument(skip_all, fields(user_id = id, duration_ms))]
+pub async fn find_preferences<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<Preferences>, sqlx::Error> {
+    let _timer = SpanTimer::start();
+    let row: Option<PreferencesRow> = sqlx::query_as(
+            r#"
+            SELECT u.notify_new_device, p.hard_mode, p.colorblind, p.word_length, p.sort_order, p.profile_visibility
+            FROM users u LEFT JOIN user_preferences p ON p.user_id = u.id
+            WHERE u.id = $1
+            "#)
+        .bind(id)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(row.map(|row| {
+        let defaults = Preferences::default();
+        Preferences {
+            hard_mode: row.hard_mode.unwrap_or(defaults.hard_mode),
+            colorblind: row.colorblind.unwrap_or(defaults.colorblind),
+            word_length: row.word_length.and_then(|length| u8::try_from(length).ok()).unwrap_or(defaults.word_length),
+            sort_order: row.sort_order.as_deref().and_then(SortOrder::parse).unwrap_or(defaults.sort_order),
+            notification_opt_outs: if row.notify_new_device { Vec::new() } else { vec![NotificationKind::NewDevice] },
+            profile_visibility: row.profile_visibility.as_deref().and_then(ProfileVisibility::parse).unwrap_or(defaults.profile_visibility),
+        }
+    }))
+}
+
+// Everything but the opt-outs, which are stored with update_notify_new_device
+pub async fn store_preferences<'e>(db: impl Executor<'e, Database = Any>, id: i32, preferences: &Preferences, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            INSERT INTO user_preferences (user_id, hard_mode, colorblind, word_length, sort_order, profile_visibility, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (user_id) DO UPDATE SET hard_mode = excluded.hard_mode, colorblind = excluded.colorblind,
+                word_length = excluded.word_length, sort_order = excluded.sort_order,
+                profile_visibility = excluded.profile_visibility, updated_at = excluded.updated_at
+            "#)
+        .bind(id)
+        .bind(preferences.hard_mode)
+        .bind(preferences.colorblind)
+        .bind(preferences.word_length as i32)
+        .bind(preferences.sort_order.as_str())
+        .bind(preferences.profile_visibility.as_str())
+        .bind(now)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Starts the grace period before the account is anonymized. Bumping the token version
+// ends its sessions, and it can't log in again. False when it was already deleted.
+pub async fn soft_delete<'e>(db: impl Executor<'e, Database = Any>, id: i32, now: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("UPDATE users SET deleted_at = $2, token_version = token_version + 1 WHERE id = $1 AND deleted_at IS NULL")
+        .bind(id)
+        .bind(now)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Accounts deleted before `cutoff` that still have personal data, oldest first
+pub async fn due_for_anonymization<'e>(db: impl Executor<'e, Database = Any>, cutoff: DateTime<Utc>, limit: i64) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar(
+            r#"
+            SELECT id FROM users
+            WHERE deleted_at < $1 AND anonymized_at IS NULL
+            ORDER BY deleted_at, id
+            LIMIT $2
+            "#)
+        .bind(cutoff)
+        .bind(limit)
+        .fetch_all(db)
+        .await
+}
+
+// Leaves a tombstone in place of a deleted account. The row and its id stay, so whatever
+// refers to it for statistics keeps counting, but everything identifying the person goes:
+// name, email and password, sessions with their addresses and user agents, password
+// history, preferences and the detail of its audit events.
+pub async fn anonymize(conn: &mut AnyConnection, id: i32, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    // Random so nobody can register the tombstone's name ahead of time and block it
+    let tombstone = format!("deleted-{}-{}", id, &uuid::Uuid::new_v4().simple().to_string()[..12]);
+    sqlx::query(
+            r#"
+            UPDATE users SET
+                username = $2, email = $3, password = '', email_verified = FALSE,
+                pending_email = NULL, email_change_token = NULL, email_change_expires_at = NULL,
+                password_reset_token = NULL, password_reset_expires_at = NULL,
+                suspension_reason = NULL, anonymized_at = $4
+            WHERE id = $1
+            "#)
+        .bind(id)
+        .bind(&tombstone)
+        .bind(format!("{}@invalid", tombstone))
+        .bind(now)
+        .execute(&mut *conn)
+        .await?;
+
+    for statement in [
+        "DELETE FROM refresh_tokens WHERE user_id = $1",
+        "DELETE FROM password_history WHERE user_id = $1",
+        "DELETE FROM user_preferences WHERE user_id = $1",
+        "DELETE FROM webhooks WHERE user_id = $1",
+        "DELETE FROM game_sessions WHERE user_id = $1",
+        "DELETE FROM user_guess_counts WHERE user_id = $1",
+        "DELETE FROM user_stats WHERE user_id = $1",
+        "UPDATE auth_events SET detail = NULL WHERE user_id = $1",
+    ] {
+        sqlx::query(statement).bind(id).execute(&mut *conn).await?;
+    }
+
+    Ok(())
+}
+
+// The current hash followed by up to `history_size` earlier ones, newest first
+pub async fn recent_password_hashes<'e>(db: impl Executor<'e, Database = Any>, id: i32, history_size: i64) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar(
+            r#"
+            SELECT password FROM users WHERE id = $1
+            UNION ALL
+            SELECT password FROM (
+                SELECT password FROM password_history WHERE user_id = $1 ORDER BY id DESC LIMIT $2
+            ) recent
+            "#)
+        .bind(id)
+        .bind(history_size)
+        .fetch_all(db)
+        .await
+}
+
+// Stores a newly set password hash and prunes the user's history down to the newest entries
+pub async fn record_password(conn: &mut AnyConnection, user_id: i32, hashed_password: &str, history_size: i64) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO password_history (user_id, password) VALUES ($1, $2)")
+        .bind(user_id)
+        .bind(hashed_password)
+        .execute(&mut *conn)
+        .await?;
+
+    sqlx::query(
+            r#"
+            DELETE FROM password_history WHERE user_id = $1 AND id NOT IN (
+                SELECT id FROM password_history WHERE user_id = $1 ORDER BY id DESC LIMIT $2
+            )
+            "#)
+        .bind(user_id)
+        .bind(history_size)
+        .execute(&mut *conn)
+        .await?;
+
+    Ok(())
+}
+
+// (total, logged in after `active_since`, email verified)
+pub async fn user_counts<'e>(db: impl Executor<'e, Database = Any>, active_since: DateTime<Utc>) -> Result<(i64, i64, i64), sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT
+                COUNT(*) AS total_users,
+                COUNT(*) FILTER (WHERE last_login_at > $1) AS active_last_7_days,
+                COUNT(*) FILTER (WHERE email_verified) AS verified_users
+            FROM users
+            "#)
+        .bind(active_since)
+        .fetch_one(db)
+        .await
+}
+
+pub async fn created_since<'e>(db: impl Executor<'e, Database = Any>, since: DateTime<Utc>) -> Result<Vec<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar("SELECT created_at FROM users WHERE created_at >= $1")
+        .bind(since)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn email_and_password<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<(String, String)>, sqlx::Error> {
+    sqlx::query_as("SELECT email, password FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Whether an account other than `id` already uses `email`, ignoring case
+pub async fn email_taken<'e>(db: impl Executor<'e, Database = Any>, email: &str, id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM users WHERE lower(email) = lower($1) AND id <> $2)")
+        .bind(email)
+        .bind(id)
+        .fetch_one(db)
+        .await
+}
+
+pub async fn set_pending_email<'e>(db: impl Executor<'e, Database = Any>, id: i32, email: &str, token: &str, expires_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            UPDATE users SET pending_email = $1, email_change_token = $2, email_change_expires_at = $3
+            WHERE id = $4
+            "#)
+        .bind(email)
+        .bind(token)
+        .bind(expires_at)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// The id and current email of the account with an unexpired change for `token`, locked
+// until the surrounding transaction ends
+pub async fn lock_pending_email_change<'e>(db: impl Executor<'e, Database = Any>, kind: AnyKind, token: &str, now: DateTime<Utc>) -> Result<Option<(i32, String)>, sqlx::Error> {
+    sqlx::query_as(&format!(
+            r#"
+            SELECT id, email FROM users
+            WHERE email_change_token = $1 AND email_change_expires_at > $2
+            {}
+            "#, row_lock(kind)))
+        .bind(token)
+        .bind(now)
+        .fetch_optional(db)
+        .await
+}
+
+// Swaps the pending email in and returns it
+pub async fn confirm_pending_email<'e>(db: impl Executor<'e, Database = Any>, id: i32, now: DateTime<Utc>) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar(
+            r#"
+            UPDATE users SET
+                email = pending_email,
+                email_verified = TRUE,
+                pending_email = NULL,
+                email_change_token = NULL,
+                email_change_expires_at = NULL,
+                version = version + 1,
+                updated_at = $1
+            WHERE id = $2
+            RETURNING email
+            "#)
+        .bind(now)
+        .bind(id)
+        .fetch_one(db)
+        .await
+}
+
+// Also logs the user out everywhere
+pub async fn suspend<'e>(db: impl Executor<'e, Database = Any>, id: i32, now: DateTime<Utc>, until: Option<DateTime<Utc>>, reason: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            UPDATE users SET suspended_at = $1, suspended_until = $2, suspension_reason = $3,
+                token_version = token_version + 1
+            WHERE id = $4
+            "#)
+        .bind(now)
+        .bind(until)
+        .bind(reason)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn unsuspend<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            UPDATE users SET suspended_at = NULL, suspended_until = NULL, suspension_reason = NULL
+            WHERE id = $1
+            "#)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// The reason and end of a suspension still in force at `now`
+pub async fn active_suspension<'e>(db: impl Executor<'e, Database = Any>, id: i32, now: DateTime<Utc>) -> Result<Option<(Option<String>, Option<DateTime<Utc>>)>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT suspension_reason, suspended_until FROM users
+            WHERE id = $1 AND suspended_at IS NOT NULL
+            AND (suspended_until IS NULL OR suspended_until > $2)
+            "#)
+        .bind(id)
+        .bind(now)
+        .fetch_optional(db)
+        .await
+}
+
+// Invalidates every token issued to the user so far
+pub async fn bump_token_version<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET token_version = token_version + 1 WHERE id = $1")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn record_login<'e>(db: impl Executor<'e, Database = Any>, id: i32, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET last_login_at = $1 WHERE id = $2")
+        .bind(now)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// The email to notify about new devices and whether the user wants to be
+pub async fn new_device_recipient<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<(String, bool)>, sqlx::Error> {
+    sqlx::query_as("SELECT email, notify_new_device FROM users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Claims the export slot unless it was claimed after `cooldown_start`, in the same
+// statement that checks it so two simultaneous requests can't both get through
+pub async fn claim_export<'e>(db: impl Executor<'e, Database = Any>, id: i32, now: DateTime<Utc>, cooldown_start: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+    let claimed: Option<i32> = sqlx::query_scalar(
+            r#"
+            UPDATE users SET last_exported_at = $1
+            WHERE id = $2 AND (last_exported_at IS NULL OR last_exported_at < $3)
+            RETURNING id
+            "#)
+        .bind(now)
+        .bind(id)
+        .bind(cooldown_start)
+        .fetch_optional(db)
+        .await?;
+    Ok(claimed.is_some())
+}
+
+pub async fn exported_profile<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<ExportedProfile, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT id, username, email, email_verified, pending_email, role, created_at,
+                updated_at, suspended_at, suspended_until, suspension_reason
+            FROM users WHERE id = $1
+            "#)
+        .bind(id)
+        .fetch_one(db)
+        .await
+}
+
+// The arrays of a data export after the profile, paged through by stream_utils
+pub fn export_sections() -> Vec<JsonSection> {
+    vec![
+        JsonSection {
+            name: "sessions",
+            query: r#"
+                SELECT id, family_id, device_label, user_agent, ip_address, created_at,
+                    rotated_at, revoked
+                FROM refresh_tokens
+                WHERE user_id = $1 AND id > $2 ORDER BY id LIMIT $3
+                "#,
+            render: json_row::<ExportedSession>,
+        },
+        JsonSection {
+            name: "auth_events",
+            query: r#"
+                SELECT id, event_type, detail, created_at FROM auth_events
+                WHERE user_id = $1 AND id > $2 ORDER BY id LIMIT $3
+                "#,
+            render: json_row::<ExportedAuthEvent>,
+        },
+    ]
+}
@@ -0,0 +1,197 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Any, Executor};
+use crate::models::webhooks_models::{PendingDelivery, WebhookDelivery, WebhookRecord};
+
+const COLUMNS: &str = "id, user_id, url, secret, events, all_users, enabled, consecutive_failures, disabled_at, created_at, updated_at";
+
+pub struct NewWebhookRecord<'a> {
+    pub user_id: i32,
+    pub url: &'a str,
+    pub secret: &'a str,
+    pub events: &'a str,
+    pub all_users: bool,
+}
+
+pub async fn insert<'e>(db: impl Executor<'e, Database = Any>, webhook: &NewWebhookRecord<'_>, now: DateTime<Utc>) -> Result<WebhookRecord, sqlx::Error> {
+    sqlx::query_as(&format!(
+            r#"
+            INSERT INTO webhooks (user_id, url, secret, events, all_users, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $6)
+            RETURNING {}
+            "#, COLUMNS))
+        .bind(webhook.user_id)
+        .bind(webhook.url)
+        .bind(webhook.secret)
+        .bind(webhook.events)
+        .bind(webhook.all_users)
+        .bind(now)
+        .fetch_one(db)
+        .await
+}
+
+pub async fn list_for_user<'e>(db: impl Executor<'e, Database = Any>, user_id: i32) -> Result<Vec<WebhookRecord>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM webhooks WHERE user_id = $1 ORDER BY id", COLUMNS))
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn find_for_user<'e>(db: impl Executor<'e, Database = Any>, id: i32, user_id: i32) -> Result<Option<WebhookRecord>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM webhooks WHERE id = $1 AND user_id = $2", COLUMNS))
+        .bind(id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+}
+
+// Writes back everything an owner can change, and the failure state that goes with it
+pub async fn update<'e>(db: impl Executor<'e, Database = Any>, webhook: &WebhookRecord, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            UPDATE webhooks SET url = $1, secret = $2, events = $3, enabled = $4, consecutive_failures = $5, disabled_at = $6, updated_at = $7
+            WHERE id = $8
+            "#)
+        .bind(&webhook.url)
+        .bind(&webhook.secret)
+        .bind(&webhook.events)
+        .bind(webhook.enabled)
+        .bind(webhook.consecutive_failures)
+        .bind(webhook.disabled_at)
+        .bind(now)
+        .bind(webhook.id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// False when the user has no such webhook. Its deliveries go with it.
+pub async fn delete_for_user<'e>(db: impl Executor<'e, Database = Any>, id: i32, user_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Enabled endpoints that get `user_id`'s events, whichever events they picked. Those of
+// deleted accounts are left out.
+pub async fn receiving_events_of<'e>(db: impl Executor<'e, Database = Any>, user_id: i32) -> Result<Vec<WebhookRecord>, sqlx::Error> {
+    sqlx::query_as(&format!(
+            r#"
+            SELECT {} FROM webhooks
+            WHERE enabled AND (user_id = $1 OR all_users) AND user_id IN (SELECT id FROM users WHERE deleted_at IS NULL)
+            ORDER BY id
+            "#, COLUMNS))
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn insert_delivery<'e>(db: impl Executor<'e, Database = Any>, webhook_id: i32, event: &str, payload: &str, now: DateTime<Utc>) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar("INSERT INTO webhook_deliveries (webhook_id, event, payload, created_at) VALUES ($1, $2, $3, $4) RETURNING id")
+        .bind(webhook_id)
+        .bind(event)
+        .bind(payload)
+        .bind(now)
+        .fetch_one(db)
+        .await
+}
+
+pub async fn find_pending_delivery<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<PendingDelivery>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT d.id, d.event, d.payload, w.id AS webhook_id, w.url, w.secret, w.enabled
+            FROM webhook_deliveries d JOIN webhooks w ON w.id = d.webhook_id
+            WHERE d.id = $1
+            "#)
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn record_attempt<'e>(db: impl Executor<'e, Database = Any>, id: i32, status: &str, response_status: Option<i32>, error: Option<&str>, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    sqlx::query(
+            r#"
+            UPDATE webhook_deliveries SET status = $1, attempts = attempts + 1, response_status = $2, error = $3, last_attempt_at = $4
+            WHERE id = $5
+            "#)
+        .bind(status)
+        .bind(response_status)
+        .bind(error)
+        .bind(now)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Settles a delivery without an attempt, e.g. when its endpoint was disabled meanwhile
+pub async fn abandon_delivery<'e>(db: impl Executor<'e, Database = Any>, id: i32, error: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE webhook_deliveries SET status = 'failed', error = $1 WHERE id = $2")
+        .bind(error)
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn record_success<'e>(db: impl Executor<'e, Database = Any>, webhook_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE webhooks SET consecutive_failures = 0 WHERE id = $1")
+        .bind(webhook_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Counts a failed attempt and disables the endpoint once `limit` failed in a row. True
+// when this failure is the one that disabled it.
+pub async fn record_failure<'e>(db: impl Executor<'e, Database = Any>, webhook_id: i32, limit: i32, now: DateTime<Utc>) -> Result<bool, sqlx::Error> {
+    let disabled: Option<i32> = sqlx::query_scalar(
+            r#"
+            UPDATE webhooks SET consecutive_failures = consecutive_failures + 1,
+                enabled = CASE WHEN consecutive_failures + 1 >= $1 THEN FALSE ELSE enabled END,
+                disabled_at = CASE WHEN consecutive_failures + 1 >= $1 AND enabled THEN $2 ELSE disabled_at END
+            WHERE id = $3 AND enabled
+            RETURNING CASE WHEN enabled THEN 0 ELSE 1 END
+            "#)
+        .bind(limit)
+        .bind(now)
+        .bind(webhook_id)
+        .fetch_optional(db)
+        .await?;
+    Ok(disabled == Some(1))
+}
+
+pub async fn find_delivery<'e>(db: impl Executor<'e, Database = Any>, id: i32) -> Result<Option<WebhookDelivery>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT id, webhook_id, event, status, attempts, response_status, error, created_at, last_attempt_at
+            FROM webhook_deliveries WHERE id = $1
+            "#)
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Newest first
+pub async fn list_deliveries<'e>(db: impl Executor<'e, Database = Any>, webhook_id: i32, limit: i64, offset: i64) -> Result<Vec<WebhookDelivery>, sqlx::Error> {
+    sqlx::query_as(
+            r#"
+            SELECT id, webhook_id, event, status, attempts, response_status, error, created_at, last_attempt_at
+            FROM webhook_deliveries WHERE webhook_id = $1
+            ORDER BY id DESC LIMIT $2 OFFSET $3
+            "#)
+        .bind(webhook_id)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn count_deliveries<'e>(db: impl Executor<'e, Database = Any>, webhook_id: i32) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM webhook_deliveries WHERE webhook_id = $1")
+        .bind(webhook_id)
+        .fetch_one(db)
+        .await
+}
@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Any, Executor};
+use crate::models::game_models::WordStatsRecord;
+
+const COLUMNS: &str = "word, plays, failures, solved_guesses";
+
+// Adds one finished game to the word's totals. The increments happen in the database, so
+// games finishing at the same time don't lose each other's counts.
+pub async fn record<'e>(db: impl Executor<'e, Database = Any>, word: &str, guesses: i64, solved: bool, now: DateTime<Utc>) -> Result<(), sqlx::Error> {
+    let (failures, solved_guesses) = if solved { (0_i64, guesses) } else { (1, 0) };
+    sqlx::query(
+            r#"
+            INSERT INTO word_stats (word, plays, failures, solved_guesses, updated_at) VALUES ($1, 1, $2, $3, $4)
+            ON CONFLICT (word) DO UPDATE SET
+                plays = word_stats.plays + 1,
+                failures = word_stats.failures + excluded.failures,
+                solved_guesses = word_stats.solved_guesses + excluded.solved_guesses,
+                updated_at = excluded.updated_at
+            "#)
+        .bind(word)
+        .bind(failures)
+        .bind(solved_guesses)
+        .bind(now)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn find<'e>(db: impl Executor<'e, Database = Any>, word: &str) -> Result<Option<WordStatsRecord>, sqlx::Error> {
+    sqlx::query_as(&format!("SELECT {} FROM word_stats WHERE word = $1", COLUMNS))
+        .bind(word)
+        .fetch_optional(db)
+        .await
+}
+
+// Highest failure rate first, then most guesses on average to solve
+pub async fn hardest<'e>(db: impl Executor<'e, Database = Any>, min_plays: i64, limit: i64, offset: i64) -> Result<Vec<WordStatsRecord>, sqlx::Error> {
+    sqlx::query_as(&format!(
+            r#"
+            SELECT {} FROM word_stats WHERE plays >= $1
+            ORDER BY failures * 1.0 / plays DESC, COALESCE(solved_guesses * 1.0 / NULLIF(plays - failures, 0), 0) DESC, word
+            LIMIT $2 OFFSET $3
+            "#, COLUMNS))
+        .bind(min_plays)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn count<'e>(db: impl Executor<'e, Database = Any>, min_plays: i64) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM word_stats WHERE plays >= $1")
+        .bind(min_plays)
+        .fetch_one(db)
+        .await
+}
@@ -0,0 +1,434 @@
+use async_trait::async_trait;
+use futures_util::stream::BoxStream;
+use chrono::{DateTime, Utc};
+use sqlx::any::AnyKind;
+use sha2::{Digest, Sha256};
+use sqlx::any::AnyArguments;
+use sqlx::query::QueryScalar;
+use sqlx::{Any, AnyPool, Executor, Row};
+use std::collections::{BTreeMap, BinaryHeap, HashSet};
+use crate::models::game_models::WordListChanges;
+use crate::solver::Constraints;
+use crate::utils::db_utils::row_lock;
+use crate::utils::tracing_utils::SpanTimer;
+use tracing::{instrument, Span};
+
+// Words matching the LIKE `pattern` that `constraints` allows. Only the pattern is left to
+// the database, the letter checks are done here so they behave the same on every backend.
+#[instrument(skip_all, fields(pattern = %pattern, rows_returned, candidate_count, duration_ms))]
+pub async fn filter_words<'e>(db: impl Executor<'e, Database = Any>, pattern: &str, constraints: &Constraints) -> Result<Vec<String>, sqlx::Error> {
+    let _timer = SpanTimer::start();
+    let pattern = pattern.to_lowercase();
+    let (sql, length) = filter_query(&pattern);
+    let mut query = sqlx::query_scalar(sql).bind(&pattern);
+    if let Some(length) = length {
+        query = query.bind(length);
+    }
+    let rows: Vec<String> = query.fetch_all(db).await?;
+    Span::current().record("rows_returned", rows.len());
+
+    let candidates: Vec<String> = rows.into_iter().filter(|word| constraints.allows(word)).collect();
+    Span::current().record("candidate_count", candidates.len());
+    Ok(candidates)
+}
+
+// Words are stored lowercase, so `word` is compared as it is and the indexes from the
+// word_filter_indexes migration apply. A pattern without % only matches words as long as
+// it is, the length index narrows to those first.
+fn filter_query(pattern: &str) -> (&'static str, Option<i32>) {
+    if pattern.contains('%') {
+        ("SELECT word FROM word_list WHERE word LIKE $1", None)
+    } else {
+        ("SELECT word FROM word_list WHERE length(word) = $2 AND word LIKE $1", Some(pattern.chars().count() as i32))
+    }
+}
+
+// A sample keeps the words whose SHA-256 of the seed and the word sorts lowest. Any seed
+// makes that a uniform pick, and Postgres can rank words the same way, so a seed picks
+// the same words whether the sample is taken from a cached list or by the database.
+fn sample_key(seed: u32, word: &str) -> [u8; 32] {
+    Sha256::digest(format!("{}:{}", seed, word).as_bytes()).into()
+}
+
+// Bottom-k reservoir sampling: one pass over `words`, holding on to the `size` lowest keys
+// seen so far. The result is in key order, which is as good as shuffled.
+pub fn pick_sample(words: impl IntoIterator<Item = String>, size: usize, seed: u32) -> Vec<String> {
+    let mut kept: BinaryHeap<([u8; 32], String)> = BinaryHeap::with_capacity(size + 1);
+    for word in words {
+        let key = sample_key(seed, &word);
+        if kept.len() < size {
+            kept.push((key, word));
+        } else if kept.peek().is_some_and(|(highest, _)| key < *highest) {
+            kept.pop();
+            kept.push((key, word));
+        }
+    }
+    kept.into_sorted_vec().into_iter().map(|(_, word)| word).collect()
+}
+
+// `pick_sample` done by Postgres, with the pattern and the letters in or out of the word
+// all checked in SQL, so only the sample and a count come back. None on SQLite, which has
+// no sha256(), and for constraints only `Constraints::allows` can check.
+#[instrument(skip_all, fields(pattern = %pattern, size, rows_returned, candidate_count, duration_ms))]
+async fn sample_in_database(db: &AnyPool, pattern: &str, constraints: &Constraints, size: usize, seed: u32) -> Result<Option<(Vec<String>, usize)>, sqlx::Error> {
+    let (required, excluded) = match constraints.letter_sets() {
+        Some(sets) if db.any_kind() == AnyKind::Postgres => sets,
+        _ => return Ok(None),
+    };
+
+    let _timer = SpanTimer::start();
+    let pattern = pattern.to_lowercase();
+    let (sql, length) = filter_query(&pattern);
+    let mut filter = sql.replacen("SELECT word ", "", 1);
+    let mut letters = Vec::new();
+    let first_letter = if length.is_some() { 3 } else { 2 };
+    for (letter, operator) in required.iter().map(|letter| (letter, "LIKE")).chain(excluded.iter().map(|letter| (letter, "NOT LIKE"))) {
+        filter.push_str(&format!(" AND word {} ${}", operator, first_letter + letters.len()));
+        letters.push(format!("%{}%", letter));
+    }
+
+    let count_sql = format!("SELECT COUNT(*) {}", filter);
+    let total: i64 = bind_filter(sqlx::query_scalar(&count_sql), &pattern, length, &letters).fetch_one(db).await?;
+    let total = total as usize;
+    Span::current().record("candidate_count", total);
+
+    // Small enough to be a sample of itself, nothing to rank
+    let sample_sql = if total <= size {
+        format!("SELECT word {}", filter)
+    } else {
+        let seed_index = first_letter + letters.len();
+        format!("SELECT word {} ORDER BY sha256(convert_to(${} || ':' || word, 'UTF8')) LIMIT ${}", filter, seed_index, seed_index + 1)
+    };
+    let mut query = bind_filter(sqlx::query_scalar(&sample_sql), &pattern, length, &letters);
+    if total > size {
+        query = query.bind(seed.to_string()).bind(size as i64);
+    }
+    let sample: Vec<String> = query.fetch_all(db).await?;
+    Span::current().record("rows_returned", sample.len());
+    Ok(Some((sample, total)))
+}
+
+fn bind_filter<'q, O>(query: QueryScalar<'q, Any, O, AnyArguments<'q>>, pattern: &'q str, length: Option<i32>, letters: &'q [String]) -> QueryScalar<'q, Any, O, AnyArguments<'q>> {
+    let mut query = query.bind(pattern);
+    if let Some(length) = length {
+        query = query.bind(length);
+    }
+    letters.iter().fold(query, |query, letter| query.bind(letter.as_str()))
+}
+
+// How the database runs `filter_words` for `pattern`, one line per step. Postgres runs the
+// query to report actual timings, SQLite only says which indexes it would use.
+pub async fn explain_filter<'e>(db: impl Executor<'e, Database = Any>, kind: AnyKind, pattern: &str) -> Result<Vec<String>, sqlx::Error> {
+    let pattern = pattern.to_lowercase();
+    let (sql, length) = filter_query(&pattern);
+    let (explain, column) = match kind {
+        AnyKind::Postgres => ("EXPLAIN (ANALYZE, BUFFERS)", "QUERY PLAN"),
+        AnyKind::Sqlite => ("EXPLAIN QUERY PLAN", "detail"),
+    };
+
+    let explained = format!("{} {}", explain, sql);
+    let mut query = sqlx::query(&explained).bind(&pattern);
+    if let Some(length) = length {
+        query = query.bind(length);
+    }
+    let rows = query.fetch_all(db).await?;
+    rows.iter().map(|row| row.try_get::<String, _>(column)).collect()
+}
+
+// Bumped by the database whenever the list changes
+#[instrument(skip_all, fields(duration_ms))]
+pub async fn version<'e>(db: impl Executor<'e, Database = Any>) -> Result<i64, sqlx::Error> {
+    let _timer = SpanTimer::start();
+    sqlx::query_scalar("SELECT version FROM word_list_version")
+        .fetch_one(db)
+        .await
+}
+
+pub async fn count_words<'e>(db: impl Executor<'e, Database = Any>) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM word_list WHERE word IS NOT NULL")
+        .fetch_one(db)
+        .await
+}
+
+#[instrument(skip_all, fields(rows_returned, duration_ms))]
+pub async fn all_words<'e>(db: impl Executor<'e, Database = Any>) -> Result<Vec<String>, sqlx::Error> {
+    let _timer = SpanTimer::start();
+    let words: Vec<String> = sqlx::query_scalar("SELECT word FROM word_list WHERE word IS NOT NULL ORDER BY word")
+        .fetch_all(db)
+        .await?;
+    Span::current().record("rows_returned", words.len());
+    Ok(words)
+}
+
+// The words that can be a game's answer, not only a guess
+#[instrument(skip_all, fields(rows_returned, duration_ms))]
+pub async fn answer_words<'e>(db: impl Executor<'e, Database = Any>) -> Result<Vec<String>, sqlx::Error> {
+    let _timer = SpanTimer::start();
+    let words: Vec<String> = sqlx::query_scalar("SELECT word FROM word_list WHERE word IS NOT NULL AND is_answer ORDER BY word")
+        .fetch_all(db)
+        .await?;
+    Span::current().record("rows_returned", words.len());
+    Ok(words)
+}
+
+// Whether `word` can be an answer, None when it isn't in the list
+pub async fn is_answer<'e>(db: impl Executor<'e, Database = Any>, word: &str) -> Result<Option<bool>, sqlx::Error> {
+    sqlx::query_scalar("SELECT is_answer FROM word_list WHERE word = $1 LIMIT 1")
+        .bind(word)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn set_answer<'e>(db: impl Executor<'e, Database = Any>, word: &str, is_answer: bool) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE word_list SET is_answer = $1 WHERE word = $2")
+        .bind(is_answer)
+        .bind(word)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Holds the version row until the transaction ends, so two syncs don't diff against the
+// same list
+pub async fn lock_version<'e>(db: impl Executor<'e, Database = Any>, kind: AnyKind) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(&format!("SELECT version FROM word_list_version {}", row_lock(kind)))
+        .fetch_one(db)
+        .await
+}
+
+pub async fn add_word<'e>(db: impl Executor<'e, Database = Any>, word: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO word_list (word) VALUES ($1)")
+        .bind(word)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Duplicates included
+pub async fn remove_word<'e>(db: impl Executor<'e, Database = Any>, word: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM word_list WHERE word = $1")
+        .bind(word)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub struct NewRevision<'a> {
+    pub source_url: &'a str,
+    pub added: i32,
+    pub removed: i32,
+    pub word_count: i32,
+    pub etag: Option<&'a str>,
+    pub last_modified: Option<&'a str>,
+    pub synced_by: Option<i32>,
+    // The list's version when the revision locked it, and once it was done
+    pub from_version: i64,
+    pub to_version: i64,
+}
+
+pub async fn insert_revision<'e>(db: impl Executor<'e, Database = Any>, revision: &NewRevision<'_>, now: DateTime<Utc>) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar(
+            r#"
+            INSERT INTO word_list_revisions (source_url, added, removed, word_count, etag, last_modified, synced_by, from_version, to_version, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING id
+            "#)
+        .bind(revision.source_url)
+        .bind(revision.added)
+        .bind(revision.removed)
+        .bind(revision.word_count)
+        .bind(revision.etag)
+        .bind(revision.last_modified)
+        .bind(revision.synced_by)
+        .bind(revision.from_version)
+        .bind(revision.to_version)
+        .bind(now)
+        .fetch_one(db)
+        .await
+}
+
+pub async fn record_change<'e>(db: impl Executor<'e, Database = Any>, revision_id: i32, word: &str, added: bool) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO word_list_changes (revision_id, word, added) VALUES ($1, $2, $3)")
+        .bind(revision_id)
+        .bind(word)
+        .bind(added)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// The words added and removed after version `since`, replayed from the revisions. None when
+// they don't lead from `since` to the current version without a gap: the history was
+// pruned, the list changed outside a revision, or `since` is no version the list had.
+pub async fn changes_since(pool: &AnyPool, since: i64) -> Result<Option<WordListChanges>, sqlx::Error> {
+    // Read first, so a revision committed meanwhile is left out rather than seen half
+    let current = version(pool).await?;
+
+    let revisions: Vec<(i64, i64)> = sqlx::query_as("SELECT from_version, to_version FROM word_list_revisions WHERE to_version > $1 AND to_version <= $2 ORDER BY to_version, id")
+        .bind(since)
+        .bind(current)
+        .fetch_all(pool)
+        .await?;
+    let mut reached = since;
+    for (from_version, to_version) in revisions {
+        if from_version != reached {
+            return Ok(None);
+        }
+        reached = to_version;
+    }
+    if reached != current {
+        return Ok(None);
+    }
+
+    let changes: Vec<(String, bool)> = sqlx::query_as(
+            r#"
+            SELECT c.word, c.added FROM word_list_changes c JOIN word_list_revisions r ON r.id = c.revision_id
+            WHERE r.to_version > $1 AND r.to_version <= $2
+            ORDER BY r.to_version, r.id
+            "#)
+        .bind(since)
+        .bind(current)
+        .fetch_all(pool)
+        .await?;
+
+    // A word's first change says whether it was in the list at `since`, its last whether
+    // it's there now. Removed and added back again is no change.
+    let mut first_and_last: BTreeMap<String, (bool, bool)> = BTreeMap::new();
+    for (word, added) in changes {
+        first_and_last.entry(word).or_insert((added, added)).1 = added;
+    }
+    let (mut added, mut removed) = (Vec::new(), Vec::new());
+    for (word, (first, last)) in first_and_last {
+        match (first, last) {
+            (true, true) => added.push(word),
+            (false, false) => removed.push(word),
+            _ => {}
+        }
+    }
+
+    Ok(Some(WordListChanges { since_version: since, version: current, added, removed }))
+}
+
+// Forgets which words the revisions before `before` changed. Their counts stay, but a
+// client holding a version from back then has to fetch the whole list again. Revisions
+// after the version of a session report are kept, so it can still be replayed.
+pub async fn prune_changes(pool: &AnyPool, before: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+    const PRUNABLE: &str = "created_at < $1 AND NOT EXISTS (SELECT 1 FROM session_reports WHERE word_list_version < word_list_revisions.to_version)";
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(&format!("DELETE FROM word_list_changes WHERE revision_id IN (SELECT id FROM word_list_revisions WHERE {})", PRUNABLE))
+        .bind(before)
+        .execute(&mut tx)
+        .await?;
+    let pruned = sqlx::query(&format!("UPDATE word_list_revisions SET from_version = NULL, to_version = NULL WHERE {} AND to_version IS NOT NULL", PRUNABLE))
+        .bind(before)
+        .execute(&mut tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+    Ok(pruned)
+}
+
+// The list as it was at version `at`: today's with the changes since undone. None when
+// `changes_since` can't tell what they were.
+pub async fn words_at(pool: &AnyPool, at: i64) -> Result<Option<Vec<String>>, sqlx::Error> {
+    loop {
+        let Some(changes) = changes_since(pool, at).await? else {
+            return Ok(None);
+        };
+        let words = all_words(pool).await?;
+        // A revision in between would be undone only partly, start over after it
+        if version(pool).await? != changes.version {
+            continue;
+        }
+
+        let added: HashSet<&str> = changes.added.iter().map(String::as_str).collect();
+        let mut then: Vec<String> = words.into_iter().filter(|word| !added.contains(word.as_str())).chain(changes.removed).collect();
+        then.sort_unstable();
+        return Ok(Some(then));
+    }
+}
+
+// ETag and Last-Modified the source sent with the list last synced from `source_url`
+pub async fn latest_validators<'e>(db: impl Executor<'e, Database = Any>, source_url: &str) -> Result<Option<(Option<String>, Option<String>)>, sqlx::Error> {
+    sqlx::query_as("SELECT etag, last_modified FROM word_list_revisions WHERE source_url = $1 ORDER BY id DESC LIMIT 1")
+        .bind(source_url)
+        .fetch_optional(db)
+        .await
+}
+
+// The word count and resulting version of the newest revision. The version is None once
+// its changes were pruned.
+pub async fn latest_revision<'e>(db: impl Executor<'e, Database = Any>) -> Result<Option<(i32, Option<i64>)>, sqlx::Error> {
+    sqlx::query_as("SELECT word_count, to_version FROM word_list_revisions ORDER BY id DESC LIMIT 1")
+        .fetch_optional(db)
+        .await
+}
+
+// Every row as it's stored, NULL words included, sorted so duplicates come out next to
+// each other. Streamed, the list is never held in memory whole.
+pub fn stream_rows<'e, E>(db: E) -> BoxStream<'e, Result<(Option<String>, bool), sqlx::Error>>
+where
+    E: Executor<'e, Database = Any> + 'e,
+{
+    sqlx::query_as("SELECT word, is_answer FROM word_list ORDER BY word, id").fetch(db)
+}
+
+// What the game handlers need from the word list, so their tests can swap in fixed words
+#[async_trait]
+pub trait WordRepository: Send + Sync {
+    async fn filter_words(&self, pattern: &str, constraints: &Constraints) -> Result<Vec<String>, sqlx::Error>;
+    async fn version(&self) -> Result<i64, sqlx::Error>;
+    async fn all_words(&self) -> Result<Vec<String>, sqlx::Error>;
+
+    // Every word unless the repository knows which ones can be answers
+    async fn answer_words(&self) -> Result<Vec<String>, sqlx::Error> {
+        self.all_words().await
+    }
+
+    // `size` of the words `filter_words` would return, as `pick_sample` picks them, and how
+    // many there were to pick from
+    async fn sample_words(&self, pattern: &str, constraints: &Constraints, size: usize, seed: u32) -> Result<(Vec<String>, usize), sqlx::Error> {
+        let words = self.filter_words(pattern, constraints).await?;
+        let total = words.len();
+        Ok((pick_sample(words, size, seed), total))
+    }
+}
+
+pub struct DbWords {
+    pool: AnyPool,
+}
+
+impl DbWords {
+    pub fn new(pool: AnyPool) -> Self {
+        DbWords { pool }
+    }
+}
+
+#[async_trait]
+impl WordRepository for DbWords {
+    async fn filter_words(&self, pattern: &str, constraints: &Constraints) -> Result<Vec<String>, sqlx::Error> {
+        filter_words(&self.pool, pattern, constraints).await
+    }
+
+    async fn version(&self) -> Result<i64, sqlx::Error> {
+        version(&self.pool).await
+    }
+
+    async fn all_words(&self) -> Result<Vec<String>, sqlx::Error> {
+        all_words(&self.pool).await
+    }
+
+    async fn answer_words(&self) -> Result<Vec<String>, sqlx::Error> {
+        answer_words(&self.pool).await
+    }
+
+    async fn sample_words(&self, pattern: &str, constraints: &Constraints, size: usize, seed: u32) -> Result<(Vec<String>, usize), sqlx::Error> {
+        if let Some(sampled) = sample_in_database(&self.pool, pattern, constraints, size, seed).await? {
+            return Ok(sampled);
+        }
+        let words = filter_words(&self.pool, pattern, constraints).await?;
+        let total = words.len();
+        Ok((pick_sample(words, size, seed), total))
+    }
+}
@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use serde::Serialize;
+use std::fmt;
+use tracing::instrument;
+use utoipa::ToSchema;
+use crate::utils::tracing_utils::SpanTimer;
+
+pub mod fixtures;
+
+// What a guess revealed about one of its letters. Serialized by meaning rather than
+// colour, since the colours depend on the palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, ToSchema)]
+pub enum Mark {
+    // Right letter, right spot
+    #[serde(rename = "correct")]
+    Green,
+    // In the answer, somewhere else
+    #[serde(rename = "present")]
+    Yellow,
+    // Not in the answer, or not as many times as it was guessed
+    #[serde(rename = "absent")]
+    Gray,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FeedbackError {
+    UnknownMark(char),
+    LengthMismatch { guess: usize, feedback: usize },
+}
+
+impl fmt::Display for FeedbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FeedbackError::UnknownMark(mark) => write!(f, "unknown mark '{}', use g, y or x", mark),
+            FeedbackError::LengthMismatch { guess, feedback } => {
+                write!(f, "the guess has {} letters but the feedback has {}", guess, feedback)
+            }
+        }
+    }
+}
+
+impl std::error::Error for FeedbackError {}
+
+// Feedback typed as one letter per square, e.g. "gyxxx": g for green, y for yellow and
+// x (or b, for black) for gray
+pub fn parse_feedback(pattern: &str) -> Result<Vec<Mark>, FeedbackError> {
+    pattern
+        .chars()
+        .map(|mark| match mark.to_ascii_lowercase() {
+            'g' => Ok(Mark::Green),
+            'y' => Ok(Mark::Yellow),
+            'x' | 'b' => Ok(Mark::Gray),
+            other => Err(FeedbackError::UnknownMark(other)),
+        })
+        .collect()
+}
+
+// The marks the game gives `guess` when the answer is `answer`. Greens are handed out
+// first, then yellows left to right while the answer has unclaimed copies of the letter.
+pub fn feedback(guess: &str, answer: &str) -> Vec<Mark> {
+    let guess: Vec<char> = guess.to_lowercase().chars().collect();
+    let answer: Vec<char> = answer.to_lowercase().chars().collect();
+
+    let mut marks = vec![Mark::Gray; guess.len()];
+    let mut unclaimed: HashMap<char, usize> = HashMap::new();
+    for (position, &letter) in answer.iter().enumerate() {
+        if guess.get(position) == Some(&letter) {
+            marks[position] = Mark::Green;
+        } else {
+            *unclaimed.entry(letter).or_insert(0) += 1;
+        }
+    }
+
+    for (position, letter) in guess.iter().enumerate() {
+        if marks[position] == Mark::Green {
+            continue;
+        }
+        if let Some(count) = unclaimed.get_mut(letter).filter(|count| **count > 0) {
+            *count -= 1;
+            marks[position] = Mark::Yellow;
+        }
+    }
+
+    marks
+}
+
+// The marks as parse_feedback reads them, "gyxxx"
+pub fn feedback_pattern(marks: &[Mark]) -> String {
+    marks
+        .iter()
+        .map(|mark| match mark {
+            Mark::Green => 'g',
+            Mark::Yellow => 'y',
+            Mark::Gray => 'x',
+        })
+        .collect()
+}
+
+// A feedback as one number, each mark a base 3 digit, so a five letter word's fits in a byte
+pub fn pattern_code(marks: &[Mark]) -> u8 {
+    marks.iter().fold(0, |code, mark| {
+        code * 3 + match mark {
+            Mark::Gray => 0,
+            Mark::Yellow => 1,
+            Mark::Green => 2,
+        }
+    })
+}
+
+// Everything known about the answer so far. The server builds one from the letters a
+// client sends and the CLI from the feedback to each guess, and both filter words with
+// `allows`. Letters are compared ignoring case.
+#[derive(Debug, Clone, Default)]
+pub struct Constraints {
+    length: Option<usize>,
+    fixed: Vec<(usize, char)>,
+    misplaced: Vec<(usize, char)>,
+    min_counts: HashMap<char, usize>,
+    max_counts: HashMap<char, usize>,
+}
+
+impl Constraints {
+    pub fn with_length(length: usize) -> Self {
+        Constraints { length: Some(length), ..Constraints::default() }
+    }
+
+    // The answer contains `letter` at least once
+    pub fn require(&mut self, letter: char) {
+        let min = self.min_counts.entry(letter.to_ascii_lowercase()).or_insert(0);
+        *min = (*min).max(1);
+    }
+
+    // The answer doesn't contain `letter` at all
+    pub fn exclude(&mut self, letter: char) {
+        self.max_counts.insert(letter.to_ascii_lowercase(), 0);
+    }
+
+    // The answer has `letter` at `position`, counting from 0
+    pub fn fix(&mut self, position: usize, letter: char) {
+        self.fixed.push((position, letter.to_ascii_lowercase()));
+        self.require(letter);
+    }
+
+    // Narrows things down with the feedback to `guess`. A gray letter that is also green
+    // or yellow elsewhere in the guess caps how often it appears rather than ruling it out.
+    pub fn apply(&mut self, guess: &str, marks: &[Mark]) -> Result<(), FeedbackError> {
+        let letters: Vec<char> = guess.to_lowercase().chars().collect();
+        if letters.len() != marks.len() {
+            return Err(FeedbackError::LengthMismatch { guess: letters.len(), feedback: marks.len() });
+        }
+
+        let mut found: HashMap<char, usize> = HashMap::new();
+        for (&letter, &mark) in letters.iter().zip(marks) {
+            if mark != Mark::Gray {
+                *found.entry(letter).or_insert(0) += 1;
+            }
+        }
+
+        for (position, (&letter, &mark)) in letters.iter().zip(marks).enumerate() {
+            let count = found.get(&letter).copied().unwrap_or(0);
+            match mark {
+                Mark::Green => self.fixed.push((position, letter)),
+                Mark::Yellow => self.misplaced.push((position, letter)),
+                Mark::Gray => {
+                    self.misplaced.push((position, letter));
+                    let max = self.max_counts.entry(letter).or_insert(count);
+                    *max = (*max).min(count);
+                }
+            }
+
+            let min = self.min_counts.entry(letter).or_insert(0);
+            *min = (*min).max(count);
+        }
+
+        self.length.get_or_insert(letters.len());
+        Ok(())
+    }
+
+    // The letters the answer must and mustn't contain, when that's all these constraints
+    // say. None once positions, repeat counts or a length come into it.
+    pub fn letter_sets(&self) -> Option<(Vec<char>, Vec<char>)> {
+        if self.length.is_some()
+            || !self.fixed.is_empty()
+            || !self.misplaced.is_empty()
+            || self.min_counts.values().any(|&min| min > 1)
+            || self.max_counts.values().any(|&max| max > 0)
+        {
+            return None;
+        }
+
+        let mut required: Vec<char> = self.min_counts.iter().filter(|(_, &min)| min > 0).map(|(letter, _)| *letter).collect();
+        let mut excluded: Vec<char> = self.max_counts.keys().copied().collect();
+        required.sort_unstable();
+        excluded.sort_unstable();
+        Some((required, excluded))
+    }
+
+    pub fn allows(&self, word: &str) -> bool {
+        let letters: Vec<char> = word.to_lowercase().chars().collect();
+
+        self.length.iter().all(|&length| letters.len() == length)
+            && self.fixed.iter().all(|&(position, letter)| letters.get(position) == Some(&letter))
+            && self.misplaced.iter().all(|&(position, letter)| letters.get(position) != Some(&letter))
+            && self.counts_allow(&letters)
+    }
+
+    // Hard mode only asks that revealed hints are reused: greens stay put and yellows
+    // appear somewhere. Gray letters may still be guessed.
+    pub fn hard_mode_allows(&self, guess: &str) -> bool {
+        let letters: Vec<char> = guess.to_lowercase().chars().collect();
+
+        self.length.iter().all(|&length| letters.len() == length)
+            && self.fixed.iter().all(|&(position, letter)| letters.get(position) == Some(&letter))
+            && self
+                .min_counts
+                .iter()
+                .all(|(letter, &min)| letters.iter().filter(|candidate| *candidate == letter).count() >= min)
+    }
+
+    fn counts_allow(&self, letters: &[char]) -> bool {
+        let count = |letter: &char| letters.iter().filter(|candidate| *candidate == letter).count();
+
+        self.min_counts.iter().all(|(letter, &min)| count(letter) >= min)
+            && self.max_counts.iter().all(|(letter, &max)| count(letter) <= max)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion<'a> {
+    pub word: &'a str,
+    pub score: usize,
+}
+
+// Ranks `guesses` by how well they split the remaining `candidates`. Each distinct letter,
+// and each letter in its spot, scores the size of the smaller side of the split, so hints
+// every candidate shares (or none does) count for nothing. Candidates win ties, since they
+// might be the answer.
+#[instrument(skip_all, fields(candidate_count = candidates.len(), guess_count = guesses.len(), duration_ms))]
+pub fn suggest<'a>(candidates: &[&str], guesses: &[&'a str], count: usize) -> Vec<Suggestion<'a>> {
+    let _timer = SpanTimer::start();
+    let mut containing: HashMap<char, usize> = HashMap::new();
+    let mut placed: HashMap<(usize, char), usize> = HashMap::new();
+    for candidate in candidates {
+        for letter in distinct_letters(candidate) {
+            *containing.entry(letter).or_insert(0) += 1;
+        }
+        for hint in candidate.to_lowercase().chars().enumerate() {
+            *placed.entry(hint).or_insert(0) += 1;
+        }
+    }
+    let split = |with: Option<&usize>| {
+        let with = with.copied().unwrap_or(0);
+        with.min(candidates.len() - with)
+    };
+
+    let ranked: Vec<(Suggestion<'a>, bool)> = guesses
+        .iter()
+        .map(|&word| {
+            let letters: usize = distinct_letters(word).into_iter().map(|letter| split(containing.get(&letter))).sum();
+            let spots: usize = word.to_lowercase().chars().enumerate().map(|hint| split(placed.get(&hint))).sum();
+            let is_candidate = candidates.iter().any(|candidate| candidate.eq_ignore_ascii_case(word));
+            (Suggestion { word, score: letters + spots }, is_candidate)
+        })
+        .collect();
+
+    best(ranked, count)
+}
+
+// Ranks `guesses` by the entropy of the feedback each would get from the remaining
+// `candidates`: how much it's expected to tell, in thousandths of a bit. Where `suggest`
+// estimates from letter counts this plays every guess against every candidate, so it's
+// exact and that much slower. `pattern` gives the feedback for a guess and a candidate,
+// e.g. from a precomputed table, in any form that tells feedbacks apart.
+#[instrument(skip_all, fields(candidate_count = candidates.len(), guess_count = guesses.len(), duration_ms))]
+pub fn suggest_exact<'a, P: Eq + Hash>(candidates: &[&str], guesses: &[&'a str], count: usize, pattern: impl Fn(&str, &str) -> P) -> Vec<Suggestion<'a>> {
+    let _timer = SpanTimer::start();
+    let total = candidates.len() as f64;
+
+    let ranked: Vec<(Suggestion<'a>, bool)> = guesses
+        .iter()
+        .map(|&word| {
+            let mut groups: HashMap<P, usize> = HashMap::new();
+            for candidate in candidates {
+                *groups.entry(pattern(word, candidate)).or_insert(0) += 1;
+            }
+            // Summed in the same order every time, so equal splits score the same
+            let mut sizes: Vec<usize> = groups.into_values().collect();
+            sizes.sort_unstable();
+            let bits: f64 = sizes
+                .into_iter()
+                .map(|size| {
+                    let share = size as f64 / total;
+                    -share * share.log2()
+                })
+                .sum();
+            let is_candidate = candidates.iter().any(|candidate| candidate.eq_ignore_ascii_case(word));
+            (Suggestion { word, score: (bits * 1000.0).round() as usize }, is_candidate)
+        })
+        .collect();
+
+    best(ranked, count)
+}
+
+// Highest score first, candidates first among equals, otherwise in the order given
+fn best<'a>(mut ranked: Vec<(Suggestion<'a>, bool)>, count: usize) -> Vec<Suggestion<'a>> {
+    ranked.sort_by(|(a, a_candidate), (b, b_candidate)| {
+        b.score.cmp(&a.score).then(b_candidate.cmp(a_candidate))
+    });
+    ranked.into_iter().take(count).map(|(suggestion, _)| suggestion).collect()
+}
+
+// What guessing `guess` would do to the remaining `candidates`, the numbers a suggestion's
+// explanation is made of. `expected` is how many candidates are left on average, with every
+// candidate equally likely to be the answer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outlook {
+    pub candidates: usize,
+    pub worst_case: usize,
+    pub expected: f64,
+    // Letters of the guess some candidates have and others don't, alphabetically
+    pub probes: Vec<char>,
+    pub could_be_answer: bool,
+}
+
+pub fn outlook(guess: &str, candidates: &[&str]) -> Outlook {
+    outlook_by(guess, candidates, |candidate| feedback(guess, candidate))
+}
+
+// `outlook` with the feedback each candidate would give looked up by `pattern`, e.g. from
+// a precomputed table, in any form that tells feedbacks apart
+pub fn outlook_by<P: Eq + Hash>(guess: &str, candidates: &[&str], pattern: impl Fn(&str) -> P) -> Outlook {
+    let mut groups: HashMap<P, usize> = HashMap::new();
+    for candidate in candidates {
+        *groups.entry(pattern(candidate)).or_insert(0) += 1;
+    }
+    let squares: usize = groups.values().map(|size| size * size).sum();
+
+    let probes = distinct_letters(guess)
+        .into_iter()
+        .filter(|letter| {
+            let containing = candidates.iter().filter(|candidate| candidate.to_lowercase().contains(*letter)).count();
+            containing > 0 && containing < candidates.len()
+        })
+        .collect();
+
+    Outlook {
+        candidates: candidates.len(),
+        worst_case: groups.values().copied().max().unwrap_or(0),
+        expected: if candidates.is_empty() { 0.0 } else { squares as f64 / candidates.len() as f64 },
+        probes,
+        could_be_answer: candidates.iter().any(|candidate| candidate.eq_ignore_ascii_case(guess)),
+    }
+}
+
+// Why `guess` is worth trying, in words. The server and the CLI both explain suggestions
+// with this, so they say the same thing.
+pub fn explain(guess: &str, outlook: &Outlook) -> String {
+    let guess = guess.to_uppercase();
+    if outlook.candidates == 1 && outlook.could_be_answer {
+        return format!("{} is the only candidate left.", guess);
+    }
+
+    let mut sentences = vec![format!(
+        "Of the {} {} left, {} leaves at most {} and about {:.1} on average.",
+        outlook.candidates,
+        if outlook.candidates == 1 { "candidate" } else { "candidates" },
+        guess,
+        outlook.worst_case,
+        outlook.expected,
+    )];
+    sentences.push(match &outlook.probes[..] {
+        [] if outlook.worst_case == outlook.candidates => "It can't tell any of them apart.".to_string(),
+        [] => "Its letters are in all of them or none, it splits them by position only.".to_string(),
+        [letter] => format!("It checks for {}.", letter.to_ascii_uppercase()),
+        [rest @ .., last] => format!(
+            "It checks for {} and {}.",
+            rest.iter().map(|letter| letter.to_ascii_uppercase().to_string()).collect::<Vec<_>>().join(", "),
+            last.to_ascii_uppercase(),
+        ),
+    });
+    sentences.push(if outlook.could_be_answer { "It could be the answer itself." } else { "It can't be the answer itself." }.to_string());
+
+    sentences.join(" ")
+}
+
+fn distinct_letters(word: &str) -> Vec<char> {
+    let mut letters: Vec<char> = word.to_lowercase().chars().collect();
+    letters.sort_unstable();
+    letters.dedup();
+    letters
+}
+
+// Plays a game against `answer`, always guessing the best ranked remaining candidate, and
+// returns how many guesses it took. None when it wasn't found within `max_guesses` or
+// `answer` isn't among `words`.
+pub fn play(answer: &str, words: &[&str], max_guesses: usize) -> Option<usize> {
+    let mut candidates: Vec<&str> = words.iter().copied().filter(|word| word.len() == answer.len()).collect();
+    let mut constraints = Constraints::with_length(answer.len());
+
+    for turn in 1..=max_guesses {
+        let guess = suggest(&candidates, &candidates, 1).first()?.word;
+        if guess.eq_ignore_ascii_case(answer) {
+            return Some(turn);
+        }
+
+        constraints.apply(guess, &feedback(guess, answer)).ok()?;
+        candidates.retain(|word| constraints.allows(word));
+    }
+
+    None
+}
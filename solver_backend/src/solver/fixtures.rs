@@ -0,0 +1,111 @@
+// Letter states the ranking is pinned for, over a word list of its own, so tuning `suggest`
+// can't change the suggestions unnoticed. tests/solver_snapshots.rs records the top ten
+// for each state in every ranking mode, and the OpenAPI examples take their letters from
+// here too. Nothing needs a database.
+use super::{feedback, pattern_code, suggest, suggest_exact, Constraints, Suggestion};
+
+pub const WORDS: &str = include_str!("fixtures/words.txt");
+
+pub fn words() -> Vec<&'static str> {
+    WORDS.lines().collect()
+}
+
+// Which words may be guessed: any word, as the best-guess endpoint ranks them; only the
+// words still possible, as `play` does; or those that reuse every hint, as the CLI's --hard.
+// Exact ranks any word by entropy, as best-guess does in exact mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RankingMode {
+    Any,
+    Candidates,
+    Hard,
+    Exact,
+}
+
+impl RankingMode {
+    pub const ALL: [RankingMode; 4] = [RankingMode::Any, RankingMode::Candidates, RankingMode::Hard, RankingMode::Exact];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RankingMode::Any => "any",
+            RankingMode::Candidates => "candidates",
+            RankingMode::Hard => "hard",
+            RankingMode::Exact => "exact",
+        }
+    }
+}
+
+// The same three fields a client sends as RequestLetters, `exact` with _ for an unknown
+// letter
+#[derive(Debug)]
+pub struct State {
+    pub name: &'static str,
+    pub correct: &'static str,
+    pub incorrect: &'static str,
+    pub exact: &'static str,
+}
+
+impl State {
+    pub fn constraints(&self) -> Constraints {
+        let mut constraints = Constraints::with_length(self.exact.len());
+        self.correct.chars().for_each(|letter| constraints.require(letter));
+        self.incorrect.chars().for_each(|letter| constraints.exclude(letter));
+        for (position, letter) in self.exact.chars().enumerate().filter(|(_, letter)| *letter != '_') {
+            constraints.fix(position, letter);
+        }
+        constraints
+    }
+
+    pub fn candidates<'a>(&self, words: &[&'a str]) -> Vec<&'a str> {
+        let constraints = self.constraints();
+        words.iter().copied().filter(|word| constraints.allows(word)).collect()
+    }
+
+    // None suggested once no word is left, as the endpoint does
+    pub fn suggest<'a>(&self, words: &[&'a str], mode: RankingMode, count: usize) -> Vec<Suggestion<'a>> {
+        let candidates = self.candidates(words);
+        if candidates.is_empty() {
+            return Vec::new();
+        }
+        match mode {
+            RankingMode::Any => suggest(&candidates, words, count),
+            RankingMode::Candidates => suggest(&candidates, &candidates, count),
+            RankingMode::Hard => {
+                let constraints = self.constraints();
+                let guesses: Vec<&str> = words.iter().copied().filter(|word| constraints.hard_mode_allows(word)).collect();
+                suggest(&candidates, &guesses, count)
+            }
+            RankingMode::Exact => suggest_exact(&candidates, words, count, |guess, candidate| pattern_code(&feedback(guess, candidate))),
+        }
+    }
+}
+
+pub fn state(name: &str) -> &'static State {
+    STATES.iter().find(|state| state.name == name).unwrap_or_else(|| panic!("no solver fixture state named {}", name))
+}
+
+pub const STATES: &[State] = &[
+    // Openings
+    State { name: "opening", correct: "", incorrect: "", exact: "_____" },
+    State { name: "contains_a", correct: "a", incorrect: "", exact: "_____" },
+    State { name: "no_e_or_a", correct: "", incorrect: "ea", exact: "_____" },
+    State { name: "slate_all_gray", correct: "", incorrect: "slate", exact: "_____" },
+    // Mid-game
+    State { name: "ends_in_e_with_r_and_a", correct: "ra", incorrect: "s", exact: "____e" },
+    State { name: "ends_in_ht", correct: "ht", incorrect: "aeo", exact: "___ht" },
+    State { name: "ends_in_tch", correct: "c", incorrect: "e", exact: "__tch" },
+    State { name: "ends_in_ound", correct: "", incorrect: "a", exact: "_ound" },
+    State { name: "starts_with_sha", correct: "a", incorrect: "t", exact: "sha__" },
+    State { name: "starts_with_th_ends_in_e", correct: "", incorrect: "a", exact: "th__e" },
+    State { name: "ee_in_the_middle", correct: "e", incorrect: "a", exact: "_ee__" },
+    // Endgames
+    State { name: "ight_without_f_l_m_n", correct: "", incorrect: "flmnae", exact: "___ht" },
+    State { name: "ound_without_h_p_s", correct: "", incorrect: "ahps", exact: "_ound" },
+    State { name: "these_or_those", correct: "t", incorrect: "a", exact: "th_se" },
+    State { name: "one_left", correct: "", incorrect: "", exact: "kayak" },
+    State { name: "nothing_left", correct: "z", incorrect: "", exact: "sh___" },
+    // Repeated letters
+    State { name: "double_p", correct: "p", incorrect: "", exact: "__pp_" },
+    State { name: "double_m", correct: "", incorrect: "", exact: "__mm_" },
+    State { name: "ends_in_double_s", correct: "s", incorrect: "e", exact: "___ss" },
+    State { name: "e_twice", correct: "", incorrect: "", exact: "__e_e" },
+];
@@ -0,0 +1,123 @@
+// How a server-chosen answer is picked. Every scope (the daily puzzle, each league) gets
+// one answer a day: the selector picks it the first time it's asked for and
+// used_answers keeps it, so later requests and other replicas see the same word.
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use sqlx::AnyPool;
+use std::collections::HashSet;
+use std::str::FromStr;
+use utoipa::ToSchema;
+use crate::repositories::{answers, words::WordRepository};
+
+#[async_trait]
+pub trait AnswerSelector: Send + Sync {
+    // One of `answers`, None only when it's empty
+    async fn pick(&self, db: &AnyPool, scope: &str, day: NaiveDate, answers: &[String]) -> Result<Option<String>, sqlx::Error>;
+}
+
+pub struct Uniform;
+
+#[async_trait]
+impl AnswerSelector for Uniform {
+    async fn pick(&self, _db: &AnyPool, _scope: &str, _day: NaiveDate, answers: &[String]) -> Result<Option<String>, sqlx::Error> {
+        Ok(answers.choose(&mut rand::thread_rng()).cloned())
+    }
+}
+
+// A word used on one day can't come back on any of the `days` days after it. Once every
+// answer was used within the window, any of them can.
+pub struct NoRepeat {
+    pub days: u32,
+}
+
+#[async_trait]
+impl AnswerSelector for NoRepeat {
+    async fn pick(&self, db: &AnyPool, scope: &str, day: NaiveDate, answers: &[String]) -> Result<Option<String>, sqlx::Error> {
+        let since = day - Duration::days(self.days.into());
+        let used: HashSet<String> = answers::used_between(db, scope, since, day).await?.into_iter().collect();
+        let fresh: Vec<String> = answers.iter().filter(|word| !used.contains(*word)).cloned().collect();
+        let pool = if fresh.is_empty() { answers } else { &fresh };
+        Ok(pool.choose(&mut rand::thread_rng()).cloned())
+    }
+}
+
+// The word answer_schedule has for the day, or what `fallback` picks on days without one.
+// A scheduled word no longer in the answer list is skipped rather than served.
+pub struct Scheduled {
+    pub fallback: Box<dyn AnswerSelector>,
+}
+
+#[async_trait]
+impl AnswerSelector for Scheduled {
+    async fn pick(&self, db: &AnyPool, scope: &str, day: NaiveDate, answers: &[String]) -> Result<Option<String>, sqlx::Error> {
+        match answers::scheduled(db, scope, day).await? {
+            Some(word) if answers.contains(&word) => Ok(Some(word)),
+            _ => self.fallback.pick(db, scope, day, answers).await,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnswerStrategy {
+    Uniform,
+    NoRepeat,
+    // Falls back to no-repeat on days nothing is scheduled
+    Scheduled,
+}
+
+impl AnswerStrategy {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnswerStrategy::Uniform => "uniform",
+            AnswerStrategy::NoRepeat => "no_repeat",
+            AnswerStrategy::Scheduled => "scheduled",
+        }
+    }
+}
+
+impl FromStr for AnswerStrategy {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "uniform" => Ok(AnswerStrategy::Uniform),
+            "no_repeat" => Ok(AnswerStrategy::NoRepeat),
+            "scheduled" => Ok(AnswerStrategy::Scheduled),
+            _ => Err(()),
+        }
+    }
+}
+
+pub fn selector(strategy: AnswerStrategy, no_repeat_days: u32) -> Box<dyn AnswerSelector> {
+    match strategy {
+        AnswerStrategy::Uniform => Box::new(Uniform),
+        AnswerStrategy::NoRepeat => Box::new(NoRepeat { days: no_repeat_days }),
+        AnswerStrategy::Scheduled => Box::new(Scheduled { fallback: Box::new(NoRepeat { days: no_repeat_days }) }),
+    }
+}
+
+// The answer of `scope` on `day`, picked the first time and the same word ever after. When
+// two requests race to pick, the first one recorded is the answer for both.
+pub async fn answer_for(
+    db: &AnyPool,
+    words: &dyn WordRepository,
+    selector: &dyn AnswerSelector,
+    scope: &str,
+    day: NaiveDate,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, sqlx::Error> {
+    if let Some(word) = answers::used_on(db, scope, day).await? {
+        return Ok(Some(word));
+    }
+    let candidates = words.answer_words().await?;
+    let Some(word) = selector.pick(db, scope, day, &candidates).await? else {
+        return Ok(None);
+    };
+    if answers::record_used(db, scope, day, &word, now).await? {
+        return Ok(Some(word));
+    }
+    answers::used_on(db, scope, day).await
+}
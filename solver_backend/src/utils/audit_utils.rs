@@ -0,0 +1,20 @@
+use tracing::error;
+use sqlx::AnyPool;
+
+pub async fn log_auth_event(pool: &AnyPool, user_id: Option<i32>, event_type: &str, detail: &str) {
+    let query = sqlx::query(
+            r#"
+            INSERT INTO auth_events (user_id, event_type, detail)
+            VALUES ($1, $2, $3)
+            "#)
+        .bind(user_id)
+        .bind(event_type)
+        .bind(detail)
+        .execute(pool)
+        .await;
+
+    // A failed audit write should never fail the request that triggered it
+    if let Err(error) = query {
+        error!("Failed to record auth event {}: {}", event_type, error);
+    }
+}
@@ -0,0 +1,304 @@
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::Method;
+use actix_web::HttpRequest;
+use rand::{distributions::Alphanumeric, Rng};
+use tracing::{error, warn};
+use crate::errors::{AppError, ErrorCode, ErrorInfo};
+use crate::handlers::{API_PREFIX, LEGACY_API_PREFIX};
+use crate::models::users_models::{AccessClaims, InactiveReason, TokenType};
+use crate::utils::audit_utils::log_auth_event;
+use crate::utils::jwt_utils::{decode_claims, TokenRejection};
+use crate::AppState;
+
+pub const ADMIN_ROLE: &str = "admin";
+// Holders of a token from /users/guest, who have no account
+pub const GUEST_ROLE: &str = "guest";
+
+// What a guest token is good for, as routes under the API prefix: filtering candidates,
+// working out feedback and starting an anonymous session to play. Everything else is a 403.
+pub static GUEST_ROUTES: &[(Method, &str)] = &[
+    (Method::POST, "/game/candidates"),
+    (Method::POST, "/game/feedback/batch"),
+    (Method::POST, "/game/sessions"),
+];
+
+// Opaque secret handed to a client, e.g. to confirm an email change
+pub fn random_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(48)
+        .map(char::from)
+        .collect()
+}
+
+pub struct AuthUser {
+    pub id: i32,
+    pub role: String,
+    // The admin acting as this user when the token is an impersonation one
+    pub impersonator: Option<i32>,
+    // The id in a guest token, whose `id` is 0 as there's no user behind it
+    pub guest: Option<String>,
+}
+
+impl AuthUser {
+    pub fn is_admin(&self) -> bool {
+        self.role == ADMIN_ROLE
+    }
+
+    pub fn is_guest(&self) -> bool {
+        self.role == GUEST_ROLE
+    }
+}
+
+pub fn get_bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|header| {
+            let header_value = header.to_str().ok()?;
+            if header_value.starts_with("Bearer ") {
+                Some(header_value.trim_start_matches("Bearer ").to_string())
+            } else {
+                None
+            }
+        })
+}
+
+// The store answers for tokens seen recently, the table for everything else. When neither
+// can answer, REVOCATION_FAIL_OPEN decides whether the token is accepted.
+pub async fn check_revoked_token(token: &str, state: &AppState) -> bool {
+    match state.stores.revocations.is_revoked(token).await {
+        Ok(Some(revoked)) => {
+            state.metrics.observe_cache_lookup("revoked_tokens", true);
+            return revoked;
+        }
+        Ok(None) => state.metrics.observe_cache_lookup("revoked_tokens", false),
+        Err(error) => warn!("Revocation store lookup failed: {}", error),
+    }
+
+    let query = sqlx::query(
+        r#"
+        SELECT token FROM revoked_tokens WHERE token = $1
+        "#,
+    )
+    .bind(token)
+    .fetch_optional(&state.db)
+    .await;
+
+    match query {
+        Ok(row) => {
+            let revoked = row.is_some();
+            record_revocation(token, revoked, state).await;
+            revoked
+        }
+        Err(error) => {
+            error!("Failed to check revoked token: {}", error);
+            !state.stores.revocation_fail_open
+        }
+    }
+}
+
+// Called after a token is written to revoked_tokens so other instances stop accepting it
+// straight away instead of once their cached answer expires
+pub async fn record_revocation(token: &str, revoked: bool, state: &AppState) {
+    if let Err(error) = state.stores.revocations.record(token, revoked).await {
+        warn!("Failed to record token revocation: {}", error);
+    }
+}
+
+async fn current_token_version(user_id: i32, state: &AppState) -> Option<(i32, String)> {
+    if let Some(cached) = state.auth_cache.token_versions.get(&user_id) {
+        return Some(cached);
+    }
+
+    let current: Result<Option<(i32, String)>, _> = sqlx::query_as("SELECT token_version, role FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&state.db)
+        .await;
+
+    let current = match current {
+        Ok(current) => current?,
+        Err(error) => {
+            error!("Failed to load token version: {}", error);
+            return None;
+        }
+    };
+
+    state.auth_cache.token_versions.insert(user_id, current.clone());
+    Some(current)
+}
+
+// Whether the request sends credentials at all, usable or not
+pub fn has_credentials(req: &HttpRequest) -> bool {
+    req.headers().contains_key(AUTHORIZATION)
+}
+
+// missing_token when there's no Authorization header, invalid_token when it isn't a bearer token
+fn bearer_credentials(req: &HttpRequest) -> Result<String, AppError> {
+    if !has_credentials(req) {
+        return Err(AppError::unauthorized(ErrorCode::MissingToken, "Authentication required"));
+    }
+
+    get_bearer_token(req)
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| AppError::unauthorized(ErrorCode::InvalidToken, "The Authorization header isn't a bearer token"))
+}
+
+// A token is only accepted while its embedded version matches the user's current one.
+// Bumping users.token_version therefore logs out every session of that user at once.
+// The error says why a token was turned away: invalid_token, unknown_signing_key,
+// token_expired or token_revoked, which also covers tokens of users that no longer exist.
+pub async fn authenticate_token(token: &str, state: &AppState) -> Result<AuthUser, AppError> {
+    inspect_token(token, state).await.map(|(user, _)| user).map_err(inactive_token)
+}
+
+// The same checks for the refresh token a new pair is asked for with
+pub async fn authenticate_refresh_token(token: &str, state: &AppState) -> Result<AuthUser, AppError> {
+    inspect_token_of_type(token, TokenType::Refresh, state).await.map(|(user, _)| user).map_err(inactive_token)
+}
+
+fn inactive_token(reason: InactiveReason) -> AppError {
+    match reason {
+        InactiveReason::BadSignature => AppError::Unauthorized(ErrorInfo::new(ErrorCode::InvalidToken, "Invalid token")),
+        InactiveReason::UnknownKey => AppError::Unauthorized(ErrorInfo::new(ErrorCode::UnknownSigningKey, "Token was signed with a key that is no longer trusted")),
+        InactiveReason::WrongType => AppError::Unauthorized(ErrorInfo::new(ErrorCode::InvalidToken, "Wrong kind of token")),
+        InactiveReason::Expired => AppError::Unauthorized(ErrorInfo::new(ErrorCode::TokenExpired, "Token has expired")),
+        InactiveReason::Revoked | InactiveReason::VersionMismatch => token_revoked(),
+    }
+}
+
+// The checks behind `authenticate_token`, telling a stale token version apart from a
+// revocation, with the claims for /auth/introspect to report. Both go through here so
+// they can't disagree about a token. Refresh tokens aren't access tokens.
+pub async fn inspect_token(token: &str, state: &AppState) -> Result<(AuthUser, AccessClaims), InactiveReason> {
+    inspect_token_of_type(token, TokenType::Access, state).await
+}
+
+async fn inspect_token_of_type(token: &str, typ: TokenType, state: &AppState) -> Result<(AuthUser, AccessClaims), InactiveReason> {
+    if check_revoked_token(token, state).await {
+        return Err(InactiveReason::Revoked);
+    }
+
+    let claims = decode_claims(&state.signing_keys, token, state.clock.now()).map_err(|rejection| match rejection {
+        TokenRejection::Invalid => InactiveReason::BadSignature,
+        TokenRejection::UnknownKey => InactiveReason::UnknownKey,
+        TokenRejection::Expired => InactiveReason::Expired,
+    })?;
+
+    if claims.typ != typ {
+        return Err(InactiveReason::WrongType);
+    }
+
+    // Guests have no users row, so no version or role to check against
+    if let Some(guest) = &claims.guest {
+        let user = AuthUser {
+            id: claims.user_id,
+            role: GUEST_ROLE.to_string(),
+            impersonator: None,
+            guest: Some(guest.clone()),
+        };
+        return Ok((user, claims));
+    }
+
+    let (token_version, role) = current_token_version(claims.user_id, state).await.ok_or(InactiveReason::Revoked)?;
+
+    if token_version != claims.token_version {
+        return Err(InactiveReason::VersionMismatch);
+    }
+
+    // Impersonation ends as soon as the admin behind it stops being one
+    if let Some(impersonator) = claims.impersonator {
+        match current_token_version(impersonator, state).await {
+            Some((_, role)) if role == ADMIN_ROLE => {}
+            _ => return Err(InactiveReason::Revoked),
+        }
+    }
+
+    let user = AuthUser {
+        id: claims.user_id,
+        role,
+        impersonator: claims.impersonator,
+        guest: None,
+    };
+    Ok((user, claims))
+}
+
+pub fn token_revoked() -> AppError {
+    AppError::Unauthorized(ErrorInfo::new(ErrorCode::TokenRevoked, "Token has been revoked"))
+}
+
+// For a logged in user reaching for something that belongs to someone else
+pub fn not_owner(message: impl Into<String>) -> AppError {
+    AppError::Forbidden(ErrorInfo::new(ErrorCode::NotOwner, message))
+}
+
+// Whether the request is to one of GUEST_ROUTES, at either API prefix
+fn guest_allowed(req: &HttpRequest) -> bool {
+    let Some(pattern) = req.match_pattern() else {
+        return false;
+    };
+    let route = pattern
+        .strip_prefix(API_PREFIX)
+        .or_else(|| pattern.strip_prefix(LEGACY_API_PREFIX))
+        .unwrap_or(&pattern);
+
+    GUEST_ROUTES.iter().any(|(method, allowed)| method == req.method() && *allowed == route)
+}
+
+// Every request made as someone else is audited under the user with the admin named.
+// Only reads go through unless IMPERSONATION_ALLOW_WRITES is set, and none of it counts
+// towards the user's API usage. Guests only get through to GUEST_ROUTES and have no
+// usage to count.
+async fn admit_request(req: &HttpRequest, state: &AppState, user: &AuthUser) -> Result<(), AppError> {
+    if user.is_guest() {
+        if !guest_allowed(req) {
+            return Err(AppError::Forbidden(ErrorInfo::new(ErrorCode::GuestNotAllowed, "Guests can only filter words and work out feedback, register to do this")));
+        }
+        return Ok(());
+    }
+
+    let impersonator = match user.impersonator {
+        Some(impersonator) => impersonator,
+        None => {
+            state.usage.record_request(req, user.id, state.clock.now().date_naive());
+            return Ok(());
+        }
+    };
+
+    let read = matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+    let allowed = read || state.settings.impersonation_allows_writes;
+    let detail = format!("{} {} by admin {}{}", req.method(), req.path(), impersonator, if allowed { "" } else { ", refused" });
+    log_auth_event(&state.db, Some(user.id), "impersonated_request", &detail).await;
+
+    if !allowed {
+        return Err(AppError::Forbidden(ErrorInfo::new(ErrorCode::ImpersonationReadOnly, "Impersonation tokens can only read")));
+    }
+
+    Ok(())
+}
+
+// None for anonymous requests and ones whose token isn't accepted
+pub async fn authenticated_user(req: &HttpRequest, state: &AppState) -> Option<AuthUser> {
+    let access_token = get_bearer_token(req)?;
+    let user = authenticate_token(&access_token, state).await.ok()?;
+    admit_request(req, state, &user).await.ok()?;
+    Some(user)
+}
+
+// Counted towards the user's API usage once the token is accepted. Credentials that are
+// missing or aren't accepted are a 401, a user who isn't allowed the request a 403.
+pub async fn require_user(req: &HttpRequest, state: &AppState) -> Result<AuthUser, AppError> {
+    let access_token = bearer_credentials(req)?;
+    let user = authenticate_token(&access_token, state).await?;
+    admit_request(req, state, &user).await?;
+    Ok(user)
+}
+
+pub async fn require_admin(req: &HttpRequest, state: &AppState) -> Result<AuthUser, AppError> {
+    let user = require_user(req, state).await?;
+
+    if !user.is_admin() {
+        return Err(AppError::Forbidden(ErrorInfo::new(ErrorCode::AdminRequired, "Only admins can do this")));
+    }
+
+    Ok(user)
+}
@@ -1,14 +1,19 @@
-use bcrypt::{hash, verify, DEFAULT_COST};
+use bcrypt::{hash, verify};
+use tracing::instrument;
+use crate::utils::tracing_utils::SpanTimer;
 
-pub fn hash_password(password: &str) -> Result<String, bcrypt::BcryptError> {
-    // Hash the password using bcrypt with a default cost factor
-    hash(password, DEFAULT_COST)
+// Both take tens of milliseconds on purpose, so they get spans of their own
+#[instrument(skip_all, fields(cost = cost, duration_ms))]
+pub fn hash_password(password: &str, cost: u32) -> Result<String, bcrypt::BcryptError> {
+    let _timer = SpanTimer::start();
+    // Hash the password using bcrypt with the configured cost factor
+    hash(password, cost)
 }
 
+#[instrument(skip_all, fields(duration_ms))]
 pub fn verify_password(password: &str, hashed_password: &str) -> bool {
+    let _timer = SpanTimer::start();
     //Verify the password against the hashed password
-    match verify(password, hashed_password) {
-        Ok(result) => result,
-        Err(_) => false,
-    }
+    verify(password, hashed_password).unwrap_or_default()
 }
+
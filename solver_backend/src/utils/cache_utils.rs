@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::utils::clock_utils::{Clock, SystemClock};
+
+const MAX_ENTRIES: usize = 10_000;
+
+// A small map whose entries expire after a fixed time-to-live, measured on `clock`
+pub struct TtlCache<K, V> {
+    entries: Mutex<HashMap<K, (V, DateTime<Utc>)>>,
+    ttl: chrono::Duration,
+    clock: Arc<dyn Clock>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl<K: Eq + Hash, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl: Duration) -> Self {
+        Self::with_clock(ttl, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(ttl: Duration, clock: Arc<dyn Clock>) -> Self {
+        TtlCache {
+            entries: Mutex::new(HashMap::new()),
+            ttl: chrono::Duration::from_std(ttl).unwrap_or(chrono::Duration::max_value()),
+            clock,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn is_fresh(&self, inserted: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        now - inserted < self.ttl
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let entries = self.entries.lock().unwrap();
+        let now = self.clock.now();
+
+        match entries.get(key) {
+            Some((value, inserted)) if self.is_fresh(*inserted, now) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(value.clone())
+            }
+            _ => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap();
+        let now = self.clock.now();
+
+        if entries.len() >= MAX_ENTRIES {
+            entries.retain(|_, (_, inserted)| self.is_fresh(*inserted, now));
+            if entries.len() >= MAX_ENTRIES {
+                entries.clear();
+            }
+        }
+
+        entries.insert(key, (value, now));
+    }
+
+    pub fn invalidate(&self, key: &K) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+// Token versions are looked up on every authenticated request. Entries live for
+// AUTH_CACHE_TTL_SECONDS, so a change made through another instance takes effect within
+// that window, while changes made through this instance invalidate the entry right away.
+pub struct AuthCache {
+    // user id -> (token version, role)
+    pub token_versions: TtlCache<i32, (i32, String)>,
+}
+
+impl AuthCache {
+    pub fn new(ttl: Duration, clock: Arc<dyn Clock>) -> Self {
+        AuthCache {
+            token_versions: TtlCache::with_clock(ttl, clock),
+        }
+    }
+}
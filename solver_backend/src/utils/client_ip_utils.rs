@@ -0,0 +1,116 @@
+use crate::config::{ForwardedHeader, ProxySettings};
+use crate::errors::AppError;
+use crate::AppState;
+use actix_web::dev::Payload;
+use actix_web::http::header::{HeaderMap, FORWARDED, X_FORWARDED_FOR};
+use actix_web::{web, FromRequest, HttpRequest};
+use futures_util::future::{ready, Ready};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+// The address of whoever sent the request. Forwarding headers are only believed when the
+// peer is a trusted proxy, and then only as far back as the hops are trusted proxies too,
+// since anything further left was written by the client and can say whatever it likes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+impl ClientIp {
+    // None when the request didn't come over a socket, e.g. in tests
+    pub fn of(req: &HttpRequest) -> Option<ClientIp> {
+        let peer = req.peer_addr()?.ip();
+
+        Some(ClientIp(match req.app_data::<web::Data<AppState>>() {
+            Some(state) => resolve(peer, req.headers(), &state.settings.proxies),
+            None => canonical(peer),
+        }))
+    }
+}
+
+impl FromRequest for ClientIp {
+    type Error = AppError;
+    type Future = Ready<Result<Self, AppError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(ClientIp::of(req).ok_or_else(|| AppError::internal("Request has no peer address")))
+    }
+}
+
+// Walks the hops right to left, nearest proxy first, and stops at the first one that
+// isn't trusted. A hop that can't be read ends the walk at the last address known good.
+pub fn resolve(peer: IpAddr, headers: &HeaderMap, proxies: &ProxySettings) -> IpAddr {
+    let trusted = |address: IpAddr| trusted_proxy(address, proxies);
+
+    let mut client = canonical(peer);
+    if !trusted(client) {
+        return client;
+    }
+
+    for hop in forwarded_hops(headers, proxies.header).into_iter().rev() {
+        match hop {
+            Some(hop) => client = canonical(hop),
+            None => break,
+        }
+        if !trusted(client) {
+            break;
+        }
+    }
+    client
+}
+
+// Whether `address` is one of TRUSTED_PROXIES
+pub fn trusted_proxy(address: IpAddr, proxies: &ProxySettings) -> bool {
+    let address = canonical(address);
+    proxies.trusted.iter().any(|network| network.contains(&address))
+}
+
+// Every hop listed, leftmost first, across repeated header lines. None for entries that
+// aren't an address, such as `unknown` or an obfuscated identifier.
+fn forwarded_hops(headers: &HeaderMap, header: ForwardedHeader) -> Vec<Option<IpAddr>> {
+    let name = match header {
+        ForwardedHeader::XForwardedFor => X_FORWARDED_FOR,
+        ForwardedHeader::Forwarded => FORWARDED,
+    };
+
+    let mut hops = Vec::new();
+    for value in headers.get_all(name) {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => {
+                hops.push(None);
+                continue;
+            }
+        };
+
+        for element in value.split(',') {
+            let node = match header {
+                ForwardedHeader::XForwardedFor => Some(element),
+                // for=192.0.2.60;proto=https;by=203.0.113.43
+                ForwardedHeader::Forwarded => element.split(';').find_map(|pair| {
+                    let (key, value) = pair.split_once('=')?;
+                    key.trim().eq_ignore_ascii_case("for").then_some(value)
+                }),
+            };
+            hops.push(node.and_then(parse_node));
+        }
+    }
+    hops
+}
+
+// An address with or without a port, IPv6 ones bracketed when they have one
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+
+    if let Some(bracketed) = node.strip_prefix('[') {
+        return bracketed.split(']').next()?.parse::<Ipv6Addr>().ok().map(IpAddr::V6);
+    }
+    node.parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|address| address.ip()))
+}
+
+// IPv4 clients of a dual stack listener show up as ::ffff:a.b.c.d
+fn canonical(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(address, IpAddr::V4),
+        IpAddr::V4(_) => address,
+    }
+}
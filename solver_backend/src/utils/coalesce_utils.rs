@@ -0,0 +1,95 @@
+use crate::errors::{AppError, ErrorCode, ErrorInfo};
+use actix_web::rt;
+use futures_util::future::{BoxFuture, FutureExt, Shared};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::error;
+
+type Outcome<V> = Result<Arc<V>, Arc<AppError>>;
+type Flights<V> = Arc<Mutex<HashMap<String, (u64, Shared<BoxFuture<'static, Outcome<V>>>)>>>;
+
+// Single-flight: requests for the same key while its computation runs wait for that one
+// instead of starting their own, and all get its result. The computation runs in a task of
+// its own, so a panic in it or a waiter going away doesn't take the others down, and it is
+// forgotten once it finishes or fails, so the next request starts afresh.
+pub struct SingleFlight<V> {
+    flights: Flights<V>,
+    next_id: AtomicU64,
+    timeout: Duration,
+}
+
+impl<V: Send + Sync + 'static> SingleFlight<V> {
+    pub fn new(timeout: Duration) -> Self {
+        SingleFlight { flights: Arc::new(Mutex::new(HashMap::new())), next_id: AtomicU64::new(0), timeout }
+    }
+
+    // `compute` only runs when no computation for `key` is underway. Errors, a panic and
+    // running past the timeout reach every waiter.
+    pub async fn run<F, Fut>(&self, key: String, compute: F) -> Result<Arc<V>, AppError>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V, AppError>> + 'static,
+    {
+        let flight = {
+            let mut flights = self.flights.lock().unwrap();
+            match flights.get(&key) {
+                Some((_, flight)) => flight.clone(),
+                None => {
+                    let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+                    let flight = self.start(key.clone(), id, compute());
+                    flights.insert(key, (id, flight.clone()));
+                    flight
+                }
+            }
+        };
+
+        flight.await.map_err(|error| error.replicate())
+    }
+
+    fn start<Fut>(&self, key: String, id: u64, computation: Fut) -> Shared<BoxFuture<'static, Outcome<V>>>
+    where
+        Fut: Future<Output = Result<V, AppError>> + 'static,
+    {
+        let flights = self.flights.clone();
+        let timeout = self.timeout;
+
+        // Forgotten by the task as soon as it's done, even when every waiter has gone
+        let task_flights = flights.clone();
+        let task_key = key.clone();
+        let mut task = rt::spawn(async move {
+            let result = computation.await;
+            forget(&task_flights, &task_key, id);
+            result
+        });
+
+        async move {
+            let outcome = match rt::time::timeout(timeout, &mut task).await {
+                Ok(Ok(result)) => result.map(Arc::new),
+                Ok(Err(join_error)) => {
+                    error!("Shared computation for {} failed: {}", key, join_error);
+                    Err(AppError::internal(format!("shared computation for {}: {}", key, join_error)))
+                }
+                Err(_) => {
+                    task.abort();
+                    Err(AppError::Timeout(ErrorInfo::new(ErrorCode::QueryTimeout, "The request took too long and was cancelled")))
+                }
+            };
+            // Panics and timeouts never got as far as the task's own cleanup
+            forget(&flights, &key, id);
+            outcome.map_err(Arc::new)
+        }
+        .boxed()
+        .shared()
+    }
+}
+
+// Only the flight `id`, a newer one under the same key is left alone
+fn forget<V>(flights: &Flights<V>, key: &str, id: u64) {
+    let mut flights = flights.lock().unwrap();
+    if flights.get(key).is_some_and(|(flight_id, _)| *flight_id == id) {
+        flights.remove(key);
+    }
+}
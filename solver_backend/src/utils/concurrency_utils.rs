@@ -0,0 +1,71 @@
+use crate::errors::{AppError, ErrorCode, ErrorInfo};
+use crate::utils::metrics_utils::Metrics;
+use actix_web::rt;
+use prometheus::IntGauge;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+// Candidate queries, the heaviest thing a request can ask for
+pub const SOLVER: &str = "solver";
+
+pub const ROUTE_GROUPS: &[&str] = &[SOLVER];
+
+// What clients turned away are told to wait, slots free up within a request's time
+const RETRY_AFTER_SECONDS: u64 = 1;
+
+// Caps how many requests of each route group run at once, so a burst of heavy requests
+// can't starve everything else. Shared by every worker. Routes outside a group, or in
+// one without a limit, never wait.
+pub struct ConcurrencyLimits {
+    groups: Vec<(&'static str, Arc<Semaphore>)>,
+    queue_timeout: Duration,
+}
+
+// Holds a slot until dropped
+pub struct ConcurrencyPermit {
+    _permit: Option<OwnedSemaphorePermit>,
+    in_flight: IntGauge,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        self.in_flight.dec();
+    }
+}
+
+impl ConcurrencyLimits {
+    // None for a group leaves it unlimited
+    pub fn new(limits: &[(&'static str, Option<usize>)], queue_timeout: Duration) -> Self {
+        ConcurrencyLimits {
+            groups: limits
+                .iter()
+                .filter_map(|(group, limit)| limit.map(|limit| (*group, Arc::new(Semaphore::new(limit)))))
+                .collect(),
+            queue_timeout,
+        }
+    }
+
+    // Waits up to the queue timeout for a slot, then gives up with a 503 and Retry-After
+    pub async fn acquire(&self, group: &'static str, metrics: &Metrics) -> Result<ConcurrencyPermit, AppError> {
+        let permit = match self.groups.iter().find(|(name, _)| *name == group) {
+            Some((_, semaphore)) => match rt::time::timeout(self.queue_timeout, semaphore.clone().acquire_owned()).await {
+                Ok(Ok(permit)) => Some(permit),
+                // Timed out, the semaphore itself is never closed
+                _ => {
+                    metrics.observe_shed_request(group);
+                    let info = ErrorInfo::new(ErrorCode::Overloaded, "Too many of these requests are running, try again shortly")
+                        .with_details(json!({ "group": group }))
+                        .with_retry_after(RETRY_AFTER_SECONDS);
+                    return Err(AppError::Unavailable(info));
+                }
+            },
+            None => None,
+        };
+
+        let in_flight = metrics.in_flight(group);
+        in_flight.inc();
+        Ok(ConcurrencyPermit { _permit: permit, in_flight })
+    }
+}
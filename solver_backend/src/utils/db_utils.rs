@@ -0,0 +1,280 @@
+use actix_web::rt::time::{sleep, timeout};
+use rand::Rng;
+use sqlx::any::{AnyKind, AnyPoolOptions};
+use sqlx::migrate::{Migrate, MigrateError, Migrator};
+use sqlx::{AnyConnection, AnyPool, Connection, Executor};
+use crate::config::{Settings, MEMORY_DATABASE_URL};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+use crate::utils::seed_utils::seed_words;
+use crate::utils::shutdown_utils::{wait_for_shutdown, ShutdownSignal};
+
+// Everything under migrations/, compiled into the binary. SQLite gets its own copies
+// where the types or syntax differ, kept under the same version numbers.
+pub static POSTGRES_MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+pub fn migrator(kind: AnyKind) -> &'static Migrator {
+    match kind {
+        AnyKind::Postgres => &POSTGRES_MIGRATOR,
+        AnyKind::Sqlite => &SQLITE_MIGRATOR,
+    }
+}
+
+// Postgres needs rows locked explicitly, SQLite only ever has one writer and has no
+// locking clause at all
+pub fn row_lock(kind: AnyKind) -> &'static str {
+    match kind {
+        AnyKind::Postgres => "FOR UPDATE",
+        AnyKind::Sqlite => "",
+    }
+}
+
+// Like `row_lock`, but rows another transaction holds are passed over instead of waited for
+pub fn skip_locked(kind: AnyKind) -> &'static str {
+    match kind {
+        AnyKind::Postgres => "FOR UPDATE SKIP LOCKED",
+        AnyKind::Sqlite => "",
+    }
+}
+
+// A round trip to the database and nothing else, for readiness checks
+pub async fn ping(pool: &AnyPool) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT 1").execute(pool).await?;
+    Ok(())
+}
+
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(15);
+// A single attempt against a host that drops packets shouldn't eat the whole budget
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Tables the handlers query directly, checked once at startup
+const EXPECTED_TABLES: &[&str] = &[
+    "users",
+    "revoked_tokens",
+    "word_list",
+    "refresh_tokens",
+    "auth_events",
+    "password_history",
+    "reserved_usernames",
+    "idempotency_keys",
+    "word_list_version",
+    "jobs",
+];
+
+// Whether the startup connection has gone through. The server starts listening before
+// the database is reachable, so readiness reports this until it has.
+#[derive(Default)]
+pub struct DbStatus {
+    connected: AtomicBool,
+}
+
+impl DbStatus {
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Acquire)
+    }
+
+    fn mark_connected(&self) {
+        self.connected.store(true, Ordering::Release);
+    }
+}
+
+// Every pooled Postgres connection gets the statement timeout, so a runaway query is
+// cancelled by Postgres itself and its connection freed even if nothing is waiting on it
+// any more. SQLite has no equivalent.
+pub fn pool_options(settings: &Settings) -> AnyPoolOptions {
+    let statement_timeout = settings.db_statement_timeout.as_millis();
+
+    AnyPoolOptions::new()
+        .max_connections(settings.db_max_connections)
+        .min_connections(settings.db_min_connections)
+        .acquire_timeout(settings.db_acquire_timeout)
+        .after_connect(move |conn, _| {
+            Box::pin(async move {
+                if statement_timeout > 0 && conn.kind() == AnyKind::Postgres {
+                    conn.execute(format!("SET statement_timeout = {}", statement_timeout).as_str()).await?;
+                }
+                Ok(())
+            })
+        })
+}
+
+// The database behind STORAGE=memory, an SQLite one living in this process, migrated and
+// filled with the bundled word list. It only lasts while the pool keeps a connection open,
+// so connections are never retired and one is always held.
+pub async fn open_memory_database(settings: &Settings) -> Result<AnyPool, String> {
+    let pool = pool_options(settings)
+        .min_connections(settings.db_min_connections.max(1))
+        .idle_timeout(None)
+        .max_lifetime(None)
+        .connect(MEMORY_DATABASE_URL)
+        .await
+        .map_err(|error| format!("Failed to open the in-memory database: {}", error))?;
+
+    SQLITE_MIGRATOR
+        .run(&pool)
+        .await
+        .map_err(|error| format!("Failed to migrate the in-memory database: {}", error))?;
+
+    let mut tx = pool.begin().await.map_err(|error| format!("Failed to start loading the word list: {}", error))?;
+    let words = seed_words(&mut tx).await?;
+    tx.commit().await.map_err(|error| format!("Failed to load the word list: {}", error))?;
+    info!("Opened an in-memory database with {} words, nothing is kept after the server stops", words);
+
+    Ok(pool)
+}
+
+// Retries until the database accepts a connection or `max_wait` runs out, then checks the
+// schema has been migrated. Gives up early, with an error, if shutdown starts first.
+pub async fn wait_for_database(
+    pool: &AnyPool,
+    database_url: &str,
+    max_wait: Duration,
+    status: &DbStatus,
+    shutdown: ShutdownSignal,
+) -> Result<(), String> {
+    connect_with_retry(database_url, max_wait, shutdown).await?;
+    check_schema(pool).await?;
+    status.mark_connected();
+
+    Ok(())
+}
+
+// Applies whatever embedded migrations the database hasn't seen yet, logging each one
+pub async fn run_migrations(
+    pool: &AnyPool,
+    database_url: &str,
+    legacy_timezone: &str,
+    max_wait: Duration,
+    shutdown: ShutdownSignal,
+) -> Result<(), String> {
+    connect_with_retry(database_url, max_wait, shutdown).await?;
+
+    // Detached so the lifted statement timeout doesn't follow the connection back into the pool
+    let mut connection = pool
+        .acquire()
+        .await
+        .map_err(|error| format!("Failed to connect for migrations: {}", error))?
+        .detach();
+    if connection.kind() == AnyKind::Postgres {
+        connection
+            .execute("SET statement_timeout = 0")
+            .await
+            .map_err(|error| format!("Failed to lift the statement timeout for migrations: {}", error))?;
+        // Read by the migration converting naive timestamps to timestamptz
+        sqlx::query("SELECT set_config('wordle.legacy_timezone', $1, false)")
+            .bind(legacy_timezone)
+            .execute(&mut connection)
+            .await
+            .map_err(|error| format!("Failed to set the legacy timezone for migrations: {}", error))?;
+    }
+    let migrator = migrator(connection.kind());
+
+    let applied = applied_migrations(&mut connection)
+        .await
+        .map_err(|error| format!("Failed to read applied migrations: {}", error))?;
+
+    migrator
+        .run(&mut connection)
+        .await
+        .map_err(|error| format!("Failed to run migrations: {}", error))?;
+    let _ = connection.close().await;
+
+    let mut count = 0;
+    for migration in migrator.iter() {
+        if migration.migration_type.is_down_migration() || applied.contains(&migration.version) {
+            continue;
+        }
+        info!("Applied migration {} {}", migration.version, migration.description);
+        count += 1;
+    }
+
+    if count == 0 {
+        info!("Database schema is up to date");
+    }
+
+    Ok(())
+}
+
+async fn applied_migrations(connection: &mut AnyConnection) -> Result<Vec<i64>, MigrateError> {
+    connection.ensure_migrations_table().await?;
+
+    Ok(connection
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect())
+}
+
+async fn connect_with_retry(database_url: &str, max_wait: Duration, mut shutdown: ShutdownSignal) -> Result<(), String> {
+    let started = Instant::now();
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        let error = match timeout(ATTEMPT_TIMEOUT, AnyConnection::connect(database_url)).await {
+            Ok(Ok(connection)) => {
+                let _ = connection.close().await;
+                break;
+            }
+            Ok(Err(error)) => error.to_string(),
+            Err(_) => format!("timed out after {}s", ATTEMPT_TIMEOUT.as_secs()),
+        };
+
+        // Full backoff scaled down by up to half so restarted replicas don't retry in step
+        let delay = backoff.mul_f64(rand::thread_rng().gen_range(0.5..=1.0));
+        if started.elapsed() + delay > max_wait {
+            return Err(format!(
+                "Could not reach the database after {} attempts over {}s: {}",
+                attempt,
+                started.elapsed().as_secs(),
+                error
+            ));
+        }
+
+        warn!("Database not reachable (attempt {}): {}, retrying in {}ms", attempt, error, delay.as_millis());
+
+        tokio::select! {
+            _ = sleep(delay) => {}
+            _ = wait_for_shutdown(&mut shutdown) => {
+                return Err("Shutdown requested before the database was reachable".to_string());
+            }
+        }
+
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+        attempt += 1;
+    }
+
+    info!("Connected to the database after {} attempt(s)", attempt);
+    Ok(())
+}
+
+async fn check_schema(pool: &AnyPool) -> Result<(), String> {
+    let query = match pool.any_kind() {
+        AnyKind::Postgres => "SELECT table_name::text FROM information_schema.tables WHERE table_schema = current_schema()",
+        AnyKind::Sqlite => "SELECT name FROM sqlite_master WHERE type = 'table'",
+    };
+
+    let tables: Vec<String> = sqlx::query_scalar(query)
+        .fetch_all(pool)
+        .await
+        .map_err(|error| format!("Failed to inspect the database schema: {}", error))?;
+
+    let missing: Vec<&str> = EXPECTED_TABLES
+        .iter()
+        .copied()
+        .filter(|expected| !tables.iter().any(|table| table == expected))
+        .collect();
+
+    if !missing.is_empty() {
+        return Err(format!(
+            "Database schema is missing tables: {}. Start with RUN_MIGRATIONS=true or run the binary with --migrate-only first",
+            missing.join(", ")
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use crate::utils::client_ip_utils::ClientIp;
+use actix_web::HttpRequest;
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+
+const MAX_USER_AGENT_LEN: usize = 512;
+const UNKNOWN_DEVICE: &str = "Unknown device";
+
+// Where a login or refresh came from, as stored on the session row
+pub struct ClientInfo {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub device_label: String,
+    pub fingerprint: String,
+}
+
+impl ClientInfo {
+    pub fn from_request(req: &HttpRequest) -> Self {
+        let user_agent = req
+            .headers()
+            .get("User-Agent")
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| value.chars().take(MAX_USER_AGENT_LEN).collect::<String>());
+
+        let ip = ClientIp::of(req).map(|ClientIp(ip)| ip);
+        let device_label = device_label(user_agent.as_deref());
+
+        ClientInfo {
+            fingerprint: device_fingerprint(ip, &device_label),
+            ip_address: ip.map(|ip| ip.to_string()),
+            device_label,
+            user_agent,
+        }
+    }
+}
+
+// Reduces a User-Agent to something like "Firefox on Windows". Order matters: Edge and
+// Opera also claim to be Chrome, Chrome claims to be Safari, Android claims to be Linux.
+pub fn device_label(user_agent: Option<&str>) -> String {
+    let user_agent = match user_agent {
+        Some(user_agent) if !user_agent.is_empty() => user_agent,
+        _ => return UNKNOWN_DEVICE.to_string(),
+    };
+
+    let browser = [
+        ("Edg/", "Edge"),
+        ("OPR/", "Opera"),
+        ("Firefox/", "Firefox"),
+        ("Chrome/", "Chrome"),
+        ("Safari/", "Safari"),
+        ("curl/", "curl"),
+    ]
+    .iter()
+    .find(|(needle, _)| user_agent.contains(needle))
+    .map(|(_, name)| *name);
+
+    let os = [
+        ("Windows", "Windows"),
+        ("Android", "Android"),
+        ("iPhone", "iOS"),
+        ("iPad", "iOS"),
+        ("Mac OS X", "macOS"),
+        ("CrOS", "ChromeOS"),
+        ("Linux", "Linux"),
+    ]
+    .iter()
+    .find(|(needle, _)| user_agent.contains(needle))
+    .map(|(_, name)| *name);
+
+    match (browser, os) {
+        (Some(browser), Some(os)) => format!("{} on {}", browser, os),
+        (Some(browser), None) => browser.to_string(),
+        (None, Some(os)) => format!("Unknown browser on {}", os),
+        (None, None) => UNKNOWN_DEVICE.to_string(),
+    }
+}
+
+// Identifies "the same device" loosely: the network prefix (/24 for IPv4, /48 for IPv6)
+// plus the coarse device label, hashed so raw addresses aren't compared or indexed
+pub fn device_fingerprint(ip: Option<IpAddr>, device_label: &str) -> String {
+    let prefix = match ip {
+        Some(IpAddr::V4(ip)) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{}.{}.{}", a, b, c)
+        }
+        Some(IpAddr::V6(ip)) => {
+            let segments = ip.segments();
+            format!("{:x}:{:x}:{:x}", segments[0], segments[1], segments[2])
+        }
+        None => "unknown".to_string(),
+    };
+
+    Sha256::digest(format!("{}|{}", prefix, device_label).as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
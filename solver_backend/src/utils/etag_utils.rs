@@ -0,0 +1,56 @@
+use actix_web::http::header::{CacheControl, CacheDirective, EntityTag, ETag, Header, IfNoneMatch, TryIntoHeaderValue};
+use actix_web::{HttpRequest, HttpResponse};
+use std::future::Future;
+use crate::errors::AppError;
+
+// Strong tag for `resource` as of `version`, e.g. "words-v42"
+pub fn versioned_tag(resource: &str, version: i64) -> EntityTag {
+    EntityTag::new_strong(format!("{}-v{}", resource, version))
+}
+
+// Answers 304 when the client already holds `tag`, otherwise builds the response. Either
+// way it carries the tag and `cache_control`, so a cacheable endpoint only has to work
+// out its tag and wrap its body:
+//
+//     conditional(&req, tag, private_cache(60), || async { Ok(HttpResponse::Ok().json(body)) }).await
+pub async fn conditional<F, Fut>(
+    req: &HttpRequest,
+    tag: EntityTag,
+    cache_control: CacheControl,
+    build: F,
+) -> Result<HttpResponse, AppError>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<HttpResponse, AppError>>,
+{
+    if matches_if_none_match(req, &tag) {
+        return Ok(HttpResponse::NotModified()
+            .insert_header(ETag(tag))
+            .insert_header(cache_control)
+            .finish());
+    }
+
+    let mut response = build().await?;
+    // Errors aren't representations of the resource and mustn't be cached as one
+    if response.status().is_success() {
+        let headers = response.headers_mut();
+        headers.insert(ETag::name(), ETag(tag).try_into_value().expect("entity tags are valid header values"));
+        headers.insert(CacheControl::name(), cache_control.try_into_value().expect("cache directives are valid header values"));
+    }
+
+    Ok(response)
+}
+
+// Cacheable by the client only, revalidated with the ETag once `max_age_seconds` is up
+pub fn private_cache(max_age_seconds: u32) -> CacheControl {
+    CacheControl(vec![CacheDirective::Private, CacheDirective::MaxAge(max_age_seconds)])
+}
+
+// If-None-Match compares weakly (RFC 9110 13.1.2), and an unparseable header is ignored
+fn matches_if_none_match(req: &HttpRequest, tag: &EntityTag) -> bool {
+    match IfNoneMatch::parse(req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|candidate| candidate.weak_eq(tag)),
+        Err(_) => false,
+    }
+}
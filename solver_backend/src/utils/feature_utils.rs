@@ -0,0 +1,132 @@
+use crate::errors::{AppError, ErrorCode, ErrorInfo};
+use crate::models::admin_models::FeatureFlag;
+use crate::repositories::features;
+use crate::utils::shutdown_utils::{wait_for_shutdown, ShutdownSignal};
+use actix_web::rt::{self, task::JoinHandle};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::AnyPool;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+pub const REGISTRATION: &str = "registration";
+// Queuing solver benchmarks, each plays hundreds of games on the job worker
+pub const BENCHMARKS: &str = "benchmarks";
+pub const DATA_EXPORT: &str = "data_export";
+// Handing out guest tokens, tokens already out keep working until they expire
+pub const GUEST_ACCESS: &str = "guest_access";
+// Not subsystems but the maintenance mode: on refuses writes, lockdown refuses reads too
+pub const MAINTENANCE: &str = "maintenance";
+pub const LOCKDOWN: &str = "maintenance_lockdown";
+
+pub const FEATURES: &[&str] = &[REGISTRATION, BENCHMARKS, DATA_EXPORT, GUEST_ACCESS, MAINTENANCE, LOCKDOWN];
+// Flags that start out off unless their FEATURE_* variable says otherwise
+pub const OFF_BY_DEFAULT: &[&str] = &[MAINTENANCE, LOCKDOWN];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceLevel {
+    Off,
+    // Reads are served, writes refused
+    ReadOnly,
+    // Only health checks, admin routes and logging in are served
+    Lockdown,
+}
+
+// Subsystems that can be switched off without a redeploy. The set of flags is fixed, so
+// checking one is an atomic load.
+pub struct FeatureFlags {
+    flags: Vec<(&'static str, AtomicBool)>,
+}
+
+impl FeatureFlags {
+    pub fn new(defaults: &[(&'static str, bool)]) -> Self {
+        FeatureFlags {
+            flags: defaults.iter().map(|(name, enabled)| (*name, AtomicBool::new(*enabled))).collect(),
+        }
+    }
+
+    fn flag(&self, name: &str) -> Option<&AtomicBool> {
+        self.flags.iter().find(|(flag, _)| *flag == name).map(|(_, enabled)| enabled)
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.flag(name).is_some_and(|enabled| enabled.load(Ordering::Relaxed))
+    }
+
+    // Lockdown whether or not maintenance is on as well
+    pub fn maintenance_level(&self) -> MaintenanceLevel {
+        if self.is_enabled(LOCKDOWN) {
+            MaintenanceLevel::Lockdown
+        } else if self.is_enabled(MAINTENANCE) {
+            MaintenanceLevel::ReadOnly
+        } else {
+            MaintenanceLevel::Off
+        }
+    }
+
+    // For handlers to bail out with before doing anything
+    pub fn require(&self, name: &'static str) -> Result<(), AppError> {
+        if self.is_enabled(name) {
+            return Ok(());
+        }
+
+        let info = ErrorInfo::new(ErrorCode::FeatureDisabled, format!("{} is switched off for now", name)).with_details(json!({ "feature": name }));
+        Err(AppError::Unavailable(info))
+    }
+
+    // False for a name that isn't a flag
+    pub fn set(&self, name: &str, enabled: bool) -> bool {
+        match self.flag(name) {
+            Some(flag) => {
+                flag.store(enabled, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<FeatureFlag> {
+        self.flags
+            .iter()
+            .map(|(name, enabled)| FeatureFlag { name: name.to_string(), enabled: enabled.load(Ordering::Relaxed) })
+            .collect()
+    }
+
+    // Applies the values admins stored over the defaults. Rows for flags that have since
+    // been removed are ignored.
+    pub async fn load(&self, pool: &AnyPool) -> Result<(), sqlx::Error> {
+        for (name, enabled) in features::stored_flags(pool).await? {
+            self.set(&name, enabled);
+        }
+        Ok(())
+    }
+}
+
+// Reads the stored flags once the database is up, then every `interval` so a toggle made
+// through another replica reaches this one too. Runs until shutdown.
+pub fn spawn_flag_refresher(flags: Arc<FeatureFlags>, pool: AnyPool, interval: Option<Duration>, mut shutdown: ShutdownSignal) -> JoinHandle<()> {
+    rt::spawn(async move {
+        // The first tick completes straight away, the first load doesn't wait for it
+        let mut ticks = rt::time::interval(interval.unwrap_or(Duration::from_secs(60)));
+        ticks.tick().await;
+
+        loop {
+            if let Err(error) = flags.load(&pool).await {
+                error!("Failed to read stored feature flags, keeping the current values: {}", error);
+            }
+            if interval.is_none() {
+                break;
+            }
+
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = wait_for_shutdown(&mut shutdown) => break,
+            }
+        }
+
+        info!("Feature flag refresher stopped");
+    })
+}
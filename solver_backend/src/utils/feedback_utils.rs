@@ -0,0 +1,77 @@
+// Feedback as responses show it. Guesses are stored with their "gyx" pattern, and every
+// response that returns them fills in the squares here so the meaning of each mark is
+// spelled out the same way everywhere.
+use serde::{Deserialize, Serialize};
+use sqlx::{Any, Executor};
+use utoipa::{IntoParams, ToSchema};
+use crate::models::game_models::{SessionGuess, SessionGuessRecord};
+use crate::repositories::users;
+use crate::solver::{parse_feedback, Mark};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    #[default]
+    Standard,
+    // Orange and blue instead of green and yellow
+    HighContrast,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PaletteQuery {
+    // Left out to follow the user's colorblind preference, standard for anonymous callers
+    pub palette: Option<Palette>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Square {
+    #[schema(example = "c")]
+    pub letter: String,
+    pub mark: Mark,
+    // The colour to draw the square in, only given with the high_contrast palette
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "orange")]
+    pub hint: Option<String>,
+}
+
+fn hint(mark: Mark, palette: Palette) -> Option<&'static str> {
+    match palette {
+        Palette::Standard => None,
+        Palette::HighContrast => Some(match mark {
+            Mark::Green => "orange",
+            Mark::Yellow => "blue",
+            Mark::Gray => "gray",
+        }),
+    }
+}
+
+// One square per letter. Patterns come from stored guesses, which were checked when they
+// were made, so an unreadable one gives no squares rather than an error.
+pub fn squares(guess: &str, pattern: &str, palette: Palette) -> Vec<Square> {
+    let marks = parse_feedback(pattern).unwrap_or_default();
+    guess
+        .chars()
+        .zip(marks)
+        .map(|(letter, mark)| Square { letter: letter.to_string(), mark, hint: hint(mark, palette).map(str::to_string) })
+        .collect()
+}
+
+pub fn painted(guesses: Vec<SessionGuessRecord>, palette: Palette) -> Vec<SessionGuess> {
+    guesses
+        .into_iter()
+        .map(|guess| SessionGuess { squares: squares(&guess.guess, &guess.feedback, palette), guess: guess.guess, feedback: guess.feedback, created_at: guess.created_at })
+        .collect()
+}
+
+// The palette asked for, otherwise high_contrast for a user with the colorblind preference
+pub async fn palette_for<'e>(db: impl Executor<'e, Database = Any>, user_id: Option<i32>, requested: Option<Palette>) -> Result<Palette, sqlx::Error> {
+    if let Some(palette) = requested {
+        return Ok(palette);
+    }
+    let Some(user_id) = user_id else {
+        return Ok(Palette::Standard);
+    };
+    let colorblind = users::find_preferences(db, user_id).await?.is_some_and(|preferences| preferences.colorblind);
+    Ok(if colorblind { Palette::HighContrast } else { Palette::Standard })
+}
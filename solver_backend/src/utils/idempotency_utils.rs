@@ -0,0 +1,159 @@
+use actix_web::body::{to_bytes, MessageBody};
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
+use chrono::{DateTime, Duration, Utc};
+use tracing::error;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::{AnyPool, FromRow};
+use crate::errors::{AppError, ErrorCode};
+
+pub const IDEMPOTENCY_HEADER: &str = "Idempotency-Key";
+
+pub enum IdempotencyClaim {
+    // First time this key was seen, the caller should handle the request and store the outcome
+    Claimed,
+    // Respond with this instead of handling the request again
+    Respond(HttpResponse),
+}
+
+// Fingerprints a payload so a reused key with a different body can be told apart. The key
+// is mixed in so the stored value can't be matched against the same payload elsewhere.
+pub fn request_hash<T: Serialize>(key: &str, payload: &T) -> String {
+    let body = serde_json::to_string(payload).unwrap_or_default();
+
+    Sha256::digest(format!("{}|{}", key, body).as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// A claimed key, the response columns stay empty until the first request finishes
+#[derive(FromRow)]
+struct StoredKey {
+    request_hash: String,
+    response_status: Option<i32>,
+    response_content_type: Option<String>,
+    response_body: Option<String>,
+}
+
+// `ttl` is how long a stored response is replayed for (IDEMPOTENCY_TTL_HOURS)
+pub async fn claim_key(pool: &AnyPool, key: &str, request_hash: &str, ttl: Duration, now: DateTime<Utc>) -> Result<IdempotencyClaim, sqlx::Error> {
+
+    sqlx::query("DELETE FROM idempotency_keys WHERE key = $1 AND created_at < $2")
+        .bind(key)
+        .bind(now - ttl)
+        .execute(pool)
+        .await?;
+
+    let claimed = sqlx::query(
+            r#"
+            INSERT INTO idempotency_keys (key, request_hash, created_at)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (key) DO NOTHING
+            RETURNING key
+            "#)
+        .bind(key)
+        .bind(request_hash)
+        .bind(now)
+        .fetch_optional(pool)
+        .await?;
+
+    if claimed.is_some() {
+        return Ok(IdempotencyClaim::Claimed);
+    }
+
+    let stored: Option<StoredKey> = sqlx::query_as(
+            r#"
+            SELECT request_hash, response_status, response_content_type, response_body
+            FROM idempotency_keys WHERE key = $1
+            "#)
+        .bind(key)
+        .fetch_optional(pool)
+        .await?;
+
+    let StoredKey { request_hash: stored_hash, response_status: status, response_content_type: content_type, response_body: body } = match stored {
+        Some(stored) => stored,
+        // Released between our insert and select, let the request through
+        None => return Ok(IdempotencyClaim::Claimed),
+    };
+
+    if stored_hash != request_hash {
+        let error = AppError::validation(ErrorCode::IdempotencyKeyReused, "This Idempotency-Key was already used with a different request");
+        return Ok(IdempotencyClaim::Respond(HttpResponse::from_error(error)));
+    }
+
+    let status = match status.and_then(|status| StatusCode::from_u16(status as u16).ok()) {
+        Some(status) => status,
+        None => {
+            let error = AppError::conflict(ErrorCode::IdempotencyRequestInProgress, "A request with this Idempotency-Key is still being processed");
+            return Ok(IdempotencyClaim::Respond(HttpResponse::from_error(error)));
+        }
+    };
+
+
+    let mut response = HttpResponse::build(status);
+    if let Some(content_type) = content_type {
+        response.content_type(content_type);
+    }
+
+    Ok(IdempotencyClaim::Respond(response.body(body.unwrap_or_default())))
+}
+
+// Records the outcome of a claimed request and hands back an equivalent response. Server
+// errors aren't stored, the key is released instead so a retry runs the request again.
+pub async fn store_response(pool: &AnyPool, key: &str, response: HttpResponse) -> HttpResponse {
+    let status = response.status();
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let body = match to_bytes(response.into_body()).await {
+        Ok(body) => body,
+        Err(_) => {
+            release_key(pool, key).await;
+            return AppError::internal("Failed to read idempotent response body").error_response();
+        }
+    };
+
+    if status.is_server_error() {
+        release_key(pool, key).await;
+    } else {
+        let stored = sqlx::query(
+                r#"
+                UPDATE idempotency_keys
+                SET response_status = $1, response_content_type = $2, response_body = $3
+                WHERE key = $4
+                "#)
+            .bind(status.as_u16() as i32)
+            .bind(&content_type)
+            .bind(String::from_utf8_lossy(&body).to_string())
+            .bind(key)
+            .execute(pool)
+            .await;
+
+        if let Err(error) = stored {
+            error!("Failed to store idempotent response: {}", error);
+        }
+    }
+
+    let mut response = HttpResponse::build(status);
+    if let Some(content_type) = content_type {
+        response.content_type(content_type);
+    }
+
+    response.body(body.try_into_bytes().unwrap_or_default())
+}
+
+pub async fn release_key(pool: &AnyPool, key: &str) {
+    let released = sqlx::query("DELETE FROM idempotency_keys WHERE key = $1")
+        .bind(key)
+        .execute(pool)
+        .await;
+
+    if let Err(error) = released {
+        error!("Failed to release idempotency key: {}", error);
+    }
+}
@@ -0,0 +1,164 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::AnyPool;
+use std::collections::HashMap;
+use tracing::error;
+use crate::models::users_models::{ImportOutcome, ImportProblem};
+use crate::repositories::users;
+use crate::utils::mail_utils::normalize_email;
+
+// Accounts written per transaction. A batch that fails is rolled back and reported on its
+// own, the batches before it stay imported.
+pub const IMPORT_BATCH_SIZE: usize = 200;
+const COLUMNS: [&str; 4] = ["username", "email", "password_hash", "created_at"];
+// The widths of the users columns
+const MAX_USERNAME_LENGTH: usize = 50;
+const MAX_EMAIL_LENGTH: usize = 100;
+
+#[derive(Deserialize)]
+struct ImportRecord {
+    username: String,
+    email: String,
+    // Empty when the old tool has no hash to hand over
+    password_hash: Option<String>,
+    created_at: String,
+}
+
+// A row that checked out, with the line of the file it came from
+pub struct ImportRow {
+    pub line: u64,
+    pub username: String,
+    pub email: String,
+    pub password_hash: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Reads an import file: a header naming the columns, in any order, then one account per
+// line. Rows that can't be read or don't validate are failures, and a later row with the
+// username or email of an earlier one is skipped. Err when the header is unusable, in
+// which case nothing can be imported.
+pub fn parse_user_csv(body: &[u8], lowercase_local: bool) -> Result<(Vec<ImportRow>, Vec<ImportProblem>), String> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(body);
+    let headers = reader.headers().map_err(|error| format!("The header can't be read: {}", error))?.clone();
+    for column in COLUMNS {
+        if column != "password_hash" && !headers.iter().any(|header| header == column) {
+            return Err(format!("The header has no {} column, it needs {}", column, COLUMNS.join(", ")));
+        }
+    }
+
+    let mut rows = Vec::new();
+    let mut problems = Vec::new();
+    let mut usernames: HashMap<String, u64> = HashMap::new();
+    let mut emails: HashMap<String, u64> = HashMap::new();
+    let mut record = csv::StringRecord::new();
+    loop {
+        // Lines are those the row starts on, the header being line 1
+        let row = match reader.read_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {
+                let line = record.position().map_or(0, |position| position.line());
+                record
+                    .deserialize::<ImportRecord>(Some(&headers))
+                    .map_err(|error| error.to_string())
+                    .and_then(|record| validate(record, line, lowercase_local))
+                    .map_err(|reason| (line, reason))
+            }
+            Err(error) => Err((error.position().map_or(0, |position| position.line()), error.to_string())),
+        };
+        let row = match row {
+            Ok(row) => row,
+            Err((line, reason)) => {
+                problems.push(ImportProblem { row: line, outcome: ImportOutcome::Failed, reason });
+                continue;
+            }
+        };
+        let problem = |reason| ImportProblem { row: row.line, outcome: ImportOutcome::Skipped, reason };
+
+        // Emails are unique whatever their case
+        if let Some(first) = usernames.get(&row.username) {
+            problems.push(problem(format!("Username {} is also on row {}", row.username, first)));
+            continue;
+        }
+        if let Some(first) = emails.get(&row.email.to_lowercase()) {
+            problems.push(problem(format!("Email {} is also on row {}", row.email, first)));
+            continue;
+        }
+        usernames.insert(row.username.clone(), row.line);
+        emails.insert(row.email.to_lowercase(), row.line);
+        rows.push(row);
+    }
+
+    Ok((rows, problems))
+}
+
+fn validate(record: ImportRecord, line: u64, lowercase_local: bool) -> Result<ImportRow, String> {
+    if record.username.is_empty() || record.username.chars().count() > MAX_USERNAME_LENGTH {
+        return Err(format!("username must have 1 to {} characters", MAX_USERNAME_LENGTH));
+    }
+    let email = normalize_email(&record.email, lowercase_local);
+    let valid_email = email.split_once('@').is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.'));
+    if !valid_email || email.chars().count() > MAX_EMAIL_LENGTH {
+        return Err(format!("{:?} isn't an email address of at most {} characters", record.email, MAX_EMAIL_LENGTH));
+    }
+    if let Some(hash) = &record.password_hash {
+        if hash.parse::<bcrypt::HashParts>().is_err() {
+            return Err("password_hash isn't a bcrypt hash".to_string());
+        }
+    }
+    let created_at = DateTime::parse_from_rfc3339(&record.created_at)
+        .map_err(|_| format!("created_at {:?} isn't an RFC 3339 timestamp", record.created_at))?
+        .with_timezone(&Utc);
+
+    Ok(ImportRow { line, username: record.username, email, password_hash: record.password_hash, created_at })
+}
+
+// Inserts the rows a batch per transaction. Returns how many were inserted, and the rows
+// skipped because their username or email is already taken or failed with their batch.
+pub async fn import_rows(pool: &AnyPool, rows: &[ImportRow], history_size: i64, now: DateTime<Utc>) -> (usize, Vec<ImportProblem>) {
+    let mut inserted = 0;
+    let mut problems = Vec::new();
+
+    for batch in rows.chunks(IMPORT_BATCH_SIZE) {
+        match import_batch(pool, batch, history_size, now).await {
+            Ok((count, skipped)) => {
+                inserted += count;
+                problems.extend(skipped);
+            }
+            Err(error) => {
+                error!("Failed to import users from row {}: {}", batch[0].line, error);
+                problems.extend(batch.iter().map(|row| ImportProblem {
+                    row: row.line,
+                    outcome: ImportOutcome::Failed,
+                    reason: "The batch of rows this one was in couldn't be saved".to_string(),
+                }));
+            }
+        }
+    }
+
+    (inserted, problems)
+}
+
+async fn import_batch(pool: &AnyPool, batch: &[ImportRow], history_size: i64, now: DateTime<Utc>) -> Result<(usize, Vec<ImportProblem>), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    let mut inserted = 0;
+    let mut skipped = Vec::new();
+
+    for row in batch {
+        match users::import_user(&mut tx, &row.username, &row.email, row.password_hash.as_deref(), row.created_at, now).await? {
+            Some(user_id) => {
+                if let (Some(hash), true) = (&row.password_hash, history_size > 0) {
+                    users::record_password(&mut tx, user_id, hash, history_size).await?;
+                }
+                inserted += 1;
+            }
+            None => skipped.push(ImportProblem {
+                row: row.line,
+                outcome: ImportOutcome::Skipped,
+                reason: "An account with this username or email already exists".to_string(),
+            }),
+        }
+    }
+
+    tx.commit().await?;
+    Ok((inserted, skipped))
+}
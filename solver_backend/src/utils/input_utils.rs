@@ -0,0 +1,47 @@
+use crate::errors::{AppError, ErrorCode, ErrorInfo};
+use crate::models::game_models::RequestLetters;
+use serde_json::json;
+
+// `exact` is a LIKE pattern, these stand for unknown letters
+const WILDCARDS: &[char] = &['_', '%'];
+// What UIs put in an empty slot of `exact`, read as `_`
+const EMPTY_SLOTS: &[char] = &[' ', '.', '-'];
+
+// Cleans up letters pasted in by hand: surrounding whitespace is dropped and case folded.
+// Anything left that isn't a to z is rejected rather than guessed at, since a zero width
+// space or a Cyrillic "а" would otherwise just match nothing.
+pub fn sanitize_letters(field: &'static str, value: &str) -> Result<String, AppError> {
+    sanitize(field, value, &[])
+}
+
+// Like `sanitize_letters`, also keeping the wildcards of an `exact` pattern. Empty slots
+// become `_`, though spaces at either end are trimmed first like in the other fields.
+pub fn sanitize_pattern(field: &'static str, value: &str) -> Result<String, AppError> {
+    let pattern: String = value.trim().chars().map(|letter| if EMPTY_SLOTS.contains(&letter) { '_' } else { letter }).collect();
+    sanitize(field, &pattern, WILDCARDS)
+}
+
+pub fn sanitize_request(letters: RequestLetters) -> Result<RequestLetters, AppError> {
+    Ok(RequestLetters {
+        correct: sanitize_letters("correct", &letters.correct)?,
+        incorrect: sanitize_letters("incorrect", &letters.incorrect)?,
+        exact: sanitize_pattern("exact", &letters.exact)?,
+        sort_order: letters.sort_order,
+    })
+}
+
+fn sanitize(field: &'static str, value: &str, allowed: &[char]) -> Result<String, AppError> {
+    let value = value.trim();
+
+    if let Some((position, character)) = value.chars().enumerate().find(|(_, character)| !character.is_ascii_alphabetic() && !allowed.contains(character)) {
+        let codepoint = format!("U+{:04X}", character as u32);
+        let info = ErrorInfo::new(
+            ErrorCode::InvalidLetter,
+            format!("{} has '{}' ({}) at position {}, only the letters a to z are allowed", field, character.escape_debug(), codepoint, position + 1),
+        )
+        .with_details(json!({ "field": field, "character": character.to_string(), "codepoint": codepoint, "position": position + 1 }));
+        return Err(AppError::Validation(info));
+    }
+
+    Ok(value.to_ascii_lowercase())
+}
@@ -0,0 +1,156 @@
+use actix_web::rt;
+use actix_web::rt::task::JoinHandle;
+use async_trait::async_trait;
+use chrono::Duration;
+use serde_json::{json, Value};
+use sqlx::AnyPool;
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{error, info, warn};
+use crate::config::WebhookSettings;
+use crate::models::jobs_models::Job;
+use crate::repositories::jobs;
+use crate::repositories::words::WordRepository;
+use crate::solver::play;
+use crate::utils::clock_utils::Clock;
+use crate::utils::mail_utils::{send_queued, Mailer, EMAIL_DELIVERY};
+use crate::utils::shutdown_utils::{is_shutting_down, wait_for_shutdown, ShutdownSignal};
+use crate::utils::stats_utils::{recompute_stats, STATS_BATCH_SIZE, STATS_RECOMPUTE};
+use crate::utils::webhook_utils::{deliver, WEBHOOK_DELIVERY};
+
+pub const SOLVER_BENCHMARK: &str = "solver_benchmark";
+pub const DEFAULT_BENCHMARK_SAMPLE: usize = 50;
+pub const MAX_BENCHMARK_SAMPLE: usize = 500;
+
+// A running job whose worker hasn't finished it by then is assumed to have died with it
+const JOB_LEASE_MINUTES: i64 = 10;
+const FIRST_RETRY_SECONDS: i64 = 5;
+const MAX_RETRY_SECONDS: i64 = 600;
+// The game counts as solved within six, a few more show how far off the misses were
+const BENCHMARK_MAX_GUESSES: usize = 10;
+const SOLVED_WITHIN: usize = 6;
+
+// Does the work of a claimed job. The error becomes the job's `error` and is retried
+// until the job runs out of attempts.
+#[async_trait]
+pub trait JobRunner: Send + Sync {
+    async fn run(&self, job: &Job) -> Result<Value, String>;
+}
+
+// The job types the server enqueues itself
+pub struct BuiltinJobs {
+    pub db: AnyPool,
+    pub words: Arc<dyn WordRepository>,
+    pub webhooks: WebhookSettings,
+    pub mailer: Arc<dyn Mailer>,
+    pub clock: Arc<dyn Clock>,
+}
+
+#[async_trait]
+impl JobRunner for BuiltinJobs {
+    async fn run(&self, job: &Job) -> Result<Value, String> {
+        let payload: Value = serde_json::from_str(&job.payload).map_err(|error| format!("invalid payload: {}", error))?;
+
+        match job.job_type.as_str() {
+            SOLVER_BENCHMARK => {
+                let sample = payload["sample"].as_u64().map_or(DEFAULT_BENCHMARK_SAMPLE, |sample| sample as usize);
+                let words = self.words.all_words().await.map_err(|error| error.to_string())?;
+                let answers = self.words.answer_words().await.map_err(|error| error.to_string())?;
+                // Playing hundreds of games is CPU bound, kept off the async workers
+                rt::task::spawn_blocking(move || benchmark(&words, &answers, sample))
+                    .await
+                    .map_err(|error| error.to_string())
+            }
+            WEBHOOK_DELIVERY => deliver(&self.db, &self.webhooks, job, self.clock.now()).await,
+            EMAIL_DELIVERY => send_queued(&self.db, self.mailer.as_ref(), job, self.clock.now()).await,
+            STATS_RECOMPUTE => recompute_stats(&self.db, STATS_BATCH_SIZE, self.clock.now()).await,
+            other => Err(format!("unknown job type {}", other)),
+        }
+    }
+}
+
+// Plays `sample` answers spread evenly over `answers`, guessing from all of `words`, and
+// summarizes how the solver did
+fn benchmark(words: &[String], answers: &[String], sample: usize) -> Value {
+    let started = Instant::now();
+    let words: Vec<&str> = words.iter().map(String::as_str).collect();
+    let step = (answers.len() / sample.max(1)).max(1);
+    let answers: Vec<&str> = answers.iter().map(String::as_str).step_by(step).take(sample).collect();
+
+    let mut guesses = Vec::new();
+    let mut unsolved = Vec::new();
+    for answer in &answers {
+        match play(answer, &words, BENCHMARK_MAX_GUESSES).filter(|turns| *turns <= SOLVED_WITHIN) {
+            Some(turns) => guesses.push(turns),
+            None => unsolved.push(*answer),
+        }
+    }
+
+    let average = if guesses.is_empty() { 0.0 } else { guesses.iter().sum::<usize>() as f64 / guesses.len() as f64 };
+    json!({
+        "games": answers.len(),
+        "solved": guesses.len(),
+        "average_guesses": average,
+        "max_guesses": guesses.iter().max(),
+        "unsolved": unsolved,
+        "elapsed_ms": started.elapsed().as_millis() as u64,
+    })
+}
+
+// Waits before attempt `attempts + 1`, doubling each time
+pub fn retry_delay(attempts: i32) -> Duration {
+    let doublings = attempts.clamp(1, 16) as u32 - 1;
+    Duration::seconds((FIRST_RETRY_SECONDS << doublings).min(MAX_RETRY_SECONDS))
+}
+
+// Claims, runs and records one job. Returns the id of the job that ran, None when no job
+// was due.
+pub async fn run_next_job(pool: &AnyPool, runner: &dyn JobRunner, clock: &dyn Clock) -> Result<Option<i32>, sqlx::Error> {
+    let now = clock.now();
+    let job = match jobs::claim(pool, pool.any_kind(), now, now - Duration::minutes(JOB_LEASE_MINUTES)).await? {
+        Some(job) => job,
+        None => return Ok(None),
+    };
+
+    match runner.run(&job).await {
+        Ok(result) => {
+            jobs::succeed(pool, job.id, &result, clock.now()).await?;
+            info!("Job {} ({}) succeeded on attempt {}", job.id, job.job_type, job.attempts);
+        }
+        Err(failure) => {
+            let now = clock.now();
+            let retry_at = (job.attempts < job.max_attempts).then(|| now + retry_delay(job.attempts));
+            jobs::fail(pool, job.id, &failure, retry_at, now).await?;
+            warn!("Job {} ({}) failed on attempt {} of {}: {}", job.id, job.job_type, job.attempts, job.max_attempts, failure);
+        }
+    }
+
+    Ok(Some(job.id))
+}
+
+// Runs due jobs one after another, checking again every `poll_interval` once none are left
+pub fn spawn_job_worker(pool: AnyPool, runner: Arc<dyn JobRunner>, clock: Arc<dyn Clock>, poll_interval: std::time::Duration, mut shutdown: ShutdownSignal) -> JoinHandle<()> {
+    rt::spawn(async move {
+        // A job already running is finished, nothing new is claimed once shutdown starts
+        while !is_shutting_down(&shutdown) {
+            let ran = match run_next_job(&pool, &*runner, &*clock).await {
+                Ok(ran) => ran.is_some(),
+                Err(error) => {
+                    error!("Job worker failed to reach the database: {}", error);
+                    false
+                }
+            };
+
+            if ran {
+                continue;
+            }
+
+            tokio::select! {
+                _ = rt::time::sleep(poll_interval) => {}
+                _ = wait_for_shutdown(&mut shutdown) => break,
+            }
+        }
+
+        info!("Job worker stopped");
+    })
+}
@@ -1,101 +1,241 @@
-use std::env;
-use jsonwebtoken::{encode, EncodingKey, Header, decode, DecodingKey, Validation, Algorithm};
-use dotenv::dotenv;
-use chrono::{Local, Utc, Duration};
-use crate::models::users_models::AccessClaims;
+use std::fs;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{encode, EncodingKey, Header, decode, decode_header, DecodingKey, Validation, Algorithm};
+use jsonwebtoken::jwk::{AlgorithmParameters, CommonParameters, Jwk, JwkSet, PublicKeyUse, RSAKeyParameters, RSAKeyType};
+use chrono::{DateTime, Duration, Utc};
+use rsa::{pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, traits::PublicKeyParts, RsaPrivateKey, RsaPublicKey};
+use tracing::{debug, error};
+use crate::config::JwtSettings;
+use crate::models::users_models::{AccessClaims, TokenType};
+
+// Key id assumed for tokens without a `kid` header and for a lone SECRET_KEY
+const DEFAULT_KID: &str = "default";
+
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    public_key: Option<RsaPublicKey>,
+}
+
+// The configured signing keys, loaded once at startup and kept in the AppState. Dropping
+// a key from the settings retires it, and tokens signed with it stop verifying.
+pub struct SigningKeys {
+    keys: Vec<SigningKey>,
+}
+
+impl SigningKeys {
+    // Fails on an RSA key that can't be read or isn't an RSA private key, rather than
+    // starting without it and turning away every token it signed
+    pub fn load(settings: &JwtSettings) -> Result<SigningKeys, String> {
+        let mut keys: Vec<SigningKey> = settings
+            .hmac_keys
+            .iter()
+            .map(|(kid, secret)| hmac_key(kid.clone(), secret))
+            .collect();
+
+        for (kid, path) in &settings.rsa_keys {
+            let key = rsa_key(kid.clone(), path).map_err(|error| format!("Couldn't load RSA key {} from {}: {}", kid, path, error))?;
+            keys.push(key);
+        }
 
+        Ok(SigningKeys { keys })
+    }
 
-pub fn generate_token(user_id: i32, expiration_duration: Duration) -> Result<String, Box<dyn std::error::Error>> {
-    dotenv().ok();
+    pub fn iter(&self) -> impl Iterator<Item = &SigningKey> {
+        self.keys.iter()
+    }
 
-    let secret_key = env::var("SECRET_KEY")?;
-    let issued_timestamp = Local::now().naive_local();
+    // Public halves of the RSA keys, for services that verify our tokens themselves
+    pub fn public_jwks(&self) -> JwkSet {
+        let keys = self
+            .keys
+            .iter()
+            .filter_map(|key| {
+                let public_key = key.public_key.as_ref()?;
+                Some(Jwk {
+                    common: CommonParameters {
+                        public_key_use: Some(PublicKeyUse::Signature),
+                        algorithm: Some(key.algorithm),
+                        key_id: Some(key.kid.clone()),
+                        ..Default::default()
+                    },
+                    algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                        key_type: RSAKeyType::RSA,
+                        n: URL_SAFE_NO_PAD.encode(public_key.n().to_bytes_be()),
+                        e: URL_SAFE_NO_PAD.encode(public_key.e().to_bytes_be()),
+                    }),
+                })
+            })
+            .collect();
+
+        JwkSet { keys }
+    }
+
+    // The newest key of the algorithm, the last one configured
+    fn signing_key(&self, algorithm: Algorithm) -> Option<&SigningKey> {
+        self.keys.iter().rev().find(|key| key.algorithm == algorithm)
+    }
 
-    let now = Utc::now();
-    let expiration = now + expiration_duration;
+    // Picks the verification key named by the token's `kid` header. Each key only verifies
+    // tokens of its own algorithm, so an HS256 token can't be checked against an RSA public
+    // key, and `alg: none` never gets past decode_header.
+    fn verification_key_for(&self, token: &str) -> Result<(&SigningKey, Validation), TokenRejection> {
+        let header = decode_header(token).map_err(|error| {
+            debug!("Rejected token: {}", error);
+            TokenRejection::Invalid
+        })?;
+        let kid = header.kid.unwrap_or_else(|| DEFAULT_KID.to_string());
+
+        let key = self.keys.iter().find(|key| key.kid == kid).ok_or_else(|| {
+            debug!("Rejected token: unknown key id {}", kid);
+            TokenRejection::UnknownKey
+        })?;
+
+        if header.alg != key.algorithm {
+            debug!("Rejected token: algorithm {:?} does not match key {}", header.alg, kid);
+            return Err(TokenRejection::Invalid);
+        }
+
+        Ok((key, Validation::new(key.algorithm)))
+    }
+}
+
+fn hmac_key(kid: String, secret: &str) -> SigningKey {
+    SigningKey {
+        kid,
+        algorithm: Algorithm::HS256,
+        encoding: EncodingKey::from_secret(secret.as_ref()),
+        decoding: DecodingKey::from_secret(secret.as_ref()),
+        public_key: None,
+    }
+}
+
+fn rsa_key(kid: String, path: &str) -> Result<SigningKey, Box<dyn std::error::Error>> {
+    let pem = fs::read_to_string(path)?;
+    let private_key = RsaPrivateKey::from_pkcs8_pem(&pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&pem))?;
+    let public_key = private_key.to_public_key();
+
+    Ok(SigningKey {
+        kid,
+        algorithm: Algorithm::RS256,
+        encoding: EncodingKey::from_rsa_pem(pem.as_bytes())?,
+        decoding: DecodingKey::from_rsa_raw_components(&public_key.n().to_bytes_be(), &public_key.e().to_bytes_be()),
+        public_key: Some(public_key),
+    })
+}
 
-    let claims = AccessClaims {
+// `now` comes from the state's clock, so tokens issued under a fake clock expire on its time
+pub fn generate_token(keys: &SigningKeys, user_id: i32, token_version: i32, typ: TokenType, expiration_duration: Duration, algorithm: Algorithm, now: DateTime<Utc>) -> Result<String, Box<dyn std::error::Error>> {
+    sign_token(keys, &base_claims(user_id, token_version, typ, expiration_duration, now), algorithm)
+}
+
+fn base_claims(user_id: i32, token_version: i32, typ: TokenType, expiration_duration: Duration, now: DateTime<Utc>) -> AccessClaims {
+    AccessClaims {
         user_id,
-        issued: issued_timestamp,
-        exp: expiration.timestamp() as usize,
-    };
+        token_version,
+        issued: now.naive_utc(),
+        exp: (now + expiration_duration).timestamp() as usize,
+        typ,
+        impersonator: None,
+        guest: None,
+    }
+}
+
+fn sign_token(keys: &SigningKeys, claims: &AccessClaims, algorithm: Algorithm) -> Result<String, Box<dyn std::error::Error>> {
+    let signing_key = keys.signing_key(algorithm).ok_or("no JWT signing key configured")?;
+
+    let mut header = Header::new(signing_key.algorithm);
+    header.kid = Some(signing_key.kid.clone());
 
     let token = encode(
-        &Header::new(Algorithm::HS256),
-        &claims,
-        &EncodingKey::from_secret(secret_key.as_ref()),
+        &header,
+        claims,
+        &signing_key.encoding,
     )?;
 
     Ok(token)
 }
 
-pub fn generate_access_token(user_id: i32) -> Result<String, Box<dyn std::error::Error>> {
-    let exp_duration = Duration::minutes(60);
+// Lets `impersonator` act as `user_id` until IMPERSONATION_TTL_MINUTES are up
+pub fn generate_impersonation_token(keys: &SigningKeys, user_id: i32, token_version: i32, impersonator: i32, settings: &JwtSettings, now: DateTime<Utc>) -> Result<String, Box<dyn std::error::Error>> {
+    let claims = AccessClaims { impersonator: Some(impersonator), ..base_claims(user_id, token_version, TokenType::Access, settings.impersonation_ttl, now) };
+    sign_token(keys, &claims, settings.algorithm).map_err(|error| {
+        error!("Failed to generate token: {}", error);
+        error
+    })
+}
+
+// For trying the solver without an account, until GUEST_TOKEN_TTL_MINUTES are up
+pub fn generate_guest_token(keys: &SigningKeys, guest_id: &str, settings: &JwtSettings, now: DateTime<Utc>) -> Result<String, Box<dyn std::error::Error>> {
+    let claims = AccessClaims { guest: Some(guest_id.to_string()), ..base_claims(0, 0, TokenType::Access, settings.guest_ttl, now) };
+    sign_token(keys, &claims, settings.algorithm).map_err(|error| {
+        error!("Failed to generate token: {}", error);
+        error
+    })
+}
+
+pub fn generate_access_token(keys: &SigningKeys, user_id: i32, token_version: i32, settings: &JwtSettings, now: DateTime<Utc>) -> Result<String, Box<dyn std::error::Error>> {
+    let exp_duration = settings.access_token_ttl;
 
-    let access_token = match generate_token(user_id, exp_duration){
+    let access_token = match generate_token(keys, user_id, token_version, TokenType::Access, exp_duration, settings.algorithm, now){
         Ok(token) => token,
         Err(error) => {
-            println!("erro: {}", error);
-            return Err(error.into());
+            error!("Failed to generate token: {}", error);
+            return Err(error);
         }
     };
 
     Ok(access_token)
 }
 
-pub fn generate_refresh_token(user_id: i32) -> Result<String, Box<dyn std::error::Error>> {
-    let exp_duration = Duration::days(7);
+pub fn generate_refresh_token(keys: &SigningKeys, user_id: i32, token_version: i32, settings: &JwtSettings, now: DateTime<Utc>) -> Result<String, Box<dyn std::error::Error>> {
+    let exp_duration = settings.refresh_token_ttl;
 
-    let refresh_token = match generate_token(user_id, exp_duration){
+    let refresh_token = match generate_token(keys, user_id, token_version, TokenType::Refresh, exp_duration, settings.algorithm, now){
         Ok(token) => token,
         Err(error) => {
-            println!("erro: {}", error);
-            return Err(error.into());
+            error!("Failed to generate token: {}", error);
+            return Err(error);
         }
     };
 
     Ok(refresh_token)
 }
 
-
-pub fn verify_token(token: &str) -> Result<bool, Box<dyn std::error::Error>> {
-    dotenv().ok();
-
-    let secret_key = env::var("SECRET_KEY")?;
-    let current_time = Utc::now().timestamp();
-
-    match decode::<AccessClaims>(
-        &token,
-        &DecodingKey::from_secret(secret_key.as_ref()),
-        &Validation::default(),
-    ) {
-        Ok(token) => {
-            let expiration_time = token.claims.exp;
-            if expiration_time > current_time as usize {
-                Ok(true) // Refresh token is valid and not expired
-            } else {
-                Ok(false) // Refresh token is expired
-            }
-        }
-        Err(error) => {
-            println!("{}", error);
-            Ok(false)
-        }, // Refresh token is invalid
-    }
+// Why a token wasn't accepted, so clients know whether refreshing could help
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenRejection {
+    // Malformed or badly signed
+    Invalid,
+    // Signed with a key id that isn't configured, e.g. one that has been retired
+    UnknownKey,
+    Expired,
 }
 
-pub fn decode_token_id(token: &str) -> i32 {
-    dotenv().ok();
+// Decodes and validates a token. Expiry is judged against `now` rather than the system
+// clock, with the library's usual leeway.
+pub fn decode_claims(keys: &SigningKeys, token: &str, now: DateTime<Utc>) -> Result<AccessClaims, TokenRejection> {
+    let (key, mut validation) = keys.verification_key_for(token)?;
 
-    let secret_key = env::var("SECRET_KEY").expect("Failed to get secret key");
+    validation.validate_exp = false;
+    let leeway = validation.leeway as i64;
 
-    let decoded = decode::<AccessClaims>(
-        &token,
-        &DecodingKey::from_secret(secret_key.as_ref()),
-        &Validation::default(),
+    let claims = decode::<AccessClaims>(
+        token,
+        &key.decoding,
+        &validation,
     )
-    .expect("Failed to decode token");
-
-    decoded.claims.user_id
+    .map_err(|error| {
+        debug!("Rejected token: {}", error);
+        TokenRejection::Invalid
+    })?
+    .claims;
+
+    if claims.exp as i64 + leeway < now.timestamp() {
+        return Err(TokenRejection::Expired);
+    }
+    Ok(claims)
 }
-
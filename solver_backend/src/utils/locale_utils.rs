@@ -0,0 +1,66 @@
+use crate::errors::ErrorCode;
+use actix_web::http::header::{AcceptLanguage, Header, Preference, Quality};
+use actix_web::HttpRequest;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+// What messages are written in where errors are raised, and what a client asking for
+// none of the languages below gets
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+// Translations of the error messages under locales/, compiled into the binary
+const CATALOGS: &[(&str, &str)] = &[("es", include_str!("../../locales/es.txt"))];
+
+static MESSAGES: OnceLock<HashMap<&'static str, HashMap<&'static str, &'static str>>> = OnceLock::new();
+
+// Every language error messages can be sent in, the default first
+pub fn supported_languages() -> impl Iterator<Item = &'static str> {
+    std::iter::once(DEFAULT_LANGUAGE).chain(CATALOGS.iter().map(|(language, _)| *language))
+}
+
+// The catalog's entries as written, without checking them against the error codes
+pub fn catalog(language: &str) -> Option<&'static HashMap<&'static str, &'static str>> {
+    MESSAGES
+        .get_or_init(|| CATALOGS.iter().map(|(language, source)| (*language, parse_catalog(source))).collect())
+        .get(language)
+}
+
+// The message for `code` in `language`. None for the default language, whose messages
+// are the ones written where the error was raised, and for codes the catalog is missing.
+pub fn translate(language: &str, code: ErrorCode) -> Option<&'static str> {
+    catalog(language)?.get(code.as_str()).copied()
+}
+
+// The language the client prefers most out of the supported ones, going by Accept-Language.
+// Region subtags are ignored, so es-MX gets Spanish.
+pub fn negotiate(req: &HttpRequest) -> &'static str {
+    let Ok(AcceptLanguage(mut preferences)) = AcceptLanguage::parse(req) else {
+        return DEFAULT_LANGUAGE;
+    };
+    preferences.retain(|preference| preference.quality > Quality::ZERO);
+    // Stable, so equally weighted languages keep the client's order
+    preferences.sort_by_key(|preference| std::cmp::Reverse(preference.quality));
+
+    for preference in preferences {
+        let tag = match preference.item {
+            Preference::Any => return DEFAULT_LANGUAGE,
+            Preference::Specific(tag) => tag,
+        };
+        if let Some(language) = supported_languages().find(|language| tag.primary_language().eq_ignore_ascii_case(language)) {
+            return language;
+        }
+    }
+
+    DEFAULT_LANGUAGE
+}
+
+// `code = message` lines, with blank lines and # comments skipped
+fn parse_catalog(source: &'static str) -> HashMap<&'static str, &'static str> {
+    source
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(code, message)| (code.trim(), message.trim()))
+        .collect()
+}
@@ -0,0 +1,230 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use lettre::message::header::ContentType;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde_json::{json, Value};
+use sqlx::AnyPool;
+use std::fmt;
+use std::sync::Arc;
+use tracing::{info, warn};
+use crate::config::{MailSettings, SmtpSettings, SmtpTls};
+use crate::models::admin_models::EmailStatus;
+use crate::models::jobs_models::Job;
+use crate::repositories::{emails, jobs};
+
+pub const EMAIL_DELIVERY: &str = "email_delivery";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    // Sent as an alternative to the text when there is one
+    pub html: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MailError {
+    // Worth trying again later: the server was unreachable or asked us to come back
+    Transient(String),
+    // Sending it again won't help, e.g. the recipient doesn't exist
+    Permanent(String),
+}
+
+impl fmt::Display for MailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MailError::Transient(message) | MailError::Permanent(message) => f.write_str(message),
+        }
+    }
+}
+
+// Anything that can deliver an email. Only the job worker sends, handlers queue messages
+// with `queue_email`, so the backend can be swapped without touching them.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), MailError>;
+}
+
+// Writes outgoing mail to the log instead of delivering it, handy for local development
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> Result<(), MailError> {
+        info!("Mail to {}: {}\n{}", email.to, email.subject, email.text);
+        Ok(())
+    }
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(settings: &SmtpSettings, from: &str) -> Result<Self, String> {
+        let builder = match settings.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.host),
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.host).map_err(|error| error.to_string())?,
+            SmtpTls::Implicit => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.host).map_err(|error| error.to_string())?,
+        };
+        let mut builder = builder.port(settings.port).timeout(Some(settings.timeout));
+        if let Some((username, password)) = &settings.credentials {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        let from = from.parse().map_err(|error| format!("invalid from address: {}", error))?;
+
+        Ok(SmtpMailer { transport: builder.build(), from })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<(), MailError> {
+        let to: Mailbox = email.to.parse().map_err(|error| MailError::Permanent(format!("invalid recipient: {}", error)))?;
+        let builder = Message::builder().from(self.from.clone()).to(to).subject(&email.subject);
+        let message = match &email.html {
+            Some(html) => builder.multipart(MultiPart::alternative_plain_html(email.text.clone(), html.clone())),
+            None => builder.header(ContentType::TEXT_PLAIN).body(email.text.clone()),
+        }
+        .map_err(|error| MailError::Permanent(error.to_string()))?;
+
+        // Only a 5xx reply says the message will never be taken, anything else may pass
+        match self.transport.send(message).await {
+            Ok(_) => Ok(()),
+            Err(error) if error.is_permanent() => Err(MailError::Permanent(error.to_string())),
+            Err(error) => Err(MailError::Transient(error.to_string())),
+        }
+    }
+}
+
+// SMTP when a host is configured, the log otherwise
+pub fn mailer_from_settings(settings: &MailSettings) -> Result<Arc<dyn Mailer>, String> {
+    match &settings.smtp {
+        Some(smtp) => Ok(Arc::new(SmtpMailer::new(smtp, &settings.from)?)),
+        None => Ok(Arc::new(LogMailer)),
+    }
+}
+
+// A message with `{name}` placeholders. Values are HTML escaped in the HTML body, which is
+// optional.
+pub struct MailTemplate {
+    pub name: &'static str,
+    subject: &'static str,
+    text: &'static str,
+    html: Option<&'static str>,
+}
+
+pub const CONFIRM_EMAIL: MailTemplate = MailTemplate {
+    name: "confirm_email",
+    subject: "Confirm your new email address",
+    text: "Confirm your new email address by visiting /api/v1/users/confirm-email?token={token}\nThis link expires in {hours} hours.",
+    html: Some("<p>Confirm your new email address by visiting <a href=\"/api/v1/users/confirm-email?token={token}\">this link</a>.</p><p>It expires in {hours} hours.</p>"),
+};
+
+pub const EMAIL_CHANGED: MailTemplate = MailTemplate {
+    name: "email_changed",
+    subject: "Your email address was changed",
+    text: "The email address on your account was changed to {email}. If this wasn't you, contact support.",
+    html: None,
+};
+
+pub const PASSWORD_RESET: MailTemplate = MailTemplate {
+    name: "password_reset",
+    subject: "Set a new password",
+    text: "Set a new password by sending this token to /api/v1/users/password-reset/confirm: {token}\nIt expires in {minutes} minutes and works once. If you didn't ask for this, ignore this email.",
+    html: Some("<p>Set a new password by sending this token to /api/v1/users/password-reset/confirm:</p><p><code>{token}</code></p><p>It expires in {minutes} minutes and works once. If you didn't ask for this, ignore this email.</p>"),
+};
+
+pub const NEW_DEVICE_LOGIN: MailTemplate = MailTemplate {
+    name: "new_device_login",
+    subject: "New login to your account",
+    text: "New login from {device}. If this wasn't you, change your password.",
+    html: None,
+};
+
+impl MailTemplate {
+    pub fn render(&self, to: &str, values: &[(&str, &str)]) -> Email {
+        let fill = |template: &str, escape: bool| {
+            values.iter().fold(template.to_string(), |filled, (name, value)| {
+                let value = if escape { escape_html(value) } else { value.to_string() };
+                filled.replace(&format!("{{{}}}", name), &value)
+            })
+        };
+
+        Email {
+            to: to.to_string(),
+            subject: fill(self.subject, false),
+            text: fill(self.text, false),
+            html: self.html.map(|html| fill(html, true)),
+        }
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
+
+// Renders the message, and records it along with the job sending it in one transaction
+pub async fn queue_email(pool: &AnyPool, template: &MailTemplate, to: &str, values: &[(&str, &str)], max_attempts: i32, now: DateTime<Utc>) -> Result<i32, sqlx::Error> {
+    let email = template.render(to, values);
+
+    let mut tx = pool.begin().await?;
+    let delivery_id = emails::insert_delivery(&mut tx, template.name, &email, now).await?;
+    jobs::enqueue(&mut tx, None, EMAIL_DELIVERY, &json!({ "delivery_id": delivery_id }), max_attempts, now).await?;
+    tx.commit().await?;
+
+    Ok(delivery_id)
+}
+
+// One attempt at a queued email. A transient failure is an error, so the job queue retries
+// it with backoff, and the email is dead-lettered along with the job's last attempt. A
+// permanent one dead-letters it straight away and ends the job.
+pub async fn send_queued(pool: &AnyPool, mailer: &dyn Mailer, job: &Job, now: DateTime<Utc>) -> Result<Value, String> {
+    let payload: Value = serde_json::from_str(&job.payload).map_err(|error| format!("invalid payload: {}", error))?;
+    let delivery_id = payload["delivery_id"].as_i64().ok_or("payload has no delivery_id")? as i32;
+
+    let email = match emails::find_unsent(pool, delivery_id).await.map_err(|error| error.to_string())? {
+        Some(email) => email,
+        None => return Ok(json!({ "delivery_id": delivery_id, "skipped": "already sent" })),
+    };
+
+    let outcome = mailer.send(&email).await;
+    let (status, error) = match &outcome {
+        Ok(()) => (EmailStatus::Sent, None),
+        Err(MailError::Transient(error)) if job.attempts < job.max_attempts => (EmailStatus::Retrying, Some(error.as_str())),
+        Err(MailError::Transient(error) | MailError::Permanent(error)) => (EmailStatus::DeadLetter, Some(error.as_str())),
+    };
+    emails::record_attempt(pool, delivery_id, status, error, now).await.map_err(|error| error.to_string())?;
+
+    match outcome {
+        Ok(()) => Ok(json!({ "delivery_id": delivery_id })),
+        // Nothing left to retry, the job is done with
+        Err(MailError::Permanent(error)) => {
+            warn!("Email {} dead-lettered, the server refused it: {}", delivery_id, error);
+            Ok(json!({ "delivery_id": delivery_id, "dead_letter": error }))
+        }
+        Err(MailError::Transient(error)) => Err(error),
+    }
+}
+
+// Puts an address in the form it is stored and compared in. Domains are case
+// insensitive so they are always lowercased; local parts technically aren't, so they
+// are only lowercased when `lowercase_local` (EMAIL_LOWERCASE_LOCAL_PART) is set.
+pub fn normalize_email(email: &str, lowercase_local: bool) -> String {
+    let email = email.trim();
+
+    let (local, domain) = match email.rsplit_once('@') {
+        Some(parts) => parts,
+        None => return email.to_string(),
+    };
+
+    if lowercase_local {
+        format!("{}@{}", local.to_lowercase(), domain.to_lowercase())
+    } else {
+        format!("{}@{}", local, domain.to_lowercase())
+    }
+}
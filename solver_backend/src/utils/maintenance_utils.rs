@@ -0,0 +1,100 @@
+use actix_web::rt::{self, task::JoinHandle};
+use chrono::Duration as ChronoDuration;
+use chrono::{DateTime, Utc};
+use tracing::{error, info};
+use sqlx::AnyPool;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::repositories::{game_sessions, usage, users, words};
+use crate::utils::audit_utils::log_auth_event;
+use crate::utils::clock_utils::Clock;
+use crate::utils::shutdown_utils::{wait_for_shutdown, ShutdownSignal};
+use crate::utils::usage_utils::USAGE_DAYS;
+use crate::utils::word_sync_utils::WORD_HISTORY_DAYS;
+
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(10 * 60);
+// Accounts anonymized per transaction, so a large backlog doesn't hold locks for long
+const ANONYMIZATION_BATCH: i64 = 50;
+
+// Periodic housekeeping that runs until shutdown. A pass already underway is allowed to
+// finish, the returned handle resolves once the task has stopped.
+pub fn spawn_maintenance(pool: AnyPool, clock: Arc<dyn Clock>, idempotency_ttl: ChronoDuration, deletion_grace: ChronoDuration, mut shutdown: ShutdownSignal) -> JoinHandle<()> {
+    rt::spawn(async move {
+        let mut interval = rt::time::interval(MAINTENANCE_INTERVAL);
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => run_maintenance(&pool, &*clock, idempotency_ttl, deletion_grace).await,
+                _ = wait_for_shutdown(&mut shutdown) => break,
+            }
+        }
+
+        info!("Maintenance task stopped");
+    })
+}
+
+pub async fn run_maintenance(pool: &AnyPool, clock: &dyn Clock, idempotency_ttl: ChronoDuration, deletion_grace: ChronoDuration) {
+    let cutoff = clock.now() - idempotency_ttl;
+
+    match sqlx::query("DELETE FROM idempotency_keys WHERE created_at < $1")
+        .bind(cutoff)
+        .execute(pool)
+        .await
+    {
+        Ok(result) => {
+            if result.rows_affected() > 0 {
+                info!("Removed {} expired idempotency keys", result.rows_affected());
+            }
+        }
+        Err(error) => error!("Failed to remove expired idempotency keys: {}", error),
+    }
+
+    match game_sessions::delete_expired(pool, clock.now()).await {
+        Ok(0) => {}
+        Ok(count) => info!("Removed {} expired anonymous game sessions", count),
+        Err(error) => error!("Failed to remove expired anonymous game sessions: {}", error),
+    }
+
+    match usage::delete_before(pool, clock.now().date_naive() - ChronoDuration::days(USAGE_DAYS)).await {
+        Ok(0) => {}
+        Ok(count) => info!("Removed {} API usage rows older than {} days", count, USAGE_DAYS),
+        Err(error) => error!("Failed to remove old API usage: {}", error),
+    }
+
+    match words::prune_changes(pool, clock.now() - ChronoDuration::days(WORD_HISTORY_DAYS)).await {
+        Ok(0) => {}
+        Ok(count) => info!("Forgot the words changed by {} word list revisions older than {} days", count, WORD_HISTORY_DAYS),
+        Err(error) => error!("Failed to prune the word list history: {}", error),
+    }
+
+    match anonymize_deleted_accounts(pool, clock.now(), deletion_grace).await {
+        Ok(0) => {}
+        Ok(count) => info!("Anonymized {} deleted accounts", count),
+        Err(error) => error!("Failed to anonymize deleted accounts: {}", error),
+    }
+}
+
+// Anonymizes every account whose deletion is older than `grace`, a batch per transaction,
+// and records each one in the audit log. Accounts already anonymized aren't picked up
+// again, so a pass cut short is finished by the next one.
+pub async fn anonymize_deleted_accounts(pool: &AnyPool, now: DateTime<Utc>, grace: ChronoDuration) -> Result<usize, sqlx::Error> {
+    let mut total = 0;
+
+    loop {
+        let mut tx = pool.begin().await?;
+        let ids = users::due_for_anonymization(&mut tx, now - grace, ANONYMIZATION_BATCH).await?;
+        for id in &ids {
+            users::anonymize(&mut tx, *id, now).await?;
+        }
+        tx.commit().await?;
+
+        for id in &ids {
+            log_auth_event(pool, Some(*id), "account_anonymized", "").await;
+        }
+        total += ids.len();
+
+        if (ids.len() as i64) < ANONYMIZATION_BATCH {
+            return Ok(total);
+        }
+    }
+}
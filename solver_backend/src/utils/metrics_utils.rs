@@ -0,0 +1,216 @@
+use chrono::NaiveDate;
+use prometheus::core::Collector;
+use prometheus::{
+    exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+use tracing::error;
+use sqlx::AnyPool;
+use std::collections::BTreeMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::Duration;
+use crate::utils::cache_utils::{AuthCache, TtlCache};
+
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    db_pool_connections: IntGaugeVec,
+    login_attempts: IntCounterVec,
+    bcrypt_duration: HistogramVec,
+    candidate_results: HistogramVec,
+    auth_cache_hits: IntCounterVec,
+    auth_cache_misses: IntCounterVec,
+    requests_in_flight: IntGaugeVec,
+    requests_shed: IntCounterVec,
+    // Candidate queries answered on the day they're counted for, reset at midnight UTC
+    solver_queries_today: Mutex<(NaiveDate, u64)>,
+    // Static token required to scrape, None leaves /metrics open
+    pub scrape_token: Option<String>,
+}
+
+impl Metrics {
+    pub fn new(scrape_token: Option<String>) -> Self {
+        let registry = Registry::new();
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled, by route and status"),
+            &["method", "route", "status"],
+        ).unwrap();
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Time spent handling HTTP requests"),
+            &["method", "route"],
+        ).unwrap();
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections, by state"),
+            &["state"],
+        ).unwrap();
+        let login_attempts = IntCounterVec::new(
+            Opts::new("login_attempts_total", "Login attempts, by outcome"),
+            &["outcome"],
+        ).unwrap();
+        let bcrypt_duration = HistogramVec::new(
+            HistogramOpts::new("bcrypt_duration_seconds", "Time spent hashing and verifying passwords")
+                .buckets(vec![0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]),
+            &["operation"],
+        ).unwrap();
+        let candidate_results = HistogramVec::new(
+            HistogramOpts::new("candidate_query_results", "Words returned by candidate queries")
+                .buckets(exponential_buckets(1.0, 4.0, 8).unwrap()),
+            &["query"],
+        ).unwrap();
+        let auth_cache_hits = IntCounterVec::new(
+            Opts::new("auth_cache_hits_total", "Auth cache lookups answered from memory"),
+            &["cache"],
+        ).unwrap();
+        let auth_cache_misses = IntCounterVec::new(
+            Opts::new("auth_cache_misses_total", "Auth cache lookups that went to the database"),
+            &["cache"],
+        ).unwrap();
+        let requests_in_flight = IntGaugeVec::new(
+            Opts::new("requests_in_flight", "Requests running in a concurrency limited route group"),
+            &["group"],
+        ).unwrap();
+        let requests_shed = IntCounterVec::new(
+            Opts::new("requests_shed_total", "Requests turned away because their route group was at its limit"),
+            &["group"],
+        ).unwrap();
+
+        registry.register(Box::new(http_requests.clone())).unwrap();
+        registry.register(Box::new(http_request_duration.clone())).unwrap();
+        registry.register(Box::new(db_pool_connections.clone())).unwrap();
+        registry.register(Box::new(login_attempts.clone())).unwrap();
+        registry.register(Box::new(bcrypt_duration.clone())).unwrap();
+        registry.register(Box::new(candidate_results.clone())).unwrap();
+        registry.register(Box::new(auth_cache_hits.clone())).unwrap();
+        registry.register(Box::new(auth_cache_misses.clone())).unwrap();
+        registry.register(Box::new(requests_in_flight.clone())).unwrap();
+        registry.register(Box::new(requests_shed.clone())).unwrap();
+
+        Metrics {
+            registry,
+            http_requests,
+            http_request_duration,
+            db_pool_connections,
+            login_attempts,
+            bcrypt_duration,
+            candidate_results,
+            auth_cache_hits,
+            auth_cache_misses,
+            requests_in_flight,
+            requests_shed,
+            solver_queries_today: Mutex::new((NaiveDate::MIN, 0)),
+            scrape_token,
+        }
+    }
+
+    pub fn observe_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.http_requests
+            .with_label_values(&[method, route, &status.to_string()])
+            .inc();
+        self.http_request_duration
+            .with_label_values(&[method, route])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_login(&self, success: bool) {
+        let outcome = if success { "success" } else { "failure" };
+        self.login_attempts.with_label_values(&[outcome]).inc();
+    }
+
+    // `operation` is "hash" or "verify"
+    pub fn observe_bcrypt(&self, operation: &str, elapsed: Duration) {
+        self.bcrypt_duration
+            .with_label_values(&[operation])
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_candidates(&self, query: &str, results: usize) {
+        self.candidate_results
+            .with_label_values(&[query])
+            .observe(results as f64);
+    }
+
+    // For caches that don't keep their own counters, `sync_cache` covers the ones that do
+    pub fn observe_cache_lookup(&self, cache: &str, hit: bool) {
+        let counter = if hit { &self.auth_cache_hits } else { &self.auth_cache_misses };
+        counter.with_label_values(&[cache]).inc();
+    }
+
+    // Raised while a request of `group` holds a slot, see ConcurrencyLimits
+    pub fn in_flight(&self, group: &str) -> IntGauge {
+        self.requests_in_flight.with_label_values(&[group])
+    }
+
+    pub fn observe_shed_request(&self, group: &str) {
+        self.requests_shed.with_label_values(&[group]).inc();
+    }
+
+    pub fn count_solver_query(&self, today: NaiveDate) {
+        let mut counted = self.solver_queries_today.lock().unwrap();
+        if counted.0 != today {
+            *counted = (today, 0);
+        }
+        counted.1 += 1;
+    }
+
+    pub fn solver_queries_on(&self, today: NaiveDate) -> u64 {
+        match *self.solver_queries_today.lock().unwrap() {
+            (day, count) if day == today => count,
+            _ => 0,
+        }
+    }
+
+    // Share of lookups each cache answered itself, None for caches not used yet
+    pub fn cache_hit_rates(&self, auth_cache: &AuthCache) -> BTreeMap<String, Option<f64>> {
+        self.sync_cache("token_versions", &auth_cache.token_versions);
+
+        let mut lookups: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+        for (counter, is_hit) in [(&self.auth_cache_hits, true), (&self.auth_cache_misses, false)] {
+            for metric in counter.collect().iter().flat_map(|family| family.get_metric()) {
+                if let Some(cache) = metric.get_label().iter().find(|label| label.get_name() == "cache") {
+                    let entry = lookups.entry(cache.get_value().to_string()).or_default();
+                    let count = metric.get_counter().get_value() as u64;
+                    if is_hit {
+                        entry.0 += count;
+                    } else {
+                        entry.1 += count;
+                    }
+                }
+            }
+        }
+
+        lookups
+            .into_iter()
+            .map(|(cache, (hits, misses))| {
+                let total = hits + misses;
+                (cache, (total > 0).then(|| hits as f64 / total as f64))
+            })
+            .collect()
+    }
+
+    // Values kept elsewhere are copied in at scrape time rather than on every change
+    pub fn render(&self, pool: &AnyPool, auth_cache: &AuthCache) -> String {
+        let idle = pool.num_idle() as i64;
+        self.db_pool_connections.with_label_values(&["idle"]).set(idle);
+        self.db_pool_connections.with_label_values(&["active"]).set(pool.size() as i64 - idle);
+
+        self.sync_cache("token_versions", &auth_cache.token_versions);
+
+        let mut buffer = Vec::new();
+        if let Err(error) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            error!("Failed to encode metrics: {}", error);
+        }
+
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+
+    fn sync_cache<K: Eq + Hash, V: Clone>(&self, name: &str, cache: &TtlCache<K, V>) {
+        let hits = self.auth_cache_hits.with_label_values(&[name]);
+        hits.inc_by(cache.hits().saturating_sub(hits.get()));
+
+        let misses = self.auth_cache_misses.with_label_values(&[name]);
+        misses.inc_by(cache.misses().saturating_sub(misses.get()));
+    }
+}
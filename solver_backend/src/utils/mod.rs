@@ -1,2 +1,41 @@
+pub mod answer_utils;
+pub mod audit_utils;
+pub mod auth_utils;
 pub mod bcrypt_utils;
+pub mod cache_utils;
+pub mod client_ip_utils;
+pub mod clock_utils;
+pub mod coalesce_utils;
+pub mod concurrency_utils;
+pub mod db_utils;
+pub mod device_utils;
+pub mod etag_utils;
+pub mod feature_utils;
+pub mod feedback_utils;
+pub mod idempotency_utils;
+pub mod import_utils;
+pub mod input_utils;
+pub mod job_utils;
 pub mod jwt_utils;
+pub mod locale_utils;
+pub mod mail_utils;
+pub mod maintenance_utils;
+pub mod metrics_utils;
+pub mod pagination_utils;
+#[cfg(feature = "redis")]
+pub mod redis_utils;
+pub mod seed_utils;
+pub mod session_utils;
+pub mod shutdown_utils;
+pub mod solver_cache_utils;
+pub mod stats_utils;
+pub mod store_utils;
+pub mod stream_utils;
+pub mod suggestion_utils;
+pub mod tls_utils;
+pub mod tracing_utils;
+pub mod usage_utils;
+pub mod username_utils;
+pub mod webhook_utils;
+pub mod word_check_utils;
+pub mod word_sync_utils;
@@ -0,0 +1,149 @@
+use actix_web::dev::Payload;
+use actix_web::{web, FromRequest, HttpRequest};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde_json::json;
+use std::future::{ready, Ready};
+use crate::errors::{AppError, ErrorCode, ErrorInfo};
+use crate::models::page_models::{CursorPage, Paginated};
+use crate::models::users_models::{CursorQuery, PageQuery};
+use crate::AppState;
+
+// `?page=&per_page=` of a listing endpoint. Values out of range are refused with a 422
+// rather than clamped, so a client never silently gets a different page than it asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    pub page: u32,
+    pub per_page: u32,
+}
+
+impl Pagination {
+    pub fn limit(&self) -> i64 {
+        self.per_page.into()
+    }
+
+    pub fn offset(&self) -> i64 {
+        (i64::from(self.page) - 1) * i64::from(self.per_page)
+    }
+
+    // Wraps a page of rows fetched with `limit` and `offset`, `total` counting all of them
+    pub fn page_of<T>(&self, items: Vec<T>, total: i64) -> Paginated<T> {
+        let per_page = i64::from(self.per_page);
+        Paginated {
+            items,
+            page: self.page,
+            per_page: self.per_page,
+            total,
+            total_pages: (total + per_page - 1) / per_page,
+        }
+    }
+
+    // For listings that are already in memory in full, e.g. cached candidate lists
+    pub fn slice<T>(&self, all: Vec<T>) -> Paginated<T> {
+        let total = all.len() as i64;
+        let items = all.into_iter().skip(self.offset() as usize).take(self.per_page as usize).collect();
+        self.page_of(items, total)
+    }
+}
+
+impl FromRequest for Pagination {
+    type Error = AppError;
+    type Future = Ready<Result<Self, AppError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(pagination(req))
+    }
+}
+
+fn pagination(req: &HttpRequest) -> Result<Pagination, AppError> {
+    let query = web::Query::<PageQuery>::from_query(req.query_string())
+        .map_err(|error| AppError::bad_request(ErrorCode::InvalidQuery, error.to_string()))?;
+    let (default_size, max_size) = page_sizes(req)?;
+
+    let page = query.page.unwrap_or(1);
+    if page == 0 {
+        return Err(AppError::validation(ErrorCode::InvalidPage, "page starts at 1"));
+    }
+
+    Ok(Pagination {
+        page,
+        per_page: page_size("per_page", query.per_page, default_size, max_size)?,
+    })
+}
+
+// `?cursor=&limit=` of a listing too long to count or to skip through by offset. The
+// cursor is opaque to clients, it carries the id of the last row they were sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorPagination {
+    // Rows with ids below this come next, None starts from the newest
+    pub before: Option<i64>,
+    pub limit: u32,
+}
+
+impl CursorPagination {
+    // Fetches one row more than the page holds, to know whether another page follows
+    pub fn fetch_limit(&self) -> i64 {
+        i64::from(self.limit) + 1
+    }
+
+    // Wraps rows fetched newest first with `fetch_limit`
+    pub fn page_of<T>(&self, mut items: Vec<T>, id_of: impl Fn(&T) -> i64) -> CursorPage<T> {
+        let next_cursor = if items.len() > self.limit as usize {
+            items.truncate(self.limit as usize);
+            items.last().map(|last| encode_cursor(id_of(last)))
+        } else {
+            None
+        };
+
+        CursorPage { items, next_cursor }
+    }
+}
+
+impl FromRequest for CursorPagination {
+    type Error = AppError;
+    type Future = Ready<Result<Self, AppError>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(cursor_pagination(req))
+    }
+}
+
+fn cursor_pagination(req: &HttpRequest) -> Result<CursorPagination, AppError> {
+    let query = web::Query::<CursorQuery>::from_query(req.query_string())
+        .map_err(|error| AppError::bad_request(ErrorCode::InvalidQuery, error.to_string()))?;
+    let (default_size, max_size) = page_sizes(req)?;
+
+    let before = match &query.cursor {
+        Some(cursor) => Some(decode_cursor(cursor).ok_or_else(|| AppError::bad_request(ErrorCode::InvalidCursor, "cursor isn't one this server handed out"))?),
+        None => None,
+    };
+
+    Ok(CursorPagination {
+        before,
+        limit: page_size("limit", query.limit, default_size, max_size)?,
+    })
+}
+
+pub fn encode_cursor(id: i64) -> String {
+    URL_SAFE_NO_PAD.encode(id.to_string())
+}
+
+fn decode_cursor(cursor: &str) -> Option<i64> {
+    let decoded = URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    std::str::from_utf8(&decoded).ok()?.parse().ok()
+}
+
+fn page_sizes(req: &HttpRequest) -> Result<(u32, u32), AppError> {
+    let state = req.app_data::<web::Data<AppState>>().ok_or_else(|| AppError::internal("pagination needs the app state"))?;
+    Ok((state.settings.default_page_size, state.settings.max_page_size))
+}
+
+fn page_size(name: &str, requested: Option<u32>, default_size: u32, max_size: u32) -> Result<u32, AppError> {
+    match requested {
+        None => Ok(default_size),
+        Some(size) if (1..=max_size).contains(&size) => Ok(size),
+        Some(_) => Err(AppError::Validation(
+            ErrorInfo::new(ErrorCode::InvalidPageSize, format!("{} must be between 1 and {}", name, max_size))
+                .with_details(json!({ "field": name, "max": max_size })),
+        )),
+    }
+}
@@ -0,0 +1,164 @@
+use actix_web::rt::time::timeout;
+use async_trait::async_trait;
+use redis::aio::MultiplexedConnection;
+use redis::{Client, FromRedisValue, Pipeline};
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+use crate::config::Settings;
+use crate::utils::store_utils::{RateLimitHit, RateLimitStore, ResultCache, RevocationStore, StoreError};
+
+const KEY_PREFIX: &str = "wordle";
+// Short, a slow Redis should fall back to the configured failure mode rather than stall requests
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+const COMMAND_TIMEOUT: Duration = Duration::from_millis(500);
+
+// One multiplexed connection shared by every store, opened on first use and reopened on
+// the next command after it breaks
+pub struct RedisStore {
+    client: Client,
+    connection: Mutex<Option<MultiplexedConnection>>,
+    // A revoked token stays revoked for as long as it could still be presented
+    revoked_ttl: Duration,
+    checked_ttl: Duration,
+    result_ttl: Duration,
+}
+
+impl RedisStore {
+    // The URL has already been validated with the rest of the settings
+    pub fn new(url: &str, settings: &Settings) -> Self {
+        RedisStore {
+            client: Client::open(url).expect("REDIS_URL was validated at startup"),
+            connection: Mutex::new(None),
+            revoked_ttl: settings.jwt.refresh_token_ttl.to_std().unwrap_or(settings.auth_cache_ttl),
+            checked_ttl: settings.auth_cache_ttl,
+            result_ttl: settings.result_cache_ttl,
+        }
+    }
+
+    async fn connection(&self) -> Result<MultiplexedConnection, StoreError> {
+        if let Some(connection) = self.connection.lock().unwrap().clone() {
+            return Ok(connection);
+        }
+
+        let connection = match timeout(CONNECT_TIMEOUT, self.client.get_multiplexed_tokio_connection()).await {
+            Ok(Ok(connection)) => connection,
+            Ok(Err(error)) => return Err(StoreError(format!("connecting to Redis: {}", error))),
+            Err(_) => return Err(StoreError("connecting to Redis timed out".to_string())),
+        };
+
+        *self.connection.lock().unwrap() = Some(connection.clone());
+        Ok(connection)
+    }
+
+    async fn query<T: FromRedisValue>(&self, pipeline: &Pipeline) -> Result<T, StoreError> {
+        let mut connection = self.connection().await?;
+
+        match timeout(COMMAND_TIMEOUT, pipeline.query_async(&mut connection)).await {
+            Ok(Ok(value)) => Ok(value),
+            Ok(Err(error)) => {
+                if error.is_io_error() || error.is_connection_dropped() {
+                    self.reset();
+                }
+                Err(StoreError(format!("Redis command failed: {}", error)))
+            }
+            Err(_) => {
+                self.reset();
+                Err(StoreError("Redis command timed out".to_string()))
+            }
+        }
+    }
+
+    fn reset(&self) {
+        if self.connection.lock().unwrap().take().is_some() {
+            warn!("Dropped the Redis connection, reconnecting on next use");
+        }
+    }
+}
+
+fn key(kind: &str, id: &str) -> String {
+    format!("{}:{}:{}", KEY_PREFIX, kind, id)
+}
+
+// Tokens and query descriptions can be long, so keys carry a digest instead
+fn digest(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[async_trait]
+impl RevocationStore for RedisStore {
+    async fn is_revoked(&self, token: &str) -> Result<Option<bool>, StoreError> {
+        let (value,): (Option<String>,) = self
+            .query(redis::pipe().cmd("GET").arg(key("revoked", &digest(token))))
+            .await?;
+
+        Ok(value.map(|value| value == "1"))
+    }
+
+    async fn record(&self, token: &str, revoked: bool) -> Result<(), StoreError> {
+        let ttl = if revoked { self.revoked_ttl } else { self.checked_ttl };
+        let mut pipeline = redis::pipe();
+        let set = pipeline
+            .cmd("SET")
+            .arg(key("revoked", &digest(token)))
+            .arg(if revoked { "1" } else { "0" })
+            .arg("PX")
+            .arg(ttl.as_millis() as u64);
+        // A "not revoked" answer must never overwrite a revocation written by another replica
+        if !revoked {
+            set.arg("NX");
+        }
+
+        self.query::<()>(set.ignore()).await
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for RedisStore {
+    async fn hit(&self, key_name: &str, window: Duration) -> Result<RateLimitHit, StoreError> {
+        let key = key("ratelimit", key_name);
+
+        // The window starts with the first hit and the counter expires along with it
+        let (hits, ttl_ms): (u64, i64) = self
+            .query(
+                redis::pipe()
+                    .atomic()
+                    .cmd("SET").arg(&key).arg(0).arg("PX").arg(window.as_millis() as u64).arg("NX").ignore()
+                    .cmd("INCR").arg(&key)
+                    .cmd("PTTL").arg(&key),
+            )
+            .await?;
+
+        // Negative when the key has no expiry, which only a hand edited key would lack
+        let resets_in = u64::try_from(ttl_ms).map_or(window, Duration::from_millis);
+        Ok(RateLimitHit { hits, resets_in })
+    }
+}
+
+#[async_trait]
+impl ResultCache for RedisStore {
+    async fn get(&self, key_name: &str) -> Result<Option<String>, StoreError> {
+        let (value,): (Option<String>,) = self
+            .query(redis::pipe().cmd("GET").arg(key("results", &digest(key_name))))
+            .await?;
+
+        Ok(value)
+    }
+
+    async fn put(&self, key_name: &str, value: &str) -> Result<(), StoreError> {
+        self.query::<()>(
+            redis::pipe()
+                .cmd("SET")
+                .arg(key("results", &digest(key_name)))
+                .arg(value)
+                .arg("PX")
+                .arg(self.result_ttl.as_millis() as u64)
+                .ignore(),
+        )
+        .await
+    }
+}
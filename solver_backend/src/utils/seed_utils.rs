@@ -0,0 +1,108 @@
+use crate::repositories::{users, words};
+use crate::utils::bcrypt_utils::hash_password;
+use chrono::{DateTime, Utc};
+use sqlx::{Any, AnyPool, Executor, Transaction};
+
+const BUNDLED_WORDS: &str = include_str!("../../data/words.txt");
+
+pub struct SeedUser {
+    pub username: &'static str,
+    pub email: &'static str,
+    pub password: &'static str,
+    pub role: &'static str,
+}
+
+// Known passwords, for logging in to a development database. Never for anything reachable.
+pub const SEED_USERS: &[SeedUser] = &[
+    SeedUser { username: "admin", email: "admin@example.com", password: "admin-password", role: "admin" },
+    SeedUser { username: "alice", email: "alice@example.com", password: "alice-password", role: "user" },
+    SeedUser { username: "bob", email: "bob@example.com", password: "bob-password", role: "user" },
+];
+
+#[derive(Debug, Default)]
+pub struct SeedReport {
+    pub words_added: usize,
+    pub users_created: Vec<&'static str>,
+}
+
+// Fills a development database with the bundled word list and the seed accounts. Whatever
+// is already there is left alone, so running it twice adds nothing the second time. Any
+// other account means the database has real users, and it refuses unless `force` is set.
+pub async fn seed(pool: &AnyPool, bcrypt_cost: u32, now: DateTime<Utc>, force: bool) -> Result<SeedReport, String> {
+    let other_users = count_other_users(pool)
+        .await
+        .map_err(|error| format!("Failed to count existing users: {}", error))?;
+    if other_users > 0 && !force {
+        return Err(format!("The database already has {} user(s) that seeding didn't create, pass --force to seed it anyway", other_users));
+    }
+
+    let mut tx = pool.begin().await.map_err(|error| format!("Failed to start the seed transaction: {}", error))?;
+    let mut report = SeedReport { words_added: seed_words(&mut tx).await?, ..SeedReport::default() };
+
+    for user in SEED_USERS {
+        if seed_user(&mut tx, user, bcrypt_cost, now).await? {
+            report.users_created.push(user.username);
+        }
+    }
+
+    tx.commit().await.map_err(|error| format!("Failed to commit the seed data: {}", error))?;
+    Ok(report)
+}
+
+// Adds whatever of the bundled word list is missing, returning how many words that was
+pub async fn seed_words(tx: &mut Transaction<'_, Any>) -> Result<usize, String> {
+    let existing = words::all_words(&mut *tx)
+        .await
+        .map_err(|error| format!("Failed to read the word list: {}", error))?;
+
+    let mut added = 0;
+    for word in BUNDLED_WORDS.lines().map(str::trim).filter(|word| !word.is_empty()) {
+        if existing.iter().any(|existing| existing == word) {
+            continue;
+        }
+        sqlx::query("INSERT INTO word_list (word) VALUES ($1)")
+            .bind(word)
+            .execute(&mut *tx)
+            .await
+            .map_err(|error| format!("Failed to add {} to the word list: {}", word, error))?;
+        added += 1;
+    }
+
+    Ok(added)
+}
+
+// False when the account is already there
+async fn seed_user(tx: &mut Transaction<'_, Any>, user: &SeedUser, bcrypt_cost: u32, now: DateTime<Utc>) -> Result<bool, String> {
+    let exists: Option<i32> = sqlx::query_scalar("SELECT id FROM users WHERE username = $1")
+        .bind(user.username)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|error| format!("Failed to look up {}: {}", user.username, error))?;
+    if exists.is_some() {
+        return Ok(false);
+    }
+
+    let hashed = hash_password(user.password, bcrypt_cost).map_err(|error| format!("Failed to hash the password for {}: {}", user.username, error))?;
+    let id = users::insert_user(&mut *tx, user.username, user.email, &hashed, now)
+        .await
+        .map_err(|error| format!("Failed to create {}: {}", user.username, error))?;
+    sqlx::query("UPDATE users SET role = $1, email_verified = TRUE WHERE id = $2")
+        .bind(user.role)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(|error| format!("Failed to set up {}: {}", user.username, error))?;
+
+    Ok(true)
+}
+
+async fn count_other_users<'e>(db: impl Executor<'e, Database = Any>) -> Result<i64, sqlx::Error> {
+    let placeholders: Vec<String> = (1..=SEED_USERS.len()).map(|n| format!("${}", n)).collect();
+    let sql = format!("SELECT COUNT(*) FROM users WHERE username NOT IN ({})", placeholders.join(", "));
+
+    let mut query = sqlx::query_scalar(&sql);
+    for user in SEED_USERS {
+        query = query.bind(user.username);
+    }
+    query.fetch_one(db).await
+}
@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use sqlx::AnyConnection;
+use crate::errors::AppError;
+use crate::models::game_models::{GuessSuggestion, SessionReport, SessionReportRecord, SolverOutput, UserStats};
+use crate::repositories::{game_sessions, user_stats};
+use crate::solver::{parse_feedback, suggest, Constraints};
+use crate::utils::word_sync_utils::WORD_LENGTH;
+
+// As many as best-guess suggests by default
+const REPORT_SUGGESTIONS: usize = 5;
+const MAX_REPORTED_CANDIDATES: usize = 100;
+
+// Everything the stored guesses revealed. They were checked when they were made, so one
+// that no longer parses means the row was changed behind our back.
+pub fn constraints_of(guesses: &[(&str, &str)]) -> Result<Constraints, AppError> {
+    let mut constraints = Constraints::with_length(WORD_LENGTH);
+    for (guess, feedback) in guesses {
+        let marks = parse_feedback(feedback).map_err(|error| AppError::internal(format!("stored feedback {:?}: {}", feedback, error)))?;
+        constraints.apply(guess, &marks).map_err(|error| AppError::internal(format!("stored guess {:?}: {}", guess, error)))?;
+    }
+    Ok(constraints)
+}
+
+// The candidates left and the best guesses from `words`, scored as best-guess scores them,
+// for a session report. The same words give the same output.
+pub fn solver_output(word_list_version: i64, mut candidates: Vec<String>, words: &[String]) -> SolverOutput {
+    candidates.sort_unstable();
+    let candidate_words: Vec<&str> = candidates.iter().map(String::as_str).collect();
+    // Ties keep the order they're in
+    let mut guesses: Vec<&str> = words.iter().map(String::as_str).filter(|word| word.len() == WORD_LENGTH).collect();
+    guesses.sort_unstable();
+    let suggestions = if candidate_words.is_empty() { Vec::new() } else { suggest(&candidate_words, &guesses, REPORT_SUGGESTIONS) };
+    let suggestions = suggestions
+        .into_iter()
+        .map(|suggestion| GuessSuggestion { word: suggestion.word.to_string(), score: suggestion.score, explanation: None })
+        .collect();
+
+    let candidate_count = candidates.len();
+    candidates.truncate(MAX_REPORTED_CANDIDATES);
+    SolverOutput { word_list_version, candidate_count, candidates, suggestions }
+}
+
+// The stored JSON only ever comes from the report endpoint, so JSON that doesn't parse is
+// an internal error
+pub fn report_of(record: SessionReportRecord) -> Result<SessionReport, AppError> {
+    let invalid = |error: serde_json::Error| AppError::internal(format!("session report {}: {}", record.id, error));
+    Ok(SessionReport {
+        id: record.id,
+        session_id: record.session_id,
+        user_id: record.user_id,
+        description: record.description,
+        guesses: serde_json::from_str(&record.guesses).map_err(invalid)?,
+        original: serde_json::from_str(&record.original).map_err(invalid)?,
+        created_at: record.created_at,
+    })
+}
+
+// Counts sessions just handed to `user_id` in their stats, as far as they were played
+pub async fn add_claimed(conn: &mut AnyConnection, user_id: i32, ids: &[i32], now: DateTime<Utc>) -> Result<(), AppError> {
+    for id in ids {
+        let (max_guesses, status, guesses) = game_sessions::outcome(&mut *conn, *id)
+            .await?
+            .ok_or_else(|| AppError::internal(format!("claimed session {} is gone", id)))?;
+        user_stats::add(conn, user_id, &UserStats::of_session(max_guesses, &status, guesses), now).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use actix_web::rt::signal;
+use tokio::sync::watch;
+
+// Flips to true once the server starts shutting down. Background tasks hold a receiver
+// and stop at their next opportunity; the readiness probe reports it as well.
+pub type ShutdownSignal = watch::Receiver<bool>;
+
+pub fn shutdown_channel() -> (watch::Sender<bool>, ShutdownSignal) {
+    watch::channel(false)
+}
+
+pub fn is_shutting_down(shutdown: &ShutdownSignal) -> bool {
+    *shutdown.borrow()
+}
+
+// Resolves when the receiver sees the shutdown flag, or when the sender is gone
+pub async fn wait_for_shutdown(shutdown: &mut ShutdownSignal) {
+    while !*shutdown.borrow() {
+        if shutdown.changed().await.is_err() {
+            return;
+        }
+    }
+}
+
+// Resolves on the first SIGINT or SIGTERM and returns the signal's name for logging
+pub async fn termination_signal() -> &'static str {
+    #[cfg(unix)]
+    {
+        let mut terminate = match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(_) => {
+                let _ = signal::ctrl_c().await;
+                return "SIGINT";
+            }
+        };
+
+        tokio::select! {
+            _ = signal::ctrl_c() => "SIGINT",
+            _ = terminate.recv() => "SIGTERM",
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = signal::ctrl_c().await;
+        "SIGINT"
+    }
+}
@@ -0,0 +1,359 @@
+use crate::errors::AppError;
+use crate::repositories::words::WordRepository;
+use crate::solver::{feedback, outlook_by, pattern_code, suggest, Outlook};
+use crate::utils::word_sync_utils::WORD_LENGTH;
+use actix_web::rt;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::thread;
+use std::time::Instant;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+// Bumped whenever the file layout or the pattern codes change, so older files are rebuilt
+const FORMAT_VERSION: u32 = 1;
+const MAGIC: &[u8; 4] = b"WSPM";
+// Magic, format version, word list hash, guess count and answer count
+const HEADER_LEN: usize = 4 + 4 + 32 + 4 + 4;
+const CHECKSUM_LEN: usize = 32;
+
+// As many as a best-guess request may ask for
+pub const STARTER_COUNT: usize = 20;
+
+// The feedback every guess gets against every answer, as `pattern_code`s, a row per guess
+pub struct PatternMatrix {
+    guesses: HashMap<String, usize>,
+    answers: HashMap<String, usize>,
+    patterns: Vec<u8>,
+}
+
+impl PatternMatrix {
+    // None when either word isn't in the list the matrix was built from
+    pub fn pattern(&self, guess: &str, answer: &str) -> Option<u8> {
+        let row = self.guesses.get(guess)?;
+        let column = self.answers.get(answer)?;
+        Some(self.patterns[row * self.answers.len() + column])
+    }
+}
+
+// Where the pattern matrix came from when the cache was built
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatrixSource {
+    // Worked out, there was no file to read it from
+    Computed,
+    // Read from SOLVER_CACHE_PATH
+    Loaded,
+    // Worked out again, the file was for another word list or format
+    RebuiltStale,
+    // Worked out again, the file was unreadable, cut short or failed its checksum
+    RebuiltCorrupt,
+}
+
+// What the best-guess route would otherwise read and work out from the word list on
+// every request
+pub struct SolverData {
+    // The word list version it was built from
+    pub version: i64,
+    // Five letter words, alphabetically: what a guess can be
+    pub guesses: Vec<String>,
+    pub answers: Vec<String>,
+    pub matrix: PatternMatrix,
+    // The best opening guesses with their scores, best first, as `suggest` ranks them with
+    // every guess still a candidate
+    pub starters: Vec<(String, usize)>,
+    pub source: MatrixSource,
+}
+
+impl SolverData {
+    // `solver::outlook`, looking feedback up in the matrix. Candidates that can't be
+    // answers aren't in it and are worked out as before.
+    pub fn outlook(&self, guess: &str, candidates: &[&str]) -> Outlook {
+        outlook_by(guess, candidates, |candidate| self.pattern(guess, candidate))
+    }
+
+    // The `pattern_code` of the feedback `guess` gets against `candidate`, from the matrix
+    // when both are in it
+    pub fn pattern(&self, guess: &str, candidate: &str) -> u8 {
+        self.matrix.pattern(guess, candidate).unwrap_or_else(|| pattern_code(&feedback(guess, candidate)))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrimingState {
+    // Not primed, the first request that needs it builds it
+    Idle,
+    Priming,
+    Ready,
+    // Priming failed, the first request that needs it tries again
+    Failed(String),
+}
+
+// Rows of the pattern matrix worked out in the build underway
+#[derive(Default)]
+struct Progress {
+    done: AtomicUsize,
+    total: AtomicUsize,
+}
+
+impl Progress {
+    fn start(&self, total: usize) {
+        self.done.store(0, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+    }
+
+    // Logs each tenth of the way
+    fn row_done(&self) {
+        let done = self.done.fetch_add(1, Ordering::Relaxed) + 1;
+        let total = self.total.load(Ordering::Relaxed).max(1);
+        if done * 10 / total > (done - 1) * 10 / total {
+            info!("Solver cache: pattern matrix {}% built, {} of {} guesses", done * 100 / total, done, total);
+        }
+    }
+
+    fn percent(&self) -> usize {
+        let total = self.total.load(Ordering::Relaxed);
+        (self.done.load(Ordering::Relaxed) * 100).checked_div(total).unwrap_or(0)
+    }
+}
+
+// Built once per word list version and shared by the workers. Priming builds it at startup,
+// before readiness passes; after that a request finding the list has moved on rebuilds it,
+// and requests arriving meanwhile wait for that one build. With SOLVER_CACHE_PATH set the
+// pattern matrix is kept in that file, and read back on restart while the list is the same.
+pub struct SolverCache {
+    path: Option<PathBuf>,
+    data: RwLock<Option<Arc<SolverData>>>,
+    building: Mutex<()>,
+    state: RwLock<PrimingState>,
+    progress: Arc<Progress>,
+}
+
+impl SolverCache {
+    pub fn new(path: Option<&str>) -> Self {
+        SolverCache {
+            path: path.map(PathBuf::from),
+            data: RwLock::new(None),
+            building: Mutex::new(()),
+            state: RwLock::new(PrimingState::Idle),
+            progress: Arc::new(Progress::default()),
+        }
+    }
+
+    // Called before the server starts answering, so readiness fails until priming is done
+    pub fn begin_priming(&self) {
+        *self.state.write().unwrap() = PrimingState::Priming;
+    }
+
+    pub fn state(&self) -> PrimingState {
+        self.state.read().unwrap().clone()
+    }
+
+    // Of the pattern matrix being built, or the last one built
+    pub fn progress_percent(&self) -> usize {
+        self.progress.percent()
+    }
+
+    pub fn latest(&self) -> Option<Arc<SolverData>> {
+        self.data.read().unwrap().clone()
+    }
+
+    pub async fn prime(&self, words: &dyn WordRepository) -> Result<Arc<SolverData>, AppError> {
+        self.begin_priming();
+        let started = Instant::now();
+
+        match self.current(words).await {
+            Ok(data) => {
+                info!(version = data.version, guesses = data.guesses.len(), answers = data.answers.len(), source = ?data.source, elapsed_ms = started.elapsed().as_millis() as u64, "Solver cache primed");
+                *self.state.write().unwrap() = PrimingState::Ready;
+                Ok(data)
+            }
+            Err(error) => {
+                *self.state.write().unwrap() = PrimingState::Failed(error.to_string());
+                Err(error)
+            }
+        }
+    }
+
+    // For the word list as it is now, built first if the list changed since
+    pub async fn current(&self, words: &dyn WordRepository) -> Result<Arc<SolverData>, AppError> {
+        let version = words.version().await?;
+        if let Some(data) = self.built_for(version) {
+            return Ok(data);
+        }
+
+        let _building = self.building.lock().await;
+        if let Some(data) = self.built_for(version) {
+            return Ok(data);
+        }
+
+        let mut guesses: Vec<String> = words.all_words().await?.into_iter().filter(|word| word.len() == WORD_LENGTH).collect();
+        guesses.sort_unstable();
+        guesses.dedup();
+        let mut answers: Vec<String> = words.answer_words().await?.into_iter().filter(|word| word.len() == WORD_LENGTH).collect();
+        answers.sort_unstable();
+        answers.dedup();
+
+        // CPU bound for seconds on a full list, kept off the async workers
+        let (path, progress) = (self.path.clone(), self.progress.clone());
+        let data = rt::task::spawn_blocking(move || build(version, guesses, answers, path.as_deref(), &progress))
+            .await
+            .map_err(|error| AppError::internal(format!("Building the solver cache failed: {}", error)))?;
+
+        let data = Arc::new(data);
+        *self.data.write().unwrap() = Some(data.clone());
+        Ok(data)
+    }
+
+    fn built_for(&self, version: i64) -> Option<Arc<SolverData>> {
+        self.latest().filter(|data| data.version == version)
+    }
+}
+
+// Why a cache file wasn't used
+enum FileFault {
+    Missing,
+    Stale(String),
+    Corrupt(String),
+}
+
+fn build(version: i64, guesses: Vec<String>, answers: Vec<String>, path: Option<&Path>, progress: &Progress) -> SolverData {
+    let hash = list_hash(&guesses, &answers);
+    let guess_words: Vec<&str> = guesses.iter().map(String::as_str).collect();
+
+    // The opening ranking is worked out alongside the matrix
+    let (patterns, source, starters) = thread::scope(|scope| {
+        let starters = scope.spawn(|| {
+            suggest(&guess_words, &guess_words, STARTER_COUNT)
+                .into_iter()
+                .map(|suggestion| (suggestion.word.to_string(), suggestion.score))
+                .collect::<Vec<_>>()
+        });
+
+        let (patterns, source) = match path {
+            None => (compute_patterns(&guesses, &answers, progress), MatrixSource::Computed),
+            Some(path) => match read_matrix(path, &hash, guesses.len(), answers.len()) {
+                Ok(patterns) => {
+                    progress.start(0);
+                    (patterns, MatrixSource::Loaded)
+                }
+                Err(fault) => {
+                    let source = match fault {
+                        FileFault::Missing => MatrixSource::Computed,
+                        FileFault::Stale(reason) => {
+                            warn!("Solver cache file {} is stale, {}. Rebuilding it.", path.display(), reason);
+                            MatrixSource::RebuiltStale
+                        }
+                        FileFault::Corrupt(reason) => {
+                            warn!("Solver cache file {} is corrupt, {}. Rebuilding it.", path.display(), reason);
+                            MatrixSource::RebuiltCorrupt
+                        }
+                    };
+                    let patterns = compute_patterns(&guesses, &answers, progress);
+                    if let Err(error) = write_matrix(path, &hash, guesses.len(), answers.len(), &patterns) {
+                        warn!("Couldn't write the solver cache file {}: {}", path.display(), error);
+                    }
+                    (patterns, source)
+                }
+            },
+        };
+
+        (patterns, source, starters.join().expect("ranking the opening guesses panicked"))
+    });
+
+    let index = |words: &[String]| words.iter().enumerate().map(|(position, word)| (word.clone(), position)).collect();
+    let matrix = PatternMatrix { guesses: index(&guesses), answers: index(&answers), patterns };
+    SolverData { version, guesses, answers, matrix, starters, source }
+}
+
+// Rows are split between the available cores
+fn compute_patterns(guesses: &[String], answers: &[String], progress: &Progress) -> Vec<u8> {
+    let width = answers.len();
+    let mut patterns = vec![0u8; guesses.len() * width];
+    progress.start(guesses.len());
+    if width == 0 || guesses.is_empty() {
+        return patterns;
+    }
+
+    let threads = thread::available_parallelism().map_or(1, usize::from);
+    let rows_per_thread = guesses.len().div_ceil(threads);
+    thread::scope(|scope| {
+        for (rows, chunk) in guesses.chunks(rows_per_thread).zip(patterns.chunks_mut(rows_per_thread * width)) {
+            scope.spawn(move || {
+                for (guess, row) in rows.iter().zip(chunk.chunks_mut(width)) {
+                    for (cell, answer) in row.iter_mut().zip(answers) {
+                        *cell = pattern_code(&feedback(guess, answer));
+                    }
+                    progress.row_done();
+                }
+            });
+        }
+    });
+
+    patterns
+}
+
+// Identifies the lists a matrix was built from, in order
+fn list_hash(guesses: &[String], answers: &[String]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(guesses.join("\n"));
+    hasher.update([0]);
+    hasher.update(answers.join("\n"));
+    hasher.finalize().into()
+}
+
+fn read_matrix(path: &Path, hash: &[u8; 32], rows: usize, columns: usize) -> Result<Vec<u8>, FileFault> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(FileFault::Missing),
+        Err(error) => return Err(FileFault::Corrupt(error.to_string())),
+    };
+
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+        return Err(FileFault::Corrupt("it isn't a pattern matrix file".to_string()));
+    }
+    let number = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap());
+    let format = number(4);
+    if format != FORMAT_VERSION {
+        return Err(FileFault::Stale(format!("it's in format {}, not {}", format, FORMAT_VERSION)));
+    }
+    if &bytes[8..40] != hash {
+        return Err(FileFault::Stale("it was built from another word list".to_string()));
+    }
+    if (number(40) as usize, number(44) as usize) != (rows, columns) {
+        return Err(FileFault::Corrupt("its size doesn't match the word list".to_string()));
+    }
+
+    let end = HEADER_LEN + rows * columns;
+    if bytes.len() != end + CHECKSUM_LEN {
+        return Err(FileFault::Corrupt(format!("it's {} bytes, not {}", bytes.len(), end + CHECKSUM_LEN)));
+    }
+    let patterns = &bytes[HEADER_LEN..end];
+    if Sha256::digest(patterns)[..] != bytes[end..] {
+        return Err(FileFault::Corrupt("its checksum doesn't match".to_string()));
+    }
+
+    Ok(patterns.to_vec())
+}
+
+// Written next to the file and renamed over it, so a crash never leaves half a file behind
+fn write_matrix(path: &Path, hash: &[u8; 32], rows: usize, columns: usize, patterns: &[u8]) -> io::Result<()> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + patterns.len() + CHECKSUM_LEN);
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(hash);
+    bytes.extend_from_slice(&(rows as u32).to_le_bytes());
+    bytes.extend_from_slice(&(columns as u32).to_le_bytes());
+    bytes.extend_from_slice(patterns);
+    bytes.extend_from_slice(&Sha256::digest(patterns));
+
+    let partial = path.with_extension("partial");
+    fs::write(&partial, &bytes)?;
+    fs::rename(&partial, path)
+}
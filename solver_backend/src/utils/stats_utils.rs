@@ -0,0 +1,84 @@
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sqlx::AnyPool;
+use std::collections::HashMap;
+use std::time::Instant;
+use tracing::{info, warn};
+use crate::models::game_models::{SessionStats, UserStats};
+use crate::repositories::{game_sessions, user_stats};
+
+pub const STATS_RECOMPUTE: &str = "stats_recompute";
+// Users rebuilt per transaction, so a recompute never holds many rows locked at once
+pub const STATS_BATCH_SIZE: i64 = 100;
+// The report lists this many, `repaired` counts them all
+const MAX_REPORTED_DISCREPANCIES: usize = 100;
+
+// A user whose stored stats disagreed with their sessions, as (user_id, stored, rebuilt)
+type Discrepancy = (i32, UserStats, UserStats);
+
+// Rebuilds every user's stats from their sessions, `batch_size` users at a time, and
+// overwrites the stored ones that disagree. The report puts what was stored next to what
+// the sessions add up to, for tracking down whatever let them drift apart.
+pub async fn recompute_stats(db: &AnyPool, batch_size: i64, now: DateTime<Utc>) -> Result<Value, String> {
+    let started = Instant::now();
+    let (mut users, mut batches, mut repaired) = (0, 0, 0);
+    let mut discrepancies = Vec::new();
+    let mut after_id = 0;
+
+    loop {
+        let ids = user_stats::user_ids_after(db, after_id, batch_size).await.map_err(|error| error.to_string())?;
+        after_id = match ids.last() {
+            Some(last) => *last,
+            None => break,
+        };
+
+        for (user_id, stored, rebuilt) in recompute_batch(db, &ids, now).await.map_err(|error| error.to_string())? {
+            warn!("Stats of user {} disagreed with their sessions and were rebuilt", user_id);
+            repaired += 1;
+            if discrepancies.len() < MAX_REPORTED_DISCREPANCIES {
+                discrepancies.push(json!({
+                    "user_id": user_id,
+                    "stored": SessionStats::from(stored),
+                    "recomputed": SessionStats::from(rebuilt),
+                }));
+            }
+        }
+        users += ids.len();
+        batches += 1;
+    }
+
+    info!("Recomputed the stats of {} users in {} batches, {} were off", users, batches, repaired);
+    Ok(json!({
+        "users": users,
+        "batches": batches,
+        "repaired": repaired,
+        "discrepancies": discrepancies,
+        "elapsed_ms": started.elapsed().as_millis() as u64,
+    }))
+}
+
+// The stats rows are locked before the sessions are read, so a session finishing meanwhile
+// either made it into what's read or waits and adds to the rebuilt row afterwards
+async fn recompute_batch(db: &AnyPool, ids: &[i32], now: DateTime<Utc>) -> Result<Vec<Discrepancy>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    user_stats::lock(&mut tx, ids).await?;
+
+    let mut rebuilt: HashMap<i32, UserStats> = HashMap::new();
+    for (user_id, max_guesses, status, guesses) in game_sessions::outcomes_of(&mut tx, ids).await? {
+        rebuilt.entry(user_id).or_default().add(&UserStats::of_session(max_guesses, &status, guesses));
+    }
+    let mut stored = user_stats::find_many(&mut tx, ids).await?;
+
+    let mut differing = Vec::new();
+    for id in ids {
+        let stored = stored.remove(id).unwrap_or_default();
+        let rebuilt = rebuilt.remove(id).unwrap_or_default();
+        if stored != rebuilt {
+            user_stats::replace(&mut tx, *id, &rebuilt, now).await?;
+            differing.push((*id, stored, rebuilt));
+        }
+    }
+    tx.commit().await?;
+
+    Ok(differing)
+}
@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+use crate::config::Settings;
+use crate::utils::cache_utils::TtlCache;
+
+const MAX_RATE_LIMIT_KEYS: usize = 100_000;
+
+// The backing store couldn't be reached or answered with something unexpected. Callers
+// decide whether that lets the request through or not.
+#[derive(Debug)]
+pub struct StoreError(pub String);
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+// Remembers which tokens have been checked against revoked_tokens. The table stays the
+// source of truth, this only saves the lookup.
+#[async_trait]
+pub trait RevocationStore: Send + Sync {
+    // None when the token hasn't been seen
+    async fn is_revoked(&self, token: &str) -> Result<Option<bool>, StoreError>;
+    async fn record(&self, token: &str, revoked: bool) -> Result<(), StoreError>;
+}
+
+// Where a key stands after a hit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitHit {
+    // Hits so far in the current window, this one included
+    pub hits: u64,
+    // Until the window ends and the count starts over
+    pub resets_in: Duration,
+}
+
+// Fixed window counters
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    // Counts a hit against `key`
+    async fn hit(&self, key: &str, window: Duration) -> Result<RateLimitHit, StoreError>;
+}
+
+// Serialized responses of expensive read-only queries
+#[async_trait]
+pub trait ResultCache: Send + Sync {
+    async fn get(&self, key: &str) -> Result<Option<String>, StoreError>;
+    async fn put(&self, key: &str, value: &str) -> Result<(), StoreError>;
+}
+
+// State that has to be shared between replicas to behave correctly. Kept in process
+// unless REDIS_URL points at a Redis every replica can reach.
+#[derive(Clone)]
+pub struct Stores {
+    pub revocations: Arc<dyn RevocationStore>,
+    pub rate_limits: Arc<dyn RateLimitStore>,
+    pub results: Arc<dyn ResultCache>,
+    // Whether a failed revocation check lets the token through
+    pub revocation_fail_open: bool,
+    // Whether a failed rate limit check lets the request through
+    pub rate_limit_fail_open: bool,
+}
+
+impl Stores {
+    pub fn from_settings(settings: &Settings) -> Self {
+        let stores = match &settings.redis_url {
+            #[cfg(feature = "redis")]
+            Some(url) => {
+                let redis = Arc::new(crate::utils::redis_utils::RedisStore::new(url, settings));
+                (redis.clone() as Arc<dyn RevocationStore>, redis.clone() as Arc<dyn RateLimitStore>, redis as Arc<dyn ResultCache>)
+            }
+            _ => (
+                Arc::new(MemoryRevocationStore::new(settings.auth_cache_ttl)) as Arc<dyn RevocationStore>,
+                Arc::new(MemoryRateLimitStore::default()) as Arc<dyn RateLimitStore>,
+                Arc::new(MemoryResultCache::new(settings.result_cache_ttl)) as Arc<dyn ResultCache>,
+            ),
+        };
+
+        Stores {
+            revocations: stores.0,
+            rate_limits: stores.1,
+            results: stores.2,
+            revocation_fail_open: settings.revocation_fail_open,
+            rate_limit_fail_open: settings.rate_limit_fail_open,
+        }
+    }
+
+    // Counts a hit on `key`. None when the store failed, the caller decides with
+    // `rate_limit_fail_open` whether the request goes through.
+    pub async fn rate_limit_hit(&self, key: &str, window: Duration) -> Option<RateLimitHit> {
+        match self.rate_limits.hit(key, window).await {
+            Ok(hit) => Some(hit),
+            Err(error) => {
+                warn!("Rate limit check for {} failed: {}", key, error);
+                None
+            }
+        }
+    }
+
+    // Lookups that fail count as misses, the caller just does the work again
+    pub async fn cached_result(&self, key: &str) -> Option<String> {
+        match self.results.get(key).await {
+            Ok(value) => value,
+            Err(error) => {
+                warn!("Result cache lookup failed: {}", error);
+                None
+            }
+        }
+    }
+
+    pub async fn cache_result(&self, key: &str, value: &str) {
+        if let Err(error) = self.results.put(key, value).await {
+            warn!("Result cache write failed: {}", error);
+        }
+    }
+}
+
+pub struct MemoryRevocationStore {
+    pub tokens: TtlCache<String, bool>,
+}
+
+impl MemoryRevocationStore {
+    pub fn new(ttl: Duration) -> Self {
+        MemoryRevocationStore { tokens: TtlCache::new(ttl) }
+    }
+}
+
+#[async_trait]
+impl RevocationStore for MemoryRevocationStore {
+    async fn is_revoked(&self, token: &str) -> Result<Option<bool>, StoreError> {
+        Ok(self.tokens.get(&token.to_string()))
+    }
+
+    async fn record(&self, token: &str, revoked: bool) -> Result<(), StoreError> {
+        self.tokens.insert(token.to_string(), revoked);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryRateLimitStore {
+    // key -> (hits, window start)
+    windows: Mutex<HashMap<String, (u64, Instant)>>,
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryRateLimitStore {
+    async fn hit(&self, key: &str, window: Duration) -> Result<RateLimitHit, StoreError> {
+        let mut windows = self.windows.lock().unwrap();
+
+        if windows.len() >= MAX_RATE_LIMIT_KEYS {
+            windows.retain(|_, (_, started)| started.elapsed() < window);
+        }
+
+        let entry = windows.entry(key.to_string()).or_insert((0, Instant::now()));
+        if entry.1.elapsed() >= window {
+            *entry = (0, Instant::now());
+        }
+        entry.0 += 1;
+
+        Ok(RateLimitHit { hits: entry.0, resets_in: window.saturating_sub(entry.1.elapsed()) })
+    }
+}
+
+pub struct MemoryResultCache {
+    results: TtlCache<String, String>,
+}
+
+impl MemoryResultCache {
+    pub fn new(ttl: Duration) -> Self {
+        MemoryResultCache { results: TtlCache::new(ttl) }
+    }
+}
+
+#[async_trait]
+impl ResultCache for MemoryResultCache {
+    async fn get(&self, key: &str) -> Result<Option<String>, StoreError> {
+        Ok(self.results.get(&key.to_string()))
+    }
+
+    async fn put(&self, key: &str, value: &str) -> Result<(), StoreError> {
+        self.results.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+}
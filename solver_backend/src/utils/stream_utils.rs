@@ -0,0 +1,190 @@
+use actix_web::web::Bytes;
+use futures::stream::{self, Stream};
+use serde::Serialize;
+use sqlx::any::AnyRow;
+use sqlx::{AnyPool, FromRow, Row};
+use std::future::Future;
+
+const PAGE_SIZE: i64 = 500;
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// One array in a streamed document. The query must take the owner id as $1, the last
+// seen id as $2 and a page size as $3, and return the row `id` among its columns.
+// `render` turns each row into its JSON, usually `json_row::<SomeModel>`.
+pub struct JsonSection {
+    pub name: &'static str,
+    pub query: &'static str,
+    pub render: fn(&AnyRow) -> Result<String, sqlx::Error>,
+}
+
+pub fn json_row<T>(row: &AnyRow) -> Result<String, sqlx::Error>
+where
+    T: for<'r> FromRow<'r, AnyRow> + Serialize,
+{
+    serde_json::to_string(&T::from_row(row)?).map_err(|error| sqlx::Error::Decode(Box::new(error)))
+}
+
+struct DocumentState {
+    pool: AnyPool,
+    owner_id: i32,
+    sections: Vec<JsonSection>,
+    prefix: Option<String>,
+    section: usize,
+    last_id: i32,
+    opened: bool,
+    wrote_item: bool,
+    done: bool,
+}
+
+// Streams `prefix` followed by each section as a JSON array, fetching rows a page at a
+// time with keyset pagination so large histories never sit in memory in full. `prefix`
+// must be an unterminated JSON object, e.g. `{"profile":{...}`; the stream closes it.
+pub fn paged_json_document(pool: AnyPool, owner_id: i32, prefix: String, sections: Vec<JsonSection>) -> impl Stream<Item = Result<Bytes, sqlx::Error>> {
+    let state = DocumentState {
+        pool,
+        owner_id,
+        sections,
+        prefix: Some(prefix),
+        section: 0,
+        last_id: 0,
+        opened: false,
+        wrote_item: false,
+        done: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        if state.done {
+            return None;
+        }
+
+        if let Some(prefix) = state.prefix.take() {
+            return Some((Ok(Bytes::from(prefix)), state));
+        }
+
+        if state.section >= state.sections.len() {
+            state.done = true;
+            return Some((Ok(Bytes::from_static(b"}")), state));
+        }
+
+        let section = &state.sections[state.section];
+        let rows = sqlx::query(section.query)
+            .bind(state.owner_id)
+            .bind(state.last_id)
+            .bind(PAGE_SIZE)
+            .fetch_all(&state.pool)
+            .await;
+
+        let rows = match rows {
+            Ok(rows) => rows,
+            Err(error) => {
+                state.done = true;
+                return Some((Err(error), state));
+            }
+        };
+
+        let mut chunk = String::new();
+        if !state.opened {
+            chunk.push_str(&format!(",\"{}\":[", section.name));
+            state.opened = true;
+        }
+
+        for row in &rows {
+            let doc = match (section.render)(row) {
+                Ok(doc) => doc,
+                Err(error) => {
+                    state.done = true;
+                    return Some((Err(error), state));
+                }
+            };
+
+            if state.wrote_item {
+                chunk.push(',');
+            }
+            state.wrote_item = true;
+            state.last_id = row.get("id");
+            chunk.push_str(&doc);
+        }
+
+        if (rows.len() as i64) < PAGE_SIZE {
+            chunk.push(']');
+            state.section += 1;
+            state.last_id = 0;
+            state.opened = false;
+            state.wrote_item = false;
+        }
+
+        Some((Ok(Bytes::from(chunk)), state))
+    })
+}
+
+// A line of a streamed CSV. Ids increase down the file, the next page is fetched after
+// the last one written.
+pub trait CsvRow {
+    fn id(&self) -> i32;
+    fn record(&self) -> Vec<String>;
+}
+
+fn csv_chunk<I, R>(records: I) -> Result<Vec<u8>, sqlx::Error>
+where
+    I: IntoIterator<Item = R>,
+    R: IntoIterator,
+    R::Item: AsRef<[u8]>,
+{
+    let mut writer = csv::WriterBuilder::new().terminator(csv::Terminator::CRLF).from_writer(Vec::new());
+    for record in records {
+        writer.write_record(record).map_err(|error| sqlx::Error::Io(error.into()))?;
+    }
+    writer.into_inner().map_err(|error| sqlx::Error::Io(error.into_error()))
+}
+
+struct CsvState<F> {
+    fetch: F,
+    head: Option<Result<Vec<u8>, sqlx::Error>>,
+    last_id: i32,
+    done: bool,
+}
+
+// Streams `header` and then the rows `fetch(after_id, limit)` returns, a page at a time
+// like `paged_json_document`, quoting fields as CSV needs. A failed fetch ends the stream
+// with the error after the rows already sent.
+pub fn paged_csv<T, F, Fut>(header: &[&str], bom: bool, fetch: F) -> impl Stream<Item = Result<Bytes, sqlx::Error>>
+where
+    T: CsvRow,
+    F: Fn(i32, i64) -> Fut,
+    Fut: Future<Output = Result<Vec<T>, sqlx::Error>>,
+{
+    let head = csv_chunk([header]).map(|line| if bom { [UTF8_BOM, &line].concat() } else { line });
+    let state = CsvState { fetch, head: Some(head), last_id: 0, done: false };
+
+    stream::unfold(state, |mut state| async move {
+        if state.done {
+            return None;
+        }
+
+        if let Some(head) = state.head.take() {
+            state.done = head.is_err();
+            return Some((head.map(Bytes::from), state));
+        }
+
+        let rows = match (state.fetch)(state.last_id, PAGE_SIZE).await {
+            Ok(rows) => rows,
+            Err(error) => {
+                state.done = true;
+                return Some((Err(error), state));
+            }
+        };
+        state.done = (rows.len() as i64) < PAGE_SIZE;
+        let last = rows.last()?.id();
+
+        match csv_chunk(rows.iter().map(CsvRow::record)) {
+            Ok(chunk) => {
+                state.last_id = last;
+                Some((Ok(Bytes::from(chunk)), state))
+            }
+            Err(error) => {
+                state.done = true;
+                Some((Err(error), state))
+            }
+        }
+    })
+}
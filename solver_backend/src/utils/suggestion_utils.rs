@@ -0,0 +1,93 @@
+use crate::errors::{AppError, ErrorCode};
+use crate::models::game_models::{SuggestionAction, SuggestionStatus, WordSuggestion};
+use crate::repositories::suggestions::{self, Review};
+use crate::repositories::words::{self, NewRevision};
+use chrono::{DateTime, Utc};
+use sqlx::{Any, AnyPool, Executor};
+
+// Why `action` wouldn't change the list as it is now, None when it would
+pub async fn unchanged_by<'e>(db: impl Executor<'e, Database = Any>, word: &str, action: SuggestionAction) -> Result<Option<String>, sqlx::Error> {
+    let is_answer = words::is_answer(db, word).await?;
+
+    Ok(match (action, is_answer) {
+        (SuggestionAction::Add, Some(_)) => Some(format!("{} is already in the word list", word)),
+        (SuggestionAction::Add, None) => None,
+        (_, None) => Some(format!("{} isn't in the word list", word)),
+        (SuggestionAction::MarkAnswer, Some(true)) => Some(format!("{} can already be an answer", word)),
+        (SuggestionAction::UnmarkAnswer, Some(false)) => Some(format!("{} already can't be an answer", word)),
+        _ => None,
+    })
+}
+
+// Makes the change and records it as a word list revision, in one transaction with
+// marking the suggestion approved. If the list already changed that way since it was
+// proposed, nothing is written and the suggestion stays pending for an admin to reject.
+pub async fn approve(pool: &AnyPool, id: i32, admin_id: i32, note: Option<&str>, now: DateTime<Utc>) -> Result<WordSuggestion, AppError> {
+    let mut tx = pool.begin().await?;
+    let suggestion = pending(suggestions::find_for_review(&mut tx, id, pool.any_kind()).await?)?;
+    let action = SuggestionAction::parse(&suggestion.action).ok_or_else(|| AppError::internal(format!("suggestion {} has unknown action {}", id, suggestion.action)))?;
+
+    let from_version = words::lock_version(&mut tx, pool.any_kind()).await?;
+    if let Some(reason) = unchanged_by(&mut tx, &suggestion.word, action).await? {
+        return Err(AppError::conflict(ErrorCode::SuggestionNoChange, reason));
+    }
+
+    let (added, removed) = match action {
+        SuggestionAction::Add => {
+            words::add_word(&mut tx, &suggestion.word).await?;
+            (1, 0)
+        }
+        SuggestionAction::Remove => {
+            words::remove_word(&mut tx, &suggestion.word).await?;
+            (0, 1)
+        }
+        SuggestionAction::MarkAnswer | SuggestionAction::UnmarkAnswer => {
+            words::set_answer(&mut tx, &suggestion.word, action == SuggestionAction::MarkAnswer).await?;
+            (0, 0)
+        }
+    };
+
+    let word_count = words::count_words(&mut tx).await?;
+    let revision = NewRevision {
+        source_url: &format!("suggestion:{}", id),
+        added,
+        removed,
+        word_count: word_count as i32,
+        etag: None,
+        last_modified: None,
+        synced_by: Some(admin_id),
+        from_version,
+        to_version: words::version(&mut tx).await?,
+    };
+    let revision_id = words::insert_revision(&mut tx, &revision, now).await?;
+    if added + removed > 0 {
+        words::record_change(&mut tx, revision_id, &suggestion.word, added > 0).await?;
+    }
+
+    let review = Review { status: SuggestionStatus::Approved, reviewed_by: admin_id, note, revision_id: Some(revision_id) };
+    suggestions::record_review(&mut tx, id, &review, now).await?;
+    let approved = suggestions::find(&mut tx, id).await?.ok_or_else(|| AppError::internal(format!("suggestion {} vanished", id)))?;
+    tx.commit().await?;
+
+    Ok(approved)
+}
+
+pub async fn reject(pool: &AnyPool, id: i32, admin_id: i32, note: Option<&str>, now: DateTime<Utc>) -> Result<WordSuggestion, AppError> {
+    let mut tx = pool.begin().await?;
+    pending(suggestions::find_for_review(&mut tx, id, pool.any_kind()).await?)?;
+
+    let review = Review { status: SuggestionStatus::Rejected, reviewed_by: admin_id, note, revision_id: None };
+    suggestions::record_review(&mut tx, id, &review, now).await?;
+    let rejected = suggestions::find(&mut tx, id).await?.ok_or_else(|| AppError::internal(format!("suggestion {} vanished", id)))?;
+    tx.commit().await?;
+
+    Ok(rejected)
+}
+
+fn pending(suggestion: Option<WordSuggestion>) -> Result<WordSuggestion, AppError> {
+    let suggestion = suggestion.ok_or_else(|| AppError::not_found("No suggestion with that id"))?;
+    if suggestion.status != SuggestionStatus::Pending.as_str() {
+        return Err(AppError::conflict(ErrorCode::SuggestionAlreadyReviewed, format!("The suggestion was already {}", suggestion.status)));
+    }
+    Ok(suggestion)
+}
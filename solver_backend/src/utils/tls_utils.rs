@@ -0,0 +1,184 @@
+use actix_web::rt::{self, task::JoinHandle};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::{self, CertifiedKey};
+use rustls::{Certificate, PrivateKey, ServerConfig, SignatureScheme};
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info};
+use crate::utils::shutdown_utils::{wait_for_shutdown, ShutdownSignal};
+
+// The certificate served on the TLS listener. Handshakes read whichever was loaded last,
+// so a renewed certificate takes effect for new connections without a restart.
+pub struct ReloadingCertificate {
+    cert_path: String,
+    key_path: String,
+    current: RwLock<Arc<CertifiedKey>>,
+    // Modification times of the files behind `current`
+    loaded_from: Mutex<Option<(SystemTime, SystemTime)>>,
+}
+
+impl ReloadingCertificate {
+    // Fails with a message naming the file when either can't be read or parsed, or when
+    // the key doesn't belong to the certificate
+    pub fn load(cert_path: &str, key_path: &str) -> Result<Arc<Self>, String> {
+        let loaded_from = modified(cert_path, key_path);
+        let key = certified_key(cert_path, key_path)?;
+
+        Ok(Arc::new(ReloadingCertificate {
+            cert_path: cert_path.to_string(),
+            key_path: key_path.to_string(),
+            current: RwLock::new(Arc::new(key)),
+            loaded_from: Mutex::new(loaded_from),
+        }))
+    }
+
+    // Swaps in the certificate now on disk. On failure the previous one keeps being served.
+    pub fn reload(&self) -> Result<(), String> {
+        let loaded_from = modified(&self.cert_path, &self.key_path);
+        let key = certified_key(&self.cert_path, &self.key_path)?;
+
+        *self.current.write().unwrap() = Arc::new(key);
+        *self.loaded_from.lock().unwrap() = loaded_from;
+        Ok(())
+    }
+
+    // Whether either file was written since the last successful load
+    pub fn changed_on_disk(&self) -> bool {
+        modified(&self.cert_path, &self.key_path) != *self.loaded_from.lock().unwrap()
+    }
+
+    pub fn current(&self) -> Arc<CertifiedKey> {
+        self.current.read().unwrap().clone()
+    }
+}
+
+impl ResolvesServerCert for ReloadingCertificate {
+    fn resolve(&self, _client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.current())
+    }
+}
+
+pub fn server_config(certificate: Arc<ReloadingCertificate>) -> ServerConfig {
+    ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_cert_resolver(certificate)
+}
+
+// Reloads the certificate on SIGHUP, and every `watch_interval` when the files have
+// changed, e.g. after certbot renewed them. Runs until shutdown.
+pub fn spawn_certificate_reloader(certificate: Arc<ReloadingCertificate>, watch_interval: Option<Duration>, mut shutdown: ShutdownSignal) -> JoinHandle<()> {
+    rt::spawn(async move {
+        #[cfg(unix)]
+        let mut hangup = rt::signal::unix::signal(rt::signal::unix::SignalKind::hangup()).ok();
+        #[cfg(not(unix))]
+        let mut hangup = None;
+        // Only polled when watching, the first tick completes straight away
+        let mut interval = rt::time::interval(watch_interval.unwrap_or(Duration::from_secs(60)));
+        interval.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = interval.tick(), if watch_interval.is_some() => {
+                    if certificate.changed_on_disk() {
+                        reload(&certificate, "its files changed");
+                    }
+                }
+                _ = hangup_received(&mut hangup) => reload(&certificate, "SIGHUP"),
+                _ = wait_for_shutdown(&mut shutdown) => break,
+            }
+        }
+
+        info!("Certificate reloader stopped");
+    })
+}
+
+fn reload(certificate: &ReloadingCertificate, reason: &str) {
+    match certificate.reload() {
+        Ok(()) => info!("Reloaded the TLS certificate after {}", reason),
+        // A renewal may have replaced one file but not yet the other, the next change or
+        // SIGHUP tries again
+        Err(error) => error!("Kept the previous TLS certificate, reloading after {} failed: {}", reason, error),
+    }
+}
+
+#[cfg(unix)]
+async fn hangup_received(hangup: &mut Option<rt::signal::unix::Signal>) {
+    match hangup {
+        Some(hangup) => {
+            hangup.recv().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(not(unix))]
+async fn hangup_received(_: &mut Option<()>) {
+    std::future::pending().await
+}
+
+fn modified(cert_path: &str, key_path: &str) -> Option<(SystemTime, SystemTime)> {
+    let modified = |path: &str| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+    Some((modified(cert_path)?, modified(key_path)?))
+}
+
+fn certified_key(cert_path: &str, key_path: &str) -> Result<CertifiedKey, String> {
+    let chain = read_certificates(cert_path)?;
+    let key = read_private_key(key_path)?;
+    let signing_key = sign::any_supported_type(&key).map_err(|_| format!("{} holds a private key type TLS can't use", key_path))?;
+
+    check_key_matches(&chain[0], &*signing_key).map_err(|problem| format!("{} doesn't belong to the certificate in {}: {}", key_path, cert_path, problem))?;
+    Ok(CertifiedKey::new(chain, signing_key))
+}
+
+fn read_certificates(path: &str) -> Result<Vec<Certificate>, String> {
+    let file = File::open(path).map_err(|error| format!("Can't read TLS certificate {}: {}", path, error))?;
+    let chain = rustls_pemfile::certs(&mut BufReader::new(file)).map_err(|error| format!("Can't parse TLS certificate {}: {}", path, error))?;
+
+    if chain.is_empty() {
+        return Err(format!("{} contains no PEM certificate", path));
+    }
+    Ok(chain.into_iter().map(Certificate).collect())
+}
+
+// PKCS#8, as certbot writes them, or the older RSA and EC formats
+fn read_private_key(path: &str) -> Result<PrivateKey, String> {
+    let file = File::open(path).map_err(|error| format!("Can't read TLS private key {}: {}", path, error))?;
+    let mut reader = BufReader::new(file);
+
+    loop {
+        match rustls_pemfile::read_one(&mut reader).map_err(|error| format!("Can't parse TLS private key {}: {}", path, error))? {
+            Some(rustls_pemfile::Item::PKCS8Key(key) | rustls_pemfile::Item::RSAKey(key) | rustls_pemfile::Item::ECKey(key)) => return Ok(PrivateKey(key)),
+            Some(_) => continue,
+            None => return Err(format!("{} contains no PEM private key", path)),
+        }
+    }
+}
+
+// Signs a probe with the key and verifies it against the certificate's public key, which
+// is what a client would do during the handshake
+fn check_key_matches(certificate: &Certificate, key: &dyn sign::SigningKey) -> Result<(), String> {
+    const PROBE: &[u8] = b"wordle-solver certificate check";
+    let schemes = [
+        SignatureScheme::ECDSA_NISTP256_SHA256,
+        SignatureScheme::ECDSA_NISTP384_SHA384,
+        SignatureScheme::ED25519,
+        SignatureScheme::RSA_PSS_SHA256,
+    ];
+
+    let signer = key.choose_scheme(&schemes).ok_or_else(|| "the key's algorithm isn't supported".to_string())?;
+    let algorithm = match signer.scheme() {
+        SignatureScheme::ECDSA_NISTP256_SHA256 => &webpki::ECDSA_P256_SHA256,
+        SignatureScheme::ECDSA_NISTP384_SHA384 => &webpki::ECDSA_P384_SHA384,
+        SignatureScheme::ED25519 => &webpki::ED25519,
+        _ => &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    };
+    let signature = signer.sign(PROBE).map_err(|error| error.to_string())?;
+
+    webpki::EndEntityCert::try_from(certificate.0.as_slice())
+        .map_err(|error| format!("the certificate can't be parsed ({:?})", error))?
+        .verify_signature(algorithm, PROBE, &signature)
+        .map_err(|_| "the public keys differ".to_string())
+}
@@ -0,0 +1,68 @@
+use std::time::Instant;
+use tracing::Span;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+use crate::config::OtlpSettings;
+
+// Logs to stdout as filtered by RUST_LOG, and with `otlp` set also ships every span to the
+// collector. Without it there's no exporter at all, a span costs only its creation. Also
+// picks up records from the log crate, so actix's Logger lines land in the request span.
+pub fn init_tracing(otlp: Option<&OtlpSettings>) -> Result<(), String> {
+    let registry = tracing_subscriber::registry().with(EnvFilter::from_default_env()).with(fmt::layer());
+
+    #[cfg(feature = "otlp")]
+    if let Some(otlp) = otlp {
+        let tracer = otlp_tracer(otlp).map_err(|error| format!("Failed to set up the OTLP exporter: {}", error))?;
+        registry.with(tracing_opentelemetry::layer().with_tracer(tracer)).init();
+        return Ok(());
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    let _ = otlp;
+    registry.init();
+    Ok(())
+}
+
+// Spans are batched and sent from a thread of their own, since actix runs each worker on
+// a single threaded runtime the exporter would otherwise block on shutdown
+#[cfg(feature = "otlp")]
+fn otlp_tracer(otlp: &OtlpSettings) -> Result<opentelemetry::sdk::trace::Tracer, opentelemetry::trace::TraceError> {
+    use opentelemetry::sdk::{trace, Resource};
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig;
+
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_endpoint(&otlp.endpoint))
+        .with_trace_config(trace::config().with_resource(Resource::new([KeyValue::new("service.name", otlp.service_name.clone())])))
+        .install_batch(opentelemetry::runtime::TokioCurrentThread)
+}
+
+// Sends the spans still waiting in the batch, nothing to do when none are exported
+pub fn shutdown_tracing() {
+    #[cfg(feature = "otlp")]
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+// Records how long the current span's function took as its duration_ms field when dropped,
+// so the log shows it too and not only a trace viewer:
+//
+//     #[instrument(skip_all, fields(duration_ms))]
+//     async fn work() { let _timer = SpanTimer::start(); ... }
+pub struct SpanTimer {
+    span: Span,
+    started: Instant,
+}
+
+impl SpanTimer {
+    pub fn start() -> Self {
+        SpanTimer { span: Span::current(), started: Instant::now() }
+    }
+}
+
+impl Drop for SpanTimer {
+    fn drop(&mut self) {
+        self.span.record("duration_ms", self.started.elapsed().as_secs_f64() * 1000.0);
+    }
+}
@@ -0,0 +1,124 @@
+use actix_web::rt::{self, task::JoinHandle};
+use actix_web::HttpRequest;
+use chrono::{Duration as ChronoDuration, NaiveDate};
+use sqlx::AnyPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{error, info};
+use crate::models::users_models::{RouteUsage, UsageReport};
+use crate::repositories::usage;
+use crate::utils::shutdown_utils::{wait_for_shutdown, ShutdownSignal};
+
+pub const USAGE_FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+// Days of usage a report covers, today included. Older rows are removed by maintenance.
+pub const USAGE_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct UsageKey {
+    user_id: i32,
+    route: String,
+    day: NaiveDate,
+}
+
+// Authenticated calls counted in memory per user, route and day. Recording is a map update
+// under a short lock, the database only sees the counts when they're flushed, so a request
+// never waits on a write.
+#[derive(Default)]
+pub struct UsageRecorder {
+    pending: Mutex<HashMap<UsageKey, i64>>,
+}
+
+impl UsageRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, user_id: i32, route: String, day: NaiveDate) {
+        *self.pending.lock().unwrap().entry(UsageKey { user_id, route, day }).or_insert(0) += 1;
+    }
+
+    // Keyed by the method and matched route pattern, so /users/7 and /users/8 count as one route
+    pub fn record_request(&self, req: &HttpRequest, user_id: i32, day: NaiveDate) {
+        let route = req.match_pattern().unwrap_or_else(|| req.path().to_string());
+        self.record(user_id, format!("{} {}", req.method(), route), day);
+    }
+
+    // Counts for the user that haven't been flushed yet
+    pub fn pending_for(&self, user_id: i32) -> Vec<RouteUsage> {
+        self.pending
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(key, _)| key.user_id == user_id)
+            .map(|(key, calls)| RouteUsage { day: key.day, route: key.route.clone(), calls: *calls })
+            .collect()
+    }
+
+    // The last USAGE_DAYS of the user's calls, what's stored plus what this server hasn't
+    // flushed yet. Counts another replica hasn't flushed, or that a flush is writing at that
+    // moment, show up on a later report.
+    pub async fn report(&self, pool: &AnyPool, user_id: i32, today: NaiveDate) -> Result<UsageReport, sqlx::Error> {
+        let since = today - ChronoDuration::days(USAGE_DAYS - 1);
+        let mut totals: HashMap<(NaiveDate, String), i64> = HashMap::new();
+        let stored = usage::for_user(pool, user_id, since).await?;
+        for entry in stored.into_iter().chain(self.pending_for(user_id)).filter(|entry| entry.day >= since) {
+            *totals.entry((entry.day, entry.route)).or_insert(0) += entry.calls;
+        }
+
+        let mut usage: Vec<RouteUsage> = totals.into_iter().map(|((day, route), calls)| RouteUsage { day, route, calls }).collect();
+        usage.sort_by(|a, b| (a.day, &a.route).cmp(&(b.day, &b.route)));
+
+        Ok(UsageReport { user_id, since, total_calls: usage.iter().map(|entry| entry.calls).sum(), usage })
+    }
+
+    // Writes everything counted so far and returns how many rows it touched. The counts are
+    // taken out before writing, so requests keep recording into an empty map meanwhile. A
+    // failed write puts its counts back for the next flush to retry.
+    pub async fn flush(&self, pool: &AnyPool) -> Result<usize, sqlx::Error> {
+        let counts = std::mem::take(&mut *self.pending.lock().unwrap());
+        if counts.is_empty() {
+            return Ok(0);
+        }
+
+        let written = write_counts(pool, &counts).await;
+        if written.is_err() {
+            let mut pending = self.pending.lock().unwrap();
+            for (key, calls) in counts {
+                *pending.entry(key).or_insert(0) += calls;
+            }
+        }
+        written
+    }
+}
+
+async fn write_counts(pool: &AnyPool, counts: &HashMap<UsageKey, i64>) -> Result<usize, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    for (key, calls) in counts {
+        usage::add_calls(&mut tx, key.user_id, &key.route, key.day, *calls).await?;
+    }
+    tx.commit().await?;
+    Ok(counts.len())
+}
+
+// Flushes every `interval` until shutdown. The last counts are flushed by main once the
+// server has finished draining, since requests still being served record more.
+pub fn spawn_usage_flusher(recorder: Arc<UsageRecorder>, pool: AnyPool, interval: Duration, mut shutdown: ShutdownSignal) -> JoinHandle<()> {
+    rt::spawn(async move {
+        let mut ticks = rt::time::interval(interval);
+        ticks.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {
+                    if let Err(error) = recorder.flush(&pool).await {
+                        error!("Failed to write API usage, keeping it for the next flush: {}", error);
+                    }
+                }
+                _ = wait_for_shutdown(&mut shutdown) => break,
+            }
+        }
+
+        info!("API usage flusher stopped");
+    })
+}
@@ -0,0 +1,60 @@
+use tracing::error;
+use sqlx::AnyPool;
+
+// Names that would let someone pass as staff or the system
+const RESERVED_USERNAMES: &[&str] = &[
+    "admin", "administrator", "root", "system", "support", "moderator", "mod", "staff",
+    "help", "security", "api", "null", "undefined", "wordle", "solver", "official",
+];
+
+// Rejected anywhere inside a username, not just as the whole name
+const BLOCKED_SUBSTRINGS: &[&str] = &["fuck", "shit", "cunt", "bitch", "nigger", "faggot", "whore"];
+
+// Folds a username to the form it is compared in: lowercase, common leetspeak digits
+// and symbols mapped back to letters, and separators dropped, so "A-d-m-1-n" and
+// "4dmin" both become "admin"
+pub fn normalize_username(username: &str) -> String {
+    username
+        .to_lowercase()
+        .chars()
+        .map(|c| match c {
+            '4' | '@' => 'a',
+            '3' => 'e',
+            '1' | '!' | '|' => 'i',
+            '0' => 'o',
+            '5' | '$' => 's',
+            '7' => 't',
+            _ => c,
+        })
+        .filter(|c| c.is_alphanumeric())
+        .collect()
+}
+
+// Returns the reason a username can't be used, if any
+pub async fn username_rejection(pool: &AnyPool, username: &str) -> Option<String> {
+    let normalized = normalize_username(username);
+
+    if RESERVED_USERNAMES.contains(&normalized.as_str()) {
+        return Some(format!("The username \"{}\" is reserved", username));
+    }
+
+    if BLOCKED_SUBSTRINGS.iter().any(|word| normalized.contains(word)) {
+        return Some("The username contains inappropriate language".to_string());
+    }
+
+    let reserved_at_runtime = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS (SELECT 1 FROM reserved_usernames WHERE word = $1)"
+        )
+        .bind(&normalized)
+        .fetch_one(pool)
+        .await;
+
+    match reserved_at_runtime {
+        Ok(true) => Some(format!("The username \"{}\" is reserved", username)),
+        Ok(false) => None,
+        Err(error) => {
+            error!("Failed to check reserved usernames: {}", error);
+            None
+        }
+    }
+}
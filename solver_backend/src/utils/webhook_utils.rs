@@ -0,0 +1,165 @@
+use crate::config::WebhookSettings;
+use crate::models::jobs_models::Job;
+use crate::models::webhooks_models::WebhookRecord;
+use crate::repositories::{jobs, webhooks};
+use actix_web::http::Uri;
+use actix_web::rt;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use sqlx::AnyPool;
+use std::net::IpAddr;
+use tracing::warn;
+
+pub const WEBHOOK_DELIVERY: &str = "webhook_delivery";
+
+// Events endpoints can subscribe to
+pub const DAILY_COMPLETED: &str = "daily_completed";
+pub const STREAK_MILESTONE: &str = "streak_milestone";
+pub const ACHIEVEMENT_EARNED: &str = "achievement_earned";
+pub const EVENTS: &[&str] = &[DAILY_COMPLETED, STREAK_MILESTONE, ACHIEVEMENT_EARNED];
+// Sent on request to check an endpoint, whatever it subscribed to
+pub const PING: &str = "ping";
+
+// `sha256=` and the hex HMAC-SHA256 of the raw body, keyed with the endpoint's secret
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+pub const DELIVERY_HEADER: &str = "X-Webhook-Delivery";
+
+pub const MIN_SECRET_LENGTH: usize = 16;
+
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    let digest: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", digest)
+}
+
+// Why `url` can't be an endpoint, None when it can. Addresses written into the URL are
+// checked, names aren't resolved, so this keeps out typos and the obvious internal
+// targets rather than anything that's determined to reach them.
+pub fn url_problem(url: &str, allow_private_hosts: bool) -> Option<String> {
+    let uri = match url.parse::<Uri>() {
+        Ok(uri) if matches!(uri.scheme_str(), Some("http" | "https")) => uri,
+        _ => return Some("url must be an http or https URL".to_string()),
+    };
+    let host = match uri.host() {
+        Some(host) if !host.is_empty() => host.trim_start_matches('[').trim_end_matches(']'),
+        _ => return Some("url has no host".to_string()),
+    };
+
+    let internal = host.eq_ignore_ascii_case("localhost")
+        || host.to_ascii_lowercase().ends_with(".localhost")
+        || host.parse::<IpAddr>().is_ok_and(is_internal);
+    (internal && !allow_private_hosts).then(|| format!("{} is a loopback or private network address", host))
+}
+
+fn is_internal(address: IpAddr) -> bool {
+    match address {
+        IpAddr::V4(v4) => v4.is_loopback() || v4.is_private() || v4.is_link_local() || v4.is_unspecified(),
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            v6.is_loopback()
+                || v6.is_unspecified()
+                // Unique local fc00::/7 and link local fe80::/10
+                || first & 0xfe00 == 0xfc00
+                || first & 0xffc0 == 0xfe80
+                || v6.to_ipv4_mapped().is_some_and(|v4| is_internal(IpAddr::V4(v4)))
+        }
+    }
+}
+
+// Queues `event` for every enabled endpoint subscribed to it that gets `user_id`'s events.
+// Returns how many deliveries were queued.
+pub async fn dispatch(pool: &AnyPool, user_id: i32, event: &str, data: &Value, max_attempts: i32, now: DateTime<Utc>) -> Result<usize, sqlx::Error> {
+    let subscribed: Vec<WebhookRecord> = webhooks::receiving_events_of(pool, user_id)
+        .await?
+        .into_iter()
+        .filter(|webhook| webhook.subscribes_to(event))
+        .collect();
+    if subscribed.is_empty() {
+        return Ok(0);
+    }
+
+    let payload = json!({ "event": event, "user_id": user_id, "occurred_at": now, "data": data }).to_string();
+    let mut tx = pool.begin().await?;
+    for webhook in &subscribed {
+        queue_delivery(&mut tx, webhook.id, event, &payload, max_attempts, now).await?;
+    }
+    tx.commit().await?;
+
+    Ok(subscribed.len())
+}
+
+// The delivery row and the job sending it, meant to run in one transaction
+pub async fn queue_delivery(conn: &mut sqlx::AnyConnection, webhook_id: i32, event: &str, payload: &str, max_attempts: i32, now: DateTime<Utc>) -> Result<i32, sqlx::Error> {
+    let delivery_id = webhooks::insert_delivery(&mut *conn, webhook_id, event, payload, now).await?;
+    jobs::enqueue(&mut *conn, None, WEBHOOK_DELIVERY, &json!({ "delivery_id": delivery_id }), max_attempts, now).await?;
+    Ok(delivery_id)
+}
+
+// One attempt at a queued delivery. A failed attempt is an error, so the job queue retries
+// it with backoff until the job runs out of attempts.
+pub async fn deliver(pool: &AnyPool, settings: &WebhookSettings, job: &Job, now: DateTime<Utc>) -> Result<Value, String> {
+    let payload: Value = serde_json::from_str(&job.payload).map_err(|error| format!("invalid payload: {}", error))?;
+    let delivery_id = payload["delivery_id"].as_i64().ok_or("payload has no delivery_id")? as i32;
+
+    let delivery = match webhooks::find_pending_delivery(pool, delivery_id).await.map_err(|error| error.to_string())? {
+        Some(delivery) => delivery,
+        // The endpoint was deleted along with its deliveries
+        None => return Ok(json!({ "delivery_id": delivery_id, "skipped": "webhook deleted" })),
+    };
+    if !delivery.enabled {
+        webhooks::abandon_delivery(pool, delivery.id, "the endpoint was disabled").await.map_err(|error| error.to_string())?;
+        return Ok(json!({ "delivery_id": delivery_id, "skipped": "webhook disabled" }));
+    }
+
+    let signature = sign(&delivery.secret, delivery.payload.as_bytes());
+    // awc's client isn't Send, so the request runs as a task of its own on this thread
+    let outcome = rt::spawn(send(delivery.url.clone(), signature, delivery.event.clone(), delivery.id, delivery.payload.clone(), settings.timeout))
+        .await
+        .map_err(|error| error.to_string())?;
+
+    let failed = if job.attempts >= job.max_attempts { "failed" } else { "retrying" };
+    let (status, response_status, error) = match outcome {
+        Ok(status) if (200..300).contains(&status) => ("succeeded", Some(status), None),
+        Ok(status) => (failed, Some(status), Some(format!("the endpoint answered {}", status))),
+        Err(error) => (failed, None, Some(error)),
+    };
+    webhooks::record_attempt(pool, delivery.id, status, response_status.map(i32::from), error.as_deref(), now)
+        .await
+        .map_err(|error| error.to_string())?;
+
+    match error {
+        None => {
+            webhooks::record_success(pool, delivery.webhook_id).await.map_err(|error| error.to_string())?;
+            Ok(json!({ "delivery_id": delivery.id, "response_status": response_status }))
+        }
+        Some(error) => {
+            let disabled = webhooks::record_failure(pool, delivery.webhook_id, settings.failure_limit, now).await.map_err(|error| error.to_string())?;
+            if disabled {
+                warn!("Webhook {} disabled after {} failed deliveries in a row", delivery.webhook_id, settings.failure_limit);
+            }
+            Err(error)
+        }
+    }
+}
+
+// The response status, or why there wasn't one. Redirects aren't followed, they'd lead
+// past the checks the URL passed.
+async fn send(url: String, signature: String, event: String, delivery_id: i32, body: String, timeout: std::time::Duration) -> Result<u16, String> {
+    let client = awc::Client::builder().timeout(timeout).disable_redirects().finish();
+    let request = client
+        .post(&url)
+        .insert_header(("Content-Type", "application/json"))
+        .insert_header((SIGNATURE_HEADER, signature))
+        .insert_header((EVENT_HEADER, event))
+        .insert_header((DELIVERY_HEADER, delivery_id.to_string()));
+
+    let response = rt::time::timeout(timeout, request.send_body(body))
+        .await
+        .map_err(|_| format!("no response within {}s", timeout.as_secs()))?
+        .map_err(|error| error.to_string())?;
+    Ok(response.status().as_u16())
+}
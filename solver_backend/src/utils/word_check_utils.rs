@@ -0,0 +1,142 @@
+use crate::models::admin_models::{CheckSeverity, WordListCheck, WordListViolation};
+use crate::repositories::words;
+use crate::utils::word_sync_utils::WORD_LENGTH;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use sqlx::AnyPool;
+use std::sync::RwLock;
+use tracing::{error, info, warn};
+
+// Offending words kept per violation, the count covers the rest
+const MAX_EXAMPLES: usize = 10;
+
+// The latest check's report, shared by the workers. None until the first check has run.
+#[derive(Default)]
+pub struct WordCheckStatus {
+    latest: RwLock<Option<WordListCheck>>,
+}
+
+impl WordCheckStatus {
+    pub fn latest(&self) -> Option<WordListCheck> {
+        self.latest.read().unwrap().clone()
+    }
+
+    pub fn record(&self, report: WordListCheck) {
+        *self.latest.write().unwrap() = Some(report);
+    }
+
+    // Only a check that ran and found something critical counts
+    pub fn failed(&self) -> bool {
+        self.latest.read().unwrap().as_ref().is_some_and(|report| !report.passed)
+    }
+}
+
+struct Tally {
+    check: &'static str,
+    severity: CheckSeverity,
+    count: i64,
+    examples: Vec<String>,
+}
+
+impl Tally {
+    fn new(check: &'static str, severity: CheckSeverity) -> Self {
+        Tally { check, severity, count: 0, examples: Vec::new() }
+    }
+
+    fn add(&mut self, word: &str) {
+        self.count += 1;
+        if self.examples.len() < MAX_EXAMPLES && !self.examples.iter().any(|example| example == word) {
+            self.examples.push(word.to_string());
+        }
+    }
+
+    fn violation(self, describe: impl FnOnce(i64) -> String) -> Option<WordListViolation> {
+        (self.count > 0).then(|| WordListViolation { check: self.check, severity: self.severity, message: describe(self.count), count: self.count, examples: self.examples })
+    }
+}
+
+fn list_violation(check: &'static str, severity: CheckSeverity, message: String) -> WordListViolation {
+    WordListViolation { check, severity, message, count: 1, examples: Vec::new() }
+}
+
+// One streamed pass over word_list. A NULL word counts as not alphabetic, a word listed
+// three times as two duplicates. The count is only held against the latest revision when
+// nothing changed the list since, and the list didn't change while it was read.
+pub async fn check_word_list(pool: &AnyPool, now: DateTime<Utc>) -> Result<WordListCheck, sqlx::Error> {
+    let version = words::version(pool).await?;
+    let revision = words::latest_revision(pool).await?;
+
+    let mut invalid = Tally::new("not_lowercase_alphabetic", CheckSeverity::Critical);
+    let mut wrong_length = Tally::new("wrong_length", CheckSeverity::Critical);
+    let mut duplicates = Tally::new("duplicate", CheckSeverity::Warning);
+    let (mut rows, mut listed, mut answers) = (0i64, 0i64, 0i64);
+    let mut previous: Option<String> = None;
+
+    let mut stream = words::stream_rows(pool);
+    while let Some((word, is_answer)) = stream.try_next().await? {
+        rows += 1;
+        let Some(word) = word else {
+            invalid.add("NULL");
+            continue;
+        };
+        listed += 1;
+        if is_answer {
+            answers += 1;
+        }
+        if !word.bytes().all(|byte| byte.is_ascii_lowercase()) {
+            invalid.add(&word);
+        }
+        if word.chars().count() != WORD_LENGTH {
+            wrong_length.add(&word);
+        }
+        if previous.as_deref() == Some(word.as_str()) {
+            duplicates.add(&word);
+        }
+        previous = Some(word);
+    }
+    drop(stream);
+
+    let mut violations = Vec::new();
+    violations.extend(invalid.violation(|count| format!("{} words aren't all lowercase a-z", count)));
+    violations.extend(wrong_length.violation(|count| format!("{} words don't have {} letters", count, WORD_LENGTH)));
+    violations.extend(duplicates.violation(|count| format!("{} rows repeat a word listed before", count)));
+
+    if rows == 0 {
+        violations.push(list_violation("empty_list", CheckSeverity::Critical, "The word list is empty".to_string()));
+    } else if answers == 0 {
+        violations.push(list_violation("no_answers", CheckSeverity::Critical, "No word in the list can be an answer".to_string()));
+    }
+
+    if let Some((expected, Some(to_version))) = revision {
+        if to_version == version && words::version(pool).await? == version && i64::from(expected) != listed {
+            let message = format!("The latest revision left {} words, the list has {}", expected, listed);
+            violations.push(list_violation("count_mismatch", CheckSeverity::Warning, message));
+        }
+    }
+
+    let passed = violations.iter().all(|violation| violation.severity != CheckSeverity::Critical);
+    Ok(WordListCheck { checked_at: now, version, words: rows, answers, passed, violations })
+}
+
+// One line for the outcome, and one for each violation
+pub fn log_report(report: &WordListCheck) {
+    for violation in &report.violations {
+        match violation.severity {
+            CheckSeverity::Critical => error!(check = violation.check, count = violation.count, examples = ?violation.examples, "Word list check: {}", violation.message),
+            CheckSeverity::Warning => warn!(check = violation.check, count = violation.count, examples = ?violation.examples, "Word list check: {}", violation.message),
+        }
+    }
+    if report.passed {
+        info!(version = report.version, words = report.words, answers = report.answers, warnings = report.violations.len(), "Word list check passed");
+    } else {
+        error!(version = report.version, words = report.words, answers = report.answers, violations = report.violations.len(), "Word list check failed");
+    }
+}
+
+// Checks, logs and keeps the report for readiness and the game routes
+pub async fn run_check(pool: &AnyPool, status: &WordCheckStatus, now: DateTime<Utc>) -> Result<WordListCheck, sqlx::Error> {
+    let report = check_word_list(pool, now).await?;
+    log_report(&report);
+    status.record(report.clone());
+    Ok(report)
+}
@@ -0,0 +1,222 @@
+use crate::config::WordSyncSettings;
+use crate::errors::{AppError, ErrorCode, ErrorInfo};
+use crate::models::game_models::WordListSync;
+use crate::repositories::words::{self, NewRevision};
+use crate::utils::clock_utils::Clock;
+use crate::utils::shutdown_utils::{wait_for_shutdown, ShutdownSignal};
+use actix_web::http::header::{HeaderMap, HeaderName, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED};
+use actix_web::http::StatusCode;
+use actix_web::rt::{self, task::JoinHandle};
+use actix_web::web::Bytes;
+use awc::error::PayloadError;
+use serde_json::json;
+use sqlx::AnyPool;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+pub const WORD_LENGTH: usize = 5;
+// Days the words each revision changed are kept, for clients catching up on the list
+pub const WORD_HISTORY_DAYS: i64 = 90;
+
+#[derive(Debug)]
+pub enum SyncError {
+    // Couldn't get a list from the source
+    Fetch(String),
+    // Got one, but it's not a list of words. Nothing was changed.
+    Invalid(String),
+    Database(sqlx::Error),
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyncError::Fetch(message) => write!(f, "couldn't fetch the word list: {}", message),
+            SyncError::Invalid(message) => write!(f, "the fetched word list is invalid: {}", message),
+            SyncError::Database(error) => write!(f, "database error: {}", error),
+        }
+    }
+}
+
+impl From<sqlx::Error> for SyncError {
+    fn from(error: sqlx::Error) -> Self {
+        SyncError::Database(error)
+    }
+}
+
+impl From<SyncError> for AppError {
+    fn from(error: SyncError) -> Self {
+        match error {
+            SyncError::Fetch(message) => AppError::bad_gateway(ErrorCode::WordSyncFetchFailed, format!("Couldn't fetch the word list: {}", message)),
+            SyncError::Invalid(message) => {
+                let info = ErrorInfo::new(ErrorCode::WordSyncInvalidList, "The fetched word list was rejected, the stored one is unchanged").with_details(json!({ "reason": message }));
+                AppError::BadGateway(info)
+            }
+            SyncError::Database(error) => AppError::from(error),
+        }
+    }
+}
+
+enum Fetched {
+    NotModified,
+    List { body: Bytes, etag: Option<String>, last_modified: Option<String> },
+}
+
+// Fetches the list and makes the stored one match it, in one transaction so a failure
+// anywhere leaves the previous list as it was. The request is conditional on what the
+// source sent last time, so an unchanged list costs a 304 and no database writes.
+pub async fn sync_words(pool: &AnyPool, settings: &WordSyncSettings, synced_by: Option<i32>, clock: &dyn Clock) -> Result<WordListSync, SyncError> {
+    let validators = words::latest_validators(pool, &settings.url).await?;
+
+    let (body, etag, last_modified) = match fetch(settings, validators).await? {
+        Fetched::NotModified => {
+            return Ok(WordListSync {
+                status: "unchanged",
+                added: 0,
+                removed: 0,
+                word_count: words::count_words(pool).await?,
+                version: words::version(pool).await?,
+                revision: None,
+            })
+        }
+        Fetched::List { body, etag, last_modified } => (body, etag, last_modified),
+    };
+    // Checked in full before the database is touched
+    let fetched = parse_word_list(&body).map_err(SyncError::Invalid)?;
+
+    let mut tx = pool.begin().await?;
+    let from_version = words::lock_version(&mut tx, pool.any_kind()).await?;
+
+    let current: BTreeSet<String> = words::all_words(&mut tx).await?.into_iter().collect();
+    let removed: Vec<&String> = current.difference(&fetched).collect();
+    let added: Vec<&String> = fetched.difference(&current).collect();
+
+    for word in &removed {
+        words::remove_word(&mut tx, word).await?;
+    }
+    for word in &added {
+        words::add_word(&mut tx, word).await?;
+    }
+
+    let revision = NewRevision {
+        source_url: &settings.url,
+        added: added.len() as i32,
+        removed: removed.len() as i32,
+        word_count: fetched.len() as i32,
+        etag: etag.as_deref(),
+        last_modified: last_modified.as_deref(),
+        synced_by,
+        from_version,
+        to_version: words::version(&mut tx).await?,
+    };
+    let revision_id = words::insert_revision(&mut tx, &revision, clock.now()).await?;
+    for word in &removed {
+        words::record_change(&mut tx, revision_id, word, false).await?;
+    }
+    for word in &added {
+        words::record_change(&mut tx, revision_id, word, true).await?;
+    }
+    tx.commit().await?;
+
+    Ok(WordListSync {
+        status: if added.is_empty() && removed.is_empty() { "unchanged" } else { "updated" },
+        added: added.len(),
+        removed: removed.len(),
+        word_count: fetched.len() as i64,
+        version: revision.to_version,
+        revision: Some(revision_id),
+    })
+}
+
+async fn fetch(settings: &WordSyncSettings, validators: Option<(Option<String>, Option<String>)>) -> Result<Fetched, SyncError> {
+    let client = awc::Client::builder().timeout(settings.timeout).finish();
+    let mut request = client.get(&settings.url);
+    if let Some((etag, last_modified)) = validators {
+        if let Some(etag) = etag {
+            request = request.insert_header((IF_NONE_MATCH, etag));
+        }
+        if let Some(last_modified) = last_modified {
+            request = request.insert_header((IF_MODIFIED_SINCE, last_modified));
+        }
+    }
+
+    // The client's own timeout stops at the headers, this one also covers the body
+    let exchange = async {
+        let mut response = request.send().await.map_err(|error| SyncError::Fetch(error.to_string()))?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_MODIFIED {
+            return Ok(Fetched::NotModified);
+        }
+        if !status.is_success() {
+            return Err(SyncError::Fetch(format!("the source answered {}", status)));
+        }
+
+        let etag = header(response.headers(), ETAG);
+        let last_modified = header(response.headers(), LAST_MODIFIED);
+        let body = response.body().limit(settings.max_bytes).await.map_err(|error| match error {
+            PayloadError::Overflow => SyncError::Fetch(format!("the list is larger than {} bytes", settings.max_bytes)),
+            error => SyncError::Fetch(error.to_string()),
+        })?;
+
+        Ok(Fetched::List { body, etag, last_modified })
+    };
+
+    rt::time::timeout(settings.timeout, exchange)
+        .await
+        .map_err(|_| SyncError::Fetch(format!("no complete response within {}s", settings.timeout.as_secs())))?
+}
+
+fn header(headers: &HeaderMap, name: HeaderName) -> Option<String> {
+    headers.get(name)?.to_str().ok().map(str::to_string)
+}
+
+// One word per line. Blank lines and # comments are skipped, case and duplicates don't
+// matter. Anything else that isn't a five letter word rejects the whole list, as does an
+// empty one, which would otherwise wipe the stored list.
+pub fn parse_word_list(body: &[u8]) -> Result<BTreeSet<String>, String> {
+    let text = std::str::from_utf8(body).map_err(|_| "the list isn't UTF-8 text".to_string())?;
+
+    let mut words = BTreeSet::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.len() != WORD_LENGTH || !line.chars().all(|letter| letter.is_ascii_alphabetic()) {
+            return Err(format!("line {}: {:?} isn't a {} letter word", number + 1, line, WORD_LENGTH));
+        }
+        words.insert(line.to_ascii_lowercase());
+    }
+
+    if words.is_empty() {
+        return Err("the list has no words".to_string());
+    }
+    Ok(words)
+}
+
+// Syncs every `interval` until shutdown. The first sync waits a full interval, startup
+// doesn't depend on the source being up.
+pub fn spawn_word_sync(pool: AnyPool, settings: WordSyncSettings, interval: Duration, clock: Arc<dyn Clock>, mut shutdown: ShutdownSignal) -> JoinHandle<()> {
+    rt::spawn(async move {
+        let mut ticks = rt::time::interval(interval);
+        ticks.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = ticks.tick() => {}
+                _ = wait_for_shutdown(&mut shutdown) => break,
+            }
+
+            match sync_words(&pool, &settings, None, clock.as_ref()).await {
+                Ok(sync) if sync.added > 0 || sync.removed > 0 => info!("Word list synced, {} added and {} removed, now at version {}", sync.added, sync.removed, sync.version),
+                Ok(_) => {}
+                Err(error) => error!("Scheduled word list sync failed, keeping the current list: {}", error),
+            }
+        }
+
+        info!("Word list sync stopped");
+    })
+}
@@ -0,0 +1,372 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, read_body_json, TestRequest};
+use chrono::{DateTime, Duration, Utc};
+use common::{access_token, get_json, init_app, init_app_with, init_app_with_clock, login, make_admin, post_json, register, settings, settings_with, user_id, TestDb, TEST_PASSWORD, TEST_USERNAME, TEST_WORDS};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wordle_solver::utils::bcrypt_utils::hash_password;
+use wordle_solver::utils::clock_utils::FakeClock;
+use wordle_solver::utils::feature_utils::{FeatureFlags, REGISTRATION};
+
+#[actix_web::test]
+async fn summary_reports_every_component() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    register(&app, "player", "player@example.com", TEST_PASSWORD).await;
+    let (_, tokens) = login(&app, "player", TEST_PASSWORD).await;
+    let (status, _) = get_json(&app, "/api/v1/admin/summary", tokens["access"].as_str()).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    make_admin(&db).await;
+    let token = access_token(&app).await;
+    // The second one is answered from the result cache
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "cr___" });
+    for _ in 0..2 {
+        let (status, _) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    let (status, summary) = get_json(&app, "/api/v1/admin/summary", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", summary);
+
+    assert_eq!(summary["users"]["total"], 2);
+    assert_eq!(summary["users"]["active_last_7_days"], 2);
+    assert!(summary["users"]["verified"].is_i64());
+    // Registering issues tokens as well as the two logins
+    assert_eq!(summary["active_sessions_last_hour"], 3);
+    assert_eq!(summary["solver_queries_today"], 2);
+    assert_eq!(summary["word_list"]["size"], TEST_WORDS.len());
+    assert!(summary["word_list"]["version"].is_i64());
+    // The seeded user's new device notice, waiting for the worker
+    assert_eq!(summary["pending_jobs"], 1);
+    assert_eq!(summary["cache_hit_rates"]["results"], 0.5);
+    assert!(summary["db_pool"]["size"].as_u64().unwrap() >= 1);
+    assert!(summary["db_pool"]["utilization"].is_f64());
+    assert!(summary["db_pool"]["max_connections"].as_u64().unwrap() >= 1);
+    assert!(summary["generated_at"].is_string());
+    assert_eq!(summary["errors"], json!([]));
+}
+
+#[actix_web::test]
+async fn failing_components_are_left_null() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    make_admin(&db).await;
+    let token = access_token(&app).await;
+
+    sqlx::query("DROP TABLE jobs").execute(&db.pool).await.unwrap();
+
+    let (status, summary) = get_json(&app, "/api/v1/admin/summary", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", summary);
+    assert!(summary["pending_jobs"].is_null());
+    assert_eq!(summary["errors"], json!([{ "component": "pending_jobs", "message": "Database query failed" }]));
+    // The rest is still there
+    assert_eq!(summary["users"]["total"], 1);
+    assert_eq!(summary["word_list"]["size"], TEST_WORDS.len());
+}
+
+#[actix_web::test]
+async fn flags_toggle_features_at_runtime_and_are_kept() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    make_admin(&db).await;
+    let token = access_token(&app).await;
+    let set_flag = |name: &str, enabled: bool| {
+        TestRequest::put()
+            .uri(&format!("/api/v1/admin/flags/{}", name))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "enabled": enabled }))
+            .to_request()
+    };
+
+    let response = call_service(&app, set_flag(REGISTRATION, false)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let flag: Value = read_body_json(response).await;
+    assert_eq!(flag, json!({ "name": "registration", "enabled": false }));
+
+    let (status, body) = register(&app, "player", "player@example.com", TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "feature_disabled");
+    assert_eq!(body["error"]["details"]["feature"], "registration");
+
+    let (status, flags) = get_json(&app, "/api/v1/admin/flags", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(flags.as_array().unwrap().contains(&json!({ "name": "registration", "enabled": false })), "{}", flags);
+
+    // As a restarted server would see it, env default notwithstanding
+    let restarted = FeatureFlags::new(&settings().feature_defaults);
+    restarted.load(&db.pool).await.unwrap();
+    assert!(!restarted.is_enabled(REGISTRATION));
+
+    assert_eq!(call_service(&app, set_flag(REGISTRATION, true)).await.status(), StatusCode::OK);
+    let (status, _) = register(&app, "player", "player@example.com", TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(call_service(&app, set_flag("multiplayer", true)).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn flags_default_from_the_environment() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[("FEATURE_BENCHMARKS", "false")]).unwrap()).await;
+    let token = access_token(&app).await;
+
+    let (status, body) = post_json(&app, "/api/v1/game/benchmark", &json!({ "sample": 5 }), Some(&token)).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(body["error"]["code"], "feature_disabled");
+
+    // Everything else is on
+    let (status, _) = register(&app, "player", "player@example.com", TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+fn import_csv(csv: &str, token: &str) -> Request {
+    TestRequest::post()
+        .uri("/api/v1/users/import")
+        .insert_header(("Content-Type", "text/csv"))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_payload(csv.to_string())
+        .to_request()
+}
+
+#[actix_web::test]
+async fn import_reports_every_row_it_leaves_out() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let hash = hash_password("imported secret", 4).unwrap();
+
+    // Columns in another order, and a quoted username over two lines
+    let csv = format!(
+        "email,created_at,username,password_hash\n\
+        ada@example.com,2022-03-01T09:30:00Z,ada,{hash}\n\
+        grace@example.com,2022-04-01T10:00:00+02:00,grace,\n\
+        ADA@Example.com,2022-05-01T00:00:00Z,ada2,\n\
+        linus@example.com,yesterday,linus,\n\
+        ken@example.com,2022-06-01T00:00:00Z\n\
+        dennis@example.com,2022-06-01T00:00:00Z,\"den\nnis\",\n\
+        barbara@example.com,2022-06-01T00:00:00Z,barbara,not-a-hash\n\
+        other@example.com,2022-07-01T00:00:00Z,{TEST_USERNAME},\n\
+        ken@example.com,2022-08-01T00:00:00Z,ken,\n"
+    );
+
+    register(&app, "player", "player@example.com", TEST_PASSWORD).await;
+    let (_, tokens) = login(&app, "player", TEST_PASSWORD).await;
+    let response = call_service(&app, import_csv(&csv, tokens["access"].as_str().unwrap())).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    make_admin(&db).await;
+    let token = access_token(&app).await;
+    let response = call_service(&app, import_csv(&csv, &token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let report: Value = read_body_json(response).await;
+
+    assert_eq!(report["inserted"], 4, "{}", report);
+    assert_eq!(report["skipped"], 2, "{}", report);
+    assert_eq!(report["failed"], 3, "{}", report);
+    let problems: Vec<(u64, &str)> = report["problems"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|problem| (problem["row"].as_u64().unwrap(), problem["outcome"].as_str().unwrap()))
+        .collect();
+    assert_eq!(problems, [(4, "skipped"), (5, "failed"), (6, "failed"), (9, "failed"), (10, "skipped")], "{}", report);
+    assert_eq!(report["problems"][0]["reason"], "Email ADA@example.com is also on row 2");
+
+    let (created_at,): (chrono::DateTime<chrono::Utc>,) = sqlx::query_as("SELECT created_at FROM users WHERE username = 'grace'")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(created_at.to_rfc3339(), "2022-04-01T08:00:00+00:00");
+    let (multiline,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM users WHERE username = 'den\nnis'").fetch_one(&db.pool).await.unwrap();
+    assert_eq!(multiline, 1);
+
+    // Importing again only skips
+    let response = call_service(&app, import_csv(&csv, &token)).await;
+    let report: Value = read_body_json(response).await;
+    assert_eq!((report["inserted"].as_u64(), report["skipped"].as_u64()), (Some(0), Some(6)), "{}", report);
+}
+
+#[actix_web::test]
+async fn imported_accounts_without_a_hash_must_set_a_password() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    make_admin(&db).await;
+    let token = access_token(&app).await;
+    let hash = hash_password("imported secret", 4).unwrap();
+
+    let csv = format!("username,email,password_hash,created_at\nada,ada@example.com,{hash},2022-03-01T09:30:00Z\ngrace,grace@example.com,,2022-03-01T09:30:00Z\n");
+    let response = call_service(&app, import_csv(&csv, &token)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let (status, _) = login(&app, "ada", "imported secret").await;
+    assert_eq!(status, StatusCode::OK);
+
+    for password in ["", "anything at all"] {
+        let (status, body) = login(&app, "grace", password).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(body["error"]["code"], "password_reset_required");
+    }
+
+    // Knowing the id isn't enough to set the password, and a password set by someone else
+    // doesn't stand in for the owner's
+    let (id,): (i32,) = sqlx::query_as("SELECT id FROM users WHERE username = 'grace'").fetch_one(&db.pool).await.unwrap();
+    let set_password = |token: Option<&str>| {
+        let request = TestRequest::put().uri(&format!("/api/v1/users/update_password/{}", id)).set_json(json!({ "password": "a brand new password" }));
+        match token {
+            Some(token) => request.insert_header(("Authorization", format!("Bearer {}", token))).to_request(),
+            None => request.to_request(),
+        }
+    };
+    assert_eq!(call_service(&app, set_password(None)).await.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(call_service(&app, set_password(Some(&token))).await.status(), StatusCode::OK);
+    let (status, body) = login(&app, "grace", "a brand new password").await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::FORBIDDEN, &json!("password_reset_required")));
+
+    // The owner proves it with the token mailed to them, which works once
+    let (status, _) = post_json(&app, "/api/v1/users/password-reset", &json!({ "email": "nobody@example.com" }), None).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let (status, _) = post_json(&app, "/api/v1/users/password-reset", &json!({ "email": "Grace@Example.com" }), None).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    let mailed: Vec<(String, String)> = sqlx::query_as("SELECT recipient, text_body FROM email_deliveries WHERE template = 'password_reset'").fetch_all(&db.pool).await.unwrap();
+    assert_eq!(mailed.len(), 1, "{:?}", mailed);
+    assert_eq!(mailed[0].0, "grace@example.com");
+    let reset_token = mailed[0].1.lines().next().unwrap().rsplit(' ').next().unwrap().to_string();
+
+    let reset = |token: &str| json!({ "token": token, "password": "grace's own password" });
+    let (status, body) = post_json(&app, "/api/v1/users/password-reset/confirm", &reset("not the token"), None).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::BAD_REQUEST, &json!("invalid_token")));
+    let (status, body) = post_json(&app, "/api/v1/users/password-reset/confirm", &reset(&reset_token), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, _) = login(&app, "grace", "grace's own password").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = post_json(&app, "/api/v1/users/password-reset/confirm", &reset(&reset_token), None).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::BAD_REQUEST, &json!("invalid_token")));
+}
+
+#[actix_web::test]
+async fn import_needs_every_column_but_the_hash() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    make_admin(&db).await;
+    let token = access_token(&app).await;
+
+    let response = call_service(&app, import_csv("username,email\nada,ada@example.com\n", &token)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = read_body_json(response).await;
+    assert_eq!(body["error"]["code"], "invalid_csv");
+
+    let response = call_service(&app, import_csv("username,email,created_at\nada,ada@example.com,2022-03-01T09:30:00Z\n", &token)).await;
+    let report: Value = read_body_json(response).await;
+    assert_eq!(report["inserted"], 1, "{}", report);
+}
+
+#[actix_web::test]
+async fn suspended_users_can_neither_log_in_nor_use_their_tokens() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    make_admin(&db).await;
+    let admin = access_token(&app).await;
+
+    register(&app, "mallory", "mallory@example.com", TEST_PASSWORD).await;
+    let (_, tokens) = login(&app, "mallory", TEST_PASSWORD).await;
+    let (access, refresh) = (tokens["access"].as_str().unwrap(), tokens["refresh"].as_str().unwrap());
+    assert_eq!(get_json(&app, "/api/v1/users/me/sessions", Some(access)).await.0, StatusCode::OK);
+
+    let mallory = user_id(&db, "mallory").await;
+    let suspend = json!({ "reason": "spam", "until": "2099-01-01T00:00:00Z" });
+    let (status, body) = post_json(&app, &format!("/api/v1/users/{}/suspend", mallory), &suspend, Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, body) = login(&app, "mallory", TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "account_suspended");
+    assert_eq!(body["error"]["details"], json!({ "reason": "spam", "until": "2099-01-01T00:00:00Z" }));
+
+    // Tokens issued before the suspension are refused too, refreshing included
+    let (status, body) = get_json(&app, "/api/v1/users/me/sessions", Some(access)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::UNAUTHORIZED, Some("token_revoked")));
+    let (status, _) = post_json(&app, "/api/v1/users/get_new_tokens", &json!({ "token": refresh }), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, _) = post_json(&app, &format!("/api/v1/users/{}/unsuspend", mallory), &json!({}), Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, body) = login(&app, "mallory", TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[actix_web::test]
+async fn admins_cannot_suspend_other_admins() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    make_admin(&db).await;
+    let admin = access_token(&app).await;
+
+    register(&app, "grace", "grace@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'grace'").execute(&db.pool).await.unwrap();
+    let (_, tokens) = login(&app, "grace", TEST_PASSWORD).await;
+    let grace_token = tokens["access"].as_str().unwrap();
+
+    let grace = user_id(&db, "grace").await;
+    let (status, body) = post_json(&app, &format!("/api/v1/users/{}/suspend", grace), &json!({ "reason": "takeover" }), Some(&admin)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::FORBIDDEN, Some("forbidden")));
+
+    // Nothing changed for the other admin
+    let suspended: Option<String> = sqlx::query_scalar("SELECT suspension_reason FROM users WHERE id = $1").bind(grace).fetch_one(&db.pool).await.unwrap();
+    assert_eq!(suspended, None);
+    assert_eq!(get_json(&app, "/api/v1/users/me/sessions", Some(grace_token)).await.0, StatusCode::OK);
+    assert_eq!(login(&app, "grace", TEST_PASSWORD).await.0, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn metrics_count_users_into_the_right_buckets() {
+    let db = TestDb::new().await;
+    let now: DateTime<Utc> = "2023-06-15T12:00:00Z".parse().unwrap();
+    let app = init_app_with_clock(&db, settings(), Arc::new(FakeClock::new(now))).await;
+    // Out of every window but the login about to happen
+    sqlx::query("UPDATE users SET role = 'admin', created_at = $1, email_verified = FALSE WHERE username = $2")
+        .bind(now - Duration::days(365))
+        .bind(TEST_USERNAME)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let token = access_token(&app).await;
+
+    // The 30 days run from 2023-05-17, active means a login in the 7 days before now
+    let seeded = [
+        ("before", "2023-05-16T23:59:59Z", None, true),
+        ("first", "2023-05-17T00:00:00Z", Some("2023-06-08T12:00:00Z"), true),
+        ("yesterday", "2023-06-14T23:59:59Z", Some("2023-06-08T12:00:01Z"), false),
+        ("midnight", "2023-06-15T00:00:00Z", None, false),
+        ("morning", "2023-06-15T11:59:59Z", None, false),
+    ];
+    for (username, created_at, last_login_at, verified) in seeded {
+        sqlx::query("INSERT INTO users (username, email, password, created_at, last_login_at, email_verified) VALUES ($1, $2, 'unused', $3, $4, $5)")
+            .bind(username)
+            .bind(format!("{}@example.com", username))
+            .bind(created_at.parse::<DateTime<Utc>>().unwrap())
+            .bind(last_login_at.map(|at| at.parse::<DateTime<Utc>>().unwrap()))
+            .bind(verified)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+    }
+
+    let (status, metrics) = get_json(&app, "/api/v1/users/metrics", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", metrics);
+    assert_eq!((&metrics["total_users"], &metrics["active_last_7_days"]), (&json!(6), &json!(2)));
+    assert!((metrics["verified_email_percentage"].as_f64().unwrap() - 100.0 / 3.0).abs() < 1e-9, "{}", metrics);
+
+    let registrations = metrics["registrations"].as_array().unwrap();
+    assert_eq!(registrations.len(), 30);
+    assert_eq!((&registrations[0]["day"], &registrations[29]["day"]), (&json!("2023-05-17"), &json!("2023-06-15")));
+    let counted: Vec<(usize, i64)> = registrations
+        .iter()
+        .enumerate()
+        .filter_map(|(index, bucket)| Some((index, bucket["count"].as_i64().filter(|count| *count > 0)?)))
+        .collect();
+    assert_eq!(counted, [(0, 1), (28, 1), (29, 2)]);
+}
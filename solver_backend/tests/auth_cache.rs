@@ -0,0 +1,69 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use chrono::{Duration, Utc};
+use common::{access_token, get_json, init_app_with_clock, login, post_json, register, settings_with, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::json;
+use std::sync::Arc;
+use wordle_solver::utils::clock_utils::FakeClock;
+
+// Token versions and roles are cached for this long
+const TTL_SECONDS: &str = "30";
+
+#[actix_web::test]
+async fn a_version_changed_elsewhere_takes_effect_once_the_cached_one_expires() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings_with(&[("AUTH_CACHE_TTL_SECONDS", TTL_SECONDS)]).unwrap(), clock.clone()).await;
+    let token = access_token(&app).await;
+    let check = || post_json(&app, "/api/v1/users/check_access", &json!({ "token": token }), None);
+    assert_eq!(check().await.0, StatusCode::OK);
+
+    // As another instance would, without touching this one's cache
+    sqlx::query("UPDATE users SET token_version = token_version + 1 WHERE username = $1").bind(TEST_USERNAME).execute(&db.pool).await.unwrap();
+    clock.advance(Duration::seconds(29));
+    assert_eq!(check().await.0, StatusCode::OK);
+
+    clock.advance(Duration::seconds(1));
+    let (status, body) = check().await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::UNAUTHORIZED, Some("token_revoked")));
+}
+
+#[actix_web::test]
+async fn a_role_changed_elsewhere_takes_effect_once_the_cached_one_expires() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings_with(&[("AUTH_CACHE_TTL_SECONDS", TTL_SECONDS)]).unwrap(), clock.clone()).await;
+    let token = access_token(&app).await;
+    let (status, body) = get_json(&app, "/api/v1/users", Some(&token)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::FORBIDDEN, Some("admin_required")));
+
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = $1").bind(TEST_USERNAME).execute(&db.pool).await.unwrap();
+    clock.advance(Duration::seconds(29));
+    assert_eq!(get_json(&app, "/api/v1/users", Some(&token)).await.0, StatusCode::FORBIDDEN);
+
+    clock.advance(Duration::seconds(1));
+    let (status, body) = get_json(&app, "/api/v1/users", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[actix_web::test]
+async fn a_forced_logout_takes_effect_at_once_on_the_same_instance() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings_with(&[("AUTH_CACHE_TTL_SECONDS", TTL_SECONDS)]).unwrap(), clock.clone()).await;
+    let token = access_token(&app).await;
+    let check = || post_json(&app, "/api/v1/users/check_access", &json!({ "token": token }), None);
+    assert_eq!(check().await.0, StatusCode::OK);
+
+    register(&app, "boss", "boss@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'boss'").execute(&db.pool).await.unwrap();
+    let (_, tokens) = login(&app, "boss", TEST_PASSWORD).await;
+    let tester: i32 = sqlx::query_scalar("SELECT id FROM users WHERE username = $1").bind(TEST_USERNAME).fetch_one(&db.pool).await.unwrap();
+    let (status, _) = post_json(&app, &format!("/api/v1/users/{}/force-logout", tester), &json!({}), tokens["access"].as_str()).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // No waiting for the cached version to expire
+    let (status, body) = check().await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::UNAUTHORIZED, Some("token_revoked")));
+}
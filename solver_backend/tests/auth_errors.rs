@@ -0,0 +1,106 @@
+mod common;
+
+use actix_web::http::header::WWW_AUTHENTICATE;
+use actix_web::http::{Method, StatusCode};
+use actix_web::test::{self, TestRequest};
+use chrono::{Duration, Utc};
+use common::{access_token, init_app, post_json, register, settings, signing_keys, user_id, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::{json, Value};
+use wordle_solver::utils::jwt_utils::generate_access_token;
+
+fn bearer(token: &str) -> Option<String> {
+    Some(format!("Bearer {}", token))
+}
+
+// Every way a request can fail authentication or authorization, against a route of each
+// kind: 401s name what was wrong with the credentials and challenge for a bearer token,
+// 403s name what the valid credentials don't allow
+#[actix_web::test]
+async fn credentials_are_refused_with_401_and_permissions_with_403() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let tester = user_id(&db, TEST_USERNAME).await;
+
+    let (_, tokens) = register(&app, "rival", "rival@example.com", TEST_PASSWORD).await;
+    let rival = tokens["access"].as_str().unwrap().to_string();
+    let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&rival)).await;
+    let rivals_session = format!("/api/v1/game/sessions/{}", created["session"]["id"]);
+    let rival_id = user_id(&db, "rival").await;
+    let (rival_account, rival_password) = (format!("/api/v1/users/{}", rival_id), format!("/api/v1/users/update_password/{}", rival_id));
+
+    let (_, tokens) = register(&app, "quitter", "quitter@example.com", TEST_PASSWORD).await;
+    let revoked = tokens["access"].as_str().unwrap().to_string();
+    let (status, _) = post_json(&app, "/api/v1/users/revoke_token", &json!({ "token": revoked }), Some(&revoked)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (_, tokens) = register(&app, "leaver", "leaver@example.com", TEST_PASSWORD).await;
+    let deleted = tokens["access"].as_str().unwrap().to_string();
+    sqlx::query("DELETE FROM users WHERE username = 'leaver'").execute(&db.pool).await.unwrap();
+
+    let expired = generate_access_token(&signing_keys(), tester, 0, &settings().jwt, Utc::now() - Duration::days(1)).unwrap();
+    let preferences = "/api/v1/users/me/preferences";
+
+    let cases: Vec<(Method, &str, Option<String>, StatusCode, &str)> = vec![
+        (Method::GET, preferences, None, StatusCode::UNAUTHORIZED, "missing_token"),
+        (Method::GET, preferences, Some("Basic dGVzdGVyOnNlY3JldA==".to_string()), StatusCode::UNAUTHORIZED, "invalid_token"),
+        (Method::GET, preferences, bearer(""), StatusCode::UNAUTHORIZED, "invalid_token"),
+        (Method::GET, preferences, bearer("not.a.token"), StatusCode::UNAUTHORIZED, "invalid_token"),
+        (Method::GET, preferences, bearer(&expired), StatusCode::UNAUTHORIZED, "token_expired"),
+        (Method::GET, preferences, bearer(&revoked), StatusCode::UNAUTHORIZED, "token_revoked"),
+        (Method::GET, preferences, bearer(&deleted), StatusCode::UNAUTHORIZED, "token_revoked"),
+        (Method::GET, preferences, bearer(&token), StatusCode::OK, ""),
+        (Method::GET, "/api/v1/admin/summary", None, StatusCode::UNAUTHORIZED, "missing_token"),
+        (Method::GET, "/api/v1/admin/summary", bearer(&expired), StatusCode::UNAUTHORIZED, "token_expired"),
+        (Method::GET, "/api/v1/admin/summary", bearer(&token), StatusCode::FORBIDDEN, "admin_required"),
+        (Method::GET, &rivals_session, None, StatusCode::UNAUTHORIZED, "missing_token"),
+        (Method::GET, &rivals_session, bearer("not.a.token"), StatusCode::UNAUTHORIZED, "invalid_token"),
+        (Method::GET, &rivals_session, bearer(&token), StatusCode::FORBIDDEN, "not_owner"),
+        (Method::GET, &rivals_session, bearer(&rival), StatusCode::OK, ""),
+        (Method::GET, "/api/v1/users", None, StatusCode::UNAUTHORIZED, "missing_token"),
+        (Method::GET, "/api/v1/users", bearer(&token), StatusCode::FORBIDDEN, "admin_required"),
+        (Method::GET, &rival_account, None, StatusCode::UNAUTHORIZED, "missing_token"),
+        (Method::GET, &rival_account, bearer(&token), StatusCode::FORBIDDEN, "not_owner"),
+        (Method::GET, &rival_account, bearer(&rival), StatusCode::OK, ""),
+        (Method::PUT, &rival_account, None, StatusCode::UNAUTHORIZED, "missing_token"),
+        (Method::PUT, &rival_account, bearer(&token), StatusCode::FORBIDDEN, "not_owner"),
+        (Method::PATCH, &rival_account, None, StatusCode::UNAUTHORIZED, "missing_token"),
+        (Method::PATCH, &rival_account, bearer(&token), StatusCode::FORBIDDEN, "not_owner"),
+        (Method::PUT, &rival_password, None, StatusCode::UNAUTHORIZED, "missing_token"),
+        (Method::PUT, &rival_password, bearer(&token), StatusCode::FORBIDDEN, "not_owner"),
+        (Method::DELETE, &rival_account, None, StatusCode::UNAUTHORIZED, "missing_token"),
+        (Method::DELETE, &rival_account, bearer(&token), StatusCode::FORBIDDEN, "not_owner"),
+    ];
+
+    for (method, uri, authorization, expected_status, expected_code) in cases {
+        let mut request = TestRequest::default().method(method.clone()).uri(uri);
+        if uri == rival_password {
+            request = request.set_json(json!({ "password": "a rival's new password" }));
+        } else if method == Method::PUT || method == Method::PATCH {
+            request = request.set_json(json!({ "username": "rival", "email": "rival@example.com", "version": 1 }));
+        }
+        if let Some(authorization) = &authorization {
+            request = request.insert_header(("Authorization", authorization.as_str()));
+        }
+        let response = test::call_service(&app, request.to_request()).await;
+        let status = response.status();
+        let challenge = response.headers().get(WWW_AUTHENTICATE).map(|value| value.to_str().unwrap().to_string());
+        let body: Value = serde_json::from_slice(&test::read_body(response).await).unwrap_or(Value::Null);
+        let scenario = format!("{} {} with {:?}: {}", method, uri, authorization, body);
+
+        assert_eq!(status, expected_status, "{}", scenario);
+        if status.is_success() {
+            assert_eq!(challenge, None, "{}", scenario);
+            continue;
+        }
+        assert_eq!(body["error"]["code"], expected_code, "{}", scenario);
+        match (status, expected_code) {
+            (StatusCode::UNAUTHORIZED, "missing_token") => assert_eq!(challenge.as_deref(), Some(r#"Bearer realm="wordle-solver""#), "{}", scenario),
+            (StatusCode::UNAUTHORIZED, _) => {
+                let challenge = challenge.unwrap_or_default();
+                assert!(challenge.starts_with(r#"Bearer realm="wordle-solver", error="invalid_token", error_description=""#), "{}: {}", scenario, challenge);
+            }
+            _ => assert_eq!(challenge, None, "{}", scenario),
+        }
+    }
+}
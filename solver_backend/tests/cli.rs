@@ -0,0 +1,65 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const WORDS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/words.txt");
+
+// Runs the CLI with `args`, feeding it `script` on stdin
+fn play(args: &[&str], script: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_wordle-cli"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start wordle-cli");
+
+    child.stdin.take().unwrap().write_all(script.as_bytes()).unwrap();
+    child.wait_with_output().expect("wordle-cli did not finish")
+}
+
+#[test]
+fn scripted_game_matches_the_snapshot() {
+    // The answer is "there". The bad line and the first feedback to "three", which no word
+    // matches, are reported on stderr and leave the game as it was.
+    let script = "slate xxxyg\nbogus\ncrane xyxxg\nthree ggxyg\nthree ggyyg\nthere ggggg\n";
+    let output = play(&[WORDS, "--top", "3"], script);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), include_str!("snapshots/scripted_game.txt"));
+
+    let errors = String::from_utf8_lossy(&output.stderr);
+    assert!(errors.contains("Expected a guess and its feedback"), "{}", errors);
+    assert!(errors.contains("No word matches that feedback"), "{}", errors);
+}
+
+#[test]
+fn hard_mode_rejects_guesses_ignoring_hints() {
+    let output = play(&[WORDS, "--hard"], "slate xxxyg\ncrane xyxxg\n");
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Hard mode: crane has to reuse every revealed hint"));
+    // Only the opening state and the one accepted guess were printed
+    assert_eq!(String::from_utf8_lossy(&output.stdout).matches("candidates left").count(), 2);
+}
+
+#[test]
+fn bundled_list_is_used_without_a_path() {
+    let output = play(&[], "");
+
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("words loaded."));
+
+    let output = play(&["--length", "4"], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No 4-letter words to solve with"));
+}
+
+#[test]
+fn explain_says_why_for_each_suggestion() {
+    let output = play(&[WORDS, "--top", "2", "--explain"], "slate xxxyg\n");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // Two suggestions explained after both the opening and the guess
+    assert_eq!(stdout.lines().filter(|line| line.starts_with("  Of the ")).count(), 4, "{}", stdout);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue, FORWARDED, X_FORWARDED_FOR};
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, read_body_json, TestRequest};
+use common::{init_app_with, settings_with, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+use wordle_solver::config::ProxySettings;
+use wordle_solver::utils::client_ip_utils::resolve;
+
+fn proxies(overrides: &[(&str, &str)]) -> ProxySettings {
+    settings_with(overrides).unwrap().proxies
+}
+
+fn client(peer: &str, headers: &[(HeaderName, &str)], proxies: &ProxySettings) -> String {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        map.append(name.clone(), HeaderValue::from_str(value).unwrap());
+    }
+    resolve(peer.parse::<IpAddr>().unwrap(), &map, proxies).to_string()
+}
+
+#[test]
+fn headers_from_untrusted_peers_are_ignored() {
+    let proxies = proxies(&[("TRUSTED_PROXIES", "10.0.0.0/8")]);
+
+    assert_eq!(client("203.0.113.9", &[(X_FORWARDED_FOR, "198.51.100.1")], &proxies), "203.0.113.9");
+    // Nothing is trusted by default
+    assert_eq!(client("10.0.0.2", &[(X_FORWARDED_FOR, "198.51.100.1")], &self::proxies(&[])), "10.0.0.2");
+}
+
+#[test]
+fn hops_are_walked_back_past_trusted_proxies_only() {
+    let proxies = proxies(&[("TRUSTED_PROXIES", "10.0.0.0/8, 192.0.2.7")]);
+
+    // The client prepended a spoofed hop, which sits left of its real address
+    let chain = "1.2.3.4, 198.51.100.1, 192.0.2.7";
+    assert_eq!(client("10.0.0.2", &[(X_FORWARDED_FOR, chain)], &proxies), "198.51.100.1");
+    // Split across header lines the order is the same
+    let lines = [(X_FORWARDED_FOR, "1.2.3.4, 198.51.100.1"), (X_FORWARDED_FOR, "192.0.2.7")];
+    assert_eq!(client("10.0.0.2", &lines, &proxies), "198.51.100.1");
+
+    // Every hop trusted, the leftmost is as far back as it goes
+    assert_eq!(client("10.0.0.2", &[(X_FORWARDED_FOR, "10.1.1.1, 10.2.2.2")], &proxies), "10.1.1.1");
+    // Garbage stops the walk at the last address that could be read
+    assert_eq!(client("10.0.0.2", &[(X_FORWARDED_FOR, "198.51.100.1, unknown, 10.3.3.3")], &proxies), "10.3.3.3");
+    // No header at all leaves the proxy itself
+    assert_eq!(client("10.0.0.2", &[], &proxies), "10.0.0.2");
+}
+
+#[test]
+fn ipv6_addresses_and_the_forwarded_header() {
+    let proxies = proxies(&[("TRUSTED_PROXIES", "fd00::/8,10.0.0.0/8"), ("FORWARDED_HEADER", "forwarded")]);
+
+    let forwarded = r#"for="[2001:db8:cafe::17]:4711";proto=https, for=10.9.9.9;by=10.0.0.1"#;
+    assert_eq!(client("fd12::1", &[(FORWARDED, forwarded)], &proxies), "2001:db8:cafe::17");
+    // X-Forwarded-For isn't the configured header, so it's not consulted
+    assert_eq!(client("fd12::1", &[(X_FORWARDED_FOR, "198.51.100.1")], &proxies), "fd12::1");
+    // An IPv4 peer on a dual stack listener is matched against the IPv4 ranges
+    assert_eq!(client("::ffff:10.0.0.2", &[(FORWARDED, "for=198.51.100.1:51234")], &proxies), "198.51.100.1");
+
+    let proxies = self::proxies(&[("TRUSTED_PROXIES", "fd00::/8")]);
+    assert_eq!(client("fd12::1", &[(X_FORWARDED_FOR, "2001:db8::5, fd34::2")], &proxies), "2001:db8::5");
+}
+
+#[test]
+fn invalid_proxy_settings_are_reported() {
+    let error = settings_with(&[("TRUSTED_PROXIES", "10.0.0.0/33")]).err().unwrap();
+    assert!(error.to_string().contains("TRUSTED_PROXIES entry \"10.0.0.0/33\""), "{}", error);
+
+    let error = settings_with(&[("FORWARDED_HEADER", "x-real-ip")]).err().unwrap();
+    assert!(error.to_string().contains("FORWARDED_HEADER must be"), "{}", error);
+}
+
+#[actix_web::test]
+async fn sessions_record_the_client_behind_the_proxy() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[("TRUSTED_PROXIES", "10.0.0.0/8")]).unwrap()).await;
+
+    let request = TestRequest::post()
+        .uri("/api/v1/users/login")
+        .peer_addr("10.0.0.2:40000".parse().unwrap())
+        .insert_header((X_FORWARDED_FOR, "198.51.100.1"))
+        .set_json(json!({ "username": TEST_USERNAME, "password": TEST_PASSWORD }))
+        .to_request();
+    let response = call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let tokens: Value = read_body_json(response).await;
+
+    let request = TestRequest::get()
+        .uri("/api/v1/users/me/sessions")
+        .insert_header(("Authorization", format!("Bearer {}", tokens["access"].as_str().unwrap())))
+        .to_request();
+    let sessions: Value = read_body_json(call_service(&app, request).await).await;
+    assert_eq!(sessions["items"][0]["ip_address"], "198.51.100.1");
+}
@@ -0,0 +1,343 @@
+// Shared setup for the end-to-end tests. Each test gets its own database, created next to
+// the one DATABASE_URL points at, migrated and seeded, and dropped again when the test
+// finishes, whether or not it passed. With a `sqlite:` DATABASE_URL every test gets a
+// fresh in-memory SQLite database instead, so the suite runs without a Postgres server.
+#![allow(dead_code)]
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App, Error};
+use serde_json::Value;
+use sqlx::any::{AnyConnectOptions, AnyKind};
+use sqlx::postgres::PgConnectOptions;
+use sqlx::{AnyPool, ConnectOptions, Connection, Executor};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use wordle_solver::config::{ConfigError, Settings};
+use wordle_solver::errors;
+use wordle_solver::handlers::api_routes;
+use wordle_solver::repositories::words::DbWords;
+use wordle_solver::utils::bcrypt_utils::hash_password;
+use wordle_solver::utils::cache_utils::AuthCache;
+use wordle_solver::utils::coalesce_utils::SingleFlight;
+use wordle_solver::utils::clock_utils::{Clock, SystemClock};
+use wordle_solver::utils::concurrency_utils::ConcurrencyLimits;
+use wordle_solver::utils::db_utils::{migrator, pool_options, DbStatus};
+use wordle_solver::utils::feature_utils::FeatureFlags;
+use wordle_solver::utils::jwt_utils::SigningKeys;
+use wordle_solver::utils::metrics_utils::Metrics;
+use wordle_solver::utils::shutdown_utils::shutdown_channel;
+use wordle_solver::utils::solver_cache_utils::SolverCache;
+use wordle_solver::utils::store_utils::Stores;
+use wordle_solver::utils::usage_utils::UsageRecorder;
+use wordle_solver::utils::word_check_utils::WordCheckStatus;
+use wordle_solver::AppState;
+
+pub const TEST_USERNAME: &str = "tester";
+pub const TEST_EMAIL: &str = "tester@example.com";
+pub const TEST_PASSWORD: &str = "correct horse battery";
+
+pub const TEST_WORDS: &[&str] = &["crane", "crate", "trace", "react", "slate", "adieu", "pious"];
+
+pub struct TestDb {
+    pub pool: AnyPool,
+    // The Postgres database to drop afterwards, in-memory SQLite goes away with the pool
+    postgres: Option<(String, PgConnectOptions)>,
+}
+
+impl TestDb {
+    pub async fn new() -> TestDb {
+        TestDb::with_settings(&settings()).await
+    }
+
+    // The pool is built the way main builds it, so pool size and timeouts come from `settings`
+    pub async fn with_settings(settings: &Settings) -> TestDb {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL must point at a Postgres server or be sqlite::memory: for the tests");
+
+        if url.starts_with("sqlite:") {
+            // Every parse of sqlite::memory: names a new database, shared by the pool's connections
+            let options = AnyConnectOptions::from_str("sqlite::memory:").unwrap();
+            let pool = pool_options(settings)
+                .connect_with(options)
+                .await
+                .expect("Failed to open an in-memory SQLite database");
+
+            return TestDb::prepare(pool, None).await;
+        }
+
+        let server = PgConnectOptions::from_str(&url).expect("DATABASE_URL is not a valid Postgres URL");
+        let name = format!("wordle_test_{}", uuid::Uuid::new_v4().simple());
+
+        let mut admin = server.connect().await.expect("Failed to connect to DATABASE_URL");
+        admin
+            .execute(format!(r#"CREATE DATABASE "{}""#, name).as_str())
+            .await
+            .expect("Failed to create the test database");
+        let _ = admin.close().await;
+
+        let pool = pool_options(settings)
+            .connect_with(AnyConnectOptions::from(server.clone().database(&name)))
+            .await
+            .expect("Failed to connect to the test database");
+
+        TestDb::prepare(pool, Some((name, server))).await
+    }
+
+    // A database set up elsewhere, used as it is
+    pub fn from_pool(pool: AnyPool) -> TestDb {
+        TestDb { pool, postgres: None }
+    }
+
+    async fn prepare(pool: AnyPool, postgres: Option<(String, PgConnectOptions)>) -> TestDb {
+        migrator(pool.any_kind()).run(&pool).await.expect("Failed to migrate the test database");
+
+        let db = TestDb { pool, postgres };
+        db.seed().await;
+        db
+    }
+
+    pub fn is_postgres(&self) -> bool {
+        self.pool.any_kind() == AnyKind::Postgres
+    }
+
+    // Makes every later insert into `table` fail, to check that the steps before it in the
+    // same transaction are undone
+    pub async fn fail_inserts_into(&self, table: &str) {
+        let statements = if self.is_postgres() {
+            format!(
+                r#"
+                CREATE OR REPLACE FUNCTION injected_failure() RETURNS trigger AS $$
+                BEGIN RAISE EXCEPTION 'injected failure'; END
+                $$ LANGUAGE plpgsql;
+                CREATE TRIGGER fail_{table} BEFORE INSERT ON {table} FOR EACH ROW EXECUTE FUNCTION injected_failure();
+                "#
+            )
+        } else {
+            format!("CREATE TRIGGER fail_{table} BEFORE INSERT ON {table} BEGIN SELECT RAISE(ABORT, 'injected failure'); END;")
+        };
+
+        self.pool.execute(statements.as_str()).await.expect("Failed to install the failing trigger");
+    }
+
+    async fn seed(&self) {
+        for word in TEST_WORDS {
+            sqlx::query("INSERT INTO word_list (word) VALUES ($1)")
+                .bind(word)
+                .execute(&self.pool)
+                .await
+                .expect("Failed to seed the word list");
+        }
+
+        sqlx::query("INSERT INTO users (username, email, password) VALUES ($1, $2, $3)")
+            .bind(TEST_USERNAME)
+            .bind(TEST_EMAIL)
+            .bind(hash_password(TEST_PASSWORD, 4).unwrap())
+            .execute(&self.pool)
+            .await
+            .expect("Failed to seed the test user");
+    }
+}
+
+impl Drop for TestDb {
+    // Runs on its own thread since the test's runtime may already be gone. FORCE drops the
+    // pool's connections along with the database.
+    fn drop(&mut self) {
+        let (name, server) = match self.postgres.take() {
+            Some(postgres) => postgres,
+            None => return,
+        };
+        let statement = format!(r#"DROP DATABASE IF EXISTS "{}" WITH (FORCE)"#, name);
+
+        let dropped = std::thread::spawn(move || {
+            futures::executor::block_on(async move {
+                let mut admin = server.connect().await?;
+                admin.execute(statement.as_str()).await?;
+                admin.close().await
+            })
+        })
+        .join();
+
+        if !matches!(dropped, Ok(Ok(()))) {
+            eprintln!("Failed to drop test database {}", name);
+        }
+    }
+}
+
+pub async fn user_id(db: &TestDb, username: &str) -> i32 {
+    sqlx::query_scalar("SELECT id FROM users WHERE username = $1").bind(username).fetch_one(&db.pool).await.unwrap()
+}
+
+// Gives the seeded user the admin role
+pub async fn make_admin(db: &TestDb) {
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = $1")
+        .bind(TEST_USERNAME)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+}
+
+// Adds `words` to the seeded word list, a few hundred per statement. They're spelled into
+// the SQL, so only made up lowercase words belong here.
+pub async fn seed_words(db: &TestDb, words: &[String]) {
+    for chunk in words.chunks(500) {
+        let values: Vec<String> = chunk.iter().map(|word| format!("('{}')", word)).collect();
+        db.pool
+            .execute(format!("INSERT INTO word_list (word) VALUES {}", values.join(", ")).as_str())
+            .await
+            .expect("Failed to seed the word list");
+    }
+}
+
+pub fn settings() -> Settings {
+    settings_with(&[]).expect("Test settings are invalid")
+}
+
+// The keys an app built from settings() signs with, for tokens made outside a request
+pub fn signing_keys() -> SigningKeys {
+    SigningKeys::load(&settings().jwt).expect("Test signing keys are invalid")
+}
+
+// The test defaults with some variables replaced or added
+pub fn settings_with(overrides: &[(&str, &str)]) -> Result<Settings, ConfigError> {
+    let mut vars: HashMap<String, String> = [
+        ("DATABASE_URL", "postgres://unused"),
+        ("SECRET_KEY", "integration-test-secret"),
+        // The lowest cost bcrypt allows, real hashing would dominate the test run
+        ("BCRYPT_COST", "4"),
+    ]
+    .into_iter()
+    .map(|(name, value)| (name.to_string(), value.to_string()))
+    .collect();
+
+    for (name, value) in overrides {
+        vars.insert(name.to_string(), value.to_string());
+    }
+
+    Settings::from_vars(&vars)
+}
+
+pub fn state(db: &TestDb, settings: Settings) -> AppState {
+    state_with_clock(db, settings, Arc::new(SystemClock))
+}
+
+// For tests that move time themselves, usually with a FakeClock
+pub fn state_with_clock(db: &TestDb, settings: Settings, clock: Arc<dyn Clock>) -> AppState {
+    let settings = Arc::new(settings);
+
+    AppState {
+        signing_keys: Arc::new(SigningKeys::load(&settings.jwt).expect("Test signing keys are invalid")),
+        db: db.pool.clone(),
+        words: Arc::new(DbWords::new(db.pool.clone())),
+        auth_cache: Arc::new(AuthCache::new(settings.auth_cache_ttl, clock.clone())),
+        stores: Stores::from_settings(&settings),
+        metrics: Arc::new(Metrics::new(None)),
+        features: Arc::new(FeatureFlags::new(&settings.feature_defaults)),
+        limits: Arc::new(ConcurrencyLimits::new(&settings.concurrency_limits, settings.concurrency_queue_timeout)),
+        db_status: Arc::new(DbStatus::default()),
+        clock,
+        shutdown: shutdown_channel().1,
+        usage: Arc::new(UsageRecorder::new()),
+        best_guesses: Arc::new(SingleFlight::new(settings.solver_timeout)),
+        solver_cache: Arc::new(SolverCache::new(settings.solver_cache_path.as_deref())),
+        word_check: Arc::new(WordCheckStatus::default()),
+        settings,
+    }
+}
+
+pub async fn init_app(db: &TestDb) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    init_app_with(db, settings()).await
+}
+
+// The API routes as main mounts them, without the outer middleware
+pub async fn init_app_with(db: &TestDb, settings: Settings) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    init_app_with_clock(db, settings, Arc::new(SystemClock)).await
+}
+
+pub async fn init_app_with_clock(db: &TestDb, settings: Settings, clock: Arc<dyn Clock>) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    init_app_with_state(state_with_clock(db, settings, clock)).await
+}
+
+// For tests that replace part of the state, like the word repository
+pub async fn init_app_with_state(state: AppState) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    let legacy_api_routes = state.settings.legacy_api_routes;
+
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .app_data(errors::json_config(errors::DEFAULT_JSON_LIMIT))
+            .app_data(errors::query_config())
+            .app_data(errors::path_config())
+            .configure(api_routes(legacy_api_routes)),
+    )
+    .await
+}
+
+// Sends a JSON body, with a bearer token when one is given, and returns the status and
+// the decoded response (Null when the body isn't JSON)
+pub async fn post_json<S, B>(app: &S, uri: &str, body: &Value, token: Option<&str>) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let mut request = test::TestRequest::post().uri(uri).set_json(body);
+    if let Some(token) = token {
+        request = request.insert_header(("Authorization", format!("Bearer {}", token)));
+    }
+
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+// Like `post_json`, for GET requests
+pub async fn get_json<S, B>(app: &S, uri: &str, token: Option<&str>) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let mut request = test::TestRequest::get().uri(uri);
+    if let Some(token) = token {
+        request = request.insert_header(("Authorization", format!("Bearer {}", token)));
+    }
+
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+pub async fn register<S, B>(app: &S, username: &str, email: &str, password: &str) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let body = serde_json::json!({ "username": username, "email": email, "password": password });
+    post_json(app, "/api/v1/users/register", &body, None).await
+}
+
+pub async fn login<S, B>(app: &S, username: &str, password: &str) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let body = serde_json::json!({ "username": username, "password": password });
+    post_json(app, "/api/v1/users/login", &body, None).await
+}
+
+// Logs in as the seeded user and returns its access token
+pub async fn access_token<S, B>(app: &S) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (status, tokens) = login(app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "seeded user could not log in: {}", tokens);
+
+    tokens["access"].as_str().expect("login response has no access token").to_string()
+}
@@ -0,0 +1,166 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, ETAG, IF_NONE_MATCH, VARY};
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App, Error, HttpResponse};
+use common::{access_token, seed_words, settings_with, state, TestDb};
+use flate2::read::GzDecoder;
+use serde_json::{json, Value};
+use std::io::{Read, Write};
+use wordle_solver::handlers::api_routes;
+use wordle_solver::handlers::health::health_routes;
+use wordle_solver::middleware::compression::Compression;
+
+fn gunzip(body: &[u8]) -> Vec<u8> {
+    let mut plain = Vec::new();
+    GzDecoder::new(body).read_to_end(&mut plain).expect("body isn't gzip");
+    plain
+}
+
+fn gzip(body: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(body).unwrap();
+    encoder.finish().unwrap()
+}
+
+async fn compressed_app(db: &TestDb) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    let settings = settings_with(&[("MAX_PAGE_SIZE", "500"), ("COMPRESSION_MIN_BYTES", "1024")]).unwrap();
+    let compression = Compression::new(&settings.compression_encodings, settings.compression_min_size);
+
+    test::init_service(
+        App::new()
+            .app_data(web::Data::new(state(db, settings)))
+            .configure(api_routes(false))
+            .configure(health_routes)
+            // Stands in for a handler serving a body it compressed ahead of time
+            .route("/pre-encoded", web::get().to(|| async {
+                HttpResponse::Ok().insert_header((CONTENT_ENCODING, "gzip")).body(gzip(b"already compressed"))
+            }))
+            .wrap(compression),
+    )
+    .await
+}
+
+// Counts up from "baaaa", enough words for a candidate list of several KiB
+fn counted_words(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|n| {
+            let mut rest = n + 26usize.pow(4);
+            let mut word = Vec::new();
+            for _ in 0..5 {
+                word.insert(0, b'a' + (rest % 26) as u8);
+                rest /= 26;
+            }
+            String::from_utf8(word).unwrap()
+        })
+        .collect()
+}
+
+#[actix_web::test]
+async fn large_candidate_lists_are_gzipped() {
+    let db = TestDb::new().await;
+    seed_words(&db, &counted_words(400)).await;
+    let app = compressed_app(&db).await;
+    let token = access_token(&app).await;
+
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "_____" });
+    let request = |encoding: Option<&str>| {
+        let mut request = test::TestRequest::post()
+            .uri("/api/v1/game/candidates?per_page=500")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(&letters);
+        if let Some(encoding) = encoding {
+            request = request.insert_header((ACCEPT_ENCODING, encoding));
+        }
+        request.to_request()
+    };
+
+    let plain = test::call_service(&app, request(None)).await;
+    assert_eq!(plain.status(), StatusCode::OK);
+    assert!(plain.headers().get(CONTENT_ENCODING).is_none());
+    let plain = test::read_body(plain).await;
+
+    let compressed = test::call_service(&app, request(Some("gzip"))).await;
+    assert_eq!(compressed.status(), StatusCode::OK);
+    assert_eq!(compressed.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+    assert_eq!(compressed.headers().get(VARY).unwrap(), "accept-encoding");
+    let compressed = test::read_body(compressed).await;
+
+    assert!(plain.len() > 1024, "only {} bytes", plain.len());
+    assert!(compressed.len() < plain.len() / 2, "{} of {} bytes", compressed.len(), plain.len());
+    assert_eq!(gunzip(&compressed), plain.to_vec());
+    let page: Value = serde_json::from_slice(&plain).unwrap();
+    assert!(page["total"].as_i64().unwrap() >= 400);
+}
+
+#[actix_web::test]
+async fn small_and_already_encoded_bodies_are_left_alone() {
+    let db = TestDb::new().await;
+    let app = compressed_app(&db).await;
+
+    let request = test::TestRequest::get().uri("/health/live").insert_header((ACCEPT_ENCODING, "gzip, br")).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(CONTENT_ENCODING).is_none());
+    // The same URL can come back compressed once it's larger, so caches still have to vary
+    assert_eq!(response.headers().get(VARY).unwrap(), "accept-encoding");
+
+    let request = test::TestRequest::get().uri("/pre-encoded").insert_header((ACCEPT_ENCODING, "br")).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+    assert_eq!(gunzip(&test::read_body(response).await), b"already compressed");
+}
+
+#[actix_web::test]
+async fn compressed_responses_revalidate_with_a_weak_etag() {
+    let db = TestDb::new().await;
+    seed_words(&db, &counted_words(400)).await;
+    let app = compressed_app(&db).await;
+    let token = access_token(&app).await;
+    let fetch = |etag: Option<&str>| {
+        let mut request = test::TestRequest::get()
+            .uri("/api/v1/game/words")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .insert_header((ACCEPT_ENCODING, "gzip"));
+        if let Some(etag) = etag {
+            request = request.insert_header((IF_NONE_MATCH, etag.to_string()));
+        }
+        request.to_request()
+    };
+
+    let response = test::call_service(&app, fetch(None)).await;
+    assert_eq!(response.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+    let etag = response.headers().get(ETAG).unwrap().to_str().unwrap().to_string();
+    assert!(etag.starts_with("W/\""), "expected a weak tag, got {}", etag);
+    let words: Vec<String> = serde_json::from_slice(&gunzip(&test::read_body(response).await)).unwrap();
+    assert!(words.len() >= 400);
+
+    let response = test::call_service(&app, fetch(Some(&etag))).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers().get(ETAG).unwrap(), etag.as_str());
+    assert_eq!(response.headers().get(VARY).unwrap(), "accept-encoding");
+    assert!(response.headers().get(CONTENT_ENCODING).is_none());
+    assert!(test::read_body(response).await.is_empty());
+}
+
+#[actix_web::test]
+async fn streamed_exports_are_compressed_as_they_go() {
+    let db = TestDb::new().await;
+    let app = compressed_app(&db).await;
+    let token = access_token(&app).await;
+
+    let request = test::TestRequest::get()
+        .uri("/api/v1/users/me/export")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .insert_header((ACCEPT_ENCODING, "gzip"))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get(CONTENT_ENCODING).unwrap(), "gzip");
+    let export: Value = serde_json::from_slice(&gunzip(&test::read_body(response).await)).unwrap();
+    assert_eq!(export["schema_version"], 1);
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use wordle_solver::config::Settings;
+
+// The fewest variables a valid configuration needs
+const REQUIRED: &[(&str, &str)] = &[("DATABASE_URL", "postgres://unused"), ("SECRET_KEY", "config-test-secret")];
+
+fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+// Every problem reported for the required variables with `overrides` on top
+fn problems(overrides: &[(&str, &str)]) -> Vec<String> {
+    let mut vars = vars(REQUIRED);
+    vars.extend(overrides.iter().map(|(name, value)| (name.to_string(), value.to_string())));
+    match Settings::from_vars(&vars) {
+        Ok(_) => Vec::new(),
+        Err(error) => error.0,
+    }
+}
+
+#[test]
+fn the_required_variables_are_enough() {
+    let settings = Settings::from_vars(&vars(REQUIRED)).unwrap();
+    assert_eq!(settings.database_url, "postgres://unused");
+    assert_eq!(settings.jwt.hmac_keys, vec![("default".to_string(), "config-test-secret".to_string())]);
+    assert!(settings.jwt.rsa_keys.is_empty());
+}
+
+#[test]
+fn every_missing_variable_is_reported_at_once() {
+    let error = Settings::from_vars(&HashMap::new()).err().expect("an empty environment was accepted");
+    assert_eq!(error.0, vec!["DATABASE_URL is required".to_string(), "SECRET_KEY or JWT_KEYS is required".to_string()]);
+
+    // Blank counts as unset
+    let error = Settings::from_vars(&vars(&[("DATABASE_URL", "  "), ("SECRET_KEY", "config-test-secret")])).err().unwrap();
+    assert_eq!(error.0, vec!["DATABASE_URL is required".to_string()]);
+}
+
+#[test]
+fn invalid_values_are_named() {
+    let cases: &[(&[(&str, &str)], &str)] = &[
+        (&[("DB_MAX_CONNECTIONS", "many")], "DB_MAX_CONNECTIONS has an invalid value \"many\""),
+        (&[("DB_MAX_CONNECTIONS", "0")], "DB_MAX_CONNECTIONS must be at least 1"),
+        (&[("RUN_MIGRATIONS", "yes")], "RUN_MIGRATIONS must be true or false, got \"yes\""),
+        (&[("STORAGE", "files")], "STORAGE must be database or memory, got \"files\""),
+        (&[("JWT_ALGORITHM", "ES256")], "JWT_ALGORITHM must be HS256 or RS256, got \"ES256\""),
+        (&[("JWT_ALGORITHM", "RS256")], "JWT_RSA_KEYS is required when JWT_ALGORITHM is RS256"),
+        (&[("JWT_KEYS", "first:one,second")], "JWT_KEYS entries must be kid:value, got \"second\""),
+        (&[("JWT_KEYS", "first:one,:two")], "JWT_KEYS entries must be kid:value, got \":two\""),
+        (&[("JWT_RSA_KEYS", "rsa:")], "JWT_RSA_KEYS entries must be kid:value, got \"rsa:\""),
+    ];
+
+    for (overrides, expected) in cases {
+        assert_eq!(problems(overrides), vec![expected.to_string()], "{:?}", overrides);
+    }
+}
+
+#[test]
+fn key_ids_are_unique() {
+    assert_eq!(problems(&[("JWT_KEYS", "old:one,new:two,old:three")]), vec!["JWT_KEYS repeats the key id \"old\"".to_string()]);
+    // Across both families too, the HMAC key would shadow the RSA one
+    assert_eq!(problems(&[("JWT_RSA_KEYS", "default:/keys/rsa.pem")]), vec!["JWT_RSA_KEYS repeats the key id \"default\"".to_string()]);
+    assert_eq!(problems(&[("JWT_KEYS", "old:one"), ("JWT_RSA_KEYS", "old:/keys/rsa.pem")]), vec!["JWT_RSA_KEYS repeats the key id \"old\"".to_string()]);
+
+    assert!(problems(&[("JWT_KEYS", "old:one, new:two"), ("JWT_RSA_KEYS", "rsa:/keys/rsa.pem")]).is_empty());
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use actix_web::http::header::{
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_ORIGIN,
+    ACCESS_CONTROL_MAX_AGE, ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+};
+use actix_web::http::header::{HeaderMap, HeaderName};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, App, HttpResponse};
+use common::settings_with;
+use wordle_solver::middleware::cors::cors;
+
+// Sends a browser style preflight for a login from `origin` and returns the status and headers
+async fn preflight(overrides: &[(&str, &str)], origin: &str, method: &str) -> (StatusCode, HeaderMap) {
+    let settings = settings_with(overrides).expect("CORS settings are invalid");
+    let app = actix_web::test::init_service(
+        App::new()
+            .wrap(cors(&settings.cors))
+            .route("/api/v1/users/login", web::post().to(HttpResponse::Ok)),
+    )
+    .await;
+
+    let request = actix_web::test::TestRequest::default()
+        .method(Method::OPTIONS)
+        .uri("/api/v1/users/login")
+        .insert_header((ORIGIN, origin))
+        .insert_header((ACCESS_CONTROL_REQUEST_METHOD, method))
+        .insert_header((ACCESS_CONTROL_REQUEST_HEADERS, "authorization, x-api-key"))
+        .to_request();
+
+    let response = actix_web::test::call_service(&app, request).await;
+    (response.status(), response.headers().clone())
+}
+
+fn header(headers: &HeaderMap, name: HeaderName) -> &str {
+    headers.get(name).and_then(|value| value.to_str().ok()).unwrap_or_default()
+}
+
+#[actix_web::test]
+async fn preflight_from_allowed_origin() {
+    let origins = [("CORS_ALLOWED_ORIGINS", "https://wordle.example.com"), ("CORS_MAX_AGE_SECONDS", "600")];
+    let (status, headers) = preflight(&origins, "https://wordle.example.com", "POST").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(header(&headers, ACCESS_CONTROL_ALLOW_ORIGIN), "https://wordle.example.com");
+    assert_eq!(header(&headers, ACCESS_CONTROL_ALLOW_CREDENTIALS), "true");
+    assert_eq!(header(&headers, ACCESS_CONTROL_MAX_AGE), "600");
+
+    let allowed_headers = header(&headers, ACCESS_CONTROL_ALLOW_HEADERS);
+    assert!(allowed_headers.contains("authorization"), "{}", allowed_headers);
+    assert!(allowed_headers.contains("x-api-key"), "{}", allowed_headers);
+}
+
+#[actix_web::test]
+async fn preflight_from_disallowed_origin() {
+    let origins = [("CORS_ALLOWED_ORIGINS", "https://wordle.example.com")];
+    let (status, headers) = preflight(&origins, "https://evil.example.com", "POST").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(headers.get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+}
+
+#[actix_web::test]
+async fn preflight_with_disallowed_method() {
+    let origins = [("CORS_ALLOWED_ORIGINS", "https://wordle.example.com"), ("CORS_ALLOWED_METHODS", "GET,POST")];
+    let (status, _) = preflight(&origins, "https://wordle.example.com", "DELETE").await;
+
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn wildcard_origin_without_credentials() {
+    let origins = [("CORS_ALLOWED_ORIGINS", "*"), ("CORS_ALLOW_CREDENTIALS", "false")];
+    let (status, headers) = preflight(&origins, "http://localhost:5173", "POST").await;
+
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers.get(ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+}
+
+#[test]
+fn wildcard_origin_with_credentials_is_rejected() {
+    let error = settings_with(&[("CORS_ALLOWED_ORIGINS", "*")]).err().expect("wildcard with credentials was accepted");
+
+    assert!(error.to_string().contains("CORS_ALLOWED_ORIGINS=*"), "{}", error);
+}
+
+#[test]
+fn origins_with_paths_are_rejected() {
+    let error = settings_with(&[("CORS_ALLOWED_ORIGINS", "https://wordle.example.com/app")])
+        .err()
+        .expect("origin with a path was accepted");
+
+    assert!(error.to_string().contains("without a path"), "{}", error);
+}
@@ -0,0 +1,97 @@
+use actix_web::rt;
+use sqlx::any::AnyPoolOptions;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use wordle_solver::utils::db_utils::{wait_for_database, DbStatus, SQLITE_MIGRATOR};
+use wordle_solver::utils::shutdown_utils::shutdown_channel;
+
+// Nothing listens on port 1, so every attempt is refused straight away
+const UNREACHABLE_URL: &str = "postgres://wordle@127.0.0.1:1/wordle";
+
+// A fresh directory for database files, removed again when the test is done
+struct DbDir(PathBuf);
+
+impl DbDir {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("wordle-db-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        DbDir(dir)
+    }
+
+    fn url(&self, name: &str) -> String {
+        format!("sqlite://{}", self.0.join(name).display())
+    }
+}
+
+impl Drop for DbDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// The number out of "Could not reach the database after N attempts ..."
+fn attempts(error: &str) -> u32 {
+    let rest = error.strip_prefix("Could not reach the database after ").unwrap_or_else(|| panic!("unexpected error: {}", error));
+    rest.split(' ').next().unwrap().parse().unwrap()
+}
+
+#[actix_web::test]
+async fn an_unreachable_database_is_given_up_on_after_max_wait() {
+    let pool = AnyPoolOptions::new().connect_lazy(UNREACHABLE_URL).unwrap();
+    let status = DbStatus::default();
+    let (_shutdown_sender, shutdown) = shutdown_channel();
+
+    let started = Instant::now();
+    let error = wait_for_database(&pool, UNREACHABLE_URL, Duration::from_secs(2), &status, shutdown).await.unwrap_err();
+
+    // Retried with backoff rather than failing on the first refusal
+    assert!(attempts(&error) >= 2, "{}", error);
+    assert!(started.elapsed() <= Duration::from_secs(3), "kept trying for {:?}", started.elapsed());
+    assert!(!status.is_connected());
+}
+
+#[actix_web::test]
+async fn a_database_that_comes_up_late_is_connected_to() {
+    let dir = DbDir::new();
+    // Migrated under another name, so it appears complete or not at all
+    let staging = AnyPoolOptions::new().connect(&format!("{}?mode=rwc", dir.url("staging.db"))).await.unwrap();
+    SQLITE_MIGRATOR.run(&staging).await.unwrap();
+    staging.close().await;
+
+    // Without mode=rwc connecting fails until the file exists
+    let url = dir.url("wordle.db");
+    let pool = AnyPoolOptions::new().connect_lazy(&url).unwrap();
+    let status = DbStatus::default();
+    let (_shutdown_sender, shutdown) = shutdown_channel();
+    let (from, to) = (dir.0.join("staging.db"), dir.0.join("wordle.db"));
+    let appears = rt::spawn(async move {
+        rt::time::sleep(Duration::from_millis(700)).await;
+        std::fs::rename(from, to).unwrap();
+    });
+
+    let started = Instant::now();
+    wait_for_database(&pool, &url, Duration::from_secs(10), &status, shutdown).await.unwrap();
+    // At least one refused attempt before the file was there
+    assert!(started.elapsed() >= Duration::from_millis(700), "connected after only {:?}", started.elapsed());
+    assert!(status.is_connected());
+
+    appears.await.unwrap();
+    pool.close().await;
+}
+
+#[actix_web::test]
+async fn shutdown_stops_the_retries() {
+    let pool = AnyPoolOptions::new().connect_lazy(UNREACHABLE_URL).unwrap();
+    let status = DbStatus::default();
+    let (shutdown_sender, shutdown) = shutdown_channel();
+    rt::spawn(async move {
+        rt::time::sleep(Duration::from_millis(300)).await;
+        let _ = shutdown_sender.send(true);
+    });
+
+    let started = Instant::now();
+    let error = wait_for_database(&pool, UNREACHABLE_URL, Duration::from_secs(60), &status, shutdown).await.unwrap_err();
+    assert_eq!(error, "Shutdown requested before the database was reachable");
+    assert!(started.elapsed() < Duration::from_secs(5), "stopped only after {:?}", started.elapsed());
+    assert!(!status.is_connected());
+}
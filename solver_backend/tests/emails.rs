@@ -0,0 +1,232 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use async_trait::async_trait;
+use chrono::{Duration, Utc};
+use common::{access_token, get_json, init_app_with_clock, post_json, register, settings, settings_with, TestDb, TEST_EMAIL, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use wordle_solver::repositories::words::DbWords;
+use wordle_solver::utils::clock_utils::{Clock, FakeClock};
+use wordle_solver::utils::job_utils::{retry_delay, run_next_job, BuiltinJobs};
+use wordle_solver::utils::mail_utils::{mailer_from_settings, queue_email, Email, MailError, Mailer, CONFIRM_EMAIL, EMAIL_CHANGED};
+
+// Takes mail over plain SMTP. Recipients starting with "refused" get a 550, ones starting
+// with "busy" a 451, as do ones starting with "flaky" the first time round.
+#[derive(Clone, Default)]
+struct Capture {
+    messages: Arc<Mutex<Vec<String>>>,
+    tries: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl Capture {
+    fn serve(&self) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let capture = self.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let capture = capture.clone();
+                thread::spawn(move || capture.session(stream));
+            }
+        });
+        port
+    }
+
+    fn session(&self, stream: TcpStream) {
+        let mut writer = stream.try_clone().unwrap();
+        let mut reply = |line: &str| writer.write_all(format!("{}\r\n", line).as_bytes());
+        let mut lines = BufReader::new(stream).lines();
+        if reply("220 capture").is_err() {
+            return;
+        }
+
+        while let Some(Ok(line)) = lines.next() {
+            let command = line.to_uppercase();
+            let answer = if command.starts_with("RCPT TO:") {
+                let recipient = line[8..].trim_matches(|c| c == '<' || c == '>' || c == ' ').to_string();
+                let mut tries = self.tries.lock().unwrap();
+                let tried = tries.entry(recipient.clone()).or_default();
+                *tried += 1;
+                if recipient.starts_with("refused") {
+                    "550 no such user"
+                } else if recipient.starts_with("busy") || (recipient.starts_with("flaky") && *tried == 1) {
+                    "451 try again later"
+                } else {
+                    "250 ok"
+                }
+            } else if command == "DATA" {
+                let _ = reply("354 go ahead");
+                let mut message = Vec::new();
+                for line in lines.by_ref().map_while(Result::ok).take_while(|line| line != ".") {
+                    message.push(line);
+                }
+                self.messages.lock().unwrap().push(message.join("\n"));
+                "250 queued"
+            } else if command == "QUIT" {
+                let _ = reply("221 bye");
+                return;
+            } else {
+                "250 capture"
+            };
+            if reply(answer).is_err() {
+                return;
+            }
+        }
+    }
+
+    fn messages(&self) -> Vec<String> {
+        self.messages.lock().unwrap().clone()
+    }
+}
+
+#[actix_web::test]
+async fn templates_fill_in_their_values_escaped_for_html() {
+    let email = CONFIRM_EMAIL.render("new@example.com", &[("token", "a<b>&c"), ("hours", "24")]);
+    assert_eq!(email.to, "new@example.com");
+    assert_eq!(email.subject, "Confirm your new email address");
+    assert_eq!(email.text, "Confirm your new email address by visiting /api/v1/users/confirm-email?token=a<b>&c\nThis link expires in 24 hours.");
+    let html = email.html.unwrap();
+    assert!(html.contains("token=a&lt;b&gt;&amp;c"), "{}", html);
+    assert!(html.contains("It expires in 24 hours."));
+
+    let email = EMAIL_CHANGED.render("old@example.com", &[("email", "new@example.com")]);
+    assert_eq!(email.text, "The email address on your account was changed to new@example.com. If this wasn't you, contact support.");
+    assert_eq!(email.html, None);
+}
+
+#[actix_web::test]
+async fn mail_goes_out_over_smtp_and_failures_are_retried_then_dead_lettered() {
+    let capture = Capture::default();
+    let port = capture.serve().to_string();
+    let db = TestDb::new().await;
+    let settings = settings_with(&[("SMTP_HOST", "127.0.0.1"), ("SMTP_PORT", &port), ("SMTP_TLS", "none"), ("MAIL_FROM", "Wordle <wordle@example.com>"), ("MAIL_MAX_ATTEMPTS", "2")]).unwrap();
+    let mail = settings.mail.clone();
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings, clock.clone()).await;
+    let runner = BuiltinJobs { db: db.pool.clone(), words: Arc::new(DbWords::new(db.pool.clone())), webhooks: common::settings().webhooks, mailer: mailer_from_settings(&mail).unwrap(), clock: clock.clone() };
+    let token = access_token(&app).await;
+    // Logging in again on the frozen clock would issue the same tokens, so keep these
+    let (_, tokens) = register(&app, "postmaster", "postmaster@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'postmaster'").execute(&db.pool).await.unwrap();
+    let admin = tokens["access"].as_str().unwrap().to_string();
+
+    // Logging in from a new device queued a notice
+    assert!(run_next_job(&db.pool, &runner, clock.as_ref()).await.unwrap().is_some());
+    let messages = capture.messages();
+    assert_eq!(messages.len(), 1);
+    assert!(messages[0].contains("Subject: New login to your account"), "{}", messages[0]);
+    assert!(messages[0].contains("To: tester@example.com"));
+    assert!(messages[0].contains("From: Wordle <wordle@example.com>"));
+
+    // Queued by the handler, the server is busy the first time
+    let (status, _) = post_json(&app, "/api/v1/users/me/email", &json!({ "email": "flaky@example.com", "password": TEST_PASSWORD }), Some(&token)).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert!(run_next_job(&db.pool, &runner, clock.as_ref()).await.unwrap().is_some());
+    let (_, page) = get_json(&app, "/api/v1/admin/emails?status=retrying", Some(&admin)).await;
+    assert_eq!(page["total"], 1);
+    let email = &page["items"][0];
+    assert_eq!((&email["template"], &email["recipient"], &email["attempts"]), (&json!("confirm_email"), &json!("flaky@example.com"), &json!(1)));
+    assert!(email["error"].as_str().unwrap().contains("451"), "{}", email);
+    assert_eq!(capture.messages().len(), 1);
+
+    // Not due before the backoff is up
+    assert_eq!(run_next_job(&db.pool, &runner, clock.as_ref()).await.unwrap(), None);
+    clock.advance(retry_delay(1));
+    assert!(run_next_job(&db.pool, &runner, clock.as_ref()).await.unwrap().is_some());
+    let (_, page) = get_json(&app, "/api/v1/admin/emails?status=sent", Some(&admin)).await;
+    assert_eq!((&page["total"], &page["items"][0]["recipient"], &page["items"][0]["attempts"]), (&json!(2), &json!("flaky@example.com"), &json!(2)));
+    assert!(page["items"][0]["sent_at"].is_string());
+    let messages = capture.messages();
+    assert_eq!(messages.len(), 2);
+    assert!(messages[1].contains("Subject: Confirm your new email address"), "{}", messages[1]);
+    assert!(messages[1].contains("To: flaky@example.com"));
+    assert!(messages[1].contains("text/html"));
+
+    // Refused for good: dead-lettered on the first attempt, and the job isn't retried
+    queue_email(&db.pool, &EMAIL_CHANGED, "refused@example.com", &[("email", "x@example.com")], mail.max_attempts, clock.now()).await.unwrap();
+    run_next_job(&db.pool, &runner, clock.as_ref()).await.unwrap();
+    // Busy every time: dead-lettered once the attempts run out
+    queue_email(&db.pool, &EMAIL_CHANGED, "busy@example.com", &[("email", "x@example.com")], mail.max_attempts, clock.now()).await.unwrap();
+    run_next_job(&db.pool, &runner, clock.as_ref()).await.unwrap();
+    clock.advance(retry_delay(1));
+    run_next_job(&db.pool, &runner, clock.as_ref()).await.unwrap();
+    clock.advance(retry_delay(2));
+    assert_eq!(run_next_job(&db.pool, &runner, clock.as_ref()).await.unwrap(), None);
+
+    let (_, page) = get_json(&app, "/api/v1/admin/emails?status=dead_letter", Some(&admin)).await;
+    let dead: Vec<(&str, i64)> = page["items"].as_array().unwrap().iter().map(|email| (email["recipient"].as_str().unwrap(), email["attempts"].as_i64().unwrap())).collect();
+    assert_eq!(dead, [("busy@example.com", 2), ("refused@example.com", 1)]);
+    let statuses: Vec<String> = sqlx::query_scalar("SELECT status FROM jobs WHERE job_type = 'email_delivery' ORDER BY id").fetch_all(&db.pool).await.unwrap();
+    assert_eq!(statuses, ["succeeded", "succeeded", "succeeded", "failed"]);
+    assert_eq!(capture.messages().len(), 2);
+
+    let (_, page) = get_json(&app, "/api/v1/admin/emails", Some(&admin)).await;
+    assert_eq!(page["total"], 4);
+    assert_eq!(get_json(&app, "/api/v1/admin/emails", Some(&token)).await.0, StatusCode::FORBIDDEN);
+}
+
+// Keeps whatever it's asked to send
+#[derive(Default)]
+struct CapturingMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+#[async_trait]
+impl Mailer for CapturingMailer {
+    async fn send(&self, email: &Email) -> Result<(), MailError> {
+        self.sent.lock().unwrap().push(email.clone());
+        Ok(())
+    }
+}
+
+#[actix_web::test]
+async fn only_a_login_from_a_new_device_sends_a_notice() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings(), clock.clone()).await;
+    let mailer = Arc::new(CapturingMailer::default());
+    let runner = BuiltinJobs { db: db.pool.clone(), words: Arc::new(DbWords::new(db.pool.clone())), webhooks: settings().webhooks, mailer: mailer.clone(), clock: clock.clone() };
+    let login_from = |agent: &'static str| {
+        let app = &app;
+        let clock = clock.clone();
+        async move {
+            // A second apart, or the frozen clock would issue the same tokens again
+            clock.advance(Duration::seconds(1));
+            let request = TestRequest::post()
+                .uri("/api/v1/users/login")
+                .peer_addr("198.51.100.7:40000".parse().unwrap())
+                .insert_header(("User-Agent", agent))
+                .set_json(json!({ "username": TEST_USERNAME, "password": TEST_PASSWORD }))
+                .to_request();
+            assert_eq!(test::call_service(app, request).await.status(), StatusCode::OK);
+        }
+    };
+    let send_queued = || {
+        let (db, runner, mailer, clock) = (&db, &runner, &mailer, &clock);
+        async move {
+            while run_next_job(&db.pool, runner, clock.as_ref()).await.unwrap().is_some() {}
+            mailer.sent.lock().unwrap().clone()
+        }
+    };
+
+    login_from("Mozilla/5.0 (Windows NT 10.0) Gecko/20100101 Firefox/115.0").await;
+    let sent = send_queued().await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!((sent[0].to.as_str(), sent[0].subject.as_str()), (TEST_EMAIL, "New login to your account"));
+    assert!(sent[0].text.contains("Firefox on Windows from 198.51.100.7"), "{}", sent[0].text);
+
+    // The same browser from the same network
+    login_from("Mozilla/5.0 (Windows NT 10.0) Gecko/20100101 Firefox/115.0").await;
+    assert_eq!(send_queued().await.len(), 1);
+
+    login_from("Mozilla/5.0 (iPhone; CPU iPhone OS 16_5 like Mac OS X) Version/16.5 Mobile Safari/604.1").await;
+    let sent = send_queued().await;
+    assert_eq!(sent.len(), 2);
+    assert!(sent[1].text.contains("Safari on iOS"), "{}", sent[1].text);
+}
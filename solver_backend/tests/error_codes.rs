@@ -0,0 +1,263 @@
+mod common;
+
+use actix_web::http::{Method, StatusCode};
+use actix_web::test::{self, TestRequest};
+use chrono::{Duration, Utc};
+use common::{access_token, init_app, login, post_json, register, settings, signing_keys, user_id, TestDb, TEST_EMAIL, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use utoipa::OpenApi;
+use wordle_solver::errors::ErrorCode;
+use wordle_solver::handlers::docs::ApiDoc;
+use wordle_solver::utils::jwt_utils::generate_access_token;
+
+// Probes answer with a bare status, not an error body
+const WITHOUT_ERROR_CODES: &[&str] = &["/health/live", "/health/ready"];
+
+const ADMIN_USERNAME: &str = "boss";
+const MISSING_ID: i32 = 999_999;
+
+#[derive(Clone, Copy)]
+enum Auth {
+    Anonymous,
+    User,
+    Admin,
+    Expired,
+}
+
+// One failing request and the code it must answer with. `route` is the documented path,
+// `uri` the one actually sent.
+struct Case {
+    method: Method,
+    route: &'static str,
+    uri: String,
+    auth: Auth,
+    body: Option<Value>,
+    status: StatusCode,
+    code: ErrorCode,
+}
+
+fn case(method: Method, route: &'static str, uri: impl Into<String>, auth: Auth, body: Option<Value>, status: StatusCode, code: ErrorCode) -> Case {
+    Case { method, route, uri: uri.into(), auth, body, status, code }
+}
+
+// Every documented endpoint needs at least one row here, so adding one means deciding
+// which codes its failures answer with. Each row runs against the same app and none of
+// them change anything the others depend on.
+#[actix_web::test]
+async fn every_endpoint_answers_with_a_deliberate_error_code() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    register(&app, ADMIN_USERNAME, "boss@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = $1").bind(ADMIN_USERNAME).execute(&db.pool).await.unwrap();
+    let (_, tokens) = login(&app, ADMIN_USERNAME, TEST_PASSWORD).await;
+    let admin_token = tokens["access"].as_str().unwrap().to_string();
+    let user_token = access_token(&app).await;
+    let tester = user_id(&db, TEST_USERNAME).await;
+    let boss = user_id(&db, ADMIN_USERNAME).await;
+
+    let expired_token = generate_access_token(&signing_keys(), tester, 0, &settings().jwt, Utc::now() - Duration::days(1)).unwrap();
+    let (_, tokens) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    let revoked_token = tokens["access"].as_str().unwrap().to_string();
+    let (status, _) = post_json(&app, "/api/v1/users/revoke_token", &json!({ "token": revoked_token }), Some(&revoked_token)).await;
+    assert_eq!(status, StatusCode::OK);
+    // Starts the export cooldown
+    let export = TestRequest::get().uri("/api/v1/users/me/export").insert_header(("Authorization", format!("Bearer {}", user_token)));
+    let response = test::call_service(&app, export.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    test::read_body(response).await;
+    // Switches guest tokens off
+    let flag = TestRequest::put().uri("/api/v1/admin/flags/guest_access").insert_header(("Authorization", format!("Bearer {}", admin_token))).set_json(json!({ "enabled": false }));
+    assert_eq!(test::call_service(&app, flag.to_request()).await.status(), StatusCode::OK);
+
+    let cases = vec![
+        // Accounts
+        case(Method::POST, "/api/v1/users/register", "/api/v1/users/register", Auth::Anonymous,
+            Some(json!({ "username": TEST_USERNAME, "email": TEST_EMAIL, "password": TEST_PASSWORD })), StatusCode::CONFLICT, ErrorCode::UserExists),
+        case(Method::POST, "/api/v1/users/guest", "/api/v1/users/guest", Auth::Anonymous, None, StatusCode::SERVICE_UNAVAILABLE, ErrorCode::FeatureDisabled),
+        case(Method::POST, "/api/v1/users/login", "/api/v1/users/login", Auth::Anonymous,
+            Some(json!({ "username": TEST_USERNAME, "password": "wrong password" })), StatusCode::UNAUTHORIZED, ErrorCode::InvalidCredentials),
+        case(Method::GET, "/api/v1/users", "/api/v1/users?page=0", Auth::Admin, None, StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidPage),
+        case(Method::GET, "/api/v1/users/confirm-email", "/api/v1/users/confirm-email?token=nope", Auth::Anonymous, None, StatusCode::BAD_REQUEST, ErrorCode::InvalidToken),
+        case(Method::GET, "/api/v1/users/metrics", "/api/v1/users/metrics", Auth::User, None, StatusCode::FORBIDDEN, ErrorCode::AdminRequired),
+        case(Method::POST, "/api/v1/users/import", "/api/v1/users/import", Auth::User, None, StatusCode::FORBIDDEN, ErrorCode::AdminRequired),
+        case(Method::GET, "/api/v1/users/{id}", format!("/api/v1/users/{}", MISSING_ID), Auth::Admin, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::PUT, "/api/v1/users/{id}", format!("/api/v1/users/{}", tester), Auth::User,
+            Some(json!({ "username": "admin", "email": TEST_EMAIL, "version": 1 })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::UsernameNotAllowed),
+        case(Method::PATCH, "/api/v1/users/{id}", format!("/api/v1/users/{}", tester), Auth::User,
+            Some(json!({ "username": "admin", "email": TEST_EMAIL, "version": 1 })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::UsernameNotAllowed),
+        case(Method::PUT, "/api/v1/users/update_password/{id}", format!("/api/v1/users/update_password/{}", tester), Auth::User,
+            Some(json!({ "password": TEST_PASSWORD })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::PasswordReused),
+        case(Method::DELETE, "/api/v1/users/{id}", "/api/v1/users/someone", Auth::Anonymous, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/users/password-reset", "/api/v1/users/password-reset", Auth::Anonymous,
+            Some(json!({})), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidField),
+        case(Method::POST, "/api/v1/users/password-reset/confirm", "/api/v1/users/password-reset/confirm", Auth::Anonymous,
+            Some(json!({ "token": "nope", "password": "a brand new password" })), StatusCode::BAD_REQUEST, ErrorCode::InvalidToken),
+        case(Method::POST, "/api/v1/users/me/email", "/api/v1/users/me/email", Auth::User,
+            Some(json!({ "email": TEST_EMAIL, "password": TEST_PASSWORD })), StatusCode::BAD_REQUEST, ErrorCode::EmailUnchanged),
+        case(Method::GET, "/api/v1/users/me/export", "/api/v1/users/me/export", Auth::User, None, StatusCode::TOO_MANY_REQUESTS, ErrorCode::TooManyRequests),
+        case(Method::GET, "/api/v1/users/me/sessions", "/api/v1/users/me/sessions", Auth::Anonymous, None, StatusCode::UNAUTHORIZED, ErrorCode::MissingToken),
+        case(Method::DELETE, "/api/v1/users/me/sessions/{id}", format!("/api/v1/users/me/sessions/{}", MISSING_ID), Auth::User, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::GET, "/api/v1/users/me/usage", "/api/v1/users/me/usage", Auth::Expired, None, StatusCode::UNAUTHORIZED, ErrorCode::TokenExpired),
+        case(Method::GET, "/api/v1/users/{id}/usage", format!("/api/v1/users/{}/usage", MISSING_ID), Auth::Admin, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::PUT, "/api/v1/users/me/settings", "/api/v1/users/me/settings", Auth::User,
+            Some(json!({ "notify_new_device": "yes" })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidField),
+        case(Method::GET, "/api/v1/users/me/preferences", "/api/v1/users/me/preferences", Auth::Expired, None, StatusCode::UNAUTHORIZED, ErrorCode::TokenExpired),
+        case(Method::PUT, "/api/v1/users/me/preferences", "/api/v1/users/me/preferences", Auth::User,
+            Some(json!({ "dark_mode": true })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidField),
+        case(Method::POST, "/api/v1/users/{id}/suspend", format!("/api/v1/users/{}/suspend", boss), Auth::Admin,
+            Some(json!({ "reason": "testing" })), StatusCode::BAD_REQUEST, ErrorCode::SelfModeration),
+        case(Method::POST, "/api/v1/users/{id}/unsuspend", format!("/api/v1/users/{}/unsuspend", MISSING_ID), Auth::Admin, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/users/{id}/force-logout", format!("/api/v1/users/{}/force-logout", tester), Auth::User, None, StatusCode::FORBIDDEN, ErrorCode::AdminRequired),
+
+        // Tokens
+        case(Method::POST, "/api/v1/users/revoke_token", "/api/v1/users/revoke_token", Auth::User,
+            Some(json!({ "token": "not.a.token" })), StatusCode::BAD_REQUEST, ErrorCode::InvalidToken),
+        case(Method::POST, "/api/v1/users/revoke_token", "/api/v1/users/revoke_token", Auth::Anonymous,
+            Some(json!({ "token": user_token })), StatusCode::UNAUTHORIZED, ErrorCode::MissingToken),
+        case(Method::POST, "/api/v1/users/get_new_tokens", "/api/v1/users/get_new_tokens", Auth::Anonymous,
+            Some(json!({ "token": user_token })), StatusCode::UNAUTHORIZED, ErrorCode::InvalidToken),
+        case(Method::POST, "/api/v1/users/check_access", "/api/v1/users/check_access", Auth::Anonymous,
+            Some(json!({ "token": "not.a.token" })), StatusCode::UNAUTHORIZED, ErrorCode::InvalidToken),
+        case(Method::POST, "/api/v1/users/check_access", "/api/v1/users/check_access", Auth::Anonymous,
+            Some(json!({ "token": expired_token })), StatusCode::UNAUTHORIZED, ErrorCode::TokenExpired),
+        case(Method::POST, "/api/v1/users/check_access", "/api/v1/users/check_access", Auth::Anonymous,
+            Some(json!({ "token": revoked_token })), StatusCode::UNAUTHORIZED, ErrorCode::TokenRevoked),
+        case(Method::POST, "/api/v1/auth/introspect", "/api/v1/auth/introspect", Auth::Anonymous,
+            Some(json!({ "token": user_token })), StatusCode::UNAUTHORIZED, ErrorCode::MissingToken),
+        case(Method::POST, "/api/v1/auth/introspect", "/api/v1/auth/introspect", Auth::User,
+            Some(json!({ "token": user_token })), StatusCode::UNAUTHORIZED, ErrorCode::InvalidToken),
+
+        // Solver
+        case(Method::POST, "/api/v1/game/candidates", "/api/v1/game/candidates", Auth::User,
+            Some(json!({ "correct": "ra", "incorrect": "st", "exact": "_r__t" })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::ConstraintContradiction),
+        case(Method::GET, "/api/v1/game/words", "/api/v1/game/words", Auth::Expired, None, StatusCode::UNAUTHORIZED, ErrorCode::TokenExpired),
+        case(Method::GET, "/api/v1/game/words/changes", "/api/v1/game/words/changes?since_version=0", Auth::User, None, StatusCode::GONE, ErrorCode::WordHistoryGone),
+        case(Method::GET, "/api/v1/game/words/{word}/stats", "/api/v1/game/words/zzzzz/stats", Auth::User, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/game/words/sync", "/api/v1/game/words/sync", Auth::Admin,
+            Some(json!({})), StatusCode::SERVICE_UNAVAILABLE, ErrorCode::WordSyncNotConfigured),
+        case(Method::POST, "/api/v1/game/words/suggestions", "/api/v1/game/words/suggestions", Auth::User,
+            Some(json!({ "word": "crane", "action": "add", "reason": "Common" })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::SuggestionNoChange),
+        case(Method::POST, "/api/v1/game/words/suggestions", "/api/v1/game/words/suggestions", Auth::User,
+            Some(json!({ "word": "cranes", "action": "add", "reason": "Common" })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidWord),
+        case(Method::POST, "/api/v1/game/diff", "/api/v1/game/diff", Auth::User,
+            Some(json!({ "base": { "correct": "", "incorrect": "", "exact": "_____" }, "first": { "guess": "crane", "feedback": "gg" }, "second": { "guess": "slate", "feedback": "xxxxx" } })),
+            StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidFeedback),
+        case(Method::POST, "/api/v1/game/feedback/batch", "/api/v1/game/feedback/batch", Auth::User,
+            Some(json!({ "pairs": vec![json!({ "guess": "crane", "answer": "crate" }); 501] })), StatusCode::PAYLOAD_TOO_LARGE, ErrorCode::BatchTooLarge),
+        case(Method::POST, "/api/v1/game/best-guess", "/api/v1/game/best-guess", Auth::User,
+            Some(json!({ "letters": { "correct": "é", "incorrect": "", "exact": "_____" } })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidLetter),
+        case(Method::POST, "/api/v1/game/sessions", "/api/v1/game/sessions", Auth::Expired, None, StatusCode::UNAUTHORIZED, ErrorCode::TokenExpired),
+        case(Method::GET, "/api/v1/game/sessions", format!("/api/v1/game/sessions?tags={}", "x".repeat(33)), Auth::User, None,
+            StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidSessionTags),
+        case(Method::GET, "/api/v1/game/history/export", "/api/v1/game/history/export?from=2023-08-02&to=2023-08-01", Auth::User, None,
+            StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidDateRange),
+        case(Method::GET, "/api/v1/game/sessions/stats", "/api/v1/game/sessions/stats", Auth::Anonymous, None, StatusCode::UNAUTHORIZED, ErrorCode::MissingToken),
+        case(Method::GET, "/api/v1/game/sessions/stats/{user_id}", format!("/api/v1/game/sessions/stats/{}", MISSING_ID), Auth::User, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::GET, "/api/v1/game/sessions/{id}", format!("/api/v1/game/sessions/{}", MISSING_ID), Auth::User, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::PATCH, "/api/v1/game/sessions/{id}", format!("/api/v1/game/sessions/{}", MISSING_ID), Auth::User,
+            Some(json!({ "note": "used new opener" })), StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/game/sessions/{id}/guesses", format!("/api/v1/game/sessions/{}/guesses", MISSING_ID), Auth::User,
+            Some(json!({ "guess": "crane", "feedback": "xxxxx" })), StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/game/sessions/{id}/claim", format!("/api/v1/game/sessions/{}/claim", MISSING_ID), Auth::User,
+            Some(json!({ "token": "nope" })), StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/game/sessions/{id}/report", format!("/api/v1/game/sessions/{}/report", MISSING_ID), Auth::User,
+            Some(json!({ "description": "wrong suggestion" })), StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/game/benchmark", "/api/v1/game/benchmark", Auth::User,
+            Some(json!({ "sample": 0 })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidSample),
+        case(Method::GET, "/api/v1/jobs/{id}", format!("/api/v1/jobs/{}", MISSING_ID), Auth::User, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+
+        // Leagues
+        case(Method::POST, "/api/v1/game/leagues", "/api/v1/game/leagues", Auth::User,
+            Some(json!({ "name": " " })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidLeagueName),
+        case(Method::GET, "/api/v1/game/leagues", "/api/v1/game/leagues", Auth::Anonymous, None, StatusCode::UNAUTHORIZED, ErrorCode::MissingToken),
+        case(Method::POST, "/api/v1/game/leagues/join", "/api/v1/game/leagues/join", Auth::User,
+            Some(json!({ "invite_code": "nope" })), StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::GET, "/api/v1/game/leagues/{id}", format!("/api/v1/game/leagues/{}", MISSING_ID), Auth::User, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/game/leagues/{id}/leave", format!("/api/v1/game/leagues/{}/leave", MISSING_ID), Auth::Expired, None, StatusCode::UNAUTHORIZED, ErrorCode::TokenExpired),
+        case(Method::DELETE, "/api/v1/game/leagues/{id}/members/{user_id}", format!("/api/v1/game/leagues/{}/members/{}", MISSING_ID, tester), Auth::User, None,
+            StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::GET, "/api/v1/game/leagues/{id}/daily", format!("/api/v1/game/leagues/{}/daily", MISSING_ID), Auth::User, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/game/leagues/{id}/daily/guesses", format!("/api/v1/game/leagues/{}/daily/guesses", MISSING_ID), Auth::User,
+            Some(json!({ "guess": "crane" })), StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::GET, "/api/v1/game/leagues/{id}/leaderboard", format!("/api/v1/game/leagues/{}/leaderboard", MISSING_ID), Auth::User, None,
+            StatusCode::NOT_FOUND, ErrorCode::NotFound),
+
+        // Webhooks
+        case(Method::POST, "/api/v1/webhooks", "/api/v1/webhooks", Auth::User,
+            Some(json!({ "url": "http://127.0.0.1/hook", "secret": "a long enough secret", "events": ["daily_completed"] })), StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidWebhookUrl),
+        case(Method::GET, "/api/v1/webhooks", "/api/v1/webhooks", Auth::Anonymous, None, StatusCode::UNAUTHORIZED, ErrorCode::MissingToken),
+        case(Method::PUT, "/api/v1/webhooks/{id}", format!("/api/v1/webhooks/{}", MISSING_ID), Auth::User,
+            Some(json!({ "enabled": false })), StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::DELETE, "/api/v1/webhooks/{id}", format!("/api/v1/webhooks/{}", MISSING_ID), Auth::User, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::GET, "/api/v1/webhooks/{id}/deliveries", format!("/api/v1/webhooks/{}/deliveries", MISSING_ID), Auth::User, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/webhooks/{id}/ping", format!("/api/v1/webhooks/{}/ping", MISSING_ID), Auth::User, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+
+        // Admin
+        case(Method::GET, "/api/v1/admin/summary", "/api/v1/admin/summary", Auth::User, None, StatusCode::FORBIDDEN, ErrorCode::AdminRequired),
+        case(Method::GET, "/api/v1/admin/flags", "/api/v1/admin/flags", Auth::Anonymous, None, StatusCode::UNAUTHORIZED, ErrorCode::MissingToken),
+        case(Method::PUT, "/api/v1/admin/flags/{name}", "/api/v1/admin/flags/nope", Auth::Admin,
+            Some(json!({ "enabled": true })), StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::GET, "/api/v1/admin/word-suggestions", "/api/v1/admin/word-suggestions?status=stale", Auth::Admin, None, StatusCode::BAD_REQUEST, ErrorCode::InvalidQuery),
+        case(Method::POST, "/api/v1/admin/word-suggestions/{id}/approve", format!("/api/v1/admin/word-suggestions/{}/approve", MISSING_ID), Auth::Admin,
+            Some(json!({})), StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/admin/word-suggestions/{id}/reject", format!("/api/v1/admin/word-suggestions/{}/reject", MISSING_ID), Auth::User,
+            Some(json!({})), StatusCode::FORBIDDEN, ErrorCode::AdminRequired),
+        case(Method::GET, "/api/v1/admin/hardest-words", "/api/v1/admin/hardest-words?min_plays=many", Auth::Admin, None, StatusCode::BAD_REQUEST, ErrorCode::InvalidQuery),
+        case(Method::POST, "/api/v1/admin/word-list/check", "/api/v1/admin/word-list/check", Auth::User, None, StatusCode::FORBIDDEN, ErrorCode::AdminRequired),
+        case(Method::POST, "/api/v1/admin/recompute-stats", "/api/v1/admin/recompute-stats", Auth::Anonymous, None, StatusCode::UNAUTHORIZED, ErrorCode::MissingToken),
+        case(Method::GET, "/api/v1/admin/emails", "/api/v1/admin/emails?status=lost", Auth::Admin, None, StatusCode::BAD_REQUEST, ErrorCode::InvalidQuery),
+        case(Method::GET, "/api/v1/admin/reports/{id}/replay", format!("/api/v1/admin/reports/{}/replay", MISSING_ID), Auth::Admin, None, StatusCode::NOT_FOUND, ErrorCode::NotFound),
+        case(Method::POST, "/api/v1/admin/impersonate/{user_id}", format!("/api/v1/admin/impersonate/{}", tester), Auth::User, None, StatusCode::FORBIDDEN, ErrorCode::AdminRequired),
+        case(Method::GET, "/api/v1/admin/word-filter-plan", "/api/v1/admin/word-filter-plan?exact=cr%C3%A1__", Auth::Admin, None, StatusCode::UNPROCESSABLE_ENTITY, ErrorCode::InvalidLetter),
+    ];
+
+    let mut documented = BTreeSet::new();
+    for (route, item) in ApiDoc::openapi().paths.paths {
+        if WITHOUT_ERROR_CODES.contains(&route.as_str()) {
+            continue;
+        }
+        for method in item.operations.keys() {
+            let method = serde_json::to_value(method).unwrap().as_str().unwrap().to_uppercase();
+            documented.insert(format!("{} {}", method, route));
+        }
+    }
+    let covered: BTreeSet<String> = cases.iter().map(|case| format!("{} {}", case.method, case.route)).collect();
+    let missing: Vec<&String> = documented.difference(&covered).collect();
+    assert!(missing.is_empty(), "endpoints without an error code case: {:?}", missing);
+    let stale: Vec<&String> = covered.difference(&documented).collect();
+    assert!(stale.is_empty(), "cases for undocumented endpoints: {:?}", stale);
+
+    for case in cases {
+        let token = match case.auth {
+            Auth::Anonymous => None,
+            Auth::User => Some(&user_token),
+            Auth::Admin => Some(&admin_token),
+            Auth::Expired => Some(&expired_token),
+        };
+        let mut request = TestRequest::default().method(case.method.clone()).uri(&case.uri);
+        if let Some(token) = token {
+            request = request.insert_header(("Authorization", format!("Bearer {}", token)));
+        }
+        if let Some(body) = &case.body {
+            request = request.set_json(body);
+        }
+
+        let response = test::call_service(&app, request.to_request()).await;
+        let status = response.status();
+        let body: Value = serde_json::from_slice(&test::read_body(response).await).unwrap_or(Value::Null);
+        assert_eq!((status, body["error"]["code"].as_str()), (case.status, Some(case.code.as_str())), "{} {}: {}", case.method, case.uri, body);
+    }
+}
+
+#[actix_web::test]
+async fn error_code_names_are_unique_snake_case() {
+    let mut names = BTreeSet::new();
+    for code in ErrorCode::ALL {
+        let name = code.as_str();
+        assert!(names.insert(name), "{} is used twice", name);
+        assert!(name.chars().all(|letter| letter.is_ascii_lowercase() || letter == '_'), "{}", name);
+        assert_eq!(serde_json::to_value(code).unwrap(), name);
+    }
+}
@@ -0,0 +1,40 @@
+use wordle_solver::solver::{explain, outlook};
+
+#[test]
+fn outlook_counts_what_a_guess_leaves() {
+    let candidates = ["crane", "crate", "trace"];
+
+    let crate_ = outlook("crate", &candidates);
+    assert_eq!((crate_.candidates, crate_.worst_case), (3, 1));
+    assert_eq!(crate_.expected, 1.0);
+    assert_eq!(crate_.probes, ['t']);
+    assert!(crate_.could_be_answer);
+
+    // Every candidate gets all gray, so they stay together
+    let pious = outlook("pious", &candidates);
+    assert_eq!((pious.worst_case, pious.expected), (3, 3.0));
+    assert!(pious.probes.is_empty());
+    assert!(!pious.could_be_answer);
+}
+
+#[test]
+fn explanations_follow_the_template() {
+    let candidates = ["crane", "crate", "trace"];
+    assert_eq!(
+        explain("crate", &outlook("crate", &candidates)),
+        "Of the 3 candidates left, CRATE leaves at most 1 and about 1.0 on average. It checks for T. It could be the answer itself.",
+    );
+    assert_eq!(
+        explain("adieu", &outlook("adieu", &["crane", "pious", "slate"])),
+        "Of the 3 candidates left, ADIEU leaves at most 2 and about 1.7 on average. It checks for A, E, I and U. It can't be the answer itself.",
+    );
+    assert_eq!(
+        explain("pious", &outlook("pious", &candidates)),
+        "Of the 3 candidates left, PIOUS leaves at most 3 and about 3.0 on average. It can't tell any of them apart. It can't be the answer itself.",
+    );
+    assert_eq!(
+        explain("trace", &outlook("trace", &["crate", "react"])),
+        "Of the 2 candidates left, TRACE leaves at most 1 and about 1.0 on average. Its letters are in all of them or none, it splits them by position only. It can't be the answer itself.",
+    );
+    assert_eq!(explain("crane", &outlook("crane", &["crane"])), "CRANE is the only candidate left.");
+}
@@ -0,0 +1,632 @@
+mod common;
+
+use actix_web::http::header::{CACHE_CONTROL, ETAG, IF_NONE_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use async_trait::async_trait;
+use common::{access_token, init_app, init_app_with, init_app_with_state, post_json, settings, settings_with, state, TestDb, TEST_WORDS};
+use serde_json::{json, Value};
+use futures_util::future::join_all;
+use futures_util::join;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use wordle_solver::handlers::examples;
+use wordle_solver::repositories::words::{self, pick_sample, DbWords, WordRepository};
+use wordle_solver::solver::{explain, feedback, outlook, pattern_code, suggest, suggest_exact, Constraints};
+
+fn sorted(page: &Value) -> Vec<&str> {
+    let mut words: Vec<&str> = page["items"]
+        .as_array()
+        .expect("expected a page of words")
+        .iter()
+        .map(|word| word.as_str().unwrap())
+        .collect();
+    words.sort_unstable();
+    words
+}
+
+#[actix_web::test]
+async fn find_letters_requires_login() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "_____" });
+    let (status, body) = post_json(&app, "/api/v1/game/candidates", &letters, None).await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "missing_token");
+}
+
+#[actix_web::test]
+async fn find_letters_filters_the_word_list() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    // The documented example: contains r and a, no s, and ends in e
+    let letters = examples::request_letters();
+    let (status, words) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", words);
+    assert_eq!(sorted(&words), vec!["crane", "crate", "trace"]);
+}
+
+#[actix_web::test]
+async fn find_letters_with_fixed_positions() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let letters = json!({ "correct": "", "incorrect": "z", "exact": "cra__" });
+    let (status, words) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", words);
+    assert_eq!(sorted(&words), vec!["crane", "crate"]);
+}
+
+#[actix_web::test]
+async fn pasted_letters_are_cleaned_up_or_rejected() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let letters = json!({ "correct": " R ", "incorrect": "Z\n", "exact": "\tCRA__ " });
+    let (status, words) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", words);
+    assert_eq!(sorted(&words), vec!["crane", "crate"]);
+
+    // A zero width space copied along with the guess
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "cr\u{200b}ne" });
+    let (status, body) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "invalid_letter");
+    assert_eq!(body["error"]["details"], json!({ "field": "exact", "character": "\u{200b}", "codepoint": "U+200B", "position": 3 }));
+    assert!(body["error"]["message"].as_str().unwrap().contains("U+200B"), "{}", body);
+
+    // Cyrillic а in an otherwise Latin word
+    let letters = json!({ "correct": "cr\u{0430}ne", "incorrect": "", "exact": "_____" });
+    let (status, body) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["details"]["field"], "correct");
+    assert_eq!(body["error"]["details"]["codepoint"], "U+0430");
+    assert_eq!(body["error"]["message"], "correct has 'а' (U+0430) at position 3, only the letters a to z are allowed");
+}
+
+#[actix_web::test]
+async fn messy_letters_match_like_clean_ones() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let clean = json!({ "correct": "r", "incorrect": "s", "exact": "_r_ne" });
+    let (status, expected) = post_json(&app, "/api/v1/game/candidates", &clean, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", expected);
+    assert_eq!(expected["items"], json!(["crane"]));
+
+    // Mobile keyboards, uppercase and the UI's other ways of drawing an empty slot
+    for exact in ["_R_NE ", " _r_ne", ".R.NE", "-r-ne", "_r ne", "\t_R_nE\n"] {
+        let messy = json!({ "correct": " R", "incorrect": "S ", "exact": exact });
+        let (status, words) = post_json(&app, "/api/v1/game/candidates", &messy, Some(&token)).await;
+        assert_eq!(status, StatusCode::OK, "{:?}: {}", exact, words);
+        assert_eq!(words, expected, "{:?}", exact);
+    }
+}
+
+#[actix_web::test]
+async fn detailed_responses_show_the_letters_used() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let messy = json!({ "correct": "RA ", "incorrect": " S", "exact": "..A-E " });
+    let (status, words) = post_json(&app, "/api/v1/game/candidates?detailed=true", &messy, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", words);
+    assert_eq!(words["normalized"], json!({ "correct": "ra", "incorrect": "s", "exact": "__a_e" }));
+    assert_eq!(sorted(&words), vec!["crane", "crate", "trace"]);
+
+    let (_, words) = post_json(&app, "/api/v1/game/candidates?page=1", &messy, Some(&token)).await;
+    assert!(words.get("normalized").is_none(), "{}", words);
+}
+
+#[actix_web::test]
+async fn samples_are_reproducible_with_their_seed() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let letters = json!({ "correct": "a", "incorrect": "", "exact": "_____" });
+
+    // The first is sampled by the repository, the last from the list the middle one cached
+    let (status, first) = post_json(&app, "/api/v1/game/candidates?sample=true&seed=7&per_page=3", &letters, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", first);
+    let (_, all) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+    let (_, again) = post_json(&app, "/api/v1/game/candidates?sample=true&seed=7&per_page=3", &letters, Some(&token)).await;
+
+    assert_eq!(first["items"], again["items"]);
+    assert_eq!(first["items"].as_array().unwrap().len(), 3);
+    assert_eq!((first["total"].as_i64(), first["seed"].as_i64(), first["page"].as_i64()), (Some(6), Some(7), Some(1)));
+    assert!(sorted(&first).iter().all(|word| sorted(&all).contains(word)), "{} {}", first, all);
+    assert!(all.get("seed").is_none());
+
+    // Without a seed one is picked, and it repeats the sample
+    let (_, random) = post_json(&app, "/api/v1/game/candidates?sample=true&per_page=2", &letters, Some(&token)).await;
+    let seed = random["seed"].as_u64().unwrap();
+    let (_, repeated) = post_json(&app, &format!("/api/v1/game/candidates?sample=true&per_page=2&seed={}", seed), &letters, Some(&token)).await;
+    assert_eq!(random["items"], repeated["items"]);
+}
+
+// Postgres samples in SQL, which has to pick what sampling the filtered list would
+#[actix_web::test]
+async fn database_samples_match_samples_of_the_list() {
+    let db = TestDb::new().await;
+    let repository = DbWords::new(db.pool.clone());
+    let mut constraints = Constraints::default();
+    constraints.require('a');
+    constraints.exclude('s');
+
+    for (pattern, size) in [("%", 2), ("_r___", 2), ("%", 10)] {
+        for seed in 0..20 {
+            let (sampled, total) = repository.sample_words(pattern, &constraints, size, seed).await.unwrap();
+            let filtered = words::filter_words(&db.pool, pattern, &constraints).await.unwrap();
+            assert_eq!(total, filtered.len());
+
+            let mut expected = pick_sample(filtered, size, seed);
+            let mut sampled = sampled;
+            expected.sort_unstable();
+            sampled.sort_unstable();
+            assert_eq!(sampled, expected, "{} {} {}", pattern, size, seed);
+        }
+    }
+}
+
+#[actix_web::test]
+async fn every_word_is_as_likely_to_be_sampled() {
+    let mut picks = vec![0; TEST_WORDS.len()];
+    for seed in 0..7_000 {
+        let sample = pick_sample(TEST_WORDS.iter().map(|word| word.to_string()), 1, seed);
+        picks[TEST_WORDS.iter().position(|word| *word == sample[0]).unwrap()] += 1;
+    }
+    assert!(picks.iter().all(|count| (850..1150).contains(count)), "{:?}", picks);
+}
+
+// Stands in for the word list so handler tests don't depend on what is seeded
+struct FixedWords(Option<Vec<&'static str>>);
+
+#[async_trait]
+impl WordRepository for FixedWords {
+    async fn filter_words(&self, _pattern: &str, constraints: &Constraints) -> Result<Vec<String>, sqlx::Error> {
+        let words = self.0.as_ref().ok_or(sqlx::Error::PoolTimedOut)?;
+        Ok(words.iter().filter(|word| constraints.allows(word)).map(|word| word.to_string()).collect())
+    }
+
+    async fn version(&self) -> Result<i64, sqlx::Error> {
+        Ok(1)
+    }
+
+    async fn all_words(&self) -> Result<Vec<String>, sqlx::Error> {
+        let words = self.0.as_ref().ok_or(sqlx::Error::PoolTimedOut)?;
+        Ok(words.iter().map(|word| word.to_string()).collect())
+    }
+}
+
+#[actix_web::test]
+async fn find_letters_answers_from_the_word_repository() {
+    let db = TestDb::new().await;
+    let mut state = state(&db, settings());
+    state.words = Arc::new(FixedWords(Some(vec!["zesty", "zebra", "crane"])));
+    let app = init_app_with_state(state).await;
+    let token = access_token(&app).await;
+
+    let letters = json!({ "correct": "z", "incorrect": "r", "exact": "_____" });
+    let (status, words) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", words);
+    assert_eq!(sorted(&words), vec!["zesty"]);
+}
+
+// Takes its time over every query, like a heavy solver request
+struct SlowWords;
+
+#[async_trait]
+impl WordRepository for SlowWords {
+    async fn filter_words(&self, _pattern: &str, _constraints: &Constraints) -> Result<Vec<String>, sqlx::Error> {
+        actix_web::rt::time::sleep(Duration::from_millis(300)).await;
+        Ok(vec!["crane".to_string()])
+    }
+
+    async fn version(&self) -> Result<i64, sqlx::Error> {
+        Ok(1)
+    }
+
+    async fn all_words(&self) -> Result<Vec<String>, sqlx::Error> {
+        Ok(vec!["crane".to_string()])
+    }
+}
+
+#[actix_web::test]
+async fn solver_queries_past_the_concurrency_limit_are_turned_away() {
+    let db = TestDb::new().await;
+    let settings = settings_with(&[("CONCURRENCY_LIMIT_SOLVER", "2"), ("CONCURRENCY_QUEUE_TIMEOUT_MS", "50")]).unwrap();
+    let mut state = state(&db, settings);
+    state.words = Arc::new(SlowWords);
+    let metrics = state.metrics.clone();
+    let app = init_app_with_state(state).await;
+    let token = access_token(&app).await;
+
+    // Different patterns, so none of them is answered from the result cache
+    let query = |exact: &str| {
+        test::TestRequest::post()
+            .uri("/api/v1/game/candidates")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "correct": "", "incorrect": "", "exact": exact }))
+            .to_request()
+    };
+    let words = test::TestRequest::get()
+        .uri("/api/v1/game/words")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let (first, second, third, words) = join!(
+        test::call_service(&app, query("c____")),
+        test::call_service(&app, query("_r___")),
+        test::call_service(&app, query("__a__")),
+        test::call_service(&app, words),
+    );
+    // Whichever reached the limit last is turned away
+    let (served, mut turned_away): (Vec<_>, Vec<_>) = [first, second, third].into_iter().partition(|response| response.status() == StatusCode::OK);
+    assert_eq!(served.len(), 2);
+    assert_eq!(turned_away.len(), 1);
+    let rejected = turned_away.remove(0);
+    assert_eq!(rejected.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(rejected.headers().get("Retry-After").unwrap(), "1");
+    let body: Value = test::read_body_json(rejected).await;
+    assert_eq!(body["error"]["code"], "overloaded");
+    // Routes outside the group don't wait for a slot
+    assert_eq!(words.status(), StatusCode::OK);
+
+    // The slots are given back
+    assert_eq!(metrics.in_flight("solver").get(), 0);
+    let response = test::call_service(&app, query("___n_")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+// Counts the filter queries and takes its time over each one. The first can be made to
+// panic or to stall past the solver timeout.
+struct CountingWords {
+    calls: AtomicUsize,
+    first_call: Option<&'static str>,
+}
+
+#[async_trait]
+impl WordRepository for CountingWords {
+    async fn filter_words(&self, _pattern: &str, constraints: &Constraints) -> Result<Vec<String>, sqlx::Error> {
+        let first = self.calls.fetch_add(1, Ordering::SeqCst) == 0;
+        actix_web::rt::time::sleep(Duration::from_millis(200)).await;
+        match self.first_call {
+            Some("panic") if first => panic!("the first filter query fails"),
+            Some("stall") if first => actix_web::rt::time::sleep(Duration::from_secs(5)).await,
+            _ => {}
+        }
+        Ok(TEST_WORDS.iter().filter(|word| constraints.allows(word)).map(|word| word.to_string()).collect())
+    }
+
+    async fn version(&self) -> Result<i64, sqlx::Error> {
+        Ok(1)
+    }
+
+    async fn all_words(&self) -> Result<Vec<String>, sqlx::Error> {
+        Ok(TEST_WORDS.iter().map(|word| word.to_string()).collect())
+    }
+}
+
+async fn counting_app(db: &TestDb, first_call: Option<&'static str>) -> (impl actix_web::dev::Service<actix_http::Request, Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>, Error = actix_web::Error>, Arc<CountingWords>) {
+    let words = Arc::new(CountingWords { calls: AtomicUsize::new(0), first_call });
+    let mut state = state(db, settings_with(&[("SOLVER_TIMEOUT_MS", "1000"), ("CONCURRENCY_LIMIT_SOLVER", "1")]).unwrap());
+    state.words = words.clone();
+    (init_app_with_state(state).await, words)
+}
+
+#[actix_web::test]
+async fn identical_best_guess_requests_share_one_computation() {
+    let db = TestDb::new().await;
+    let (app, words) = counting_app(&db, None).await;
+    let token = access_token(&app).await;
+    // The same letters written differently
+    let bodies = [
+        json!({ "letters": { "correct": "ar", "incorrect": "", "exact": "_____" } }),
+        json!({ "letters": { "correct": "raa", "incorrect": "", "exact": "_____" } }),
+    ];
+
+    // One solver slot, yet none of them is turned away for want of one
+    let responses = join_all((0..12).map(|n| post_json(&app, "/api/v1/game/best-guess", &bodies[n % 2], Some(&token)))).await;
+    for (status, best) in &responses {
+        assert_eq!(*status, StatusCode::OK, "{}", best);
+        assert_eq!(best, &responses[0].1);
+    }
+    assert_eq!(responses[0].1["candidates"], 4);
+    assert_eq!(words.calls.load(Ordering::SeqCst), 1);
+
+    // Finished computations aren't kept
+    let (status, _) = post_json(&app, "/api/v1/game/best-guess", &bodies[0], Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(words.calls.load(Ordering::SeqCst), 2);
+}
+
+#[actix_web::test]
+async fn a_failed_shared_computation_fails_every_waiter_once() {
+    let db = TestDb::new().await;
+    for (first_call, failure) in [("panic", (StatusCode::INTERNAL_SERVER_ERROR, "internal_error")), ("stall", (StatusCode::GATEWAY_TIMEOUT, "query_timeout"))] {
+        let (app, words) = counting_app(&db, Some(first_call)).await;
+        let token = access_token(&app).await;
+        let body = json!({ "letters": { "correct": "c", "incorrect": "", "exact": "_____" } });
+
+        let responses = join_all((0..5).map(|_| post_json(&app, "/api/v1/game/best-guess", &body, Some(&token)))).await;
+        for (status, response) in responses {
+            assert_eq!((status, response["error"]["code"].as_str()), (failure.0, Some(failure.1)), "{}", first_call);
+        }
+        assert_eq!(words.calls.load(Ordering::SeqCst), 1, "{}", first_call);
+
+        // The next request starts afresh rather than finding the failure
+        let (status, best) = post_json(&app, "/api/v1/game/best-guess", &body, Some(&token)).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", first_call, best);
+    }
+}
+
+#[actix_web::test]
+async fn word_repository_failures_are_internal_errors() {
+    let db = TestDb::new().await;
+    let mut state = state(&db, settings());
+    state.words = Arc::new(FixedWords(None));
+    let app = init_app_with_state(state).await;
+    let token = access_token(&app).await;
+
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "_____" });
+    let (status, body) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body["error"]["code"], "internal_error");
+}
+
+#[actix_web::test]
+async fn oversized_body_is_rejected() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let letters = json!({ "correct": "a".repeat(4096), "incorrect": "", "exact": "_____" });
+    let (status, body) = post_json(&app, "/api/v1/game/candidates", &letters, None).await;
+
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(body["error"]["code"], "payload_too_large");
+    assert_eq!(body["error"]["details"]["limit"], 1024);
+}
+
+#[actix_web::test]
+async fn wrong_typed_field_is_rejected() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let letters = json!({ "correct": 5, "incorrect": "", "exact": "_____" });
+    let (status, body) = post_json(&app, "/api/v1/game/candidates", &letters, None).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "invalid_field");
+    assert!(body["error"]["message"].as_str().unwrap().contains("expected a string"), "{}", body);
+}
+
+#[actix_web::test]
+async fn missing_field_is_named() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let letters = json!({ "correct": "", "incorrect": "" });
+    let (status, body) = post_json(&app, "/api/v1/game/candidates", &letters, None).await;
+
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "invalid_field");
+    assert_eq!(body["error"]["details"]["field"], "exact");
+}
+
+#[actix_web::test]
+async fn word_list_is_revalidated_with_its_etag() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let fetch = |etag: Option<String>| {
+        let mut request = test::TestRequest::get()
+            .uri("/api/v1/game/words")
+            .insert_header(("Authorization", format!("Bearer {}", token)));
+        if let Some(etag) = etag {
+            request = request.insert_header((IF_NONE_MATCH, etag));
+        }
+        request.to_request()
+    };
+
+    let response = test::call_service(&app, fetch(None)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers().get(ETAG).expect("no ETag").to_str().unwrap().to_string();
+    assert!(etag.starts_with('"'), "expected a strong tag, got {}", etag);
+    assert_eq!(response.headers().get(CACHE_CONTROL).unwrap(), "private, max-age=300");
+    let words: Vec<String> = test::read_body_json(response).await;
+    assert_eq!(words.len(), TEST_WORDS.len());
+
+    let response = test::call_service(&app, fetch(Some(etag.clone()))).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers().get(ETAG).unwrap(), etag.as_str());
+    assert!(test::read_body(response).await.is_empty());
+
+    sqlx::query("INSERT INTO word_list (word) VALUES ('lemon')").execute(&db.pool).await.unwrap();
+
+    let response = test::call_service(&app, fetch(Some(etag.clone()))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers().get(ETAG).unwrap(), etag.as_str());
+    let words: Vec<String> = test::read_body_json(response).await;
+    assert!(words.iter().any(|word| word == "lemon"));
+}
+
+#[actix_web::test]
+async fn diff_shows_what_each_feedback_rules_out() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let body = json!({
+        "base": { "correct": "", "incorrect": "", "exact": "_____" },
+        "first": { "guess": "slate", "feedback": "xxggg" },
+        "second": { "guess": " ADIEU ", "feedback": "YXXYX" },
+    });
+    let (status, diff) = post_json(&app, "/api/v1/game/diff", &body, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", diff);
+    assert_eq!(diff["candidates"], TEST_WORDS.len());
+    assert_eq!(diff["first"]["remaining"], 1);
+    assert_eq!(diff["second"]["remaining"], 5);
+    assert_eq!(diff["second"]["guess"], "adieu");
+    assert_eq!(sorted(&diff["first"]["eliminated"]), ["crane", "react", "slate", "trace"]);
+    assert_eq!(diff["second"]["eliminated"]["total"], 0);
+
+    let (_, diff) = post_json(&app, "/api/v1/game/diff?per_page=2&page=2", &body, Some(&token)).await;
+    assert_eq!(diff["first"]["eliminated"]["items"].as_array().unwrap().len(), 2);
+    assert_eq!(diff["first"]["eliminated"]["total"], 4);
+}
+
+#[actix_web::test]
+async fn diff_says_which_branch_is_inconsistent() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let diff = |base: Value, first: Value, second: Value| json!({ "base": base, "first": first, "second": second });
+    let open = json!({ "correct": "", "incorrect": "s", "exact": "c____" });
+    let fine = json!({ "guess": "crate", "feedback": "gxxxx" });
+    for (body, code, branch) in [
+        (diff(json!({ "correct": "s", "incorrect": "s", "exact": "_____" }), fine.clone(), fine.clone()), "constraint_contradiction", "base"),
+        (diff(open.clone(), json!({ "guess": "crate", "feedback": "gxxx" }), fine.clone()), "invalid_feedback", "first"),
+        (diff(open.clone(), json!({ "guess": "crate", "feedback": "gqxxx" }), fine.clone()), "invalid_feedback", "first"),
+        (diff(open.clone(), json!({ "guess": "crates", "feedback": "gxxxxx" }), fine.clone()), "invalid_word", "first"),
+        // s is excluded by the base letters, and c is fixed where this puts t
+        (diff(open.clone(), fine.clone(), json!({ "guess": "slate", "feedback": "yxxxx" })), "constraint_contradiction", "second"),
+        (diff(open.clone(), fine.clone(), json!({ "guess": "trace", "feedback": "ggggg" })), "constraint_contradiction", "second"),
+    ] {
+        let (status, response) = post_json(&app, "/api/v1/game/diff", &body, Some(&token)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+        assert_eq!(response["error"]["code"], code, "{}", body);
+        assert_eq!(response["error"]["details"]["branch"], branch, "{}", body);
+    }
+}
+
+#[actix_web::test]
+async fn best_guess_explains_its_suggestions_on_request() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let letters = json!({ "correct": "a", "incorrect": "", "exact": "_____" });
+
+    let (status, best) = post_json(&app, "/api/v1/game/best-guess", &json!({ "letters": letters, "count": 3 }), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", best);
+    assert_eq!(best["candidates"], 6);
+    assert_eq!(best["suggestions"].as_array().unwrap().len(), 3);
+    assert!(best["suggestions"][0].get("explanation").is_none());
+
+    // The explanation is the solver's own template, the one the CLI prints too
+    let (_, explained) = post_json(&app, "/api/v1/game/best-guess", &json!({ "letters": letters, "count": 3, "explain": true }), Some(&token)).await;
+    let candidates: Vec<&str> = TEST_WORDS.iter().copied().filter(|word| word.contains('a')).collect();
+    for suggestion in explained["suggestions"].as_array().unwrap() {
+        let word = suggestion["word"].as_str().unwrap();
+        assert_eq!(suggestion["explanation"], explain(word, &outlook(word, &candidates)));
+    }
+    assert_eq!(explained["suggestions"][0]["word"], best["suggestions"][0]["word"]);
+
+    let (status, body) = post_json(&app, "/api/v1/game/best-guess", &json!({ "letters": letters, "count": 0 }), Some(&token)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("invalid_field")));
+}
+
+#[actix_web::test]
+async fn best_guess_ranks_in_the_mode_asked_for() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let body = |mode: &str| json!({ "letters": { "correct": "a", "incorrect": "", "exact": "_____" }, "count": 3, "mode": mode });
+    let ranked = |best: &Value| -> Vec<(String, u64)> {
+        best["suggestions"].as_array().unwrap().iter().map(|suggestion| (suggestion["word"].as_str().unwrap().to_string(), suggestion["score"].as_u64().unwrap())).collect()
+    };
+    // Guesses are ranked in the order the solver cache keeps them, alphabetically
+    let mut guesses: Vec<&str> = TEST_WORDS.to_vec();
+    guesses.sort_unstable();
+    let candidates: Vec<&str> = guesses.iter().copied().filter(|word| word.contains('a')).collect();
+
+    let (status, fast) = post_json(&app, "/api/v1/game/best-guess", &body("fast"), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", fast);
+    assert_eq!((&fast["mode"], &fast["candidates"]), (&json!("fast"), &json!(6)));
+    let expected: Vec<(String, u64)> = suggest(&candidates, &guesses, 3).into_iter().map(|suggestion| (suggestion.word.to_string(), suggestion.score as u64)).collect();
+    assert_eq!(ranked(&fast), expected);
+
+    let (status, exact) = post_json(&app, "/api/v1/game/best-guess", &body("exact"), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", exact);
+    assert_eq!((&exact["mode"], &exact["candidates"]), (&json!("exact"), &json!(6)));
+    let expected: Vec<(String, u64)> = suggest_exact(&candidates, &guesses, 3, |guess, candidate| pattern_code(&feedback(guess, candidate)))
+        .into_iter()
+        .map(|suggestion| (suggestion.word.to_string(), suggestion.score as u64))
+        .collect();
+    assert_eq!(ranked(&exact), expected);
+
+    // Left out it's fast, as before there were modes
+    let (_, default) = post_json(&app, "/api/v1/game/best-guess", &json!({ "letters": { "correct": "a", "incorrect": "", "exact": "_____" }, "count": 3 }), Some(&token)).await;
+    assert_eq!(default, fast);
+
+    let (status, body) = post_json(&app, "/api/v1/game/best-guess", &body("thorough"), Some(&token)).await;
+    assert!(status.is_client_error(), "{}", body);
+}
+
+#[actix_web::test]
+async fn auto_mode_switches_to_fast_at_the_threshold() {
+    let db = TestDb::new().await;
+    // Six words have an a
+    let body = |mode: &str| json!({ "letters": { "correct": "a", "incorrect": "", "exact": "_____" }, "mode": mode });
+    let mode_used = |threshold: &str, ceiling: &str, mode: &'static str| {
+        let settings = settings_with(&[("BEST_GUESS_EXACT_THRESHOLD", threshold), ("BEST_GUESS_EXACT_CEILING", ceiling)]).unwrap();
+        let (db, request) = (&db, body(mode));
+        async move {
+            let app = init_app_with(db, settings).await;
+            let token = access_token(&app).await;
+            let (status, best) = post_json(&app, "/api/v1/game/best-guess", &request, Some(&token)).await;
+            assert_eq!((status, &best["candidates"]), (StatusCode::OK, &json!(6)), "{}", best);
+            best["mode"].as_str().unwrap().to_string()
+        }
+    };
+
+    assert_eq!(mode_used("7", "100", "auto").await, "exact");
+    assert_eq!(mode_used("6", "100", "auto").await, "fast");
+    // The ceiling holds even when exact is asked for
+    assert_eq!(mode_used("0", "6", "exact").await, "exact");
+    assert_eq!(mode_used("0", "5", "exact").await, "fast");
+    assert_eq!(mode_used("5", "5", "fast").await, "fast");
+
+    assert!(settings_with(&[("BEST_GUESS_EXACT_THRESHOLD", "10"), ("BEST_GUESS_EXACT_CEILING", "5")]).is_err());
+}
+
+#[actix_web::test]
+async fn feedback_batches_mark_each_pair_on_its_own() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[("FEEDBACK_BATCH_MAX", "4")]).unwrap()).await;
+    let token = access_token(&app).await;
+
+    let pairs = json!([
+        { "guess": "SPEED", "answer": "abide" },
+        { "guess": "crane", "answer": "cranes" },
+        { "guess": "cr4ne", "answer": "crate" },
+        { "guess": " trace ", "answer": "crate" },
+    ]);
+    let (status, batch) = post_json(&app, "/api/v1/game/feedback/batch", &json!({ "pairs": pairs }), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", batch);
+    let results = batch["results"].as_array().unwrap();
+    let feedback: Vec<&Value> = results.iter().map(|result| &result["feedback"]).collect();
+    assert_eq!(feedback, [&json!("xxyxy"), &Value::Null, &Value::Null, &json!("yggyg")]);
+    assert_eq!((&results[1]["error"]["code"], &results[1]["error"]["details"]["field"]), (&json!("invalid_word"), &json!("answer")));
+    assert_eq!((&results[2]["error"]["code"], &results[2]["error"]["details"]["position"]), (&json!("invalid_letter"), &json!(3)));
+    assert_eq!(results[3]["squares"][0], json!({ "letter": "t", "mark": "present" }));
+    assert!(results[0]["error"].is_null());
+
+    let too_many = json!({ "pairs": vec![json!({ "guess": "crane", "answer": "crate" }); 5] });
+    let (status, body) = post_json(&app, "/api/v1/game/feedback/batch", &too_many, Some(&token)).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::PAYLOAD_TOO_LARGE, &json!("batch_too_large")));
+    assert_eq!(body["error"]["details"], json!({ "max_pairs": 4, "pairs": 5 }));
+}
@@ -0,0 +1,580 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::Error;
+use chrono::{Duration, Utc};
+use common::{access_token, get_json, init_app, init_app_with_clock, login, post_json, register, settings, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wordle_solver::repositories::words::DbWords;
+use wordle_solver::repositories::{game_sessions, words};
+use wordle_solver::utils::clock_utils::{Clock, FakeClock, SystemClock};
+use wordle_solver::utils::job_utils::{run_next_job, BuiltinJobs};
+use wordle_solver::utils::mail_utils::LogMailer;
+
+// A request playing an anonymous session, by its token rather than a login
+async fn anonymous<S, B>(app: &S, request: TestRequest, session_token: &str) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, request.insert_header(("X-Session-Token", session_token)).to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+fn guess(uri: &str, guess: &str, feedback: &str) -> TestRequest {
+    TestRequest::post().uri(uri).set_json(json!({ "guess": guess, "feedback": feedback }))
+}
+
+#[actix_web::test]
+async fn anonymous_sessions_are_claimed_once() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let (status, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), None).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(created["session"]["anonymous"], true);
+    assert_eq!(created["session"]["remaining"], 7);
+    let session_token = created["token"].as_str().unwrap().to_string();
+    let uri = format!("/api/v1/game/sessions/{}", created["session"]["id"]);
+    let guesses = format!("{}/guesses", uri);
+
+    let (status, session) = anonymous(&app, guess(&guesses, "CRANE", "gggxg"), &session_token).await;
+    assert_eq!(status, StatusCode::OK, "{}", session);
+    assert_eq!(session["remaining"], 1);
+    assert_eq!(session["guesses"][0]["guess"], "crane");
+    let (status, body) = anonymous(&app, guess(&guesses, "adieu", "xxxxx"), &session_token).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("constraint_contradiction")));
+
+    // Without the token it's as good as missing
+    let (status, _) = get_json(&app, &uri, None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = anonymous(&app, TestRequest::get().uri(&uri), "not the token").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let token = access_token(&app).await;
+    let (status, claimed) = post_json(&app, &format!("{}/claim", uri), &json!({ "token": session_token }), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", claimed);
+    assert_eq!(claimed["anonymous"], false);
+    assert_eq!(claimed["guesses"].as_array().unwrap().len(), 1);
+    assert!(claimed["claimed_at"].is_string());
+    assert!(claimed["expires_at"].is_null());
+
+    let (status, body) = post_json(&app, &format!("{}/claim", uri), &json!({ "token": session_token }), Some(&token)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::CONFLICT, Some("session_already_claimed")));
+    // The token no longer plays it, the account does
+    let (status, _) = anonymous(&app, TestRequest::get().uri(&uri), &session_token).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let (status, solved) = post_json(&app, &guesses, &json!({ "guess": "crate", "feedback": "ggggg" }), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(solved["status"], "solved");
+    assert!(solved["completed_at"].is_string());
+    let (status, body) = post_json(&app, &guesses, &json!({ "guess": "trace", "feedback": "xgggg" }), Some(&token)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::CONFLICT, Some("session_finished")));
+}
+
+#[actix_web::test]
+async fn claims_need_the_token_of_a_live_session() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings(), clock.clone()).await;
+    // Logging in too would issue the same tokens again on a clock that stands still
+    let (_, tokens) = register(&app, "stranger", "stranger@example.com", TEST_PASSWORD).await;
+    let stranger = tokens["access"].as_str().unwrap().to_string();
+
+    let (status, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), None).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", created);
+    let session_token = created["token"].as_str().unwrap();
+    let claim = format!("/api/v1/game/sessions/{}/claim", created["session"]["id"]);
+
+    let (status, _) = post_json(&app, &claim, &json!({ "token": "guessed" }), Some(&stranger)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let (status, _) = post_json(&app, &claim, &json!({ "token": session_token }), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    // Sessions started while logged in are the account's from the start
+    let (_, owned) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&stranger)).await;
+    assert!(owned.get("token").is_none());
+    assert_eq!(owned["session"]["anonymous"], false);
+
+    clock.advance(Duration::hours(25));
+    let (_, tokens) = register(&app, "latecomer", "latecomer@example.com", TEST_PASSWORD).await;
+    let latecomer = tokens["access"].as_str().unwrap();
+    let (status, body) = post_json(&app, &claim, &json!({ "token": session_token }), Some(latecomer)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::GONE, Some("session_expired")));
+    let (status, _) = anonymous(&app, TestRequest::get().uri(&format!("/api/v1/game/sessions/{}", created["session"]["id"])), session_token).await;
+    assert_eq!(status, StatusCode::GONE);
+
+    assert_eq!(game_sessions::delete_expired(&db.pool, clock.now()).await.unwrap(), 1);
+}
+
+// Plays `guesses` in order as the logged in owner and returns the last response
+async fn play<S, B>(app: &S, token: &str, id: &Value, guesses: &[(&str, &str)]) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let mut last = (StatusCode::OK, Value::Null);
+    for (guess, feedback) in guesses {
+        last = post_json(app, &format!("/api/v1/game/sessions/{}/guesses", id), &json!({ "guess": guess, "feedback": feedback }), Some(token)).await;
+    }
+    last
+}
+
+#[actix_web::test]
+async fn sessions_end_once_their_guesses_run_out() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    // No body at all is a standard game
+    let response = test::call_service(&app, TestRequest::post().uri("/api/v1/game/sessions").insert_header(("Authorization", format!("Bearer {}", token))).to_request()).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = test::read_body_json(response).await;
+    assert_eq!(created["session"]["max_guesses"], 6);
+
+    let (status, created) = post_json(&app, "/api/v1/game/sessions", &json!({ "max_guesses": 2 }), Some(&token)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", created);
+    let id = &created["session"]["id"];
+
+    let (status, session) = play(&app, &token, id, &[("pious", "xxxxx"), ("adieu", "yxxyx")]).await;
+    assert_eq!(status, StatusCode::OK, "{}", session);
+    assert_eq!(session["status"], "lost");
+    assert!(session["completed_at"].is_string());
+
+    let (status, body) = play(&app, &token, id, &[("crane", "ggggg")]).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::CONFLICT, Some("game_over")));
+    assert_eq!(body["error"]["details"]["max_guesses"], 2);
+
+    for max_guesses in [json!(0), json!(21), json!("six")] {
+        let (status, body) = post_json(&app, "/api/v1/game/sessions", &json!({ "max_guesses": max_guesses }), Some(&token)).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{}: {}", max_guesses, body);
+    }
+}
+
+#[actix_web::test]
+async fn free_play_has_no_limit() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({ "max_guesses": null }), Some(&token)).await;
+    assert_eq!(created["session"]["max_guesses"], Value::Null);
+
+    // The same guess twice in a row would be taken for a retry
+    let guesses: Vec<_> = [("pious", "xxxxx"), ("adieu", "yxxyx")].into_iter().cycle().take(9).collect();
+    let (status, session) = play(&app, &token, &created["session"]["id"], &guesses).await;
+    assert_eq!(status, StatusCode::OK, "{}", session);
+    assert_eq!(session["status"], "active");
+    assert_eq!(session["guesses"].as_array().unwrap().len(), 9);
+}
+
+#[actix_web::test]
+async fn free_play_stays_out_of_the_guess_distribution() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let (_, solved) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+    play(&app, &token, &solved["session"]["id"], &[("pious", "xxxxx"), ("crane", "ggggg")]).await;
+    let (_, lost) = post_json(&app, "/api/v1/game/sessions", &json!({ "max_guesses": 1 }), Some(&token)).await;
+    play(&app, &token, &lost["session"]["id"], &[("pious", "xxxxx")]).await;
+    let (_, free) = post_json(&app, "/api/v1/game/sessions", &json!({ "max_guesses": null }), Some(&token)).await;
+    let mut long_game: Vec<_> = [("pious", "xxxxx"), ("adieu", "yxxyx")].into_iter().cycle().take(7).collect();
+    long_game.push(("crane", "ggggg"));
+    let (_, session) = play(&app, &token, &free["session"]["id"], &long_game).await;
+    assert_eq!(session["status"], "solved");
+
+    let (status, stats) = get_json(&app, "/api/v1/game/sessions/stats", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", stats);
+    assert_eq!((stats["played"].as_i64(), stats["solved"].as_i64(), stats["lost"].as_i64()), (Some(2), Some(1), Some(1)));
+    let counts: Vec<i64> = stats["distribution"].as_array().unwrap().iter().map(|bucket| bucket["count"].as_i64().unwrap()).collect();
+    assert_eq!(counts, [0, 1, 0, 0, 0, 0]);
+    assert_eq!(stats["free_play"], json!({ "played": 1, "solved": 1 }));
+}
+
+#[actix_web::test]
+async fn stats_follow_the_owners_profile_visibility() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let (_, stranger) = register(&app, "stranger", "stranger@example.com", TEST_PASSWORD).await;
+    let stranger = stranger["access"].as_str().unwrap().to_string();
+    register(&app, "referee", "referee@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'referee'").execute(&db.pool).await.unwrap();
+    let (_, admin) = login(&app, "referee", TEST_PASSWORD).await;
+    let admin = admin["access"].as_str().unwrap().to_string();
+
+    let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+    play(&app, &token, &created["session"]["id"], &[("crane", "ggggg")]).await;
+    let id: i32 = sqlx::query_scalar("SELECT id FROM users WHERE username = $1").bind(TEST_USERNAME).fetch_one(&db.pool).await.unwrap();
+    let uri = format!("/api/v1/game/sessions/stats/{}", id);
+
+    for (visibility, stranger_status) in [("private", StatusCode::FORBIDDEN), ("friends", StatusCode::FORBIDDEN), ("public", StatusCode::OK)] {
+        let request = TestRequest::put()
+            .uri("/api/v1/users/me/preferences")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "profile_visibility": visibility }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+        for (viewer, expected) in [(&token, StatusCode::OK), (&stranger, stranger_status), (&admin, StatusCode::OK)] {
+            let (status, body) = get_json(&app, &uri, Some(viewer)).await;
+            assert_eq!(status, expected, "{}: {}", visibility, body);
+            if status == StatusCode::OK {
+                assert_eq!(body["solved"], 1, "{}", body);
+            } else {
+                assert_eq!(body["error"]["code"], "profile_private");
+            }
+        }
+    }
+}
+
+#[actix_web::test]
+async fn claimed_sessions_count_towards_the_stats() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), None).await;
+    let session_token = created["token"].as_str().unwrap().to_string();
+    let uri = format!("/api/v1/game/sessions/{}", created["session"]["id"]);
+    anonymous(&app, guess(&format!("{}/guesses", uri), "pious", "xxxxx"), &session_token).await;
+    let (status, solved) = anonymous(&app, guess(&format!("{}/guesses", uri), "crane", "ggggg"), &session_token).await;
+    assert_eq!((status, solved["status"].as_str()), (StatusCode::OK, Some("solved")));
+
+    let token = access_token(&app).await;
+    let (_, stats) = get_json(&app, "/api/v1/game/sessions/stats", Some(&token)).await;
+    assert_eq!(stats["played"], 0, "{}", stats);
+
+    let (status, _) = post_json(&app, &format!("{}/claim", uri), &json!({ "token": session_token }), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    // Started while logged in, played from the start
+    post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+
+    let (_, stats) = get_json(&app, "/api/v1/game/sessions/stats", Some(&token)).await;
+    assert_eq!((stats["played"].as_i64(), stats["solved"].as_i64(), stats["lost"].as_i64()), (Some(2), Some(1), Some(0)), "{}", stats);
+    assert_eq!(stats["distribution"][1], json!({ "guesses": 2, "count": 1 }));
+}
+
+#[actix_web::test]
+async fn recomputing_stats_repairs_a_corrupted_row() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    register(&app, "referee", "referee@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'referee'").execute(&db.pool).await.unwrap();
+    let (_, admin) = login(&app, "referee", TEST_PASSWORD).await;
+    let admin = admin["access"].as_str().unwrap().to_string();
+
+    let (_, solved) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+    play(&app, &token, &solved["session"]["id"], &[("pious", "xxxxx"), ("crane", "ggggg")]).await;
+    let (_, lost) = post_json(&app, "/api/v1/game/sessions", &json!({ "max_guesses": 1 }), Some(&token)).await;
+    play(&app, &token, &lost["session"]["id"], &[("pious", "xxxxx")]).await;
+    let (_, expected) = get_json(&app, "/api/v1/game/sessions/stats", Some(&token)).await;
+    assert_eq!((expected["played"].as_i64(), expected["solved"].as_i64(), expected["lost"].as_i64()), (Some(2), Some(1), Some(1)), "{}", expected);
+
+    // As a bug in the incremental updates would leave it
+    let tester: i32 = sqlx::query_scalar("SELECT id FROM users WHERE username = $1").bind(TEST_USERNAME).fetch_one(&db.pool).await.unwrap();
+    sqlx::query("UPDATE user_stats SET solved = 5, lost = 0 WHERE user_id = $1").bind(tester).execute(&db.pool).await.unwrap();
+    sqlx::query("INSERT INTO user_guess_counts (user_id, guesses, count) VALUES ($1, 4, 3)").bind(tester).execute(&db.pool).await.unwrap();
+    let (_, corrupted) = get_json(&app, "/api/v1/game/sessions/stats", Some(&token)).await;
+    assert_eq!(corrupted["solved"], 5, "the stats aren't read from the table: {}", corrupted);
+
+    let (status, body) = post_json(&app, "/api/v1/admin/recompute-stats", &json!({}), Some(&token)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::FORBIDDEN, Some("admin_required")));
+    let (status, accepted) = post_json(&app, "/api/v1/admin/recompute-stats", &json!({}), Some(&admin)).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", accepted);
+
+    // Login notices may be queued ahead of it
+    let runner = BuiltinJobs { db: db.pool.clone(), words: Arc::new(DbWords::new(db.pool.clone())), webhooks: settings().webhooks, mailer: Arc::new(LogMailer), clock: Arc::new(SystemClock) };
+    let id = accepted["id"].as_i64().map(|id| id as i32);
+    while let Some(ran) = run_next_job(&db.pool, &runner, &SystemClock).await.unwrap() {
+        if Some(ran) == id {
+            break;
+        }
+    }
+
+    let (_, job) = get_json(&app, accepted["status_url"].as_str().unwrap(), Some(&admin)).await;
+    assert_eq!(job["status"], "succeeded", "{}", job);
+    let result = &job["result"];
+    assert_eq!((result["users"].as_i64(), result["repaired"].as_i64()), (Some(2), Some(1)), "{}", result);
+    let discrepancy = &result["discrepancies"][0];
+    assert_eq!(discrepancy["user_id"], tester);
+    assert_eq!((discrepancy["stored"]["solved"].as_i64(), discrepancy["stored"]["distribution"][3]["count"].as_i64()), (Some(5), Some(3)));
+    assert_eq!(discrepancy["recomputed"], expected);
+
+    let (_, repaired) = get_json(&app, "/api/v1/game/sessions/stats", Some(&token)).await;
+    assert_eq!(repaired, expected);
+}
+
+async fn annotate<S, B>(app: &S, token: &str, id: &Value, update: Value) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::patch()
+        .uri(&format!("/api/v1/game/sessions/{}", id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(update);
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let body = test::read_body(response).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[actix_web::test]
+async fn notes_and_tags_are_normalized() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+    let id = &created["session"]["id"];
+
+    let tags = json!([" Double-Letter ", "double-letter", "NEW-OPENER", "new-opener"]);
+    let (status, session) = annotate(&app, &token, id, json!({ "note": "  answer had a double letter ", "tags": tags })).await;
+    assert_eq!(status, StatusCode::OK, "{}", session);
+    assert_eq!(session["note"], "answer had a double letter");
+    assert_eq!(session["tags"], json!(["double-letter", "new-opener"]));
+
+    // Leaving a field out keeps it
+    let (_, session) = annotate(&app, &token, id, json!({ "tags": ["Trap"] })).await;
+    assert_eq!((&session["note"], &session["tags"]), (&json!("answer had a double letter"), &json!(["trap"])));
+    let (_, session) = get_json(&app, &format!("/api/v1/game/sessions/{}", id), Some(&token)).await;
+    assert_eq!(session["tags"], json!(["trap"]));
+
+    // Eleven tags, ten once the ones differing only in case are merged
+    let mut tags: Vec<String> = (0..10).map(|n| format!("tag-{}", n)).collect();
+    tags.push("TAG-0".to_string());
+    let (status, session) = annotate(&app, &token, id, json!({ "tags": tags })).await;
+    assert_eq!(status, StatusCode::OK, "{}", session);
+    assert_eq!(session["tags"].as_array().unwrap().len(), 10);
+
+    tags.push("tag-10".to_string());
+    for update in [json!({ "tags": tags }), json!({ "tags": [" "] }), json!({ "tags": ["a,b"] }), json!({ "tags": ["x".repeat(33)] })] {
+        let (status, body) = annotate(&app, &token, id, update).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["error"]["code"], "invalid_session_tags");
+    }
+    let (status, body) = annotate(&app, &token, id, json!({ "note": "é".repeat(501) })).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "invalid_session_note");
+
+    let (_, session) = annotate(&app, &token, id, json!({ "note": " ", "tags": [] })).await;
+    assert_eq!((&session["note"], &session["tags"]), (&Value::Null, &json!([])));
+
+    // Other players are refused
+    register(&app, "referee", "referee@example.com", TEST_PASSWORD).await;
+    let (_, tokens) = login(&app, "referee", TEST_PASSWORD).await;
+    let (status, body) = annotate(&app, tokens["access"].as_str().unwrap(), id, json!({ "note": "mine now" })).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::FORBIDDEN, Some("not_owner")));
+}
+
+#[actix_web::test]
+async fn history_filters_on_tags_and_note_words() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let mut ids = Vec::new();
+    for (note, tags) in [
+        ("answer had a double letter", json!(["double-letter", "new-opener"])),
+        ("used the new opener, trap word", json!(["New-Opener"])),
+        ("", json!([])),
+    ] {
+        let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+        let id = created["session"]["id"].clone();
+        annotate(&app, &token, &id, json!({ "note": note, "tags": tags })).await;
+        ids.push(id);
+    }
+    play(&app, &token, &ids[0], &[("pious", "xxxxx")]).await;
+
+    // Another player's tagged session stays out of it
+    register(&app, "referee", "referee@example.com", TEST_PASSWORD).await;
+    let (_, tokens) = login(&app, "referee", TEST_PASSWORD).await;
+    let other_token = tokens["access"].as_str().unwrap();
+    let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(other_token)).await;
+    annotate(&app, other_token, &created["session"]["id"], json!({ "note": "double trouble", "tags": ["new-opener"] })).await;
+
+    let found = |history: &Value| history["items"].as_array().unwrap().iter().map(|item| item["id"].clone()).collect::<Vec<_>>();
+    let (status, history) = get_json(&app, "/api/v1/game/sessions", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", history);
+    assert_eq!(found(&history), [ids[2].clone(), ids[1].clone(), ids[0].clone()]);
+    assert_eq!(history["items"][2]["guesses"], 1);
+    assert_eq!(history["items"][2]["tags"], json!(["double-letter", "new-opener"]));
+
+    for (query, expected) in [
+        ("tags=NEW-OPENER", vec![ids[1].clone(), ids[0].clone()]),
+        ("tags=new-opener,%20double-letter", vec![ids[0].clone()]),
+        ("q=Double", vec![ids[0].clone()]),
+        ("q=opener%20trap", vec![ids[1].clone()]),
+        ("q=opener&tags=double-letter", vec![]),
+        ("tags=new-opener&per_page=1&page=2", vec![ids[0].clone()]),
+    ] {
+        let (status, history) = get_json(&app, &format!("/api/v1/game/sessions?{}", query), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK, "{}", history);
+        assert_eq!(found(&history), expected, "{}", query);
+    }
+    let (_, history) = get_json(&app, "/api/v1/game/sessions?tags=new-opener&per_page=1", Some(&token)).await;
+    assert_eq!(history["total"], 2);
+}
+
+#[actix_web::test]
+async fn a_guess_sent_twice_at_once_is_recorded_once() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+    let uri = format!("/api/v1/game/sessions/{}/guesses", created["session"]["id"]);
+
+    let send = || test::call_service(&app, guess(&uri, "pious", "xxxxx").insert_header(("Authorization", format!("Bearer {}", token))).to_request());
+    let (first, second) = futures::join!(send(), send());
+    let mut statuses = [first.status(), second.status()];
+    statuses.sort();
+    // The loser either waited on the lock and got the winner's answer, or lost the turn
+    assert!(matches!(statuses, [StatusCode::OK, StatusCode::OK] | [StatusCode::OK, StatusCode::CONFLICT]), "{:?}", statuses);
+
+    let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM session_guesses WHERE session_id = $1")
+        .bind(created["session"]["id"].as_i64().unwrap() as i32)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
+
+#[actix_web::test]
+async fn a_retried_guess_gets_the_first_answer() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings(), clock.clone()).await;
+    let (_, tokens) = register(&app, "referee", "referee@example.com", TEST_PASSWORD).await;
+    let token = tokens["access"].as_str().unwrap();
+    let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({ "max_guesses": 2 }), Some(token)).await;
+    let id = &created["session"]["id"];
+
+    let (status, first) = play(&app, token, id, &[("pious", "xxxxx")]).await;
+    assert_eq!(status, StatusCode::OK, "{}", first);
+    clock.advance(Duration::seconds(10));
+    let (status, retried) = play(&app, token, id, &[("PIOUS", "XXXXX")]).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(retried, first);
+
+    // The winning guess retried after the session was solved
+    let (_, solved) = play(&app, token, id, &[("crane", "ggggg")]).await;
+    assert_eq!(solved["status"], "solved");
+    let (status, retried) = play(&app, token, id, &[("crane", "ggggg")]).await;
+    assert_eq!((status, &retried), (StatusCode::OK, &solved));
+
+    // Once the window has passed it's a guess of its own
+    clock.advance(Duration::seconds(31));
+    let (status, body) = play(&app, token, id, &[("crane", "ggggg")]).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::CONFLICT, Some("session_finished")));
+}
+
+#[actix_web::test]
+async fn a_turn_can_only_be_taken_once() {
+    let db = TestDb::new().await;
+    let session = game_sessions::insert(&db.pool, None, None, None, Some(6), None, Utc::now()).await.unwrap();
+
+    game_sessions::insert_guess(&db.pool, session.id, 1, "pious", "xxxxx", Utc::now()).await.unwrap();
+    assert!(game_sessions::insert_guess(&db.pool, session.id, 1, "crane", "xxxxx", Utc::now()).await.is_err());
+    game_sessions::insert_guess(&db.pool, session.id, 2, "crane", "xxxxx", Utc::now()).await.unwrap();
+}
+
+#[actix_web::test]
+async fn reports_replay_against_the_word_list_they_were_made_with() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    register(&app, "referee", "referee@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'referee'").execute(&db.pool).await.unwrap();
+    let (_, tokens) = login(&app, "referee", TEST_PASSWORD).await;
+    let admin = tokens["access"].as_str().unwrap().to_string();
+
+    let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+    let id = &created["session"]["id"];
+    play(&app, &token, id, &[("pious", "xxxxx")]).await;
+    let uri = format!("/api/v1/game/sessions/{}/report", id);
+    let (status, body) = post_json(&app, &uri, &json!({ "description": "  " }), Some(&token)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("invalid_field")));
+    let (status, body) = post_json(&app, &uri, &json!({ "description": "Only crane is a real word" }), Some(&admin)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+
+    let (status, report) = post_json(&app, &uri, &json!({ "description": "Only crane is a real word" }), Some(&token)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", report);
+    assert_eq!(report["guesses"], json!([{ "guess": "pious", "feedback": "xxxxx" }]));
+    assert_eq!(report["original"]["candidates"], json!(["crane", "crate", "react", "trace"]));
+    assert_eq!(report["original"]["candidate_count"], 4);
+
+    // The list changes after the report
+    let (_, suggestion) = post_json(&app, "/api/v1/game/words/suggestions", &json!({ "word": "crate", "action": "remove", "reason": "Not a word" }), Some(&token)).await;
+    let (status, _) = post_json(&app, &format!("/api/v1/admin/word-suggestions/{}/approve", suggestion["id"]), &json!({}), Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    // and its older history is pruned, except what the report needs
+    words::prune_changes(&db.pool, Utc::now() + Duration::days(1)).await.unwrap();
+
+    let replay_uri = format!("/api/v1/admin/reports/{}/replay", report["id"]);
+    let (status, _) = get_json(&app, &replay_uri, Some(&token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    let (status, replay) = get_json(&app, &replay_uri, Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK, "{}", replay);
+    assert_eq!(replay["report"], report);
+    assert_eq!(replay["replayed"], report["original"]);
+    assert_eq!(replay["current"]["candidates"], json!(["crane", "react", "trace"]));
+    assert!(replay["current"]["word_list_version"].as_i64() > report["original"]["word_list_version"].as_i64());
+    let (status, _) = get_json(&app, "/api/v1/admin/reports/999/replay", Some(&admin)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+// Pinned so a mark's meaning and its colour can never drift apart
+#[actix_web::test]
+async fn feedback_squares_spell_out_each_mark_in_either_palette() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let (_, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+    let uri = format!("/api/v1/game/sessions/{}", created["session"]["id"]);
+    let (status, session) = post_json(&app, &format!("{}/guesses", uri), &json!({ "guess": "trace", "feedback": "xggyg" }), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", session);
+    assert_eq!(
+        session["guesses"][0]["squares"],
+        json!([
+            { "letter": "t", "mark": "absent" },
+            { "letter": "r", "mark": "correct" },
+            { "letter": "a", "mark": "correct" },
+            { "letter": "c", "mark": "present" },
+            { "letter": "e", "mark": "correct" },
+        ])
+    );
+    let high_contrast = json!([
+        { "letter": "t", "mark": "absent", "hint": "gray" },
+        { "letter": "r", "mark": "correct", "hint": "orange" },
+        { "letter": "a", "mark": "correct", "hint": "orange" },
+        { "letter": "c", "mark": "present", "hint": "blue" },
+        { "letter": "e", "mark": "correct", "hint": "orange" },
+    ]);
+    let (_, session) = get_json(&app, &format!("{}?palette=high_contrast", uri), Some(&token)).await;
+    assert_eq!(session["guesses"][0]["squares"], high_contrast);
+
+    // The colorblind preference picks high contrast unless the request says otherwise
+    let request = TestRequest::put()
+        .uri("/api/v1/users/me/preferences")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "colorblind": true }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let (_, session) = get_json(&app, &uri, Some(&token)).await;
+    assert_eq!(session["guesses"][0]["squares"], high_contrast);
+    let (_, session) = get_json(&app, &format!("{}?palette=standard", uri), Some(&token)).await;
+    assert!(session["guesses"][0]["squares"][0].get("hint").is_none());
+
+    let (status, body) = get_json(&app, &format!("{}?palette=neon", uri), Some(&token)).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::BAD_REQUEST, &json!("invalid_query")));
+}
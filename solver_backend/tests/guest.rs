@@ -0,0 +1,126 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::Error;
+use chrono::Utc;
+use common::{access_token, get_json, init_app, init_app_with, post_json, settings_with, signing_keys, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::{json, Value};
+use wordle_solver::handlers::examples;
+use wordle_solver::utils::jwt_utils::decode_claims;
+
+async fn guest_token<S, B>(app: &S) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (status, body) = post_json(app, "/api/v1/users/guest", &json!({}), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    body["access"].as_str().unwrap().to_string()
+}
+
+#[actix_web::test]
+async fn guests_can_filter_words_and_nothing_else() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let guest = guest_token(&app).await;
+    let claims = decode_claims(&signing_keys(), &guest, Utc::now()).unwrap();
+    assert_eq!(claims.user_id, 0);
+    assert!(claims.guest.is_some());
+
+    let (status, words) = post_json(&app, "/api/v1/game/candidates", &examples::request_letters(), Some(&guest)).await;
+    assert_eq!(status, StatusCode::OK, "{}", words);
+    assert_eq!(words["total"], 3);
+    let pairs = json!({ "pairs": [{ "guess": "crane", "answer": "crate" }] });
+    let (status, batch) = post_json(&app, "/api/v1/game/feedback/batch", &pairs, Some(&guest)).await;
+    assert_eq!((status, &batch["results"][0]["feedback"]), (StatusCode::OK, &json!("gggxg")));
+
+    for uri in ["/api/v1/game/sessions/stats", "/api/v1/game/sessions", "/api/v1/users", "/api/v1/users/me/sessions", "/api/v1/users/me/preferences", "/api/v1/users/me/usage"] {
+        let (status, body) = get_json(&app, uri, Some(&guest)).await;
+        assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::FORBIDDEN, Some("guest_not_allowed")), "{}", uri);
+    }
+    let (status, body) = post_json(&app, "/api/v1/game/best-guess", &json!({ "letters": examples::request_letters() }), Some(&guest)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::FORBIDDEN, Some("guest_not_allowed")));
+    let tester: i32 = sqlx::query_scalar("SELECT id FROM users WHERE username = $1").bind(TEST_USERNAME).fetch_one(&db.pool).await.unwrap();
+    let profile = TestRequest::put()
+        .uri(&format!("/api/v1/users/{}", tester))
+        .insert_header(("Authorization", format!("Bearer {}", guest)))
+        .set_json(json!({ "username": "guest", "email": "guest@example.com", "version": 1 }));
+    let response = test::call_service(&app, profile.to_request()).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["error"]["code"], "guest_not_allowed");
+
+    // The same routes at the legacy prefix
+    let (status, _) = post_json(&app, "/api/game/candidates", &examples::request_letters(), Some(&guest)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = get_json(&app, "/api/users/me/sessions", Some(&guest)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    // Nothing to refresh into
+    let (status, body) = post_json(&app, "/api/v1/users/get_new_tokens", &json!({ "token": guest }), None).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::UNAUTHORIZED, Some("invalid_token")));
+}
+
+#[actix_web::test]
+async fn registering_with_a_guest_token_claims_its_sessions() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let guest = guest_token(&app).await;
+
+    let (status, created) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&guest)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", created);
+    assert_eq!(created["session"]["anonymous"], true);
+    let session_token = created["token"].as_str().unwrap().to_string();
+    let guesses = format!("/api/v1/game/sessions/{}/guesses", created["session"]["id"]);
+    let request = TestRequest::post()
+        .uri(&guesses)
+        .insert_header(("X-Session-Token", session_token.as_str()))
+        .set_json(json!({ "guess": "crane", "feedback": "gggxg" }));
+    assert_eq!(test::call_service(&app, request.to_request()).await.status(), StatusCode::OK);
+    // Someone else's anonymous session stays theirs
+    let (_, other) = post_json(&app, "/api/v1/game/sessions", &json!({}), None).await;
+
+    let (status, body) = post_json(&app, "/api/v1/users/register", &json!({ "username": "player", "email": "player@example.com", "password": TEST_PASSWORD, "guest_token": "not.a.token" }), None).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::BAD_REQUEST, Some("invalid_token")));
+    let account = access_token(&app).await;
+    let (status, _) = post_json(&app, "/api/v1/users/register", &json!({ "username": "player", "email": "player@example.com", "password": TEST_PASSWORD, "guest_token": account }), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, tokens) = post_json(&app, "/api/v1/users/register", &json!({ "username": "player", "email": "player@example.com", "password": TEST_PASSWORD, "guest_token": guest }), None).await;
+    assert_eq!(status, StatusCode::OK, "{}", tokens);
+    let player = tokens["access"].as_str().unwrap();
+
+    let (status, history) = get_json(&app, "/api/v1/game/sessions", Some(player)).await;
+    assert_eq!(status, StatusCode::OK, "{}", history);
+    let ids: Vec<&Value> = history["items"].as_array().unwrap().iter().map(|session| &session["id"]).collect();
+    assert_eq!(ids, [&created["session"]["id"]]);
+    let (status, session) = get_json(&app, &format!("/api/v1/game/sessions/{}", created["session"]["id"]), Some(player)).await;
+    assert_eq!((status, &session["anonymous"], &session["guesses"][0]["guess"]), (StatusCode::OK, &json!(false), &json!("crane")));
+
+    // The session token no longer plays it, and the other session wasn't touched
+    let request = TestRequest::get().uri(&format!("/api/v1/game/sessions/{}", created["session"]["id"])).insert_header(("X-Session-Token", session_token.as_str()));
+    assert_eq!(test::call_service(&app, request.to_request()).await.status(), StatusCode::UNAUTHORIZED);
+    let request = TestRequest::get().uri(&format!("/api/v1/game/sessions/{}", other["session"]["id"])).insert_header(("X-Session-Token", other["token"].as_str().unwrap()));
+    let response = test::call_service(&app, request.to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let session: Value = test::read_body_json(response).await;
+    assert_eq!(session["anonymous"], true);
+}
+
+#[actix_web::test]
+async fn guest_tokens_are_rate_limited_per_client() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[("RATE_LIMIT_GUEST", "2")]).unwrap()).await;
+    let guest = |peer: &str| TestRequest::post().uri("/api/v1/users/guest").peer_addr(format!("{}:40000", peer).parse().unwrap()).to_request();
+
+    for _ in 0..2 {
+        assert_eq!(test::call_service(&app, guest("203.0.113.9")).await.status(), StatusCode::OK);
+    }
+    let response = test::call_service(&app, guest("203.0.113.9")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(test::call_service(&app, guest("198.51.100.1")).await.status(), StatusCode::OK);
+}
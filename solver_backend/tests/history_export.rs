@@ -0,0 +1,131 @@
+mod common;
+
+use actix_web::body::MessageBody;
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web::Bytes;
+use chrono::{Duration, TimeZone, Utc};
+use common::{access_token, get_json, init_app, init_app_with_clock, post_json, register, settings, TestDb, TEST_PASSWORD};
+use futures::future::poll_fn;
+use serde_json::json;
+use std::pin::{pin, Pin};
+use std::sync::Arc;
+use wordle_solver::utils::clock_utils::FakeClock;
+
+fn rows(csv: &[u8]) -> Vec<Vec<String>> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .from_reader(csv)
+        .records()
+        .map(|record| record.unwrap().iter().map(str::to_string).collect())
+        .collect()
+}
+
+async fn next_chunk<B: MessageBody>(body: &mut Pin<&mut B>) -> Option<Result<Bytes, B::Error>> {
+    poll_fn(|cx| body.as_mut().poll_next(cx)).await
+}
+
+#[actix_web::test]
+async fn history_is_exported_a_line_per_guess_with_notes_quoted() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc.with_ymd_and_hms(2023, 7, 20, 9, 0, 0).unwrap()));
+    let app = init_app_with_clock(&db, settings(), clock.clone()).await;
+    let token = access_token(&app).await;
+
+    // Solved in 95 seconds, with a note CSV has to quote
+    let (_, solved) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+    let solved = solved["session"]["id"].to_string();
+    let note = "Doubled, \"again\"\nsecond line";
+    let update = TestRequest::patch().uri(&format!("/api/v1/game/sessions/{}", solved)).insert_header(("Authorization", format!("Bearer {}", token))).set_json(json!({ "note": note }));
+    assert_eq!(test::call_service(&app, update.to_request()).await.status(), StatusCode::OK);
+    for (advance, guess, feedback) in [(5, "crane", "yggxg"), (90, "trace", "ggggg")] {
+        clock.advance(Duration::seconds(advance));
+        let (status, body) = post_json(&app, &format!("/api/v1/game/sessions/{}/guesses", solved), &json!({ "guess": guess, "feedback": feedback }), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK, "{}", body);
+    }
+
+    // The next day, still going
+    clock.advance(Duration::days(1));
+    let token = access_token(&app).await;
+    let (_, going) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+    let going = going["session"]["id"].to_string();
+    let (status, body) = post_json(&app, &format!("/api/v1/game/sessions/{}/guesses", going), &json!({ "guess": "slate", "feedback": "xxggg" }), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    // Someone else's game isn't in it
+    let (_, tokens) = register(&app, "stranger", "stranger@example.com", TEST_PASSWORD).await;
+    let stranger = tokens["access"].as_str().unwrap();
+    let (_, theirs) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(stranger)).await;
+    post_json(&app, &format!("/api/v1/game/sessions/{}/guesses", theirs["session"]["id"]), &json!({ "guess": "adieu", "feedback": "xxxxx" }), Some(stranger)).await;
+
+    let export = |query: &str| TestRequest::get().uri(&format!("/api/v1/game/history/export{}", query)).insert_header(("Authorization", format!("Bearer {}", token))).to_request();
+    let response = test::call_service(&app, export("")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "text/csv; charset=utf-8");
+    assert_eq!(response.headers().get("Content-Disposition").unwrap(), "attachment; filename=\"wordle-history-2023-07-21.csv\"");
+    let body = test::read_body(response).await;
+    assert!(String::from_utf8_lossy(&body).contains("\"Doubled, \"\"again\"\"\nsecond line\"\r\n"));
+    let line = |session: &str, date: &str, turn: &str, guess: &str, pattern: &str, solved: &str, duration: &str, note: &str| {
+        [session, date, turn, guess, pattern, solved, duration, note].map(str::to_string).to_vec()
+    };
+    assert_eq!(
+        rows(&body),
+        [
+            line("session_id", "date", "turn", "guess", "pattern", "solved", "duration_seconds", "note"),
+            line(&solved, "2023-07-20 09:00:05", "1", "crane", "yggxg", "true", "95", note),
+            line(&solved, "2023-07-20 09:01:35", "2", "trace", "ggggg", "true", "95", note),
+            line(&going, "2023-07-21 09:01:35", "1", "slate", "xxggg", "false", "", ""),
+        ]
+    );
+
+    // Filtered by the day each session started, both ends included
+    let body = test::read_body(test::call_service(&app, export("?from=2023-07-21")).await).await;
+    assert_eq!(rows(&body).iter().skip(1).map(|row| row[0].as_str()).collect::<Vec<_>>(), [going.as_str()]);
+    let body = test::read_body(test::call_service(&app, export("?from=2023-07-19&to=2023-07-20")).await).await;
+    assert_eq!(rows(&body).iter().skip(1).map(|row| row[0].as_str()).collect::<Vec<_>>(), [solved.as_str(), solved.as_str()]);
+    let body = test::read_body(test::call_service(&app, export("?to=2023-07-19")).await).await;
+    assert_eq!(rows(&body).len(), 1);
+
+    let body = test::read_body(test::call_service(&app, export("?bom=true&from=2023-07-21")).await).await;
+    assert!(body.starts_with(b"\xEF\xBB\xBFsession_id,date,"));
+
+    let (status, body) = get_json(&app, "/api/v1/game/history/export?from=2023-07-21&to=2023-07-20", Some(&token)).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::UNPROCESSABLE_ENTITY, &json!("invalid_date_range")));
+    assert_eq!(get_json(&app, "/api/v1/game/history/export", None).await.0, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn export_ends_with_an_error_when_the_database_fails_mid_stream() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let (_, session) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&token)).await;
+    let id = session["session"]["id"].as_i64().unwrap();
+    // More guesses than one page holds
+    sqlx::query(
+            r#"
+            WITH RECURSIVE turns (turn) AS (SELECT 1 UNION ALL SELECT turn + 1 FROM turns WHERE turn < 600)
+            INSERT INTO session_guesses (session_id, turn, guess, feedback, created_at)
+            SELECT $1, turn, 'crane', 'xxxxx', $2 FROM turns
+            "#)
+        .bind(id as i32)
+        .bind(Utc::now())
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+    let request = TestRequest::get().uri("/api/v1/game/history/export").insert_header(("Authorization", format!("Bearer {}", token))).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let mut body = pin!(response.into_body());
+
+    let header = next_chunk(&mut body).await.unwrap().ok().unwrap();
+    assert_eq!(&header[..], b"session_id,date,turn,guess,pattern,solved,duration_seconds,note\r\n");
+    let page = next_chunk(&mut body).await.unwrap().ok().unwrap();
+    assert_eq!(rows(&page).len(), 500);
+
+    // Gone before the second page is read: the error ends the stream, nothing follows it
+    sqlx::query("ALTER TABLE session_guesses RENAME TO session_guesses_moved").execute(&db.pool).await.unwrap();
+    assert!(next_chunk(&mut body).await.unwrap().is_err());
+    assert!(next_chunk(&mut body).await.is_none());
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use chrono::Utc;
+use common::{access_token, get_json, init_app, init_app_with, login, post_json, register, settings_with, signing_keys, user_id, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::{json, Value};
+use wordle_solver::utils::jwt_utils::decode_claims;
+
+async fn audit_trail(db: &TestDb, user_id: i32) -> Vec<(String, String)> {
+    sqlx::query_as("SELECT event_type, detail FROM auth_events WHERE user_id = $1 AND event_type IN ('impersonation_started', 'impersonating', 'impersonated_request') ORDER BY id")
+        .bind(user_id)
+        .fetch_all(&db.pool)
+        .await
+        .unwrap()
+}
+
+fn preferences(token: &str, body: Value) -> TestRequest {
+    TestRequest::put().uri("/api/v1/users/me/preferences").insert_header(("Authorization", format!("Bearer {}", token))).set_json(body)
+}
+
+#[actix_web::test]
+async fn impersonation_tokens_read_as_the_user_refuse_writes_and_are_audited() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    register(&app, "helper", "helper@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'helper'").execute(&db.pool).await.unwrap();
+    let (_, admin) = login(&app, "helper", TEST_PASSWORD).await;
+    let admin = admin["access"].as_str().unwrap().to_string();
+    let (tester, support) = (user_id(&db, TEST_USERNAME).await, user_id(&db, "helper").await);
+
+    let saved = test::call_service(&app, preferences(&token, json!({ "hard_mode": true })).to_request()).await;
+    assert_eq!(saved.status(), StatusCode::OK);
+
+    let (status, impersonation) = post_json(&app, &format!("/api/v1/admin/impersonate/{}", tester), &json!({}), Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK, "{}", impersonation);
+    assert_eq!((&impersonation["user_id"], &impersonation["impersonator"]), (&json!(tester), &json!(support)));
+    let impersonated = impersonation["access"].as_str().unwrap().to_string();
+    let claims = decode_claims(&signing_keys(), &impersonated, Utc::now()).unwrap();
+    assert_eq!((claims.user_id, claims.impersonator), (tester, Some(support)));
+    assert_eq!(decode_claims(&signing_keys(), &token, Utc::now()).unwrap().impersonator, None);
+
+    // Reads see what the user sees
+    let (status, body) = get_json(&app, "/api/v1/users/me/preferences", Some(&impersonated)).await;
+    assert_eq!((status, &body["hard_mode"]), (StatusCode::OK, &json!(true)));
+
+    // Writes are refused, and the token can't be turned into a normal session
+    let refused = test::call_service(&app, preferences(&impersonated, json!({ "hard_mode": false })).to_request()).await;
+    assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(refused).await;
+    assert_eq!(body["error"]["code"], "impersonation_read_only");
+    assert_eq!(get_json(&app, "/api/v1/users/me/preferences", Some(&token)).await.1["hard_mode"], true);
+    let profile = TestRequest::patch()
+        .uri(&format!("/api/v1/users/{}", tester))
+        .insert_header(("Authorization", format!("Bearer {}", impersonated)))
+        .set_json(json!({ "username": "hijacked", "email": "hijacked@example.com", "version": 1 }));
+    let refused = test::call_service(&app, profile.to_request()).await;
+    assert_eq!(refused.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(refused).await;
+    assert_eq!(body["error"]["code"], "impersonation_read_only");
+    assert_eq!(get_json(&app, &format!("/api/v1/users/{}", tester), Some(&token)).await.1["username"], TEST_USERNAME);
+    let (status, body) = post_json(&app, "/api/v1/users/get_new_tokens", &json!({ "token": impersonated }), None).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::UNAUTHORIZED, &json!("invalid_token")));
+
+    // Both parties are on record
+    assert_eq!(
+        audit_trail(&db, tester).await,
+        [
+            ("impersonation_started".to_string(), format!("by admin {}", support)),
+            ("impersonated_request".to_string(), format!("GET /api/v1/users/me/preferences by admin {}", support)),
+            ("impersonated_request".to_string(), format!("PUT /api/v1/users/me/preferences by admin {}, refused", support)),
+            ("impersonated_request".to_string(), format!("PATCH /api/v1/users/{} by admin {}, refused", tester, support)),
+        ]
+    );
+    assert_eq!(audit_trail(&db, support).await, [("impersonating".to_string(), format!("user {}", tester))]);
+
+    // Only admins impersonate, and only non-admins
+    assert_eq!(get_json(&app, "/api/v1/admin/summary", Some(&impersonated)).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(post_json(&app, &format!("/api/v1/admin/impersonate/{}", support), &json!({}), Some(&token)).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(post_json(&app, &format!("/api/v1/admin/impersonate/{}", support), &json!({}), Some(&admin)).await.0, StatusCode::FORBIDDEN);
+    assert_eq!(post_json(&app, "/api/v1/admin/impersonate/999999", &json!({}), Some(&admin)).await.0, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn users_see_impersonations_among_their_sessions_and_can_revoke_them() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[("IMPERSONATION_ALLOW_WRITES", "true")]).unwrap()).await;
+    let token = access_token(&app).await;
+    let (_, admin) = register(&app, "helper", "helper@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'helper'").execute(&db.pool).await.unwrap();
+    let admin = admin["access"].as_str().unwrap().to_string();
+    let (tester, support) = (user_id(&db, TEST_USERNAME).await, user_id(&db, "helper").await);
+
+    let (_, impersonation) = post_json(&app, &format!("/api/v1/admin/impersonate/{}", tester), &json!({}), Some(&admin)).await;
+    let impersonated = impersonation["access"].as_str().unwrap().to_string();
+
+    // Allowed to write, still audited
+    let saved = test::call_service(&app, preferences(&impersonated, json!({ "colorblind": true })).to_request()).await;
+    assert_eq!(saved.status(), StatusCode::OK);
+    assert_eq!(audit_trail(&db, tester).await.last().unwrap().1, format!("PUT /api/v1/users/me/preferences by admin {}", support));
+
+    let (_, page) = get_json(&app, "/api/v1/users/me/sessions", Some(&token)).await;
+    assert_eq!(page["total"], 2);
+    let sessions = page["items"].as_array().unwrap();
+    let session = sessions.iter().find(|session| session["impersonated_by"] == json!(support)).unwrap();
+    assert!(session["expires_at"].is_string());
+    assert!(sessions.iter().any(|session| session["impersonated_by"].is_null() && session["expires_at"].is_null()));
+
+    let revoke = |id: &Value| TestRequest::delete().uri(&format!("/api/v1/users/me/sessions/{}", id)).insert_header(("Authorization", format!("Bearer {}", token))).to_request();
+    assert_eq!(test::call_service(&app, revoke(&session["id"])).await.status(), StatusCode::NO_CONTENT);
+    let (status, body) = get_json(&app, "/api/v1/users/me/preferences", Some(&impersonated)).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::UNAUTHORIZED, &json!("token_revoked")));
+    assert_eq!(get_json(&app, "/api/v1/users/me/sessions", Some(&token)).await.1["total"], 1);
+    assert_eq!(test::call_service(&app, revoke(&session["id"])).await.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use chrono::{Duration, Utc};
+use common::{access_token, init_app, init_app_with, post_json, settings, settings_with, signing_keys, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde_json::{json, Value};
+use wordle_solver::models::users_models::AccessClaims;
+use wordle_solver::utils::jwt_utils::{decode_claims, generate_access_token};
+
+const SECRET: &str = "introspection-test-secret-of-32-chars";
+const INTROSPECT: &str = "/api/v1/auth/introspect";
+
+async fn tester_id(db: &TestDb) -> i32 {
+    sqlx::query_scalar("SELECT id FROM users WHERE username = $1").bind(TEST_USERNAME).fetch_one(&db.pool).await.unwrap()
+}
+
+#[actix_web::test]
+async fn accepted_tokens_are_active_with_their_claims() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[("INTROSPECTION_SECRET", SECRET)]).unwrap()).await;
+    let token = access_token(&app).await;
+
+    let (status, body) = post_json(&app, INTROSPECT, &json!({ "token": token }), Some(SECRET)).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let claims = decode_claims(&signing_keys(), &token, Utc::now()).unwrap();
+    assert_eq!(body, json!({
+        "active": true,
+        "sub": tester_id(&db).await.to_string(),
+        "role": "user",
+        "exp": claims.exp,
+        "iat": claims.issued.timestamp(),
+    }));
+
+    // The same answer the routes give
+    let (status, _) = post_json(&app, "/api/v1/users/check_access", &json!({ "token": token }), None).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn inactive_tokens_say_why() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[("INTROSPECTION_SECRET", SECRET)]).unwrap()).await;
+    let tester = tester_id(&db).await;
+    let introspect = |token: String| {
+        let app = &app;
+        async move { post_json(app, INTROSPECT, &json!({ "token": token }), Some(SECRET)).await }
+    };
+
+    // Before anything caches the tester's current version
+    let stale = access_token(&app).await;
+    sqlx::query("UPDATE users SET token_version = token_version + 1 WHERE id = $1").bind(tester).execute(&db.pool).await.unwrap();
+    assert_eq!(introspect(stale).await, (StatusCode::OK, json!({ "active": false, "reason": "version_mismatch" })));
+
+    let expired = generate_access_token(&signing_keys(), tester, 1, &settings().jwt, Utc::now() - Duration::days(1)).unwrap();
+    assert_eq!(introspect(expired).await, (StatusCode::OK, json!({ "active": false, "reason": "expired" })));
+
+    let revoked = access_token(&app).await;
+    let (status, _) = post_json(&app, "/api/v1/users/revoke_token", &json!({ "token": revoked }), Some(&revoked)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(introspect(revoked).await, (StatusCode::OK, json!({ "active": false, "reason": "revoked" })));
+
+    let claims = decode_claims(&signing_keys(), &access_token(&app).await, Utc::now()).unwrap();
+    let mut header = Header::default();
+    header.kid = Some("default".to_string());
+    let forged = encode(&header, &claims, &EncodingKey::from_secret(b"someone-elses-secret")).unwrap();
+    assert_eq!(introspect(forged).await, (StatusCode::OK, json!({ "active": false, "reason": "bad_signature" })));
+    assert_eq!(introspect("not.a.token".to_string()).await, (StatusCode::OK, json!({ "active": false, "reason": "bad_signature" })));
+
+    // Refresh tokens are only good for getting a new pair
+    let (_, tokens) = post_json(&app, "/api/v1/users/login", &json!({ "username": TEST_USERNAME, "password": TEST_PASSWORD }), None).await;
+    let refresh = tokens["refresh"].as_str().unwrap().to_string();
+    assert_eq!(introspect(refresh).await, (StatusCode::OK, json!({ "active": false, "reason": "wrong_type" })));
+
+    // Users that are gone count as revoked
+    let orphan = AccessClaims { user_id: 999_999, ..claims };
+    let orphan = encode(&header, &orphan, &EncodingKey::from_secret(b"integration-test-secret")).unwrap();
+    assert_eq!(introspect(orphan).await, (StatusCode::OK, json!({ "active": false, "reason": "revoked" })));
+}
+
+#[actix_web::test]
+async fn only_services_may_introspect() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[("INTROSPECTION_SECRET", SECRET)]).unwrap()).await;
+    let token = access_token(&app).await;
+    let body = json!({ "token": token });
+
+    let (status, error) = post_json(&app, INTROSPECT, &body, None).await;
+    assert_eq!((status, error["error"]["code"].as_str()), (StatusCode::UNAUTHORIZED, Some("missing_token")));
+    for wrong in ["introspection-test-secret-of-32-chart", token.as_str()] {
+        let (status, error) = post_json(&app, INTROSPECT, &body, Some(wrong)).await;
+        assert_eq!((status, error["error"]["code"].as_str()), (StatusCode::UNAUTHORIZED, Some("invalid_token")));
+    }
+
+    // Without a secret configured nothing gets in
+    let app = init_app(&db).await;
+    let (status, _) = post_json(&app, INTROSPECT, &body, Some(SECRET)).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn a_trusted_proxy_can_vouch_for_a_client_certificate() {
+    let db = TestDb::new().await;
+    let settings = settings_with(&[("TRUSTED_PROXIES", "10.0.0.0/8"), ("INTROSPECTION_MTLS_HEADER", "X-Client-Verify")]).unwrap();
+    let app = init_app_with(&db, settings).await;
+    let token = access_token(&app).await;
+    let introspect = |peer: &str, verified: &str| {
+        TestRequest::post()
+            .uri(INTROSPECT)
+            .peer_addr(format!("{}:40000", peer).parse().unwrap())
+            .insert_header(("X-Client-Verify", verified))
+            .set_json(json!({ "token": token }))
+            .to_request()
+    };
+
+    let response = test::call_service(&app, introspect("10.1.2.3", "SUCCESS")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["active"], true);
+
+    assert_eq!(test::call_service(&app, introspect("10.1.2.3", "FAILED:unknown ca")).await.status(), StatusCode::UNAUTHORIZED);
+    // Anyone can send the header, only the proxy is believed
+    assert_eq!(test::call_service(&app, introspect("203.0.113.9", "SUCCESS")).await.status(), StatusCode::UNAUTHORIZED);
+
+    assert!(settings_with(&[("INTROSPECTION_MTLS_HEADER", "X-Client-Verify")]).is_err());
+    assert!(settings_with(&[("INTROSPECTION_SECRET", "too short")]).is_err());
+}
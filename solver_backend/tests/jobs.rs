@@ -0,0 +1,132 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use async_trait::async_trait;
+use chrono::Utc;
+use common::{access_token, get_json, init_app, post_json, register, settings, TestDb, TEST_USERNAME};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use wordle_solver::models::jobs_models::Job;
+use wordle_solver::repositories::words::DbWords;
+use wordle_solver::repositories::{jobs, users};
+use wordle_solver::utils::clock_utils::{Clock, FakeClock, SystemClock};
+use wordle_solver::utils::job_utils::{retry_delay, run_next_job, BuiltinJobs, JobRunner};
+use wordle_solver::utils::mail_utils::LogMailer;
+
+// Fails the first `failures` runs of any job
+struct FlakyRunner {
+    failures: usize,
+    runs: AtomicUsize,
+}
+
+#[async_trait]
+impl JobRunner for FlakyRunner {
+    async fn run(&self, _job: &Job) -> Result<Value, String> {
+        if self.runs.fetch_add(1, Ordering::SeqCst) < self.failures {
+            return Err("simulated failure".to_string());
+        }
+        Ok(json!({ "done": true }))
+    }
+}
+
+#[actix_web::test]
+async fn benchmark_runs_in_the_background_and_is_polled_by_its_owner() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let (status, accepted) = post_json(&app, "/api/v1/game/benchmark", &json!({ "sample": 3 }), Some(&token)).await;
+    assert_eq!(status, StatusCode::ACCEPTED, "{}", accepted);
+    let status_url = accepted["status_url"].as_str().unwrap().to_string();
+
+    let (status, job) = get_json(&app, &status_url, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", job);
+    assert_eq!(job["status"], "queued");
+
+    let runner = BuiltinJobs { db: db.pool.clone(), words: Arc::new(DbWords::new(db.pool.clone())), webhooks: settings().webhooks, mailer: Arc::new(LogMailer), clock: Arc::new(SystemClock) };
+    // The login's new device notice was queued ahead of it
+    run_next_job(&db.pool, &runner, &SystemClock).await.unwrap();
+    let ran = run_next_job(&db.pool, &runner, &SystemClock).await.unwrap();
+    assert_eq!(ran, accepted["id"].as_i64().map(|id| id as i32));
+
+    let (_, job) = get_json(&app, &status_url, Some(&token)).await;
+    assert_eq!(job["status"], "succeeded", "{}", job);
+    assert_eq!(job["result"]["games"], 3);
+
+    let (_, other) = register(&app, "someoneelse", "someoneelse@example.com", "a long enough password").await;
+    let (status, _) = get_json(&app, &status_url, other["access"].as_str()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn benchmark_sample_is_bounded() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let (status, body) = post_json(&app, "/api/v1/game/benchmark", &json!({ "sample": 0 }), Some(&token)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "invalid_sample");
+}
+
+#[actix_web::test]
+async fn two_workers_never_claim_the_same_job() {
+    let db = TestDb::new().await;
+    let now = Utc::now();
+    let mut queued = Vec::new();
+    for _ in 0..20 {
+        queued.push(jobs::enqueue(&db.pool, None, "noop", &json!({}), 1, now).await.unwrap());
+    }
+
+    let worker = || async {
+        let mut claimed = Vec::new();
+        while let Some(job) = jobs::claim(&db.pool, db.pool.any_kind(), Utc::now(), now).await.unwrap() {
+            claimed.push(job.id);
+        }
+        claimed
+    };
+    let (first, second) = futures::join!(worker(), worker());
+
+    let mut claimed: Vec<i32> = first.iter().chain(&second).copied().collect();
+    claimed.sort_unstable();
+    assert_eq!(claimed, queued, "first claimed {:?}, second {:?}", first, second);
+}
+
+#[actix_web::test]
+async fn failed_jobs_are_retried_after_a_backoff() {
+    let db = TestDb::new().await;
+    let clock = FakeClock::new(Utc::now());
+    let user = users::find_by_username(&db.pool, TEST_USERNAME).await.unwrap().unwrap();
+    let runner = FlakyRunner { failures: 1, runs: AtomicUsize::new(0) };
+
+    let id = jobs::enqueue(&db.pool, Some(user.id), "flaky", &json!({}), 3, clock.now()).await.unwrap();
+    assert_eq!(run_next_job(&db.pool, &runner, &clock).await.unwrap(), Some(id));
+
+    let job = jobs::find_for_user(&db.pool, id, user.id).await.unwrap().unwrap();
+    assert_eq!((job.status.as_str(), job.attempts), ("queued", 1));
+    assert_eq!(job.last_error.as_deref(), Some("simulated failure"));
+
+    // Not due again until the backoff has passed
+    assert_eq!(run_next_job(&db.pool, &runner, &clock).await.unwrap(), None);
+    clock.advance(retry_delay(1));
+    assert_eq!(run_next_job(&db.pool, &runner, &clock).await.unwrap(), Some(id));
+
+    let job = jobs::find_for_user(&db.pool, id, user.id).await.unwrap().unwrap();
+    assert_eq!((job.status.as_str(), job.attempts), ("succeeded", 2));
+    assert_eq!(job.result.as_deref(), Some(r#"{"done":true}"#));
+}
+
+#[actix_web::test]
+async fn jobs_out_of_attempts_are_marked_failed() {
+    let db = TestDb::new().await;
+    let user = users::find_by_username(&db.pool, TEST_USERNAME).await.unwrap().unwrap();
+    let runner = FlakyRunner { failures: usize::MAX, runs: AtomicUsize::new(0) };
+
+    let id = jobs::enqueue(&db.pool, Some(user.id), "flaky", &json!({}), 1, Utc::now()).await.unwrap();
+    run_next_job(&db.pool, &runner, &SystemClock).await.unwrap();
+
+    let job = jobs::find_for_user(&db.pool, id, user.id).await.unwrap().unwrap();
+    assert_eq!(job.status, "failed");
+    assert!(job.finished_at.is_some());
+}
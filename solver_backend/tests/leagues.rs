@@ -0,0 +1,159 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{test, Error};
+use chrono::Utc;
+use common::{access_token, get_json, init_app, login, post_json, register, TestDb, TEST_PASSWORD};
+use serde_json::{json, Value};
+use wordle_solver::repositories::answers;
+
+async fn send<S, B>(app: &S, method: Method, uri: &str, token: &str) -> StatusCode
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let request = test::TestRequest::default().method(method).uri(uri).insert_header(("Authorization", format!("Bearer {}", token)));
+    test::call_service(app, request.to_request()).await.status()
+}
+
+// Registers and logs in, returning the access token
+async fn player<S, B>(app: &S, username: &str) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (status, _) = register(app, username, &format!("{}@example.com", username), TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, tokens) = login(app, username, TEST_PASSWORD).await;
+    tokens["access"].as_str().unwrap().to_string()
+}
+
+async fn create<S, B>(app: &S, token: &str, body: Value) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let (status, league) = post_json(app, "/api/v1/game/leagues", &body, Some(token)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", league);
+    league
+}
+
+#[actix_web::test]
+async fn members_join_by_invite_code_and_only_the_owner_kicks() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let owner = access_token(&app).await;
+    let rival = player(&app, "rival").await;
+    let rival_id: i32 = sqlx::query_scalar("SELECT id FROM users WHERE username = 'rival'").fetch_one(&db.pool).await.unwrap();
+
+    let league = create(&app, &owner, json!({ "name": "  Third floor " })).await;
+    assert_eq!(league["name"], "Third floor");
+    let uri = format!("/api/v1/game/leagues/{}", league["id"]);
+    // Outsiders can't tell the league exists
+    assert_eq!(get_json(&app, &uri, Some(&rival)).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(get_json(&app, &format!("{}/leaderboard", uri), Some(&rival)).await.0, StatusCode::NOT_FOUND);
+
+    let (status, _) = post_json(&app, "/api/v1/game/leagues/join", &json!({ "invite_code": "not-a-code" }), Some(&rival)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    for _ in 0..2 {
+        let (status, joined) = post_json(&app, "/api/v1/game/leagues/join", &json!({ "invite_code": league["invite_code"] }), Some(&rival)).await;
+        assert_eq!((status, &joined["id"]), (StatusCode::OK, &league["id"]));
+    }
+    let (status, details) = get_json(&app, &uri, Some(&rival)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(details["members"].as_array().unwrap().len(), 2);
+
+    // A member can be in several leagues
+    create(&app, &rival, json!({ "name": "Rival's own" })).await;
+    let (_, mine) = get_json(&app, "/api/v1/game/leagues", Some(&rival)).await;
+    assert_eq!(mine.as_array().unwrap().len(), 2);
+
+    let (status, _) = get_json(&app, &format!("{}/leaderboard", uri), Some(&rival)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(send(&app, Method::DELETE, &format!("{}/members/{}", uri, details["league"]["owner_id"]), &rival).await, StatusCode::FORBIDDEN);
+    assert_eq!(send(&app, Method::POST, &format!("{}/leave", uri), &owner).await, StatusCode::CONFLICT);
+
+    assert_eq!(send(&app, Method::DELETE, &format!("{}/members/{}", uri, rival_id), &owner).await, StatusCode::NO_CONTENT);
+    assert_eq!(get_json(&app, &uri, Some(&rival)).await.0, StatusCode::NOT_FOUND);
+    assert_eq!(send(&app, Method::DELETE, &format!("{}/members/{}", uri, rival_id), &owner).await, StatusCode::NOT_FOUND);
+
+    // Alone now, so leaving deletes the league
+    assert_eq!(send(&app, Method::POST, &format!("{}/leave", uri), &owner).await, StatusCode::NO_CONTENT);
+    let (_, code) = post_json(&app, "/api/v1/game/leagues/join", &json!({ "invite_code": league["invite_code"] }), Some(&rival)).await;
+    assert_eq!(code["error"]["code"], "not_found");
+}
+
+#[actix_web::test]
+async fn each_league_plays_its_own_daily_word() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let today = Utc::now().date_naive();
+
+    let first = create(&app, &token, json!({ "name": "First", "answer_strategy": "scheduled" })).await;
+    let second = create(&app, &token, json!({ "name": "Second", "answer_strategy": "scheduled" })).await;
+    answers::schedule(&db.pool, &format!("league:{}", first["id"]), today, "crane").await.unwrap();
+    answers::schedule(&db.pool, &format!("league:{}", second["id"]), today, "pious").await.unwrap();
+
+    let guess = json!({ "guess": "crane" });
+    let (status, daily) = post_json(&app, &format!("/api/v1/game/leagues/{}/daily/guesses", first["id"]), &guess, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", daily);
+    assert_eq!((&daily["status"], &daily["answer"], &daily["guesses"][0]["feedback"]), (&json!("solved"), &json!("crane"), &json!("ggggg")));
+
+    let (status, daily) = post_json(&app, &format!("/api/v1/game/leagues/{}/daily/guesses", second["id"]), &guess, Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((&daily["status"], &daily["answer"], &daily["guesses"][0]["feedback"]), (&json!("playing"), &Value::Null, &json!("xxxxx")));
+    let (status, body) = post_json(&app, &format!("/api/v1/game/leagues/{}/daily/guesses", second["id"]), &json!({ "guess": "zzzzz" }), Some(&token)).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::UNPROCESSABLE_ENTITY, &json!("invalid_word")));
+
+    let (status, body) = post_json(&app, &format!("/api/v1/game/leagues/{}/daily/guesses", first["id"]), &guess, Some(&token)).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::CONFLICT, &json!("daily_finished")));
+
+    let (status, board) = get_json(&app, &format!("/api/v1/game/leagues/{}/leaderboard", first["id"]), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(board["entries"][0]["day_points"], 6);
+    assert_eq!(board["entries"][0]["total_points"], 6);
+    let (_, board) = get_json(&app, &format!("/api/v1/game/leagues/{}/leaderboard", second["id"]), Some(&token)).await;
+    assert_eq!((&board["entries"][0]["guesses"], &board["entries"][0]["day_points"]), (&json!(1), &json!(0)));
+}
+
+#[actix_web::test]
+async fn finished_dailies_add_up_in_the_word_stats() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let owner = access_token(&app).await;
+    let today = Utc::now().date_naive();
+
+    let league = create(&app, &owner, json!({ "name": "Stats", "answer_strategy": "scheduled" })).await;
+    answers::schedule(&db.pool, &format!("league:{}", league["id"]), today, "crane").await.unwrap();
+    let guesses = format!("/api/v1/game/leagues/{}/daily/guesses", league["id"]);
+
+    // Solved in 1 and in 3, and one player runs out of guesses
+    let plays: [&[&str]; 3] = [&["crane"], &["slate", "trace", "crane"], &["pious"; 6]];
+    for (index, words) in plays.iter().enumerate() {
+        let token = if index == 0 { owner.clone() } else { player(&app, &format!("player{}", index)).await };
+        if index > 0 {
+            let (status, _) = post_json(&app, "/api/v1/game/leagues/join", &json!({ "invite_code": league["invite_code"] }), Some(&token)).await;
+            assert_eq!(status, StatusCode::OK);
+        }
+        let (stats, _) = get_json(&app, "/api/v1/game/words/crane/stats", Some(&token)).await;
+        assert_eq!(stats, StatusCode::OK);
+        for word in words.iter() {
+            let (status, body) = post_json(&app, &guesses, &json!({ "guess": word }), Some(&token)).await;
+            assert_eq!(status, StatusCode::OK, "{}", body);
+        }
+    }
+
+    let (status, stats) = get_json(&app, "/api/v1/game/words/CRANE/stats", Some(&owner)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((&stats["plays"], &stats["failures"], &stats["average_guesses"]), (&json!(3), &json!(1), &json!(2.0)));
+    assert!((stats["failure_rate"].as_f64().unwrap() - 1.0 / 3.0).abs() < 1e-9);
+
+    // A word no game has finished on yet
+    let (_, stats) = get_json(&app, "/api/v1/game/words/slate/stats", Some(&owner)).await;
+    assert_eq!((&stats["plays"], &stats["average_guesses"]), (&json!(0), &Value::Null));
+    assert_eq!(get_json(&app, "/api/v1/game/words/zzzzz/stats", Some(&owner)).await.0, StatusCode::NOT_FOUND);
+}
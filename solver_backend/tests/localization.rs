@@ -0,0 +1,93 @@
+mod common;
+
+use actix_web::http::header::{ACCEPT_LANGUAGE, CONTENT_LANGUAGE};
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, read_body_json, TestRequest};
+use common::{init_app, init_app_with, settings_with, TestDb, TEST_USERNAME};
+use serde_json::{json, Value};
+use wordle_solver::errors::ErrorCode;
+use wordle_solver::utils::locale_utils::{catalog, supported_languages, DEFAULT_LANGUAGE};
+
+#[actix_web::test]
+async fn catalogs_translate_every_error_code() {
+    for language in supported_languages().filter(|language| *language != DEFAULT_LANGUAGE) {
+        let catalog = catalog(language).unwrap();
+        for code in ErrorCode::ALL {
+            assert!(catalog.contains_key(code.as_str()), "{} has no {} message", language, code);
+        }
+        for key in catalog.keys() {
+            assert!(ErrorCode::ALL.iter().any(|code| code.as_str() == *key), "{} translates unknown code {}", language, key);
+        }
+    }
+}
+
+#[actix_web::test]
+async fn error_messages_follow_accept_language() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let failed_login = |uri: &str, accept_language: Option<&str>| {
+        let mut request = TestRequest::post().uri(uri).set_json(json!({ "username": TEST_USERNAME, "password": "wrong password" }));
+        if let Some(accept_language) = accept_language {
+            request = request.insert_header((ACCEPT_LANGUAGE, accept_language));
+        }
+        request.to_request()
+    };
+
+    let mut english = None;
+    // Region subtags and weights are honoured, unknown languages fall back to English
+    for (accept_language, expected) in [
+        (None, "en"),
+        (Some("fr-FR"), "en"),
+        (Some("es;q=0, en"), "en"),
+        (Some("es-MX,en;q=0.5"), "es"),
+        (Some("fr, es;q=0.8, en;q=0.5"), "es"),
+        (Some("en-GB, es;q=0.9"), "en"),
+    ] {
+        let response = call_service(&app, failed_login("/api/v1/users/login", accept_language)).await;
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let language = response.headers().get(CONTENT_LANGUAGE).map(|value| value.to_str().unwrap().to_string());
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["error"]["code"], "invalid_credentials", "{:?}", accept_language);
+
+        let message = body["error"]["message"].as_str().unwrap().to_string();
+        if expected == "es" {
+            assert_eq!(message, "Usuario o contraseña incorrectos", "{:?}", accept_language);
+            assert_eq!(language.as_deref(), Some("es"));
+        } else {
+            assert_eq!(english.get_or_insert_with(|| message.clone()), &message, "{:?}", accept_language);
+            assert_eq!(language, None);
+        }
+    }
+
+    // The unversioned routes get the same treatment
+    let response = call_service(&app, failed_login("/api/users/login", Some("es"))).await;
+    let body: Value = read_body_json(response).await;
+    assert_eq!(body["error"]["message"], "Usuario o contraseña incorrectos");
+}
+
+#[actix_web::test]
+async fn errors_raised_by_middleware_are_translated_too() {
+    let db = TestDb::new().await;
+    let settings = settings_with(&[("RATE_LIMIT_AUTH", "1")]).unwrap();
+    let app = init_app_with(&db, settings).await;
+    let login = || {
+        TestRequest::post()
+            .uri("/api/v1/users/login")
+            .peer_addr("203.0.113.9:40000".parse().unwrap())
+            .insert_header((ACCEPT_LANGUAGE, "es"))
+            .set_json(json!({ "username": TEST_USERNAME, "password": "wrong password" }))
+            .to_request()
+    };
+
+    call_service(&app, login()).await;
+    let response = call_service(&app, login()).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    // Headers the error went out with are kept
+    assert!(response.headers().contains_key("x-ratelimit-reset"));
+    assert!(response.headers().contains_key("retry-after"));
+    let body: Value = read_body_json(response).await;
+    assert_eq!(body["error"]["code"], "too_many_requests");
+    assert_eq!(body["error"]["message"], "Demasiadas peticiones, inténtalo de nuevo más tarde");
+    // Details aren't translated, they're for programs
+    assert_eq!(body["error"]["details"]["limit"], 1);
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{test, web, App, Error};
+use common::{access_token, get_json, login, post_json, register, settings, settings_with, state, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::{json, Value};
+use wordle_solver::config::Settings;
+use wordle_solver::handlers::api_routes;
+use wordle_solver::handlers::health::health_routes;
+
+async fn app_with_health(db: &TestDb, settings: Settings) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    test::init_service(App::new().app_data(web::Data::new(state(db, settings))).configure(api_routes(false)).configure(health_routes)).await
+}
+
+// The status, the Retry-After header and the decoded body
+async fn send<S, B>(app: &S, method: Method, uri: &str, token: &str, body: Value) -> (StatusCode, Option<String>, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let request = test::TestRequest::default().method(method).uri(uri).insert_header(("Authorization", format!("Bearer {}", token))).set_json(body);
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let retry_after = response.headers().get("Retry-After").map(|value| value.to_str().unwrap().to_string());
+    let body = test::read_body(response).await;
+    (status, retry_after, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+async fn set_flag<S, B>(app: &S, admin: &str, name: &str, enabled: bool) -> (StatusCode, Option<String>, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    send(app, Method::PUT, &format!("/api/v1/admin/flags/{}", name), admin, json!({ "enabled": enabled })).await
+}
+
+#[actix_web::test]
+async fn maintenance_refuses_writes_and_lockdown_everything() {
+    let db = TestDb::new().await;
+    let app = app_with_health(&db, settings()).await;
+    let player = access_token(&app).await;
+    register(&app, "referee", "referee@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'referee'").execute(&db.pool).await.unwrap();
+    let (_, tokens) = login(&app, "referee", TEST_PASSWORD).await;
+    let admin = tokens["access"].as_str().unwrap().to_string();
+
+    let (_, health) = get_json(&app, "/health/ready", None).await;
+    assert_eq!(health["maintenance"], "off");
+    let (status, _, _) = set_flag(&app, &admin, "maintenance", true).await;
+    assert_eq!(status, StatusCode::OK);
+    // Shown, but not a failed check
+    let (_, health) = get_json(&app, "/health/ready", None).await;
+    assert_eq!(health["maintenance"], "read_only");
+    assert!(health["checks"].get("maintenance").is_none());
+
+    // Writes are refused whatever the method, reads go through
+    let (status, retry_after, body) = send(&app, Method::POST, "/api/v1/game/sessions", &player, json!({})).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(retry_after.as_deref(), Some("300"));
+    assert_eq!(body["error"]["code"], "maintenance");
+    assert_eq!(body["error"]["details"]["level"], "read_only");
+    for (method, uri) in [(Method::PUT, "/api/v1/users/me/preferences"), (Method::PATCH, "/api/v1/game/sessions/1"), (Method::DELETE, "/api/v1/users/1")] {
+        let (status, _, body) = send(&app, method.clone(), uri, &player, json!({})).await;
+        assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::SERVICE_UNAVAILABLE, Some("maintenance")), "{} {}", method, uri);
+    }
+    let (status, _) = get_json(&app, "/api/v1/game/sessions/stats", Some(&player)).await;
+    assert_eq!(status, StatusCode::OK);
+    // Logging in and the admin routes keep working, or maintenance couldn't be ended
+    let (status, _) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _, _) = send(&app, Method::POST, "/api/v1/admin/word-suggestions/999/reject", &admin, json!({})).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, _) = set_flag(&app, &admin, "maintenance_lockdown", true).await;
+    assert_eq!(status, StatusCode::OK);
+    let (_, health) = get_json(&app, "/health/ready", None).await;
+    assert_eq!(health["maintenance"], "lockdown");
+    let (status, body) = get_json(&app, "/api/v1/game/sessions/stats", Some(&player)).await;
+    assert_eq!((status, &body["error"]["details"]["level"]), (StatusCode::SERVICE_UNAVAILABLE, &json!("lockdown")));
+    let (status, _) = get_json(&app, "/api/v1/admin/summary", Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+
+    set_flag(&app, &admin, "maintenance_lockdown", false).await;
+    set_flag(&app, &admin, "maintenance", false).await;
+    let (status, _) = post_json(&app, "/api/v1/game/sessions", &json!({}), Some(&player)).await;
+    assert_eq!(status, StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn maintenance_can_be_on_from_the_start() {
+    let db = TestDb::new().await;
+    let app = app_with_health(&db, settings_with(&[("FEATURE_MAINTENANCE", "true"), ("MAINTENANCE_RETRY_AFTER_SECONDS", "60")]).unwrap()).await;
+
+    let (_, health) = get_json(&app, "/health/ready", None).await;
+    assert_eq!(health["maintenance"], "read_only");
+    let (status, body) = register(&app, "player", "player@example.com", TEST_PASSWORD).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::SERVICE_UNAVAILABLE, Some("maintenance")));
+    let (status, retry_after, _) = send(&app, Method::POST, "/api/v1/game/sessions", "no token", json!({})).await;
+    assert_eq!((status, retry_after.as_deref()), (StatusCode::SERVICE_UNAVAILABLE, Some("60")));
+}
@@ -0,0 +1,235 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use common::{access_token, init_app, post_json, TestDb};
+use serde_json::{json, Map, Value};
+use utoipa::OpenApi;
+use wordle_solver::handlers::docs::ApiDoc;
+use wordle_solver::handlers::examples;
+
+const SCHEMA_PREFIX: &str = "#/components/schemas/";
+
+fn spec() -> Value {
+    serde_json::to_value(ApiDoc::openapi()).unwrap()
+}
+
+// Every JSON body in the doc along with where it's found: "POST /path request" or
+// "POST /path 200"
+fn json_bodies(spec: &Value) -> Vec<(String, &Value)> {
+    let mut bodies = Vec::new();
+    for (route, item) in spec["paths"].as_object().unwrap() {
+        for (method, operation) in item.as_object().unwrap() {
+            let operation_name = format!("{} {}", method.to_uppercase(), route);
+            if let Some(body) = operation["requestBody"]["content"].get("application/json") {
+                bodies.push((format!("{} request", operation_name), body));
+            }
+            for (status, response) in operation["responses"].as_object().unwrap() {
+                if let Some(body) = response["content"].get("application/json") {
+                    bodies.push((format!("{} {}", operation_name, status), body));
+                }
+            }
+        }
+    }
+    bodies
+}
+
+// Checks `value` against the subset of OpenAPI schemas utoipa generates: refs, allOf,
+// nullable, enums and the basic types. Properties the schema doesn't list are refused so
+// a renamed field can't linger in an example.
+fn validate(spec: &Value, schema: &Value, value: &Value, at: &str) -> Result<(), String> {
+    if let Some(reference) = schema["$ref"].as_str() {
+        let name = reference.strip_prefix(SCHEMA_PREFIX).ok_or_else(|| format!("{}: unexpected ref {}", at, reference))?;
+        let target = spec["components"]["schemas"].get(name).ok_or_else(|| format!("{}: missing schema {}", at, name))?;
+        return validate(spec, target, value, at);
+    }
+    if value.is_null() {
+        return if schema["nullable"] == true { Ok(()) } else { Err(format!("{}: null isn't allowed", at)) };
+    }
+    match schema["allOf"].as_array().map(Vec::as_slice) {
+        Some([part]) => return validate(spec, part, value, at),
+        Some(parts) => return validate(spec, &merged(spec, parts), value, at),
+        None => {}
+    }
+    if let Some(allowed) = schema["enum"].as_array() {
+        if !allowed.contains(value) {
+            return Err(format!("{}: {} isn't one of {:?}", at, value, allowed));
+        }
+    }
+
+    match schema["type"].as_str() {
+        Some("object") => {
+            let object = value.as_object().ok_or_else(|| format!("{}: expected an object, got {}", at, value))?;
+            validate_object(spec, schema, object, at)
+        }
+        Some("array") => {
+            let items = value.as_array().ok_or_else(|| format!("{}: expected an array, got {}", at, value))?;
+            items.iter().enumerate().try_for_each(|(index, item)| validate(spec, &schema["items"], item, &format!("{}[{}]", at, index)))
+        }
+        Some("string") => {
+            let string = value.as_str().ok_or_else(|| format!("{}: expected a string, got {}", at, value))?;
+            match schema["format"].as_str() {
+                Some("date-time") if chrono::DateTime::parse_from_rfc3339(string).is_err() => Err(format!("{}: {} isn't a date-time", at, string)),
+                Some("date") if chrono::NaiveDate::parse_from_str(string, "%Y-%m-%d").is_err() => Err(format!("{}: {} isn't a date", at, string)),
+                _ => Ok(()),
+            }
+        }
+        Some("integer") => {
+            let unsigned = schema["minimum"].as_f64().is_some_and(|minimum| minimum >= 0.0);
+            if value.is_u64() || (value.is_i64() && !unsigned) {
+                Ok(())
+            } else {
+                Err(format!("{}: expected {} integer, got {}", at, if unsigned { "a non negative" } else { "an" }, value))
+            }
+        }
+        Some("number") if value.is_number() => Ok(()),
+        Some("boolean") if value.is_boolean() => Ok(()),
+        Some(kind) => Err(format!("{}: expected a {}, got {}", at, kind, value)),
+        None => Ok(()),
+    }
+}
+
+// Flattened structs come out as one allOf part each, every part only listing its own fields
+fn merged(spec: &Value, parts: &[Value]) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for part in parts {
+        let part = match part["$ref"].as_str().and_then(|reference| reference.strip_prefix(SCHEMA_PREFIX)) {
+            Some(name) => &spec["components"]["schemas"][name],
+            None => part,
+        };
+        properties.extend(part["properties"].as_object().cloned().unwrap_or_default());
+        required.extend(part["required"].as_array().cloned().unwrap_or_default());
+    }
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn validate_object(spec: &Value, schema: &Value, object: &Map<String, Value>, at: &str) -> Result<(), String> {
+    for required in schema["required"].as_array().into_iter().flatten() {
+        let field = required.as_str().unwrap();
+        if !object.contains_key(field) {
+            return Err(format!("{}: missing required field {}", at, field));
+        }
+    }
+    for (field, value) in object {
+        let path = format!("{}.{}", at, field);
+        match (schema["properties"].get(field), schema.get("additionalProperties")) {
+            (Some(property), _) => validate(spec, property, value, &path)?,
+            (None, Some(additional)) if additional.is_object() => validate(spec, additional, value, &path)?,
+            (None, _) if schema.get("properties").is_none() => {}
+            (None, _) => return Err(format!("{}: not in the schema", path)),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn every_json_body_has_an_example() {
+    let spec = spec();
+    let missing: Vec<String> = json_bodies(&spec)
+        .into_iter()
+        // Errors are described by ErrorResponse and the error code list
+        .filter(|(location, _)| location.ends_with("request") || location.rsplit(' ').next().unwrap().starts_with('2'))
+        .filter(|(_, body)| body.get("example").is_none())
+        .map(|(location, _)| location)
+        .collect();
+
+    assert!(missing.is_empty(), "bodies without an example: {:#?}", missing);
+}
+
+#[test]
+fn examples_match_their_schemas() {
+    let spec = spec();
+    let mut checked = 0;
+    for (location, body) in json_bodies(&spec) {
+        if let Some(example) = body.get("example") {
+            validate(&spec, &body["schema"], example, &location).unwrap();
+            checked += 1;
+        }
+    }
+    assert!(checked > 40, "only {} examples checked", checked);
+}
+
+// Field names anywhere under `schema`, following refs once each
+fn field_names<'a>(spec: &'a Value, schema: &'a Value, seen: &mut Vec<&'a str>, names: &mut Vec<&'a str>) {
+    if let Some(name) = schema["$ref"].as_str().and_then(|reference| reference.strip_prefix(SCHEMA_PREFIX)) {
+        if !seen.contains(&name) {
+            seen.push(name);
+            field_names(spec, &spec["components"]["schemas"][name], seen, names);
+        }
+        return;
+    }
+    for (name, property) in schema["properties"].as_object().into_iter().flatten() {
+        names.push(name);
+        field_names(spec, property, seen, names);
+    }
+    for part in schema["allOf"].as_array().into_iter().chain(schema["oneOf"].as_array()).flatten() {
+        field_names(spec, part, seen, names);
+    }
+    field_names_of(spec, &schema["items"], seen, names);
+    field_names_of(spec, &schema["additionalProperties"], seen, names);
+}
+
+fn field_names_of<'a>(spec: &'a Value, schema: &'a Value, seen: &mut Vec<&'a str>, names: &mut Vec<&'a str>) {
+    if schema.is_object() {
+        field_names(spec, schema, seen, names);
+    }
+}
+
+// Passwords only ever go in, hashes included
+#[test]
+fn no_response_has_a_password_field() {
+    let spec = spec();
+    for (location, body) in json_bodies(&spec).into_iter().filter(|(location, _)| !location.ends_with("request")) {
+        let mut names = Vec::new();
+        field_names(&spec, &body["schema"], &mut Vec::new(), &mut names);
+        assert!(!names.contains(&"password"), "{} documents a password", location);
+        if let Some(example) = body.get("example") {
+            assert!(!example.to_string().contains("\"password\""), "{} has a password in its example", location);
+        }
+    }
+}
+
+#[test]
+fn the_validator_catches_mistakes() {
+    let spec = spec();
+    let schema = json!({ "$ref": "#/components/schemas/GameSession" });
+
+    let mut session = examples::game_session();
+    validate(&spec, &schema, &session, "session").unwrap();
+
+    session["guesses"][0]["created_at"] = "yesterday".into();
+    assert!(validate(&spec, &schema, &session, "session").is_err());
+
+    let mut session = examples::game_session();
+    session["guesses_made"] = 1.into();
+    assert!(validate(&spec, &schema, &session, "session").is_err());
+
+    let mut session = examples::game_session();
+    session.as_object_mut().unwrap().remove("remaining");
+    assert!(validate(&spec, &schema, &session, "session").is_err());
+
+    let mut session = examples::game_session();
+    session["remaining"] = (-1).into();
+    assert!(validate(&spec, &schema, &session, "session").is_err());
+}
+
+// The solver examples are real answers for the test word list, so a change in how the
+// solver ranks or pages shows up here rather than in a stale doc
+#[actix_web::test]
+async fn solver_examples_are_what_the_api_answers() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let spec = spec();
+
+    for route in ["/api/v1/game/candidates", "/api/v1/game/best-guess", "/api/v1/game/diff", "/api/v1/game/feedback/batch"] {
+        let operation = &spec["paths"][route]["post"];
+        let request = &operation["requestBody"]["content"]["application/json"]["example"];
+        let documented = &operation["responses"]["200"]["content"]["application/json"]["example"];
+
+        let (status, body) = post_json(&app, route, request, Some(&token)).await;
+
+        assert_eq!(status, StatusCode::OK, "{}: {}", route, body);
+        assert_eq!(&body, documented, "{}", route);
+    }
+}
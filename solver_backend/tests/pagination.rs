@@ -0,0 +1,102 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::TestRequest;
+use actix_web::{web, FromRequest, ResponseError};
+use common::{access_token, get_json, init_app, register, settings_with, state, TestDb};
+use serde_json::json;
+use wordle_solver::models::page_models::{CursorPage, Paginated};
+use wordle_solver::utils::pagination_utils::{encode_cursor, CursorPagination, Pagination};
+
+#[test]
+fn paginated_serializes_to_the_documented_shape() {
+    let page = Pagination { page: 2, per_page: 2 }.slice(vec!["crane", "crate", "trace", "react", "caret"]);
+
+    assert_eq!(
+        serde_json::to_value(&page).unwrap(),
+        json!({ "items": ["trace", "react"], "page": 2, "per_page": 2, "total": 5, "total_pages": 3 })
+    );
+}
+
+#[test]
+fn empty_listings_have_no_pages() {
+    let page: Paginated<String> = Pagination { page: 1, per_page: 20 }.page_of(Vec::new(), 0);
+
+    assert_eq!(
+        serde_json::to_value(&page).unwrap(),
+        json!({ "items": [], "page": 1, "per_page": 20, "total": 0, "total_pages": 0 })
+    );
+}
+
+#[test]
+fn cursor_pages_point_at_the_last_item_sent() {
+    let cursor = CursorPagination { before: None, limit: 2 };
+
+    let page: CursorPage<i64> = cursor.page_of(vec![9, 8, 7], |id| *id);
+    assert_eq!(serde_json::to_value(&page).unwrap(), json!({ "items": [9, 8], "next_cursor": encode_cursor(8) }));
+
+    let last: CursorPage<i64> = cursor.page_of(vec![2, 1], |id| *id);
+    assert_eq!(serde_json::to_value(&last).unwrap(), json!({ "items": [2, 1], "next_cursor": null }));
+}
+
+#[actix_web::test]
+async fn out_of_range_pages_are_refused_not_clamped() {
+    let db = TestDb::new().await;
+    let settings = settings_with(&[("DEFAULT_PAGE_SIZE", "2"), ("MAX_PAGE_SIZE", "5")]).unwrap();
+    let data = web::Data::new(state(&db, settings));
+    let extract = |query: &str| {
+        let req = TestRequest::get().uri(&format!("/?{}", query)).app_data(data.clone()).to_http_request();
+        Pagination::extract(&req)
+    };
+
+    assert_eq!(extract("").await.unwrap(), Pagination { page: 1, per_page: 2 });
+    assert_eq!(extract("page=3&per_page=5").await.unwrap(), Pagination { page: 3, per_page: 5 });
+
+    for query in ["per_page=6", "per_page=0", "page=0"] {
+        let error = extract(query).await.unwrap_err();
+        assert_eq!(error.status_code(), StatusCode::UNPROCESSABLE_ENTITY, "{}", query);
+    }
+    assert_eq!(extract("page=-1").await.unwrap_err().status_code(), StatusCode::BAD_REQUEST);
+
+    let req = TestRequest::get().uri("/?cursor=bm90IGFuIGlk&limit=6").app_data(data.clone()).to_http_request();
+    assert_eq!(CursorPagination::extract(&req).await.unwrap_err().status_code(), StatusCode::BAD_REQUEST);
+
+    let req = TestRequest::get().uri(&format!("/?cursor={}&limit=6", encode_cursor(42))).app_data(data.clone()).to_http_request();
+    assert_eq!(CursorPagination::extract(&req).await.unwrap_err().status_code(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let req = TestRequest::get().uri(&format!("/?cursor={}", encode_cursor(42))).app_data(data.clone()).to_http_request();
+    assert_eq!(CursorPagination::extract(&req).await.unwrap(), CursorPagination { before: Some(42), limit: 2 });
+}
+
+#[actix_web::test]
+async fn users_are_listed_a_page_at_a_time() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    for name in ["pageone", "pagetwo"] {
+        register(&app, name, &format!("{}@example.com", name), "a long enough password").await;
+    }
+    let (_, tokens) = register(&app, "pagethree", "pagethree@example.com", "a long enough password").await;
+    let player = tokens["access"].as_str().unwrap().to_string();
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'tester'").execute(&db.pool).await.unwrap();
+    let admin = access_token(&app).await;
+
+    // The listing is for admins only
+    assert_eq!(get_json(&app, "/api/v1/users", None).await.0, StatusCode::UNAUTHORIZED);
+    let (status, body) = get_json(&app, "/api/v1/users", Some(&player)).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::FORBIDDEN, &json!("admin_required")));
+
+    let (status, page) = get_json(&app, "/api/v1/users?page=2&per_page=2", Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK, "{}", page);
+    assert_eq!((page["page"].as_i64(), page["per_page"].as_i64()), (Some(2), Some(2)));
+
+    let total = page["total"].as_i64().unwrap();
+    assert!(total >= 2);
+    assert_eq!(page["total_pages"].as_i64(), Some((total + 1) / 2));
+    assert_eq!(page["items"].as_array().unwrap().len(), (total - 2).clamp(0, 2) as usize);
+    assert!(page["items"].as_array().unwrap().iter().all(|user| user.get("password").is_none()), "{}", page);
+
+    let (status, body) = get_json(&app, "/api/v1/users?per_page=1000", Some(&admin)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "invalid_page_size");
+    assert_eq!(body["error"]["details"]["max"], 100);
+}
@@ -0,0 +1,122 @@
+// Throws arbitrary bytes at everything that parses text a client or user typed. None of it
+// may panic: each input either parses or comes back as a structured error.
+use proptest::prelude::*;
+use wordle_solver::errors::{AppError, ErrorCode};
+use wordle_solver::models::game_models::RequestLetters;
+use wordle_solver::solver::{feedback, parse_feedback, Constraints, FeedbackError};
+use wordle_solver::utils::input_utils::sanitize_request;
+
+// Inputs that are easy to get wrong, checked on every run on top of the generated ones.
+// Anything a generated case ever trips over belongs here too.
+const REGRESSIONS: &[&str] = &[
+    "",
+    " ",
+    "\0",
+    "\u{200B}crane",
+    // Lowercases to two characters, so lengths measured before and after folding differ
+    "\u{130}",
+    "cra\u{301}ne",
+    "сrane",
+    "🟩🟨⬛⬛⬛",
+    "\u{FEFF}_%_%_",
+    "ß",
+    "\u{10FFFF}",
+];
+
+// Mostly arbitrary bytes, read the way a JSON body of them would be. The rest lean on
+// the characters the parsers give meaning to, so inputs that parse turn up too.
+fn text() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::collection::vec(any::<u8>(), 0..32).prop_map(|bytes| String::from_utf8_lossy(&bytes).into_owned()),
+        any::<String>(),
+        "[a-zA-Z_%gyxb \u{200B}\u{130}]{0,12}",
+    ]
+}
+
+fn letters(correct: String, incorrect: String, exact: String) -> RequestLetters {
+    RequestLetters { correct, incorrect, exact, sort_order: None }
+}
+
+// Everything done with a constraint payload before it reaches the database
+fn canonicalize(letters: RequestLetters) -> Result<(), TestCaseError> {
+    match sanitize_request(letters) {
+        Ok(letters) => {
+            for field in [&letters.correct, &letters.incorrect] {
+                prop_assert!(field.chars().all(|letter| letter.is_ascii_lowercase()), "{:?}", field);
+            }
+            prop_assert!(letters.exact.chars().all(|letter| letter.is_ascii_lowercase() || matches!(letter, '_' | '%')), "{:?}", letters.exact);
+
+            let constraints = letters.constraints();
+            if let Some(letter) = letters.contradiction() {
+                prop_assert!(letters.incorrect.contains(letter));
+            }
+            constraints.allows(&letters.correct);
+            constraints.hard_mode_allows(&letters.exact);
+        }
+        Err(AppError::Validation(info)) => prop_assert_eq!(info.code, ErrorCode::InvalidLetter),
+        Err(other) => prop_assert!(false, "unexpected error {:?}", other),
+    }
+    Ok(())
+}
+
+// A run of guesses as the CLI takes them, each word with the marks typed for it
+fn derive(turns: &[(String, String)], words: &[String]) -> Result<(), TestCaseError> {
+    let mut constraints = Constraints::default();
+
+    for (guess, pattern) in turns {
+        let marks = match parse_feedback(pattern) {
+            Ok(marks) => marks,
+            Err(error) => {
+                prop_assert!(matches!(error, FeedbackError::UnknownMark(_)), "{:?}", error);
+                continue;
+            }
+        };
+        prop_assert_eq!(marks.len(), pattern.chars().count());
+
+        if let Err(error) = constraints.apply(guess, &marks) {
+            prop_assert!(matches!(error, FeedbackError::LengthMismatch { .. }), "{:?}", error);
+        }
+    }
+
+    for word in words {
+        constraints.allows(word);
+        constraints.hard_mode_allows(word);
+    }
+    Ok(())
+}
+
+proptest! {
+    // A fixed number of cases per run, enough to reach the odd paths while `cargo test`
+    // stays quick
+    #![proptest_config(ProptestConfig::with_cases(1024))]
+
+    #[test]
+    fn constraint_payloads_canonicalize_or_are_rejected(correct in text(), incorrect in text(), exact in text()) {
+        canonicalize(letters(correct, incorrect, exact))?;
+    }
+
+    #[test]
+    fn feedback_derivation_never_panics(
+        turns in prop::collection::vec((text(), text()), 0..6),
+        words in prop::collection::vec(text(), 0..4),
+    ) {
+        derive(&turns, &words)?;
+    }
+
+    #[test]
+    fn marking_arbitrary_words_never_panics(guess in text(), answer in text()) {
+        let marks = feedback(&guess, &answer);
+        prop_assert_eq!(marks.len(), guess.to_lowercase().chars().count());
+    }
+}
+
+#[test]
+fn regressions_still_parse_or_fail_cleanly() {
+    for &input in REGRESSIONS {
+        let input = input.to_string();
+        canonicalize(letters(input.clone(), input.clone(), input.clone())).unwrap();
+        derive(&[(input.clone(), input.clone()), ("crane".to_string(), input.clone())], std::slice::from_ref(&input)).unwrap();
+        feedback(&input, "crane");
+        feedback("crane", &input);
+    }
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header::RETRY_AFTER;
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, read_body_json, TestRequest};
+use chrono::Utc;
+use common::{init_app_with, settings_with, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::{json, Value};
+
+fn header(response: &ServiceResponse<impl actix_web::body::MessageBody>, name: &str) -> Option<i64> {
+    response.headers().get(name).map(|value| value.to_str().unwrap().parse().unwrap())
+}
+
+#[actix_web::test]
+async fn responses_carry_the_callers_remaining_budget() {
+    let db = TestDb::new().await;
+    let settings = settings_with(&[("RATE_LIMIT_AUTH", "3"), ("RATE_LIMIT_WINDOW_SECONDS", "60")]).unwrap();
+    let app = init_app_with(&db, settings).await;
+    let login = |peer: &str, password: &str| {
+        TestRequest::post()
+            .uri("/api/v1/users/login")
+            .peer_addr(format!("{}:40000", peer).parse().unwrap())
+            .set_json(json!({ "username": TEST_USERNAME, "password": password }))
+            .to_request()
+    };
+
+    let started = Utc::now().timestamp();
+    let mut resets = Vec::new();
+    // Failed logins count the same as successful ones
+    for (password, remaining) in [(TEST_PASSWORD, 2), ("wrong password", 1), (TEST_PASSWORD, 0)] {
+        let response = call_service(&app, login("203.0.113.9", password)).await;
+        assert_ne!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(header(&response, "x-ratelimit-limit"), Some(3));
+        assert_eq!(header(&response, "x-ratelimit-remaining"), Some(remaining));
+        resets.push(header(&response, "x-ratelimit-reset").unwrap());
+    }
+    // One window, so one reset time, no sooner than now and no later than a window away,
+    // give or take the rounding up to a whole second
+    assert!(resets.iter().all(|reset| *reset == resets[0]), "{:?}", resets);
+    assert!(resets[0] >= started && resets[0] <= Utc::now().timestamp() + 61, "{:?}", resets);
+
+    let response = call_service(&app, login("203.0.113.9", TEST_PASSWORD)).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&response, "x-ratelimit-remaining"), Some(0));
+    assert_eq!(header(&response, "x-ratelimit-reset"), Some(resets[0]));
+    let retry_after = header(&response, RETRY_AFTER.as_str()).unwrap();
+    assert!((1..=60).contains(&retry_after), "{}", retry_after);
+    let body: Value = read_body_json(response).await;
+    assert_eq!(body["error"]["code"], "too_many_requests");
+
+    // Another client has a budget of its own
+    let response = call_service(&app, login("198.51.100.1", TEST_PASSWORD)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "x-ratelimit-remaining"), Some(2));
+
+    // Routes outside a rate limited group say nothing about it
+    let request = TestRequest::get().uri("/api/v1/users").peer_addr("203.0.113.9:40000".parse().unwrap()).to_request();
+    let response = call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(response.headers().get("x-ratelimit-limit").is_none());
+}
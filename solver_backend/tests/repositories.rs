@@ -0,0 +1,124 @@
+mod common;
+
+use chrono::{Duration, NaiveDate, TimeZone, Utc};
+use common::{TestDb, TEST_EMAIL, TEST_USERNAME, TEST_WORDS};
+use wordle_solver::repositories::{answers, tokens, users, words};
+use wordle_solver::solver::Constraints;
+use wordle_solver::utils::answer_utils::{answer_for, NoRepeat, Scheduled, Uniform};
+use wordle_solver::utils::device_utils::ClientInfo;
+
+#[actix_web::test]
+async fn inserted_users_are_found_by_username() {
+    let db = TestDb::new().await;
+    let now = Utc.with_ymd_and_hms(2023, 6, 1, 12, 0, 0).unwrap();
+
+    let id = users::insert_user(&db.pool, "newplayer", "newplayer@example.com", "hash", now).await.unwrap();
+    let user = users::find_by_username(&db.pool, "newplayer").await.unwrap().expect("user not found");
+
+    assert_eq!((user.id, user.email.as_str(), user.created_at), (id, "newplayer@example.com", now));
+    assert!(users::find_by_username(&db.pool, "nobody").await.unwrap().is_none());
+}
+
+#[actix_web::test]
+async fn credentials_prefer_a_username_over_an_email() {
+    let db = TestDb::new().await;
+    // Someone registered the seeded user's email address as their username
+    let impostor = users::insert_user(&db.pool, TEST_EMAIL, "impostor@example.com", "hash", Utc::now()).await.unwrap();
+
+    let (id, _, _) = users::find_credentials(&db.pool, TEST_EMAIL, TEST_EMAIL).await.unwrap().unwrap();
+    assert_eq!(id, impostor);
+
+    let (id, _, _) = users::find_credentials(&db.pool, TEST_USERNAME, "unused").await.unwrap().unwrap();
+    assert_ne!(id, impostor);
+}
+
+#[actix_web::test]
+async fn password_history_keeps_the_newest_entries() {
+    let db = TestDb::new().await;
+    let user = users::find_by_username(&db.pool, TEST_USERNAME).await.unwrap().unwrap();
+    let (_, current, _) = users::find_credentials(&db.pool, TEST_USERNAME, "unused").await.unwrap().unwrap();
+
+    let mut tx = db.pool.begin().await.unwrap();
+    for hash in ["first", "second", "third"] {
+        users::record_password(&mut tx, user.id, hash, 2).await.unwrap();
+    }
+    tx.commit().await.unwrap();
+
+    let hashes = users::recent_password_hashes(&db.pool, user.id, 2).await.unwrap();
+    assert_eq!(hashes, vec![current, "third".to_string(), "second".to_string()]);
+}
+
+#[actix_web::test]
+async fn token_families_are_revoked_together() {
+    let db = TestDb::new().await;
+    let user = users::find_by_username(&db.pool, TEST_USERNAME).await.unwrap().unwrap();
+    let client = ClientInfo { user_agent: None, ip_address: None, device_label: "test".to_string(), fingerprint: "f".to_string() };
+    let now = Utc::now();
+
+    let mut conn = db.pool.acquire().await.unwrap();
+    let first = tokens::insert_refresh_token(&mut conn, user.id, None, "refresh-1", "access-1", &client).await.unwrap();
+    let second = tokens::insert_refresh_token(&mut conn, user.id, Some(first), "refresh-2", "access-2", &client).await.unwrap();
+    tokens::mark_rotated(&mut conn, first, second, now).await.unwrap();
+
+    assert_eq!(tokens::live_successor(&mut conn, second).await.unwrap().map(|token| token.token), Some("refresh-2".to_string()));
+    assert!(tokens::device_seen(&mut conn, user.id, "f").await.unwrap());
+
+    let mut revoked = tokens::revoke_family(&mut conn, first, now + Duration::seconds(1)).await.unwrap();
+    revoked.sort();
+    assert_eq!(revoked, vec!["access-1", "access-2", "refresh-1", "refresh-2"]);
+    assert!(tokens::live_successor(&mut conn, second).await.unwrap().is_none());
+    assert!(tokens::active_sessions(&mut conn, user.id, now, 10, 0).await.unwrap().is_empty());
+    assert_eq!(tokens::count_active_sessions(&mut conn, user.id, now).await.unwrap(), 0);
+}
+
+#[actix_web::test]
+async fn words_are_filtered_by_pattern_and_constraints() {
+    let db = TestDb::new().await;
+    let mut constraints = Constraints::default();
+    constraints.require('t');
+
+    let mut found = words::filter_words(&db.pool, "_r___", &constraints).await.unwrap();
+    found.sort();
+
+    assert_eq!(found, vec!["crate", "trace"]);
+}
+
+#[actix_web::test]
+async fn no_repeat_answers_stay_out_for_the_whole_window() {
+    let db = TestDb::new().await;
+    let words = words::DbWords::new(db.pool.clone());
+    // One day short of the word count, so each day has exactly one word left to pick
+    let selector = NoRepeat { days: TEST_WORDS.len() as u32 - 1 };
+    let first = NaiveDate::from_ymd_opt(2023, 8, 1).unwrap();
+
+    let mut picked = Vec::new();
+    for offset in 0..TEST_WORDS.len() as i64 * 3 {
+        let day = first + Duration::days(offset);
+        let word = answer_for(&db.pool, &words, &selector, "daily", day, Utc::now()).await.unwrap().unwrap();
+        // Asking again for the same day never picks anew
+        assert_eq!(answer_for(&db.pool, &words, &selector, "daily", day, Utc::now()).await.unwrap(), Some(word.clone()));
+        picked.push(word);
+    }
+
+    for window in picked.windows(TEST_WORDS.len()) {
+        let mut distinct = window.to_vec();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), window.len(), "a word came back within the window: {:?}", window);
+    }
+}
+
+#[actix_web::test]
+async fn scheduled_answers_fall_back_on_unscheduled_days() {
+    let db = TestDb::new().await;
+    let words = words::DbWords::new(db.pool.clone());
+    let selector = Scheduled { fallback: Box::new(Uniform) };
+    let day = NaiveDate::from_ymd_opt(2023, 8, 1).unwrap();
+    answers::schedule(&db.pool, "daily", day, "adieu").await.unwrap();
+    // Not in the word list, so it isn't served
+    answers::schedule(&db.pool, "daily", day.succ_opt().unwrap(), "zzzzz").await.unwrap();
+
+    assert_eq!(answer_for(&db.pool, &words, &selector, "daily", day, Utc::now()).await.unwrap().as_deref(), Some("adieu"));
+    let fallback = answer_for(&db.pool, &words, &selector, "daily", day.succ_opt().unwrap(), Utc::now()).await.unwrap().unwrap();
+    assert!(TEST_WORDS.contains(&fallback.as_str()));
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{Method, StatusCode};
+use actix_web::{test, Error};
+use common::{access_token, init_app, TestDb, TEST_EMAIL, TEST_USERNAME};
+use serde_json::{json, Value};
+
+// The status, the Deprecation and Link headers, and the decoded body
+async fn send<S, B>(app: &S, method: Method, uri: &str, token: &str, body: Option<&Value>) -> (StatusCode, Option<String>, Option<String>, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let mut request = test::TestRequest::default().method(method).uri(uri).insert_header(("Authorization", format!("Bearer {}", token)));
+    if let Some(body) = body {
+        request = request.set_json(body);
+    }
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status();
+    let header = |name: &str| response.headers().get(name).map(|value| value.to_str().unwrap().to_string());
+    let (deprecation, link) = (header("deprecation"), header("link"));
+    let body = test::read_body(response).await;
+    (status, deprecation, link, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+#[actix_web::test]
+async fn legacy_paths_reach_the_same_handlers_marked_deprecated() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "cr___" });
+    let id: i32 = sqlx::query_scalar("SELECT id FROM users WHERE username = $1").bind(TEST_USERNAME).fetch_one(&db.pool).await.unwrap();
+
+    let current = send(&app, Method::POST, "/api/v1/game/candidates", &token, Some(&letters)).await;
+    let legacy = send(&app, Method::POST, "/api/v1/game/general-letters", &token, Some(&letters)).await;
+    assert_eq!(current.0, StatusCode::OK);
+    assert_eq!((current.1, current.2), (None, None));
+    assert_eq!((legacy.0, &legacy.3), (current.0, &current.3));
+    assert_eq!(legacy.1.as_deref(), Some("true"));
+    assert_eq!(legacy.2.as_deref(), Some("</api/v1/game/candidates>; rel=\"successor-version\""));
+
+    let routes = [(Method::PUT, format!("/api/v1/users/{}", id)), (Method::PATCH, format!("/api/v1/users/{}", id)), (Method::PUT, format!("/api/v1/users/update/{}", id))];
+    for (version, (method, uri)) in (1..).zip(routes) {
+        let profile = json!({ "username": TEST_USERNAME, "email": TEST_EMAIL, "version": version });
+        let (status, _, _, body) = send(&app, method, &uri, &token, Some(&profile)).await;
+        assert_eq!((status, &body["version"]), (StatusCode::OK, &json!(version + 1)), "{}", uri);
+    }
+
+    // Under the unversioned prefix the successor is the versioned, renamed route
+    let (status, deprecation, link, _) = send(&app, Method::DELETE, &format!("/api/users/delete/{}", id), &token, None).await;
+    assert_eq!((status, deprecation.as_deref()), (StatusCode::OK, Some("true")));
+    assert_eq!(link.unwrap(), format!("</api/v1/users/{}>; rel=\"successor-version\"", id));
+    // The account is gone, so its token is refused on the current path too
+    let (status, _, _, _) = send(&app, Method::DELETE, &format!("/api/v1/users/{}", id), &token, None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn trailing_slashes_make_no_difference() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = $1").bind(TEST_USERNAME).execute(&db.pool).await.unwrap();
+    let token = access_token(&app).await;
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "cr___" });
+
+    let (status, _, _, users) = send(&app, Method::GET, "/api/v1/users", &token, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(send(&app, Method::GET, "/api/v1/users/", &token, None).await.3, users);
+
+    let (status, _, _, words) = send(&app, Method::POST, "/api/v1/game/candidates", &token, Some(&letters)).await;
+    assert_eq!(status, StatusCode::OK);
+    for uri in ["/api/v1/game/candidates/", "/api/v1//game/candidates", "/api/v1/game/general-letters/"] {
+        let (status, _, _, body) = send(&app, Method::POST, uri, &token, Some(&letters)).await;
+        assert_eq!((status, &body), (StatusCode::OK, &words), "{}", uri);
+    }
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header::{
+    HeaderMap, CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY, X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+};
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{web, App, Error, HttpResponse};
+use common::{access_token, settings_with, state, TestDb};
+use wordle_solver::handlers::api_routes;
+use wordle_solver::handlers::docs::docs_routes;
+use wordle_solver::handlers::health::health_routes;
+use wordle_solver::handlers::metrics::metrics_routes;
+use wordle_solver::middleware::security_headers::SecurityHeaders;
+
+async fn app_with(db: &TestDb, overrides: &[(&str, &str)]) -> impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error> {
+    let settings = settings_with(overrides).unwrap();
+    let security_headers = SecurityHeaders::new(&settings.security_headers);
+
+    init_service(
+        App::new()
+            .app_data(web::Data::new(state(db, settings)))
+            .configure(api_routes(false))
+            .configure(health_routes)
+            .configure(metrics_routes)
+            .configure(docs_routes)
+            // Stands in for a handler that needs a looser policy than the default
+            .route("/embeddable", web::get().to(|| async {
+                HttpResponse::Ok().insert_header((X_FRAME_OPTIONS, "SAMEORIGIN")).finish()
+            }))
+            .wrap(security_headers),
+    )
+    .await
+}
+
+async fn headers_of(app: &impl Service<Request, Response = ServiceResponse<impl MessageBody>, Error = Error>, uri: &str) -> HeaderMap {
+    let response = call_service(app, TestRequest::get().uri(uri).to_request()).await;
+    assert!(response.status().is_success(), "{} returned {}", uri, response.status());
+    response.headers().clone()
+}
+
+#[actix_web::test]
+async fn api_responses_carry_the_security_headers() {
+    let db = TestDb::new().await;
+    let app = app_with(&db, &[]).await;
+    let token = access_token(&app).await;
+
+    let request = TestRequest::get()
+        .uri("/api/v1/users/me/sessions")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let response = call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let headers = response.headers();
+    assert_eq!(headers.get(X_CONTENT_TYPE_OPTIONS).unwrap(), "nosniff");
+    assert_eq!(headers.get(X_FRAME_OPTIONS).unwrap(), "DENY");
+    assert_eq!(headers.get(REFERRER_POLICY).unwrap(), "no-referrer");
+    assert_eq!(headers.get(CONTENT_SECURITY_POLICY).unwrap(), "default-src 'none'; frame-ancestors 'none'");
+    // Neither TLS here nor a trusted proxy in front
+    assert!(headers.get(STRICT_TRANSPORT_SECURITY).is_none());
+
+    // Errors are responses browsers render too
+    let response = call_service(&app, TestRequest::get().uri("/api/v1/users/me/sessions").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers().get(X_CONTENT_TYPE_OPTIONS).unwrap(), "nosniff");
+}
+
+#[actix_web::test]
+async fn routes_get_their_own_policy_and_probes_get_none() {
+    let db = TestDb::new().await;
+    let app = app_with(&db, &[("REFERRER_POLICY", "same-origin"), ("CONTENT_SECURITY_POLICY_ROUTES", "/embeddable=frame-ancestors 'self'")]).await;
+
+    let docs = headers_of(&app, "/docs/").await;
+    let policy = docs.get(CONTENT_SECURITY_POLICY).unwrap().to_str().unwrap();
+    assert!(policy.starts_with("default-src 'self'; script-src 'self'"), "{}", policy);
+    assert_eq!(docs.get(REFERRER_POLICY).unwrap(), "same-origin");
+
+    let embeddable = headers_of(&app, "/embeddable").await;
+    assert_eq!(embeddable.get(CONTENT_SECURITY_POLICY).unwrap(), "frame-ancestors 'self'");
+    // Set by the handler, so left alone
+    assert_eq!(embeddable.get(X_FRAME_OPTIONS).unwrap(), "SAMEORIGIN");
+
+    for probe in ["/health/live", "/metrics"] {
+        let headers = headers_of(&app, probe).await;
+        assert!(headers.get(X_CONTENT_TYPE_OPTIONS).is_none(), "{} has security headers", probe);
+        assert!(headers.get(CONTENT_SECURITY_POLICY).is_none(), "{} has a policy", probe);
+    }
+}
+
+#[actix_web::test]
+async fn hsts_is_only_sent_behind_a_trusted_proxy_or_over_tls() {
+    let db = TestDb::new().await;
+
+    let app = app_with(&db, &[("TRUSTED_PROXY", "true"), ("HSTS_INCLUDE_SUBDOMAINS", "true")]).await;
+    let headers = headers_of(&app, "/docs/").await;
+    assert_eq!(headers.get(STRICT_TRANSPORT_SECURITY).unwrap(), "max-age=31536000; includeSubDomains");
+
+    // A plain connection to our own listener isn't HTTPS for the client
+    let app = app_with(&db, &[("TLS_CERT_DIR", "/etc/letsencrypt/live/wordle.example.com")]).await;
+    assert!(headers_of(&app, "/docs/").await.get(STRICT_TRANSPORT_SECURITY).is_none());
+
+    let app = app_with(&db, &[("TRUSTED_PROXY", "true"), ("HSTS_MAX_AGE_SECONDS", "0")]).await;
+    assert!(headers_of(&app, "/docs/").await.get(STRICT_TRANSPORT_SECURITY).is_none());
+}
+
+#[test]
+fn invalid_header_settings_are_reported() {
+    let error = settings_with(&[("CONTENT_SECURITY_POLICY_ROUTES", "docs=default-src 'self'")]).err().unwrap();
+    assert!(error.to_string().contains("must look like /prefix=policy"), "{}", error);
+
+    let error = settings_with(&[("REFERRER_POLICY", "no-referrer\nX-Injected: 1")]).err().unwrap();
+    assert!(error.to_string().contains("REFERRER_POLICY has an invalid header value"), "{}", error);
+
+    let settings = settings_with(&[("X_FRAME_OPTIONS", "off")]).unwrap();
+    assert!(settings.security_headers.frame_options.is_none());
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use chrono::Utc;
+use common::{init_app_with, login, post_json, register, settings_with, TestDb, TEST_USERNAME};
+use serde_json::json;
+use wordle_solver::config::{Storage, MEMORY_DATABASE_URL};
+use wordle_solver::repositories::words;
+use wordle_solver::utils::bcrypt_utils::verify_password;
+use wordle_solver::utils::db_utils::open_memory_database;
+use wordle_solver::utils::seed_utils::{seed, SEED_USERS};
+
+#[actix_web::test]
+async fn seeding_refuses_real_users_and_runs_only_once() {
+    let db = TestDb::new().await;
+
+    // The test database already has the tester account
+    let error = seed(&db.pool, 4, Utc::now(), false).await.err().unwrap();
+    assert!(error.contains("1 user(s)"), "{}", error);
+    assert!(error.contains("--force"), "{}", error);
+
+    let report = seed(&db.pool, 4, Utc::now(), true).await.unwrap();
+    assert_eq!(report.users_created, ["admin", "alice", "bob"]);
+    // The test words are part of the bundled list and aren't added twice
+    let size = words::count_words(&db.pool).await.unwrap();
+    assert_eq!(size, report.words_added as i64 + 7);
+
+    let (role, verified, hashed): (String, bool, String) = sqlx::query_as("SELECT role, email_verified, password FROM users WHERE username = 'admin'")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(role, "admin");
+    assert!(verified);
+    assert!(verify_password(SEED_USERS[0].password, &hashed));
+
+    // Only seed accounts besides the real one, nothing is left to add
+    sqlx::query("DELETE FROM users WHERE username = $1").bind(TEST_USERNAME).execute(&db.pool).await.unwrap();
+    let report = seed(&db.pool, 4, Utc::now(), false).await.unwrap();
+    assert_eq!(report.words_added, 0);
+    assert!(report.users_created.is_empty());
+    assert_eq!(words::count_words(&db.pool).await.unwrap(), size);
+}
+
+#[actix_web::test]
+async fn memory_storage_serves_without_a_database_server() {
+    let mut vars: std::collections::HashMap<String, String> = [("STORAGE", "memory"), ("SECRET_KEY", "secret")]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    // DATABASE_URL isn't needed, and ignored when it's there
+    let settings = wordle_solver::config::Settings::from_vars(&vars).unwrap();
+    assert_eq!(settings.storage, Storage::Memory);
+    vars.insert("DATABASE_URL".to_string(), "postgres://unused".to_string());
+    assert_eq!(wordle_solver::config::Settings::from_vars(&vars).unwrap().database_url, MEMORY_DATABASE_URL);
+    assert!(settings_with(&[("STORAGE", "files")]).is_err());
+
+    let settings = settings_with(&[("STORAGE", "memory")]).unwrap();
+    let db = TestDb::from_pool(open_memory_database(&settings).await.unwrap());
+    // Holds the bundled list and no accounts
+    let bundled = include_str!("../data/words.txt").lines().filter(|word| !word.trim().is_empty()).count();
+    assert_eq!(words::count_words(&db.pool).await.unwrap(), bundled as i64);
+    let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users").fetch_one(&db.pool).await.unwrap();
+    assert_eq!(users, 0);
+
+    let app = init_app_with(&db, settings).await;
+    let (status, body) = register(&app, "player", "player@example.com", "a long enough password").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+    let (status, tokens) = login(&app, "player", "a long enough password").await;
+    assert_eq!(status, StatusCode::OK, "{}", tokens);
+
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "cran_" });
+    let (status, words) = post_json(&app, "/api/v1/game/candidates", &letters, tokens["access"].as_str()).await;
+    assert_eq!(status, StatusCode::OK, "{}", words);
+    assert_eq!(words["items"], json!(["crane"]));
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use actix_web::{test, web, App, HttpRequest, HttpResponse};
+use common::{settings, state, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wordle_solver::handlers::api_routes;
+use wordle_solver::middleware::server_timing::{RequestTimings, ServerTiming, SERVER_TIMING_HEADER};
+
+// Collects everything logged while it's the default subscriber
+#[derive(Clone, Default)]
+struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+impl CapturedLogs {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).to_string()
+    }
+}
+
+impl Write for CapturedLogs {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn slow_handler(req: HttpRequest) -> HttpResponse {
+    RequestTimings::of(&req)
+        .measure("work", actix_web::rt::time::sleep(Duration::from_millis(150)))
+        .await;
+    HttpResponse::Ok().finish()
+}
+
+async fn fast_handler() -> HttpResponse {
+    HttpResponse::Ok().finish()
+}
+
+// `name;dur=<milliseconds>` entries, in the order they were sent
+fn phases(header: &str) -> Vec<(String, f64)> {
+    header
+        .split(", ")
+        .map(|entry| {
+            let (name, duration) = entry.split_once(";dur=").expect("entry has no duration");
+            (name.to_string(), duration.parse().expect("duration is not a number"))
+        })
+        .collect()
+}
+
+#[actix_web::test]
+async fn login_reports_its_phases() {
+    let db = TestDb::new().await;
+    let settings = settings();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state(&db, settings)))
+            .configure(api_routes(false))
+            .wrap(ServerTiming::new(Duration::ZERO, true)),
+    )
+    .await;
+
+    let request = test::TestRequest::post()
+        .uri("/api/v1/users/login")
+        .set_json(serde_json::json!({ "username": TEST_USERNAME, "password": TEST_PASSWORD }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert!(response.status().is_success());
+
+    let header = response.headers().get(SERVER_TIMING_HEADER).expect("no Server-Timing header");
+    let phases = phases(header.to_str().unwrap());
+    let names: Vec<&str> = phases.iter().map(|(name, _)| name.as_str()).collect();
+
+    assert_eq!(names, ["db", "bcrypt", "tokens", "total"]);
+    let total = phases.last().unwrap().1;
+    assert!(phases.iter().all(|(_, duration)| *duration >= 0.0 && *duration <= total));
+}
+
+#[actix_web::test]
+async fn slow_requests_are_logged_with_their_breakdown() {
+    let logs = CapturedLogs::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let app = test::init_service(
+        App::new()
+            .route("/slow", web::get().to(slow_handler))
+            .route("/fast", web::get().to(fast_handler))
+            .wrap(ServerTiming::new(Duration::from_millis(100), false)),
+    )
+    .await;
+
+    let response = test::call_service(&app, test::TestRequest::get().uri("/fast").to_request()).await;
+    assert!(response.headers().get(SERVER_TIMING_HEADER).is_none());
+    assert!(!logs.contents().contains("Slow request"));
+
+    test::call_service(&app, test::TestRequest::get().uri("/slow").to_request()).await;
+    let logged = logs.contents();
+    assert!(logged.contains("WARN"), "{}", logged);
+    assert!(logged.contains("Slow request GET /slow took"), "{}", logged);
+    assert!(logged.contains("work;dur=") && logged.contains("total;dur="), "{}", logged);
+}
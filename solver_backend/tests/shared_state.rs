@@ -0,0 +1,72 @@
+// Needs the redis feature, and for the cross-instance test a Redis reachable at REDIS_URL:
+//   docker run --rm -p 6379:6379 redis:alpine
+//   REDIS_URL=redis://127.0.0.1:6379 cargo test --features redis --test shared_state
+#![cfg(feature = "redis")]
+
+mod common;
+
+use actix_web::http::StatusCode;
+use common::{access_token, init_app, init_app_with, post_json, settings_with, TestDb};
+use serde_json::json;
+
+#[actix_web::test]
+async fn instances_see_each_others_revocations() {
+    let url = match std::env::var("REDIS_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("REDIS_URL is not set, skipping");
+            return;
+        }
+    };
+
+    let db = TestDb::new().await;
+    let first = init_app_with(&db, settings_with(&[("REDIS_URL", &url)]).unwrap()).await;
+    let second = init_app_with(&db, settings_with(&[("REDIS_URL", &url)]).unwrap()).await;
+    let token = access_token(&first).await;
+
+    // The second instance has now seen the token as valid
+    let (status, _) = post_json(&second, "/api/v1/users/check_access", &json!({ "token": token }), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = post_json(&first, "/api/v1/users/revoke_token", &json!({ "token": token }), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, body) = post_json(&second, "/api/v1/users/check_access", &json!({ "token": token }), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "token_revoked");
+}
+
+#[actix_web::test]
+async fn unreachable_redis_falls_back_to_the_database() {
+    let db = TestDb::new().await;
+    // Nothing listens on port 1
+    let app = init_app_with(&db, settings_with(&[("REDIS_URL", "redis://127.0.0.1:1")]).unwrap()).await;
+    let token = access_token(&app).await;
+
+    let (status, _) = post_json(&app, "/api/v1/users/check_access", &json!({ "token": token }), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = post_json(&app, "/api/v1/users/revoke_token", &json!({ "token": token }), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = post_json(&app, "/api/v1/users/check_access", &json!({ "token": token }), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn in_memory_stores_are_per_instance() {
+    let db = TestDb::new().await;
+    let first = init_app(&db).await;
+    let second = init_app(&db).await;
+    let token = access_token(&first).await;
+
+    let (status, _) = post_json(&second, "/api/v1/users/check_access", &json!({ "token": token }), None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = post_json(&first, "/api/v1/users/revoke_token", &json!({ "token": token }), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+
+    // Without Redis the second instance keeps its cached answer until AUTH_CACHE_TTL_SECONDS
+    let (status, _) = post_json(&second, "/api/v1/users/check_access", &json!({ "token": token }), None).await;
+    assert_eq!(status, StatusCode::OK);
+}
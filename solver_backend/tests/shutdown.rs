@@ -0,0 +1,73 @@
+use actix_web::{rt, web, App, HttpResponse, HttpServer};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wordle_solver::utils::shutdown_utils::{is_shutting_down, shutdown_channel, ShutdownSignal};
+
+// Long enough to still be running when the server is told to stop
+const SLOW_REQUEST: Duration = Duration::from_millis(1500);
+
+// Marks itself started, then answers once it's done with whether shutdown began meanwhile
+async fn slow(started: web::Data<AtomicBool>, shutdown: web::Data<ShutdownSignal>) -> HttpResponse {
+    started.store(true, Ordering::SeqCst);
+    rt::time::sleep(SLOW_REQUEST).await;
+    HttpResponse::Ok().body(if is_shutting_down(&shutdown) { "finished while shutting down" } else { "finished" })
+}
+
+fn get(port: u16, path: &str) -> std::io::Result<String> {
+    let mut socket = TcpStream::connect(("127.0.0.1", port))?;
+    socket.write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", path).as_bytes())?;
+    let mut response = String::new();
+    socket.read_to_string(&mut response)?;
+    Ok(response)
+}
+
+// The same order as main: raise the shutdown flag, then stop the server gracefully
+#[actix_web::test]
+async fn in_flight_requests_finish_while_new_connections_are_refused() {
+    let started = web::Data::from(Arc::new(AtomicBool::new(false)));
+    let (shutdown_sender, shutdown) = shutdown_channel();
+    let app_started = started.clone();
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(app_started.clone())
+            .app_data(web::Data::new(shutdown.clone()))
+            .route("/slow", web::get().to(slow))
+    })
+    .workers(1)
+    .disable_signals()
+    .shutdown_timeout(10)
+    .bind(("127.0.0.1", 0))
+    .unwrap();
+    let port = server.addrs()[0].port();
+    let server = server.run();
+    let server_handle = server.handle();
+    let stopped = rt::spawn(server);
+
+    let in_flight = rt::task::spawn_blocking(move || get(port, "/slow"));
+    let waiting = Instant::now();
+    while !started.load(Ordering::SeqCst) {
+        assert!(waiting.elapsed() < Duration::from_secs(5), "the slow request never started");
+        rt::time::sleep(Duration::from_millis(10)).await;
+    }
+
+    let _ = shutdown_sender.send(true);
+    let stopping = rt::spawn(async move { server_handle.stop(true).await });
+
+    // The listener goes away at once, long before the slow request is done
+    let stop_sent = Instant::now();
+    while TcpStream::connect(("127.0.0.1", port)).is_ok() {
+        assert!(stop_sent.elapsed() < SLOW_REQUEST / 2, "new connections were still accepted");
+        rt::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert!(!in_flight.is_finished(), "the slow request was cut short");
+
+    let response = in_flight.await.unwrap().expect("the slow request was cut off");
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.ends_with("finished while shutting down"), "{}", response);
+
+    stopping.await.unwrap();
+    stopped.await.unwrap().unwrap();
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use actix_web::{test, web, App};
+use async_trait::async_trait;
+use common::{get_json, settings, state, TestDb};
+use serde_json::json;
+use std::fs;
+use std::sync::Mutex;
+use wordle_solver::handlers::health::health_routes;
+use wordle_solver::repositories::words::WordRepository;
+use wordle_solver::solver::{feedback, pattern_code, Constraints};
+use wordle_solver::utils::solver_cache_utils::{MatrixSource, PrimingState, SolverCache, SolverData};
+
+const WORDS: [&str; 8] = ["crane", "slate", "trace", "crate", "pious", "zesty", "react", "adieu"];
+
+// A word list that can be swapped out, bumping its version
+struct ListedWords(Mutex<(i64, Vec<String>)>);
+
+impl ListedWords {
+    fn new(words: &[&str]) -> Self {
+        ListedWords(Mutex::new((1, words.iter().map(|word| word.to_string()).collect())))
+    }
+
+    fn replace(&self, words: &[&str]) {
+        let mut list = self.0.lock().unwrap();
+        *list = (list.0 + 1, words.iter().map(|word| word.to_string()).collect());
+    }
+}
+
+#[async_trait]
+impl WordRepository for ListedWords {
+    async fn filter_words(&self, _pattern: &str, constraints: &Constraints) -> Result<Vec<String>, sqlx::Error> {
+        Ok(self.0.lock().unwrap().1.iter().filter(|word| constraints.allows(word)).cloned().collect())
+    }
+
+    async fn version(&self) -> Result<i64, sqlx::Error> {
+        Ok(self.0.lock().unwrap().0)
+    }
+
+    async fn all_words(&self) -> Result<Vec<String>, sqlx::Error> {
+        Ok(self.0.lock().unwrap().1.clone())
+    }
+}
+
+fn assert_patterns(data: &SolverData, words: &[&str]) {
+    for guess in words {
+        for answer in words {
+            assert_eq!(data.matrix.pattern(guess, answer), Some(pattern_code(&feedback(guess, answer))), "{} against {}", guess, answer);
+        }
+    }
+}
+
+#[actix_web::test]
+async fn the_pattern_matrix_file_is_reused_until_it_is_stale_or_corrupt() {
+    let path = std::env::temp_dir().join(format!("solver-cache-{}.bin", uuid::Uuid::new_v4()));
+    let words = ListedWords::new(&WORDS);
+
+    let cache = SolverCache::new(path.to_str());
+    let built = cache.prime(&words).await.unwrap();
+    assert_eq!((built.source, built.version, cache.state()), (MatrixSource::Computed, 1, PrimingState::Ready));
+    assert_eq!(built.starters.len(), WORDS.len());
+    assert_patterns(&built, &WORDS);
+    assert!(path.exists());
+
+    // A restart with the same list reads it back
+    let loaded = SolverCache::new(path.to_str()).prime(&words).await.unwrap();
+    assert_eq!(loaded.source, MatrixSource::Loaded);
+    assert_patterns(&loaded, &WORDS);
+    assert_eq!(loaded.starters, built.starters);
+
+    // The running cache notices the list moved on, and the file no longer matches it
+    let grown = [&WORDS[..], &["ghost"]].concat();
+    words.replace(&grown);
+    let rebuilt = cache.current(&words).await.unwrap();
+    assert_eq!((rebuilt.source, rebuilt.version), (MatrixSource::RebuiltStale, 2));
+    assert_patterns(&rebuilt, &grown);
+    assert_eq!(SolverCache::new(path.to_str()).prime(&words).await.unwrap().source, MatrixSource::Loaded);
+
+    // A flipped byte fails the checksum, a file cut short fails its length
+    let mut bytes = fs::read(&path).unwrap();
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 0xff;
+    fs::write(&path, &bytes).unwrap();
+    let repaired = SolverCache::new(path.to_str()).prime(&words).await.unwrap();
+    assert_eq!(repaired.source, MatrixSource::RebuiltCorrupt);
+    assert_patterns(&repaired, &grown);
+
+    let bytes = fs::read(&path).unwrap();
+    fs::write(&path, &bytes[..bytes.len() - 10]).unwrap();
+    assert_eq!(SolverCache::new(path.to_str()).prime(&words).await.unwrap().source, MatrixSource::RebuiltCorrupt);
+    assert_eq!(SolverCache::new(path.to_str()).prime(&words).await.unwrap().source, MatrixSource::Loaded);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[actix_web::test]
+async fn readiness_waits_for_priming_to_finish() {
+    let db = TestDb::new().await;
+    let state = state(&db, settings());
+    let (cache, words) = (state.solver_cache.clone(), state.words.clone());
+    let app = test::init_service(App::new().app_data(web::Data::new(state)).configure(health_routes)).await;
+
+    // Not asked to prime: the first request builds it, readiness isn't held up. The database
+    // check fails here anyway, nothing marks the test pool connected.
+    let (_, health) = get_json(&app, "/health/ready", None).await;
+    assert_eq!(health["checks"]["solver_cache"], json!({ "status": "pending" }));
+
+    cache.begin_priming();
+    let (_, health) = get_json(&app, "/health/ready", None).await;
+    assert_eq!(health["checks"]["solver_cache"]["status"], "failed");
+    assert_eq!(health["checks"]["solver_cache"]["error"], "priming");
+    assert!(health["failed"].as_array().unwrap().contains(&json!("solver_cache")));
+
+    let primed = cache.prime(words.as_ref()).await.unwrap();
+    let (_, health) = get_json(&app, "/health/ready", None).await;
+    assert_eq!(health["checks"]["solver_cache"]["status"], "ok");
+    assert_eq!(health["checks"]["solver_cache"]["version"], primed.version);
+    assert_eq!(health["checks"]["solver_cache"]["source"], "computed");
+    assert!(!health["failed"].as_array().unwrap().contains(&json!("solver_cache")));
+}
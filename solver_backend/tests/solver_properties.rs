@@ -0,0 +1,57 @@
+use proptest::prelude::*;
+use std::collections::HashMap;
+use wordle_solver::solver::{feedback, Constraints, Mark};
+
+// A small alphabet makes repeated letters, where the marking rules get subtle, common
+fn word() -> impl Strategy<Value = String> {
+    "[a-e]{5}"
+}
+
+fn counts(letters: impl Iterator<Item = char>) -> HashMap<char, usize> {
+    let mut counts = HashMap::new();
+    for letter in letters {
+        *counts.entry(letter).or_insert(0) += 1;
+    }
+    counts
+}
+
+proptest! {
+    // Enough to find duplicate letter mistakes while keeping `cargo test` quick
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn all_green_only_for_the_answer(guess in word(), answer in word()) {
+        let marks = feedback(&guess, &answer);
+        prop_assert_eq!(marks.iter().all(|mark| *mark == Mark::Green), guess == answer);
+    }
+
+    #[test]
+    fn marked_letters_never_outnumber_the_answer(guess in word(), answer in word()) {
+        let marks = feedback(&guess, &answer);
+        let marked = counts(guess.chars().zip(&marks).filter(|(_, mark)| **mark != Mark::Gray).map(|(letter, _)| letter));
+        let available = counts(answer.chars());
+
+        for (letter, count) in marked {
+            prop_assert!(count <= available.get(&letter).copied().unwrap_or(0), "{} marked {} times", letter, count);
+        }
+    }
+
+    #[test]
+    fn answer_survives_its_own_feedback(answer in word(), guesses in prop::collection::vec(word(), 1..6)) {
+        let mut constraints = Constraints::with_length(5);
+        for guess in &guesses {
+            constraints.apply(guess, &feedback(guess, &answer)).unwrap();
+            prop_assert!(constraints.allows(&answer), "{} ruled out after {}", answer, guess);
+        }
+    }
+
+    #[test]
+    fn guess_is_rechecked_consistently(guess in word(), answer in word()) {
+        let mut constraints = Constraints::with_length(5);
+        constraints.apply(&guess, &feedback(&guess, &answer)).unwrap();
+
+        // A wrong guess is ruled out by its own feedback but always reuses its own hints
+        prop_assert_eq!(constraints.allows(&guess), guess == answer);
+        prop_assert!(constraints.hard_mode_allows(&guess));
+    }
+}
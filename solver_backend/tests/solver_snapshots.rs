@@ -0,0 +1,24 @@
+// The top ten suggestions in every ranking mode for each of the solver's fixture states,
+// kept in tests/snapshots/solver_snapshots__<state>.snap. A change to the ranking that
+// moves any of them fails here. When it was meant to, run `cargo xtask review-snapshots`,
+// which rewrites the snapshots, then check the diff and commit it with the change.
+use wordle_solver::solver::fixtures::{self, RankingMode};
+
+#[test]
+fn suggestions_for_each_fixture_state_are_unchanged() {
+    let words = fixtures::words();
+    for state in fixtures::STATES {
+        let mut lines = vec![format!("{} candidates", state.candidates(&words).len())];
+        for mode in RankingMode::ALL {
+            let suggestions = state.suggest(&words, mode, 10);
+            lines.push(String::new());
+            lines.push(format!("{}:", mode.as_str()));
+            if suggestions.is_empty() {
+                lines.push("(none)".to_string());
+            }
+            lines.extend(suggestions.iter().map(|suggestion| format!("{} {}", suggestion.word, suggestion.score)));
+        }
+        let rendered = lines.join("\n");
+        insta::assert_snapshot!(state.name, rendered);
+    }
+}
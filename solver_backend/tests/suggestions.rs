@@ -0,0 +1,126 @@
+mod common;
+
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::Error;
+use common::{access_token, get_json, init_app, login, post_json, register, TestDb, TEST_PASSWORD};
+use serde_json::json;
+use wordle_solver::repositories::words;
+
+async fn admin_token<S, B>(app: &S, db: &TestDb) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    register(app, "curator", "curator@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'curator'").execute(&db.pool).await.unwrap();
+    let (_, tokens) = login(app, "curator", TEST_PASSWORD).await;
+    tokens["access"].as_str().unwrap().to_string()
+}
+
+#[actix_web::test]
+async fn approved_suggestions_change_the_word_list() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let player = access_token(&app).await;
+    register(&app, "second", "second@example.com", TEST_PASSWORD).await;
+    let (_, tokens) = login(&app, "second", TEST_PASSWORD).await;
+    let second = tokens["access"].as_str().unwrap().to_string();
+    let admin = admin_token(&app, &db).await;
+
+    let (status, opened) = post_json(&app, "/api/v1/game/words/suggestions", &json!({ "word": " Plumb ", "action": "add", "reason": "A common word" }), Some(&player)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!(opened["word"], "plumb");
+    assert_eq!(opened["status"], "pending");
+
+    // The same change again is merged, once per supporter
+    let (status, merged) = post_json(&app, "/api/v1/game/words/suggestions", &json!({ "word": "plumb", "action": "add", "reason": "Was an answer last year" }), Some(&second)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(merged["id"], opened["id"]);
+    let (_, merged) = post_json(&app, "/api/v1/game/words/suggestions", &json!({ "word": "plumb", "action": "add", "reason": "Really" }), Some(&second)).await;
+    assert_eq!(merged["supporters"], 2);
+    assert_eq!(merged["reason"], "A common word\nWas an answer last year");
+
+    // Changes the list already reflects are refused up front
+    let (status, body) = post_json(&app, "/api/v1/game/words/suggestions", &json!({ "word": "crane", "action": "mark_answer", "reason": "It is one" }), Some(&player)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "suggestion_no_change");
+    let (status, _) = post_json(&app, "/api/v1/game/words/suggestions", &json!({ "word": "crane", "action": "unmark_answer", "reason": "Too easy" }), Some(&player)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, queue) = get_json(&app, "/api/v1/admin/word-suggestions", Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(queue["total"], 2);
+    // Most supported first
+    assert_eq!(queue["items"][0]["word"], "plumb");
+    let (status, _) = get_json(&app, "/api/v1/admin/word-suggestions", Some(&player)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let before = words::version(&db.pool).await.unwrap();
+    let uri = format!("/api/v1/admin/word-suggestions/{}/approve", opened["id"]);
+    let (status, approved) = post_json(&app, &uri, &json!({ "note": "fair" }), Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK, "{}", approved);
+    assert_eq!(approved["status"], "approved");
+    assert!(words::all_words(&db.pool).await.unwrap().contains(&"plumb".to_string()));
+    assert!(words::version(&db.pool).await.unwrap() > before);
+
+    let (source_url, added, synced_by): (String, i32, Option<i32>) =
+        sqlx::query_as("SELECT source_url, added, synced_by FROM word_list_revisions WHERE id = $1")
+            .bind(approved["revision_id"].as_i64().unwrap() as i32)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!(source_url, format!("suggestion:{}", opened["id"]));
+    assert_eq!(added, 1);
+    assert_eq!(synced_by.map(i64::from), approved["reviewed_by"].as_i64());
+
+    let (status, body) = post_json(&app, &uri, &json!({}), Some(&admin)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "suggestion_already_reviewed");
+
+    let pending = queue["items"][1]["id"].clone();
+    let (status, _) = post_json(&app, &format!("/api/v1/admin/word-suggestions/{}/approve", pending), &json!({}), Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(!words::answer_words(&db.pool).await.unwrap().contains(&"crane".to_string()));
+    assert!(words::all_words(&db.pool).await.unwrap().contains(&"crane".to_string()));
+
+    let (_, queue) = get_json(&app, "/api/v1/admin/word-suggestions", Some(&admin)).await;
+    assert_eq!(queue["total"], 0);
+    let (_, queue) = get_json(&app, "/api/v1/admin/word-suggestions?status=approved", Some(&admin)).await;
+    assert_eq!(queue["total"], 2);
+}
+
+#[actix_web::test]
+async fn rejected_suggestions_leave_the_word_list_alone() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let player = access_token(&app).await;
+    let admin = admin_token(&app, &db).await;
+    let before = words::all_words(&db.pool).await.unwrap();
+
+    let (status, body) = post_json(&app, "/api/v1/game/words/suggestions", &json!({ "word": "cranes", "action": "add", "reason": "Plural" }), Some(&player)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "invalid_word");
+    let (status, body) = post_json(&app, "/api/v1/game/words/suggestions", &json!({ "word": "slate", "action": "remove", "reason": "  " }), Some(&player)).await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"]["code"], "invalid_field");
+
+    let (_, opened) = post_json(&app, "/api/v1/game/words/suggestions", &json!({ "word": "slate", "action": "remove", "reason": "Obscure" }), Some(&player)).await;
+    let uri = format!("/api/v1/admin/word-suggestions/{}/reject", opened["id"]);
+    let (status, rejected) = post_json(&app, &uri, &json!({ "note": "It's fine" }), Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(rejected["status"], "rejected");
+    assert_eq!(rejected["review_note"], "It's fine");
+    assert!(rejected["revision_id"].is_null());
+    assert_eq!(words::all_words(&db.pool).await.unwrap(), before);
+
+    let (status, _) = post_json(&app, &format!("/api/v1/admin/word-suggestions/{}/approve", opened["id"]), &json!({}), Some(&admin)).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+
+    // Once rejected, the same change can be proposed afresh
+    let (status, reopened) = post_json(&app, "/api/v1/game/words/suggestions", &json!({ "word": "slate", "action": "remove", "reason": "Still obscure" }), Some(&player)).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_ne!(reopened["id"], opened["id"]);
+}
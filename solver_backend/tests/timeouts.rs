@@ -0,0 +1,85 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::rt::time::timeout;
+use actix_web::{test, web, App, HttpResponse};
+use common::{settings_with, state, TestDb};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use wordle_solver::errors::AppError;
+use wordle_solver::AppState;
+
+async fn sleep_query(state: web::Data<AppState>, seconds: web::Path<f64>) -> Result<HttpResponse, AppError> {
+    sqlx::query("SELECT pg_sleep($1)").bind(*seconds).execute(&state.db).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+async fn quick_query(state: web::Data<AppState>) -> Result<HttpResponse, AppError> {
+    sqlx::query("SELECT 1").execute(&state.db).await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+// A single connection pool, so a query that never gave its connection back would block
+// every request after it. Statement timeouts only exist on Postgres, so the tests using
+// this pass trivially on SQLite.
+async fn single_connection_db(statement_timeout_ms: &str) -> (TestDb, wordle_solver::config::Settings) {
+    let overrides = [
+        ("DB_STATEMENT_TIMEOUT_MS", statement_timeout_ms),
+        ("DB_MAX_CONNECTIONS", "1"),
+        ("DB_ACQUIRE_TIMEOUT_SECONDS", "10"),
+    ];
+    let settings = settings_with(&overrides).unwrap();
+    (TestDb::with_settings(&settings).await, settings)
+}
+
+#[actix_web::test]
+async fn slow_query_times_out_and_pool_recovers() {
+    let (db, settings) = single_connection_db("200").await;
+    if !db.is_postgres() {
+        return;
+    }
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state(&db, settings)))
+            .route("/sleep/{seconds}", web::get().to(sleep_query))
+            .route("/quick", web::get().to(quick_query)),
+    )
+    .await;
+
+    let started = Instant::now();
+    let response = test::call_service(&app, test::TestRequest::get().uri("/sleep/5").to_request()).await;
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    let body: Value = test::read_body_json(response).await;
+    assert_eq!(body["error"]["code"], "query_timeout");
+
+    let response = test::call_service(&app, test::TestRequest::get().uri("/quick").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn abandoned_request_returns_its_connection() {
+    let (db, settings) = single_connection_db("1000").await;
+    if !db.is_postgres() {
+        return;
+    }
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state(&db, settings)))
+            .route("/sleep/{seconds}", web::get().to(sleep_query))
+            .route("/quick", web::get().to(quick_query)),
+    )
+    .await;
+
+    // Dropping the future mid-query is what happens when the client hangs up
+    let request = test::call_service(&app, test::TestRequest::get().uri("/sleep/30").to_request());
+    assert!(timeout(Duration::from_millis(100), request).await.is_err());
+
+    // The server side of the query is still bounded by the statement timeout, well short
+    // of the 30 seconds it asked for
+    let started = Instant::now();
+    let response = test::call_service(&app, test::TestRequest::get().uri("/quick").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(started.elapsed() < Duration::from_secs(5), "took {:?}", started.elapsed());
+}
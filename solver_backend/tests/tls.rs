@@ -0,0 +1,181 @@
+mod common;
+
+use actix_web::http::header::{HOST, LOCATION};
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, init_service, TestRequest};
+use actix_web::{rt, web, App, HttpResponse, HttpServer};
+use common::settings_with;
+use rustls::{Certificate, ClientConfig, ClientConnection, RootCertStore};
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use wordle_solver::middleware::https_redirect::HttpsRedirect;
+use wordle_solver::utils::shutdown_utils::shutdown_channel;
+use wordle_solver::utils::tls_utils::{server_config, spawn_certificate_reloader, ReloadingCertificate};
+
+// A self-signed certificate for localhost, as PEM and the DER a client sees
+struct TestCertificate {
+    cert_pem: String,
+    key_pem: String,
+    der: Vec<u8>,
+}
+
+impl TestCertificate {
+    fn generate() -> Self {
+        let generated = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        // Each serialization signs afresh, so the DER is read back from the one PEM written
+        let cert_pem = generated.serialize_pem().unwrap();
+        let der = rustls_pemfile::certs(&mut cert_pem.as_bytes()).unwrap().remove(0);
+
+        TestCertificate { cert_pem, key_pem: generated.serialize_private_key_pem(), der }
+    }
+}
+
+// A fresh directory laid out like certbot's live/<domain>
+struct CertDir(PathBuf);
+
+impl CertDir {
+    fn new() -> Self {
+        let dir = std::env::temp_dir().join(format!("wordle-tls-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        CertDir(dir)
+    }
+
+    fn cert_path(&self) -> String {
+        self.0.join("fullchain.pem").to_string_lossy().to_string()
+    }
+
+    fn key_path(&self) -> String {
+        self.0.join("privkey.pem").to_string_lossy().to_string()
+    }
+
+    fn write(&self, cert: &TestCertificate, key: &TestCertificate) {
+        std::fs::write(self.cert_path(), &cert.cert_pem).unwrap();
+        std::fs::write(self.key_path(), &key.key_pem).unwrap();
+    }
+}
+
+impl Drop for CertDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// Makes one HTTPS request trusting `trusted`, returning the status line and the
+// certificate the server presented
+fn https_get(port: u16, trusted: &[&TestCertificate]) -> (String, Vec<u8>) {
+    let mut roots = RootCertStore::empty();
+    for certificate in trusted {
+        roots.add(&Certificate(certificate.der.clone())).unwrap();
+    }
+    let config = ClientConfig::builder().with_safe_defaults().with_root_certificates(roots).with_no_client_auth();
+    let mut connection = ClientConnection::new(Arc::new(config), "localhost".try_into().unwrap()).unwrap();
+    let mut socket = TcpStream::connect(("127.0.0.1", port)).unwrap();
+    let mut stream = rustls::Stream::new(&mut connection, &mut socket);
+
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").unwrap();
+    let mut response = Vec::new();
+    // The server may close without a close_notify once the response is sent
+    let _ = stream.read_to_end(&mut response);
+
+    let status = String::from_utf8_lossy(&response).lines().next().unwrap_or_default().to_string();
+    (status, connection.peer_certificates().unwrap()[0].0.clone())
+}
+
+#[test]
+fn unreadable_or_mismatched_certificates_fail_to_load() {
+    let dir = CertDir::new();
+    let (first, second) = (TestCertificate::generate(), TestCertificate::generate());
+
+    let error = ReloadingCertificate::load(&dir.cert_path(), &dir.key_path()).err().unwrap();
+    assert!(error.contains(&dir.cert_path()), "{}", error);
+
+    dir.write(&first, &second);
+    let error = ReloadingCertificate::load(&dir.cert_path(), &dir.key_path()).err().unwrap();
+    assert!(error.contains("doesn't belong to the certificate"), "{}", error);
+
+    dir.write(&first, &first);
+    let certificate = ReloadingCertificate::load(&dir.cert_path(), &dir.key_path()).unwrap();
+    assert_eq!(certificate.current().cert[0].0, first.der);
+}
+
+#[test]
+fn tls_settings_must_be_complete() {
+    let error = settings_with(&[("TLS_CERT_PATH", "/etc/wordle/cert.pem")]).err().unwrap();
+    assert!(error.to_string().contains("TLS_CERT_PATH and TLS_KEY_PATH must be set together"), "{}", error);
+
+    let error = settings_with(&[("HTTP_ENABLED", "false")]).err().unwrap();
+    assert!(error.to_string().contains("HTTP_ENABLED"), "{}", error);
+
+    let settings = settings_with(&[("TLS_CERT_DIR", "/etc/letsencrypt/live/wordle.example.com/")]).unwrap();
+    let tls = settings.tls.unwrap();
+    assert_eq!(tls.cert_path, "/etc/letsencrypt/live/wordle.example.com/fullchain.pem");
+    assert_eq!(tls.key_path, "/etc/letsencrypt/live/wordle.example.com/privkey.pem");
+    assert_eq!(tls.watch_interval, Some(Duration::from_secs(60)));
+}
+
+#[actix_web::test]
+async fn renewed_certificates_are_served_without_a_restart() {
+    let dir = CertDir::new();
+    let (first, renewed) = (Arc::new(TestCertificate::generate()), Arc::new(TestCertificate::generate()));
+    dir.write(&first, &first);
+
+    let certificate = ReloadingCertificate::load(&dir.cert_path(), &dir.key_path()).unwrap();
+    let server = HttpServer::new(|| App::new().route("/", web::get().to(HttpResponse::Ok)))
+        .workers(1)
+        .bind_rustls(("127.0.0.1", 0), server_config(certificate.clone()))
+        .unwrap();
+    let port = server.addrs()[0].port();
+    let server = server.run();
+    let server_handle = server.handle();
+    rt::spawn(server);
+
+    let (_stop, shutdown) = shutdown_channel();
+    spawn_certificate_reloader(certificate, Some(Duration::from_millis(50)), shutdown);
+
+    let get = |first: Arc<TestCertificate>, renewed: Arc<TestCertificate>| rt::task::spawn_blocking(move || https_get(port, &[&first, &renewed]));
+    let (status, served) = get(first.clone(), renewed.clone()).await.unwrap();
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    assert_eq!(served, first.der);
+
+    // As certbot would on renewal
+    dir.write(&renewed, &renewed);
+
+    let started = Instant::now();
+    loop {
+        let (status, served) = get(first.clone(), renewed.clone()).await.unwrap();
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        if served == renewed.der {
+            break;
+        }
+        assert!(started.elapsed() < Duration::from_secs(5), "the renewed certificate was never served");
+        rt::time::sleep(Duration::from_millis(50)).await;
+    }
+
+    server_handle.stop(false).await;
+}
+
+#[actix_web::test]
+async fn plain_http_is_redirected_to_the_tls_port() {
+    let app = init_service(
+        App::new()
+            .route("/api/v1/users/register", web::post().to(HttpResponse::Ok))
+            .route("/health/live", web::get().to(HttpResponse::Ok))
+            .wrap(HttpsRedirect::new(8443)),
+    )
+    .await;
+
+    let request = TestRequest::post()
+        .uri("/api/v1/users/register?source=app")
+        .insert_header((HOST, "wordle.example.com:8080"))
+        .to_request();
+    let response = call_service(&app, request).await;
+    // 308 so the POST is repeated as a POST
+    assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(response.headers().get(LOCATION).unwrap(), "https://wordle.example.com:8443/api/v1/users/register?source=app");
+
+    let request = TestRequest::get().uri("/health/live").insert_header((HOST, "wordle.example.com")).to_request();
+    assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+}
@@ -0,0 +1,159 @@
+mod common;
+
+use actix_web::dev::ServiceResponse;
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::{web, App, HttpMessage, HttpRequest, HttpResponse};
+use common::{access_token, init_app, post_json, TestDb};
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+use wordle_solver::handlers::examples;
+use wordle_solver::middleware::request_id::{RequestId, RequestIdentifier};
+
+#[derive(Debug, Clone)]
+struct ClosedSpan {
+    name: String,
+    parent: Option<String>,
+    fields: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct Fields(HashMap<String, String>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+// Keeps every span once it closes, with its parent and the fields it ended up with
+#[derive(Clone, Default)]
+struct Capture {
+    closed: Arc<Mutex<Vec<ClosedSpan>>>,
+}
+
+impl Capture {
+    fn named(&self, name: &str) -> Vec<ClosedSpan> {
+        self.closed.lock().unwrap().iter().filter(|span| span.name == name).cloned().collect()
+    }
+
+    fn clear(&self) {
+        self.closed.lock().unwrap().clear();
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Capture {
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attributes.record(&mut fields);
+        ctx.span(id).unwrap().extensions_mut().insert(fields);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(fields) = ctx.span(id).unwrap().extensions_mut().get_mut::<Fields>() {
+            values.record(fields);
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let span = ctx.span(&id).unwrap();
+        let fields = span.extensions_mut().remove::<Fields>().unwrap_or_default();
+        self.closed.lock().unwrap().push(ClosedSpan {
+            name: span.name().to_string(),
+            parent: span.parent().map(|parent| parent.name().to_string()),
+            fields: fields.0,
+        });
+    }
+}
+
+#[actix_web::test]
+async fn find_letters_spans_its_lookups_and_the_filter() {
+    let capture = Capture::default();
+    let _subscriber = tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let login = capture.named("verify_password");
+    assert_eq!(login.len(), 1);
+    assert!(login[0].fields.contains_key("duration_ms"), "{:?}", login);
+    capture.clear();
+
+    let (status, matches) = post_json(&app, "/api/v1/game/candidates", &examples::request_letters(), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", matches);
+    let total = matches["total"].to_string();
+
+    let handler = capture.named("find_letters");
+    assert_eq!(handler.len(), 1);
+    assert_eq!(handler[0].parent, None);
+    assert_eq!(handler[0].fields["cache_hit"], "false");
+    assert_eq!(handler[0].fields["candidate_count"], total);
+    assert!(handler[0].fields.contains_key("user_id") && handler[0].fields.contains_key("duration_ms"), "{:?}", handler);
+
+    for name in ["find_preferences", "version", "filter_words"] {
+        let spans = capture.named(name);
+        assert_eq!(spans.len(), 1, "{}", name);
+        assert_eq!(spans[0].parent.as_deref(), Some("find_letters"), "{}", name);
+        assert!(spans[0].fields.contains_key("duration_ms"), "{:?}", spans);
+    }
+    let filter = &capture.named("filter_words")[0];
+    assert_eq!(filter.fields["pattern"], "____e");
+    assert_eq!(filter.fields["candidate_count"], total);
+    assert!(filter.fields["rows_returned"].parse::<usize>().unwrap() >= total.parse::<usize>().unwrap(), "{:?}", filter);
+
+    // Served from the cache, the list isn't filtered again
+    capture.clear();
+    post_json(&app, "/api/v1/game/candidates", &examples::request_letters(), Some(&token)).await;
+    assert_eq!(capture.named("find_letters")[0].fields["cache_hit"], "true");
+    assert_eq!(capture.named("find_letters")[0].fields["candidate_count"], total);
+    assert!(capture.named("filter_words").is_empty());
+}
+
+fn returned_id<B>(response: &ServiceResponse<B>) -> Option<String> {
+    response.headers().get("x-request-id").map(|value| value.to_str().unwrap().to_string())
+}
+
+#[actix_web::test]
+async fn request_ids_are_echoed_or_generated() {
+    let capture = Capture::default();
+    let _subscriber = tracing::subscriber::set_default(tracing_subscriber::registry().with(capture.clone()));
+    let app = test::init_service(
+        App::new()
+            .wrap(RequestIdentifier)
+            .route("/ping", web::get().to(|req: HttpRequest| async move { HttpResponse::Ok().body(req.extensions().get::<RequestId>().unwrap().0.clone()) })),
+    )
+    .await;
+    let ping = |request_id: Option<&str>| {
+        let mut request = TestRequest::get().uri("/ping");
+        if let Some(request_id) = request_id {
+            request = request.insert_header(("X-Request-Id", request_id));
+        }
+        request.to_request()
+    };
+
+    // The client's id is kept for the response, the handler and the span
+    let response = test::call_service(&app, ping(Some("client-id.42"))).await;
+    assert_eq!(returned_id(&response).as_deref(), Some("client-id.42"));
+    assert_eq!(test::read_body(response).await, "client-id.42");
+    assert_eq!(capture.named("request")[0].fields["request_id"], "client-id.42");
+
+    // Without one, or with one that isn't safe to log, a fresh one is made
+    for request_id in [None, Some("has spaces"), Some(&*"x".repeat(129))] {
+        let response = test::call_service(&app, ping(request_id)).await;
+        let generated = returned_id(&response).expect("no X-Request-Id returned");
+        assert!(uuid::Uuid::parse_str(&generated).is_ok(), "{}", generated);
+        assert_eq!(test::read_body(response).await, generated.as_str());
+    }
+
+    // Error responses carry it too
+    let response = test::call_service(&app, TestRequest::get().uri("/missing").insert_header(("X-Request-Id", "lost-1")).to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(returned_id(&response).as_deref(), Some("lost-1"));
+}
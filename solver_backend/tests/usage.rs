@@ -0,0 +1,124 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use common::{access_token, get_json, init_app_with_state, post_json, register, settings, state, TestDb};
+use serde_json::Value;
+use sqlx::Executor;
+use std::time::Duration;
+use wordle_solver::handlers::examples;
+use wordle_solver::utils::shutdown_utils::shutdown_channel;
+use wordle_solver::utils::usage_utils::spawn_usage_flusher;
+
+const LETTERS: &str = "POST /api/v1/game/candidates";
+const MY_USAGE: &str = "GET /api/v1/users/me/usage";
+
+// Calls to `route` over every day in the report
+fn calls(report: &Value, route: &str) -> i64 {
+    report["usage"].as_array().unwrap().iter().filter(|entry| entry["route"] == route).map(|entry| entry["calls"].as_i64().unwrap()).sum()
+}
+
+async fn stored_calls(db: &TestDb, route: &str) -> i64 {
+    sqlx::query_scalar("SELECT CAST(COALESCE(SUM(calls), 0) AS BIGINT) FROM usage_stats WHERE route = $1")
+        .bind(route)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn counts_add_up_across_a_flush() {
+    let db = TestDb::new().await;
+    let state = state(&db, settings());
+    let usage = state.usage.clone();
+    let app = init_app_with_state(state).await;
+    let token = access_token(&app).await;
+
+    for _ in 0..3 {
+        let (status, _) = post_json(&app, "/api/v1/game/candidates", &examples::request_letters(), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+    assert_eq!(usage.flush(&db.pool).await.unwrap(), 1);
+    assert_eq!(stored_calls(&db, LETTERS).await, 3);
+
+    for _ in 0..2 {
+        post_json(&app, "/api/v1/game/candidates", &examples::request_letters(), Some(&token)).await;
+    }
+    // Half stored, half still in memory
+    let (status, report) = get_json(&app, "/api/v1/users/me/usage", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", report);
+    assert_eq!(calls(&report, LETTERS), 5, "{}", report);
+    assert_eq!(calls(&report, MY_USAGE), 1, "{}", report);
+    assert_eq!(report["total_calls"], 6);
+
+    usage.flush(&db.pool).await.unwrap();
+    assert_eq!(stored_calls(&db, LETTERS).await, 5);
+    let (_, report) = get_json(&app, "/api/v1/users/me/usage", Some(&token)).await;
+    assert_eq!(calls(&report, LETTERS), 5, "{}", report);
+    assert_eq!(calls(&report, MY_USAGE), 2, "{}", report);
+}
+
+#[actix_web::test]
+async fn a_failed_flush_keeps_the_counts_for_the_next_one() {
+    let db = TestDb::new().await;
+    let state = state(&db, settings());
+    let usage = state.usage.clone();
+    let app = init_app_with_state(state).await;
+    let token = access_token(&app).await;
+
+    post_json(&app, "/api/v1/game/candidates", &examples::request_letters(), Some(&token)).await;
+    db.fail_inserts_into("usage_stats").await;
+    assert!(usage.flush(&db.pool).await.is_err());
+
+    let drop_trigger = if db.is_postgres() { "DROP TRIGGER fail_usage_stats ON usage_stats" } else { "DROP TRIGGER fail_usage_stats" };
+    db.pool.execute(drop_trigger).await.unwrap();
+    usage.flush(&db.pool).await.unwrap();
+    assert_eq!(stored_calls(&db, LETTERS).await, 1);
+}
+
+#[actix_web::test]
+async fn the_flusher_writes_counts_until_shutdown() {
+    let db = TestDb::new().await;
+    let state = state(&db, settings());
+    let usage = state.usage.clone();
+    let app = init_app_with_state(state).await;
+    let token = access_token(&app).await;
+    let (shutdown_sender, shutdown) = shutdown_channel();
+    let flusher = spawn_usage_flusher(usage, db.pool.clone(), Duration::from_millis(20), shutdown);
+
+    post_json(&app, "/api/v1/game/candidates", &examples::request_letters(), Some(&token)).await;
+    for _ in 0..100 {
+        if stored_calls(&db, LETTERS).await == 1 {
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(stored_calls(&db, LETTERS).await, 1);
+
+    shutdown_sender.send(true).unwrap();
+    flusher.await.unwrap();
+}
+
+#[actix_web::test]
+async fn admins_can_see_anyones_usage() {
+    let db = TestDb::new().await;
+    db.pool.execute("UPDATE users SET role = 'admin'").await.unwrap();
+    let app = init_app_with_state(state(&db, settings())).await;
+    let admin_token = access_token(&app).await;
+
+    let (_, tokens) = register(&app, "counted", "counted@example.com", "correct horse battery").await;
+    let token = tokens["access"].as_str().unwrap();
+    post_json(&app, "/api/v1/game/candidates", &examples::request_letters(), Some(token)).await;
+    let (_, own) = get_json(&app, "/api/v1/users/me/usage", Some(token)).await;
+    let id = own["user_id"].as_i64().unwrap();
+
+    let (status, report) = get_json(&app, &format!("/api/v1/users/{}/usage", id), Some(&admin_token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", report);
+    assert_eq!(report["user_id"], id);
+    assert_eq!(calls(&report, LETTERS), 1, "{}", report);
+    assert_eq!(calls(&report, MY_USAGE), 1, "{}", report);
+    // The admin's own call is counted against the admin
+    assert_eq!(calls(&report, "GET /api/v1/users/{id}/usage"), 0, "{}", report);
+
+    let (status, _) = get_json(&app, "/api/v1/users/1/usage", Some(token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,618 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use chrono::{DateTime, Duration, Utc};
+use common::{access_token, get_json, init_app, init_app_with, init_app_with_clock, login, post_json, register, settings, settings_with, signing_keys, TestDb, TEST_EMAIL, TEST_PASSWORD, TEST_USERNAME};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use wordle_solver::models::users_models::TokenType;
+use wordle_solver::utils::clock_utils::{Clock, FakeClock};
+use wordle_solver::utils::jwt_utils::decode_claims;
+use wordle_solver::utils::maintenance_utils::run_maintenance;
+
+#[actix_web::test]
+async fn register_issues_tokens() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let (status, tokens) = register(&app, "newplayer", "newplayer@example.com", "a long password").await;
+
+    assert_eq!(status, StatusCode::OK, "{}", tokens);
+    assert!(tokens["access"].is_string());
+    assert!(tokens["refresh"].is_string());
+}
+
+#[actix_web::test]
+async fn register_rejects_taken_username() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let (status, body) = register(&app, TEST_USERNAME, "someone.else@example.com", "a long password").await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "user_exists");
+}
+
+#[actix_web::test]
+async fn register_rejects_reserved_names_however_they_are_spelled() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let reserved = ["admin", "ADMIN", "AdMiN", "a-d-m-i-n", "a.d.m.i.n", "4dmin", "@dm1n", "Adm!n", "r00t", "$upp0rt", "5y5t3m", "Mod_Erator"];
+    for (index, username) in reserved.iter().enumerate() {
+        let (status, body) = register(&app, username, &format!("reserved{}@example.com", index), TEST_PASSWORD).await;
+        assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("username_not_allowed")), "{}", username);
+    }
+
+    // Only the whole name counts, and only the mapped digits
+    let allowed = ["admiral", "badminton", "rooted", "admin2", "m0dest"];
+    for (index, username) in allowed.iter().enumerate() {
+        let (status, body) = register(&app, username, &format!("allowed{}@example.com", index), TEST_PASSWORD).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", username, body);
+    }
+}
+
+#[actix_web::test]
+async fn register_rejects_email_differing_only_by_case() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let (status, body) = register(&app, "othername", &TEST_EMAIL.to_uppercase(), "a long password").await;
+
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(body["error"]["code"], "user_exists");
+}
+
+#[actix_web::test]
+async fn login_by_email_ignores_its_case() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let (status, body) = register(&app, "mixedcase", "Mixed.Case@Example.com", "a long password").await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+
+    let (status, tokens) = login(&app, "mIXED.cASE@eXAMPLE.COM", "a long password").await;
+
+    assert_eq!(status, StatusCode::OK, "{}", tokens);
+    assert!(tokens["access"].is_string());
+    assert!(tokens["refresh"].is_string());
+}
+
+#[actix_web::test]
+async fn login_with_valid_credentials() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let (status, tokens) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+
+    assert_eq!(status, StatusCode::OK, "{}", tokens);
+    assert!(tokens["access"].is_string());
+}
+
+#[actix_web::test]
+async fn login_with_wrong_password() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let (status, body) = login(&app, TEST_USERNAME, "not the password").await;
+
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(body["error"]["code"], "invalid_credentials");
+}
+
+#[actix_web::test]
+async fn registered_user_can_log_in() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let (status, _) = register(&app, "newplayer", "newplayer@example.com", "a long password").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _) = login(&app, "newplayer", "a long password").await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn legacy_paths_are_deprecated_aliases() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+
+    let body = json!({ "username": TEST_USERNAME, "password": TEST_PASSWORD });
+    let response = test::call_service(&app, test::TestRequest::post().uri("/api/users/login").set_json(&body).to_request()).await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("deprecation").unwrap(), "true");
+    assert_eq!(response.headers().get("link").unwrap(), "</api/v1/users/login>; rel=\"successor-version\"");
+}
+
+#[actix_web::test]
+async fn legacy_paths_can_be_turned_off() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[("LEGACY_API_ROUTES", "false")]).unwrap()).await;
+
+    let body = json!({ "username": TEST_USERNAME, "password": TEST_PASSWORD });
+    let response = test::call_service(&app, test::TestRequest::post().uri("/api/users/login").set_json(&body).to_request()).await;
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn access_tokens_expire_on_the_state_clock() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings(), clock.clone()).await;
+    let token = access_token(&app).await;
+
+    let sessions = || {
+        test::TestRequest::get()
+            .uri("/api/v1/users/me/sessions")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    clock.advance(Duration::minutes(59));
+    assert_eq!(test::call_service(&app, sessions()).await.status(), StatusCode::OK);
+
+    // Past the hour the token is valid for, and the minute of leeway on top
+    clock.advance(Duration::minutes(3));
+    assert_eq!(test::call_service(&app, sessions()).await.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn suspensions_lift_on_the_state_clock() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings(), clock.clone()).await;
+
+    sqlx::query("UPDATE users SET suspended_at = $1, suspended_until = $2, suspension_reason = 'spam' WHERE username = $3")
+        .bind(clock.now())
+        .bind(clock.now() + Duration::days(1))
+        .bind(TEST_USERNAME)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+    let (status, body) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["code"], "account_suspended");
+
+    clock.advance(Duration::days(1) + Duration::seconds(1));
+    let (status, body) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[actix_web::test]
+async fn suspensions_set_across_a_dst_change_end_at_the_same_instant() {
+    let db = TestDb::new().await;
+    // 01:55 in New York, five minutes before the clocks went forward on 2023-03-12
+    let clock = Arc::new(FakeClock::new("2023-03-12T06:55:00Z".parse().unwrap()));
+    let app = init_app_with_clock(&db, settings(), clock.clone()).await;
+
+    // 03:10 EDT, which is only 15 minutes later despite the wall clock moving 75
+    let until: DateTime<Utc> = DateTime::parse_from_rfc3339("2023-03-12T03:10:00-04:00").unwrap().into();
+    sqlx::query("UPDATE users SET suspended_at = $1, suspended_until = $2, suspension_reason = 'spam' WHERE username = $3")
+        .bind(clock.now())
+        .bind(until)
+        .bind(TEST_USERNAME)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+
+    let (status, body) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["details"]["until"], "2023-03-12T07:10:00Z");
+
+    clock.advance(Duration::minutes(20));
+    let (status, body) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[actix_web::test]
+async fn registration_is_undone_when_a_later_step_fails() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    // Tokens are the last thing registration writes
+    db.fail_inserts_into("refresh_tokens").await;
+
+    let (status, _) = register(&app, "halfway", "halfway@example.com", "a long enough password").await;
+    assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+
+    let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE username = 'halfway'")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    let history: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM password_history")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!((users, history), (0, 0));
+}
+
+#[actix_web::test]
+async fn password_change_is_undone_when_history_cannot_be_recorded() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    db.fail_inserts_into("password_history").await;
+    let token = access_token(&app).await;
+
+    let user_id: i32 = sqlx::query_scalar("SELECT id FROM users WHERE username = $1")
+        .bind(TEST_USERNAME)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    let request = test::TestRequest::put()
+        .uri(&format!("/api/v1/users/update_password/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "password": "a brand new password" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let (status, _) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn the_last_passwords_cannot_be_reused_but_older_ones_can() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[("PASSWORD_HISTORY_SIZE", "3")]).unwrap()).await;
+    // Registering records the first password, the three changes push it out of the history
+    let passwords = ["history password zero", "history password one", "history password two", "history password three"];
+    register(&app, "rotator", "rotator@example.com", passwords[0]).await;
+    let user_id: i32 = sqlx::query_scalar("SELECT id FROM users WHERE username = 'rotator'").fetch_one(&db.pool).await.unwrap();
+
+    let change = |current: &'static str, next: &'static str| {
+        let app = &app;
+        async move {
+            let (_, tokens) = login(app, "rotator", current).await;
+            let request = test::TestRequest::put()
+                .uri(&format!("/api/v1/users/update_password/{}", user_id))
+                .insert_header(("Authorization", format!("Bearer {}", tokens["access"].as_str().unwrap())))
+                .set_json(json!({ "password": next }))
+                .to_request();
+            let response = test::call_service(app, request).await;
+            let status = response.status();
+            let body: Value = test::read_body_json(response).await;
+            (status, body)
+        }
+    };
+
+    for pair in passwords.windows(2) {
+        assert_eq!(change(pair[0], pair[1]).await.0, StatusCode::OK);
+    }
+
+    // The current password and the two before it
+    for reused in &passwords[1..] {
+        let (status, body) = change(passwords[3], *reused).await;
+        assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("password_reused")), "{}", reused);
+    }
+
+    assert_eq!(change(passwords[3], passwords[0]).await.0, StatusCode::OK);
+    assert_eq!(login(&app, "rotator", passwords[0]).await.0, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn profile_edits_made_to_an_old_version_are_refused_with_the_current_one() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let (_, user) = get_json(&app, &format!("/api/v1/users/{}", tester_id(&db).await), Some(&token)).await;
+    assert_eq!(user["version"], 1);
+    let uri = format!("/api/v1/users/{}", user["id"]);
+    let authorization = format!("Bearer {}", token);
+    let patch_at = |uri: &str, body: serde_json::Value| test::TestRequest::patch().uri(uri).insert_header(("Authorization", authorization.as_str())).set_json(body).to_request();
+    let patch = |body: serde_json::Value| patch_at(&uri, body);
+
+    // Both devices read version 1, the phone saves first
+    let response = test::call_service(&app, patch(json!({ "username": "phone", "email": TEST_EMAIL, "version": 1 }))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let saved: serde_json::Value = test::read_body_json(response).await;
+    assert_eq!((&saved["username"], &saved["version"]), (&json!("phone"), &json!(2)));
+
+    let response = test::call_service(&app, patch(json!({ "username": "laptop", "email": "laptop@example.com", "version": 1 }))).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(response).await;
+    assert_eq!(body["error"]["code"], "stale_version");
+    assert_eq!(body["error"]["details"]["version"], 1);
+    assert_eq!(body["error"]["details"]["current"], saved);
+    assert!(saved.get("password").is_none(), "{}", saved);
+    assert_eq!(get_json(&app, &uri, Some(&token)).await.1, saved);
+
+    // Merged onto the current version it goes through
+    let response = test::call_service(&app, patch(json!({ "username": "phone", "email": "laptop@example.com", "version": 2 }))).await;
+    let merged: serde_json::Value = test::read_body_json(response).await;
+    assert_eq!((&merged["email"], &merged["version"]), (&json!("laptop@example.com"), &json!(3)));
+
+    // Without a version there is nothing to check against
+    let response = test::call_service(&app, patch(json!({ "username": "phone", "email": TEST_EMAIL }))).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    // Someone else's id is refused before it's looked up, so whether it exists doesn't show
+    let response = test::call_service(&app, patch_at("/api/v1/users/999999", json!({ "username": "ghost", "email": "ghost@example.com", "version": 1 }))).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+async fn tester_id(db: &TestDb) -> i32 {
+    sqlx::query_scalar("SELECT id FROM users WHERE username = $1").bind(TEST_USERNAME).fetch_one(&db.pool).await.unwrap()
+}
+
+#[actix_web::test]
+async fn preferences_default_until_saved_and_reject_unknown_keys() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+    let put = |body: serde_json::Value| {
+        test::TestRequest::put()
+            .uri("/api/v1/users/me/preferences")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+
+    let (status, preferences) = get_json(&app, "/api/v1/users/me/preferences", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        preferences,
+        json!({ "hard_mode": false, "colorblind": false, "word_length": 5, "sort_order": "alphabetical", "notification_opt_outs": [], "profile_visibility": "public" })
+    );
+
+    let response = test::call_service(&app, put(json!({ "hard_mode": true, "sort_order": "reverse_alphabetical", "notification_opt_outs": ["new_device"] }))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let (_, preferences) = get_json(&app, "/api/v1/users/me/preferences", Some(&token)).await;
+    assert_eq!(preferences["hard_mode"], true);
+    assert_eq!(preferences["colorblind"], false);
+    assert_eq!(preferences["sort_order"], "reverse_alphabetical");
+    assert_eq!(preferences["notification_opt_outs"], json!(["new_device"]));
+    // The opt-out is the same switch /me/settings sets
+    let notify: bool = sqlx::query_scalar("SELECT notify_new_device FROM users WHERE username = $1").bind(TEST_USERNAME).fetch_one(&db.pool).await.unwrap();
+    assert!(!notify);
+
+    let response = test::call_service(&app, put(json!({ "dark_mode": true }))).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let body: serde_json::Value = test::read_body_json(response).await;
+    assert_eq!(body["error"]["details"]["field"], "dark_mode");
+    assert!(body["error"]["message"].as_str().unwrap().contains("`hard_mode`, `colorblind`, `word_length`, `sort_order`, `notification_opt_outs`, `profile_visibility`"), "{}", body);
+
+    for body in [json!({ "word_length": 12 }), json!({ "colorblind": "yes" }), json!({ "sort_order": "random" })] {
+        let response = test::call_service(&app, put(body.clone())).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY, "{}", body);
+    }
+    let (_, unchanged) = get_json(&app, "/api/v1/users/me/preferences", Some(&token)).await;
+    assert_eq!(unchanged, preferences);
+
+    // Solver results follow the saved order unless the request picks one
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "cra__" });
+    let (_, words) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+    assert_eq!(words["items"], json!(["crate", "crane"]));
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "cra__", "sort_order": "alphabetical" });
+    let (_, words) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&token)).await;
+    assert_eq!(words["items"], json!(["crane", "crate"]));
+}
+
+#[actix_web::test]
+async fn tokens_can_only_be_revoked_by_their_owner_or_an_admin() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let revoke = |token: String, caller: Option<String>| {
+        let app = &app;
+        async move { post_json(app, "/api/v1/users/revoke_token", &json!({ "token": token }), caller.as_deref()).await }
+    };
+
+    let (_, tokens) = register(&app, "mallory", "mallory@example.com", TEST_PASSWORD).await;
+    let mallory = tokens["access"].as_str().unwrap().to_string();
+    let (_, tokens) = register(&app, "boss", "boss@example.com", TEST_PASSWORD).await;
+    let boss = tokens["access"].as_str().unwrap().to_string();
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'boss'").execute(&db.pool).await.unwrap();
+    let (_, tokens) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    let (access, refresh) = (tokens["access"].as_str().unwrap().to_string(), tokens["refresh"].as_str().unwrap().to_string());
+
+    let (status, body) = revoke(refresh.clone(), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", body);
+    let (status, body) = revoke(refresh.clone(), Some(mallory.clone())).await;
+    assert_eq!(status, StatusCode::FORBIDDEN, "{}", body);
+    let (status, body) = revoke("garbage".to_string(), Some(access.clone())).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "invalid_token");
+
+    // Owners may revoke their own, and repeats don't add rows
+    let (status, body) = revoke(refresh.clone(), Some(access.clone())).await;
+    assert_eq!((status, body), (StatusCode::OK, json!({ "already_revoked": false })));
+    let (status, body) = revoke(refresh.clone(), Some(access.clone())).await;
+    assert_eq!((status, body), (StatusCode::OK, json!({ "already_revoked": true })));
+
+    // Admins may revoke anyone's
+    let (status, body) = revoke(access.clone(), Some(boss)).await;
+    assert_eq!((status, body), (StatusCode::OK, json!({ "already_revoked": false })));
+    let (status, _) = post_json(&app, "/api/v1/users/check_access", &json!({ "token": access }), None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM revoked_tokens WHERE token = $1").bind(&refresh).fetch_one(&db.pool).await.unwrap();
+    assert_eq!(rows, 1);
+}
+
+#[actix_web::test]
+async fn deleted_accounts_are_anonymized_once_the_grace_period_is_over() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings(), clock.clone()).await;
+    let grace = Duration::days(30);
+
+    let (status, tokens) = register(&app, "leaver", "leaver@example.com", TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{}", tokens);
+    let id: i32 = sqlx::query_scalar("SELECT id FROM users WHERE username = 'leaver'").fetch_one(&db.pool).await.unwrap();
+    sqlx::query("INSERT INTO auth_events (user_id, event_type, detail) VALUES ($1, 'login', 'from 203.0.113.9, Firefox')")
+        .bind(id)
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let users_before: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users").fetch_one(&db.pool).await.unwrap();
+
+    let request = test::TestRequest::delete()
+        .uri(&format!("/api/v1/users/{}", id))
+        .insert_header(("Authorization", format!("Bearer {}", tokens["access"].as_str().unwrap())))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    // Gone for the user straight away: no logging in, existing tokens stop working
+    let (status, _) = login(&app, "leaver", TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    let (status, _) = get_json(&app, "/api/v1/users/me/preferences", tokens["access"].as_str()).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+    let pool = &db.pool;
+    let profile = || async move {
+        let row: (String, String, String, bool) = sqlx::query_as("SELECT username, email, password, anonymized_at IS NOT NULL FROM users WHERE id = $1")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        row
+    };
+    let count = |sql: &'static str| async move {
+        let count: i64 = sqlx::query_scalar(sql).bind(id).fetch_one(pool).await.unwrap();
+        count
+    };
+
+    // Still within the grace period, nothing is touched
+    clock.advance(grace - Duration::hours(1));
+    run_maintenance(&db.pool, &*clock, Duration::hours(24), grace).await;
+    assert_eq!(profile().await.0, "leaver");
+
+    clock.advance(Duration::hours(2));
+    run_maintenance(&db.pool, &*clock, Duration::hours(24), grace).await;
+    let (username, email, password, anonymized) = profile().await;
+    assert!(username.starts_with(&format!("deleted-{}-", id)), "{}", username);
+    assert_eq!(email, format!("{}@invalid", username));
+    assert_eq!(password, "");
+    assert!(anonymized);
+    assert_eq!(count("SELECT COUNT(*) FROM refresh_tokens WHERE user_id = $1").await, 0);
+    assert_eq!(count("SELECT COUNT(*) FROM password_history WHERE user_id = $1").await, 0);
+    assert_eq!(count("SELECT COUNT(*) FROM auth_events WHERE user_id = $1 AND detail IS NOT NULL AND detail <> ''").await, 0);
+    assert_eq!(count("SELECT COUNT(*) FROM auth_events WHERE user_id = $1 AND event_type = 'account_anonymized'").await, 1);
+    // The tombstone keeps counting towards totals
+    let users_after: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users").fetch_one(&db.pool).await.unwrap();
+    assert_eq!(users_after, users_before);
+
+    // Another pass finds nothing left to do
+    run_maintenance(&db.pool, &*clock, Duration::hours(24), grace).await;
+    assert_eq!(profile().await.0, username);
+    assert_eq!(count("SELECT COUNT(*) FROM auth_events WHERE user_id = $1 AND event_type = 'account_anonymized'").await, 1);
+
+    // And the name and address are free again
+    let (status, body) = register(&app, "leaver", "leaver@example.com", TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[actix_web::test]
+async fn only_the_owner_or_an_admin_can_delete_an_account() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let delete = |id: i32, token: String| test::TestRequest::delete().uri(&format!("/api/v1/users/{}", id)).insert_header(("Authorization", format!("Bearer {}", token))).to_request();
+
+    let (_, tokens) = register(&app, "mallory", "mallory@example.com", TEST_PASSWORD).await;
+    let mallory = tokens["access"].as_str().unwrap().to_string();
+    let (_, tokens) = register(&app, "boss", "boss@example.com", TEST_PASSWORD).await;
+    let boss = tokens["access"].as_str().unwrap().to_string();
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'boss'").execute(&db.pool).await.unwrap();
+    let tester = tester_id(&db).await;
+
+    let response = test::call_service(&app, delete(tester, mallory)).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: serde_json::Value = test::read_body_json(response).await;
+    assert_eq!(body["error"]["code"], "not_owner");
+    let deleted: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE id = $1 AND deleted_at IS NOT NULL").bind(tester).fetch_one(&db.pool).await.unwrap();
+    assert_eq!(deleted, 0);
+    let (status, _) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::OK);
+
+    assert_eq!(test::call_service(&app, delete(tester, boss)).await.status(), StatusCode::OK);
+    let (status, _) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn refresh_tokens_rotate_and_a_replay_revokes_the_family() {
+    let db = TestDb::new().await;
+    let clock = Arc::new(FakeClock::new(Utc::now()));
+    let app = init_app_with_clock(&db, settings(), clock.clone()).await;
+    let refresh = |token: &serde_json::Value| {
+        let app = &app;
+        let body = json!({ "token": token });
+        async move { post_json(app, "/api/v1/users/get_new_tokens", &body, None).await }
+    };
+
+    // A second apart, or the fake clock would issue the same tokens twice
+    let (_, first) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    clock.advance(Duration::seconds(1));
+    let (_, other_login) = login(&app, TEST_USERNAME, TEST_PASSWORD).await;
+    assert_eq!(decode_claims(&signing_keys(), first["access"].as_str().unwrap(), clock.now()).unwrap().typ, TokenType::Access);
+    assert_eq!(decode_claims(&signing_keys(), first["refresh"].as_str().unwrap(), clock.now()).unwrap().typ, TokenType::Refresh);
+
+    // Neither half of the pair stands in for the other
+    let (status, body) = refresh(&first["access"]).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::UNAUTHORIZED, &json!("invalid_token")));
+    let (status, body) = get_json(&app, "/api/v1/users/me/preferences", first["refresh"].as_str()).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::UNAUTHORIZED, &json!("invalid_token")));
+
+    clock.advance(Duration::seconds(1));
+    let (status, second) = refresh(&first["refresh"]).await;
+    assert_eq!(status, StatusCode::OK, "{}", second);
+    assert_ne!(second["refresh"], first["refresh"]);
+    assert_eq!(get_json(&app, "/api/v1/users/me/preferences", second["access"].as_str()).await.0, StatusCode::OK);
+
+    // A client that lost the response inside the grace window gets the same pair again
+    clock.advance(Duration::seconds(5));
+    let (status, again) = refresh(&first["refresh"]).await;
+    assert_eq!((status, &again), (StatusCode::OK, &second));
+
+    // Past it the token must have been copied, and the whole family goes
+    clock.advance(Duration::seconds(10));
+    let (status, body) = refresh(&first["refresh"]).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::UNAUTHORIZED, &json!("refresh_token_reused")));
+    let (status, body) = refresh(&second["refresh"]).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::UNAUTHORIZED, &json!("token_revoked")));
+    let (status, body) = get_json(&app, "/api/v1/users/me/preferences", second["access"].as_str()).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::UNAUTHORIZED, &json!("token_revoked")));
+
+    // Other logins are families of their own
+    let (status, body) = refresh(&other_login["refresh"]).await;
+    assert_eq!(status, StatusCode::OK, "{}", body);
+}
+
+#[actix_web::test]
+async fn idempotent_registrations_replay_successes_and_validation_failures() {
+    let db = TestDb::new().await;
+    let app = init_app(&db).await;
+    let register_with_key = |key: &'static str, body: Value| {
+        let app = &app;
+        async move {
+            let request = test::TestRequest::post().uri("/api/v1/users/register").insert_header(("Idempotency-Key", key)).set_json(body).to_request();
+            let response = test::call_service(app, request).await;
+            let status = response.status();
+            let body: Value = test::read_body_json(response).await;
+            (status, body)
+        }
+    };
+    let stored = |key: &'static str| sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM idempotency_keys WHERE key = $1").bind(key).fetch_one(&db.pool);
+
+    // A retry gets the first response back instead of user_exists
+    let signup = json!({ "username": "retrier", "email": "retrier@example.com", "password": TEST_PASSWORD });
+    let (status, first) = register_with_key("signup-1", signup.clone()).await;
+    assert_eq!(status, StatusCode::OK, "{}", first);
+    assert_eq!(register_with_key("signup-1", signup).await, (StatusCode::OK, first));
+
+    // The same key with another body
+    let other = json!({ "username": "someone", "email": "someone@example.com", "password": TEST_PASSWORD });
+    let (status, body) = register_with_key("signup-1", other).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("idempotency_key_reused")));
+
+    // A validation failure is stored too, and a retry gets the same 422 back even once the
+    // cause is gone
+    sqlx::query("INSERT INTO reserved_usernames (word) VALUES ('gatekeeper')").execute(&db.pool).await.unwrap();
+    let gatekeeper = json!({ "username": "gatekeeper", "email": "gatekeeper@example.com", "password": TEST_PASSWORD });
+    let (status, failure) = register_with_key("signup-2", gatekeeper.clone()).await;
+    assert_eq!((status, failure["error"]["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some("username_not_allowed")));
+    assert_eq!(stored("signup-2").await.unwrap(), 1);
+
+    sqlx::query("DELETE FROM reserved_usernames WHERE word = 'gatekeeper'").execute(&db.pool).await.unwrap();
+    assert_eq!(register_with_key("signup-2", gatekeeper).await, (StatusCode::UNPROCESSABLE_ENTITY, failure));
+}
@@ -0,0 +1,235 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, TestRequest};
+use actix_web::{rt, web, App, HttpRequest, HttpResponse, HttpServer};
+use chrono::Utc;
+use common::{access_token, get_json, init_app_with, init_app_with_clock, login, post_json, register, settings_with, TestDb, TEST_PASSWORD, TEST_USERNAME};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::atomic::{AtomicU16, Ordering};
+use std::sync::{Arc, Mutex};
+use wordle_solver::config::Settings;
+use wordle_solver::repositories::users;
+use wordle_solver::repositories::words::DbWords;
+use wordle_solver::utils::clock_utils::{Clock, FakeClock, SystemClock};
+use wordle_solver::utils::job_utils::{retry_delay, run_next_job, BuiltinJobs};
+use wordle_solver::utils::mail_utils::LogMailer;
+use wordle_solver::utils::webhook_utils::{dispatch, DAILY_COMPLETED, STREAK_MILESTONE};
+
+const SECRET: &str = "league bot shared secret";
+
+struct Captured {
+    signature: String,
+    event: String,
+    body: Vec<u8>,
+}
+
+// Stands in for the bot, keeping every request and answering with `status`
+#[derive(Clone)]
+struct Receiver {
+    captured: Arc<Mutex<Vec<Captured>>>,
+    status: Arc<AtomicU16>,
+}
+
+impl Receiver {
+    fn new() -> Self {
+        Receiver { captured: Arc::default(), status: Arc::new(AtomicU16::new(200)) }
+    }
+
+    // The URL deliveries go to
+    fn serve(&self) -> String {
+        let receiver = self.clone();
+        let server = HttpServer::new(move || {
+            let receiver = receiver.clone();
+            App::new().route(
+                "/hook",
+                web::post().to(move |req: HttpRequest, body: web::Bytes| {
+                    let header = |name: &str| req.headers().get(name).map_or(String::new(), |value| value.to_str().unwrap().to_string());
+                    receiver.captured.lock().unwrap().push(Captured {
+                        signature: header("X-Webhook-Signature"),
+                        event: header("X-Webhook-Event"),
+                        body: body.to_vec(),
+                    });
+                    let status = StatusCode::from_u16(receiver.status.load(Ordering::SeqCst)).unwrap();
+                    async move { HttpResponse::build(status).finish() }
+                }),
+            )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        rt::spawn(server.run());
+        format!("http://{}/hook", address)
+    }
+
+    fn count(&self) -> usize {
+        self.captured.lock().unwrap().len()
+    }
+}
+
+fn webhook_settings(overrides: &[(&str, &str)]) -> Settings {
+    let mut vars = vec![("WEBHOOK_ALLOW_PRIVATE_HOSTS", "true")];
+    vars.extend_from_slice(overrides);
+    settings_with(&vars).unwrap()
+}
+
+fn runner(db: &TestDb, settings: &Settings, clock: Arc<dyn Clock>) -> BuiltinJobs {
+    BuiltinJobs { db: db.pool.clone(), words: Arc::new(DbWords::new(db.pool.clone())), webhooks: settings.webhooks.clone(), mailer: Arc::new(LogMailer), clock }
+}
+
+// Worked out independently of the server's signing code
+fn expected_signature(body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(body);
+    let digest: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("sha256={}", digest)
+}
+
+#[actix_web::test]
+async fn events_are_delivered_signed() {
+    let db = TestDb::new().await;
+    let settings = webhook_settings(&[]);
+    // The app queues pings by its own clock, so the runner has to share it
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let runner = runner(&db, &settings, clock.clone());
+    let app = init_app_with(&db, settings).await;
+    let token = access_token(&app).await;
+    // Out of the way: the login's new device notice
+    run_next_job(&db.pool, &runner, &*clock).await.unwrap();
+    let tester = users::find_by_username(&db.pool, TEST_USERNAME).await.unwrap().unwrap().id;
+    let receiver = Receiver::new();
+
+    let body = json!({ "url": receiver.serve(), "secret": SECRET, "events": [DAILY_COMPLETED, DAILY_COMPLETED] });
+    let (status, webhook) = post_json(&app, "/api/v1/webhooks", &body, Some(&token)).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", webhook);
+    assert_eq!(webhook["events"], json!([DAILY_COMPLETED]));
+    assert!(webhook.get("secret").is_none());
+    assert_eq!(webhook["all_users"], false);
+
+    // Only subscribed events are queued
+    assert_eq!(dispatch(&db.pool, tester, STREAK_MILESTONE, &json!({}), 3, clock.now()).await.unwrap(), 0);
+    assert_eq!(dispatch(&db.pool, tester, DAILY_COMPLETED, &json!({ "puzzle": 42, "guesses": 4 }), 3, clock.now()).await.unwrap(), 1);
+    assert!(run_next_job(&db.pool, &runner, &*clock).await.unwrap().is_some());
+
+    {
+        let captured = receiver.captured.lock().unwrap();
+        assert_eq!(captured.len(), 1);
+        assert_eq!(captured[0].event, DAILY_COMPLETED);
+        assert_eq!(captured[0].signature, expected_signature(&captured[0].body));
+        let payload: Value = serde_json::from_slice(&captured[0].body).unwrap();
+        assert_eq!(payload["event"], DAILY_COMPLETED);
+        assert_eq!(payload["user_id"], tester);
+        assert_eq!(payload["data"]["puzzle"], 42);
+    }
+
+    let deliveries_uri = format!("/api/v1/webhooks/{}/deliveries", webhook["id"]);
+    let (_, deliveries) = get_json(&app, &deliveries_uri, Some(&token)).await;
+    assert_eq!(deliveries["total"], 1);
+    assert_eq!(deliveries["items"][0]["status"], "succeeded");
+    assert_eq!(deliveries["items"][0]["response_status"], 200);
+    assert_eq!(deliveries["items"][0]["attempts"], 1);
+
+    let (status, ping) = post_json(&app, &format!("/api/v1/webhooks/{}/ping", webhook["id"]), &json!({}), Some(&token)).await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(ping["status"], "pending");
+    run_next_job(&db.pool, &runner, &*clock).await.unwrap();
+    assert_eq!(receiver.captured.lock().unwrap()[1].event, "ping");
+
+    // Other users can't see or touch it
+    register(&app, "stranger", "stranger@example.com", TEST_PASSWORD).await;
+    let (_, tokens) = login(&app, "stranger", TEST_PASSWORD).await;
+    let stranger = tokens["access"].as_str().unwrap();
+    let (status, _) = get_json(&app, &deliveries_uri, Some(stranger)).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let delete = TestRequest::delete().uri(&format!("/api/v1/webhooks/{}", webhook["id"])).insert_header(("Authorization", format!("Bearer {}", token)));
+    assert_eq!(call_service(&app, delete.to_request()).await.status(), StatusCode::NO_CONTENT);
+    let (_, registered) = get_json(&app, "/api/v1/webhooks", Some(&token)).await;
+    assert_eq!(registered, json!([]));
+    assert_eq!(dispatch(&db.pool, tester, DAILY_COMPLETED, &json!({}), 3, clock.now()).await.unwrap(), 0);
+}
+
+#[actix_web::test]
+async fn failing_endpoints_are_retried_with_backoff_then_disabled() {
+    let db = TestDb::new().await;
+    let settings = webhook_settings(&[("WEBHOOK_FAILURE_LIMIT", "2")]);
+    let fake = Arc::new(FakeClock::new(Utc::now()));
+    let clock: Arc<dyn Clock> = fake.clone();
+    let runner = runner(&db, &settings, clock.clone());
+    let app = init_app_with_clock(&db, settings, clock.clone()).await;
+    let token = access_token(&app).await;
+    run_next_job(&db.pool, &runner, &*clock).await.unwrap();
+    let tester = users::find_by_username(&db.pool, TEST_USERNAME).await.unwrap().unwrap().id;
+    let receiver = Receiver::new();
+    receiver.status.store(500, Ordering::SeqCst);
+
+    let body = json!({ "url": receiver.serve(), "secret": SECRET, "events": [DAILY_COMPLETED] });
+    let (_, webhook) = post_json(&app, "/api/v1/webhooks", &body, Some(&token)).await;
+    let deliveries_uri = format!("/api/v1/webhooks/{}/deliveries", webhook["id"]);
+    dispatch(&db.pool, tester, DAILY_COMPLETED, &json!({}), 5, clock.now()).await.unwrap();
+
+    assert!(run_next_job(&db.pool, &runner, &*clock).await.unwrap().is_some());
+    let (_, deliveries) = get_json(&app, &deliveries_uri, Some(&token)).await;
+    assert_eq!(deliveries["items"][0]["status"], "retrying");
+    assert_eq!(deliveries["items"][0]["response_status"], 500);
+
+    // The retry waits out the backoff
+    assert!(run_next_job(&db.pool, &runner, &*clock).await.unwrap().is_none());
+    fake.advance(retry_delay(1));
+    assert!(run_next_job(&db.pool, &runner, &*clock).await.unwrap().is_some());
+    assert_eq!(receiver.count(), 2);
+
+    // Two failures in a row disabled it, so the next retry isn't sent
+    let (_, registered) = get_json(&app, "/api/v1/webhooks", Some(&token)).await;
+    assert_eq!(registered[0]["enabled"], false);
+    assert_eq!(registered[0]["consecutive_failures"], 2);
+    assert!(registered[0]["disabled_at"].is_string());
+    fake.advance(retry_delay(2));
+    assert!(run_next_job(&db.pool, &runner, &*clock).await.unwrap().is_some());
+    assert_eq!(receiver.count(), 2);
+    let (_, deliveries) = get_json(&app, &deliveries_uri, Some(&token)).await;
+    assert_eq!(deliveries["items"][0]["status"], "failed");
+    assert_eq!(dispatch(&db.pool, tester, DAILY_COMPLETED, &json!({}), 5, clock.now()).await.unwrap(), 0);
+
+    // Turning it back on starts the count afresh
+    receiver.status.store(204, Ordering::SeqCst);
+    let update = TestRequest::put()
+        .uri(&format!("/api/v1/webhooks/{}", webhook["id"]))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "enabled": true }));
+    assert_eq!(call_service(&app, update.to_request()).await.status(), StatusCode::OK);
+    assert_eq!(dispatch(&db.pool, tester, DAILY_COMPLETED, &json!({}), 5, clock.now()).await.unwrap(), 1);
+    run_next_job(&db.pool, &runner, &*clock).await.unwrap();
+    let (_, registered) = get_json(&app, "/api/v1/webhooks", Some(&token)).await;
+    assert_eq!((registered[0]["enabled"].clone(), registered[0]["consecutive_failures"].clone()), (json!(true), json!(0)));
+}
+
+#[actix_web::test]
+async fn admin_endpoints_get_every_users_events() {
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, webhook_settings(&[])).await;
+    register(&app, "leaguebot", "leaguebot@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'leaguebot'").execute(&db.pool).await.unwrap();
+    let (_, tokens) = login(&app, "leaguebot", TEST_PASSWORD).await;
+    let admin = tokens["access"].as_str().unwrap();
+    let tester = users::find_by_username(&db.pool, TEST_USERNAME).await.unwrap().unwrap().id;
+
+    let body = json!({ "url": "http://127.0.0.1:9/hook", "secret": SECRET, "events": [DAILY_COMPLETED] });
+    let (_, webhook) = post_json(&app, "/api/v1/webhooks", &body, Some(admin)).await;
+    assert_eq!(webhook["all_users"], true);
+    assert_eq!(dispatch(&db.pool, tester, DAILY_COMPLETED, &json!({}), 1, Utc::now()).await.unwrap(), 1);
+
+    // Registration is checked before anything is stored
+    for (body, code) in [
+        (json!({ "url": "ftp://example.com/hook", "secret": SECRET, "events": [DAILY_COMPLETED] }), "invalid_webhook_url"),
+        (json!({ "url": "https://example.com/hook", "secret": "short", "events": [DAILY_COMPLETED] }), "invalid_field"),
+        (json!({ "url": "https://example.com/hook", "secret": SECRET, "events": ["game_lost"] }), "unknown_webhook_event"),
+        (json!({ "url": "https://example.com/hook", "secret": SECRET, "events": [] }), "unknown_webhook_event"),
+    ] {
+        let (status, response) = post_json(&app, "/api/v1/webhooks", &body, Some(admin)).await;
+        assert_eq!((status, response["error"]["code"].as_str()), (StatusCode::UNPROCESSABLE_ENTITY, Some(code)), "{}", body);
+    }
+}
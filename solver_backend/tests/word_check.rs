@@ -0,0 +1,92 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App};
+use chrono::Utc;
+use common::{get_json, login, post_json, register, settings_with, state, TestDb, TEST_PASSWORD};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use wordle_solver::handlers::api_routes;
+use wordle_solver::handlers::health::health_routes;
+use wordle_solver::repositories::words::{self, NewRevision};
+
+// check -> (severity, count, examples sorted)
+fn violations(report: &Value) -> BTreeMap<String, (String, i64, Vec<String>)> {
+    report["violations"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|violation| {
+            let mut examples: Vec<String> = violation["examples"].as_array().unwrap().iter().map(|example| example.as_str().unwrap().to_string()).collect();
+            examples.sort();
+            let check = violation["check"].as_str().unwrap().to_string();
+            (check, (violation["severity"].as_str().unwrap().to_string(), violation["count"].as_i64().unwrap(), examples))
+        })
+        .collect()
+}
+
+#[actix_web::test]
+async fn a_broken_word_list_is_reported_and_keeps_games_down() {
+    let db = TestDb::new().await;
+    let settings = settings_with(&[("WORD_CHECK_ENFORCED", "true")]).unwrap();
+    let app = test::init_service(App::new().app_data(web::Data::new(state(&db, settings))).configure(api_routes(false)).configure(health_routes)).await;
+    register(&app, "referee", "referee@example.com", TEST_PASSWORD).await;
+    sqlx::query("UPDATE users SET role = 'admin' WHERE username = 'referee'").execute(&db.pool).await.unwrap();
+    let (_, tokens) = login(&app, "referee", TEST_PASSWORD).await;
+    let admin = tokens["access"].as_str().unwrap().to_string();
+    let letters = json!({ "correct": "", "incorrect": "", "exact": "cr___" });
+
+    // Not checked yet: readiness waits for it, games are served. The database check fails
+    // here anyway, nothing marks the test pool connected.
+    let (_, health) = get_json(&app, "/health/ready", None).await;
+    assert_eq!(health["checks"]["word_list"], json!({ "status": "failed", "error": "not checked yet" }));
+    assert_eq!(post_json(&app, "/api/v1/game/candidates", &letters, Some(&admin)).await.0, StatusCode::OK);
+
+    let (status, report) = post_json(&app, "/api/v1/admin/word-list/check", &json!({}), Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK, "{}", report);
+    assert_eq!((&report["passed"], &report["words"], &report["violations"]), (&json!(true), &json!(7), &json!([])));
+    assert_eq!(get_json(&app, "/health/ready", None).await.1["checks"]["word_list"]["status"], "ok");
+
+    // A bad import: wrong case, wrong length, a repeat and a NULL, with no answers left
+    // and a revision that claims fewer words
+    for word in [Some("CRANE"), Some("cranes"), Some("crane"), None] {
+        sqlx::query("INSERT INTO word_list (word) VALUES ($1)").bind(word).execute(&db.pool).await.unwrap();
+    }
+    sqlx::query("UPDATE word_list SET is_answer = FALSE").execute(&db.pool).await.unwrap();
+    let version = words::version(&db.pool).await.unwrap();
+    let revision = NewRevision { source_url: "https://example.com/words.txt", added: 0, removed: 0, word_count: 7, etag: None, last_modified: None, synced_by: None, from_version: version, to_version: version };
+    words::insert_revision(&db.pool, &revision, Utc::now()).await.unwrap();
+
+    let (status, report) = post_json(&app, "/api/v1/admin/word-list/check", &json!({}), Some(&admin)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((&report["passed"], &report["words"], &report["answers"]), (&json!(false), &json!(11), &json!(0)));
+    let critical = |count: i64, examples: &[&str]| ("critical".to_string(), count, examples.iter().map(|example| example.to_string()).collect::<Vec<_>>());
+    let warning = |count: i64, examples: &[&str]| ("warning".to_string(), count, examples.iter().map(|example| example.to_string()).collect::<Vec<_>>());
+    let expected = BTreeMap::from([
+        ("not_lowercase_alphabetic".to_string(), critical(2, &["CRANE", "NULL"])),
+        ("wrong_length".to_string(), critical(1, &["cranes"])),
+        ("duplicate".to_string(), warning(1, &["crane"])),
+        ("no_answers".to_string(), critical(1, &[])),
+        ("count_mismatch".to_string(), warning(1, &[])),
+    ]);
+    assert_eq!(violations(&report), expected);
+
+    let (_, health) = get_json(&app, "/health/ready", None).await;
+    assert_eq!(health["checks"]["word_list"]["status"], "failed");
+    assert!(health["failed"].as_array().unwrap().contains(&json!("word_list")));
+    let (status, body) = post_json(&app, "/api/v1/game/candidates", &letters, Some(&admin)).await;
+    assert_eq!((status, &body["error"]["code"]), (StatusCode::SERVICE_UNAVAILABLE, &json!("word_list_unhealthy")));
+    // Everything else, and syncing a fixed list, is still served
+    let (_, body) = post_json(&app, "/api/v1/game/words/sync", &json!({}), Some(&admin)).await;
+    assert_eq!(body["error"]["code"], "word_sync_not_configured");
+    assert_eq!(get_json(&app, "/api/v1/users", Some(&admin)).await.0, StatusCode::OK);
+
+    // Fixed by hand. The list moved on from the revision, so its count no longer applies.
+    sqlx::query("DELETE FROM word_list WHERE word IS NULL OR word IN ('CRANE', 'cranes')").execute(&db.pool).await.unwrap();
+    sqlx::query("UPDATE word_list SET is_answer = TRUE").execute(&db.pool).await.unwrap();
+    let (_, report) = post_json(&app, "/api/v1/admin/word-list/check", &json!({}), Some(&admin)).await;
+    assert_eq!(report["passed"], true);
+    assert_eq!(violations(&report).into_keys().collect::<Vec<_>>(), ["duplicate"]);
+    assert_eq!(post_json(&app, "/api/v1/game/candidates", &letters, Some(&admin)).await.0, StatusCode::OK);
+    assert_eq!(get_json(&app, "/health/ready", None).await.1["checks"]["word_list"]["status"], "ok");
+}
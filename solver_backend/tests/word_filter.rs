@@ -0,0 +1,114 @@
+mod common;
+
+use actix_web::http::StatusCode;
+use common::{access_token, get_json, init_app, seed_words, TestDb};
+use sqlx::any::AnyKind;
+use sqlx::pool::PoolConnection;
+use sqlx::{Any, Executor};
+use std::time::Duration;
+use wordle_solver::repositories::words;
+use wordle_solver::solver::Constraints;
+
+// About the size of the full guess list
+const SEEDED_WORDS: usize = 13_000;
+const RUNS: usize = 5;
+const PATTERNS: &[&str] = &["cra__", "_r_ne", "%ran%", "zz___"];
+
+// Made up five letter words, the same ones on every run
+fn made_up_words(count: usize) -> Vec<String> {
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state = state.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        (b'a' + ((state >> 33) % 26) as u8) as char
+    };
+    (0..count).map(|_| (0..5).map(|_| next()).collect()).collect()
+}
+
+// Server side time over every pattern, the fastest of RUNS for each so a stray slow run
+// doesn't decide the comparison. The results come back too, sorted since an index scan
+// returns them in another order.
+async fn time_patterns(connection: &mut PoolConnection<Any>) -> (Duration, Vec<Vec<String>>) {
+    let mut total = Duration::ZERO;
+    let mut results = Vec::new();
+    for pattern in PATTERNS {
+        let mut fastest = Duration::MAX;
+        for _ in 0..RUNS {
+            let plan = words::explain_filter(&mut *connection, AnyKind::Postgres, pattern).await.unwrap();
+            fastest = fastest.min(execution_time(&plan));
+        }
+        total += fastest;
+        let mut found = words::filter_words(&mut *connection, pattern, &Constraints::default()).await.unwrap();
+        found.sort_unstable();
+        results.push(found);
+    }
+    (total, results)
+}
+
+fn execution_time(plan: &[String]) -> Duration {
+    let milliseconds: f64 = plan
+        .iter()
+        .find_map(|line| line.strip_prefix("Execution Time: "))
+        .and_then(|time| time.trim_end_matches(" ms").parse().ok())
+        .unwrap_or_else(|| panic!("no execution time in {:#?}", plan));
+    Duration::from_secs_f64(milliseconds / 1000.0)
+}
+
+// Before is the same table with the planner kept off the indexes, as if the migration
+// hadn't run
+#[actix_web::test]
+async fn indexes_speed_up_filtering_a_full_word_list() {
+    let db = TestDb::new().await;
+    if !db.is_postgres() {
+        return;
+    }
+    seed_words(&db, &made_up_words(SEEDED_WORDS)).await;
+    db.pool.execute("ANALYZE word_list").await.unwrap();
+    let mut connection = db.pool.acquire().await.unwrap();
+
+    connection.execute("SET enable_indexscan = off; SET enable_bitmapscan = off").await.unwrap();
+    let plan = words::explain_filter(&mut *connection, AnyKind::Postgres, "cra__").await.unwrap();
+    assert!(plan[0].starts_with("Seq Scan"), "{:#?}", plan);
+    let (before, scanned) = time_patterns(&mut connection).await;
+
+    connection.execute("RESET enable_indexscan; RESET enable_bitmapscan").await.unwrap();
+    let plan = words::explain_filter(&mut *connection, AnyKind::Postgres, "cra__").await.unwrap();
+    assert!(plan.iter().any(|line| line.contains("word_list_word_pattern")), "{:#?}", plan);
+    let (after, indexed) = time_patterns(&mut connection).await;
+
+    eprintln!("{:?} over {} words: {:?} scanning, {:?} with indexes", PATTERNS, SEEDED_WORDS, before, after);
+    assert_eq!(indexed, scanned);
+    assert!(after < before, "{:?} with indexes, {:?} without", after, before);
+}
+
+#[actix_web::test]
+async fn filtering_matches_whatever_case_the_pattern_is_in() {
+    let db = TestDb::new().await;
+
+    for pattern in ["CRA__", "cRa__"] {
+        let found = words::filter_words(&db.pool, pattern, &Constraints::default()).await.unwrap();
+        assert_eq!(found.len(), 2, "{}: {:?}", pattern, found);
+    }
+    // Fixed length patterns only match words as long as they are
+    let found = words::filter_words(&db.pool, "cra_", &Constraints::default()).await.unwrap();
+    assert!(found.is_empty(), "{:?}", found);
+    let found = words::filter_words(&db.pool, "cra%", &Constraints::default()).await.unwrap();
+    assert_eq!(found.len(), 2, "{:?}", found);
+}
+
+#[actix_web::test]
+async fn admins_can_see_the_plan_for_a_pattern() {
+    let db = TestDb::new().await;
+    db.pool.execute("UPDATE users SET role = 'admin'").await.unwrap();
+    let app = init_app(&db).await;
+    let token = access_token(&app).await;
+
+    let (status, plan) = get_json(&app, "/api/v1/admin/word-filter-plan?exact=_R_NE", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", plan);
+    assert_eq!(plan["pattern"], "_r_ne");
+    assert_eq!(plan["database"], if db.is_postgres() { "postgres" } else { "sqlite" });
+    assert!(!plan["plan"].as_array().unwrap().is_empty(), "{}", plan);
+
+    let (status, plan) = get_json(&app, "/api/v1/admin/word-filter-plan", Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", plan);
+    assert_eq!(plan["pattern"], "cra__");
+}
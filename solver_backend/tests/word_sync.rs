@@ -0,0 +1,198 @@
+mod common;
+
+use actix_web::http::header::{ETAG, IF_NONE_MATCH};
+use actix_web::http::StatusCode;
+use actix_web::{rt, web, App, HttpRequest, HttpResponse, HttpServer};
+use actix_web::test::{self, TestRequest};
+use chrono::{Duration, Utc};
+use common::{access_token, get_json, init_app_with, make_admin, post_json, settings_with, TestDb};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use wordle_solver::repositories::words;
+use wordle_solver::utils::word_sync_utils::parse_word_list;
+
+// Stands in for the hosted list. The ETag is the number of times the list was replaced.
+#[derive(Clone, Default)]
+struct Source {
+    list: Arc<Mutex<(u32, String)>>,
+}
+
+impl Source {
+    fn publish(&self, list: &str) {
+        let mut current = self.list.lock().unwrap();
+        *current = (current.0 + 1, list.to_string());
+    }
+
+    // The URL the list is served at
+    fn serve(&self) -> String {
+        let source = self.clone();
+        let server = HttpServer::new(move || {
+            let source = source.clone();
+            App::new().route(
+                "/words.txt",
+                web::get().to(move |req: HttpRequest| {
+                    let (revision, list) = source.list.lock().unwrap().clone();
+                    let etag = format!("\"{}\"", revision);
+                    let response = if req.headers().get(IF_NONE_MATCH).is_some_and(|tag| tag == etag.as_str()) {
+                        HttpResponse::NotModified().finish()
+                    } else {
+                        HttpResponse::Ok().insert_header((ETAG, etag)).body(list)
+                    };
+                    async move { response }
+                }),
+            )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        rt::spawn(server.run());
+        format!("http://{}/words.txt", address)
+    }
+}
+
+async fn stored_words(db: &TestDb) -> Vec<String> {
+    words::all_words(&db.pool).await.unwrap()
+}
+
+#[actix_web::test]
+async fn sync_applies_the_difference_and_skips_unchanged_lists() {
+    let db = TestDb::new().await;
+    let source = Source::default();
+    let url = source.serve();
+    let app = init_app_with(&db, settings_with(&[("WORD_LIST_SYNC_URL", &url)]).unwrap()).await;
+    make_admin(&db).await;
+    let token = access_token(&app).await;
+    let version = words::version(&db.pool).await.unwrap();
+
+    // Keeps crane and slate, drops the other test words
+    source.publish("# five letter words\nCRANE\nslate\n\nbrick\nslate\n");
+    let (status, sync) = post_json(&app, "/api/v1/game/words/sync", &json!({}), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", sync);
+    assert_eq!(sync["status"], "updated");
+    assert_eq!(sync["added"], 1);
+    assert_eq!(sync["removed"], 5);
+    assert_eq!(sync["word_count"], 3);
+    assert!(sync["version"].as_i64().unwrap() > version);
+    assert_eq!(stored_words(&db).await, ["brick", "crane", "slate"]);
+
+    let (added, removed, etag): (i32, i32, Option<String>) = sqlx::query_as("SELECT added, removed, etag FROM word_list_revisions WHERE id = $1")
+        .bind(sync["revision"].as_i64().unwrap() as i32)
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!((added, removed, etag.as_deref()), (1, 5, Some("\"1\"")));
+
+    // The source answers 304, nothing is recorded
+    let (status, again) = post_json(&app, "/api/v1/game/words/sync", &json!({}), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK, "{}", again);
+    assert_eq!(again["status"], "unchanged");
+    assert!(again["revision"].is_null());
+    assert_eq!(again["version"], sync["version"]);
+}
+
+#[actix_web::test]
+async fn bad_lists_leave_the_stored_one_alone() {
+    let db = TestDb::new().await;
+    let source = Source::default();
+    let url = source.serve();
+    let settings = settings_with(&[("WORD_LIST_SYNC_URL", &url), ("WORD_LIST_SYNC_MAX_BYTES", "64")]).unwrap();
+    let app = init_app_with(&db, settings).await;
+    make_admin(&db).await;
+    let token = access_token(&app).await;
+    let before = stored_words(&db).await;
+
+    source.publish("crane\nslate\nlonger\n");
+    let (status, error) = post_json(&app, "/api/v1/game/words/sync", &json!({}), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(error["error"]["code"], "word_sync_invalid_list");
+    assert!(error["error"]["details"]["reason"].as_str().unwrap().contains("line 3"), "{}", error);
+
+    source.publish(&"crane\n".repeat(20));
+    let (status, error) = post_json(&app, "/api/v1/game/words/sync", &json!({}), Some(&token)).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(error["error"]["code"], "word_sync_fetch_failed");
+
+    assert_eq!(stored_words(&db).await, before);
+    let revisions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM word_list_revisions").fetch_one(&db.pool).await.unwrap();
+    assert_eq!(revisions, 0);
+}
+
+#[actix_web::test]
+async fn sync_needs_an_admin_and_a_source() {
+    let db = TestDb::new().await;
+    let settings = settings_with(&[("WORD_LIST_SYNC_URL", "http://127.0.0.1:9/words.txt")]).unwrap();
+    let app = init_app_with(&db, settings).await;
+    let token = access_token(&app).await;
+    let (status, _) = post_json(&app, "/api/v1/game/words/sync", &json!({}), Some(&token)).await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+
+    let db = TestDb::new().await;
+    let app = init_app_with(&db, settings_with(&[]).unwrap()).await;
+    make_admin(&db).await;
+    let token = access_token(&app).await;
+    let (status, error) = post_json(&app, "/api/v1/game/words/sync", &json!({}), Some(&token)).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(error["error"]["code"], "word_sync_not_configured");
+
+    let error = settings_with(&[("WORD_LIST_SYNC_URL", "ftp://example.com/words.txt")]).err().unwrap();
+    assert!(error.to_string().contains("WORD_LIST_SYNC_URL must be"), "{}", error);
+}
+
+#[test]
+fn word_lists_are_normalized_or_rejected() {
+    let words = parse_word_list(b"  Crane \r\n# comment\ncrane\nADIEU\n").unwrap();
+    assert_eq!(words.into_iter().collect::<Vec<_>>(), ["adieu", "crane"]);
+
+    assert!(parse_word_list(b"\n# nothing here\n").unwrap_err().contains("no words"));
+    assert!(parse_word_list(b"crane\ncr4ne\n").unwrap_err().contains("line 2"));
+    assert!(parse_word_list(&[0xff, 0xfe]).unwrap_err().contains("UTF-8"));
+}
+
+#[actix_web::test]
+async fn changes_replay_every_revision_since_a_version() {
+    let db = TestDb::new().await;
+    let source = Source::default();
+    let url = source.serve();
+    let app = init_app_with(&db, settings_with(&[("WORD_LIST_SYNC_URL", &url)]).unwrap()).await;
+    make_admin(&db).await;
+    let token = access_token(&app).await;
+
+    // Each version the list had, with its words
+    let mut history = vec![(words::version(&db.pool).await.unwrap(), stored_words(&db).await)];
+    let lists = ["crane\nslate\nbrick\n", "crane\nbrick\nplumb\nadieu\n", "crane\nbrick\nplumb\nadieu\n", "slate\nplumb\nghost\n"];
+    for list in lists {
+        source.publish(list);
+        let (status, sync) = post_json(&app, "/api/v1/game/words/sync", &json!({}), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK, "{}", sync);
+        history.push((sync["version"].as_i64().unwrap(), stored_words(&db).await));
+    }
+    let (current, now) = history.last().unwrap().clone();
+    let now: BTreeSet<String> = now.into_iter().collect();
+
+    for (version, then) in &history {
+        let then: BTreeSet<String> = then.iter().cloned().collect();
+        let (status, changes) = get_json(&app, &format!("/api/v1/game/words/changes?since_version={}", version), Some(&token)).await;
+        assert_eq!(status, StatusCode::OK, "{}: {}", version, changes);
+        assert_eq!(changes["version"], current);
+        assert_eq!(changes["added"], json!(now.difference(&then).collect::<Vec<_>>()), "since {}", version);
+        assert_eq!(changes["removed"], json!(then.difference(&now).collect::<Vec<_>>()), "since {}", version);
+    }
+
+    let uri = format!("/api/v1/game/words/changes?since_version={}", history[1].0);
+    let response = test::call_service(&app, TestRequest::get().uri(&uri).insert_header(("Authorization", format!("Bearer {}", token))).to_request()).await;
+    let etag = response.headers().get(ETAG).unwrap().clone();
+    let request = TestRequest::get().uri(&uri).insert_header(("Authorization", format!("Bearer {}", token))).insert_header((IF_NONE_MATCH, etag));
+    assert_eq!(test::call_service(&app, request.to_request()).await.status(), StatusCode::NOT_MODIFIED);
+
+    // A version the list never had, and one from before the kept history
+    let (status, body) = get_json(&app, &format!("/api/v1/game/words/changes?since_version={}", current + 1), Some(&token)).await;
+    assert_eq!((status, body["error"]["code"].as_str()), (StatusCode::GONE, Some("word_history_gone")));
+    words::prune_changes(&db.pool, Utc::now() + Duration::days(1)).await.unwrap();
+    let (status, _) = get_json(&app, &format!("/api/v1/game/words/changes?since_version={}", history[0].0), Some(&token)).await;
+    assert_eq!(status, StatusCode::GONE);
+    let (status, changes) = get_json(&app, &format!("/api/v1/game/words/changes?since_version={}", current), Some(&token)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(changes["added"], json!([]));
+}
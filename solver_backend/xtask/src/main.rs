@@ -0,0 +1,107 @@
+// Project chores, run as `cargo xtask <task>` through the alias in .cargo/config.toml.
+//
+// generate-client: writes the OpenAPI document the server serves at /api-docs/openapi.json
+// to clients/ts, regenerates the TypeScript types from it with openapi-typescript and checks
+// the client compiles. With --check nothing may change, for CI to catch a stale client.
+//
+// review-snapshots: reruns the solver snapshot tests, writing whatever they now suggest over
+// the recorded snapshots, and lists the ones that changed. Check `git diff` before committing.
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+use utoipa::OpenApi;
+use wordle_solver::handlers::docs::ApiDoc;
+
+const USAGE: &str = "Usage: cargo xtask generate-client [--check] | review-snapshots";
+// Relative to the repository root
+const CLIENT_DIR: &str = "clients/ts";
+const SPEC_FILE: &str = "openapi.json";
+const SCHEMA_FILE: &str = "src/schema.ts";
+const SNAPSHOT_DIR: &str = "solver_backend/tests/snapshots";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["generate-client"] => generate_client(false),
+        ["generate-client", "--check"] => generate_client(true),
+        ["review-snapshots"] => review_snapshots(),
+        _ => {
+            eprintln!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn generate_client(check: bool) -> Result<(), String> {
+    let dir = repository_root().join(CLIENT_DIR);
+    let before = generated_files(&dir);
+
+    let spec = ApiDoc::openapi().to_pretty_json().map_err(|error| format!("couldn't render the OpenAPI document: {}", error))?;
+    fs::write(dir.join(SPEC_FILE), spec + "\n").map_err(|error| format!("couldn't write {}: {}", SPEC_FILE, error))?;
+
+    if !dir.join("node_modules").is_dir() {
+        npm(&dir, &["install"])?;
+    }
+    npm(&dir, &["run", "generate"])?;
+    npm(&dir, &["run", "typecheck"])?;
+
+    if check && generated_files(&dir) != before {
+        return Err(format!("{} is out of date, run `cargo xtask generate-client` and commit the result", CLIENT_DIR));
+    }
+    Ok(())
+}
+
+fn review_snapshots() -> Result<(), String> {
+    let root = repository_root();
+    let status = Command::new(env!("CARGO"))
+        .args(["test", "--test", "solver_snapshots"])
+        .env("INSTA_UPDATE", "always")
+        .env("INSTA_FORCE_PASS", "1")
+        .current_dir(root.join("solver_backend"))
+        .status()
+        .map_err(|error| format!("couldn't run cargo test: {}", error))?;
+    if !status.success() {
+        return Err(format!("the snapshot tests failed with {}", status));
+    }
+
+    let status = Command::new("git")
+        .args(["status", "--short", "--", SNAPSHOT_DIR])
+        .current_dir(&root)
+        .status()
+        .map_err(|error| format!("couldn't run git: {}", error))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("git status failed with {}", status))
+    }
+}
+
+fn generated_files(dir: &Path) -> Vec<Option<String>> {
+    [SPEC_FILE, SCHEMA_FILE].iter().map(|file| fs::read_to_string(dir.join(file)).ok()).collect()
+}
+
+fn npm(dir: &Path, args: &[&str]) -> Result<(), String> {
+    let status = Command::new("npm")
+        .args(args)
+        .current_dir(dir)
+        .status()
+        .map_err(|error| format!("couldn't run npm, is Node.js installed? {}", error))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("npm {} failed with {}", args.join(" "), status))
+    }
+}
+
+fn repository_root() -> PathBuf {
+    // xtask sits in solver_backend/xtask
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
@@ -14,7 +14,7 @@ function DeleteModal(props) {
   const [open, setOpen] = useState(false);
 
   function handleDelete() {
-    axios.delete(`http://localhost:8080/api/users/delete/${decodedId}`)
+    axios.delete(`http://localhost:8080/api/v1/users/delete/${decodedId}`)
       .then((res) => {
         console.log(res)
       })
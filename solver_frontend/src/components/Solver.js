@@ -43,7 +43,7 @@ function Solver() {
   const fetchData = async () => {
       try {
         const response = await axios.post(
-          "http://localhost:8080/api/users/check_access",
+          "http://localhost:8080/api/v1/users/check_access",
           { token }
         );
         if (response.status === 401) {
@@ -107,8 +107,11 @@ function Solver() {
     let yellow = letterForm.correct
     let grey = ""
     letterForm.incorrect === "" ? grey = "_" : grey = letterForm.incorrect
-     axios.post('http://localhost:8080/api/game/general-letters', {correct: yellow, incorrect: grey, exact: posLetter},
+     axios.post('http://localhost:8080/api/v1/game/general-letters', {correct: yellow, incorrect: grey, exact: posLetter},
     {
+      params: {
+        per_page: 100
+      },
       headers: {
         Authorization: `Bearer ${token}`
       }
@@ -116,7 +119,7 @@ function Solver() {
     )
       .then((res) => {
         console.log(res.data)
-        setWords(res.data)
+        setWords(res.data.items)
       })
       .catch(function (error) {
         console.log(error);
@@ -127,7 +130,7 @@ function Solver() {
     try {
       let refreshToken = cookies.refresh_token;
 
-      let response = await axios.post('http://localhost:8080/api/users/get_new_tokens', {
+      let response = await axios.post('http://localhost:8080/api/v1/users/get_new_tokens', {
         token: refreshToken
       });
       console.log(response)